@@ -2,6 +2,9 @@
 #include <cstdlib>
 #include <cstring>
 #include <cctype>
+#include <csignal>
+#include <sys/resource.h>
+#include <unistd.h>
 
 extern "C" {
 
@@ -13,17 +16,40 @@ void printString(const char *a) {
     printf("%s\n", a ? a : "");
 }
 
-void error() {
+void printBoolean(bool b) {
+    printf("%s\n", b ? "true" : "false");
+}
+
+// not buffered, so it separates the program output from the error reports
+void printErr(const char *a) {
+    fprintf(stderr, "%s\n", a ? a : "");
+}
+
+[[noreturn]] void error() {
     printf("runtime error\n");
     exit(1);
 }
 
+// errors found by the runtime: the reason goes to stderr (like in the
+// interpreter), then the program ends like after error()
+[[noreturn]] static void runtime_error(const char *reason) {
+    fprintf(stderr, "Runtime error: %s\n", reason);
+    error();
+}
+
+// the output is fully buffered (see _bltn_runtime_init), it's written when
+// the buffer is full, before reading the input, at exit and on request
+void flushOutput() {
+    fflush(stdout);
+}
+
 int readInt() {
+    flushOutput();
     char *line = 0;
     size_t len = 0;
     size_t read = getline(&line, &len, stdin);
     if (read <= 0) {
-        error();
+        runtime_error("readInt: no input");
     }
 
     char *ptr = line;
@@ -32,12 +58,12 @@ int readInt() {
     else if (ptr < line+read && *ptr == '+') ptr++;
     while (ptr < line+read && isspace(*ptr)) ptr++;
     if (!(ptr < line+read && isdigit(*ptr))) {
-        error();
+        runtime_error("readInt: not a number");
     }
     while (ptr < line+read && isdigit(*ptr)) ptr++;
     while (ptr < line+read && isspace(*ptr)) ptr++;
     if (ptr != line + read) {
-        error();
+        runtime_error("readInt: not a number");
     }
 
     int num = atoi(line);
@@ -46,6 +72,7 @@ int readInt() {
 }
 
 const char *readString() {
+    flushOutput();
     char *line = 0;
     size_t len = 0;
     size_t read = getline(&line, &len, stdin);
@@ -59,6 +86,43 @@ const char *readString() {
     return line;
 }
 
+// the whole content of the file
+const char *readFile(const char *path) {
+    FILE *file = fopen(path ? path : "", "rb");
+    if (!file) {
+        runtime_error("readFile: cannot open the file");
+    }
+    size_t len = 0;
+    size_t cap = 4096;
+    char *buf = (char*) malloc(cap);
+    while (buf) {
+        len += fread(buf + len, 1, cap - len - 1, file);
+        if (len < cap - 1) {
+            break;
+        }
+        cap *= 2;
+        buf = (char*) realloc(buf, cap);
+    }
+    if (!buf || ferror(file)) {
+        runtime_error("readFile: cannot read the file");
+    }
+    fclose(file);
+    buf[len] = '\0';
+    return buf;
+}
+
+// replaces the content of the file
+void writeFile(const char *path, const char *contents) {
+    FILE *file = fopen(path ? path : "", "wb");
+    if (!file) {
+        runtime_error("writeFile: cannot open the file");
+    }
+    bool failed = contents && fputs(contents, file) == EOF;
+    if (fclose(file) != 0 || failed) {
+        runtime_error("writeFile: cannot write the file");
+    }
+}
+
 const char *_bltn_string_concat(const char *a, const char *b) {
     if (!a) {
         return b;
@@ -74,6 +138,27 @@ const char *_bltn_string_concat(const char *a, const char *b) {
     return ptr;
 }
 
+const char *_bltn_string_concat_n(int cnt, const char **parts) {
+    size_t buf_size = 1;
+    for (int i = 0; i < cnt; i++) {
+        if (parts[i]) {
+            buf_size += strlen(parts[i]);
+        }
+    }
+
+    char *ptr = (char*) malloc(buf_size);
+    char *end = ptr;
+    *end = '\0';
+    for (int i = 0; i < cnt; i++) {
+        if (parts[i]) {
+            size_t len = strlen(parts[i]);
+            strcpy(end, parts[i]);
+            end += len;
+        }
+    }
+    return ptr;
+}
+
 bool _bltn_string_eq(const char *a, const char *b) {
     if (!a && !b) {
         return true;
@@ -89,29 +174,493 @@ bool _bltn_string_ne(const char *a, const char *b) {
     return !_bltn_string_eq(a, b);
 }
 
-void *_bltn_malloc(int size) {
-    if (size <= 0) {
-        error();
+void _bltn_string_check_index(const char *s, int idx) {
+    if (!s || idx < 0 || (size_t) idx >= strlen(s)) {
+        runtime_error("string index out of bounds");
+    }
+}
+
+// lowest address the stack may reach in programs compiled with --check-stack,
+// a bit above the real limit, so the error can still be reported
+const char *_bltn_stack_limit = nullptr;
+
+// the compiled code doesn't check for null nor division by zero, the crash
+// is reported like in the interpreter (without an alternate stack too deep
+// recursion still kills the program with SIGSEGV)
+static void on_crash(int sig) {
+    flushOutput();
+    fprintf(stderr, "Runtime error: %s\n", sig == SIGFPE
+            ? "division by zero or overflow"
+            : "invalid memory access (null dereference?)");
+    _exit(1);
+}
+
+// called before main by a constructor in every compiled program (so an
+// object file linked with the runtime by hand is initialized too)
+void _bltn_runtime_init() {
+    setvbuf(stdout, nullptr, _IOFBF, 64 * 1024);
+    signal(SIGSEGV, on_crash);
+    signal(SIGFPE, on_crash);
+
+    const rlim_t reserve = 64 * 1024;
+    rlim_t size = 8 * 1024 * 1024;
+    struct rlimit limit;
+    if (getrlimit(RLIMIT_STACK, &limit) == 0 && limit.rlim_cur != RLIM_INFINITY) {
+        size = limit.rlim_cur;
+    }
+    // the constructor runs just before main, close to the bottom of the stack
+    const char *stack_base = static_cast<const char*>(__builtin_frame_address(0));
+    _bltn_stack_limit = stack_base - size + reserve;
+}
+
+// counters of a program compiled with --instrument, per block of the IR:
+// executions and branches taken (see optimizer/profile.rs in the compiler)
+static long long *profile_counts = nullptr;
+static long long *profile_taken = nullptr;
+static int profile_size = 0;
+static const char *profile_checksum = nullptr;
+static const char *profile_path = nullptr;
+
+// called at the start of main
+void _bltn_profile_init(int size, const char *checksum, const char *path) {
+    profile_counts = static_cast<long long*>(calloc(size, sizeof(long long)));
+    profile_taken = static_cast<long long*>(calloc(size, sizeof(long long)));
+    if (!profile_counts || !profile_taken) {
+        runtime_error("out of memory");
+    }
+    profile_size = size;
+    profile_checksum = checksum;
+    profile_path = path;
+}
+
+void _bltn_profile_block(int id) {
+    profile_counts[id]++;
+}
+
+void _bltn_profile_branch(int id, bool taken) {
+    profile_taken[id] += taken;
+}
+
+static void profile_write_counters(FILE *file, const long long *counters) {
+    for (int i = 0; i < profile_size; i++) {
+        fprintf(file, "%s%lld", i > 0 ? ", " : "", counters[i]);
+    }
+}
+
+// at exit, so the exit code isn't changed by a failure
+static void profile_dump() {
+    FILE *file = fopen(profile_path, "wb");
+    if (!file) {
+        fprintf(stderr, "Runtime error: %s\n", "cannot write the profile");
+        return;
+    }
+    fprintf(file, "[\"latte-profile\", \"%s\",\n [", profile_checksum);
+    profile_write_counters(file, profile_counts);
+    fputs("],\n [", file);
+    profile_write_counters(file, profile_taken);
+    fputs("]]\n", file);
+    if (fclose(file) != 0) {
+        fprintf(stderr, "Runtime error: %s\n", "cannot write the profile");
+    }
+}
+
+// called at exit (also after error()) by a destructor in every program
+void _bltn_runtime_shutdown() {
+    flushOutput();
+    if (profile_path) {
+        profile_dump();
+    }
+}
+
+void _bltn_stack_overflow_error() {
+    runtime_error("stack overflow, recursion is too deep");
+}
+
+// a method called on a null constant (e.g. of a variable assigned null)
+void _bltn_null_dereference_error() {
+    runtime_error("null dereference (method call)");
+}
+
+// sizes have the type of pointer width, so large allocations don't overflow
+void *_bltn_malloc(size_t size) {
+    if (size == 0) {
+        runtime_error("allocation of 0 bytes");
     }
     void *ptr = malloc(size);
     if (!ptr) {
-        error();
+        runtime_error("out of memory");
     }
     memset(ptr, 0, size);
     return ptr;
 }
 
-void *_bltn_alloc_array(int elem_cnt, int elem_size) {
+void *_bltn_alloc_array(int elem_cnt, size_t elem_size) {
     static_assert(sizeof(int) == 4, "sizeof(int) == 4");
-    if (elem_cnt <= 0 || elem_size <= 0) { // todo readme <-- alokacja co najmniej 1 bajtu
-        error();
+    if (elem_cnt <= 0 || elem_size == 0) { // todo readme <-- alokacja co najmniej 1 bajtu
+        runtime_error("invalid array size");
     }
 
-    int header_size = sizeof(int);
-    int size = elem_cnt * elem_size + header_size;
+    size_t header_size = sizeof(int);
+    size_t size = elem_cnt * elem_size + header_size;
     int *header_ptr = static_cast<int*>(_bltn_malloc(size));
     *header_ptr = elem_cnt;
     return header_ptr + 1;
 }
 
+void *_bltn_array_copy(const void *arr, size_t elem_size) {
+    if (!arr) {
+        runtime_error("null dereference (copy of an array)");
+    }
+    int elem_cnt = static_cast<const int*>(arr)[-1];
+    void *copy = _bltn_alloc_array(elem_cnt, elem_size);
+    memcpy(copy, arr, elem_cnt * elem_size);
+    return copy;
+}
+
+// null is treated as an empty array, new elements are zeroed
+void *_bltn_array_resize(const void *arr, int elem_cnt, size_t elem_size) {
+    void *result = _bltn_alloc_array(elem_cnt, elem_size);
+    if (arr) {
+        int old_cnt = static_cast<const int*>(arr)[-1];
+        int copied_cnt = old_cnt < elem_cnt ? old_cnt : elem_cnt;
+        memcpy(result, arr, copied_cnt * elem_size);
+    }
+    return result;
+}
+
+// the appended element is set by the compiled code
+void *_bltn_array_append(const void *arr, size_t elem_size) {
+    int elem_cnt = arr ? static_cast<const int*>(arr)[-1] : 0;
+    return _bltn_array_resize(arr, elem_cnt + 1, elem_size);
+}
+
+// the first element is already set by the compiled code
+void _bltn_array_fill(void *arr, size_t elem_size) {
+    int elem_cnt = static_cast<int*>(arr)[-1];
+    char *elem = static_cast<char*>(arr);
+    for (int i = 1; i < elem_cnt; i++) {
+        elem += elem_size;
+        memcpy(elem, arr, elem_size);
+    }
+}
+
+// the parts of s between the occurrences of sep (at least one part, so the
+// result is a valid array, with the length before the first element)
+const char **split(const char *s, const char *sep) {
+    if (!sep || !*sep) {
+        runtime_error("split: empty separator");
+    }
+    s = s ? s : "";
+    size_t sep_len = strlen(sep);
+    int cnt = 1;
+    for (const char *match = strstr(s, sep); match; match = strstr(match + sep_len, sep)) {
+        cnt++;
+    }
+    const char **parts = static_cast<const char**>(_bltn_alloc_array(cnt, sizeof(char*)));
+    for (int i = 0;; i++) {
+        const char *end = strstr(s, sep);
+        size_t len = end ? end - s : strlen(s);
+        char *part = static_cast<char*>(malloc(len + 1));
+        if (!part) {
+            runtime_error("out of memory");
+        }
+        memcpy(part, s, len);
+        part[len] = '\0';
+        parts[i] = part;
+        if (!end) {
+            return parts;
+        }
+        s = end + sep_len;
+    }
+}
+
+// null is treated as an empty array, like in arrayResize
+const char *join(const char **parts, const char *sep) {
+    sep = sep ? sep : "";
+    long cnt = parts ? reinterpret_cast<const int*>(parts)[-1] : 0;
+    size_t sep_len = strlen(sep);
+    size_t len = 0;
+    for (long i = 0; i < cnt; i++) {
+        len += (i > 0 ? sep_len : 0) + strlen(parts[i] ? parts[i] : "");
+    }
+    char *res = static_cast<char*>(malloc(len + 1));
+    if (!res) {
+        runtime_error("out of memory");
+    }
+    char *dst = res;
+    for (long i = 0; i < cnt; i++) {
+        const char *part = parts[i] ? parts[i] : "";
+        size_t part_len = strlen(part);
+        size_t part_sep_len = i > 0 ? sep_len : 0;
+        memcpy(dst, sep, part_sep_len);
+        memcpy(dst + part_sep_len, part, part_len);
+        dst += part_sep_len + part_len;
+    }
+    *dst = '\0';
+    return res;
+}
+
+// the layout must match the StringBuilder class in the compiler
+struct StringBuilder {
+    void *vtable;
+    char *buf;
+    int len;
+    int cap;
+};
+
+StringBuilder *_bltn_string_builder_append(StringBuilder *sb, const char *s) {
+    if (!sb) {
+        runtime_error("null dereference (StringBuilder)");
+    }
+    if (!s) {
+        return sb;
+    }
+
+    int s_len = strlen(s);
+    if (sb->len + s_len + 1 > sb->cap) {
+        int new_cap = sb->cap > 0 ? sb->cap : 16;
+        while (new_cap < sb->len + s_len + 1) {
+            new_cap *= 2;
+        }
+        char *new_buf = (char*) realloc(sb->buf, new_cap);
+        if (!new_buf) {
+            runtime_error("out of memory");
+        }
+        sb->buf = new_buf;
+        sb->cap = new_cap;
+    }
+    strcpy(sb->buf + sb->len, s);
+    sb->len += s_len;
+    return sb;
+}
+
+const char *_bltn_string_builder_to_string(StringBuilder *sb) {
+    if (!sb) {
+        runtime_error("null dereference (StringBuilder)");
+    }
+
+    char *ptr = (char*) malloc(sb->len + 1);
+    if (!ptr) {
+        runtime_error("out of memory");
+    }
+    ptr[0] = '\0';
+    if (sb->buf) {
+        strcpy(ptr, sb->buf);
+    }
+    return ptr;
+}
+
+// the layout must match the list classes in the compiler
+struct List {
+    void *vtable;
+    char *data;
+    int size;
+    int cap;
+};
+
+static void list_reserve(List *list, int elem_size) {
+    if (list->size < list->cap) {
+        return;
+    }
+
+    int new_cap = list->cap > 0 ? 2 * list->cap : 8;
+    char *new_data = (char*) realloc(list->data, (size_t) new_cap * elem_size);
+    if (!new_data) {
+        runtime_error("out of memory");
+    }
+    list->data = new_data;
+    list->cap = new_cap;
+}
+
+static void list_check_index(List *list, int idx) {
+    if (!list || idx < 0 || idx >= list->size) {
+        runtime_error("list index out of bounds");
+    }
+}
+
+#define DEFINE_LIST_METHODS(prefix, T)                  \
+    void prefix##_push(List *list, T x) {               \
+        if (!list) {                                    \
+            runtime_error("null dereference (list)");     \
+        }                                               \
+        list_reserve(list, sizeof(T));                  \
+        ((T*) list->data)[list->size++] = x;            \
+    }                                                   \
+                                                        \
+    T prefix##_get(List *list, int idx) {               \
+        list_check_index(list, idx);                    \
+        return ((T*) list->data)[idx];                  \
+    }                                                   \
+                                                        \
+    void prefix##_set(List *list, int idx, T x) {       \
+        list_check_index(list, idx);                    \
+        ((T*) list->data)[idx] = x;                     \
+    }                                                   \
+                                                        \
+    int prefix##_size(List *list) {                     \
+        if (!list) {                                    \
+            runtime_error("null dereference (list)");     \
+        }                                               \
+        return list->size;                              \
+    }
+
+DEFINE_LIST_METHODS(_bltn_int_list, int)
+DEFINE_LIST_METHODS(_bltn_string_list, const char*)
+
+// the layout must match the StringIntMap class in the compiler: the entries
+// in the order of insertion (so keys() gives them in this order), then in
+// the same buffer an open addressing table of 2 * cap slots with the entry
+// numbers + 1 (0 is an empty slot), at most half full
+struct Map {
+    void *vtable;
+    char *data;
+    int size;
+    int cap;
+};
+
+struct MapEntry {
+    const char *key;
+    int value;
+};
+
+// FNV-1a, null is the same key as ""
+static unsigned map_hash(const char *key) {
+    unsigned hash = 2166136261u;
+    for (const char *c = key ? key : ""; *c; c++) {
+        hash = (hash ^ (unsigned char) *c) * 16777619u;
+    }
+    return hash;
+}
+
+static int *map_slots(Map *map) {
+    return reinterpret_cast<int*>(map->data + (size_t) map->cap * sizeof(MapEntry));
+}
+
+// the entry with the key or -1, the slot is where the key is or would be
+static int map_find(Map *map, const char *key, unsigned *slot) {
+    if (!map) {
+        runtime_error("null dereference (map)");
+    }
+    if (map->cap == 0) {
+        return -1;
+    }
+
+    MapEntry *entries = reinterpret_cast<MapEntry*>(map->data);
+    int *slots = map_slots(map);
+    unsigned mask = 2 * map->cap - 1;
+    key = key ? key : "";
+    unsigned i = map_hash(key) & mask;
+    for (; slots[i]; i = (i + 1) & mask) {
+        const char *other = entries[slots[i] - 1].key;
+        if (strcmp(other ? other : "", key) == 0) {
+            *slot = i;
+            return slots[i] - 1;
+        }
+    }
+    *slot = i;
+    return -1;
+}
+
+static void map_grow(Map *map) {
+    int new_cap = map->cap > 0 ? 2 * map->cap : 8;
+    size_t entries_size = (size_t) new_cap * sizeof(MapEntry);
+    char *new_data = static_cast<char*>(calloc(entries_size + 2 * (size_t) new_cap * sizeof(int), 1));
+    if (!new_data) {
+        runtime_error("out of memory");
+    }
+    if (map->size > 0) {
+        memcpy(new_data, map->data, (size_t) map->size * sizeof(MapEntry));
+    }
+    free(map->data);
+    map->data = new_data;
+    map->cap = new_cap;
+
+    MapEntry *entries = reinterpret_cast<MapEntry*>(map->data);
+    int *slots = map_slots(map);
+    unsigned mask = 2 * new_cap - 1;
+    for (int e = 0; e < map->size; e++) {
+        unsigned i = map_hash(entries[e].key) & mask;
+        while (slots[i]) {
+            i = (i + 1) & mask;
+        }
+        slots[i] = e + 1;
+    }
+}
+
+void _bltn_string_int_map_put(Map *map, const char *key, int value) {
+    unsigned slot;
+    int e = map_find(map, key, &slot);
+    if (e < 0 && map->size == map->cap) {
+        map_grow(map);
+        map_find(map, key, &slot);
+    }
+    MapEntry *entries = reinterpret_cast<MapEntry*>(map->data);
+    if (e < 0) {
+        e = map->size++;
+        entries[e].key = key;
+        map_slots(map)[slot] = e + 1;
+    }
+    entries[e].value = value;
+}
+
+int _bltn_string_int_map_get(Map *map, const char *key) {
+    unsigned slot;
+    int e = map_find(map, key, &slot);
+    if (e < 0) {
+        runtime_error("map key not found");
+    }
+    return reinterpret_cast<MapEntry*>(map->data)[e].value;
+}
+
+bool _bltn_string_int_map_contains(Map *map, const char *key) {
+    unsigned slot;
+    return map_find(map, key, &slot) >= 0;
+}
+
+int _bltn_string_int_map_size(Map *map) {
+    if (!map) {
+        runtime_error("null dereference (map)");
+    }
+    return map->size;
+}
+
+// the keys of an empty map are an array of length 0 (only the header)
+const char **_bltn_string_int_map_keys(Map *map) {
+    if (!map) {
+        runtime_error("null dereference (map)");
+    }
+    int *header_ptr = static_cast<int*>(_bltn_malloc(sizeof(int) + (size_t) map->size * sizeof(char*)));
+    *header_ptr = map->size;
+    const char **keys = reinterpret_cast<const char**>(header_ptr + 1);
+    MapEntry *entries = reinterpret_cast<MapEntry*>(map->data);
+    for (int e = 0; e < map->size; e++) {
+        keys[e] = entries[e].key;
+    }
+    return keys;
+}
+
+// the layout must match the Object class in the compiler,
+// the default methods are in the vtables of all classes
+struct Object {
+    void *vtable;
+};
+
+bool _bltn_object_equals(Object *self, Object *other) {
+    return self == other;
+}
+
+int _bltn_object_hash_code(Object *self) {
+    unsigned long long addr = (unsigned long long) self;
+    return (int) (addr ^ (addr >> 32));
+}
+
+const char *_bltn_object_to_string(Object *self) {
+    char *ptr = (char*) malloc(32);
+    if (!ptr) {
+        runtime_error("out of memory");
+    }
+    sprintf(ptr, "Object@%x", _bltn_object_hash_code(self));
+    return ptr;
+}
+
 }
@@ -7,12 +7,59 @@ target triple = "x86_64-pc-linux-gnu"
 %struct._IO_marker = type opaque
 %struct._IO_codecvt = type opaque
 %struct._IO_wide_data = type opaque
+%struct.StringBuilder = type { i8*, i8*, i32, i32 }
+%struct.List = type { i8*, i8*, i32, i32 }
+%struct.Object = type { i8* }
+%struct.Map = type { i8*, i8*, i32, i32 }
+%struct.MapEntry = type { i8*, i32 }
+%struct.rlimit = type { i64, i64 }
 
 @.str = private unnamed_addr constant [4 x i8] c"%d\0A\00", align 1
 @.str.1 = private unnamed_addr constant [4 x i8] c"%s\0A\00", align 1
 @.str.2 = private unnamed_addr constant [1 x i8] zeroinitializer, align 1
 @.str.3 = private unnamed_addr constant [15 x i8] c"runtime error\0A\00", align 1
 @stdin = external local_unnamed_addr global %struct._IO_FILE*, align 8
+@_bltn_stack_limit = dso_local local_unnamed_addr global i8* null, align 8
+@stderr = external local_unnamed_addr global %struct._IO_FILE*, align 8
+@stdout = external local_unnamed_addr global %struct._IO_FILE*, align 8
+@.str.5 = private unnamed_addr constant [10 x i8] c"Object@%x\00", align 1
+@.str.6 = private unnamed_addr constant [5 x i8] c"true\00", align 1
+@.str.7 = private unnamed_addr constant [6 x i8] c"false\00", align 1
+@.str.8 = private unnamed_addr constant [19 x i8] c"Runtime error: %s\0A\00", align 1
+@.str.9 = private unnamed_addr constant [18 x i8] c"readInt: no input\00", align 1
+@.str.10 = private unnamed_addr constant [22 x i8] c"readInt: not a number\00", align 1
+@.str.11 = private unnamed_addr constant [27 x i8] c"string index out of bounds\00", align 1
+@.str.12 = private unnamed_addr constant [38 x i8] c"stack overflow, recursion is too deep\00", align 1
+@.str.13 = private unnamed_addr constant [22 x i8] c"allocation of 0 bytes\00", align 1
+@.str.14 = private unnamed_addr constant [14 x i8] c"out of memory\00", align 1
+@.str.15 = private unnamed_addr constant [19 x i8] c"invalid array size\00", align 1
+@.str.16 = private unnamed_addr constant [36 x i8] c"null dereference (copy of an array)\00", align 1
+@.str.17 = private unnamed_addr constant [33 x i8] c"null dereference (StringBuilder)\00", align 1
+@.str.18 = private unnamed_addr constant [25 x i8] c"list index out of bounds\00", align 1
+@.str.19 = private unnamed_addr constant [24 x i8] c"null dereference (list)\00", align 1
+@.str.20 = private unnamed_addr constant [29 x i8] c"division by zero or overflow\00", align 1
+@.str.21 = private unnamed_addr constant [42 x i8] c"invalid memory access (null dereference?)\00", align 1
+@.str.22 = private unnamed_addr constant [3 x i8] c"rb\00", align 1
+@.str.23 = private unnamed_addr constant [3 x i8] c"wb\00", align 1
+@.str.24 = private unnamed_addr constant [31 x i8] c"readFile: cannot open the file\00", align 1
+@.str.25 = private unnamed_addr constant [31 x i8] c"readFile: cannot read the file\00", align 1
+@.str.26 = private unnamed_addr constant [32 x i8] c"writeFile: cannot open the file\00", align 1
+@.str.27 = private unnamed_addr constant [33 x i8] c"writeFile: cannot write the file\00", align 1
+@.str.28 = private unnamed_addr constant [23 x i8] c"split: empty separator\00", align 1
+@.str.29 = private unnamed_addr constant [23 x i8] c"null dereference (map)\00", align 1
+@.str.30 = private unnamed_addr constant [18 x i8] c"map key not found\00", align 1
+@.str.31 = private unnamed_addr constant [25 x i8] c"cannot write the profile\00", align 1
+@.str.32 = private unnamed_addr constant [7 x i8] c"%s%lld\00", align 1
+@.str.33 = private unnamed_addr constant [3 x i8] c", \00", align 1
+@.str.34 = private unnamed_addr constant [27 x i8] c"[\22latte-profile\22, \22%s\22,\0A [\00", align 1
+@.str.35 = private unnamed_addr constant [6 x i8] c"],\0A [\00", align 1
+@.str.36 = private unnamed_addr constant [4 x i8] c"]]\0A\00", align 1
+@.str.37 = private unnamed_addr constant [31 x i8] c"null dereference (method call)\00", align 1
+@_ZL14profile_counts = internal unnamed_addr global i64* null, align 8
+@_ZL13profile_taken = internal unnamed_addr global i64* null, align 8
+@_ZL12profile_size = internal unnamed_addr global i32 0, align 4
+@_ZL16profile_checksum = internal unnamed_addr global i8* null, align 8
+@_ZL12profile_path = internal unnamed_addr global i8* null, align 8
 
 ; Function Attrs: sspstrong uwtable
 define dso_local void @printInt(i32) local_unnamed_addr #0 {
@@ -30,6 +77,13 @@ define dso_local void @printString(i8*) local_unnamed_addr #0 {
   ret void
 }
 
+; Function Attrs: sspstrong uwtable
+define dso_local void @printBoolean(i1 zeroext) local_unnamed_addr #0 {
+  %2 = select i1 %0, i8* getelementptr inbounds ([5 x i8], [5 x i8]* @.str.6, i64 0, i64 0), i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.str.7, i64 0, i64 0)
+  %3 = tail call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([4 x i8], [4 x i8]* @.str.1, i64 0, i64 0), i8* %2) #9
+  ret void
+}
+
 ; Function Attrs: noreturn sspstrong uwtable
 define dso_local void @error() local_unnamed_addr #2 {
   %1 = tail call i32 (i8*, ...) @printf(i8* getelementptr inbounds ([15 x i8], [15 x i8]* @.str.3, i64 0, i64 0)) #9
@@ -40,8 +94,36 @@ define dso_local void @error() local_unnamed_addr #2 {
 ; Function Attrs: noreturn nounwind
 declare void @exit(i32) local_unnamed_addr #3
 
+; Function Attrs: nounwind sspstrong uwtable
+define dso_local void @printErr(i8*) local_unnamed_addr #6 {
+  %2 = icmp eq i8* %0, null
+  %3 = select i1 %2, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.2, i64 0, i64 0), i8* %0
+  %4 = load %struct._IO_FILE*, %struct._IO_FILE** @stderr, align 8, !tbaa !4
+  %5 = tail call i32 (%struct._IO_FILE*, i8*, ...) @fprintf(%struct._IO_FILE* %4, i8* getelementptr inbounds ([4 x i8], [4 x i8]* @.str.1, i64 0, i64 0), i8* %3) #12
+  ret void
+}
+
+; Function Attrs: nounwind
+declare i32 @fprintf(%struct._IO_FILE* nocapture, i8* nocapture readonly, ...) local_unnamed_addr #5
+
+; Function Attrs: noreturn sspstrong uwtable
+define internal fastcc void @_ZL13runtime_errorPKc(i8*) unnamed_addr #2 {
+  %2 = load %struct._IO_FILE*, %struct._IO_FILE** @stderr, align 8, !tbaa !4
+  %3 = tail call i32 (%struct._IO_FILE*, i8*, ...) @fprintf(%struct._IO_FILE* %2, i8* getelementptr inbounds ([19 x i8], [19 x i8]* @.str.8, i64 0, i64 0), i8* %0) #12
+  tail call void @error() #10
+  unreachable
+}
+
+; Function Attrs: nounwind sspstrong uwtable
+define dso_local void @flushOutput() local_unnamed_addr #6 {
+  %1 = load %struct._IO_FILE*, %struct._IO_FILE** @stdout, align 8, !tbaa !4
+  %2 = tail call i32 @fflush(%struct._IO_FILE* %1) #12
+  ret void
+}
+
 ; Function Attrs: sspstrong uwtable
 define dso_local i32 @readInt() local_unnamed_addr #0 {
+  tail call void @flushOutput()
   %1 = alloca i8*, align 8
   %2 = alloca i64, align 8
   %3 = bitcast i8** %1 to i8*
@@ -56,7 +138,7 @@ define dso_local i32 @readInt() local_unnamed_addr #0 {
   br i1 %7, label %8, label %9
 
 ; <label>:8:                                      ; preds = %0
-  call void @error() #9
+  call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([18 x i8], [18 x i8]* @.str.9, i64 0, i64 0)) #10
   unreachable
 
 ; <label>:9:                                      ; preds = %0
@@ -150,7 +232,7 @@ define dso_local i32 @readInt() local_unnamed_addr #0 {
   br i1 %68, label %70, label %84
 
 ; <label>:69:                                     ; preds = %60, %56
-  call void @error() #9
+  call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([22 x i8], [22 x i8]* @.str.10, i64 0, i64 0)) #10
   unreachable
 
 ; <label>:70:                                     ; preds = %65, %79
@@ -208,7 +290,7 @@ define dso_local i32 @readInt() local_unnamed_addr #0 {
   br i1 %107, label %109, label %108
 
 ; <label>:108:                                    ; preds = %103
-  call void @error() #9
+  call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([22 x i8], [22 x i8]* @.str.10, i64 0, i64 0)) #10
   unreachable
 
 ; <label>:109:                                    ; preds = %103
@@ -237,6 +319,7 @@ declare void @llvm.lifetime.end.p0i8(i64, i8* nocapture) #4
 
 ; Function Attrs: sspstrong uwtable
 define dso_local i8* @readString() local_unnamed_addr #0 {
+  tail call void @flushOutput()
   %1 = alloca i8*, align 8
   %2 = alloca i64, align 8
   %3 = bitcast i8** %1 to i8*
@@ -270,6 +353,112 @@ define dso_local i8* @readString() local_unnamed_addr #0 {
   ret i8* %17
 }
 
+; Function Attrs: sspstrong uwtable
+define dso_local i8* @readFile(i8* %path) local_unnamed_addr #0 {
+entry:
+  %is_null = icmp eq i8* %path, null
+  %name = select i1 %is_null, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.2, i64 0, i64 0), i8* %path
+  %file = tail call %struct._IO_FILE* @fopen(i8* %name, i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.str.22, i64 0, i64 0)) #12
+  %no_file = icmp eq %struct._IO_FILE* %file, null
+  br i1 %no_file, label %open_error, label %alloc
+
+open_error:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([31 x i8], [31 x i8]* @.str.24, i64 0, i64 0)) #10
+  unreachable
+
+alloc:
+  %first = tail call noalias i8* @malloc(i64 4096) #12
+  br label %loop
+
+loop:
+  %buf = phi i8* [ %first, %alloc ], [ %grown, %grow ]
+  %cap = phi i64 [ 4096, %alloc ], [ %new_cap, %grow ]
+  %len = phi i64 [ 0, %alloc ], [ %new_len, %grow ]
+  %no_buf = icmp eq i8* %buf, null
+  br i1 %no_buf, label %read_error, label %read
+
+read:
+  %dst = getelementptr inbounds i8, i8* %buf, i64 %len
+  %last = add i64 %cap, -1
+  %room = sub i64 %last, %len
+  %cnt = tail call i64 @fread(i8* %dst, i64 1, i64 %room, %struct._IO_FILE* %file) #12
+  %new_len = add i64 %cnt, %len
+  %short = icmp ult i64 %new_len, %last
+  br i1 %short, label %done, label %grow
+
+grow:
+  %new_cap = shl i64 %cap, 1
+  %grown = tail call i8* @realloc(i8* %buf, i64 %new_cap) #12
+  br label %loop
+
+done:
+  %err = tail call i32 @ferror(%struct._IO_FILE* %file) #12
+  %failed = icmp ne i32 %err, 0
+  br i1 %failed, label %read_error, label %finish
+
+read_error:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([31 x i8], [31 x i8]* @.str.25, i64 0, i64 0)) #10
+  unreachable
+
+finish:
+  %closed = tail call i32 @fclose(%struct._IO_FILE* %file) #12
+  %end = getelementptr inbounds i8, i8* %buf, i64 %new_len
+  store i8 0, i8* %end, align 1
+  ret i8* %buf
+}
+
+; Function Attrs: nounwind
+declare noalias %struct._IO_FILE* @fopen(i8* nocapture readonly, i8* nocapture readonly) local_unnamed_addr #5
+
+; Function Attrs: nounwind
+declare i64 @fread(i8* nocapture, i64, i64, %struct._IO_FILE* nocapture) local_unnamed_addr #5
+
+; Function Attrs: nounwind
+declare i32 @ferror(%struct._IO_FILE* nocapture) local_unnamed_addr #5
+
+; Function Attrs: nounwind
+declare i32 @fclose(%struct._IO_FILE* nocapture) local_unnamed_addr #5
+
+; Function Attrs: sspstrong uwtable
+define dso_local void @writeFile(i8* %path, i8* %contents) local_unnamed_addr #0 {
+entry:
+  %is_null = icmp eq i8* %path, null
+  %name = select i1 %is_null, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.2, i64 0, i64 0), i8* %path
+  %file = tail call %struct._IO_FILE* @fopen(i8* %name, i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.str.23, i64 0, i64 0)) #12
+  %no_file = icmp eq %struct._IO_FILE* %file, null
+  br i1 %no_file, label %open_error, label %write
+
+open_error:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([32 x i8], [32 x i8]* @.str.26, i64 0, i64 0)) #10
+  unreachable
+
+write:
+  %empty = icmp eq i8* %contents, null
+  br i1 %empty, label %close, label %put
+
+put:
+  %put_res = tail call i32 @fputs(i8* nonnull %contents, %struct._IO_FILE* %file) #12
+  %put_failed = icmp eq i32 %put_res, -1
+  br label %close
+
+close:
+  %failed = phi i1 [ false, %write ], [ %put_failed, %put ]
+  %closed = tail call i32 @fclose(%struct._IO_FILE* %file) #12
+  %close_failed = icmp ne i32 %closed, 0
+  %any_failed = or i1 %failed, %close_failed
+  br i1 %any_failed, label %write_error, label %ok
+
+write_error:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([33 x i8], [33 x i8]* @.str.27, i64 0, i64 0)) #10
+  unreachable
+
+ok:
+  ret void
+}
+
+; Function Attrs: nounwind
+declare i32 @fputs(i8* nocapture readonly, %struct._IO_FILE* nocapture) local_unnamed_addr #5
+
 ; Function Attrs: nounwind sspstrong uwtable
 define dso_local i8* @_bltn_string_concat(i8*, i8*) local_unnamed_addr #6 {
   %3 = icmp eq i8* %0, null
@@ -306,6 +495,68 @@ declare i8* @strcpy(i8*, i8*) local_unnamed_addr #5
 ; Function Attrs: nounwind
 declare i8* @strcat(i8*, i8*) local_unnamed_addr #5
 
+; Function Attrs: nounwind sspstrong uwtable
+define dso_local i8* @_bltn_string_concat_n(i32, i8** nocapture readonly) local_unnamed_addr #6 {
+  %3 = icmp sgt i32 %0, 0
+  br i1 %3, label %4, label %19
+
+; <label>:4:                                      ; preds = %2
+  %5 = zext i32 %0 to i64
+  br label %6
+
+; <label>:6:                                      ; preds = %15, %4
+  %7 = phi i64 [ 0, %4 ], [ %17, %15 ]
+  %8 = phi i64 [ 1, %4 ], [ %16, %15 ]
+  %9 = getelementptr inbounds i8*, i8** %1, i64 %7
+  %10 = load i8*, i8** %9, align 8
+  %11 = icmp eq i8* %10, null
+  br i1 %11, label %15, label %12
+
+; <label>:12:                                     ; preds = %6
+  %13 = tail call i64 @strlen(i8* nonnull %10) #13
+  %14 = add i64 %13, %8
+  br label %15
+
+; <label>:15:                                     ; preds = %6, %12
+  %16 = phi i64 [ %14, %12 ], [ %8, %6 ]
+  %17 = add nuw nsw i64 %7, 1
+  %18 = icmp eq i64 %17, %5
+  br i1 %18, label %19, label %6
+
+; <label>:19:                                     ; preds = %15, %2
+  %20 = phi i64 [ 1, %2 ], [ %16, %15 ]
+  %21 = tail call noalias i8* @malloc(i64 %20) #12
+  store i8 0, i8* %21, align 1
+  br i1 %3, label %22, label %38
+
+; <label>:22:                                     ; preds = %19
+  %23 = zext i32 %0 to i64
+  br label %24
+
+; <label>:24:                                     ; preds = %34, %22
+  %25 = phi i64 [ 0, %22 ], [ %36, %34 ]
+  %26 = phi i8* [ %21, %22 ], [ %35, %34 ]
+  %27 = getelementptr inbounds i8*, i8** %1, i64 %25
+  %28 = load i8*, i8** %27, align 8
+  %29 = icmp eq i8* %28, null
+  br i1 %29, label %34, label %30
+
+; <label>:30:                                     ; preds = %24
+  %31 = tail call i64 @strlen(i8* nonnull %28) #13
+  %32 = tail call i8* @strcpy(i8* %26, i8* nonnull %28) #12
+  %33 = getelementptr inbounds i8, i8* %26, i64 %31
+  br label %34
+
+; <label>:34:                                     ; preds = %24, %30
+  %35 = phi i8* [ %33, %30 ], [ %26, %24 ]
+  %36 = add nuw nsw i64 %25, 1
+  %37 = icmp eq i64 %36, %23
+  br i1 %37, label %38, label %24
+
+; <label>:38:                                     ; preds = %34, %19
+  ret i8* %21
+}
+
 ; Function Attrs: nounwind readonly sspstrong uwtable
 define dso_local zeroext i1 @_bltn_string_eq(i8* readonly, i8* readonly) local_unnamed_addr #8 {
   %3 = icmp ne i8* %0, null
@@ -352,69 +603,1128 @@ define dso_local zeroext i1 @_bltn_string_ne(i8* readonly, i8* readonly) local_u
 }
 
 ; Function Attrs: sspstrong uwtable
-define dso_local i8* @_bltn_malloc(i32) local_unnamed_addr #0 {
-  %2 = icmp slt i32 %0, 1
+define dso_local void @_bltn_string_check_index(i8*, i32) local_unnamed_addr #0 {
+  %3 = icmp eq i8* %0, null
+  %4 = icmp slt i32 %1, 0
+  %5 = or i1 %3, %4
+  br i1 %5, label %10, label %6
+
+; <label>:6:                                      ; preds = %2
+  %7 = tail call i64 @strlen(i8* nonnull %0) #13
+  %8 = sext i32 %1 to i64
+  %9 = icmp ult i64 %8, %7
+  br i1 %9, label %11, label %10
+
+; <label>:10:                                     ; preds = %6, %2
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([27 x i8], [27 x i8]* @.str.11, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:11:                                     ; preds = %6
+  ret void
+}
+
+; Function Attrs: noreturn sspstrong uwtable
+define internal void @_ZL8on_crashi(i32) #2 {
+  tail call void @flushOutput()
+  %2 = icmp eq i32 %0, 8
+  %3 = select i1 %2, i8* getelementptr inbounds ([29 x i8], [29 x i8]* @.str.20, i64 0, i64 0), i8* getelementptr inbounds ([42 x i8], [42 x i8]* @.str.21, i64 0, i64 0)
+  %4 = load %struct._IO_FILE*, %struct._IO_FILE** @stderr, align 8, !tbaa !4
+  %5 = tail call i32 (%struct._IO_FILE*, i8*, ...) @fprintf(%struct._IO_FILE* %4, i8* getelementptr inbounds ([19 x i8], [19 x i8]* @.str.8, i64 0, i64 0), i8* %3) #12
+  tail call void @_exit(i32 1) #10
+  unreachable
+}
+
+; Function Attrs: noreturn
+declare void @_exit(i32) local_unnamed_addr #3
+
+; Function Attrs: sspstrong uwtable
+define dso_local void @_bltn_runtime_init() local_unnamed_addr #0 {
+  %.stdout = load %struct._IO_FILE*, %struct._IO_FILE** @stdout, align 8, !tbaa !4
+  %.setvbuf = call i32 @setvbuf(%struct._IO_FILE* %.stdout, i8* null, i32 0, i64 65536) #11
+  %.segv = call void (i32)* @signal(i32 11, void (i32)* @_ZL8on_crashi) #11
+  %.fpe = call void (i32)* @signal(i32 8, void (i32)* @_ZL8on_crashi) #11
+  %1 = alloca %struct.rlimit, align 8
+  %2 = bitcast %struct.rlimit* %1 to i8*
+  call void @llvm.lifetime.start.p0i8(i64 16, i8* nonnull %2) #11
+  %3 = call i32 @getrlimit(i32 3, %struct.rlimit* nonnull %1) #11
+  %4 = icmp eq i32 %3, 0
+  br i1 %4, label %5, label %10
+
+; <label>:5:                                      ; preds = %0
+  %6 = getelementptr inbounds %struct.rlimit, %struct.rlimit* %1, i64 0, i32 0
+  %7 = load i64, i64* %6, align 8
+  %8 = icmp eq i64 %7, -1
+  %9 = select i1 %8, i64 8388608, i64 %7
+  br label %10
+
+; <label>:10:                                     ; preds = %5, %0
+  %11 = phi i64 [ 8388608, %0 ], [ %9, %5 ]
+  %12 = call i8* @llvm.frameaddress.p0i8(i32 0)
+  %13 = sub i64 65536, %11
+  %14 = getelementptr inbounds i8, i8* %12, i64 %13
+  store i8* %14, i8** @_bltn_stack_limit, align 8
+  call void @llvm.lifetime.end.p0i8(i64 16, i8* nonnull %2) #11
+  ret void
+}
+
+; Function Attrs: nounwind
+; Function Attrs: nounwind
+declare void (i32)* @signal(i32, void (i32)*) local_unnamed_addr #5
+
+; Function Attrs: nounwind
+declare i32 @setvbuf(%struct._IO_FILE* nocapture, i8*, i32, i64) local_unnamed_addr #5
+
+declare i32 @getrlimit(i32, %struct.rlimit*) local_unnamed_addr #5
+
+; Function Attrs: sspstrong uwtable
+define dso_local void @_bltn_profile_init(i32 %size, i8* %checksum, i8* %path) local_unnamed_addr #0 {
+entry:
+  %n = sext i32 %size to i64
+  %counts_raw = tail call noalias i8* @calloc(i64 %n, i64 8) #12
+  %counts = bitcast i8* %counts_raw to i64*
+  store i64* %counts, i64** @_ZL14profile_counts, align 8, !tbaa !4
+  %taken_raw = tail call noalias i8* @calloc(i64 %n, i64 8) #12
+  %taken = bitcast i8* %taken_raw to i64*
+  store i64* %taken, i64** @_ZL13profile_taken, align 8, !tbaa !4
+  %no_counts = icmp eq i8* %counts_raw, null
+  %no_taken = icmp eq i8* %taken_raw, null
+  %failed = or i1 %no_counts, %no_taken
+  br i1 %failed, label %alloc_error, label %ok
+
+alloc_error:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.14, i64 0, i64 0)) #10
+  unreachable
+
+ok:
+  store i32 %size, i32* @_ZL12profile_size, align 4, !tbaa !11
+  store i8* %checksum, i8** @_ZL16profile_checksum, align 8, !tbaa !4
+  store i8* %path, i8** @_ZL12profile_path, align 8, !tbaa !4
+  ret void
+}
+
+; Function Attrs: norecurse nounwind sspstrong uwtable
+define dso_local void @_bltn_profile_block(i32 %id) local_unnamed_addr #15 {
+entry:
+  %counts = load i64*, i64** @_ZL14profile_counts, align 8, !tbaa !4
+  %idx = sext i32 %id to i64
+  %ptr = getelementptr inbounds i64, i64* %counts, i64 %idx
+  %old = load i64, i64* %ptr, align 8, !tbaa !13
+  %new = add nsw i64 %old, 1
+  store i64 %new, i64* %ptr, align 8, !tbaa !13
+  ret void
+}
+
+; Function Attrs: norecurse nounwind sspstrong uwtable
+define dso_local void @_bltn_profile_branch(i32 %id, i1 zeroext %taken) local_unnamed_addr #15 {
+entry:
+  %taken_count = load i64*, i64** @_ZL13profile_taken, align 8, !tbaa !4
+  %idx = sext i32 %id to i64
+  %ptr = getelementptr inbounds i64, i64* %taken_count, i64 %idx
+  %old = load i64, i64* %ptr, align 8, !tbaa !13
+  %inc = zext i1 %taken to i64
+  %new = add nsw i64 %old, %inc
+  store i64 %new, i64* %ptr, align 8, !tbaa !13
+  ret void
+}
+
+; Function Attrs: nounwind sspstrong uwtable
+define internal fastcc void @_ZL22profile_write_countersP8_IO_FILEPKx(%struct._IO_FILE* %file, i64* %counters) unnamed_addr #6 {
+entry:
+  %size = load i32, i32* @_ZL12profile_size, align 4, !tbaa !11
+  %non_empty = icmp sgt i32 %size, 0
+  br i1 %non_empty, label %loop, label %exit
+
+loop:
+  %i = phi i64 [ 0, %entry ], [ %next, %loop ]
+  %first = icmp eq i64 %i, 0
+  %sep = select i1 %first, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.2, i64 0, i64 0), i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.str.33, i64 0, i64 0)
+  %ptr = getelementptr inbounds i64, i64* %counters, i64 %i
+  %count = load i64, i64* %ptr, align 8, !tbaa !13
+  %res = tail call i32 (%struct._IO_FILE*, i8*, ...) @fprintf(%struct._IO_FILE* %file, i8* getelementptr inbounds ([7 x i8], [7 x i8]* @.str.32, i64 0, i64 0), i8* %sep, i64 %count) #12
+  %next = add nuw nsw i64 %i, 1
+  %n = load i32, i32* @_ZL12profile_size, align 4, !tbaa !11
+  %n64 = sext i32 %n to i64
+  %more = icmp slt i64 %next, %n64
+  br i1 %more, label %loop, label %exit
+
+exit:
+  ret void
+}
+
+; Function Attrs: nounwind sspstrong uwtable
+define internal fastcc void @_ZL12profile_dumpv() unnamed_addr #6 {
+entry:
+  %path = load i8*, i8** @_ZL12profile_path, align 8, !tbaa !4
+  %file = tail call %struct._IO_FILE* @fopen(i8* %path, i8* getelementptr inbounds ([3 x i8], [3 x i8]* @.str.23, i64 0, i64 0)) #12
+  %no_file = icmp eq %struct._IO_FILE* %file, null
+  br i1 %no_file, label %error, label %write
+
+write:
+  %checksum = load i8*, i8** @_ZL16profile_checksum, align 8, !tbaa !4
+  %0 = tail call i32 (%struct._IO_FILE*, i8*, ...) @fprintf(%struct._IO_FILE* nonnull %file, i8* getelementptr inbounds ([27 x i8], [27 x i8]* @.str.34, i64 0, i64 0), i8* %checksum) #12
+  %counts = load i64*, i64** @_ZL14profile_counts, align 8, !tbaa !4
+  tail call fastcc void @_ZL22profile_write_countersP8_IO_FILEPKx(%struct._IO_FILE* nonnull %file, i64* %counts)
+  %1 = tail call i32 @fputs(i8* getelementptr inbounds ([6 x i8], [6 x i8]* @.str.35, i64 0, i64 0), %struct._IO_FILE* nonnull %file) #12
+  %taken = load i64*, i64** @_ZL13profile_taken, align 8, !tbaa !4
+  tail call fastcc void @_ZL22profile_write_countersP8_IO_FILEPKx(%struct._IO_FILE* nonnull %file, i64* %taken)
+  %2 = tail call i32 @fputs(i8* getelementptr inbounds ([4 x i8], [4 x i8]* @.str.36, i64 0, i64 0), %struct._IO_FILE* nonnull %file) #12
+  %closed = tail call i32 @fclose(%struct._IO_FILE* nonnull %file) #12
+  %close_failed = icmp ne i32 %closed, 0
+  br i1 %close_failed, label %error, label %exit
+
+error:
+  %stderr = load %struct._IO_FILE*, %struct._IO_FILE** @stderr, align 8, !tbaa !4
+  %3 = tail call i32 (%struct._IO_FILE*, i8*, ...) @fprintf(%struct._IO_FILE* %stderr, i8* getelementptr inbounds ([19 x i8], [19 x i8]* @.str.8, i64 0, i64 0), i8* getelementptr inbounds ([25 x i8], [25 x i8]* @.str.31, i64 0, i64 0)) #12
+  br label %exit
+
+exit:
+  ret void
+}
+
+; Function Attrs: nounwind sspstrong uwtable
+define dso_local void @_bltn_runtime_shutdown() local_unnamed_addr #6 {
+entry:
+  tail call void @flushOutput()
+  %path = load i8*, i8** @_ZL12profile_path, align 8, !tbaa !4
+  %no_profile = icmp eq i8* %path, null
+  br i1 %no_profile, label %exit, label %dump
+
+dump:
+  tail call fastcc void @_ZL12profile_dumpv()
+  br label %exit
+
+exit:
+  ret void
+}
+
+; Function Attrs: nounwind
+declare i32 @fflush(%struct._IO_FILE* nocapture) local_unnamed_addr #5
+
+; Function Attrs: nounwind readnone
+declare i8* @llvm.frameaddress.p0i8(i32) #14
+
+; Function Attrs: noreturn sspstrong uwtable
+define dso_local void @_bltn_stack_overflow_error() local_unnamed_addr #2 {
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([38 x i8], [38 x i8]* @.str.12, i64 0, i64 0)) #10
+  unreachable
+}
+
+; Function Attrs: noreturn sspstrong uwtable
+define dso_local void @_bltn_null_dereference_error() local_unnamed_addr #2 {
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([31 x i8], [31 x i8]* @.str.37, i64 0, i64 0)) #10
+  unreachable
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i8* @_bltn_malloc(i64) local_unnamed_addr #0 {
+  %2 = icmp eq i64 %0, 0
   br i1 %2, label %3, label %4
 
 ; <label>:3:                                      ; preds = %1
-  tail call void @error() #9
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([22 x i8], [22 x i8]* @.str.13, i64 0, i64 0)) #10
   unreachable
 
 ; <label>:4:                                      ; preds = %1
-  %5 = sext i32 %0 to i64
-  %6 = tail call noalias i8* @malloc(i64 %5) #12
-  %7 = icmp eq i8* %6, null
-  br i1 %7, label %8, label %9
+  %5 = tail call noalias i8* @malloc(i64 %0) #12
+  %6 = icmp eq i8* %5, null
+  br i1 %6, label %7, label %8
 
-; <label>:8:                                      ; preds = %4
-  tail call void @error() #9
+; <label>:7:                                      ; preds = %4
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.14, i64 0, i64 0)) #10
   unreachable
 
-; <label>:9:                                      ; preds = %4
-  %10 = tail call i8* @memset(i8* nonnull %6, i32 0, i64 %5) #12
-  ret i8* %6
+; <label>:8:                                      ; preds = %4
+  %9 = tail call i8* @memset(i8* nonnull %5, i32 0, i64 %0) #12
+  ret i8* %5
 }
 
 ; Function Attrs: nounwind
 declare i8* @memset(i8*, i32, i64) local_unnamed_addr #5
 
 ; Function Attrs: sspstrong uwtable
-define dso_local nonnull i8* @_bltn_alloc_array(i32, i32) local_unnamed_addr #0 {
+define dso_local nonnull i8* @_bltn_alloc_array(i32, i64) local_unnamed_addr #0 {
   %3 = icmp slt i32 %0, 1
-  %4 = icmp slt i32 %1, 1
+  %4 = icmp eq i64 %1, 0
   %5 = or i1 %3, %4
   br i1 %5, label %6, label %7
 
 ; <label>:6:                                      ; preds = %2
-  tail call void @error() #9
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([19 x i8], [19 x i8]* @.str.15, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:7:                                      ; preds = %2
+  %8 = zext i32 %0 to i64
+  %9 = mul i64 %8, %1
+  %10 = add i64 %9, 4
+  %11 = tail call noalias i8* @malloc(i64 %10) #12
+  %12 = icmp eq i8* %11, null
+  br i1 %12, label %13, label %14
+
+; <label>:13:                                     ; preds = %7
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.14, i64 0, i64 0)) #10
   unreachable
 
+; <label>:14:                                     ; preds = %7
+  %15 = tail call i8* @memset(i8* nonnull %11, i32 0, i64 %10) #12
+  %16 = bitcast i8* %11 to i32*
+  store i32 %0, i32* %16, align 4, !tbaa !11
+  %17 = getelementptr inbounds i8, i8* %11, i64 4
+  ret i8* %17
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i8* @_bltn_array_copy(i8*, i64) local_unnamed_addr #0 {
+  %3 = icmp eq i8* %0, null
+  br i1 %3, label %4, label %5
+
+; <label>:4:                                      ; preds = %2
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([36 x i8], [36 x i8]* @.str.16, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:5:                                      ; preds = %2
+  %6 = getelementptr inbounds i8, i8* %0, i64 -4
+  %7 = bitcast i8* %6 to i32*
+  %8 = load i32, i32* %7, align 4, !tbaa !11
+  %9 = tail call i8* @_bltn_alloc_array(i32 %8, i64 %1)
+  %10 = sext i32 %8 to i64
+  %11 = mul i64 %10, %1
+  %12 = tail call i8* @memcpy(i8* nonnull %9, i8* nonnull %0, i64 %11) #12
+  ret i8* %9
+}
+
+; Function Attrs: nounwind
+declare i8* @memcpy(i8*, i8*, i64) local_unnamed_addr #5
+
+; Function Attrs: sspstrong uwtable
+define dso_local i8* @_bltn_array_resize(i8*, i32, i64) local_unnamed_addr #0 {
+  %4 = tail call i8* @_bltn_alloc_array(i32 %1, i64 %2)
+  %5 = icmp eq i8* %0, null
+  br i1 %5, label %15, label %6
+
+; <label>:6:                                      ; preds = %3
+  %7 = getelementptr inbounds i8, i8* %0, i64 -4
+  %8 = bitcast i8* %7 to i32*
+  %9 = load i32, i32* %8, align 4, !tbaa !11
+  %10 = icmp slt i32 %9, %1
+  %11 = select i1 %10, i32 %9, i32 %1
+  %12 = sext i32 %11 to i64
+  %13 = mul i64 %12, %2
+  %14 = tail call i8* @memcpy(i8* nonnull %4, i8* nonnull %0, i64 %13) #12
+  br label %15
+
+; <label>:15:                                     ; preds = %3, %6
+  ret i8* %4
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i8* @_bltn_array_append(i8*, i64) local_unnamed_addr #0 {
+  %3 = icmp eq i8* %0, null
+  br i1 %3, label %8, label %4
+
+; <label>:4:                                      ; preds = %2
+  %5 = getelementptr inbounds i8, i8* %0, i64 -4
+  %6 = bitcast i8* %5 to i32*
+  %7 = load i32, i32* %6, align 4, !tbaa !11
+  br label %8
+
+; <label>:8:                                      ; preds = %2, %4
+  %9 = phi i32 [ %7, %4 ], [ 0, %2 ]
+  %10 = add nsw i32 %9, 1
+  %11 = tail call i8* @_bltn_array_resize(i8* %0, i32 %10, i64 %1)
+  ret i8* %11
+}
+
+; Function Attrs: nounwind sspstrong uwtable
+define dso_local void @_bltn_array_fill(i8*, i64) local_unnamed_addr #6 {
+  %3 = getelementptr inbounds i8, i8* %0, i64 -4
+  %4 = bitcast i8* %3 to i32*
+  %5 = load i32, i32* %4, align 4, !tbaa !11
+  %6 = icmp sgt i32 %5, 1
+  br i1 %6, label %7, label %8
+
 ; <label>:7:                                      ; preds = %2
-  %8 = mul nsw i32 %1, %0
-  %9 = icmp slt i32 %8, -3
-  br i1 %9, label %10, label %11
+  br label %9
 
-; <label>:10:                                     ; preds = %7
-  tail call void @error() #9
+; <label>:8:                                      ; preds = %9, %2
+  ret void
+
+; <label>:9:                                      ; preds = %7, %9
+  %10 = phi i32 [ 1, %7 ], [ %14, %9 ]
+  %11 = phi i8* [ %0, %7 ], [ %12, %9 ]
+  %12 = getelementptr inbounds i8, i8* %11, i64 %1
+  %13 = tail call i8* @memcpy(i8* %12, i8* %0, i64 %1) #12
+  %14 = add nuw nsw i32 %10, 1
+  %15 = icmp eq i32 %14, %5
+  br i1 %15, label %8, label %9
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i8** @split(i8* %s, i8* %sep) local_unnamed_addr #0 {
+entry:
+  %sep_null = icmp eq i8* %sep, null
+  br i1 %sep_null, label %sep_error, label %check_empty
+
+check_empty:
+  %sep_first = load i8, i8* %sep, align 1, !tbaa !10
+  %sep_empty = icmp eq i8 %sep_first, 0
+  br i1 %sep_empty, label %sep_error, label %count_start
+
+sep_error:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([23 x i8], [23 x i8]* @.str.28, i64 0, i64 0)) #10
   unreachable
 
-; <label>:11:                                     ; preds = %7
-  %12 = add nsw i32 %8, 4
-  %13 = sext i32 %12 to i64
-  %14 = tail call noalias i8* @malloc(i64 %13) #12
+count_start:
+  %s_null = icmp eq i8* %s, null
+  %str = select i1 %s_null, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.2, i64 0, i64 0), i8* %s
+  %sep_len = tail call i64 @strlen(i8* nonnull %sep) #13
+  %first_match = tail call i8* @strstr(i8* %str, i8* nonnull %sep) #13
+  br label %count
+
+count:
+  %cnt = phi i32 [ 1, %count_start ], [ %cnt_next, %count_next ]
+  %match = phi i8* [ %first_match, %count_start ], [ %next_match, %count_next ]
+  %no_match = icmp eq i8* %match, null
+  br i1 %no_match, label %alloc, label %count_next
+
+count_next:
+  %cnt_next = add nsw i32 %cnt, 1
+  %after = getelementptr inbounds i8, i8* %match, i64 %sep_len
+  %next_match = tail call i8* @strstr(i8* %after, i8* nonnull %sep) #13
+  br label %count
+
+alloc:
+  %arr = tail call i8* @_bltn_alloc_array(i32 %cnt, i64 8)
+  %parts = bitcast i8* %arr to i8**
+  br label %part
+
+part:
+  %i = phi i64 [ 0, %alloc ], [ %i_next, %part_next ]
+  %rest = phi i8* [ %str, %alloc ], [ %rest_next, %part_next ]
+  %end = tail call i8* @strstr(i8* %rest, i8* nonnull %sep) #13
+  %is_last = icmp eq i8* %end, null
+  br i1 %is_last, label %last_len, label %mid_len
+
+last_len:
+  %rest_len = tail call i64 @strlen(i8* %rest) #13
+  br label %copy
+
+mid_len:
+  %end_addr = ptrtoint i8* %end to i64
+  %rest_addr = ptrtoint i8* %rest to i64
+  %mid = sub i64 %end_addr, %rest_addr
+  br label %copy
+
+copy:
+  %len = phi i64 [ %rest_len, %last_len ], [ %mid, %mid_len ]
+  %size = add i64 %len, 1
+  %part_buf = tail call noalias i8* @malloc(i64 %size) #12
+  %no_mem = icmp eq i8* %part_buf, null
+  br i1 %no_mem, label %oom, label %fill
+
+oom:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.14, i64 0, i64 0)) #10
+  unreachable
+
+fill:
+  %copied = tail call i8* @memcpy(i8* nonnull %part_buf, i8* %rest, i64 %len) #12
+  %nul = getelementptr inbounds i8, i8* %part_buf, i64 %len
+  store i8 0, i8* %nul, align 1, !tbaa !10
+  %slot = getelementptr inbounds i8*, i8** %parts, i64 %i
+  store i8* %part_buf, i8** %slot, align 8, !tbaa !4
+  br i1 %is_last, label %done, label %part_next
+
+part_next:
+  %i_next = add nuw nsw i64 %i, 1
+  %rest_next = getelementptr inbounds i8, i8* %end, i64 %sep_len
+  br label %part
+
+done:
+  ret i8** %parts
+}
+
+; Function Attrs: nounwind readonly
+declare i8* @strstr(i8*, i8*) local_unnamed_addr #7
+
+; Function Attrs: sspstrong uwtable
+define dso_local i8* @join(i8** %parts, i8* %sep) local_unnamed_addr #0 {
+entry:
+  %sep_null = icmp eq i8* %sep, null
+  %sep_str = select i1 %sep_null, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.2, i64 0, i64 0), i8* %sep
+  %sep_len = tail call i64 @strlen(i8* %sep_str) #13
+  %parts_null = icmp eq i8** %parts, null
+  br i1 %parts_null, label %counted, label %header
+
+header:
+  %header_base = bitcast i8** %parts to i32*
+  %cnt_ptr = getelementptr inbounds i32, i32* %header_base, i64 -1
+  %cnt32 = load i32, i32* %cnt_ptr, align 4, !tbaa !11
+  %cnt64 = sext i32 %cnt32 to i64
+  br label %counted
+
+counted:
+  %cnt = phi i64 [ 0, %entry ], [ %cnt64, %header ]
+  br label %measure
+
+measure:
+  %i = phi i64 [ 0, %counted ], [ %i_next, %measure_part ]
+  %total = phi i64 [ 0, %counted ], [ %total_next, %measure_part ]
+  %more = icmp slt i64 %i, %cnt
+  br i1 %more, label %measure_part, label %alloc
+
+measure_part:
+  %slot = getelementptr inbounds i8*, i8** %parts, i64 %i
+  %part = load i8*, i8** %slot, align 8, !tbaa !4
+  %part_null = icmp eq i8* %part, null
+  %part_str = select i1 %part_null, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.2, i64 0, i64 0), i8* %part
+  %part_len = tail call i64 @strlen(i8* %part_str) #13
+  %first = icmp eq i64 %i, 0
+  %part_sep_len = select i1 %first, i64 0, i64 %sep_len
+  %with_sep = add i64 %total, %part_sep_len
+  %total_next = add i64 %with_sep, %part_len
+  %i_next = add nuw nsw i64 %i, 1
+  br label %measure
+
+alloc:
+  %size = add i64 %total, 1
+  %res = tail call noalias i8* @malloc(i64 %size) #12
+  %no_mem = icmp eq i8* %res, null
+  br i1 %no_mem, label %oom, label %copy
+
+oom:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.14, i64 0, i64 0)) #10
+  unreachable
+
+copy:
+  %j = phi i64 [ 0, %alloc ], [ %j_next, %copy_part ]
+  %dst = phi i8* [ %res, %alloc ], [ %dst_next, %copy_part ]
+  %more_copy = icmp slt i64 %j, %cnt
+  br i1 %more_copy, label %copy_part, label %done
+
+copy_part:
+  %cslot = getelementptr inbounds i8*, i8** %parts, i64 %j
+  %cpart = load i8*, i8** %cslot, align 8, !tbaa !4
+  %cpart_null = icmp eq i8* %cpart, null
+  %cpart_str = select i1 %cpart_null, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.2, i64 0, i64 0), i8* %cpart
+  %cpart_len = tail call i64 @strlen(i8* %cpart_str) #13
+  %cfirst = icmp eq i64 %j, 0
+  %csep_len = select i1 %cfirst, i64 0, i64 %sep_len
+  %sep_copied = tail call i8* @memcpy(i8* %dst, i8* %sep_str, i64 %csep_len) #12
+  %part_dst = getelementptr inbounds i8, i8* %dst, i64 %csep_len
+  %part_copied = tail call i8* @memcpy(i8* %part_dst, i8* %cpart_str, i64 %cpart_len) #12
+  %dst_next = getelementptr inbounds i8, i8* %part_dst, i64 %cpart_len
+  %j_next = add nuw nsw i64 %j, 1
+  br label %copy
+
+done:
+  store i8 0, i8* %dst, align 1, !tbaa !10
+  ret i8* %res
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local %struct.StringBuilder* @_bltn_string_builder_append(%struct.StringBuilder*, i8*) local_unnamed_addr #0 {
+  %3 = icmp eq %struct.StringBuilder* %0, null
+  br i1 %3, label %4, label %5
+
+; <label>:4:                                      ; preds = %2
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([33 x i8], [33 x i8]* @.str.17, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:5:                                      ; preds = %2
+  %6 = icmp eq i8* %1, null
+  br i1 %6, label %38, label %7
+
+; <label>:7:                                      ; preds = %5
+  %8 = tail call i64 @strlen(i8* nonnull %1) #13
+  %9 = trunc i64 %8 to i32
+  %10 = getelementptr inbounds %struct.StringBuilder, %struct.StringBuilder* %0, i64 0, i32 2
+  %11 = load i32, i32* %10, align 8
+  %12 = add i32 %11, 1
+  %13 = add i32 %12, %9
+  %14 = getelementptr inbounds %struct.StringBuilder, %struct.StringBuilder* %0, i64 0, i32 3
+  %15 = load i32, i32* %14, align 4
+  %16 = icmp sgt i32 %13, %15
+  %17 = getelementptr inbounds %struct.StringBuilder, %struct.StringBuilder* %0, i64 0, i32 1
+  %18 = load i8*, i8** %17, align 8
+  br i1 %16, label %19, label %32
+
+; <label>:19:                                     ; preds = %7
+  %20 = icmp sgt i32 %15, 0
+  %21 = select i1 %20, i32 %15, i32 16
+  br label %22
+
+; <label>:22:                                     ; preds = %22, %19
+  %23 = phi i32 [ %21, %19 ], [ %24, %22 ]
+  %24 = shl nsw i32 %23, 1
+  %25 = icmp slt i32 %23, %13
+  br i1 %25, label %22, label %26
+
+; <label>:26:                                     ; preds = %22
+  %27 = sext i32 %23 to i64
+  %28 = tail call i8* @realloc(i8* %18, i64 %27) #12
+  %29 = icmp eq i8* %28, null
+  br i1 %29, label %30, label %31
+
+; <label>:30:                                     ; preds = %26
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.14, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:31:                                     ; preds = %26
+  store i8* %28, i8** %17, align 8
+  store i32 %23, i32* %14, align 4
+  br label %32
+
+; <label>:32:                                     ; preds = %31, %7
+  %33 = phi i8* [ %28, %31 ], [ %18, %7 ]
+  %34 = sext i32 %11 to i64
+  %35 = getelementptr inbounds i8, i8* %33, i64 %34
+  %36 = tail call i8* @strcpy(i8* %35, i8* nonnull %1) #12
+  %37 = add nsw i32 %11, %9
+  store i32 %37, i32* %10, align 8
+  br label %38
+
+; <label>:38:                                     ; preds = %32, %5
+  ret %struct.StringBuilder* %0
+}
+
+; Function Attrs: nounwind
+declare noalias i8* @realloc(i8* nocapture, i64) local_unnamed_addr #5
+
+; Function Attrs: sspstrong uwtable
+define dso_local i8* @_bltn_string_builder_to_string(%struct.StringBuilder*) local_unnamed_addr #0 {
+  %2 = icmp eq %struct.StringBuilder* %0, null
+  br i1 %2, label %3, label %4
+
+; <label>:3:                                      ; preds = %1
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([33 x i8], [33 x i8]* @.str.17, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:4:                                      ; preds = %1
+  %5 = getelementptr inbounds %struct.StringBuilder, %struct.StringBuilder* %0, i64 0, i32 2
+  %6 = load i32, i32* %5, align 8
+  %7 = add nsw i32 %6, 1
+  %8 = sext i32 %7 to i64
+  %9 = tail call noalias i8* @malloc(i64 %8) #12
+  %10 = icmp eq i8* %9, null
+  br i1 %10, label %11, label %12
+
+; <label>:11:                                     ; preds = %4
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.14, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:12:                                     ; preds = %4
+  store i8 0, i8* %9, align 1
+  %13 = getelementptr inbounds %struct.StringBuilder, %struct.StringBuilder* %0, i64 0, i32 1
+  %14 = load i8*, i8** %13, align 8
   %15 = icmp eq i8* %14, null
-  br i1 %15, label %16, label %17
+  br i1 %15, label %18, label %16
+
+; <label>:16:                                     ; preds = %12
+  %17 = tail call i8* @strcpy(i8* nonnull %9, i8* nonnull %14) #12
+  br label %18
+
+; <label>:18:                                     ; preds = %16, %12
+  ret i8* %9
+}
+
+; Function Attrs: sspstrong uwtable
+define internal fastcc void @list_reserve(%struct.List*, i32) unnamed_addr #0 {
+  %3 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 2
+  %4 = load i32, i32* %3, align 8
+  %5 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 3
+  %6 = load i32, i32* %5, align 4
+  %7 = icmp slt i32 %4, %6
+  br i1 %7, label %21, label %8
+
+; <label>:8:                                      ; preds = %2
+  %9 = icmp sgt i32 %6, 0
+  %10 = shl nsw i32 %6, 1
+  %11 = select i1 %9, i32 %10, i32 8
+  %12 = sext i32 %11 to i64
+  %13 = sext i32 %1 to i64
+  %14 = mul nsw i64 %13, %12
+  %15 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 1
+  %16 = load i8*, i8** %15, align 8
+  %17 = tail call i8* @realloc(i8* %16, i64 %14) #12
+  %18 = icmp eq i8* %17, null
+  br i1 %18, label %19, label %20
+
+; <label>:19:                                     ; preds = %8
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.14, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:20:                                     ; preds = %8
+  store i8* %17, i8** %15, align 8
+  store i32 %11, i32* %5, align 4
+  br label %21
+
+; <label>:21:                                     ; preds = %2, %20
+  ret void
+}
+
+; Function Attrs: sspstrong uwtable
+define internal fastcc void @list_check_index(%struct.List*, i32) unnamed_addr #0 {
+  %3 = icmp eq %struct.List* %0, null
+  %4 = icmp slt i32 %1, 0
+  %5 = or i1 %3, %4
+  br i1 %5, label %10, label %6
+
+; <label>:6:                                      ; preds = %2
+  %7 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 2
+  %8 = load i32, i32* %7, align 8
+  %9 = icmp slt i32 %1, %8
+  br i1 %9, label %11, label %10
+
+; <label>:10:                                     ; preds = %6, %2
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([25 x i8], [25 x i8]* @.str.18, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:11:                                     ; preds = %6
+  ret void
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local void @_bltn_int_list_push(%struct.List*, i32) local_unnamed_addr #0 {
+  %3 = icmp eq %struct.List* %0, null
+  br i1 %3, label %4, label %5
+
+; <label>:4:                                      ; preds = %2
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([24 x i8], [24 x i8]* @.str.19, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:5:                                      ; preds = %2
+  tail call fastcc void @list_reserve(%struct.List* nonnull %0, i32 4)
+  %6 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 1
+  %7 = bitcast i8** %6 to i32**
+  %8 = load i32*, i32** %7, align 8
+  %9 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 2
+  %10 = load i32, i32* %9, align 8
+  %11 = add nsw i32 %10, 1
+  store i32 %11, i32* %9, align 8
+  %12 = sext i32 %10 to i64
+  %13 = getelementptr inbounds i32, i32* %8, i64 %12
+  store i32 %1, i32* %13, align 4
+  ret void
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i32 @_bltn_int_list_get(%struct.List*, i32) local_unnamed_addr #0 {
+  tail call fastcc void @list_check_index(%struct.List* %0, i32 %1)
+  %3 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 1
+  %4 = bitcast i8** %3 to i32**
+  %5 = load i32*, i32** %4, align 8
+  %6 = sext i32 %1 to i64
+  %7 = getelementptr inbounds i32, i32* %5, i64 %6
+  %8 = load i32, i32* %7, align 4
+  ret i32 %8
+}
 
-; <label>:16:                                     ; preds = %11
-  tail call void @error() #9
+; Function Attrs: sspstrong uwtable
+define dso_local void @_bltn_int_list_set(%struct.List*, i32, i32) local_unnamed_addr #0 {
+  tail call fastcc void @list_check_index(%struct.List* %0, i32 %1)
+  %4 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 1
+  %5 = bitcast i8** %4 to i32**
+  %6 = load i32*, i32** %5, align 8
+  %7 = sext i32 %1 to i64
+  %8 = getelementptr inbounds i32, i32* %6, i64 %7
+  store i32 %2, i32* %8, align 4
+  ret void
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i32 @_bltn_int_list_size(%struct.List*) local_unnamed_addr #0 {
+  %2 = icmp eq %struct.List* %0, null
+  br i1 %2, label %3, label %4
+
+; <label>:3:                                      ; preds = %1
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([24 x i8], [24 x i8]* @.str.19, i64 0, i64 0)) #10
   unreachable
 
-; <label>:17:                                     ; preds = %11
-  %18 = tail call i8* @memset(i8* nonnull %14, i32 0, i64 %13) #12
-  %19 = bitcast i8* %14 to i32*
-  store i32 %0, i32* %19, align 4, !tbaa !11
-  %20 = getelementptr inbounds i8, i8* %14, i64 4
-  ret i8* %20
+; <label>:4:                                      ; preds = %1
+  %5 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 2
+  %6 = load i32, i32* %5, align 8
+  ret i32 %6
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local void @_bltn_string_list_push(%struct.List*, i8*) local_unnamed_addr #0 {
+  %3 = icmp eq %struct.List* %0, null
+  br i1 %3, label %4, label %5
+
+; <label>:4:                                      ; preds = %2
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([24 x i8], [24 x i8]* @.str.19, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:5:                                      ; preds = %2
+  tail call fastcc void @list_reserve(%struct.List* nonnull %0, i32 8)
+  %6 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 1
+  %7 = bitcast i8** %6 to i8***
+  %8 = load i8**, i8*** %7, align 8
+  %9 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 2
+  %10 = load i32, i32* %9, align 8
+  %11 = add nsw i32 %10, 1
+  store i32 %11, i32* %9, align 8
+  %12 = sext i32 %10 to i64
+  %13 = getelementptr inbounds i8*, i8** %8, i64 %12
+  store i8* %1, i8** %13, align 8
+  ret void
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i8* @_bltn_string_list_get(%struct.List*, i32) local_unnamed_addr #0 {
+  tail call fastcc void @list_check_index(%struct.List* %0, i32 %1)
+  %3 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 1
+  %4 = bitcast i8** %3 to i8***
+  %5 = load i8**, i8*** %4, align 8
+  %6 = sext i32 %1 to i64
+  %7 = getelementptr inbounds i8*, i8** %5, i64 %6
+  %8 = load i8*, i8** %7, align 8
+  ret i8* %8
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local void @_bltn_string_list_set(%struct.List*, i32, i8*) local_unnamed_addr #0 {
+  tail call fastcc void @list_check_index(%struct.List* %0, i32 %1)
+  %4 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 1
+  %5 = bitcast i8** %4 to i8***
+  %6 = load i8**, i8*** %5, align 8
+  %7 = sext i32 %1 to i64
+  %8 = getelementptr inbounds i8*, i8** %6, i64 %7
+  store i8* %2, i8** %8, align 8
+  ret void
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i32 @_bltn_string_list_size(%struct.List*) local_unnamed_addr #0 {
+  %2 = icmp eq %struct.List* %0, null
+  br i1 %2, label %3, label %4
+
+; <label>:3:                                      ; preds = %1
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([24 x i8], [24 x i8]* @.str.19, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:4:                                      ; preds = %1
+  %5 = getelementptr inbounds %struct.List, %struct.List* %0, i64 0, i32 2
+  %6 = load i32, i32* %5, align 8
+  ret i32 %6
+}
+
+; Function Attrs: nounwind readonly sspstrong uwtable
+define internal fastcc i32 @map_hash(i8* %key) unnamed_addr #8 {
+entry:
+  %key_null = icmp eq i8* %key, null
+  %str = select i1 %key_null, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.2, i64 0, i64 0), i8* %key
+  br label %loop
+
+loop:
+  %hash = phi i32 [ -2128831035, %entry ], [ %hash_next, %next ]
+  %c = phi i8* [ %str, %entry ], [ %c_next, %next ]
+  %ch = load i8, i8* %c, align 1, !tbaa !10
+  %end = icmp eq i8 %ch, 0
+  br i1 %end, label %done, label %next
+
+next:
+  %ch_ext = zext i8 %ch to i32
+  %mixed = xor i32 %hash, %ch_ext
+  %hash_next = mul i32 %mixed, 16777619
+  %c_next = getelementptr inbounds i8, i8* %c, i64 1
+  br label %loop
+
+done:
+  ret i32 %hash
+}
+
+; Function Attrs: sspstrong uwtable
+define internal fastcc i32 @map_find(%struct.Map* %map, i8* %key, i32* nocapture %slot) unnamed_addr #0 {
+entry:
+  %map_null = icmp eq %struct.Map* %map, null
+  br i1 %map_null, label %null_error, label %check_cap
+
+null_error:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([23 x i8], [23 x i8]* @.str.29, i64 0, i64 0)) #10
+  unreachable
+
+check_cap:
+  %cap_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 3
+  %cap = load i32, i32* %cap_ptr, align 4, !tbaa !11
+  %no_table = icmp eq i32 %cap, 0
+  br i1 %no_table, label %done, label %search
+
+search:
+  %data_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 1
+  %data = load i8*, i8** %data_ptr, align 8, !tbaa !4
+  %entries = bitcast i8* %data to %struct.MapEntry*
+  %cap_ext = sext i32 %cap to i64
+  %entries_size = shl nsw i64 %cap_ext, 4
+  %slots_raw = getelementptr inbounds i8, i8* %data, i64 %entries_size
+  %slots = bitcast i8* %slots_raw to i32*
+  %slot_cnt = shl i32 %cap, 1
+  %mask = add i32 %slot_cnt, -1
+  %key_null = icmp eq i8* %key, null
+  %str = select i1 %key_null, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.2, i64 0, i64 0), i8* %key
+  %hash = tail call fastcc i32 @map_hash(i8* %str)
+  %first = and i32 %hash, %mask
+  br label %probe
+
+probe:
+  %i = phi i32 [ %first, %search ], [ %i_next, %probe_next ]
+  %i_ext = zext i32 %i to i64
+  %slot_ptr = getelementptr inbounds i32, i32* %slots, i64 %i_ext
+  %entry_num = load i32, i32* %slot_ptr, align 4, !tbaa !11
+  %empty_slot = icmp eq i32 %entry_num, 0
+  br i1 %empty_slot, label %not_found, label %compare
+
+compare:
+  %e = add nsw i32 %entry_num, -1
+  %e_ext = sext i32 %e to i64
+  %other_ptr = getelementptr inbounds %struct.MapEntry, %struct.MapEntry* %entries, i64 %e_ext, i32 0
+  %other = load i8*, i8** %other_ptr, align 8, !tbaa !4
+  %other_null = icmp eq i8* %other, null
+  %other_str = select i1 %other_null, i8* getelementptr inbounds ([1 x i8], [1 x i8]* @.str.2, i64 0, i64 0), i8* %other
+  %cmp = tail call i32 @strcmp(i8* %other_str, i8* %str) #13
+  %same = icmp eq i32 %cmp, 0
+  br i1 %same, label %found, label %probe_next
+
+probe_next:
+  %i_inc = add i32 %i, 1
+  %i_next = and i32 %i_inc, %mask
+  br label %probe
+
+found:
+  store i32 %i, i32* %slot, align 4, !tbaa !11
+  br label %done
+
+not_found:
+  store i32 %i, i32* %slot, align 4, !tbaa !11
+  br label %done
+
+done:
+  %res = phi i32 [ -1, %check_cap ], [ %e, %found ], [ -1, %not_found ]
+  ret i32 %res
+}
+
+; Function Attrs: sspstrong uwtable
+define internal fastcc void @map_grow(%struct.Map* %map) unnamed_addr #0 {
+entry:
+  %cap_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 3
+  %cap = load i32, i32* %cap_ptr, align 4, !tbaa !11
+  %has_cap = icmp sgt i32 %cap, 0
+  %doubled = shl nsw i32 %cap, 1
+  %new_cap = select i1 %has_cap, i32 %doubled, i32 8
+  %new_cap_ext = sext i32 %new_cap to i64
+  %entries_size = shl nsw i64 %new_cap_ext, 4
+  %slots_size = shl nsw i64 %new_cap_ext, 3
+  %size = add nsw i64 %entries_size, %slots_size
+  %new_data = tail call noalias i8* @calloc(i64 %size, i64 1) #12
+  %no_mem = icmp eq i8* %new_data, null
+  br i1 %no_mem, label %oom, label %copy_start
+
+oom:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.14, i64 0, i64 0)) #10
+  unreachable
+
+copy_start:
+  %size_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 2
+  %map_size = load i32, i32* %size_ptr, align 8, !tbaa !11
+  %data_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 1
+  %old_data = load i8*, i8** %data_ptr, align 8, !tbaa !4
+  %non_empty = icmp sgt i32 %map_size, 0
+  br i1 %non_empty, label %copy, label %replace
+
+copy:
+  %map_size_ext = zext i32 %map_size to i64
+  %copied_size = shl nuw nsw i64 %map_size_ext, 4
+  %copied = tail call i8* @memcpy(i8* nonnull %new_data, i8* %old_data, i64 %copied_size) #12
+  br label %replace
+
+replace:
+  tail call void @free(i8* %old_data) #12
+  store i8* %new_data, i8** %data_ptr, align 8, !tbaa !4
+  store i32 %new_cap, i32* %cap_ptr, align 4, !tbaa !11
+  %entries = bitcast i8* %new_data to %struct.MapEntry*
+  %slots_raw = getelementptr inbounds i8, i8* %new_data, i64 %entries_size
+  %slots = bitcast i8* %slots_raw to i32*
+  %slot_cnt = shl i32 %new_cap, 1
+  %mask = add i32 %slot_cnt, -1
+  br label %rehash
+
+rehash:
+  %e = phi i32 [ 0, %replace ], [ %e_next, %insert ]
+  %more = icmp slt i32 %e, %map_size
+  br i1 %more, label %hash_entry, label %done
+
+hash_entry:
+  %e_ext = sext i32 %e to i64
+  %key_ptr = getelementptr inbounds %struct.MapEntry, %struct.MapEntry* %entries, i64 %e_ext, i32 0
+  %key = load i8*, i8** %key_ptr, align 8, !tbaa !4
+  %hash = tail call fastcc i32 @map_hash(i8* %key)
+  %first = and i32 %hash, %mask
+  br label %probe
+
+probe:
+  %i = phi i32 [ %first, %hash_entry ], [ %i_next, %probe_next ]
+  %i_ext = zext i32 %i to i64
+  %slot_ptr = getelementptr inbounds i32, i32* %slots, i64 %i_ext
+  %used = load i32, i32* %slot_ptr, align 4, !tbaa !11
+  %empty_slot = icmp eq i32 %used, 0
+  br i1 %empty_slot, label %insert, label %probe_next
+
+probe_next:
+  %i_inc = add i32 %i, 1
+  %i_next = and i32 %i_inc, %mask
+  br label %probe
+
+insert:
+  %e_next = add nsw i32 %e, 1
+  store i32 %e_next, i32* %slot_ptr, align 4, !tbaa !11
+  br label %rehash
+
+done:
+  ret void
+}
+
+; Function Attrs: nounwind
+declare noalias i8* @calloc(i64, i64) local_unnamed_addr #5
+
+; Function Attrs: sspstrong uwtable
+define dso_local void @_bltn_string_int_map_put(%struct.Map* %map, i8* %key, i32 %value) local_unnamed_addr #0 {
+entry:
+  %slot = alloca i32, align 4
+  %found = call fastcc i32 @map_find(%struct.Map* %map, i8* %key, i32* nonnull %slot)
+  %missing = icmp slt i32 %found, 0
+  br i1 %missing, label %check_full, label %set
+
+check_full:
+  %size_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 2
+  %size = load i32, i32* %size_ptr, align 8, !tbaa !11
+  %cap_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 3
+  %cap = load i32, i32* %cap_ptr, align 4, !tbaa !11
+  %full = icmp eq i32 %size, %cap
+  br i1 %full, label %grow, label %add
+
+grow:
+  call fastcc void @map_grow(%struct.Map* nonnull %map)
+  %refound = call fastcc i32 @map_find(%struct.Map* nonnull %map, i8* %key, i32* nonnull %slot)
+  br label %add
+
+add:
+  %new_size = add nsw i32 %size, 1
+  store i32 %new_size, i32* %size_ptr, align 8, !tbaa !11
+  %data_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 1
+  %data = load i8*, i8** %data_ptr, align 8, !tbaa !4
+  %entries = bitcast i8* %data to %struct.MapEntry*
+  %size_ext = sext i32 %size to i64
+  %key_ptr = getelementptr inbounds %struct.MapEntry, %struct.MapEntry* %entries, i64 %size_ext, i32 0
+  store i8* %key, i8** %key_ptr, align 8, !tbaa !4
+  %table_cap = load i32, i32* %cap_ptr, align 4, !tbaa !11
+  %table_cap_ext = sext i32 %table_cap to i64
+  %entries_size = shl nsw i64 %table_cap_ext, 4
+  %slots_raw = getelementptr inbounds i8, i8* %data, i64 %entries_size
+  %slots = bitcast i8* %slots_raw to i32*
+  %slot_num = load i32, i32* %slot, align 4, !tbaa !11
+  %slot_ext = zext i32 %slot_num to i64
+  %slot_ptr = getelementptr inbounds i32, i32* %slots, i64 %slot_ext
+  store i32 %new_size, i32* %slot_ptr, align 4, !tbaa !11
+  br label %set
+
+set:
+  %e = phi i32 [ %found, %entry ], [ %size, %add ]
+  %set_data_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 1
+  %set_data = load i8*, i8** %set_data_ptr, align 8, !tbaa !4
+  %set_entries = bitcast i8* %set_data to %struct.MapEntry*
+  %e_ext = sext i32 %e to i64
+  %value_ptr = getelementptr inbounds %struct.MapEntry, %struct.MapEntry* %set_entries, i64 %e_ext, i32 1
+  store i32 %value, i32* %value_ptr, align 8, !tbaa !11
+  ret void
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i32 @_bltn_string_int_map_get(%struct.Map* %map, i8* %key) local_unnamed_addr #0 {
+entry:
+  %slot = alloca i32, align 4
+  %e = call fastcc i32 @map_find(%struct.Map* %map, i8* %key, i32* nonnull %slot)
+  %missing = icmp slt i32 %e, 0
+  br i1 %missing, label %not_found, label %found
+
+not_found:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([18 x i8], [18 x i8]* @.str.30, i64 0, i64 0)) #10
+  unreachable
+
+found:
+  %data_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 1
+  %data = load i8*, i8** %data_ptr, align 8, !tbaa !4
+  %entries = bitcast i8* %data to %struct.MapEntry*
+  %e_ext = sext i32 %e to i64
+  %value_ptr = getelementptr inbounds %struct.MapEntry, %struct.MapEntry* %entries, i64 %e_ext, i32 1
+  %value = load i32, i32* %value_ptr, align 8, !tbaa !11
+  ret i32 %value
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local zeroext i1 @_bltn_string_int_map_contains(%struct.Map* %map, i8* %key) local_unnamed_addr #0 {
+entry:
+  %slot = alloca i32, align 4
+  %e = call fastcc i32 @map_find(%struct.Map* %map, i8* %key, i32* nonnull %slot)
+  %found = icmp sgt i32 %e, -1
+  ret i1 %found
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i32 @_bltn_string_int_map_size(%struct.Map* %map) local_unnamed_addr #0 {
+entry:
+  %map_null = icmp eq %struct.Map* %map, null
+  br i1 %map_null, label %null_error, label %ok
+
+null_error:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([23 x i8], [23 x i8]* @.str.29, i64 0, i64 0)) #10
+  unreachable
+
+ok:
+  %size_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 2
+  %size = load i32, i32* %size_ptr, align 8, !tbaa !11
+  ret i32 %size
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i8** @_bltn_string_int_map_keys(%struct.Map* %map) local_unnamed_addr #0 {
+entry:
+  %map_null = icmp eq %struct.Map* %map, null
+  br i1 %map_null, label %null_error, label %alloc
+
+null_error:
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([23 x i8], [23 x i8]* @.str.29, i64 0, i64 0)) #10
+  unreachable
+
+alloc:
+  %size_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 2
+  %size = load i32, i32* %size_ptr, align 8, !tbaa !11
+  %size_ext = sext i32 %size to i64
+  %keys_size = shl nsw i64 %size_ext, 3
+  %alloc_size = add nsw i64 %keys_size, 4
+  %raw = tail call i8* @_bltn_malloc(i64 %alloc_size)
+  %header = bitcast i8* %raw to i32*
+  store i32 %size, i32* %header, align 4, !tbaa !11
+  %keys_raw = getelementptr inbounds i8, i8* %raw, i64 4
+  %keys = bitcast i8* %keys_raw to i8**
+  %data_ptr = getelementptr inbounds %struct.Map, %struct.Map* %map, i64 0, i32 1
+  %data = load i8*, i8** %data_ptr, align 8, !tbaa !4
+  %entries = bitcast i8* %data to %struct.MapEntry*
+  br label %loop
+
+loop:
+  %e = phi i64 [ 0, %alloc ], [ %e_next, %copy ]
+  %more = icmp slt i64 %e, %size_ext
+  br i1 %more, label %copy, label %done
+
+copy:
+  %key_ptr = getelementptr inbounds %struct.MapEntry, %struct.MapEntry* %entries, i64 %e, i32 0
+  %key = load i8*, i8** %key_ptr, align 8, !tbaa !4
+  %dst = getelementptr inbounds i8*, i8** %keys, i64 %e
+  store i8* %key, i8** %dst, align 4, !tbaa !4
+  %e_next = add nsw i64 %e, 1
+  br label %loop
+
+done:
+  ret i8** %keys
 }
 
 declare i64 @__getdelim(i8**, i64*, i32, %struct._IO_FILE*) local_unnamed_addr #1
@@ -422,6 +1732,43 @@ declare i64 @__getdelim(i8**, i64*, i32, %struct._IO_FILE*) local_unnamed_addr #
 ; Function Attrs: nounwind
 declare i64 @strtol(i8*, i8**, i32) local_unnamed_addr #5
 
+; Function Attrs: norecurse nounwind readnone sspstrong uwtable
+define dso_local zeroext i1 @_bltn_object_equals(%struct.Object* readnone, %struct.Object* readnone) local_unnamed_addr #15 {
+  %3 = icmp eq %struct.Object* %0, %1
+  ret i1 %3
+}
+
+; Function Attrs: norecurse nounwind readnone sspstrong uwtable
+define dso_local i32 @_bltn_object_hash_code(%struct.Object*) local_unnamed_addr #15 {
+  %2 = ptrtoint %struct.Object* %0 to i64
+  %3 = lshr i64 %2, 32
+  %4 = xor i64 %3, %2
+  %5 = trunc i64 %4 to i32
+  ret i32 %5
+}
+
+; Function Attrs: sspstrong uwtable
+define dso_local i8* @_bltn_object_to_string(%struct.Object*) local_unnamed_addr #0 {
+  %2 = tail call noalias i8* @malloc(i64 32) #12
+  %3 = icmp eq i8* %2, null
+  br i1 %3, label %4, label %5
+
+; <label>:4:                                      ; preds = %1
+  tail call fastcc void @_ZL13runtime_errorPKc(i8* getelementptr inbounds ([14 x i8], [14 x i8]* @.str.14, i64 0, i64 0)) #10
+  unreachable
+
+; <label>:5:                                      ; preds = %1
+  %6 = ptrtoint %struct.Object* %0 to i64
+  %7 = lshr i64 %6, 32
+  %8 = xor i64 %7, %6
+  %9 = trunc i64 %8 to i32
+  %10 = tail call i32 (i8*, i8*, ...) @sprintf(i8* nonnull %2, i8* getelementptr inbounds ([10 x i8], [10 x i8]* @.str.5, i64 0, i64 0), i32 %9) #12
+  ret i8* %2
+}
+
+; Function Attrs: nounwind
+declare i32 @sprintf(i8*, i8*, ...) local_unnamed_addr #5
+
 attributes #0 = { sspstrong uwtable "correctly-rounded-divide-sqrt-fp-math"="false" "disable-tail-calls"="false" "less-precise-fpmad"="false" "no-frame-pointer-elim"="false" "no-infs-fp-math"="false" "no-jump-tables"="false" "no-nans-fp-math"="false" "no-signed-zeros-fp-math"="false" "no-trapping-math"="false" "stack-protector-buffer-size"="8" "target-cpu"="x86-64" "target-features"="+fxsr,+mmx,+sse,+sse2,+x87" "unsafe-fp-math"="false" "use-soft-float"="false" }
 attributes #1 = { "correctly-rounded-divide-sqrt-fp-math"="false" "disable-tail-calls"="false" "less-precise-fpmad"="false" "no-frame-pointer-elim"="false" "no-infs-fp-math"="false" "no-nans-fp-math"="false" "no-signed-zeros-fp-math"="false" "no-trapping-math"="false" "stack-protector-buffer-size"="8" "target-cpu"="x86-64" "target-features"="+fxsr,+mmx,+sse,+sse2,+x87" "unsafe-fp-math"="false" "use-soft-float"="false" }
 attributes #2 = { noreturn sspstrong uwtable "correctly-rounded-divide-sqrt-fp-math"="false" "disable-tail-calls"="false" "less-precise-fpmad"="false" "no-frame-pointer-elim"="false" "no-infs-fp-math"="false" "no-jump-tables"="false" "no-nans-fp-math"="false" "no-signed-zeros-fp-math"="false" "no-trapping-math"="false" "stack-protector-buffer-size"="8" "target-cpu"="x86-64" "target-features"="+fxsr,+mmx,+sse,+sse2,+x87" "unsafe-fp-math"="false" "use-soft-float"="false" }
@@ -436,6 +1783,8 @@ attributes #10 = { nobuiltin noreturn nounwind }
 attributes #11 = { nounwind }
 attributes #12 = { nobuiltin nounwind }
 attributes #13 = { nobuiltin nounwind readonly }
+attributes #14 = { nounwind readnone }
+attributes #15 = { norecurse nounwind readnone sspstrong uwtable "correctly-rounded-divide-sqrt-fp-math"="false" "disable-tail-calls"="false" "less-precise-fpmad"="false" "no-frame-pointer-elim"="false" "no-infs-fp-math"="false" "no-jump-tables"="false" "no-nans-fp-math"="false" "no-signed-zeros-fp-math"="false" "no-trapping-math"="false" "stack-protector-buffer-size"="8" "target-cpu"="x86-64" "target-features"="+fxsr,+mmx,+sse,+sse2,+x87" "unsafe-fp-math"="false" "use-soft-float"="false" }
 
 !llvm.module.flags = !{!0, !1, !2}
 !llvm.ident = !{!3}
@@ -453,3 +1802,5 @@ attributes #13 = { nobuiltin nounwind readonly }
 !10 = !{!6, !6, i64 0}
 !11 = !{!12, !12, i64 0}
 !12 = !{!"int", !6, i64 0}
+!13 = !{!14, !14, i64 0}
+!14 = !{!"long long", !6, i64 0}
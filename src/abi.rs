@@ -0,0 +1,295 @@
+use model::ir::{get_runtime_functions, Program, Type, TypeKind};
+use options::{ReportFormat, Target};
+
+// Calling conventions of the targets of a native backend (System V on
+// x86-64, AAPCS64 on AArch64, LP64D on RISC-V), shared by the register
+// allocator and the lowering of calls, e.g. of the functions of the C
+// runtime. The values of the IR are integers, pointers and structs of them
+// (no floats), so every argument is passed in whole 8-byte words and
+// a register holds one word. Structs of at most two words are passed in
+// registers, bigger ones in memory: copied to the stack on x86-64, by
+// reference to a copy made by the caller on AArch64 and RISC-V.
+
+pub struct Abi {
+    pub arg_regs: &'static [&'static str],
+    pub ret_regs: &'static [&'static str], // a struct of two words uses both
+    // the address of a big struct result, allocated by the caller
+    pub indirect_ret_reg: &'static str,
+    // the address takes the first argument register (x86-64)
+    pub indirect_ret_is_arg: bool,
+    // registers available to the register allocator
+    pub caller_saved: &'static [&'static str],
+    pub callee_saved: &'static [&'static str],
+    // left for loading spilled values and for call sequences
+    pub scratch: &'static [&'static str],
+    pub stack_alignment: i64, // at calls
+    pub max_struct_in_regs: i64,
+    pub big_structs_by_reference: bool,
+    // a struct which doesn't fit the remaining argument registers moves
+    // the following arguments to the stack too (AArch64)
+    pub stack_struct_ends_regs: bool,
+    // or is split between the last register and the stack (RISC-V)
+    pub split_structs: bool,
+}
+
+pub static X86_64: Abi = Abi {
+    arg_regs: &["rdi", "rsi", "rdx", "rcx", "r8", "r9"],
+    ret_regs: &["rax", "rdx"],
+    indirect_ret_reg: "rdi",
+    indirect_ret_is_arg: true,
+    caller_saved: &["rax", "rcx", "rdx", "rsi", "rdi", "r8", "r9"],
+    callee_saved: &["rbx", "r12", "r13", "r14", "r15"],
+    scratch: &["r10", "r11"],
+    stack_alignment: 16,
+    max_struct_in_regs: 16,
+    big_structs_by_reference: false,
+    stack_struct_ends_regs: false,
+    split_structs: false,
+};
+
+// x18 is reserved by some platforms, x29 and x30 are the frame pointer
+// and the link register
+pub static AARCH64: Abi = Abi {
+    arg_regs: &["x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7"],
+    ret_regs: &["x0", "x1"],
+    indirect_ret_reg: "x8",
+    indirect_ret_is_arg: false,
+    caller_saved: &[
+        "x0", "x1", "x2", "x3", "x4", "x5", "x6", "x7", "x8", "x9", "x10", "x11", "x12", "x13",
+        "x14", "x15",
+    ],
+    callee_saved: &[
+        "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28",
+    ],
+    scratch: &["x16", "x17"],
+    stack_alignment: 16,
+    max_struct_in_regs: 16,
+    big_structs_by_reference: true,
+    stack_struct_ends_regs: true,
+    split_structs: false,
+};
+
+// s0 is the frame pointer, t5 and t6 are left as scratch registers; gp and
+// tp aren't touched
+pub static RISCV64: Abi = Abi {
+    arg_regs: &["a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7"],
+    ret_regs: &["a0", "a1"],
+    indirect_ret_reg: "a0",
+    indirect_ret_is_arg: true,
+    caller_saved: &[
+        "t0", "t1", "t2", "t3", "t4", "a0", "a1", "a2", "a3", "a4", "a5", "a6", "a7",
+    ],
+    callee_saved: &[
+        "s1", "s2", "s3", "s4", "s5", "s6", "s7", "s8", "s9", "s10", "s11",
+    ],
+    scratch: &["t5", "t6"],
+    stack_alignment: 16,
+    max_struct_in_regs: 16,
+    big_structs_by_reference: true,
+    stack_struct_ends_regs: false,
+    split_structs: true,
+};
+
+impl Abi {
+    pub fn for_target(target: Target) -> &'static Abi {
+        match target {
+            Target::X86_64 => &X86_64,
+            Target::AArch64 => &AARCH64,
+            Target::RiscV64 => &RISCV64,
+        }
+    }
+
+    pub fn lower_call(&self, prog: &Program, ret_type: &Type, arg_types: &[Type]) -> CallLowering {
+        let mut next_reg = 0;
+        let mut stack_bytes = 0;
+        let ret = match *ret_type {
+            Type::Void => RetPassing::Void,
+            _ if get_size(prog, ret_type) <= self.max_struct_in_regs => {
+                let words = get_words(prog, ret_type);
+                RetPassing::Regs(self.ret_regs[..words].to_vec())
+            }
+            _ => {
+                if self.indirect_ret_is_arg {
+                    next_reg += 1;
+                }
+                RetPassing::Indirect(self.indirect_ret_reg)
+            }
+        };
+        let mut take_stack_words = |words: usize| -> Vec<Slot> {
+            let slots = (0..words)
+                .map(|i| Slot::Stack(stack_bytes + 8 * i as i64))
+                .collect();
+            stack_bytes += 8 * words as i64;
+            slots
+        };
+        let mut args = vec![];
+        for arg_type in arg_types {
+            let words = get_words(prog, arg_type);
+            let in_memory = get_size(prog, arg_type) > self.max_struct_in_regs;
+            let passing = if in_memory && self.big_structs_by_reference {
+                // the address is passed like a pointer
+                match self.arg_regs.get(next_reg) {
+                    Some(reg) => {
+                        next_reg += 1;
+                        ArgPassing::Reference(Slot::Reg(reg))
+                    }
+                    None => ArgPassing::Reference(take_stack_words(1)[0]),
+                }
+            } else if in_memory {
+                ArgPassing::Words(take_stack_words(words))
+            } else if next_reg + words <= self.arg_regs.len() {
+                let regs = &self.arg_regs[next_reg..next_reg + words];
+                next_reg += words;
+                ArgPassing::Words(regs.iter().map(|reg| Slot::Reg(reg)).collect())
+            } else if self.split_structs && next_reg < self.arg_regs.len() {
+                let regs = &self.arg_regs[next_reg..];
+                next_reg = self.arg_regs.len();
+                let mut slots: Vec<_> = regs.iter().map(|reg| Slot::Reg(reg)).collect();
+                slots.extend(take_stack_words(words - slots.len()));
+                ArgPassing::Words(slots)
+            } else {
+                if words > 1 && self.stack_struct_ends_regs {
+                    next_reg = self.arg_regs.len();
+                }
+                ArgPassing::Words(take_stack_words(words))
+            };
+            args.push(passing);
+        }
+        let align = self.stack_alignment;
+        CallLowering {
+            args,
+            ret,
+            stack_bytes: (stack_bytes + align - 1) / align * align,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Slot {
+    Reg(&'static str),
+    Stack(i64), // offset in the outgoing arguments
+}
+
+pub enum ArgPassing {
+    Words(Vec<Slot>),
+    Reference(Slot), // the address of a copy of the value
+}
+
+pub enum RetPassing {
+    Void,
+    Regs(Vec<&'static str>),
+    Indirect(&'static str), // the register with the address of the result
+}
+
+pub struct CallLowering {
+    pub args: Vec<ArgPassing>,
+    pub ret: RetPassing,
+    pub stack_bytes: i64, // of the outgoing arguments, aligned
+}
+
+fn get_size(prog: &Program, t: &Type) -> i64 {
+    prog.get_size_and_align(t).0
+}
+
+fn get_words(prog: &Program, t: &Type) -> usize {
+    ((get_size(prog, t) + 7) / 8) as usize
+}
+
+// the functions of the C runtime (with the methods of the builtin classes)
+// and of the program
+pub fn format_calls(prog: &Program, abi: &Abi, format: ReportFormat) -> String {
+    let mut functions: Vec<_> = get_runtime_functions(&prog.size_type)
+        .into_iter()
+        .map(|(name, ret_type, arg_types)| (name.to_string(), ret_type, arg_types))
+        .collect();
+    for cl in prog.classes.iter().filter(|cl| cl.is_builtin) {
+        for (method_type, name) in &cl.vtable {
+            if let TypeKind::Ptr(t) = method_type.kind() {
+                if let TypeKind::Func(ret_type, arg_types) = t.kind() {
+                    functions.push((name.clone(), *ret_type, arg_types.clone()));
+                }
+            }
+        }
+    }
+    for fun in &prog.functions {
+        let arg_types = fun.args.iter().map(|(_, t)| *t).collect();
+        functions.push((fun.name.clone(), fun.ret_type, arg_types));
+    }
+    let calls: Vec<_> = functions
+        .iter()
+        .map(|(name, ret_type, arg_types)| {
+            (name.as_str(), abi.lower_call(prog, ret_type, arg_types))
+        })
+        .collect();
+    match format {
+        ReportFormat::Table => format_table(&calls),
+        ReportFormat::Json => format_json(&calls),
+    }
+}
+
+fn format_slot(slot: Slot) -> String {
+    match slot {
+        Slot::Reg(reg) => reg.to_string(),
+        Slot::Stack(offset) => format!("[sp+{}]", offset),
+    }
+}
+
+fn format_arg(arg: &ArgPassing) -> String {
+    match arg {
+        ArgPassing::Words(slots) => {
+            let slots: Vec<_> = slots.iter().map(|slot| format_slot(*slot)).collect();
+            slots.join("+")
+        }
+        ArgPassing::Reference(slot) => format!("&{}", format_slot(*slot)),
+    }
+}
+
+fn format_ret(ret: &RetPassing) -> String {
+    match ret {
+        RetPassing::Void => "void".to_string(),
+        RetPassing::Regs(regs) => regs.join("+"),
+        RetPassing::Indirect(reg) => format!("&{}", reg),
+    }
+}
+
+fn format_table(calls: &[(&str, CallLowering)]) -> String {
+    let name_width = calls.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    let mut res = String::new();
+    for (name, call) in calls {
+        let args: Vec<_> = call.args.iter().map(format_arg).collect();
+        res += &format!(
+            "{:<w$}  args: {}  result: {}  stack bytes: {}\n",
+            name,
+            if args.is_empty() {
+                "none".to_string()
+            } else {
+                args.join(", ")
+            },
+            format_ret(&call.ret),
+            call.stack_bytes,
+            w = name_width
+        );
+    }
+    res
+}
+
+fn format_json(calls: &[(&str, CallLowering)]) -> String {
+    let mut res = "[\n".to_string();
+    for (i, (name, call)) in calls.iter().enumerate() {
+        let name = name.replace("\\", "\\\\").replace("\"", "\\\"");
+        let args: Vec<_> = call
+            .args
+            .iter()
+            .map(|arg| format!("\"{}\"", format_arg(arg)))
+            .collect();
+        res += &format!(
+            "  {{\"function\": \"{}\", \"args\": [{}], \"result\": \"{}\", \"stack_bytes\": {}}}",
+            name,
+            args.join(", "),
+            format_ret(&call.ret),
+            call.stack_bytes
+        );
+        res += if i + 1 < calls.len() { ",\n" } else { "\n" };
+    }
+    res + "]\n"
+}
@@ -0,0 +1,241 @@
+// Benchmarks of the optimizer: compiles the bundled programs (tests/bench)
+// with -O0, -O1 and -O2, runs them and reports the run time and the size of
+// the code (instructions of the printed LLVM IR) of every level, with the
+// change relative to -O0; all the levels have to print the same output.
+// With --interp the programs are run by the interpreter, which runs the AST
+// before the optimizer, so the time is measured once and only the sizes are
+// compared. Native builds need LLVM and gcc and have to be run from the
+// repository directory, like latte-fuzz (the runtime is in lib).
+// usage: latte-bench [--interp] [--runs=N] [--dir=DIR] [BENCHMARKS_DIR]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant};
+
+const OPT_LEVELS: &[&str] = &["-O0", "-O1", "-O2"];
+
+struct Measurement {
+    time: Option<Duration>, // the best of the runs
+    size: usize,
+}
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    let mut interpret = false;
+    let mut runs = 3;
+    let mut dir = PathBuf::from("bench");
+    let mut bench_dir = PathBuf::from("tests/bench");
+    for arg in &args[1..] {
+        if arg == "--interp" {
+            interpret = true;
+        } else if let Some(value) = arg.strip_prefix("--runs=") {
+            runs = match value.parse() {
+                Ok(n) if n > 0 => n,
+                _ => exit_with_usage(&args[0]),
+            };
+        } else if let Some(value) = arg.strip_prefix("--dir=") {
+            dir = PathBuf::from(value);
+        } else if !arg.starts_with('-') {
+            bench_dir = PathBuf::from(arg);
+        } else {
+            exit_with_usage(&args[0]);
+        }
+    }
+    let mut files: Vec<_> = match fs::read_dir(&bench_dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "lat"))
+            .collect(),
+        Err(_) => {
+            eprintln!("Cannot read directory: {}", bench_dir.display());
+            process::exit(1);
+        }
+    };
+    files.sort();
+    // the compiler is built next to this binary
+    let compiler = env::current_exe().unwrap().with_file_name("latte-compiler");
+
+    let mut failures = 0;
+    let mut totals = vec![(Duration::new(0, 0), 0); OPT_LEVELS.len()];
+    println!(
+        "{:<16} {:<5} {:>10} {:>8} {:>8} {:>8}",
+        "benchmark", "level", "time (ms)", "vs -O0", "instrs", "vs -O0"
+    );
+    for file in &files {
+        let name = file.file_stem().unwrap().to_string_lossy().into_owned();
+        let res = if interpret {
+            measure_interpreted(&compiler, file, runs)
+        } else {
+            measure_native(&compiler, file, &dir, runs)
+        };
+        let measurements = match res {
+            Ok(measurements) => measurements,
+            Err(err) => {
+                println!("{:<16} {}", name, err);
+                failures += 1;
+                continue;
+            }
+        };
+        for (i, (level, m)) in OPT_LEVELS.iter().zip(&measurements).enumerate() {
+            let base = &measurements[0];
+            totals[i].0 += m.time.or(base.time).unwrap_or_default();
+            totals[i].1 += m.size;
+            print_row(&name, level, m.time, base.time, m.size, base.size);
+        }
+    }
+    if failures == 0 && !files.is_empty() {
+        for (level, (time, size)) in OPT_LEVELS.iter().zip(&totals) {
+            let time = if interpret { None } else { Some(*time) };
+            print_row("total", level, time, Some(totals[0].0), *size, totals[0].1);
+        }
+    }
+    println!("{} benchmark(s), {} failure(s)", files.len(), failures);
+    if failures > 0 {
+        process::exit(1);
+    }
+}
+
+fn print_row(
+    name: &str,
+    level: &str,
+    time: Option<Duration>,
+    base_time: Option<Duration>,
+    size: usize,
+    base_size: usize,
+) {
+    let (time_str, time_delta) = match (time, base_time) {
+        (Some(time), Some(base_time)) => (
+            format!("{:.1}", time.as_secs_f64() * 1000.0),
+            format_delta(time.as_secs_f64(), base_time.as_secs_f64()),
+        ),
+        _ => ("-".to_string(), String::new()),
+    };
+    println!(
+        "{:<16} {:<5} {:>10} {:>8} {:>8} {:>8}",
+        name,
+        level,
+        time_str,
+        time_delta,
+        size,
+        format_delta(size as f64, base_size as f64)
+    );
+}
+
+// the change relative to the base, empty for the base itself
+fn format_delta(value: f64, base: f64) -> String {
+    if value == base || base == 0.0 {
+        return String::new();
+    }
+    format!("{:+.1}%", (value - base) / base * 100.0)
+}
+
+fn measure_native(
+    compiler: &Path,
+    file: &Path,
+    dir: &Path,
+    runs: u32,
+) -> Result<Vec<Measurement>, String> {
+    let mut res = vec![];
+    let mut expected = None;
+    for level in OPT_LEVELS {
+        let out_dir = dir.join(level[1..].to_lowercase());
+        let status = process::Command::new(compiler)
+            .args(["build", level])
+            .arg(format!("--out-dir={}", out_dir.display()))
+            .arg(file)
+            .output();
+        match status {
+            Ok(out) if out.status.success() => (),
+            _ => return Err(format!("compilation with {} failed", level)),
+        }
+        let exec = out_dir.join(file.file_stem().unwrap());
+        let mut best = None;
+        for _ in 0..runs {
+            let (output, time) = run_timed(process::Command::new(&exec))
+                .map_err(|_| format!("program compiled with {} crashed", level))?;
+            check_output(&mut expected, output, level)?;
+            best = Some(best.map_or(time, |best: Duration| best.min(time)));
+        }
+        res.push(Measurement {
+            time: best,
+            size: get_code_size(compiler, file, level)?,
+        });
+    }
+    Ok(res)
+}
+
+fn measure_interpreted(
+    compiler: &Path,
+    file: &Path,
+    runs: u32,
+) -> Result<Vec<Measurement>, String> {
+    let mut best = None;
+    let mut expected = None;
+    for _ in 0..runs {
+        let mut cmd = process::Command::new(compiler);
+        cmd.args(["run", "--interp"]).arg(file);
+        let (output, time) = run_timed(cmd).map_err(|_| "the interpreter failed".to_string())?;
+        check_output(&mut expected, output, "--interp")?;
+        best = Some(best.map_or(time, |best: Duration| best.min(time)));
+    }
+    let mut res = vec![];
+    for (i, level) in OPT_LEVELS.iter().enumerate() {
+        res.push(Measurement {
+            time: if i == 0 { best } else { None },
+            size: get_code_size(compiler, file, level)?,
+        });
+    }
+    Ok(res)
+}
+
+fn run_timed(mut cmd: process::Command) -> Result<(Vec<u8>, Duration), ()> {
+    let start = Instant::now();
+    match cmd.output() {
+        Ok(out) if out.status.success() => Ok((out.stdout, start.elapsed())),
+        _ => Err(()),
+    }
+}
+
+fn check_output(
+    expected: &mut Option<Vec<u8>>,
+    output: Vec<u8>,
+    level: &str,
+) -> Result<(), String> {
+    match expected {
+        Some(expected) if *expected != output => Err(format!(
+            "output of the program compiled with {} differs",
+            level
+        )),
+        Some(_) => Ok(()),
+        None => {
+            *expected = Some(output);
+            Ok(())
+        }
+    }
+}
+
+// instructions of the functions of the program in the printed LLVM IR
+fn get_code_size(compiler: &Path, file: &Path, level: &str) -> Result<usize, String> {
+    let output = process::Command::new(compiler)
+        .args(["build", "--stdout", level])
+        .arg(file)
+        .output();
+    let ll_code = match output {
+        Ok(out) if out.status.success() => String::from_utf8_lossy(&out.stdout).into_owned(),
+        _ => return Err(format!("compilation with {} failed", level)),
+    };
+    Ok(ll_code
+        .lines()
+        .filter(|line| line.starts_with("    ") && !line.trim().is_empty())
+        .count())
+}
+
+fn exit_with_usage(program: &str) -> ! {
+    eprintln!(
+        "Usage: {} [--interp] [--runs=N] [--dir=DIR] [BENCHMARKS_DIR]",
+        program
+    );
+    process::exit(1);
+}
@@ -0,0 +1,127 @@
+// Runs the tests with FileCheck-like directives (see filecheck.rs): every
+// RUN line of a test file runs the compiler and its output is checked
+// against the directives of the file. Directories are searched for .lat
+// files recursively.
+// usage: latte-filecheck <files or directories...>
+
+extern crate latte_compiler;
+
+use latte_compiler::filecheck::{check_output, get_directives, get_run_lines, CheckError};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{self, Command, Output, Stdio};
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    if args.len() < 2 || args[1..].iter().any(|arg| arg.starts_with('-')) {
+        eprintln!("Usage: {} <files or directories...>", args[0]);
+        process::exit(1);
+    }
+    let mut files = vec![];
+    for arg in &args[1..] {
+        collect_tests(Path::new(arg), &mut files);
+    }
+    // the compiler is built next to this binary
+    let compiler = env::current_exe().unwrap().with_file_name("latte-compiler");
+
+    let mut failures = 0;
+    for file in &files {
+        match run_test(&compiler, file) {
+            Ok(()) => println!("PASS {}", file.display()),
+            Err(err) => {
+                println!("FAIL {}:{}: {}", file.display(), err.line, err.message);
+                failures += 1;
+            }
+        }
+    }
+    println!("{} test(s), {} failure(s)", files.len(), failures);
+    if failures > 0 {
+        process::exit(1);
+    }
+}
+
+fn collect_tests(path: &Path, files: &mut Vec<PathBuf>) {
+    if path.is_dir() {
+        let mut entries: Vec<_> = match fs::read_dir(path) {
+            Ok(entries) => entries.filter_map(|e| e.ok()).map(|e| e.path()).collect(),
+            Err(_) => {
+                eprintln!("Cannot read directory: {}", path.display());
+                process::exit(1);
+            }
+        };
+        entries.sort();
+        for entry in entries {
+            if entry.is_dir() || entry.extension().map(|ext| ext == "lat") == Some(true) {
+                collect_tests(&entry, files);
+            }
+        }
+    } else {
+        files.push(path.to_path_buf());
+    }
+}
+
+fn run_test(compiler: &Path, file: &Path) -> Result<(), CheckError> {
+    let error = |line, message: String| CheckError { line, message };
+    let code =
+        fs::read_to_string(file).map_err(|_| error(0, "cannot read the file".to_string()))?;
+    let run_lines = get_run_lines(&code, &file.to_string_lossy());
+    if run_lines.is_empty() {
+        return Err(error(0, "no RUN lines".to_string()));
+    }
+    for run in run_lines {
+        let directives = get_directives(&code, &run.check_prefix)?;
+        if directives.is_empty() {
+            return Err(error(
+                run.line,
+                format!("no {}: directives", run.check_prefix),
+            ));
+        }
+        let mut output = match Command::new(compiler).args(&run.args).output() {
+            Ok(output) => output,
+            Err(_) => return Err(error(run.line, "cannot run the compiler".to_string())),
+        };
+        let mut stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+        for args in &run.piped_args {
+            if !output.status.success() {
+                break;
+            }
+            output = match run_with_input(compiler, args, &output.stdout) {
+                Ok(output) => output,
+                Err(_) => return Err(error(run.line, "cannot run the compiler".to_string())),
+            };
+            stderr += &String::from_utf8_lossy(&output.stderr);
+        }
+        let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+        text += &stderr;
+        if output.status.success() == run.expect_failure {
+            let expected = if run.expect_failure {
+                "fail"
+            } else {
+                "succeed"
+            };
+            return Err(error(
+                run.line,
+                format!(
+                    "the compiler was expected to {}, its output:\n{}",
+                    expected, text
+                ),
+            ));
+        }
+        check_output(&text, &directives)?;
+    }
+    Ok(())
+}
+
+fn run_with_input(compiler: &Path, args: &[String], input: &[u8]) -> io::Result<Output> {
+    let mut child = Command::new(compiler)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    // the compiler reads the whole input before writing anything
+    child.stdin.take().unwrap().write_all(input)?;
+    child.wait_with_output()
+}
@@ -0,0 +1,445 @@
+// Differential testing of the SSA construction: generates random well-typed
+// programs (lots of nested ifs and loops with shadowing, break and continue,
+// which are the tricky paths of FunctionCodeGen) and compares outputs of
+// the programs compiled with -O0, -O1 and -O2, and of their optimized IR run
+// by the interpreter of the IR (run --interp=ir), with the output of the
+// interpreter of the AST (--interp). Every compilation runs the IR verifier
+// after the codegen and after the optimizer, and the LLVM verifier
+// (--validate-llvm) on the output, so a broken invariant is reported with
+// the message of the verifier.
+// usage: latte-fuzz [--seed=N] [--count=N] [--dir=DIR]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+
+const OPT_LEVELS: &[&str] = &["-O0", "-O1", "-O2"];
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    let mut seed = 1;
+    let mut count = 100;
+    let mut dir = PathBuf::from("fuzz");
+    for arg in &args[1..] {
+        if let Some(value) = arg.strip_prefix("--seed=") {
+            seed = parse_or_exit(value, &args[0]);
+        } else if let Some(value) = arg.strip_prefix("--count=") {
+            count = parse_or_exit(value, &args[0]);
+        } else if let Some(value) = arg.strip_prefix("--dir=") {
+            dir = PathBuf::from(value);
+        } else {
+            exit_with_usage(&args[0]);
+        }
+    }
+    if fs::create_dir_all(&dir).is_err() {
+        eprintln!("Cannot create directory: {}", dir.display());
+        process::exit(1);
+    }
+    // the compiler is built next to this binary
+    let compiler = env::current_exe().unwrap().with_file_name("latte-compiler");
+
+    let mut failures = 0;
+    for s in seed..seed + count {
+        let code = ProgramGenerator::new(s).generate();
+        let mut errors = vec![];
+        let expected = run_interpreter(&compiler, &dir.join(format!("fuzz_{}.lat", s)), &code);
+        for opt_level in OPT_LEVELS {
+            let file = dir.join(format!("fuzz_{}{}.lat", s, opt_level.to_lowercase()));
+            fs::write(&file, &code).unwrap();
+            match (&expected, compile_and_run(&compiler, &file, opt_level)) {
+                (Ok(expected), Ok(output)) if *expected != output => errors.push(format!(
+                    "output of the program compiled with {} differs from the interpreter",
+                    opt_level
+                )),
+                (_, Err(err)) => errors.push(err),
+                _ => (),
+            }
+            match (&expected, run_ir_interpreter(&compiler, &file, opt_level)) {
+                (Ok(expected), Ok(output)) if *expected != output => errors.push(format!(
+                    "output of the IR optimized with {} differs from the interpreter",
+                    opt_level
+                )),
+                (_, Err(err)) => errors.push(err),
+                _ => (),
+            }
+        }
+        errors.extend(expected.err());
+        if errors.is_empty() {
+            let _ = fs::remove_file(dir.join(format!("fuzz_{}.lat", s)));
+            for opt_level in OPT_LEVELS {
+                let base = dir.join(format!("fuzz_{}{}", s, opt_level.to_lowercase()));
+                for ext in &["lat", "ll", "bc", "o", ""] {
+                    let _ = fs::remove_file(base.with_extension(ext));
+                }
+            }
+        } else {
+            failures += 1;
+            for err in errors {
+                println!("seed {}: {}", s, err);
+            }
+        }
+    }
+    println!(
+        "{} program(s) checked, {} failure(s), failing programs are kept in {}",
+        count,
+        failures,
+        dir.display()
+    );
+    if failures > 0 {
+        process::exit(1);
+    }
+}
+
+fn compile_and_run(compiler: &Path, file: &Path, opt_level: &str) -> Result<Vec<u8>, String> {
+    let status = process::Command::new(compiler)
+        .args(["build", "--validate-llvm", opt_level])
+        .arg(file)
+        .output();
+    match status {
+        Ok(out) if out.status.success() => (),
+        // e.g. the internal error of a failed IR verification
+        Ok(out) => {
+            return Err(format!(
+                "compilation with {} failed:\n{}",
+                opt_level,
+                String::from_utf8_lossy(&out.stderr).trim_end()
+            ))
+        }
+        Err(_) => return Err("cannot run the compiler".to_string()),
+    }
+    match process::Command::new(file.with_extension("")).output() {
+        Ok(out) if out.status.success() => Ok(out.stdout),
+        _ => Err(format!("program compiled with {} crashed", opt_level)),
+    }
+}
+
+// the generated programs use only ints and booleans, so the interpreter
+// of the IR supports them
+fn run_ir_interpreter(compiler: &Path, file: &Path, opt_level: &str) -> Result<Vec<u8>, String> {
+    match process::Command::new(compiler)
+        .args(["run", "--interp=ir", opt_level])
+        .arg(file)
+        .output()
+    {
+        Ok(out) if out.status.success() => Ok(out.stdout),
+        Ok(out) => Err(format!(
+            "the IR interpreter failed on the IR optimized with {}:\n{}",
+            opt_level,
+            String::from_utf8_lossy(&out.stderr).trim_end()
+        )),
+        Err(_) => Err("cannot run the compiler".to_string()),
+    }
+}
+
+fn run_interpreter(compiler: &Path, file: &Path, code: &str) -> Result<Vec<u8>, String> {
+    fs::write(file, code).unwrap();
+    match process::Command::new(compiler)
+        .args(["run", "--interp"])
+        .arg(file)
+        .output()
+    {
+        Ok(out) if out.status.success() => Ok(out.stdout),
+        _ => Err("the interpreter failed".to_string()),
+    }
+}
+
+fn parse_or_exit(s: &str, program: &str) -> u64 {
+    match s.parse() {
+        Ok(n) => n,
+        Err(_) => exit_with_usage(program),
+    }
+}
+
+fn exit_with_usage(program: &str) -> ! {
+    eprintln!("Usage: {} [--seed=N] [--count=N] [--dir=DIR]", program);
+    process::exit(1);
+}
+
+// --------------------------------------------------------
+// ----------------- generator ----------------------------
+// --------------------------------------------------------
+
+// values are kept small (below MODULUS), so the arithmetic never overflows
+const MODULUS: i32 = 10007;
+const MAX_DEPTH: u32 = 3;
+const FUNCTIONS_CNT: usize = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+enum VarType {
+    Int,
+    Bool,
+}
+
+struct Var {
+    name: String,
+    var_type: VarType,
+    assignable: bool, // loop counters aren't, so the loops terminate
+}
+
+struct ProgramGenerator {
+    rng: u64,
+    scopes: Vec<Vec<Var>>,
+    next_var: usize,
+    functions: Vec<(String, usize)>, // name and number of arguments
+    loop_depth: u32,
+}
+
+impl ProgramGenerator {
+    fn new(seed: u64) -> Self {
+        ProgramGenerator {
+            // any non-zero state works for xorshift
+            rng: seed.wrapping_mul(0x9e37_79b9_7f4a_7c15) | 1,
+            scopes: vec![],
+            next_var: 0,
+            functions: vec![],
+            loop_depth: 0,
+        }
+    }
+
+    fn next(&mut self, bound: u64) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng % bound
+    }
+
+    fn chance(&mut self, percent: u64) -> bool {
+        self.next(100) < percent
+    }
+
+    fn generate(mut self) -> String {
+        let mut res = String::new();
+        // functions call only the earlier ones, so there is no recursion
+        for i in 0..FUNCTIONS_CNT {
+            let name = format!("f{}", i);
+            let args_cnt = 1 + self.next(3) as usize;
+            let args: Vec<_> = (0..args_cnt).map(|a| format!("a{}", a)).collect();
+            self.scopes = vec![args
+                .iter()
+                .map(|a| Var {
+                    name: a.to_string(),
+                    var_type: VarType::Int,
+                    assignable: true,
+                })
+                .collect()];
+            let args_decl: Vec<_> = args.iter().map(|a| format!("int {}", a)).collect();
+            res += &format!("int {}({}) {{\n", name, args_decl.join(", "));
+            res += &self.gen_stmts(1, 4);
+            res += &format!("    return {};\n}}\n\n", self.gen_int_expr());
+            self.functions.push((name, args_cnt));
+        }
+
+        self.scopes = vec![vec![]];
+        res += "int main() {\n";
+        res += &self.gen_stmts(1, 8);
+        res += &self.gen_print_all(1);
+        res += "    return 0;\n}\n";
+        res
+    }
+
+    fn gen_stmts(&mut self, depth: u32, max_cnt: u64) -> String {
+        let cnt = 1 + self.next(max_cnt);
+        let mut res = String::new();
+        for _ in 0..cnt {
+            res += &self.gen_stmt(depth);
+        }
+        res
+    }
+
+    fn gen_block(&mut self, depth: u32, prefix: &str) -> String {
+        self.scopes.push(vec![]);
+        let mut res = format!("{{\n{}", prefix);
+        res += &self.gen_stmts(depth + 1, 4);
+        res += &format!("{}}}", indent(depth));
+        self.scopes.pop();
+        res
+    }
+
+    fn gen_stmt(&mut self, depth: u32) -> String {
+        let ind = indent(depth);
+        let nested = depth < MAX_DEPTH;
+        match self.next(11) {
+            0 | 1 => {
+                let var_type = if self.chance(75) {
+                    VarType::Int
+                } else {
+                    VarType::Bool
+                };
+                // sometimes shadows a variable of an outer block
+                let name = match self.pick_var(var_type, false) {
+                    Some(name) if self.chance(30) && !self.is_in_current_scope(&name) => name,
+                    _ => self.new_var_name(),
+                };
+                let (type_str, init) = match var_type {
+                    VarType::Int if self.chance(20) => ("int", String::new()),
+                    VarType::Int => ("int", format!(" = {}", self.gen_int_expr())),
+                    VarType::Bool => ("boolean", format!(" = {}", self.gen_bool_expr())),
+                };
+                self.declare(&name, var_type, true);
+                format!("{}{} {}{};\n", ind, type_str, name, init)
+            }
+            2 | 3 => match self.pick_var(VarType::Int, true) {
+                Some(name) => format!("{}{} = {};\n", ind, name, self.gen_int_expr()),
+                None => String::new(),
+            },
+            4 => match self.pick_var(VarType::Bool, true) {
+                Some(name) => format!("{}{} = {};\n", ind, name, self.gen_bool_expr()),
+                None => String::new(),
+            },
+            5 if nested => {
+                let cond = self.gen_bool_expr();
+                let then_block = self.gen_block(depth, "");
+                if self.chance(50) {
+                    let else_block = self.gen_block(depth, "");
+                    format!("{}if ({}) {} else {}\n", ind, cond, then_block, else_block)
+                } else {
+                    format!("{}if ({}) {}\n", ind, cond, then_block)
+                }
+            }
+            6 if nested => {
+                // the counter is incremented first, so continue can't skip it
+                let counter = self.new_var_name();
+                let bound = 1 + self.next(4);
+                self.scopes.push(vec![]);
+                self.declare(&counter, VarType::Int, false);
+                self.loop_depth += 1;
+                let body =
+                    self.gen_block(depth + 1, &format!("{}{}++;\n", indent(depth + 2), counter));
+                self.loop_depth -= 1;
+                self.scopes.pop();
+                format!(
+                    "{}{{\n{}int {} = 0;\n{}while ({} < {}) {}\n{}}}\n",
+                    ind,
+                    indent(depth + 1),
+                    counter,
+                    indent(depth + 1),
+                    counter,
+                    bound,
+                    body,
+                    ind
+                )
+            }
+            7 if self.loop_depth > 0 => {
+                let jump = if self.chance(50) { "break" } else { "continue" };
+                format!("{}if ({}) {};\n", ind, self.gen_bool_expr(), jump)
+            }
+            8 if nested => format!("{}{}\n", ind, self.gen_block(depth, "")),
+            9 => match self.pick_var(VarType::Int, false) {
+                Some(name) => format!("{}printInt({});\n", ind, name),
+                None => String::new(),
+            },
+            10 => match self.pick_var(VarType::Int, true) {
+                Some(name) => format!(
+                    "{}{}{};\n",
+                    ind,
+                    name,
+                    if self.chance(50) { "++" } else { "--" }
+                ),
+                None => String::new(),
+            },
+            _ => String::new(),
+        }
+    }
+
+    fn gen_print_all(&mut self, depth: u32) -> String {
+        let mut res = String::new();
+        for scope in &self.scopes {
+            for var in scope {
+                res += &match var.var_type {
+                    VarType::Int => format!("{}printInt({});\n", indent(depth), var.name),
+                    VarType::Bool => format!(
+                        "{}if ({}) printInt(1); else printInt(0);\n",
+                        indent(depth),
+                        var.name
+                    ),
+                };
+            }
+        }
+        res
+    }
+
+    fn gen_atom(&mut self) -> String {
+        match self.pick_var(VarType::Int, false) {
+            Some(name) if self.chance(70) => name,
+            _ => self.next(100).to_string(),
+        }
+    }
+
+    fn gen_int_expr(&mut self) -> String {
+        let lhs = self.gen_atom();
+        let rhs = self.gen_atom();
+        let op = ["+", "-", "*"][self.next(3) as usize];
+        if !self.functions.is_empty() && self.chance(20) {
+            let i = self.next(self.functions.len() as u64) as usize;
+            let (name, args_cnt) = self.functions[i].clone();
+            let args: Vec<_> = (0..args_cnt).map(|_| self.gen_atom()).collect();
+            format!("{}({}) % {}", name, args.join(", "), MODULUS)
+        } else {
+            format!("({} {} {}) % {}", lhs, op, rhs, MODULUS)
+        }
+    }
+
+    fn gen_bool_expr(&mut self) -> String {
+        let cmp = || ["<", "<=", "==", "!=", ">"];
+        match self.next(4) {
+            0 => match self.pick_var(VarType::Bool, false) {
+                Some(name) => name,
+                None => "true".to_string(),
+            },
+            1 => format!("!({})", self.gen_cmp(&cmp())),
+            2 => format!("{} && {}", self.gen_cmp(&cmp()), self.gen_cmp(&cmp())),
+            _ => format!("{} || {}", self.gen_cmp(&cmp()), self.gen_cmp(&cmp())),
+        }
+    }
+
+    fn gen_cmp(&mut self, ops: &[&str]) -> String {
+        let op = ops[self.next(ops.len() as u64) as usize];
+        format!("{} {} {}", self.gen_atom(), op, self.gen_atom())
+    }
+
+    fn pick_var(&mut self, var_type: VarType, must_be_assignable: bool) -> Option<String> {
+        // shadowed variables aren't visible
+        let mut visible: Vec<&Var> = vec![];
+        for scope in self.scopes.iter().rev() {
+            for var in scope {
+                if !visible.iter().any(|v| v.name == var.name) {
+                    visible.push(var);
+                }
+            }
+        }
+        let candidates: Vec<_> = visible
+            .into_iter()
+            .filter(|v| v.var_type == var_type && (v.assignable || !must_be_assignable))
+            .map(|v| v.name.to_string())
+            .collect();
+        if candidates.is_empty() {
+            None
+        } else {
+            let i = self.next(candidates.len() as u64) as usize;
+            Some(candidates[i].to_string())
+        }
+    }
+
+    fn is_in_current_scope(&self, name: &str) -> bool {
+        self.scopes.last().unwrap().iter().any(|v| v.name == name) || self.scopes.len() == 1
+        // function arguments
+    }
+
+    fn new_var_name(&mut self) -> String {
+        self.next_var += 1;
+        format!("v{}", self.next_var)
+    }
+
+    fn declare(&mut self, name: &str, var_type: VarType, assignable: bool) {
+        self.scopes.last_mut().unwrap().push(Var {
+            name: name.to_string(),
+            var_type,
+            assignable,
+        });
+    }
+}
+
+fn indent(depth: u32) -> String {
+    "    ".repeat(depth as usize)
+}
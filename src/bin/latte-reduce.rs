@@ -0,0 +1,367 @@
+// Minimizes a program for a bug report (delta debugging): removes top-level
+// definitions, class items and statements, and replaces ifs and loops with
+// their bodies, as long as the program stays interesting, i.e. still
+// triggers the bug. Parts of the program are found by the parser, so every
+// candidate is cut out of the source along the spans of the AST.
+// usage: latte-reduce <file.lat> [--predicate=ice|verifier|differs] [--test=CMD]
+//        [--timeout=SECONDS] [--output=FILE] [-- compiler options]
+
+extern crate latte_compiler;
+
+use latte_compiler::codemap::CodeMap;
+use latte_compiler::model::ast::*;
+use latte_compiler::options::IntDivision;
+use latte_compiler::parser;
+use std::env;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::process::{self, Command, Output, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+enum Predicate {
+    // the compiler reports an internal error
+    InternalError,
+    // the compiler accepts the program, but a tool run after it
+    // (llvm-as, or llc and gcc when linking) fails
+    Verifier,
+    // output of the program compiled by the compiler differs from the output
+    // of the interpreter (which has to succeed)
+    Differs,
+    // the command run with the file as its last argument succeeds
+    Command(String),
+}
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    let mut input = None;
+    let mut output = None;
+    let mut predicate = Predicate::InternalError;
+    let mut timeout = 10;
+    let mut compiler_args = vec![];
+    for (i, arg) in args.iter().enumerate().skip(1) {
+        match arg.as_str() {
+            "--predicate=ice" => predicate = Predicate::InternalError,
+            "--predicate=verifier" => predicate = Predicate::Verifier,
+            "--predicate=differs" => predicate = Predicate::Differs,
+            "--" => {
+                compiler_args = args[i + 1..].to_vec();
+                break;
+            }
+            _ if arg.starts_with("--test=") => {
+                predicate = Predicate::Command(arg["--test=".len()..].to_string())
+            }
+            _ if arg.starts_with("--timeout=") => match arg["--timeout=".len()..].parse() {
+                Ok(t) => timeout = t,
+                Err(_) => exit_with_usage(&args[0]),
+            },
+            _ if arg.starts_with("--output=") => {
+                output = Some(PathBuf::from(&arg["--output=".len()..]))
+            }
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(PathBuf::from(arg)),
+            _ => exit_with_usage(&args[0]),
+        }
+    }
+    let input = match input {
+        Some(f) => f,
+        None => exit_with_usage(&args[0]),
+    };
+    let output = output.unwrap_or_else(|| input.with_extension("reduced.lat"));
+    let code = match fs::read_to_string(&input) {
+        // the spans of the parser are positions in the code with tabs expanded
+        Ok(code) => CodeMap::new("", &code).get_code().to_string(),
+        Err(_) => {
+            eprintln!("Cannot read file: {}", input.display());
+            process::exit(1);
+        }
+    };
+
+    let dir = env::temp_dir().join(format!("latte-reduce-{}", process::id()));
+    if fs::create_dir_all(&dir).is_err() {
+        eprintln!("Cannot create directory: {}", dir.display());
+        process::exit(1);
+    }
+    let mut reducer = Reducer {
+        // the compiler is built next to this binary
+        compiler: env::current_exe().unwrap().with_file_name("latte-compiler"),
+        predicate,
+        compiler_args,
+        timeout: Duration::from_secs(timeout),
+        file: dir.join("reduced.lat"),
+        tests_cnt: 0,
+    };
+    let res = reducer.reduce(&code);
+    let _ = fs::remove_dir_all(&dir);
+    match res {
+        Some(reduced) => {
+            fs::write(&output, &reduced).unwrap();
+            println!(
+                "Reduced {} bytes to {} bytes in {} tests, written to {}",
+                code.len(),
+                reduced.len(),
+                reducer.tests_cnt,
+                output.display()
+            );
+        }
+        None => {
+            eprintln!("The program isn't interesting (the predicate doesn't hold for it)");
+            process::exit(1);
+        }
+    }
+}
+
+fn exit_with_usage(program: &str) -> ! {
+    eprintln!(
+        "Usage: {} <file.lat> [--predicate=ice|verifier|differs] [--test=CMD] \
+         [--timeout=SECONDS] [--output=FILE] [-- compiler options]",
+        program
+    );
+    process::exit(1);
+}
+
+struct Reducer {
+    compiler: PathBuf,
+    predicate: Predicate,
+    compiler_args: Vec<String>,
+    timeout: Duration,
+    file: PathBuf, // where the candidates are written
+    tests_cnt: usize,
+}
+
+impl Reducer {
+    // None if the program isn't interesting
+    fn reduce(&mut self, code: &str) -> Option<String> {
+        if !self.is_interesting(code) {
+            return None;
+        }
+        let mut code = code.to_string();
+        // candidates are cut out in chunks, halved when none of them can be
+        // removed; after every success the program is parsed again
+        let mut chunk_size = usize::MAX;
+        loop {
+            let candidates = get_candidates(&code);
+            if candidates.is_empty() {
+                break;
+            }
+            chunk_size = chunk_size.min(candidates.len());
+            let reduced = candidates
+                .chunks(chunk_size)
+                .filter_map(|chunk| apply_candidates(&code, chunk))
+                .find(|reduced| reduced.len() < code.len() && self.is_interesting(reduced));
+            match reduced {
+                Some(reduced) => code = reduced,
+                None if chunk_size > 1 => chunk_size /= 2,
+                None => break,
+            }
+        }
+
+        let without_blank_lines: String = code
+            .lines()
+            .map(|l| l.trim_end())
+            .filter(|l| !l.is_empty())
+            .map(|l| format!("{}\n", l))
+            .collect();
+        if self.is_interesting(&without_blank_lines) {
+            code = without_blank_lines;
+        }
+        // then single lines, e.g. comments (they aren't in the AST)
+        let mut i = 0;
+        loop {
+            let lines: Vec<_> = code.lines().collect();
+            if i >= lines.len() {
+                break;
+            }
+            let reduced: String = lines
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .map(|(_, l)| format!("{}\n", l))
+                .collect();
+            if self.is_interesting(&reduced) {
+                code = reduced;
+            } else {
+                i += 1;
+            }
+        }
+        Some(code)
+    }
+
+    fn is_interesting(&mut self, code: &str) -> bool {
+        self.tests_cnt += 1;
+        fs::write(&self.file, code).unwrap();
+        match &self.predicate {
+            Predicate::InternalError => match self.run_compiler(&["build", "--no-link"]) {
+                Some(out) => {
+                    String::from_utf8_lossy(&out.stderr).contains("Internal compiler error")
+                }
+                None => false,
+            },
+            Predicate::Verifier => match self.run_compiler(&["build", "--no-link"]) {
+                Some(out) => !out.status.success() && out.stderr.starts_with(b"OK\n"),
+                None => false,
+            },
+            Predicate::Differs => {
+                let expected = match self.run_compiler(&["run", "--interp"]) {
+                    Some(out) if out.status.success() => out.stdout,
+                    _ => return false,
+                };
+                match self.run_compiler(&["build"]) {
+                    Some(ref out) if out.status.success() => (),
+                    _ => return false,
+                }
+                match self.run(&mut Command::new(self.file.with_extension(""))) {
+                    Some(out) => !out.status.success() || out.stdout != expected,
+                    None => false,
+                }
+            }
+            Predicate::Command(cmd) => match self.run(Command::new(cmd).arg(&self.file)) {
+                Some(out) => out.status.success(),
+                None => false,
+            },
+        }
+    }
+
+    fn run_compiler(&self, extra_args: &[&str]) -> Option<Output> {
+        self.run(
+            Command::new(&self.compiler)
+                .args(extra_args)
+                .args(&self.compiler_args)
+                .arg(&self.file),
+        )
+    }
+
+    // None on a timeout (removing statements easily makes infinite loops)
+    fn run(&self, cmd: &mut Command) -> Option<Output> {
+        let mut child = cmd
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .ok()?;
+        // read concurrently, so the child doesn't block on a full pipe
+        let stdout = read_in_background(child.stdout.take().unwrap());
+        let stderr = read_in_background(child.stderr.take().unwrap());
+        let start = Instant::now();
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if start.elapsed() < self.timeout => {
+                    thread::sleep(Duration::from_millis(5))
+                }
+                _ => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    return None;
+                }
+            }
+        };
+        Some(Output {
+            status,
+            stdout: stdout.join().unwrap(),
+            stderr: stderr.join().unwrap(),
+        })
+    }
+}
+
+fn read_in_background<R: Read + Send + 'static>(mut pipe: R) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buf = vec![];
+        let _ = pipe.read_to_end(&mut buf);
+        buf
+    })
+}
+
+// --------------------------------------------------------
+// ----------------- candidates ---------------------------
+// --------------------------------------------------------
+
+// a part of the program and its replacement
+type Candidate = (Span, String);
+
+// outer parts first, so the bigger ones are tried before their insides;
+// no candidates if the program doesn't parse
+fn get_candidates(code: &str) -> Vec<Candidate> {
+    let codemap = CodeMap::new("", code);
+    let prog = match parser::parse(&codemap, IntDivision::Truncating) {
+        Ok(prog) => prog,
+        Err(_) => return vec![],
+    };
+    let mut res = vec![];
+    for def in &prog.defs {
+        match def {
+            TopDef::FunDef(fun) => {
+                res.push((fun.span, String::new()));
+                add_block_candidates(code, &fun.body, &mut res);
+            }
+            TopDef::ClassDef(cl) => {
+                res.push((cl.span, String::new()));
+                for it in &cl.items {
+                    res.push((it.span, String::new()));
+                    if let InnerClassItemDef::Method(fun) = &it.inner {
+                        add_block_candidates(code, &fun.body, &mut res);
+                    }
+                }
+            }
+            TopDef::StructDef(st) => res.push((st.span, String::new())),
+            TopDef::TypeDef(td) => res.push((td.span, String::new())),
+            TopDef::Error => (),
+        }
+    }
+    res
+}
+
+fn add_block_candidates(code: &str, bl: &Block, res: &mut Vec<Candidate>) {
+    let text = |span: Span| code.get(span.0..span.1).unwrap_or("").to_string();
+    for st in &bl.stmts {
+        // a body of an if or a loop without braces is the statement itself,
+        // and it can't be empty
+        let removed = if st.span == bl.span { ";" } else { "" };
+        res.push((st.span, removed.to_string()));
+        match &st.inner {
+            InnerStmt::Block(bl) => add_block_candidates(code, bl, res),
+            InnerStmt::Cond {
+                true_branch,
+                false_branch,
+                ..
+            } => {
+                res.push((st.span, text(true_branch.span)));
+                if let Some(false_branch) = false_branch {
+                    res.push((st.span, text(false_branch.span)));
+                }
+                add_block_candidates(code, true_branch, res);
+                if let Some(false_branch) = false_branch {
+                    add_block_candidates(code, false_branch, res);
+                }
+            }
+            InnerStmt::While { body, .. } | InnerStmt::ForEach { body, .. } => {
+                res.push((st.span, text(body.span)));
+                add_block_candidates(code, body, res);
+            }
+            InnerStmt::NestedFun(fun) => add_block_candidates(code, &fun.body, res),
+            _ => (),
+        }
+    }
+}
+
+// candidates overlapping an earlier one are skipped; None if nothing changes
+fn apply_candidates(code: &str, candidates: &[Candidate]) -> Option<String> {
+    let mut res = String::new();
+    let mut pos = 0;
+    let mut changed = false;
+    for (span, replacement) in candidates {
+        if span.0 < pos || code.get(span.0..span.1).is_none() {
+            continue;
+        }
+        res += &code[pos..span.0];
+        res += replacement;
+        pos = span.1;
+        changed = true;
+    }
+    res += &code[pos..];
+    if changed {
+        Some(res)
+    } else {
+        None
+    }
+}
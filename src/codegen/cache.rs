@@ -0,0 +1,210 @@
+// Persistent cache of the generated IR of functions (--cache-dir=DIR), so
+// rebuilding a program regenerates only the functions whose code or
+// dependencies changed. The key of a function covers its AST (without
+// spans), the signatures of functions and the layouts of classes and
+// structs reachable from the names it uses (through their types, so also
+// classes of fields it only accesses), the codegen options and the compiler
+// binary. The IR is cached as generated, the optimizer runs on the whole
+// program afterwards. The source spans aren't in the key, so they are
+// dropped from the loaded IR.
+//
+// Layout: DIR/functions/<hash of the key>.ir with the s-expression
+// (<second hash of the key> <string literals with their ids> <function>),
+// see model::ir_encoding.
+
+use super::class::ClassRegistry;
+use model::ast;
+use model::ir::{self, fnv1a_hash, GlobalStrId, GlobalStrings};
+use model::ir_encoding::{Encode, Node};
+use options::{CompilerOptions, IntDivision};
+use semantics::global_context::GlobalContext;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::UNIX_EPOCH;
+
+// changed with the encoding or the meaning of the keys
+const FORMAT_VERSION: u32 = 10;
+
+pub struct FunctionCache {
+    dir: PathBuf,
+    // a rebuilt compiler may generate different code
+    compiler_id: String,
+}
+
+pub struct CacheKey {
+    file_name: String,
+    check: u64, // against collisions of the file names
+}
+
+type CacheEntry = (u64, Vec<(String, u64)>, ir::Function);
+
+impl FunctionCache {
+    pub fn new(dir: &Path) -> Self {
+        let exe_metadata = env::current_exe().and_then(fs::metadata);
+        let compiler_id = match exe_metadata {
+            Ok(m) => {
+                let modified = m
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok());
+                format!("{}:{:?}", m.len(), modified)
+            }
+            Err(_) => "unknown".to_string(),
+        };
+        FunctionCache {
+            dir: dir.join("functions"),
+            compiler_id,
+        }
+    }
+
+    // class_name is given for methods
+    pub fn get_key(
+        &self,
+        fun: &ast::FunDef,
+        class_name: Option<&str>,
+        gctx: &GlobalContext,
+        class_registry: &ClassRegistry,
+        options: &CompilerOptions,
+    ) -> CacheKey {
+        let (code, mut names) = scan_debug_output(&format!("{:?}", fun));
+        let mut key = format!(
+            "{} {}\n{} {} {} {} {}\n{:?}\n{}\n",
+            FORMAT_VERSION,
+            self.compiler_id,
+            options.check_bounds,
+            options.check_stack,
+            options.trace_eval_order,
+            options.pointer_width,
+            options.int_division == IntDivision::Euclidean,
+            class_name,
+            code
+        );
+        names.extend(class_name.map(str::to_string));
+        let mut visited = HashSet::new();
+        while let Some(name) = names.pop() {
+            if !visited.insert(name.clone()) {
+                continue;
+            }
+            let mut deps = String::new();
+            if let Some(desc) = gctx.get_function_description(&name) {
+                let args: Vec<_> = desc.args_types.iter().map(|t| &t.inner).collect();
+                deps += &format!("fun {} {:?} {:?}\n", name, desc.ret_type.inner, args);
+            }
+            if let Some(cl) = class_registry.find_class_ir(&name) {
+                deps += &format!(
+                    "class {} {:?} {:?} {:?} {:?} {} {:?} {}\n",
+                    name,
+                    cl.fields,
+                    cl.field_names,
+                    cl.vtable,
+                    cl.parent,
+                    cl.is_final,
+                    cl.overridden,
+                    cl.needs_vtable
+                );
+            }
+            if let Some(st) = class_registry.find_struct_ir(&name) {
+                deps += &format!("struct {} {:?}\n", name, st.fields);
+            }
+            names.extend(scan_debug_output(&deps).1);
+            key += &deps;
+        }
+        // the second hash uses another offset basis
+        let check = fnv1a_hash(format!("check\n{}", key).as_bytes());
+        CacheKey {
+            file_name: format!("{:016x}.ir", fnv1a_hash(key.as_bytes())),
+            check,
+        }
+    }
+
+    // the string literals of the function are interned, if it's found
+    pub fn load(&self, key: &CacheKey, global_strings: &mut GlobalStrings) -> Option<ir::Function> {
+        let text = fs::read_to_string(self.dir.join(&key.file_name)).ok()?;
+        let node = Node::from_text(&text).ok()?;
+        let (check, strings, mut fun) = CacheEntry::decode(&node).ok()?;
+        // the names of the literals in the cached IR must be the same
+        // (they differ only after hash collisions)
+        let same_ids = strings
+            .iter()
+            .all(|(s, id)| global_strings.peek(s) == GlobalStrId(*id));
+        if check != key.check || !same_ids {
+            return None;
+        }
+        for (s, _) in &strings {
+            global_strings.intern(s);
+        }
+        for bl in &mut fun.blocks {
+            bl.body
+                .retain(|op| !matches!(op, ir::Operation::SourceSpan(_)));
+        }
+        Some(fun)
+    }
+
+    // errors are ignored, the cache is only an optimization
+    pub fn store(&self, key: &CacheKey, fun: &ir::Function, global_strings: &GlobalStrings) {
+        let names: HashMap<_, _> = global_strings
+            .iter_sorted()
+            .into_iter()
+            .map(|(s, id)| (ir::format_global_string(id), (s, id)))
+            .collect();
+        let mut strings = vec![];
+        for val in fun.blocks.iter().flat_map(|bl| bl.get_used_values()) {
+            if let ir::Value::GlobalRegister(name, _) = val {
+                if let Some((s, id)) = names.get(name) {
+                    strings.push((s.to_string(), id.0));
+                }
+            }
+        }
+        strings.sort();
+        strings.dedup();
+        let entry = Node::List(vec![key.check.encode(), strings.encode(), fun.encode()]);
+        // written to a temporary file first, so a concurrent build doesn't
+        // read a partial entry
+        let path = self.dir.join(&key.file_name);
+        let tmp_path = self
+            .dir
+            .join(format!("{}.{}.tmp", key.file_name, process::id()));
+        if fs::create_dir_all(&self.dir).is_ok()
+            && fs::write(&tmp_path, entry.to_text()).is_ok()
+            && fs::rename(&tmp_path, &path).is_err()
+        {
+            let _ = fs::remove_file(&tmp_path);
+        }
+    }
+}
+
+// removes spans (they don't matter for the codegen) from the Debug output
+// of the AST and returns it with the strings found in it (names and literals)
+fn scan_debug_output(text: &str) -> (String, Vec<String>) {
+    const SPAN: &str = "span: (";
+    let mut res = String::new();
+    let mut strings = vec![];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if c == '"' {
+            // the string with its quotes, escaped quotes are skipped
+            let mut escaped = false;
+            let len = rest[1..]
+                .char_indices()
+                .find(|&(_, c)| {
+                    let end = c == '"' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    end
+                })
+                .map_or(rest.len(), |(i, _)| i + 2);
+            strings.push(rest[1..len.max(2) - 1].to_string());
+            res += &rest[..len];
+            rest = &rest[len..];
+        } else if rest.starts_with(SPAN) {
+            let len = rest.find(')').map_or(rest.len(), |i| i + 1);
+            rest = &rest[len..];
+        } else {
+            res.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    (res, strings)
+}
@@ -1,53 +1,227 @@
 use model::{ast, ir};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet, VecDeque};
 
-// will take more arguments, probably
-pub fn get_size_of_primitive(type_: &ir::Type) -> i32 {
-    use self::ir::Type::*;
-    match type_ {
+// The layout of the classes and structs of a program, shared by the code
+// generation and other tools (e.g. debug info, RTTI or type maps of
+// a garbage collector): ClassRegistry::build computes it once for the whole
+// (lowered) program, then every lookup is in the prepared tables.
+//  - a field of a class or a struct has a number (its index in the LLVM
+//    type, the vtable pointer of a class is field 0); fields of the parent
+//    keep their numbers in subclasses, the new ones follow them,
+//  - a method has a number of its slot in the vtable; an overriding method
+//    takes the slot of the overridden one, so the number is the same in the
+//    whole subtree of the class which introduced the method,
+//  - the byte offsets depend on the target, they're computed from the types
+//    by ir::Program::get_fields_layout.
+// The lookups by name panic for unknown names (the type checking has
+// rejected them), the find_* methods return None instead.
+// todo (ext) interfaces: when the language has them, a class gets an itable
+// per implemented interface (slots numbered by the interface), and the slots
+// are resolved here in build like the vtable ones, with an analogue of
+// get_method_number_and_type, so an interface call stays a table lookup
+
+// size of an array element or a value in memory; objects, arrays, strings
+// and lambdas are all pointers in IR, so e.g. `new Node[n]` uses Ptr(Class),
+// only structs are stored inline (see generate_calculation_of_type_size)
+pub fn get_size_of_primitive(type_: &ir::Type, pointer_width: u32) -> i32 {
+    use self::ir::TypeKind::*;
+    match type_.kind() {
         Void => unreachable!(),
         Int => 4,
+        Long => 8,
         Bool => 1,
         Char => 1,
-        Ptr(_) => 8, // 64-bit
-        Class(_) => unreachable!(),
+        Ptr(_) => pointer_width as i32 / 8,
+        Class(_) | Struct(_) => unreachable!(),
         Func(_, _) => unreachable!(),
     }
 }
 
+// toString -> to_string
+fn to_snake_case(name: &str) -> String {
+    let mut res = String::new();
+    for c in name.chars() {
+        if c.is_uppercase() {
+            res.push('_');
+        }
+        res.extend(c.to_lowercase());
+    }
+    res
+}
+
+// compares types of methods, except for the type of "this"
+fn is_same_signature(lhs: &ir::Type, rhs: &ir::Type) -> bool {
+    match (lhs.kind(), rhs.kind()) {
+        (ir::TypeKind::Ptr(lhs), ir::TypeKind::Ptr(rhs)) => match (lhs.kind(), rhs.kind()) {
+            (ir::TypeKind::Func(l_ret, l_args), ir::TypeKind::Func(r_ret, r_args)) => {
+                l_ret == r_ret && l_args[1..] == r_args[1..]
+            }
+            _ => unreachable!(),
+        },
+        _ => unreachable!(),
+    }
+}
+
 pub struct ClassRegistry<'a> {
     classes: HashMap<&'a str, ClassDescription<'a>>,
+    structs: HashMap<&'a str, StructDescription<'a>>,
+    // the order of processing (parents before subclasses), so the IR doesn't
+    // depend on the order of hashing
+    class_order: Vec<&'a str>,
+    struct_order: Vec<&'a str>,
 }
 
 pub struct ClassDescription<'a> {
     fields: HashMap<&'a str, usize>,
     methods: HashMap<&'a str, usize>,
+    method_names: Vec<&'a str>, // per vtable slot
     class: ir::Class,
 }
 
+pub struct StructDescription<'a> {
+    fields: HashMap<&'a str, usize>,
+    st: ir::Struct,
+}
+
 impl<'a> ClassRegistry<'a> {
-    pub fn new() -> ClassRegistry<'a> {
+    // the whole layout of the program, with the closed world analysis
+    // (requirement: the program is type checked)
+    pub fn build(prog: &'a ast::Program) -> ClassRegistry<'a> {
+        let mut registry = ClassRegistry::new();
+        // Object (the root of the hierarchy) is registered as a builtin class
+        let mut class_queue = VecDeque::new();
+        class_queue.push_back(ast::OBJECT_CLASS);
+        let mut class_hierarchy = HashMap::new();
+        for def in &prog.defs {
+            if let ast::TopDef::StructDef(st) = def {
+                registry.process_struct_def(st);
+            }
+            if let ast::TopDef::ClassDef(cl) = def {
+                let parent_name = match &cl.parent_type {
+                    Some(ast::ItemWithSpan {
+                        inner: ast::InnerType::Class(parent_name),
+                        ..
+                    }) => parent_name.as_str(),
+                    None => ast::OBJECT_CLASS,
+                    _ => unreachable!(),
+                };
+                class_hierarchy
+                    .entry(parent_name)
+                    .or_insert_with(Vec::new)
+                    .push(cl);
+            }
+        }
+        while let Some(cl_name) = class_queue.pop_front() {
+            if let Some(sons) = class_hierarchy.get(&cl_name) {
+                for cl in sons {
+                    registry.process_class_def(cl);
+                    class_queue.push_back(cl.name.inner.as_str());
+                }
+            }
+        }
+        registry.analyze_class_hierarchy();
+        registry
+    }
+
+    fn new() -> ClassRegistry<'a> {
+        let str_type = ir::Type::ptr(ir::Type::Char);
+        let sb_type = ir::Type::from_class_name(ast::STRING_BUILDER_CLASS);
+        let mut classes = HashMap::new();
+        let object_type = ir::Type::from_class_name(ast::OBJECT_CLASS);
+        classes.insert(
+            ast::OBJECT_CLASS,
+            ClassDescription::new_builtin(
+                ast::OBJECT_CLASS,
+                vec![
+                    (ast::EQUALS_METHOD, ir::Type::Bool, vec![object_type]),
+                    ("toString", str_type, vec![]),
+                    ("hashCode", ir::Type::Int, vec![]),
+                ],
+            ),
+        );
+        classes.insert(
+            ast::STRING_BUILDER_CLASS,
+            ClassDescription::new_builtin(
+                ast::STRING_BUILDER_CLASS,
+                vec![
+                    ("append", sb_type, vec![str_type]),
+                    ("toString", str_type, vec![]),
+                ],
+            ),
+        );
+        for (name, elem_type) in [
+            (ast::INT_LIST_CLASS, ir::Type::Int),
+            (ast::STRING_LIST_CLASS, str_type),
+        ] {
+            let methods = vec![
+                ("push", ir::Type::Void, vec![elem_type]),
+                ("get", elem_type, vec![ir::Type::Int]),
+                ("set", ir::Type::Void, vec![ir::Type::Int, elem_type]),
+                ("size", ir::Type::Int, vec![]),
+            ];
+            classes.insert(name, ClassDescription::new_builtin(name, methods));
+        }
+        let str_array_type = ir::Type::ptr(str_type);
+        classes.insert(
+            ast::STRING_INT_MAP_CLASS,
+            ClassDescription::new_builtin(
+                ast::STRING_INT_MAP_CLASS,
+                vec![
+                    ("put", ir::Type::Void, vec![str_type, ir::Type::Int]),
+                    ("get", ir::Type::Int, vec![str_type]),
+                    ("contains", ir::Type::Bool, vec![str_type]),
+                    ("size", ir::Type::Int, vec![]),
+                    ("keys", str_array_type, vec![]),
+                ],
+            ),
+        );
+
+        let mut class_order: Vec<_> = classes.keys().cloned().collect();
+        class_order.sort_unstable();
         ClassRegistry {
-            classes: HashMap::new(),
+            classes,
+            structs: HashMap::new(),
+            class_order,
+            struct_order: vec![],
+        }
+    }
+
+    fn process_struct_def(&mut self, st: &'a ast::StructDef) {
+        let mut st_desc = StructDescription {
+            fields: HashMap::new(),
+            st: ir::Struct {
+                name: st.name.inner.to_string(),
+                fields: vec![],
+            },
+        };
+        for (f_type, f_name) in &st.fields {
+            st_desc
+                .fields
+                .insert(&f_name.inner, st_desc.st.fields.len());
+            st_desc.st.fields.push(ir::Type::from_ast(&f_type.inner));
         }
+        self.structs.insert(&st.name.inner, st_desc);
+        self.struct_order.push(&st.name.inner);
     }
 
-    pub fn process_class_def(&mut self, cl: &'a ast::ClassDef) {
-        let mut cl_desc = if let Some(cl_type) = &cl.parent_type {
-            match &cl_type.inner {
-                ast::InnerType::Class(parent_cl_name) => ClassDescription::new_subclass(
-                    &cl.name.inner,
-                    &self.classes[parent_cl_name.as_str()],
-                ),
+    fn process_class_def(&mut self, cl: &'a ast::ClassDef) {
+        let parent_cl_name = match &cl.parent_type {
+            Some(cl_type) => match &cl_type.inner {
+                ast::InnerType::Class(parent_cl_name) => parent_cl_name.as_str(),
                 _ => unreachable!(),
-            }
-        } else {
-            ClassDescription::new(&cl.name.inner)
+            },
+            None => ast::OBJECT_CLASS,
         };
+        let mut cl_desc =
+            ClassDescription::new_subclass(&cl.name.inner, &self.classes[parent_cl_name]);
 
         let vtable_type = ir::get_class_vtable_type(&cl.name.inner);
         if cl_desc.class.fields.is_empty() {
             cl_desc.class.fields.push(vtable_type);
+            cl_desc
+                .class
+                .field_names
+                .push(ir::VTABLE_FIELD_NAME.to_string());
         } else {
             cl_desc.class.fields[0] = vtable_type;
         }
@@ -58,6 +232,7 @@ impl<'a> ClassRegistry<'a> {
                     let ir_type = ir::Type::from_ast(&f_type.inner);
                     let new_idx = cl_desc.class.fields.len();
                     cl_desc.class.fields.push(ir_type);
+                    cl_desc.class.field_names.push(f_name.inner.to_string());
                     cl_desc.fields.insert(&f_name.inner, new_idx);
                 }
                 ast::InnerClassItemDef::Method(fun) => {
@@ -66,68 +241,283 @@ impl<'a> ClassRegistry<'a> {
 
                     // cloned to satisfy borrow checker
                     match cl_desc.methods.get(fun.name.inner.as_str()).cloned() {
-                        Some(idx) => cl_desc.class.vtable[idx] = (fun_type, fun_name),
-                        None => {
+                        Some(idx) if is_same_signature(&cl_desc.class.vtable[idx].0, &fun_type) => {
+                            cl_desc.class.vtable[idx] = (fun_type, fun_name)
+                        }
+                        // a default method of Object hidden by a method with other signature
+                        // is still called through Object references, so it keeps its slot
+                        _ => {
                             let new_idx = cl_desc.class.vtable.len();
                             cl_desc.class.vtable.push((fun_type, fun_name));
                             cl_desc.methods.insert(&fun.name.inner, new_idx);
+                            cl_desc.method_names.push(&fun.name.inner);
                         }
                     }
                 }
-                ast::InnerClassItemDef::Error => unreachable!(),
+                ast::InnerClassItemDef::Class(_) | ast::InnerClassItemDef::Error => unreachable!(),
             }
         }
 
         self.classes.insert(&cl.name.inner, cl_desc);
+        self.class_order.push(&cl.name.inner);
+    }
+
+    // must be called after processing all classes
+    fn analyze_class_hierarchy(&mut self) {
+        let names: Vec<&'a str> = self.classes.keys().cloned().collect();
+        let get_ancestors = |name: &'a str| {
+            let mut ancestors = vec![];
+            let mut cur = self.classes[name].class.parent.as_ref();
+            while let Some(parent) = cur {
+                let parent_desc = &self.classes[parent.as_str()];
+                ancestors.push(parent_desc);
+                cur = parent_desc.class.parent.as_ref();
+            }
+            ancestors
+        };
+
+        let mut overridden: HashMap<&str, Vec<bool>> = names
+            .iter()
+            .map(|name| (*name, vec![false; self.classes[name].class.vtable.len()]))
+            .collect();
+        let mut has_subclasses = HashSet::new();
+        for name in &names {
+            let vtable = &self.classes[name].class.vtable;
+            for ancestor in get_ancestors(name) {
+                has_subclasses.insert(ancestor.class.name.clone());
+                let ancestor_overridden = overridden.get_mut(ancestor.class.name.as_str()).unwrap();
+                for (i, (_, fun_name)) in ancestor.class.vtable.iter().enumerate() {
+                    if vtable[i].1 != *fun_name {
+                        ancestor_overridden[i] = true;
+                    }
+                }
+            }
+        }
+
+        // vtable of a class is used only by virtual calls on the class
+        // or on its ancestors
+        let mut needs_vtable = HashSet::new();
+        for name in &names {
+            let is_virtual = |cl_name: &str| overridden[cl_name].iter().any(|o| *o);
+            if is_virtual(name)
+                || get_ancestors(name)
+                    .iter()
+                    .any(|anc| is_virtual(&anc.class.name))
+            {
+                needs_vtable.insert(*name);
+            }
+        }
+
+        for name in names {
+            let has_subclasses = has_subclasses.contains(name);
+            let needs_vtable = needs_vtable.contains(name);
+            let cl = &mut self.classes.get_mut(name).unwrap().class;
+            cl.is_final = !has_subclasses;
+            cl.overridden = overridden.remove(name).unwrap();
+            cl.needs_vtable = needs_vtable;
+        }
     }
 
-    pub fn insert_classes_ir_into(self, program: &mut ir::Program) {
-        for (_, cl) in self.classes.into_iter() {
-            program.classes.push(cl.get_class_ir())
+    pub fn insert_classes_ir_into(mut self, program: &mut ir::Program) {
+        for name in &self.class_order {
+            let cl = self.classes.remove(name).unwrap();
+            program.classes.push(cl.into_class_ir())
+        }
+        for name in &self.struct_order {
+            let st = self.structs.remove(name).unwrap();
+            program.structs.push(st.st)
         }
     }
 
+    // the classes (with the builtin ones) in the order of the IR,
+    // parents before subclasses
+    pub fn get_class_names(&self) -> &[&'a str] {
+        &self.class_order
+    }
+
+    pub fn get_struct_names(&self) -> &[&'a str] {
+        &self.struct_order
+    }
+
     pub fn get_class_description(&self, name: &str) -> &ClassDescription<'a> {
         &self.classes[name]
     }
+
+    pub fn find_class_description(&self, name: &str) -> Option<&ClassDescription<'a>> {
+        self.classes.get(name)
+    }
+
+    pub fn get_struct_description(&self, name: &str) -> &StructDescription<'a> {
+        &self.structs[name]
+    }
+
+    // None for unknown names, e.g. of functions (used by the function cache)
+    pub fn find_class_ir(&self, name: &str) -> Option<&ir::Class> {
+        self.classes.get(name).map(|cl_desc| &cl_desc.class)
+    }
+
+    pub fn find_struct_ir(&self, name: &str) -> Option<&ir::Struct> {
+        self.structs.get(name).map(|st_desc| &st_desc.st)
+    }
+
+    // the class itself or one of its ancestors
+    pub fn is_subclass(&self, subclass: &str, superclass: &str) -> bool {
+        let mut cur = Some(subclass);
+        while let Some(name) = cur {
+            if name == superclass {
+                return true;
+            }
+            cur = self.find_class_ir(name).and_then(|cl| cl.parent.as_deref());
+        }
+        false
+    }
 }
 
 impl<'a> ClassDescription<'a> {
-    fn new(name: &str) -> ClassDescription {
+    fn new(name: &str) -> ClassDescription<'_> {
         ClassDescription {
             fields: HashMap::new(),
             methods: HashMap::new(),
+            method_names: vec![],
             class: ir::Class {
                 name: name.to_string(),
                 fields: vec![],
+                field_names: vec![],
                 vtable: vec![],
+                is_builtin: false,
+                parent: None,
+                is_final: true,
+                overridden: vec![],
+                needs_vtable: false,
             },
         }
     }
 
+    // the object layout must match the structs in the runtime
+    // (StringBuilder, List and Map have the same one, Object has only the vtable)
+    fn new_builtin(name: &'a str, methods: Vec<(&'a str, ir::Type, Vec<ir::Type>)>) -> Self {
+        let mut cl_desc = ClassDescription::new(name);
+        cl_desc.class.is_builtin = true;
+        cl_desc.class.fields = vec![ir::get_class_vtable_type(name)];
+        cl_desc.class.field_names = vec![ir::VTABLE_FIELD_NAME.to_string()];
+        if name != ast::OBJECT_CLASS {
+            cl_desc.class.fields.extend(vec![
+                ir::Type::ptr(ir::Type::Char), // buffer
+                ir::Type::Int,                 // length
+                ir::Type::Int,                 // capacity
+            ]);
+            let names = ["buffer", "length", "capacity"];
+            cl_desc
+                .class
+                .field_names
+                .extend(names.iter().map(|n| n.to_string()));
+        }
+
+        // e.g. IntList.push is implemented by _bltn_int_list_push
+        let fun_prefix = match name {
+            ast::OBJECT_CLASS => "_bltn_object",
+            ast::STRING_BUILDER_CLASS => "_bltn_string_builder",
+            ast::INT_LIST_CLASS => "_bltn_int_list",
+            ast::STRING_LIST_CLASS => "_bltn_string_list",
+            ast::STRING_INT_MAP_CLASS => "_bltn_string_int_map",
+            _ => unreachable!(),
+        };
+        for (method_name, ret_type, args_types) in methods {
+            let args_types = vec![ir::Type::from_class_name(name)]
+                .into_iter()
+                .chain(args_types)
+                .collect();
+            let fun_type = ir::Type::ptr(ir::Type::func(ret_type, args_types));
+            let fun_name = format!("{}_{}", fun_prefix, to_snake_case(method_name));
+            cl_desc
+                .methods
+                .insert(method_name, cl_desc.class.vtable.len());
+            cl_desc.method_names.push(method_name);
+            cl_desc.class.vtable.push((fun_type, fun_name));
+        }
+        cl_desc
+    }
+
     fn new_subclass(name: &str, parent_cl_desc: &ClassDescription<'a>) -> ClassDescription<'a> {
         ClassDescription {
             fields: parent_cl_desc.fields.clone(),
             methods: parent_cl_desc.methods.clone(),
+            method_names: parent_cl_desc.method_names.clone(),
             class: ir::Class {
                 name: name.to_string(),
                 fields: parent_cl_desc.class.fields.clone(),
+                field_names: parent_cl_desc.class.field_names.clone(),
                 vtable: parent_cl_desc.class.vtable.clone(),
+                is_builtin: false,
+                parent: Some(parent_cl_desc.class.name.clone()),
+                is_final: true,
+                overridden: vec![],
+                needs_vtable: false,
             },
         }
     }
 
-    fn get_class_ir(self) -> ir::Class {
+    fn into_class_ir(self) -> ir::Class {
         self.class
     }
 
+    // fields (with the vtable pointer), the vtable and the hierarchy
+    pub fn get_class_ir(&self) -> &ir::Class {
+        &self.class
+    }
+
     pub fn get_field_number_and_type(&self, field: &str) -> (usize, ir::Type) {
-        let no = self.fields[field];
-        (no, self.class.fields[no].clone())
+        self.find_field(field).unwrap()
     }
 
+    pub fn find_field(&self, field: &str) -> Option<(usize, ir::Type)> {
+        let no = *self.fields.get(field)?;
+        Some((no, self.class.fields[no]))
+    }
+
+    // the type is of the implementation in this class (its "this" is of this class)
     pub fn get_method_number_and_type(&self, method: &str) -> (usize, ir::Type) {
+        self.find_method(method).unwrap()
+    }
+
+    pub fn find_method(&self, method: &str) -> Option<(usize, ir::Type)> {
+        let no = *self.methods.get(method)?;
+        Some((no, self.class.vtable[no].0))
+    }
+
+    // the names of the methods in the vtable, by slot
+    pub fn get_method_names(&self) -> &[&'a str] {
+        &self.method_names
+    }
+
+    // function implementing the method, if it's the same for all subclasses
+    // (then the call doesn't need the vtable)
+    pub fn get_devirtualized_method(&self, method: &str) -> Option<ir::Value> {
         let no = self.methods[method];
-        (no, self.class.vtable[no].0.clone())
+        if self.class.overridden[no] {
+            None
+        } else {
+            let (fun_type, fun_name) = &self.class.vtable[no];
+            Some(ir::Value::GlobalRegister(fun_name.clone(), *fun_type))
+        }
+    }
+
+    pub fn needs_vtable(&self) -> bool {
+        self.class.needs_vtable
+    }
+}
+
+impl<'a> StructDescription<'a> {
+    pub fn get_struct_ir(&self) -> &ir::Struct {
+        &self.st
+    }
+
+    pub fn get_field_number_and_type(&self, field: &str) -> (usize, ir::Type) {
+        self.find_field(field).unwrap()
+    }
+
+    pub fn find_field(&self, field: &str) -> Option<(usize, ir::Type)> {
+        let no = *self.fields.get(field)?;
+        Some((no, self.st.fields[no]))
     }
 }
@@ -1,23 +1,33 @@
 use codegen::class::get_size_of_primitive;
 use codegen::class::ClassRegistry;
+use ice;
 use model::{ast, ir};
-use semantics::global_context::{ClassDesc, GlobalContext};
-use std::collections::{HashMap, HashSet};
+use options::{CompilerOptions, IntDivision};
+use semantics::global_context::{ClassDesc, FunDesc, GlobalContext, TypeWrapper};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 struct Env<'a> {
     global_ctx: &'a GlobalContext,
     class_ctx: Option<&'a ClassDesc>,
     frames: HashMap<ir::Label, EnvFrame<'a>>,
+    // blocks continuing the scope of another block (after if, loop, &&, ...)
+    // share its frame, so the chains of frames are as deep as the nesting
+    // of the scopes, not as long as the function
+    shared_frames: HashMap<ir::Label, ir::Label>,
     next_proxy_frame: ir::Label,
 }
 
 struct EnvFrame<'a> {
     parent: Option<ir::Label>,
     locals: HashMap<&'a str, ir::Value>,
+    // created by create_proxy_env, doesn't declare variables; it's copy-on-write:
+    // holds only the variables assigned below it, other ones are read from the parent
+    is_proxy_copy: bool,
 }
 
 const ARGS_LABEL: ir::Label = ir::Label(std::u32::MAX);
 const UNREACHABLE_LABEL: ir::Label = ir::Label(std::u32::MAX - 1);
+const FIRST_PROXY_LABEL: ir::Label = ir::Label(std::u32::MAX - 42); // some arbitrary big label
 
 impl<'a> Env<'a> {
     pub fn new(gctx: &'a GlobalContext, cctx: Option<&'a ClassDesc>) -> Env<'a> {
@@ -27,22 +37,26 @@ impl<'a> Env<'a> {
             EnvFrame {
                 parent: None,
                 locals: HashMap::new(),
+                is_proxy_copy: false,
             },
         );
         Env {
             global_ctx: gctx,
             class_ctx: cctx,
             frames,
-            next_proxy_frame: ir::Label(std::u32::MAX - 42), // some arbitrary big label
+            shared_frames: HashMap::new(),
+            next_proxy_frame: FIRST_PROXY_LABEL,
         }
     }
 
     pub fn allocate_new_frame(&mut self, label: ir::Label, parent_label: ir::Label) {
+        let parent_label = self.get_frame(parent_label);
         let old_frame = self.frames.insert(
             label,
             EnvFrame {
                 parent: Some(parent_label),
                 locals: HashMap::new(),
+                is_proxy_copy: false,
             },
         );
         match old_frame {
@@ -51,16 +65,32 @@ impl<'a> Env<'a> {
         }
     }
 
+    pub fn share_frame(&mut self, label: ir::Label, frame_label: ir::Label) {
+        let frame_label = self.get_frame(frame_label);
+        let old_frame = self.shared_frames.insert(label, frame_label);
+        assert!(old_frame.is_none() && !self.frames.contains_key(&label));
+    }
+
+    // label of the frame holding variables of the block
+    fn get_frame(&self, label: ir::Label) -> ir::Label {
+        match self.shared_frames.get(&label) {
+            Some(frame_label) => *frame_label,
+            None => label,
+        }
+    }
+
     pub fn add_new_local_variable(&mut self, frame: ir::Label, name: &'a str, value: ir::Value) {
+        let frame = self.get_frame(frame);
         let old_val = self
             .frames
             .get_mut(&frame)
             .unwrap()
             .locals
             .insert(name, value);
+        // redeclarations are rejected by the semantic analysis (Env::add_variable)
         match old_val {
             None => (),
-            Some(_) => unreachable!(), // assert
+            Some(_) => unreachable!("variable {} declared twice in one scope", name),
         }
     }
 
@@ -70,10 +100,11 @@ impl<'a> Env<'a> {
         name: &'a str,
         value: ir::Value,
     ) {
-        let mut it = Some(frame);
+        let mut it = Some(self.get_frame(frame));
         while let Some(frame) = it {
             let frame = self.frames.get_mut(&frame).unwrap();
-            if frame.locals.contains_key(name) {
+            // a proxy copies the variable declared above it on the first write
+            if frame.is_proxy_copy || frame.locals.contains_key(name) {
                 frame.locals.insert(name, value);
                 return;
             } else {
@@ -87,21 +118,17 @@ impl<'a> Env<'a> {
     pub fn create_proxy_env(&mut self, frame_label: ir::Label) -> ir::Label {
         // loop body would modify mapping (name -> value in phi set) in condition block
         // - we want to avoid that, since body after loop needs value from this phi set
-        let names = self.get_all_visible_local_variables(frame_label);
         let proxy_frame_label = self.insert_empty_proxy_frame(frame_label);
-        for n in names {
-            let value = self.get_variable(frame_label, n).clone();
-            self.frames
-                .get_mut(&proxy_frame_label)
-                .unwrap()
-                .locals
-                .insert(n, value);
-        }
+        self.frames
+            .get_mut(&proxy_frame_label)
+            .unwrap()
+            .is_proxy_copy = true;
 
         proxy_frame_label
     }
 
     pub fn insert_empty_proxy_frame(&mut self, frame_label: ir::Label) -> ir::Label {
+        let frame_label = self.get_frame(frame_label);
         let proxy_frame_label = self.next_proxy_frame;
         self.next_proxy_frame.0 -= 1;
 
@@ -118,29 +145,55 @@ impl<'a> Env<'a> {
     }
 
     pub fn apply_proxy_env(&mut self, proxy: ir::Label, target: ir::Label) {
-        let names = self.get_all_visible_local_variables(proxy);
-        for n in names {
+        // values are updated where the variables are visible from, not copied to target,
+        // so an enclosing proxy (of if branch or loop body) sees them
+        for n in self.get_proxy_variables(proxy) {
             let value = self.get_variable(proxy, n).clone();
-            self.frames
-                .get_mut(&target)
-                .unwrap()
-                .locals
-                .insert(n, value);
+            self.update_existing_local_variable(target, n, value);
         }
     }
 
     pub fn get_variable(&self, frame: ir::Label, name: &'a str) -> &ir::Value {
-        let mut it = Some(frame);
+        self.find_variable(frame, name).unwrap()
+    }
+
+    pub fn find_variable(&self, frame: ir::Label, name: &'a str) -> Option<&ir::Value> {
+        let mut it = Some(self.get_frame(frame));
 
         while let Some(frame_no) = it {
             let frame = &self.frames[&frame_no];
             match frame.locals.get(name) {
-                Some(v) => return v,
+                Some(v) => return Some(v),
                 None => it = frame.parent,
             }
         }
 
-        unreachable!()
+        None
+    }
+
+    // value of a variable visible before the loop (with given proxy env of its body)
+    // at some point of the body; the body could define a variable shadowing it
+    pub fn get_variable_in_loop(
+        &self,
+        frame: ir::Label,
+        loop_proxy: ir::Label,
+        name: &'a str,
+    ) -> &ir::Value {
+        let frame = self.get_frame(frame);
+        let mut shadowing_frame = None;
+        let mut it = frame;
+        while it != loop_proxy {
+            let frame = &self.frames[&it];
+            if !frame.is_proxy_copy && frame.locals.contains_key(name) {
+                shadowing_frame = Some(it);
+            }
+            it = frame.parent.unwrap();
+        }
+
+        match shadowing_frame {
+            Some(label) => self.get_variable(self.frames[&label].parent.unwrap(), name),
+            None => self.get_variable(frame, name),
+        }
     }
 
     pub fn get_function_type(&self, name: &str) -> ir::Type {
@@ -148,51 +201,108 @@ impl<'a> Env<'a> {
         ir::Type::from_function_desc(&desc)
     }
 
-    fn get_all_visible_local_variables(&self, frame: ir::Label) -> HashSet<&'a str> {
-        let mut names = HashSet::new();
-        let mut it = Some(frame);
-
-        while let Some(frame_no) = it {
-            let frame = &self.frames[&frame_no];
-            names.extend(frame.locals.keys());
-            it = frame.parent;
+    // variables assigned below the proxy (a block being a proxy for itself has none)
+    fn get_proxy_variables(&self, proxy: ir::Label) -> Vec<&'a str> {
+        let frame = &self.frames[&self.get_frame(proxy)];
+        if !frame.is_proxy_copy {
+            return vec![];
         }
-
+        let mut names: Vec<_> = frame.locals.keys().cloned().collect();
+        names.sort();
         names
     }
 }
 
+// loop enclosing currently processed statement, targets of break and continue
+struct LoopContext<'a> {
+    label: Option<&'a str>,
+    continue_target: ir::Label,
+    break_target: Option<ir::Label>, // allocated with first break for while (true)
+    break_target_parent: ir::Label,
+    proxy_label: ir::Label,
+    names: Vec<&'a str>, // variables visible before the loop, in order of stub info
+    // source blocks of the jumps and values of the variables there
+    continues: Vec<(ir::Label, Vec<ir::Value>)>,
+    breaks: Vec<(ir::Label, Vec<ir::Value>)>,
+}
+
+impl<'a> LoopContext<'a> {
+    fn new(
+        label: &'a Option<ast::Ident>,
+        continue_target: ir::Label,
+        break_target: Option<ir::Label>,
+        break_target_parent: ir::Label,
+        proxy_label: ir::Label,
+        stub_info: &[(&'a str, ir::Value, ir::Value)],
+    ) -> Self {
+        LoopContext {
+            label: label.as_ref().map(|l| l.inner.as_str()),
+            continue_target,
+            break_target,
+            break_target_parent,
+            proxy_label,
+            names: stub_info.iter().map(|(name, _, _)| *name).collect(),
+            continues: vec![],
+            breaks: vec![],
+        }
+    }
+}
+
 pub struct FunctionCodeGen<'a> {
-    global_strings: &'a mut HashMap<String, ir::GlobalStrNum>,
+    global_strings: &'a mut ir::GlobalStrings,
     class_registry: &'a ClassRegistry<'a>,
+    options: &'a CompilerOptions,
     env: Env<'a>,
     blocks: Vec<ir::Block>,
     next_reg_num: ir::RegNum,
+    loops: Vec<LoopContext<'a>>,
+    fun_name: String, // for the trace
+    fun_span: ast::Span,
+    ret_type: ir::Type,
+    stmt_span: Option<ast::Span>, // the innermost statement being generated
 }
 
 impl<'a> FunctionCodeGen<'a> {
     pub fn new(
         gctx: &'a GlobalContext,
         cctx: Option<&'a ClassDesc>,
-        global_strings: &'a mut HashMap<String, ir::GlobalStrNum>,
+        global_strings: &'a mut ir::GlobalStrings,
         class_registry: &'a ClassRegistry<'a>,
+        options: &'a CompilerOptions,
     ) -> Self {
         FunctionCodeGen {
             global_strings,
             class_registry,
+            options,
             env: Env::new(gctx, cctx),
             blocks: vec![],
             next_reg_num: ir::RegNum(0),
+            loops: vec![],
+            fun_name: String::new(),
+            fun_span: ast::EMPTY_SPAN,
+            ret_type: ir::Type::Void,
+            stmt_span: None,
         }
     }
 
     pub fn generate_function_ir(mut self, fun_def: &'a ast::FunDef) -> ir::Function {
         let mut ir_args = vec![];
         let fun_name: String;
+        let fun_desc = match self.env.class_ctx {
+            Some(cctx) => match cctx.get_item(self.env.global_ctx, &fun_def.name.inner) {
+                Some(TypeWrapper::Fun(fun_desc)) => fun_desc,
+                _ => unreachable!(),
+            },
+            None => self
+                .env
+                .global_ctx
+                .get_function_description(&fun_def.name.inner)
+                .unwrap(),
+        };
         {
             let mut add_to_args = |self_: &mut Self, arg_type: ir::Type, arg_name| {
                 let reg_num = self_.get_new_reg_num();
-                let arg_val = ir::Value::Register(reg_num, arg_type.clone());
+                let arg_val = ir::Value::Register(reg_num, arg_type);
                 ir_args.push((reg_num, arg_type));
                 self_
                     .env
@@ -209,6 +319,7 @@ impl<'a> FunctionCodeGen<'a> {
             } else {
                 fun_name = fun_def.name.inner.to_string();
             }
+            self.fun_name = fun_name.clone();
 
             for (ast_type, ast_ident) in &fun_def.args {
                 add_to_args(
@@ -218,28 +329,119 @@ impl<'a> FunctionCodeGen<'a> {
                 );
             }
 
-            let entry_point = self.allocate_new_block(ARGS_LABEL);
+            self.fun_span = fun_def.span;
+            self.ret_type = ir::Type::from_ast(&fun_def.ret_type.inner);
+            let mut entry_point = self.allocate_new_block(ARGS_LABEL);
+            if self.options.check_stack {
+                entry_point = self.generate_stack_check(entry_point);
+            }
             let last_label = self.process_block(&fun_def.body, entry_point, false);
             if last_label != UNREACHABLE_LABEL {
-                self.get_block(last_label)
-                    .body
-                    .push(ir::Operation::Return(None));
+                self.set_terminator(last_label, ir::Terminator::Return(None));
             }
         }
 
+        let attributes = get_function_attributes(&fun_name, fun_desc);
         ir::Function {
-            ret_type: ir::Type::from_ast(&fun_def.ret_type.inner),
+            ret_type: self.ret_type,
             name: fun_name,
             args: ir_args,
             blocks: self.blocks,
+            inline_hint: fun_desc.inline_hint,
+            purity: ir::Purity::Impure,
+            always_returns: false,
+            never_recurses: false,
+            attributes,
         }
     }
 
+    // compares the frame address with the bound calculated by the runtime
+    // (the stack grows down), returns label of the block after the check
+    fn generate_stack_check(&mut self, cur_label: ir::Label) -> ir::Label {
+        let ptr_type = ir::Type::ptr(ir::Type::Char);
+        let frame_reg = self.get_new_reg_num();
+        let limit_reg = self.get_new_reg_num();
+        let cmp_reg = self.get_new_reg_num();
+        let frame_fun_type = ir::Type::ptr(ir::Type::func(ptr_type, vec![ir::Type::Int]));
+        let limit_value =
+            ir::Value::GlobalRegister("_bltn_stack_limit".to_string(), ir::Type::ptr(ptr_type));
+        let body = &mut self.get_block(cur_label).body;
+        body.push(ir::Operation::FunctionCall(
+            Some(frame_reg),
+            ptr_type,
+            ir::Value::GlobalRegister("llvm.frameaddress.p0i8".to_string(), frame_fun_type),
+            vec![ir::Value::LitInt(0)],
+        ));
+        body.push(ir::Operation::Load(limit_reg, limit_value));
+        // addresses of the stack are below 2^47, so a signed comparison is fine
+        body.push(ir::Operation::Compare(
+            cmp_reg,
+            ir::CmpOp::LT,
+            ir::Value::Register(frame_reg, ptr_type),
+            ir::Value::Register(limit_reg, ptr_type),
+        ));
+
+        let overflow_label = self.allocate_continuation_block(cur_label);
+        let next_label = self.allocate_continuation_block(cur_label);
+        self.add_branch2_op(
+            cur_label,
+            ir::Value::Register(cmp_reg, ir::Type::Bool),
+            overflow_label,
+            next_label,
+            ir::BranchHint::Unlikely,
+        );
+        let error_fun_type = ir::Type::ptr(ir::Type::func(ir::Type::Void, vec![]));
+        self.get_block(overflow_label)
+            .body
+            .push(ir::Operation::FunctionCall(
+                None,
+                ir::Type::Void,
+                ir::Value::GlobalRegister("_bltn_stack_overflow_error".to_string(), error_fun_type),
+                vec![],
+            ));
+        // the error function doesn't return, but the block needs a terminator
+        self.add_branch1_op(overflow_label, next_label);
+        next_label
+    }
+
+    // the code after it is dead, it's removed by the optimizer
+    fn add_null_dereference_error(&mut self, cur_label: ir::Label) {
+        let error_fun_type = ir::Type::ptr(ir::Type::func(ir::Type::Void, vec![]));
+        self.get_block(cur_label)
+            .body
+            .push(ir::Operation::FunctionCall(
+                None,
+                ir::Type::Void,
+                ir::Value::GlobalRegister(
+                    "_bltn_null_dereference_error".to_string(),
+                    error_fun_type,
+                ),
+                vec![],
+            ));
+    }
+
     fn process_block(
         &mut self,
         block: &'a ast::Block,
         parent_label: ir::Label,
         allocate_new_label: bool,
+    ) -> ir::Label {
+        // after the statements of the block the enclosing one is current again
+        let outer_span = self.stmt_span;
+        let end_label = self.process_block_stmts(block, parent_label, allocate_new_label);
+        self.stmt_span = outer_span;
+        ice::enter_statement(outer_span);
+        if end_label != UNREACHABLE_LABEL {
+            self.mark_source_span(end_label);
+        }
+        end_label
+    }
+
+    fn process_block_stmts(
+        &mut self,
+        block: &'a ast::Block,
+        parent_label: ir::Label,
+        allocate_new_label: bool,
     ) -> ir::Label {
         let mut cur_label = if allocate_new_label {
             let new_label = self.allocate_new_block(parent_label);
@@ -250,6 +452,9 @@ impl<'a> FunctionCodeGen<'a> {
         };
 
         for stmt in &block.stmts {
+            self.stmt_span = Some(stmt.span);
+            ice::enter_statement(self.stmt_span);
+            self.mark_source_span(cur_label);
             use model::ast::InnerStmt::*;
             match &stmt.inner {
                 Empty => (),
@@ -258,7 +463,7 @@ impl<'a> FunctionCodeGen<'a> {
                     if end_block_label == UNREACHABLE_LABEL {
                         return UNREACHABLE_LABEL;
                     }
-                    let cont_label = self.allocate_new_block(cur_label);
+                    let cont_label = self.allocate_continuation_block(cur_label);
                     self.add_branch1_op(end_block_label, cont_label);
                     cur_label = cont_label;
                 }
@@ -272,7 +477,8 @@ impl<'a> FunctionCodeGen<'a> {
                                 let (new_label, value) =
                                     self.process_expression(&expr.inner, cur_label);
                                 cur_label = new_label;
-                                value
+                                let var_type = ir::Type::from_ast(&var_type.inner);
+                                self.generate_coercion(cur_label, value, var_type)
                             }
                             None => {
                                 use model::ast::InnerType::*;
@@ -282,7 +488,11 @@ impl<'a> FunctionCodeGen<'a> {
                                     String | Array(_) | Class(_) => ir::Value::LitNullPtr(Some(
                                         ir::Type::from_ast(&var_type.inner),
                                     )),
-                                    Null | Void => unreachable!(),
+                                    Struct(_) => ir::Value::LitZeroStruct(ir::Type::from_ast(
+                                        &var_type.inner,
+                                    )),
+                                    Null | Void | GenericClass(..) | TypeParam(_)
+                                    | Function(..) => unreachable!(),
                                 }
                             }
                         };
@@ -291,63 +501,13 @@ impl<'a> FunctionCodeGen<'a> {
                     }
                 }
                 Assign(lhs, rhs) => {
-                    let (new_label, rhs_value) = self.process_expression(&rhs.inner, cur_label);
-                    cur_label = new_label;
-                    use model::ast::InnerExpr::*;
-                    match &lhs.inner {
-                        LitVar(var_name) => {
-                            self.env
-                                .update_existing_local_variable(cur_label, &var_name, rhs_value);
-                        }
-                        ArrayElem { .. } | ObjField { .. } => {
-                            let (new_label, ref_val) =
-                                self.process_lvalue_ref_expression(&lhs.inner, cur_label);
-                            cur_label = new_label;
-                            self.get_block(cur_label)
-                                .body
-                                .push(ir::Operation::Store(rhs_value, ref_val));
-                        }
-                        _ => unreachable!(),
-                    };
+                    cur_label = self.process_assignment(&lhs.inner, &rhs.inner, cur_label).0;
                 }
                 Incr(lhs) | Decr(lhs) => {
-                    let op = match &stmt.inner {
-                        Incr(_) => ir::ArithOp::Add,
-                        Decr(_) => ir::ArithOp::Sub,
-                        _ => unreachable!(),
-                    };
-                    use model::ast::InnerExpr::*;
-                    match &lhs.inner {
-                        LitVar(var_name) => {
-                            let new_reg = self.get_new_reg_num();
-                            let val_l = self.env.get_variable(cur_label, var_name).clone();
-                            let val_r = ir::Value::LitInt(1);
-                            self.get_block(cur_label)
-                                .body
-                                .push(ir::Operation::Arithmetic(new_reg, op, val_l, val_r));
-                            let val_res = ir::Value::Register(new_reg, ir::Type::Int);
-                            self.env
-                                .update_existing_local_variable(cur_label, &var_name, val_res);
-                        }
-                        ArrayElem { .. } | ObjField { .. } => {
-                            let (new_label, ref_val) =
-                                self.process_lvalue_ref_expression(&lhs.inner, cur_label);
-                            cur_label = new_label;
-                            let loaded_reg = self.get_new_reg_num();
-                            let changed_reg = self.get_new_reg_num(); // after +/- 1
-                            let body = &mut self.get_block(cur_label).body;
-                            body.push(ir::Operation::Load(loaded_reg, ref_val.clone()));
-                            body.push(ir::Operation::Arithmetic(
-                                changed_reg,
-                                op,
-                                ir::Value::Register(loaded_reg, ir::Type::Int),
-                                ir::Value::LitInt(1),
-                            ));
-                            let changed_value = ir::Value::Register(changed_reg, ir::Type::Int);
-                            body.push(ir::Operation::Store(changed_value, ref_val));
-                        }
-                        _ => unreachable!(),
-                    };
+                    let is_incr = matches!(stmt.inner, Incr(_));
+                    cur_label = self
+                        .process_incr_decr(&lhs.inner, is_incr, true, cur_label)
+                        .0;
                 }
                 Ret(opt_expr) => {
                     let mut opt_value = opt_expr.as_ref().map(|expr| {
@@ -357,46 +517,60 @@ impl<'a> FunctionCodeGen<'a> {
                     });
                     opt_value = match opt_value {
                         Some(ir::Value::Register(_, ir::Type::Void)) => None,
-                        _ => opt_value,
+                        Some(value) => {
+                            Some(self.generate_coercion(cur_label, value, self.ret_type))
+                        }
+                        None => None,
                     };
-                    self.get_block(cur_label)
-                        .body
-                        .push(ir::Operation::Return(opt_value));
+                    self.set_terminator(cur_label, ir::Terminator::Return(opt_value));
                     return UNREACHABLE_LABEL;
                 }
                 Cond {
                     cond,
                     true_branch,
                     false_branch,
-                } => match &cond.inner {
-                    ast::InnerExpr::LitBool(true) => {
+                } if self.is_select_assignment(cur_label, cond, true_branch, false_branch) => {
+                    cur_label =
+                        self.process_select_assignment(cur_label, cond, true_branch, false_branch);
+                }
+                Cond {
+                    cond,
+                    true_branch,
+                    false_branch,
+                } => match get_const_cond_value(&cond.inner) {
+                    Some(true) => {
                         let end_true_label = self.process_block(true_branch, cur_label, true);
                         if end_true_label == UNREACHABLE_LABEL {
                             return UNREACHABLE_LABEL;
                         }
-                        let cont_label = self.allocate_new_block(cur_label);
+                        let cont_label = self.allocate_continuation_block(cur_label);
                         self.add_branch1_op(end_true_label, cont_label);
                         cur_label = cont_label;
                     }
-                    ast::InnerExpr::LitBool(false) => match false_branch {
+                    Some(false) => match false_branch {
                         Some(bl) => {
                             let end_false_label = self.process_block(bl, cur_label, true);
                             if end_false_label == UNREACHABLE_LABEL {
                                 return UNREACHABLE_LABEL;
                             }
-                            let cont_label = self.allocate_new_block(cur_label);
+                            let cont_label = self.allocate_continuation_block(cur_label);
                             self.add_branch1_op(end_false_label, cont_label);
                             cur_label = cont_label;
                         }
                         None => (),
                     },
-                    expr => match false_branch {
+                    None => match false_branch {
                         None => {
                             let true_label = self.allocate_new_block(cur_label);
                             let false_label = self.allocate_new_block(cur_label); // simplifies calculation of phi function
-                            let cont_label = self.allocate_new_block(cur_label);
-                            self.process_expression_cond(&expr, cur_label, true_label, false_label);
-                            let true_proxy_label = self.env.create_proxy_env(true_label);
+                            let cont_label = self.allocate_continuation_block(cur_label);
+                            self.process_expression_cond(
+                                &cond.inner,
+                                cur_label,
+                                true_label,
+                                false_label,
+                            );
+                            let true_proxy_label = self.create_proxy_env(true_label);
                             let end_true_label = self.process_block(true_branch, true_label, false);
                             self.add_branch1_op(false_label, cont_label);
                             if end_true_label != UNREACHABLE_LABEL {
@@ -414,9 +588,14 @@ impl<'a> FunctionCodeGen<'a> {
                         Some(bl) => {
                             let true_label = self.allocate_new_block(cur_label);
                             let false_label = self.allocate_new_block(cur_label);
-                            self.process_expression_cond(&expr, cur_label, true_label, false_label);
-                            let true_proxy_label = self.env.create_proxy_env(true_label);
-                            let false_proxy_label = self.env.create_proxy_env(false_label);
+                            self.process_expression_cond(
+                                &cond.inner,
+                                cur_label,
+                                true_label,
+                                false_label,
+                            );
+                            let true_proxy_label = self.create_proxy_env(true_label);
+                            let false_proxy_label = self.create_proxy_env(false_label);
                             let end_true_label = self.process_block(true_branch, true_label, false);
                             let end_false_label = self.process_block(bl, false_label, false);
                             match (
@@ -425,19 +604,19 @@ impl<'a> FunctionCodeGen<'a> {
                             ) {
                                 (true, true) => return UNREACHABLE_LABEL,
                                 (true, false) => {
-                                    let cont_label = self.allocate_new_block(cur_label);
+                                    let cont_label = self.allocate_continuation_block(cur_label);
                                     self.add_branch1_op(end_false_label, cont_label);
-                                    self.env.apply_proxy_env(false_proxy_label, cont_label);
+                                    self.apply_proxy_env(false_proxy_label, cont_label);
                                     cur_label = cont_label;
                                 }
                                 (false, true) => {
-                                    let cont_label = self.allocate_new_block(cur_label);
+                                    let cont_label = self.allocate_continuation_block(cur_label);
                                     self.add_branch1_op(end_true_label, cont_label);
-                                    self.env.apply_proxy_env(true_proxy_label, cont_label);
+                                    self.apply_proxy_env(true_proxy_label, cont_label);
                                     cur_label = cont_label;
                                 }
                                 (false, false) => {
-                                    let cont_label = self.allocate_new_block(cur_label);
+                                    let cont_label = self.allocate_continuation_block(cur_label);
                                     self.add_branch1_op(end_false_label, cont_label);
                                     self.add_branch1_op(end_true_label, cont_label);
                                     self.calculate_phi_set_for_if(
@@ -453,109 +632,175 @@ impl<'a> FunctionCodeGen<'a> {
                         }
                     },
                 },
-                While(cond, block) => match &cond.inner {
-                    ast::InnerExpr::LitBool(false) => (),
-                    ast::InnerExpr::LitBool(true) => {
+                While { label, cond, body } => match get_const_cond_value(&cond.inner) {
+                    Some(false) => (),
+                    Some(true) => {
                         let body_label = self.allocate_new_block(cur_label);
-                        let stub_info =
-                            self.prepare_env_and_stub_phi_set_for_loop_cond(cur_label, body_label);
+                        let stub_info = self.prepare_env_and_stub_phi_set_for_loop_cond(
+                            cur_label, body_label, None, body,
+                        );
+                        let proxy_label = self.create_proxy_env(body_label);
                         self.add_branch1_op(cur_label, body_label);
-                        let mut end_body_label = self.process_block(block, body_label, false);
-                        if end_body_label != UNREACHABLE_LABEL {
-                            self.add_branch1_op(end_body_label, body_label);
+                        // the loop is left only by break, so block after it is allocated lazily
+                        self.loops.push(LoopContext::new(
+                            label,
+                            body_label,
+                            None,
+                            cur_label,
+                            proxy_label,
+                            &stub_info,
+                        ));
+                        let end_body_label = self.process_block(body, body_label, false);
+                        let loop_ctx = self.finish_loop_body(end_body_label);
+                        self.finalize_phi_set_for_loop_cond(
+                            cur_label,
+                            body_label,
+                            &loop_ctx.continues,
+                            stub_info,
+                        );
+                        match loop_ctx.break_target {
+                            Some(cont_label) => {
+                                self.calculate_phi_set_for_loop_exit(cont_label, &loop_ctx);
+                                cur_label = cont_label;
+                            }
+                            None => return UNREACHABLE_LABEL,
                         }
-                        self.finalize_phi_set_for_loop_cond(cur_label, body_label, None, stub_info);
-                        return UNREACHABLE_LABEL;
                     }
-                    expr => {
-                        let cond_label = self.allocate_new_block(cur_label);
-                        let stub_info =
-                            self.prepare_env_and_stub_phi_set_for_loop_cond(cur_label, cond_label);
+                    None => {
+                        let cond_label = self.allocate_continuation_block(cur_label);
+                        let stub_info = self.prepare_env_and_stub_phi_set_for_loop_cond(
+                            cur_label,
+                            cond_label,
+                            Some(cond),
+                            body,
+                        );
                         // cond_label is just fine for body_label and cond_label
                         // they will see phi functions and local variables
                         // can't be changed further in condition block
                         let body_label = self.allocate_new_block(cond_label);
-                        let cont_label = self.allocate_new_block(cond_label);
-                        let proxy_label = self.env.create_proxy_env(body_label);
+                        let cont_label = self.allocate_continuation_block(cond_label);
+                        let proxy_label = self.create_proxy_env(body_label);
                         self.add_branch1_op(cur_label, cond_label);
-                        self.process_expression_cond(expr, cond_label, body_label, cont_label);
-                        let mut end_body_label = self.process_block(block, body_label, false);
-                        if end_body_label != UNREACHABLE_LABEL {
-                            self.add_branch1_op(end_body_label, cond_label);
-                        }
+                        self.process_expression_cond(
+                            &cond.inner,
+                            cond_label,
+                            body_label,
+                            cont_label,
+                        );
+                        self.loops.push(LoopContext::new(
+                            label,
+                            cond_label,
+                            Some(cont_label),
+                            cond_label,
+                            proxy_label,
+                            &stub_info,
+                        ));
+                        let end_body_label = self.process_block(body, body_label, false);
+                        let loop_ctx = self.finish_loop_body(end_body_label);
                         self.finalize_phi_set_for_loop_cond(
                             cur_label,
                             cond_label,
-                            Some(proxy_label),
+                            &loop_ctx.continues,
                             stub_info,
                         );
+                        self.calculate_phi_set_for_loop_exit(cont_label, &loop_ctx);
                         cur_label = cont_label;
                     }
                 },
                 // could be syntax sugar, but it introduces other problems
                 ForEach {
+                    label,
                     iter_type,
                     iter_name,
                     array,
                     body,
                 } => {
-                    // calculate array
-                    let (new_label, arr_val) = self.process_expression(&array.inner, cur_label);
-                    cur_label = new_label;
-                    let arr_type = arr_val.get_type();
                     let elem_type = ir::Type::from_ast(&iter_type.inner);
+                    let (start_val, length_val, arr_val) = match &array.inner {
+                        // a range is iterated by the counter of the loop (without an array)
+                        ast::InnerExpr::Range(start, end) => {
+                            let (new_label, start_val) =
+                                self.process_expression(&start.inner, cur_label);
+                            let (new_label, end_val) =
+                                self.process_expression(&end.inner, new_label);
+                            cur_label = new_label;
+                            (start_val, end_val, None)
+                        }
+                        _ => {
+                            // calculate array
+                            let (new_label, arr_val) =
+                                self.process_expression(&array.inner, cur_label);
+                            cur_label = new_label;
 
-                    // calculate its length
-                    let length_reg = self.get_new_reg_num();
-                    let length_ref_val = self
-                        .generate_calculation_of_ref_to_array_length(cur_label, arr_val.clone());
-                    self.get_block(cur_label)
-                        .body
-                        .push(ir::Operation::Load(length_reg, length_ref_val));
-                    let length_val = ir::Value::Register(length_reg, ir::Type::Int);
-
-                    // calc base+length=end
-                    let end_ptr_reg = self.get_new_reg_num();
-                    self.get_block(cur_label)
-                        .body
-                        .push(ir::Operation::GetElementPtr(
-                            end_ptr_reg,
-                            elem_type.clone(),
-                            vec![arr_val.clone(), length_val],
-                        ));
-                    let end_ptr_val = ir::Value::Register(end_ptr_reg, arr_type.clone());
+                            // calculate its length
+                            let length_reg = self.get_new_reg_num();
+                            let length_ref_val = self.generate_calculation_of_ref_to_array_length(
+                                cur_label,
+                                arr_val.clone(),
+                            );
+                            self.get_block(cur_label)
+                                .body
+                                .push(ir::Operation::Load(length_reg, length_ref_val));
+                            let length_val = ir::Value::Register(length_reg, ir::Type::Int);
+                            (ir::Value::LitInt(0), length_val, Some(arr_val))
+                        }
+                    };
 
-                    // loop: while it<end { name=*it; it++; <body> }
-                    let cond_label = self.allocate_new_block(cur_label);
-                    let stub_info =
-                        self.prepare_env_and_stub_phi_set_for_loop_cond(cur_label, cond_label);
+                    // loop: while i<length { name=arr[i]; i++; <body> }
+                    // (the optimizer turns the index into a pointer increment),
+                    // for a range: while i<end { name=i; i++; <body> }
+                    let cond_label = self.allocate_continuation_block(cur_label);
+                    let stub_info = self.prepare_env_and_stub_phi_set_for_loop_cond(
+                        cur_label, cond_label, None, body,
+                    );
                     let body_label = self.allocate_new_block(cond_label);
-                    let cont_label = self.allocate_new_block(cond_label);
-                    let proxy_label = self.env.create_proxy_env(body_label);
+                    let cont_label = self.allocate_continuation_block(cond_label);
+                    let proxy_label = self.create_proxy_env(body_label);
                     self.add_branch1_op(cur_label, cond_label);
 
                     // loop cond
-                    let cur_it_reg = self.get_new_reg_num();
-                    let next_it_reg = self.get_new_reg_num();
+                    let cur_idx_reg = self.get_new_reg_num();
+                    let next_idx_reg = self.get_new_reg_num();
                     let cond_reg = self.get_new_reg_num();
-                    let cur_it_val = ir::Value::Register(cur_it_reg, arr_type.clone());
-                    let next_it_val = ir::Value::Register(next_it_reg, arr_type.clone());
+                    let cur_idx_val = ir::Value::Register(cur_idx_reg, ir::Type::Int);
+                    let next_idx_val = ir::Value::Register(next_idx_reg, ir::Type::Int);
                     let cond_val = ir::Value::Register(cond_reg, ir::Type::Bool);
                     self.get_block(cond_label).body.push(ir::Operation::Compare(
                         cond_reg,
                         ir::CmpOp::LT,
-                        cur_it_val.clone(),
-                        end_ptr_val,
+                        cur_idx_val.clone(),
+                        length_val,
                     ));
-                    self.add_branch2_op(cond_label, cond_val, body_label, cont_label);
+                    self.add_branch2_op(
+                        cond_label,
+                        cond_val,
+                        body_label,
+                        cont_label,
+                        ir::BranchHint::None,
+                    );
 
                     // loop body
-                    let loaded_iter_reg = self.get_new_reg_num();
-                    let loaded_iter_val = ir::Value::Register(loaded_iter_reg, elem_type.clone());
-                    self.get_block(body_label)
-                        .body
-                        .push(ir::Operation::Load(loaded_iter_reg, cur_it_val.clone()));
-                    let loop_iter_env_label = self.env.insert_empty_proxy_frame(body_label);
+                    let loaded_iter_val = match arr_val {
+                        Some(arr_val) => {
+                            let arr_type = arr_val.get_type();
+                            let elem_ptr_reg = self.get_new_reg_num();
+                            let loaded_iter_reg = self.get_new_reg_num();
+                            self.get_block(body_label)
+                                .body
+                                .push(ir::Operation::GetElementPtr(
+                                    elem_ptr_reg,
+                                    elem_type,
+                                    vec![arr_val, cur_idx_val.clone()],
+                                ));
+                            self.get_block(body_label).body.push(ir::Operation::Load(
+                                loaded_iter_reg,
+                                ir::Value::Register(elem_ptr_reg, arr_type),
+                            ));
+                            ir::Value::Register(loaded_iter_reg, elem_type)
+                        }
+                        None => cur_idx_val.clone(),
+                    };
+                    let loop_iter_env_label = self.insert_empty_proxy_frame(body_label);
                     self.env.add_new_local_variable(
                         loop_iter_env_label,
                         &iter_name.inner,
@@ -563,33 +808,69 @@ impl<'a> FunctionCodeGen<'a> {
                     );
                     self.get_block(body_label)
                         .body
-                        .push(ir::Operation::GetElementPtr(
-                            next_it_reg,
-                            elem_type,
-                            vec![cur_it_val, ir::Value::LitInt(1)],
+                        .push(ir::Operation::Arithmetic(
+                            next_idx_reg,
+                            ir::ArithOp::Add,
+                            cur_idx_val,
+                            ir::Value::LitInt(1),
                         ));
+                    self.loops.push(LoopContext::new(
+                        label,
+                        cond_label,
+                        Some(cont_label),
+                        cond_label,
+                        proxy_label,
+                        &stub_info,
+                    ));
                     let end_body_label = self.process_block(body, body_label, false);
-                    let mut phi_vec = vec![(arr_val, cur_label)]; // for iter ptr
-                    if end_body_label != UNREACHABLE_LABEL {
-                        self.add_branch1_op(end_body_label, cond_label);
-                        phi_vec.push((next_it_val, end_body_label));
+                    let loop_ctx = self.finish_loop_body(end_body_label);
+                    let mut phi_vec = vec![(start_val, cur_label)]; // for index
+                    for (src_label, _) in &loop_ctx.continues {
+                        phi_vec.push((next_idx_val.clone(), *src_label));
                     }
                     self.finalize_phi_set_for_loop_cond(
                         cur_label,
                         cond_label,
-                        Some(proxy_label),
+                        &loop_ctx.continues,
                         stub_info,
                     );
-                    self.get_block(cond_label)
-                        .phi_set
-                        .insert((cur_it_reg, arr_type, phi_vec));
+                    self.get_block(cond_label).add_phi(ir::Phi::new(
+                        cur_idx_reg,
+                        ir::Type::Int,
+                        phi_vec,
+                    ));
+                    self.calculate_phi_set_for_loop_exit(cont_label, &loop_ctx);
                     cur_label = cont_label;
                 }
+                Break(label) => {
+                    let idx = self.find_loop(label);
+                    let values = self.get_loop_variables(cur_label, &self.loops[idx]);
+                    let target = match self.loops[idx].break_target {
+                        Some(target) => target,
+                        None => {
+                            let parent_label = self.loops[idx].break_target_parent;
+                            let target = self.allocate_continuation_block(parent_label);
+                            self.loops[idx].break_target = Some(target);
+                            target
+                        }
+                    };
+                    self.add_branch1_op(cur_label, target);
+                    self.loops[idx].breaks.push((cur_label, values));
+                    return UNREACHABLE_LABEL;
+                }
+                Continue(label) => {
+                    let idx = self.find_loop(label);
+                    let values = self.get_loop_variables(cur_label, &self.loops[idx]);
+                    let target = self.loops[idx].continue_target;
+                    self.add_branch1_op(cur_label, target);
+                    self.loops[idx].continues.push((cur_label, values));
+                    return UNREACHABLE_LABEL;
+                }
                 Expr(expr) => {
                     let (new_label, _) = self.process_expression(&expr.inner, cur_label);
                     cur_label = new_label;
                 }
-                Error => unreachable!(),
+                NestedFun(_) | Error => unreachable!(), // nested functions are lifted by semantic analysis
             }
         }
         // todo (optional) expressions / statements from code in comments (extract from AST)
@@ -600,51 +881,114 @@ impl<'a> FunctionCodeGen<'a> {
 
     fn process_expression_cond(
         &mut self,
-        expr: &ast::InnerExpr,
+        expr: &'a ast::InnerExpr,
         cur_label: ir::Label,
         true_label: ir::Label,
         false_label: ir::Label,
     ) {
         use model::ast::{BinaryOp::*, InnerExpr::*, InnerUnaryOp::*};
         match expr {
-            BinaryOp(lhs, And, rhs) => {
-                let mid_label = self.allocate_new_block(cur_label);
-                self.process_expression_cond(&lhs.inner, cur_label, mid_label, false_label);
-                self.process_expression_cond(&rhs.inner, mid_label, true_label, false_label);
-            }
-            BinaryOp(lhs, Or, rhs) => {
-                let mid_label = self.allocate_new_block(cur_label);
-                self.process_expression_cond(&lhs.inner, cur_label, true_label, mid_label);
-                self.process_expression_cond(&rhs.inner, mid_label, true_label, false_label);
-            }
+            // known operands don't need their blocks; the one which is always
+            // evaluated still needs its side effects if it decides nothing;
+            // the right operand assigning variables is evaluated like an if
+            BinaryOp(lhs, And, rhs) if !assigns_local_vars(&rhs.inner) => match (
+                get_const_cond_value(&lhs.inner),
+                get_const_cond_value(&rhs.inner),
+            ) {
+                (Some(true), _) => {
+                    self.process_expression_cond(&rhs.inner, cur_label, true_label, false_label)
+                }
+                (Some(false), _) => self.add_branch1_op(cur_label, false_label),
+                (None, Some(true)) => {
+                    self.process_expression_cond(&lhs.inner, cur_label, true_label, false_label)
+                }
+                (None, Some(false)) => {
+                    let (new_label, _) = self.process_expression(&lhs.inner, cur_label);
+                    self.add_branch1_op(new_label, false_label);
+                }
+                (None, None) => {
+                    let mid_label = self.allocate_continuation_block(cur_label);
+                    self.process_expression_cond(&lhs.inner, cur_label, mid_label, false_label);
+                    self.process_expression_cond(&rhs.inner, mid_label, true_label, false_label);
+                }
+            },
+            BinaryOp(lhs, Or, rhs) if !assigns_local_vars(&rhs.inner) => match (
+                get_const_cond_value(&lhs.inner),
+                get_const_cond_value(&rhs.inner),
+            ) {
+                (Some(false), _) => {
+                    self.process_expression_cond(&rhs.inner, cur_label, true_label, false_label)
+                }
+                (Some(true), _) => self.add_branch1_op(cur_label, true_label),
+                (None, Some(false)) => {
+                    self.process_expression_cond(&lhs.inner, cur_label, true_label, false_label)
+                }
+                (None, Some(true)) => {
+                    let (new_label, _) = self.process_expression(&lhs.inner, cur_label);
+                    self.add_branch1_op(new_label, true_label);
+                }
+                (None, None) => {
+                    let mid_label = self.allocate_continuation_block(cur_label);
+                    self.process_expression_cond(&lhs.inner, cur_label, true_label, mid_label);
+                    self.process_expression_cond(&rhs.inner, mid_label, true_label, false_label);
+                }
+            },
             UnaryOp(ast::ItemWithSpan { inner: BoolNeg, .. }, lhs) => {
                 self.process_expression_cond(&lhs.inner, cur_label, false_label, true_label);
             }
             _ => {
                 let (new_label, value) = self.process_expression(&expr, cur_label);
-                self.add_branch2_op(new_label, value, true_label, false_label);
+                self.add_branch2_op(
+                    new_label,
+                    value,
+                    true_label,
+                    false_label,
+                    ir::BranchHint::None,
+                );
             }
         }
     }
 
     fn process_expression(
         &mut self,
-        expr: &ast::InnerExpr,
+        expr: &'a ast::InnerExpr,
+        cur_label: ir::Label,
+    ) -> (ir::Label, ir::Value) {
+        let (new_label, value) = self.process_untraced_expression(expr, cur_label);
+        // implicit casts would repeat the line of their operand
+        let traced = !is_side_effect_free(expr) && !matches!(expr, ast::InnerExpr::CastType(..));
+        if self.options.trace_eval_order && traced {
+            self.add_eval_trace(new_label, expr);
+        }
+        (new_label, value)
+    }
+
+    fn process_untraced_expression(
+        &mut self,
+        expr: &'a ast::InnerExpr,
         cur_label: ir::Label,
     ) -> (ir::Label, ir::Value) {
         let process_fun_call = |self_: &mut Self,
                                 function_value: ir::Value,
                                 this_ptr: Option<ir::Value>,
-                                args: &Vec<Box<ast::Expr>>,
+                                args: &'a Vec<Box<ast::Expr>>,
                                 cur_label: ir::Label| {
-            let fun_ret_type = match &function_value {
-                ir::Value::Register(_, ir::Type::Ptr(t))
-                | ir::Value::GlobalRegister(_, ir::Type::Ptr(t)) => match &**t {
-                    ir::Type::Func(t, _) => (**t).clone(),
-                    _ => unreachable!(),
-                },
-                _ => unreachable!(),
+            let fun_type = function_value.get_type();
+            let (fun_ret_type, params_types) = match fun_type.pointee().map(|t| t.kind()) {
+                Some(ir::TypeKind::Func(ret_type, params_types)) => (*ret_type, params_types),
+                _ => panic!(
+                    "call of {} of non-function type {}",
+                    function_value, fun_type
+                ),
             };
+            let args_cnt = args.len() + this_ptr.iter().count();
+            assert!(
+                args_cnt == params_types.len(),
+                "call of {} of type {} with {} arguments",
+                function_value,
+                fun_type,
+                args_cnt
+            );
             let mut args_values = vec![];
             args_values.extend(this_ptr);
 
@@ -654,6 +998,11 @@ impl<'a> FunctionCodeGen<'a> {
                 cur_label = new_label;
                 args_values.push(value);
             }
+            let args_values = args_values
+                .into_iter()
+                .zip(params_types)
+                .map(|(value, param_type)| self_.generate_coercion(cur_label, value, *param_type))
+                .collect();
 
             let reg_num = self_.get_new_reg_num();
             let op_reg_num = match fun_ret_type {
@@ -666,7 +1015,7 @@ impl<'a> FunctionCodeGen<'a> {
                 .body
                 .push(ir::Operation::FunctionCall(
                     op_reg_num,
-                    fun_ret_type.clone(),
+                    fun_ret_type,
                     function_value,
                     args_values,
                 ));
@@ -683,7 +1032,7 @@ impl<'a> FunctionCodeGen<'a> {
             LitBool(bool_val) => (cur_label, ir::Value::LitBool(*bool_val)),
             LitStr(str_val) => {
                 if str_val == "" {
-                    let str_type = ir::Type::Ptr(Box::new(ir::Type::Char));
+                    let str_type = ir::Type::ptr(ir::Type::Char);
                     (cur_label, ir::Value::LitNullPtr(Some(str_type)))
                 } else {
                     let reg_num = self.get_new_reg_num();
@@ -700,7 +1049,7 @@ impl<'a> FunctionCodeGen<'a> {
                         }
                         _ => unreachable!(),
                     }
-                    let str_type = ir::Type::Ptr(Box::new(ir::Type::Char));
+                    let str_type = ir::Type::ptr(ir::Type::Char);
                     let casted_val = ir::Value::Register(reg_num, str_type);
                     (cur_label, casted_val)
                 }
@@ -715,16 +1064,26 @@ impl<'a> FunctionCodeGen<'a> {
                         let new_reg = self.get_new_reg_num();
                         self.get_block(new_label).body.push(ir::Operation::CastPtr {
                             dst: new_reg,
-                            dst_type: dst_type.clone(),
+                            dst_type,
                             src_value: expr_val,
                         });
                         (new_label, ir::Value::Register(new_reg, dst_type))
                     }
                 }
             }
+            // only generic builtins keep type arguments after monomorphization
+            FunCall {
+                function_name,
+                args,
+                type_args,
+            } if !type_args.is_empty() => {
+                let elem_type = ir::Type::from_ast(&type_args[0]);
+                self.process_array_builtin_call(cur_label, &function_name.inner, elem_type, args)
+            }
             FunCall {
                 function_name,
                 args,
+                ..
             } => {
                 let fun_type = self.env.get_function_type(function_name.inner.as_ref());
                 let function_value =
@@ -732,15 +1091,73 @@ impl<'a> FunctionCodeGen<'a> {
                 process_fun_call(self, function_value, None, args, cur_label)
             }
             BinaryOp(lhs, op, rhs) => match op {
+                // both operands can be evaluated, no blocks are needed
+                And | Or if is_side_effect_free(&lhs.inner) && is_side_effect_free(&rhs.inner) => {
+                    let (new_label, lhs_val) = self.process_expression(&lhs.inner, cur_label);
+                    let (new_label, rhs_val) = self.process_expression(&rhs.inner, new_label);
+                    let value = match (op, lhs_val, rhs_val) {
+                        (And, ir::Value::LitBool(false), _)
+                        | (And, _, ir::Value::LitBool(false)) => ir::Value::LitBool(false),
+                        (Or, ir::Value::LitBool(true), _) | (Or, _, ir::Value::LitBool(true)) => {
+                            ir::Value::LitBool(true)
+                        }
+                        (And, ir::Value::LitBool(true), val)
+                        | (And, val, ir::Value::LitBool(true))
+                        | (Or, ir::Value::LitBool(false), val)
+                        | (Or, val, ir::Value::LitBool(false)) => val,
+                        (op, lhs_val, rhs_val) => {
+                            let new_op = match op {
+                                And => ir::ArithOp::And,
+                                _ => ir::ArithOp::Or,
+                            };
+                            let new_reg = self.get_new_reg_num();
+                            self.get_block(new_label)
+                                .body
+                                .push(ir::Operation::Arithmetic(new_reg, new_op, lhs_val, rhs_val));
+                            ir::Value::Register(new_reg, ir::Type::Bool)
+                        }
+                    };
+                    (new_label, value)
+                }
+                // variables assigned in the right operand get phis, like after an if
+                And | Or if assigns_local_vars(&rhs.inner) => {
+                    let rhs_label = self.allocate_new_block(cur_label);
+                    let skip_label = self.allocate_new_block(cur_label);
+                    let cont_label = self.allocate_continuation_block(cur_label);
+                    let skip_value = ir::Value::LitBool(*op == Or);
+                    match op {
+                        And => self
+                            .process_expression_cond(&lhs.inner, cur_label, rhs_label, skip_label),
+                        _ => self
+                            .process_expression_cond(&lhs.inner, cur_label, skip_label, rhs_label),
+                    }
+                    let rhs_proxy_label = self.create_proxy_env(rhs_label);
+                    let (end_rhs_label, rhs_val) = self.process_expression(&rhs.inner, rhs_label);
+                    self.add_branch1_op(end_rhs_label, cont_label);
+                    self.add_branch1_op(skip_label, cont_label);
+                    self.calculate_phi_set_for_if(
+                        cur_label,
+                        cont_label,
+                        (end_rhs_label, rhs_proxy_label),
+                        (skip_label, skip_label),
+                    );
+                    let new_reg = self.get_new_reg_num();
+                    self.get_block(cont_label).add_phi(ir::Phi::new(
+                        new_reg,
+                        ir::Type::Bool,
+                        vec![(rhs_val, end_rhs_label), (skip_value, skip_label)],
+                    ));
+                    (cont_label, ir::Value::Register(new_reg, ir::Type::Bool))
+                }
                 And | Or => {
-                    let true_label = self.allocate_new_block(cur_label);
-                    let false_label = self.allocate_new_block(cur_label);
+                    let true_label = self.allocate_continuation_block(cur_label);
+                    let false_label = self.allocate_continuation_block(cur_label);
                     self.process_expression_cond(&expr, cur_label, true_label, false_label);
-                    let cont_label = self.allocate_new_block(cur_label);
+                    let cont_label = self.allocate_continuation_block(cur_label);
                     self.add_branch1_op(true_label, cont_label);
                     self.add_branch1_op(false_label, cont_label);
                     let new_reg = self.get_new_reg_num();
-                    self.get_block(cont_label).phi_set.insert((
+                    self.get_block(cont_label).add_phi(ir::Phi::new(
                         new_reg,
                         ir::Type::Bool,
                         vec![
@@ -763,23 +1180,29 @@ impl<'a> FunctionCodeGen<'a> {
                                 Mod => ir::ArithOp::Mod,
                                 _ => unreachable!(),
                             };
+                            if (op == &Div || op == &Mod)
+                                && self.options.int_division == IntDivision::Euclidean
+                            {
+                                let value = self.generate_euclidean_division(
+                                    new_label, new_op, lhs_val, rhs_val,
+                                );
+                                return (new_label, value);
+                            }
                             let new_reg = self.get_new_reg_num();
                             self.get_block(new_label)
                                 .body
                                 .push(ir::Operation::Arithmetic(new_reg, new_op, lhs_val, rhs_val));
                             (new_label, ir::Value::Register(new_reg, ir::Type::Int))
                         }
-                        str_type @ ir::Type::Ptr(_) => {
+                        str_type if str_type.is_ptr() => {
                             let new_reg = self.get_new_reg_num();
-                            let fun_type = ir::Type::Ptr(Box::new(ir::Type::Func(
-                                Box::new(str_type.clone()),
-                                vec![str_type.clone(), str_type.clone()],
-                            )));
+                            let fun_type =
+                                ir::Type::ptr(ir::Type::func(str_type, vec![str_type, str_type]));
                             self.get_block(new_label)
                                 .body
                                 .push(ir::Operation::FunctionCall(
                                     Some(new_reg),
-                                    str_type.clone(),
+                                    str_type,
                                     ir::Value::GlobalRegister(
                                         "_bltn_string_concat".to_string(),
                                         fun_type,
@@ -794,8 +1217,26 @@ impl<'a> FunctionCodeGen<'a> {
                 LT | LE | GT | GE | EQ | NE => {
                     let (new_label, lhs_val) = self.process_expression(&lhs.inner, cur_label);
                     let (new_label, rhs_val) = self.process_expression(&rhs.inner, new_label);
-                    match lhs_val.get_type() {
-                        ir::Type::Int | ir::Type::Bool => {
+                    // e.g. `null != obj` compares pointers of the class
+                    let (lhs_val, rhs_val) = match (&lhs_val, &rhs_val) {
+                        (ir::Value::LitNullPtr(_), ir::Value::LitNullPtr(_)) => {
+                            let lhs_type = lhs_val.get_type();
+                            (
+                                lhs_val.with_null_type(lhs_type),
+                                rhs_val.with_null_type(lhs_type),
+                            )
+                        }
+                        (ir::Value::LitNullPtr(_), _) => {
+                            (lhs_val.with_null_type(rhs_val.get_type()), rhs_val)
+                        }
+                        (_, ir::Value::LitNullPtr(_)) => {
+                            let lhs_type = lhs_val.get_type();
+                            (lhs_val, rhs_val.with_null_type(lhs_type))
+                        }
+                        _ => (lhs_val, rhs_val),
+                    };
+                    match lhs_val.get_type().kind() {
+                        ir::TypeKind::Int | ir::TypeKind::Bool => {
                             let new_op = match op {
                                 LT => ir::CmpOp::LT,
                                 LE => ir::CmpOp::LE,
@@ -811,7 +1252,7 @@ impl<'a> FunctionCodeGen<'a> {
                                 .push(ir::Operation::Compare(new_reg, new_op, lhs_val, rhs_val));
                             (new_label, ir::Value::Register(new_reg, ir::Type::Bool))
                         }
-                        ir::Type::Ptr(subtype) => match *subtype {
+                        ir::TypeKind::Ptr(subtype) => match *subtype {
                             ir::Type::Char => {
                                 let fun_name = match op {
                                     EQ => "_bltn_string_eq",
@@ -819,11 +1260,10 @@ impl<'a> FunctionCodeGen<'a> {
                                     _ => unreachable!(),
                                 };
                                 let new_reg = self.get_new_reg_num();
-                                let str_type = ir::Type::Ptr(Box::new(ir::Type::Char));
-                                let fun_type = ir::Type::Ptr(Box::new(ir::Type::Func(
-                                    Box::new(ir::Type::Bool),
-                                    vec![str_type.clone(), str_type],
-                                )));
+                                let str_type = ir::Type::ptr(ir::Type::Char);
+                                let fun_type = ir::Type::ptr(ir::Type::func(ir::Type::Bool,
+                                    vec![str_type, str_type],
+                                ));
                                 self.get_block(cur_label)
                                     .body
                                     .push(ir::Operation::FunctionCall(
@@ -842,18 +1282,21 @@ impl<'a> FunctionCodeGen<'a> {
                                     _ => unreachable!(),
                                 };
                                 let new_reg = self.get_new_reg_num();
-                                self.get_block(cur_label).body.push(ir::Operation::Compare(
+                                self.get_block(new_label).body.push(ir::Operation::Compare(
                                     new_reg, cmp_op, lhs_val, rhs_val,
                                 ));
-                                (cur_label, ir::Value::Register(new_reg, ir::Type::Bool))
+                                (new_label, ir::Value::Register(new_reg, ir::Type::Bool))
                             }
                         },
-                        ir::Type::Void
-                        | ir::Type::Char
-                        | ir::Type::Class(_)
-                        | ir::Type::Func(_, _) => unreachable!(),
+                        ir::TypeKind::Void
+                        | ir::TypeKind::Long
+                        | ir::TypeKind::Char
+                        | ir::TypeKind::Class(_)
+                        | ir::TypeKind::Struct(_) // structs can't be compared
+                        | ir::TypeKind::Func(_, _) => unreachable!(),
                     }
                 }
+                RefEQ | RefNE => unreachable!(), // lowered by semantic analysis
             },
             UnaryOp(op, lhs) => match &op.inner {
                 IntNeg => {
@@ -874,12 +1317,7 @@ impl<'a> FunctionCodeGen<'a> {
                     let new_reg = self.get_new_reg_num();
                     self.get_block(new_label)
                         .body
-                        .push(ir::Operation::Arithmetic(
-                            new_reg,
-                            ir::ArithOp::Sub,
-                            ir::Value::LitBool(true),
-                            value,
-                        ));
+                        .push(ir::Operation::Not(new_reg, value));
                     (new_label, ir::Value::Register(new_reg, ir::Type::Bool))
                 }
             },
@@ -888,29 +1326,29 @@ impl<'a> FunctionCodeGen<'a> {
                 elem_cnt,
             } => {
                 let elem_type_ir = ir::Type::from_ast(&elem_type.inner);
-                let elem_size = get_size_of_primitive(&elem_type_ir);
                 let (new_label, elem_cnt_value) =
                     self.process_expression(&elem_cnt.inner, cur_label);
+                let elem_size = self.generate_calculation_of_elem_size(new_label, &elem_type_ir);
 
                 let reg_num = self.get_new_reg_num();
                 let casted_reg_num = self.get_new_reg_num();
-                let array_type_ir = ir::Type::Ptr(Box::new(elem_type_ir));
-                let void_ptr_type = ir::Type::Ptr(Box::new(ir::Type::Char));
-                let malloc_type = ir::Type::Ptr(Box::new(ir::Type::Func(
-                    Box::new(void_ptr_type.clone()),
-                    vec![ir::Type::Int, ir::Type::Int],
-                )));
+                let array_type_ir = ir::Type::ptr(elem_type_ir);
+                let void_ptr_type = ir::Type::ptr(ir::Type::Char);
+                let malloc_type = ir::Type::ptr(ir::Type::func(
+                    void_ptr_type,
+                    vec![ir::Type::Int, self.get_size_type()],
+                ));
                 let body = &mut self.get_block(new_label).body;
                 body.push(ir::Operation::FunctionCall(
                     Some(reg_num),
                     void_ptr_type,
                     ir::Value::GlobalRegister("_bltn_alloc_array".to_string(), malloc_type),
-                    vec![elem_cnt_value, ir::Value::LitInt(elem_size)],
+                    vec![elem_cnt_value, elem_size],
                 ));
-                let void_ptr_type = ir::Type::Ptr(Box::new(ir::Type::Char));
+                let void_ptr_type = ir::Type::ptr(ir::Type::Char);
                 body.push(ir::Operation::CastPtr {
                     dst: casted_reg_num,
-                    dst_type: array_type_ir.clone(),
+                    dst_type: array_type_ir,
                     src_value: ir::Value::Register(reg_num, void_ptr_type),
                 });
 
@@ -923,95 +1361,136 @@ impl<'a> FunctionCodeGen<'a> {
                 // "it's an optimization - inlined constructor"
                 match &class_type.inner {
                     ast::InnerType::Class(class_name) => {
-                        let class_type = ir::Type::Class(class_name.to_string());
-                        let class_type_ptr = ir::Type::Ptr(Box::new(class_type.clone()));
+                        let class_type =
+                            ir::Type::intern(ir::TypeKind::Class(class_name.to_string()));
+                        let class_type_ptr = ir::Type::ptr(class_type);
 
-                        // calc object size
-                        let size_ptr_reg = self.get_new_reg_num();
-                        let size_int_reg = self.get_new_reg_num();
-                        self.get_block(cur_label)
-                            .body
-                            .push(ir::Operation::GetElementPtr(
-                                size_ptr_reg,
-                                class_type.clone(),
-                                vec![
-                                    ir::Value::LitNullPtr(Some(class_type_ptr.clone())),
-                                    ir::Value::LitInt(1),
-                                ],
-                            ));
-                        self.get_block(cur_label)
-                            .body
-                            .push(ir::Operation::CastPtrToInt {
-                                dst: size_int_reg,
-                                src_value: ir::Value::Register(
-                                    size_ptr_reg,
-                                    class_type_ptr.clone(),
-                                ),
-                            });
+                        let size_value =
+                            self.generate_calculation_of_type_size(cur_label, class_type);
 
                         // malloc
                         let allocd_void_ptr_reg = self.get_new_reg_num();
                         let allocd_cl_ptr_reg = self.get_new_reg_num();
                         let allocd_cl_ptr_val =
-                            ir::Value::Register(allocd_cl_ptr_reg, class_type_ptr.clone());
-                        let void_ptr_type = ir::Type::Ptr(Box::new(ir::Type::Char));
-                        let malloc_type = ir::Type::Ptr(Box::new(ir::Type::Func(
-                            Box::new(void_ptr_type.clone()),
-                            vec![ir::Type::Int],
-                        )));
+                            ir::Value::Register(allocd_cl_ptr_reg, class_type_ptr);
+                        let void_ptr_type = ir::Type::ptr(ir::Type::Char);
+                        let malloc_type = ir::Type::ptr(ir::Type::func(
+                            void_ptr_type,
+                            vec![self.get_size_type()],
+                        ));
                         self.get_block(cur_label)
                             .body
                             .push(ir::Operation::FunctionCall(
                                 Some(allocd_void_ptr_reg),
-                                void_ptr_type.clone(),
+                                void_ptr_type,
                                 ir::Value::GlobalRegister("_bltn_malloc".to_string(), malloc_type),
-                                vec![ir::Value::Register(size_int_reg, ir::Type::Int)],
+                                vec![size_value],
                             ));
                         self.get_block(cur_label).body.push(ir::Operation::CastPtr {
                             dst: allocd_cl_ptr_reg,
-                            dst_type: class_type_ptr.clone(),
+                            dst_type: class_type_ptr,
                             src_value: ir::Value::Register(allocd_void_ptr_reg, void_ptr_type),
                         });
 
-                        // set vtable
-                        let vtable_ptr_reg = self.get_new_reg_num();
-                        let vtable_type = ir::get_class_vtable_type(class_name);
-                        let vtable_val = ir::Value::GlobalRegister(
-                            ir::format_class_vtable_data(class_name),
-                            vtable_type.clone(),
-                        );
-                        self.get_block(cur_label)
-                            .body
-                            .push(ir::Operation::GetElementPtr(
-                                vtable_ptr_reg,
-                                class_type,
-                                vec![
-                                    allocd_cl_ptr_val.clone(),
-                                    ir::Value::LitInt(0),
-                                    ir::Value::LitInt(0),
-                                ],
+                        // set vtable (unless it's never used)
+                        let class_desc = self.class_registry.get_class_description(class_name);
+                        if class_desc.needs_vtable() {
+                            let vtable_ptr_reg = self.get_new_reg_num();
+                            let vtable_type = ir::get_class_vtable_type(class_name);
+                            let vtable_val = ir::Value::GlobalRegister(
+                                ir::format_class_vtable_data(class_name),
+                                vtable_type,
+                            );
+                            self.get_block(cur_label)
+                                .body
+                                .push(ir::Operation::GetElementPtr(
+                                    vtable_ptr_reg,
+                                    class_type,
+                                    vec![
+                                        allocd_cl_ptr_val.clone(),
+                                        ir::Value::LitInt(0),
+                                        ir::Value::LitInt(0),
+                                    ],
+                                ));
+                            self.get_block(cur_label).body.push(ir::Operation::Store(
+                                vtable_val,
+                                ir::Value::Register(vtable_ptr_reg, ir::Type::ptr(vtable_type)),
                             ));
-                        self.get_block(cur_label).body.push(ir::Operation::Store(
-                            vtable_val,
-                            ir::Value::Register(
-                                vtable_ptr_reg,
-                                ir::Type::Ptr(Box::new(vtable_type)),
-                            ),
-                        ));
+                        }
 
                         (cur_label, allocd_cl_ptr_val)
                     }
                     _ => unreachable!(),
                 }
             }
+            ArrayElem {
+                array,
+                index,
+                is_array_a_string: Some(true),
+            } => {
+                let (new_label, str_value) = self.process_expression(&array.inner, cur_label);
+                let (new_label, index_value) = self.process_expression(&index.inner, new_label);
+                if self.options.check_bounds {
+                    let fun_type = ir::Type::ptr(ir::Type::func(
+                        ir::Type::Void,
+                        vec![str_value.get_type(), ir::Type::Int],
+                    ));
+                    self.get_block(new_label)
+                        .body
+                        .push(ir::Operation::FunctionCall(
+                            None,
+                            ir::Type::Void,
+                            ir::Value::GlobalRegister(
+                                "_bltn_string_check_index".to_string(),
+                                fun_type,
+                            ),
+                            vec![str_value.clone(), index_value.clone()],
+                        ));
+                }
+                let char_ptr_reg = self.get_new_reg_num();
+                let char_reg = self.get_new_reg_num();
+                let int_reg = self.get_new_reg_num();
+                let str_type = str_value.get_type();
+                let body = &mut self.get_block(new_label).body;
+                body.push(ir::Operation::GetElementPtr(
+                    char_ptr_reg,
+                    ir::Type::Char,
+                    vec![str_value, index_value],
+                ));
+                body.push(ir::Operation::Load(
+                    char_reg,
+                    ir::Value::Register(char_ptr_reg, str_type),
+                ));
+                body.push(ir::Operation::CastCharToInt {
+                    dst: int_reg,
+                    src_value: ir::Value::Register(char_reg, ir::Type::Char),
+                });
+                (new_label, ir::Value::Register(int_reg, ir::Type::Int))
+            }
+            ObjField {
+                obj,
+                is_obj_a_struct: Some(true),
+                field,
+                ..
+            } => {
+                let (new_label, struct_value) = self.process_expression(&obj.inner, cur_label);
+                let (field_number, field_type) =
+                    self.get_struct_field(&struct_value.get_type(), &field.inner);
+                let new_reg = self.get_new_reg_num();
+                self.get_block(new_label)
+                    .body
+                    .push(ir::Operation::ExtractValue {
+                        dst: new_reg,
+                        src_value: struct_value,
+                        indices: vec![field_number as u32],
+                    });
+                (new_label, ir::Value::Register(new_reg, field_type))
+            }
             ArrayElem { .. } | ObjField { .. } => {
                 let (new_label, elem_ref_value) =
                     self.process_lvalue_ref_expression(expr, cur_label);
                 let new_reg = self.get_new_reg_num();
-                let elem_type = match &elem_ref_value {
-                    ir::Value::Register(_, ir::Type::Ptr(subtype)) => (**subtype).clone(),
-                    _ => unreachable!(),
-                };
+                let elem_type = elem_ref_value.get_type().pointee().unwrap();
                 self.get_block(new_label)
                     .body
                     .push(ir::Operation::Load(new_reg, elem_ref_value));
@@ -1024,85 +1503,54 @@ impl<'a> FunctionCodeGen<'a> {
             } => {
                 let (new_label, this_value) = self.process_expression(&obj.inner, cur_label);
 
-                // load vtable
                 let this_type = match &this_value {
-                    ir::Value::Register(_, t) => (*t).clone(),
-                    _ => unreachable!(),
-                };
-                let elem_this_type = match &this_type {
-                    ir::Type::Ptr(t) => (**t).clone(),
+                    ir::Value::Register(_, t) => *t,
+                    // e.g. a variable assigned null, the call always fails
+                    ir::Value::LitNullPtr(Some(t)) => {
+                        self.add_null_dereference_error(new_label);
+                        *t
+                    }
                     _ => unreachable!(),
                 };
-                let class_name = match &this_type {
-                    ir::Type::Ptr(t) => match &**t {
-                        ir::Type::Class(name) => name.to_string(),
-                        _ => unreachable!(),
-                    },
-                    _ => unreachable!(),
-                };
-                let vtable_type = ir::get_class_vtable_type(&class_name);
-                let vtable_reg = self.get_new_reg_num();
-                let vtable_val = ir::Value::Register(vtable_reg, vtable_type.clone());
-                let vtable_ptr_reg = self.get_new_reg_num();
-                let vtable_ptr_type = ir::Type::Ptr(Box::new(vtable_type.clone()));
-                let vtable_ptr_val = ir::Value::Register(vtable_ptr_reg, vtable_ptr_type);
-                self.get_block(new_label)
-                    .body
-                    .push(ir::Operation::GetElementPtr(
-                        vtable_ptr_reg,
-                        elem_this_type,
-                        vec![
-                            this_value.clone(),
-                            ir::Value::LitInt(0),
-                            ir::Value::LitInt(0),
-                        ],
-                    ));
-                self.get_block(new_label)
-                    .body
-                    .push(ir::Operation::Load(vtable_reg, vtable_ptr_val));
-
-                // load the method from vtable
-                let vtable_elem_type = match &vtable_type {
-                    ir::Type::Ptr(t) => (**t).clone(),
-                    _ => unreachable!(),
+                let class_name = match this_type.pointee().unwrap().kind() {
+                    ir::TypeKind::Class(name) => name.to_string(),
+                    _ => {
+                        return self.process_array_method_call(
+                            new_label,
+                            this_value,
+                            &method_name.inner,
+                            args,
+                        );
+                    }
                 };
                 let class_desc = self.class_registry.get_class_description(&class_name);
-                let (method_number, method_type) =
-                    class_desc.get_method_number_and_type(&method_name.inner);
-                let method_ptr_type = ir::Type::Ptr(Box::new(method_type.clone()));
-                let method_ptr_reg = self.get_new_reg_num();
-                let method_reg = self.get_new_reg_num();
-                let method_ptr_val = ir::Value::Register(method_ptr_reg, method_ptr_type.clone());
-                let method_val = ir::Value::Register(method_reg, method_type.clone());
-                self.get_block(new_label)
-                    .body
-                    .push(ir::Operation::GetElementPtr(
-                        method_ptr_reg,
-                        vtable_elem_type,
-                        vec![
-                            vtable_val,
-                            ir::Value::LitInt(0),
-                            ir::Value::LitInt(method_number as i32),
-                        ],
-                    ));
-                self.get_block(new_label)
-                    .body
-                    .push(ir::Operation::Load(method_reg, method_ptr_val));
+                let (method_val, method_type) =
+                    match class_desc.get_devirtualized_method(&method_name.inner) {
+                        Some(method_val) => {
+                            let method_type = method_val.get_type();
+                            (method_val, method_type)
+                        }
+                        None => self.generate_method_lookup_in_vtable(
+                            new_label,
+                            this_value.clone(),
+                            &class_name,
+                            &method_name.inner,
+                        ),
+                    };
 
                 // cast this if needed
                 let casted_this_value;
-                match &method_type {
-                    ir::Type::Ptr(t) => match &**t {
-                        ir::Type::Func(_, args_types) => {
+                match method_type.kind() {
+                    ir::TypeKind::Ptr(t) => match t.kind() {
+                        ir::TypeKind::Func(_, args_types) => {
                             if args_types[0] != this_type {
                                 let casted_reg = self.get_new_reg_num();
                                 self.get_block(new_label).body.push(ir::Operation::CastPtr {
                                     dst: casted_reg,
-                                    dst_type: args_types[0].clone(),
+                                    dst_type: args_types[0],
                                     src_value: this_value,
                                 });
-                                casted_this_value =
-                                    ir::Value::Register(casted_reg, args_types[0].clone())
+                                casted_this_value = ir::Value::Register(casted_reg, args_types[0])
                             } else {
                                 casted_this_value = this_value;
                             }
@@ -1115,25 +1563,244 @@ impl<'a> FunctionCodeGen<'a> {
                 // do the call
                 process_fun_call(self, method_val, Some(casted_this_value), args, cur_label)
             }
+            // lowered to classes by semantic analysis
+            Lambda { .. } | ClosureCall { .. } => unreachable!(),
+            NamedArg { .. } => unreachable!(), // reordered by semantic analysis
+            Range(..) => unreachable!(),       // only iterated by foreach loops
+            Assign(lhs, rhs) => self.process_assignment(&lhs.inner, &rhs.inner, cur_label),
+            IncrDecr {
+                lvalue,
+                is_incr,
+                is_prefix,
+            } => self.process_incr_decr(&lvalue.inner, *is_incr, *is_prefix, cur_label),
+        }
+    }
+
+    // returns the assigned value, after the conversion to the type of lhs
+    fn process_assignment(
+        &mut self,
+        lhs: &'a ast::InnerExpr,
+        rhs: &'a ast::InnerExpr,
+        cur_label: ir::Label,
+    ) -> (ir::Label, ir::Value) {
+        let (mut cur_label, rhs_value) = self.process_expression(rhs, cur_label);
+        use model::ast::InnerExpr::*;
+        let rhs_value = match lhs {
+            LitVar(var_name) => {
+                let var_type = self.env.get_variable(cur_label, var_name).get_type();
+                let rhs_value = self.generate_coercion(cur_label, rhs_value, var_type);
+                self.env
+                    .update_existing_local_variable(cur_label, var_name, rhs_value.clone());
+                rhs_value
+            }
+            ObjField { .. } if is_struct_field_of_local_var(lhs) => {
+                let (var_name, indices, field_type) = self.get_struct_field_path(lhs, cur_label);
+                let rhs_value = self.generate_coercion(cur_label, rhs_value, field_type);
+                self.update_struct_field_of_local_var(
+                    cur_label,
+                    var_name,
+                    indices,
+                    rhs_value.clone(),
+                );
+                rhs_value
+            }
+            ArrayElem { .. } | ObjField { .. } => {
+                let (new_label, ref_val) = self.process_lvalue_ref_expression(lhs, cur_label);
+                cur_label = new_label;
+                let elem_type = ref_val.get_type().pointee().unwrap();
+                let rhs_value = self.generate_coercion(cur_label, rhs_value, elem_type);
+                self.get_block(cur_label)
+                    .body
+                    .push(ir::Operation::Store(rhs_value.clone(), ref_val));
+                rhs_value
+            }
+            _ => unreachable!(),
+        };
+        (cur_label, rhs_value)
+    }
+
+    // returns the changed value for the prefix `++`/`--`, the original one otherwise
+    fn process_incr_decr(
+        &mut self,
+        lvalue: &'a ast::InnerExpr,
+        is_incr: bool,
+        is_prefix: bool,
+        mut cur_label: ir::Label,
+    ) -> (ir::Label, ir::Value) {
+        let op = if is_incr {
+            ir::ArithOp::Add
+        } else {
+            ir::ArithOp::Sub
+        };
+        use model::ast::InnerExpr::*;
+        let (original_value, changed_value) = match lvalue {
+            LitVar(var_name) => {
+                let original_value = self.env.get_variable(cur_label, var_name).clone();
+                let changed_value = self.generate_incr_decr(cur_label, op, original_value.clone());
+                self.env
+                    .update_existing_local_variable(cur_label, var_name, changed_value.clone());
+                (original_value, changed_value)
+            }
+            ObjField { .. } if is_struct_field_of_local_var(lvalue) => {
+                let (var_name, indices, _) = self.get_struct_field_path(lvalue, cur_label);
+                let struct_value = self.env.get_variable(cur_label, var_name).clone();
+                let field_reg = self.get_new_reg_num();
+                self.get_block(cur_label)
+                    .body
+                    .push(ir::Operation::ExtractValue {
+                        dst: field_reg,
+                        src_value: struct_value,
+                        indices: indices.clone(),
+                    });
+                let field_value = ir::Value::Register(field_reg, ir::Type::Int);
+                let changed_value = self.generate_incr_decr(cur_label, op, field_value.clone());
+                self.update_struct_field_of_local_var(
+                    cur_label,
+                    var_name,
+                    indices,
+                    changed_value.clone(),
+                );
+                (field_value, changed_value)
+            }
+            ArrayElem { .. } | ObjField { .. } => {
+                let (new_label, ref_val) = self.process_lvalue_ref_expression(lvalue, cur_label);
+                cur_label = new_label;
+                let loaded_reg = self.get_new_reg_num();
+                self.get_block(cur_label)
+                    .body
+                    .push(ir::Operation::Load(loaded_reg, ref_val.clone()));
+                let loaded_value = ir::Value::Register(loaded_reg, ir::Type::Int);
+                let changed_value = self.generate_incr_decr(cur_label, op, loaded_value.clone());
+                self.get_block(cur_label)
+                    .body
+                    .push(ir::Operation::Store(changed_value.clone(), ref_val));
+                (loaded_value, changed_value)
+            }
+            _ => unreachable!(),
+        };
+        if is_prefix {
+            (cur_label, changed_value)
+        } else {
+            (cur_label, original_value)
         }
     }
 
+    // sdiv or srem adjusted when the remainder is negative: the quotient is
+    // moved away from zero and the remainder by |divisor|
+    fn generate_euclidean_division(
+        &mut self,
+        cur_label: ir::Label,
+        op: ir::ArithOp,
+        lhs_val: ir::Value,
+        rhs_val: ir::Value,
+    ) -> ir::Value {
+        use model::ir::{ArithOp, CmpOp, Operation, Value};
+        let int_reg = |reg| Value::Register(reg, ir::Type::Int);
+        let bool_reg = |reg| Value::Register(reg, ir::Type::Bool);
+        let mut ops = vec![];
+        let result_reg = self.get_new_reg_num();
+        ops.push(Operation::Arithmetic(
+            result_reg,
+            op.clone(),
+            lhs_val.clone(),
+            rhs_val.clone(),
+        ));
+        let rem_value = if op == ArithOp::Mod {
+            int_reg(result_reg)
+        } else {
+            let rem_reg = self.get_new_reg_num();
+            ops.push(Operation::Arithmetic(
+                rem_reg,
+                ArithOp::Mod,
+                lhs_val,
+                rhs_val.clone(),
+            ));
+            int_reg(rem_reg)
+        };
+        let is_rem_neg_reg = self.get_new_reg_num();
+        ops.push(Operation::Compare(
+            is_rem_neg_reg,
+            CmpOp::LT,
+            rem_value,
+            Value::LitInt(0),
+        ));
+        let is_rhs_neg_reg = self.get_new_reg_num();
+        ops.push(Operation::Compare(
+            is_rhs_neg_reg,
+            CmpOp::LT,
+            rhs_val.clone(),
+            Value::LitInt(0),
+        ));
+        // the quotient moves by 1 away from zero, the remainder by |divisor|
+        let (step_if_neg, step_if_pos) = if op == ArithOp::Div {
+            (Value::LitInt(1), Value::LitInt(-1))
+        } else {
+            let neg_rhs_reg = self.get_new_reg_num();
+            ops.push(Operation::Arithmetic(
+                neg_rhs_reg,
+                ArithOp::Sub,
+                Value::LitInt(0),
+                rhs_val.clone(),
+            ));
+            (int_reg(neg_rhs_reg), rhs_val)
+        };
+        let step_reg = self.get_new_reg_num();
+        ops.push(Operation::Select(
+            step_reg,
+            bool_reg(is_rhs_neg_reg),
+            step_if_neg,
+            step_if_pos,
+        ));
+        let adjusted_reg = self.get_new_reg_num();
+        ops.push(Operation::Arithmetic(
+            adjusted_reg,
+            ArithOp::Add,
+            int_reg(result_reg),
+            int_reg(step_reg),
+        ));
+        let res_reg = self.get_new_reg_num();
+        ops.push(Operation::Select(
+            res_reg,
+            bool_reg(is_rem_neg_reg),
+            int_reg(adjusted_reg),
+            int_reg(result_reg),
+        ));
+        self.get_block(cur_label).body.extend(ops);
+        int_reg(res_reg)
+    }
+
+    // the value after +/- 1
+    fn generate_incr_decr(
+        &mut self,
+        cur_label: ir::Label,
+        op: ir::ArithOp,
+        value: ir::Value,
+    ) -> ir::Value {
+        let changed_reg = self.get_new_reg_num();
+        self.get_block(cur_label)
+            .body
+            .push(ir::Operation::Arithmetic(
+                changed_reg,
+                op,
+                value,
+                ir::Value::LitInt(1),
+            ));
+        ir::Value::Register(changed_reg, ir::Type::Int)
+    }
+
     fn process_lvalue_ref_expression(
         &mut self,
-        expr: &ast::InnerExpr,
+        expr: &'a ast::InnerExpr,
         cur_label: ir::Label,
     ) -> (ir::Label, ir::Value) {
         use model::ast::InnerExpr::{ArrayElem, ObjField};
         match expr {
-            ArrayElem { array, index } => {
+            ArrayElem { array, index, .. } => {
                 let (new_label, array_value) = self.process_expression(&array.inner, cur_label);
                 let (new_label, index_value) = self.process_expression(&index.inner, new_label);
                 let new_reg = self.get_new_reg_num();
                 let array_type = array_value.get_type();
-                let elem_type = match &array_type {
-                    ir::Type::Ptr(subtype) => (**subtype).clone(),
-                    _ => unreachable!(),
-                };
+                let elem_type = array_type.pointee().unwrap();
                 self.get_block(new_label)
                     .body
                     .push(ir::Operation::GetElementPtr(
@@ -1143,10 +1810,39 @@ impl<'a> FunctionCodeGen<'a> {
                     ));
                 (new_label, ir::Value::Register(new_reg, array_type))
             }
+            // struct kept in memory (array element or object field)
+            ObjField {
+                obj,
+                is_obj_a_struct: Some(true),
+                field,
+                ..
+            } => {
+                let (new_label, struct_ptr_value) =
+                    self.process_lvalue_ref_expression(&obj.inner, cur_label);
+                let struct_type = struct_ptr_value.get_type().pointee().unwrap();
+                let (field_number, field_type) = self.get_struct_field(&struct_type, &field.inner);
+                let field_ptr_reg = self.get_new_reg_num();
+                self.get_block(new_label)
+                    .body
+                    .push(ir::Operation::GetElementPtr(
+                        field_ptr_reg,
+                        struct_type,
+                        vec![
+                            struct_ptr_value,
+                            ir::Value::LitInt(0),
+                            ir::Value::LitInt(field_number as i32),
+                        ],
+                    ));
+                (
+                    new_label,
+                    ir::Value::Register(field_ptr_reg, ir::Type::ptr(field_type)),
+                )
+            }
             ObjField {
                 obj,
                 is_obj_an_array,
                 field,
+                ..
             } => {
                 let (new_label, obj_ptr_value) = self.process_expression(&obj.inner, cur_label);
                 let field_ptr_val = match is_obj_an_array {
@@ -1155,12 +1851,9 @@ impl<'a> FunctionCodeGen<'a> {
                     }
                     Some(false) => {
                         let field_ptr_reg = self.get_new_reg_num();
-                        let class_type = match &obj_ptr_value {
-                            ir::Value::Register(_, ir::Type::Ptr(t)) => (**t).clone(),
-                            _ => unreachable!(),
-                        };
-                        let class_desc = match &class_type {
-                            ir::Type::Class(name) => {
+                        let class_type = obj_ptr_value.get_type().pointee().unwrap();
+                        let class_desc = match class_type.kind() {
+                            ir::TypeKind::Class(name) => {
                                 self.class_registry.get_class_description(name)
                             }
                             _ => unreachable!(),
@@ -1178,7 +1871,7 @@ impl<'a> FunctionCodeGen<'a> {
                                     ir::Value::LitInt(field_number as i32),
                                 ],
                             ));
-                        ir::Value::Register(field_ptr_reg, ir::Type::Ptr(Box::new(field_type)))
+                        ir::Value::Register(field_ptr_reg, ir::Type::ptr(field_type))
                     }
                     None => unreachable!(),
                 };
@@ -1188,6 +1881,321 @@ impl<'a> FunctionCodeGen<'a> {
         }
     }
 
+    // returns (method, method type)
+    fn generate_method_lookup_in_vtable(
+        &mut self,
+        cur_label: ir::Label,
+        this_value: ir::Value,
+        class_name: &str,
+        method_name: &str,
+    ) -> (ir::Value, ir::Type) {
+        // load vtable
+        let this_type = this_value.get_type();
+        let elem_this_type = this_type.pointee().unwrap();
+        let vtable_type = ir::get_class_vtable_type(class_name);
+        let vtable_reg = self.get_new_reg_num();
+        let vtable_val = ir::Value::Register(vtable_reg, vtable_type);
+        let vtable_ptr_reg = self.get_new_reg_num();
+        let vtable_ptr_type = ir::Type::ptr(vtable_type);
+        let vtable_ptr_val = ir::Value::Register(vtable_ptr_reg, vtable_ptr_type);
+        self.get_block(cur_label)
+            .body
+            .push(ir::Operation::GetElementPtr(
+                vtable_ptr_reg,
+                elem_this_type,
+                vec![this_value, ir::Value::LitInt(0), ir::Value::LitInt(0)],
+            ));
+        self.get_block(cur_label)
+            .body
+            .push(ir::Operation::Load(vtable_reg, vtable_ptr_val));
+
+        // load the method from vtable
+        let vtable_elem_type = vtable_type.pointee().unwrap();
+        let class_desc = self.class_registry.get_class_description(class_name);
+        let (method_number, method_type) = class_desc.get_method_number_and_type(method_name);
+        let method_ptr_type = ir::Type::ptr(method_type);
+        let method_ptr_reg = self.get_new_reg_num();
+        let method_reg = self.get_new_reg_num();
+        let method_ptr_val = ir::Value::Register(method_ptr_reg, method_ptr_type);
+        let method_val = ir::Value::Register(method_reg, method_type);
+        self.get_block(cur_label)
+            .body
+            .push(ir::Operation::GetElementPtr(
+                method_ptr_reg,
+                vtable_elem_type,
+                vec![
+                    vtable_val,
+                    ir::Value::LitInt(0),
+                    ir::Value::LitInt(method_number as i32),
+                ],
+            ));
+        self.get_block(cur_label)
+            .body
+            .push(ir::Operation::Load(method_reg, method_ptr_val));
+        (method_val, method_type)
+    }
+
+    // size of the type in bytes, computed by llvm: (int)(&((T*)null)[1])
+    // copy and fill, the only methods of arrays
+    fn process_array_method_call(
+        &mut self,
+        cur_label: ir::Label,
+        array_value: ir::Value,
+        method_name: &str,
+        args: &'a [Box<ast::Expr>],
+    ) -> (ir::Label, ir::Value) {
+        let array_type = array_value.get_type();
+        let elem_type = array_type.pointee().unwrap();
+        let void_ptr_type = ir::Type::ptr(ir::Type::Char);
+        let array_ptr_reg = self.get_new_reg_num();
+        let result_reg = self.get_new_reg_num();
+        match method_name {
+            "copy" => {
+                let elem_size = self.generate_calculation_of_elem_size(cur_label, &elem_type);
+                let copied_reg = self.get_new_reg_num();
+                let fun_type = ir::Type::ptr(ir::Type::func(
+                    void_ptr_type,
+                    vec![void_ptr_type, self.get_size_type()],
+                ));
+                let body = &mut self.get_block(cur_label).body;
+                body.push(ir::Operation::CastPtr {
+                    dst: array_ptr_reg,
+                    dst_type: void_ptr_type,
+                    src_value: array_value,
+                });
+                body.push(ir::Operation::FunctionCall(
+                    Some(copied_reg),
+                    void_ptr_type,
+                    ir::Value::GlobalRegister("_bltn_array_copy".to_string(), fun_type),
+                    vec![ir::Value::Register(array_ptr_reg, void_ptr_type), elem_size],
+                ));
+                body.push(ir::Operation::CastPtr {
+                    dst: result_reg,
+                    dst_type: array_type,
+                    src_value: ir::Value::Register(copied_reg, void_ptr_type),
+                });
+                (cur_label, ir::Value::Register(result_reg, array_type))
+            }
+            "fill" => {
+                // the first element is stored here, the runtime copies it to the others
+                let (new_label, value) = self.process_expression(&args[0].inner, cur_label);
+                let elem_size = self.generate_calculation_of_elem_size(new_label, &elem_type);
+                let first_elem_reg = self.get_new_reg_num();
+                let fun_type = ir::Type::ptr(ir::Type::func(
+                    ir::Type::Void,
+                    vec![void_ptr_type, self.get_size_type()],
+                ));
+                let body = &mut self.get_block(new_label).body;
+                body.push(ir::Operation::GetElementPtr(
+                    first_elem_reg,
+                    elem_type,
+                    vec![array_value.clone(), ir::Value::LitInt(0)],
+                ));
+                body.push(ir::Operation::Store(
+                    value,
+                    ir::Value::Register(first_elem_reg, array_type),
+                ));
+                body.push(ir::Operation::CastPtr {
+                    dst: array_ptr_reg,
+                    dst_type: void_ptr_type,
+                    src_value: array_value,
+                });
+                body.push(ir::Operation::FunctionCall(
+                    None,
+                    ir::Type::Void,
+                    ir::Value::GlobalRegister("_bltn_array_fill".to_string(), fun_type),
+                    vec![ir::Value::Register(array_ptr_reg, void_ptr_type), elem_size],
+                ));
+                (new_label, ir::Value::Register(result_reg, ir::Type::Void))
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // arrayResize and append, the runtime returns a new array (null is an empty array)
+    fn process_array_builtin_call(
+        &mut self,
+        cur_label: ir::Label,
+        function_name: &str,
+        elem_type: ir::Type,
+        args: &'a [Box<ast::Expr>],
+    ) -> (ir::Label, ir::Value) {
+        let (new_label, array_value) = self.process_expression(&args[0].inner, cur_label);
+        let (new_label, arg_value) = self.process_expression(&args[1].inner, new_label);
+        let elem_size = self.generate_calculation_of_elem_size(new_label, &elem_type);
+        let array_type = ir::Type::ptr(elem_type);
+        let void_ptr_type = ir::Type::ptr(ir::Type::Char);
+        let array_ptr_reg = self.get_new_reg_num();
+        let new_array_ptr_reg = self.get_new_reg_num();
+        let result_reg = self.get_new_reg_num();
+        let (runtime_fun, runtime_args, runtime_args_types) =
+            if function_name == ast::ARRAY_RESIZE_FUN {
+                (
+                    "_bltn_array_resize",
+                    vec![arg_value.clone(), elem_size],
+                    vec![void_ptr_type, ir::Type::Int, self.get_size_type()],
+                )
+            } else {
+                (
+                    "_bltn_array_append",
+                    vec![elem_size],
+                    vec![void_ptr_type, self.get_size_type()],
+                )
+            };
+        let fun_type = ir::Type::ptr(ir::Type::func(void_ptr_type, runtime_args_types));
+        let mut call_args = vec![ir::Value::Register(array_ptr_reg, void_ptr_type)];
+        call_args.extend(runtime_args);
+        let body = &mut self.get_block(new_label).body;
+        body.push(ir::Operation::CastPtr {
+            dst: array_ptr_reg,
+            dst_type: void_ptr_type,
+            src_value: array_value,
+        });
+        body.push(ir::Operation::FunctionCall(
+            Some(new_array_ptr_reg),
+            void_ptr_type,
+            ir::Value::GlobalRegister(runtime_fun.to_string(), fun_type),
+            call_args,
+        ));
+        body.push(ir::Operation::CastPtr {
+            dst: result_reg,
+            dst_type: array_type,
+            src_value: ir::Value::Register(new_array_ptr_reg, void_ptr_type),
+        });
+        let result_value = ir::Value::Register(result_reg, array_type);
+
+        if function_name == ast::ARRAY_APPEND_FUN {
+            // the appended element is the last one, after the hidden length word
+            let length_ref =
+                self.generate_calculation_of_ref_to_array_length(new_label, result_value.clone());
+            let length_reg = self.get_new_reg_num();
+            let last_index_reg = self.get_new_reg_num();
+            let last_elem_reg = self.get_new_reg_num();
+            let body = &mut self.get_block(new_label).body;
+            body.push(ir::Operation::Load(length_reg, length_ref));
+            body.push(ir::Operation::Arithmetic(
+                last_index_reg,
+                ir::ArithOp::Sub,
+                ir::Value::Register(length_reg, ir::Type::Int),
+                ir::Value::LitInt(1),
+            ));
+            body.push(ir::Operation::GetElementPtr(
+                last_elem_reg,
+                elem_type,
+                vec![
+                    result_value.clone(),
+                    ir::Value::Register(last_index_reg, ir::Type::Int),
+                ],
+            ));
+            body.push(ir::Operation::Store(
+                arg_value,
+                ir::Value::Register(last_elem_reg, array_type),
+            ));
+        }
+        (new_label, result_value)
+    }
+
+    fn generate_calculation_of_elem_size(
+        &mut self,
+        cur_label: ir::Label,
+        elem_type: &ir::Type,
+    ) -> ir::Value {
+        match elem_type.kind() {
+            ir::TypeKind::Struct(_) => {
+                self.generate_calculation_of_type_size(cur_label, *elem_type)
+            }
+            _ => {
+                let size = get_size_of_primitive(elem_type, self.options.pointer_width);
+                match self.get_size_type() {
+                    ir::Type::Int => ir::Value::LitInt(size),
+                    _ => ir::Value::LitLong(i64::from(size)),
+                }
+            }
+        }
+    }
+
+    fn get_size_type(&self) -> ir::Type {
+        ir::Type::size_type(self.options.pointer_width)
+    }
+
+    // the value converted to the type of a variable, field, array element,
+    // parameter or the returned value: objects (and arrays of objects) of
+    // subclasses are cast to the superclass, otherwise the types must be the same
+    fn generate_coercion(
+        &mut self,
+        cur_label: ir::Label,
+        value: ir::Value,
+        dst_type: ir::Type,
+    ) -> ir::Value {
+        let src_type = value.get_type();
+        if src_type == dst_type {
+            return value;
+        }
+        if let ir::Value::LitNullPtr(_) = value {
+            if dst_type.is_ptr() {
+                return ir::Value::LitNullPtr(Some(dst_type));
+            }
+        }
+        assert!(
+            self.is_upcast(src_type, dst_type),
+            "value {} of type {} passed as {}",
+            value,
+            src_type,
+            dst_type
+        );
+        let casted_reg = self.get_new_reg_num();
+        self.get_block(cur_label).body.push(ir::Operation::CastPtr {
+            dst: casted_reg,
+            dst_type,
+            src_value: value,
+        });
+        ir::Value::Register(casted_reg, dst_type)
+    }
+
+    // pointers to subclasses or arrays (of the same dimension) of them
+    fn is_upcast(&self, src_type: ir::Type, dst_type: ir::Type) -> bool {
+        match (src_type.pointee(), dst_type.pointee()) {
+            (Some(src_pointee), Some(dst_pointee)) => {
+                match (src_pointee.kind(), dst_pointee.kind()) {
+                    (ir::TypeKind::Class(subclass), ir::TypeKind::Class(superclass)) => {
+                        self.class_registry.is_subclass(subclass, superclass)
+                    }
+                    (ir::TypeKind::Ptr(_), ir::TypeKind::Ptr(_)) => {
+                        self.is_upcast(src_pointee, dst_pointee)
+                    }
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    }
+
+    fn generate_calculation_of_type_size(
+        &mut self,
+        cur_label: ir::Label,
+        t: ir::Type,
+    ) -> ir::Value {
+        let t_ptr = ir::Type::ptr(t);
+        let size_ptr_reg = self.get_new_reg_num();
+        let size_int_reg = self.get_new_reg_num();
+        let size_type = self.get_size_type();
+        self.get_block(cur_label)
+            .body
+            .push(ir::Operation::GetElementPtr(
+                size_ptr_reg,
+                t,
+                vec![ir::Value::LitNullPtr(Some(t_ptr)), ir::Value::LitInt(1)],
+            ));
+        self.get_block(cur_label)
+            .body
+            .push(ir::Operation::CastPtrToInt {
+                dst: size_int_reg,
+                dst_type: size_type,
+                src_value: ir::Value::Register(size_ptr_reg, t_ptr),
+            });
+        ir::Value::Register(size_int_reg, size_type)
+    }
+
     fn generate_calculation_of_ref_to_array_length(
         &mut self,
         cur_label: ir::Label,
@@ -1195,11 +2203,8 @@ impl<'a> FunctionCodeGen<'a> {
     ) -> ir::Value {
         let casted_reg: ir::RegNum;
         let array_type = array_ptr.get_type();
-        let elem_type = match &array_type {
-            ir::Type::Ptr(subtype) => (**subtype).clone(),
-            _ => unreachable!(),
-        };
-        let int_ptr_type = ir::Type::Ptr(Box::new(ir::Type::Int));
+        let elem_type = array_type.pointee().unwrap();
+        let int_ptr_type = ir::Type::ptr(ir::Type::Int);
         match elem_type {
             ir::Type::Int => match array_ptr {
                 ir::Value::Register(reg, _) => casted_reg = reg,
@@ -1209,7 +2214,7 @@ impl<'a> FunctionCodeGen<'a> {
                 casted_reg = self.get_new_reg_num();
                 self.get_block(cur_label).body.push(ir::Operation::CastPtr {
                     dst: casted_reg,
-                    dst_type: int_ptr_type.clone(),
+                    dst_type: int_ptr_type,
                     src_value: array_ptr,
                 });
             }
@@ -1221,13 +2226,150 @@ impl<'a> FunctionCodeGen<'a> {
                 result_reg,
                 ir::Type::Int,
                 vec![
-                    ir::Value::Register(casted_reg, int_ptr_type.clone()),
+                    ir::Value::Register(casted_reg, int_ptr_type),
                     ir::Value::LitInt(-1),
                 ],
             ));
         ir::Value::Register(result_reg, int_ptr_type)
     }
 
+    // (field number, field type)
+    fn get_struct_field(&self, struct_type: &ir::Type, field: &str) -> (usize, ir::Type) {
+        match struct_type.kind() {
+            ir::TypeKind::Struct(name) => self
+                .class_registry
+                .get_struct_description(name)
+                .get_field_number_and_type(field),
+            _ => unreachable!(),
+        }
+    }
+
+    // for a struct field of a local variable, e.g. p.pos.x:
+    // (variable name, path of field numbers, field type)
+    fn get_struct_field_path(
+        &self,
+        expr: &'a ast::InnerExpr,
+        cur_label: ir::Label,
+    ) -> (&'a str, Vec<u32>, ir::Type) {
+        match expr {
+            ast::InnerExpr::ObjField { obj, field, .. } => {
+                let (var_name, mut indices, struct_type) = match &obj.inner {
+                    ast::InnerExpr::LitVar(var_name) => (
+                        var_name.as_str(),
+                        vec![],
+                        self.env.get_variable(cur_label, var_name).get_type(),
+                    ),
+                    inner => self.get_struct_field_path(inner, cur_label),
+                };
+                let (field_number, field_type) = self.get_struct_field(&struct_type, &field.inner);
+                indices.push(field_number as u32);
+                (var_name, indices, field_type)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // local variables are in registers, so the struct is replaced by its modified copy
+    fn update_struct_field_of_local_var(
+        &mut self,
+        cur_label: ir::Label,
+        var_name: &'a str,
+        indices: Vec<u32>,
+        field_value: ir::Value,
+    ) {
+        let struct_value = self.env.get_variable(cur_label, var_name).clone();
+        let struct_type = struct_value.get_type();
+        let new_reg = self.get_new_reg_num();
+        self.get_block(cur_label)
+            .body
+            .push(ir::Operation::InsertValue {
+                dst: new_reg,
+                src_value: struct_value,
+                field_value,
+                indices,
+            });
+        self.env.update_existing_local_variable(
+            cur_label,
+            var_name,
+            ir::Value::Register(new_reg, struct_type),
+        );
+    }
+
+    // `if (c) x = a; else x = b;` (the else is optional), where a and b
+    // are literals or variables of a scalar type, doesn't need its own
+    // blocks: x becomes `select c, a, b`
+    fn is_select_assignment(
+        &self,
+        cur_label: ir::Label,
+        cond: &'a ast::Expr,
+        true_branch: &'a ast::Block,
+        false_branch: &'a Option<ast::Block>,
+    ) -> bool {
+        use model::ast::{BinaryOp::*, InnerExpr::*};
+        if get_const_cond_value(&cond.inner).is_some() {
+            return false;
+        }
+        // short-circuit operators have their own blocks anyway
+        if let BinaryOp(_, And, _) | BinaryOp(_, Or, _) = cond.inner {
+            return false;
+        }
+        let (var_name, _) = match get_single_assignment(true_branch) {
+            Some(assignment) => assignment,
+            None => return false,
+        };
+        let same_var = match false_branch {
+            Some(bl) => matches!(get_single_assignment(bl), Some((name, _)) if name == var_name),
+            None => true,
+        };
+        let var_type = self.env.get_variable(cur_label, var_name).get_type();
+        same_var
+            && !matches!(
+                var_type.kind(),
+                ir::TypeKind::Struct(_) | ir::TypeKind::Void | ir::TypeKind::Func(..)
+            )
+    }
+
+    fn process_select_assignment(
+        &mut self,
+        cur_label: ir::Label,
+        cond: &'a ast::Expr,
+        true_branch: &'a ast::Block,
+        false_branch: &'a Option<ast::Block>,
+    ) -> ir::Label {
+        let (var_name, true_expr) = get_single_assignment(true_branch).unwrap();
+        let (label, cond_value) = self.process_expression(&cond.inner, cur_label);
+        let (label, true_value) = self.process_expression(true_expr, label);
+        let (label, false_value) = match false_branch.as_ref().and_then(get_single_assignment) {
+            Some((_, false_expr)) => self.process_expression(false_expr, label),
+            None => (label, self.env.get_variable(label, var_name).clone()),
+        };
+        let var_type = self.env.get_variable(label, var_name).get_type();
+        let true_value = self.generate_coercion(label, true_value, var_type);
+        let false_value = self.generate_coercion(label, false_value, var_type);
+        use model::ir::Value::LitBool;
+        let new_value = match (cond_value, &true_value, &false_value) {
+            _ if true_value == false_value => true_value,
+            (LitBool(true), _, _) => true_value,
+            (LitBool(false), _, _) => false_value,
+            // `if (c) b = true; else b = false;`
+            (cond_value, LitBool(true), LitBool(false)) => cond_value,
+            (cond_value, _, _) => {
+                let new_reg = self.get_new_reg_num();
+                self.get_block(label).body.push(ir::Operation::Select(
+                    new_reg,
+                    cond_value,
+                    true_value,
+                    false_value,
+                ));
+                ir::Value::Register(new_reg, var_type)
+            }
+        };
+        self.trace("select", || format!("{} {}", format_frame(label), var_name));
+        self.env
+            .update_existing_local_variable(label, var_name, new_value);
+        label
+    }
+
     fn calculate_phi_set_for_if(
         &mut self,
         common_pred: ir::Label,
@@ -1235,7 +2377,11 @@ impl<'a> FunctionCodeGen<'a> {
         (br1, br1_proxy): (ir::Label, ir::Label),
         (br2, br2_proxy): (ir::Label, ir::Label),
     ) {
-        let names = self.env.get_all_visible_local_variables(common_pred);
+        // other variables have the same value on both paths
+        let mut names = self.env.get_proxy_variables(br1_proxy);
+        names.extend(self.env.get_proxy_variables(br2_proxy));
+        names.sort();
+        names.dedup();
 
         for name in names {
             let value0 = self.env.get_variable(common_pred, name).clone();
@@ -1247,11 +2393,14 @@ impl<'a> FunctionCodeGen<'a> {
                     value1 // no need to emit phi function, just update environment
                 } else {
                     let reg_num = self.get_new_reg_num();
-                    let reg_type = value1.get_type();
-                    self.get_block(common_succ).phi_set.insert((
+                    let reg_type = value0.get_type();
+                    self.get_block(common_succ).add_phi(ir::Phi::new(
                         reg_num,
-                        reg_type.clone(),
-                        vec![(value1, br1), (value2, br2)],
+                        reg_type,
+                        vec![
+                            (value1.with_null_type(reg_type), br1),
+                            (value2.with_null_type(reg_type), br2),
+                        ],
                     ));
                     ir::Value::Register(reg_num, reg_type)
                 };
@@ -1262,16 +2411,29 @@ impl<'a> FunctionCodeGen<'a> {
     }
 
     // must be called before processing an expression (it updates environment)
+    // only variables assigned in the body get phi functions, other ones keep their values
     fn prepare_env_and_stub_phi_set_for_loop_cond(
         &mut self,
         pred_label: ir::Label,
         cond_label: ir::Label,
+        cond: Option<&'a ast::Expr>,
+        body: &'a ast::Block,
     ) -> Vec<(&'a str, ir::Value, ir::Value)> {
-        let names = self.env.get_all_visible_local_variables(pred_label);
+        let mut names = HashSet::new();
+        if let Some(cond) = cond {
+            collect_assigned_in_expr(&cond.inner, &mut names);
+        }
+        collect_assigned_variables(body, &mut names);
+        let mut names: Vec<_> = names.into_iter().collect();
+        names.sort();
         let mut stub_info = vec![];
 
         for name in names {
-            let value = self.env.get_variable(pred_label, name).clone();
+            // the name could be declared only in the body
+            let value = match self.env.find_variable(pred_label, name) {
+                Some(value) => value.clone(),
+                None => continue,
+            };
             let reg_num = self.get_new_reg_num();
             let phi_value = ir::Value::Register(reg_num, value.get_type());
             stub_info.push((name, value, phi_value.clone()));
@@ -1279,72 +2441,263 @@ impl<'a> FunctionCodeGen<'a> {
                 .update_existing_local_variable(cond_label, name, phi_value);
         }
 
+        self.trace("phi-stubs", || {
+            let stubs: Vec<_> = stub_info
+                .iter()
+                .map(|(name, value, phi_value)| format!("{}={} (from {})", name, phi_value, value))
+                .collect();
+            format!("{} {}", format_frame(cond_label), stubs.join(", "))
+        });
         stub_info
     }
 
+    fn find_loop(&self, label: &Option<ast::Ident>) -> usize {
+        match label {
+            Some(l) => self
+                .loops
+                .iter()
+                .rposition(|lp| lp.label == Some(l.inner.as_str()))
+                .unwrap(), // checked by semantic analysis
+            None => self.loops.len() - 1,
+        }
+    }
+
+    // values of variables visible before the loop, in order of its names
+    fn get_loop_variables(&self, label: ir::Label, loop_ctx: &LoopContext<'a>) -> Vec<ir::Value> {
+        // this is really tricky; we need to skip variables defined
+        // in body loop which shadows original ones
+        loop_ctx
+            .names
+            .iter()
+            .map(|name| {
+                self.env
+                    .get_variable_in_loop(label, loop_ctx.proxy_label, name)
+                    .clone()
+            })
+            .collect()
+    }
+
+    // falling off the end of the body works like continue
+    fn finish_loop_body(&mut self, end_body_label: ir::Label) -> LoopContext<'a> {
+        let mut loop_ctx = self.loops.pop().unwrap();
+        if end_body_label != UNREACHABLE_LABEL {
+            let values = self.get_loop_variables(end_body_label, &loop_ctx);
+            self.add_branch1_op(end_body_label, loop_ctx.continue_target);
+            loop_ctx.continues.push((end_body_label, values));
+        }
+        loop_ctx
+    }
+
     // must be called after processing cond and body blocks
+    // back_edges: continues of the loop (including end of its body)
     fn finalize_phi_set_for_loop_cond(
         &mut self,
         pred_label: ir::Label,
         cond_label: ir::Label,
-        proxy_label: Option<ir::Label>,
+        back_edges: &[(ir::Label, Vec<ir::Value>)],
         stub_info: Vec<(&'a str, ir::Value, ir::Value)>,
     ) {
-        let end_body_label = {
-            let preds = &self.get_block(cond_label).predecessors;
-            if preds.len() == 1 {
-                UNREACHABLE_LABEL
-            } else {
-                assert_eq!(preds.len(), 2);
-                if preds[0] != pred_label {
-                    preds[0]
-                } else {
-                    preds[1]
-                }
-            }
-        };
-
-        for (name, value1, phi_value) in stub_info {
-            let mut phi_vec = vec![(value1, pred_label)];
-            if end_body_label != UNREACHABLE_LABEL {
-                // this is really tricky; we need to lookup proxy_label, not
-                // end_body_label, so we will not confuse new variables
-                // defined in body loop which shadows original ones
-                let proxy_label = proxy_label.unwrap();
-                let value2 = self.env.get_variable(proxy_label, name).clone();
-                phi_vec.push((value2, end_body_label));
-            }
+        for (i, (name, value1, phi_value)) in stub_info.into_iter().enumerate() {
             let (reg_num, reg_type) = match phi_value {
                 ir::Value::Register(reg_num, reg_type) => (reg_num, reg_type),
                 _ => unreachable!(),
             };
+            let mut phi_vec = vec![(value1.with_null_type(reg_type), pred_label)];
+            for (src_label, values) in back_edges {
+                phi_vec.push((values[i].clone().with_null_type(reg_type), *src_label));
+            }
+            self.trace("phi-finalized", || {
+                let entries: Vec<_> = phi_vec
+                    .iter()
+                    .map(|(value, label)| format!("[{}, {}]", value, format_frame(*label)))
+                    .collect();
+                format!(
+                    "{} {}=%.r{} = phi {}",
+                    format_frame(cond_label),
+                    name,
+                    reg_num.0,
+                    entries.join(", ")
+                )
+            });
             self.get_block(cond_label)
-                .phi_set
-                .insert((reg_num, reg_type, phi_vec));
+                .add_phi(ir::Phi::new(reg_num, reg_type, phi_vec));
         }
     }
 
-    fn allocate_new_block(&mut self, parent_env_label: ir::Label) -> ir::Label {
+    // merges values of variables from the condition (if any) and breaks of the loop
+    fn calculate_phi_set_for_loop_exit(
+        &mut self,
+        cont_label: ir::Label,
+        loop_ctx: &LoopContext<'a>,
+    ) {
+        if loop_ctx.breaks.is_empty() {
+            return;
+        }
+        let preds = self.get_block(cont_label).predecessors.clone();
+        let breaks: HashMap<_, _> = loop_ctx
+            .breaks
+            .iter()
+            .map(|(src_label, values)| (*src_label, values))
+            .collect();
+        for (i, name) in loop_ctx.names.iter().enumerate() {
+            // value from the condition is one of the phi functions of the loop
+            let cond_value = self.env.get_variable(cont_label, name).clone();
+            let reg_type = cond_value.get_type();
+            let phi_vec: Vec<_> = preds
+                .iter()
+                .map(|pred| match breaks.get(pred) {
+                    Some(values) => (values[i].clone().with_null_type(reg_type), *pred),
+                    None => (cond_value.clone(), *pred),
+                })
+                .collect();
+
+            let new_value = if phi_vec.iter().all(|(v, _)| *v == phi_vec[0].0) {
+                phi_vec[0].0.clone() // no need to emit phi function
+            } else {
+                let reg_num = self.get_new_reg_num();
+                self.get_block(cont_label)
+                    .add_phi(ir::Phi::new(reg_num, reg_type, phi_vec));
+                ir::Value::Register(reg_num, reg_type)
+            };
+            self.env
+                .update_existing_local_variable(cont_label, name, new_value);
+        }
+    }
+
+    fn push_empty_block(&mut self) -> ir::Label {
         let label = ir::Label(self.blocks.len() as u32);
-        self.blocks.push(ir::Block {
-            label,
-            phi_set: HashSet::new(),
-            predecessors: vec![],
-            body: vec![],
-        });
+        self.blocks.push(ir::Block::new(label));
+        label
+    }
+
+    fn allocate_new_block(&mut self, parent_env_label: ir::Label) -> ir::Label {
+        let label = self.push_empty_block();
         self.env.allocate_new_frame(label, parent_env_label);
+        self.mark_source_span(label);
+        self.trace("new-block", || {
+            format!(
+                "{} env-parent={}",
+                format_frame(label),
+                format_frame(parent_env_label)
+            )
+        });
+        label
+    }
+
+    // block continuing the scope of the given one, e.g. after an if or a loop;
+    // it declares its variables in the frame of that block
+    fn allocate_continuation_block(&mut self, prev_label: ir::Label) -> ir::Label {
+        let label = self.push_empty_block();
+        self.env.share_frame(label, prev_label);
+        self.mark_source_span(label);
+        self.trace("new-block", || {
+            format!(
+                "{} env-shared={}",
+                format_frame(label),
+                format_frame(prev_label)
+            )
+        });
         label
     }
 
+    fn create_proxy_env(&mut self, frame_label: ir::Label) -> ir::Label {
+        let proxy_label = self.env.create_proxy_env(frame_label);
+        self.trace("proxy-frame", || {
+            format!(
+                "{} for={} copy-on-write",
+                format_frame(proxy_label),
+                format_frame(frame_label)
+            )
+        });
+        proxy_label
+    }
+
+    fn insert_empty_proxy_frame(&mut self, frame_label: ir::Label) -> ir::Label {
+        let proxy_label = self.env.insert_empty_proxy_frame(frame_label);
+        self.trace("proxy-frame", || {
+            format!(
+                "{} for={} empty",
+                format_frame(proxy_label),
+                format_frame(frame_label)
+            )
+        });
+        proxy_label
+    }
+
+    fn apply_proxy_env(&mut self, proxy: ir::Label, target: ir::Label) {
+        self.trace("apply-proxy", || {
+            let names: Vec<_> = self.env.get_proxy_variables(proxy);
+            format!(
+                "{} to={} copies=[{}]",
+                format_frame(proxy),
+                format_frame(target),
+                names.join(", ")
+            )
+        });
+        self.env.apply_proxy_env(proxy, target);
+    }
+
+    // the next operations of the block belong to the current statement
+    // (or to the function, outside of statements)
+    fn mark_source_span(&mut self, label: ir::Label) {
+        let span = self.stmt_span.unwrap_or(self.fun_span);
+        let body = &mut self.get_block(label).body;
+        match body.last_mut() {
+            Some(ir::Operation::SourceSpan(last)) => *last = span,
+            _ => body.push(ir::Operation::SourceSpan(span)),
+        }
+    }
+
+    // --trace-eval-order: the program prints the evaluated expression, the
+    // calls are impure so the optimizer keeps their order
+    fn add_eval_trace(&mut self, label: ir::Label, expr: &ast::InnerExpr) {
+        let text = format!("eval {}", expr);
+        let str_type = ir::Type::ptr(ir::Type::Char);
+        let str_val = self.get_global_string(&text);
+        let reg_num = self.get_new_reg_num();
+        let fun_type = ir::Type::ptr(ir::Type::func(ir::Type::Void, vec![str_type]));
+        let body = &mut self.get_block(label).body;
+        body.push(ir::Operation::CastGlobalString(
+            reg_num,
+            text.len() + 1,
+            str_val,
+        ));
+        body.push(ir::Operation::FunctionCall(
+            None,
+            ir::Type::Void,
+            ir::Value::GlobalRegister("printString".to_string(), fun_type),
+            vec![ir::Value::Register(reg_num, str_type)],
+        ));
+    }
+
+    // --trace=codegen, one line per decision on stderr
+    fn trace(&self, event: &str, details: impl FnOnce() -> String) {
+        if self.options.trace_codegen {
+            eprintln!("codegen {}: {} {}", self.fun_name, event, details());
+        }
+    }
+
+    // each block is finished once
+    fn set_terminator(&mut self, label: ir::Label, terminator: ir::Terminator) {
+        let bl = self.get_block(label);
+        debug_assert!(bl.terminator == ir::Terminator::Unreachable);
+        bl.terminator = terminator;
+    }
+
     fn add_branch1_op(&mut self, src: ir::Label, dst: ir::Label) {
-        self.get_block(src).body.push(ir::Operation::Branch1(dst));
+        self.set_terminator(src, ir::Terminator::Branch1(dst));
         self.get_block(dst).predecessors.push(src);
     }
 
-    fn add_branch2_op(&mut self, src: ir::Label, cond: ir::Value, br1: ir::Label, br2: ir::Label) {
-        self.get_block(src)
-            .body
-            .push(ir::Operation::Branch2(cond, br1, br2));
+    fn add_branch2_op(
+        &mut self,
+        src: ir::Label,
+        cond: ir::Value,
+        br1: ir::Label,
+        br2: ir::Label,
+        hint: ir::BranchHint,
+    ) {
+        self.set_terminator(src, ir::Terminator::Branch2(cond, br1, br2, hint));
         self.get_block(br1).predecessors.push(src);
         self.get_block(br2).predecessors.push(src);
     }
@@ -1360,13 +2713,226 @@ impl<'a> FunctionCodeGen<'a> {
     }
 
     fn get_global_string(&mut self, string: &str) -> ir::Value {
-        let str_type = ir::Type::Ptr(Box::new(ir::Type::Char));
-        if let Some(num) = self.global_strings.get(string) {
-            return ir::Value::GlobalRegister(ir::format_global_string(*num), str_type);
+        let str_type = ir::Type::ptr(ir::Type::Char);
+        let id = self.global_strings.intern(string);
+        ir::Value::GlobalRegister(ir::format_global_string(id), str_type)
+    }
+}
+
+// struct fields reached from a local variable only through struct fields,
+// like p.pos.x, are modified in registers; other ones (e.g. arr.[i].x) in memory
+// blocks are named like in the IR, proxy frames by their creation order
+// main is called by the runtime, other functions only from the module
+fn get_function_attributes(fun_name: &str, fun_desc: &FunDesc) -> BTreeSet<ir::FunctionAttribute> {
+    let mut res = BTreeSet::new();
+    if fun_name == "main" {
+        res.insert(ir::FunctionAttribute::Used);
+    } else {
+        res.insert(ir::FunctionAttribute::Internal);
+    }
+    if fun_desc.no_return {
+        res.insert(ir::FunctionAttribute::NoReturn);
+    }
+    res
+}
+
+fn format_frame(label: ir::Label) -> String {
+    match label {
+        ARGS_LABEL => "args".to_string(),
+        UNREACHABLE_LABEL => "unreachable".to_string(),
+        _ if label.0 <= FIRST_PROXY_LABEL.0 && label.0 > u32::MAX / 2 => {
+            format!("P{}", FIRST_PROXY_LABEL.0 - label.0)
+        }
+        _ => format!(".L{}", label.0),
+    }
+}
+
+// variables (or their struct fields) assigned in the block and its nested blocks,
+// possibly also ones declared there, only they can change their values in a loop
+fn collect_assigned_variables<'a>(block: &'a ast::Block, names: &mut HashSet<&'a str>) {
+    use model::ast::InnerStmt::*;
+    for stmt in &block.stmts {
+        match &stmt.inner {
+            Block(bl) => collect_assigned_variables(bl, names),
+            Decl { var_items, .. } => {
+                for (_, init_expr) in var_items {
+                    if let Some(e) = init_expr {
+                        collect_assigned_in_expr(&e.inner, names);
+                    }
+                }
+            }
+            Cond {
+                cond,
+                true_branch,
+                false_branch,
+            } => {
+                collect_assigned_in_expr(&cond.inner, names);
+                collect_assigned_variables(true_branch, names);
+                if let Some(bl) = false_branch {
+                    collect_assigned_variables(bl, names);
+                }
+            }
+            While { cond: e, body, .. } | ForEach { array: e, body, .. } => {
+                collect_assigned_in_expr(&e.inner, names);
+                collect_assigned_variables(body, names);
+            }
+            Assign(lhs, rhs) => {
+                collect_assigned_in_expr(&rhs.inner, names);
+                collect_assigned_lvalue(&lhs.inner, names);
+            }
+            Incr(lhs) | Decr(lhs) => collect_assigned_lvalue(&lhs.inner, names),
+            Expr(e) | Ret(Some(e)) => collect_assigned_in_expr(&e.inner, names),
+            Empty | Ret(None) | Break(_) | Continue(_) => (),
+            NestedFun(_) | Error => unreachable!(),
+        }
+    }
+}
+
+fn collect_assigned_lvalue<'a>(lhs: &'a ast::InnerExpr, names: &mut HashSet<&'a str>) {
+    if let Some(name) = get_assigned_local_var(lhs) {
+        names.insert(name);
+    }
+    // e.g. the index of an array element
+    collect_assigned_in_expr(lhs, names);
+}
+
+// assignment, increment and decrement expressions, e.g. `(x = f())` or `i++`
+fn collect_assigned_in_expr<'a>(expr: &'a ast::InnerExpr, names: &mut HashSet<&'a str>) {
+    use model::ast::InnerExpr::*;
+    match expr {
+        LitVar(_) | LitInt(_) | LitBool(_) | LitStr(_) | LitNull | NewObject(_) => (),
+        Assign(lhs, rhs) => {
+            collect_assigned_in_expr(&rhs.inner, names);
+            collect_assigned_lvalue(&lhs.inner, names);
+        }
+        IncrDecr { lvalue, .. } => collect_assigned_lvalue(&lvalue.inner, names),
+        CastType(e, _) | UnaryOp(_, e) | ObjField { obj: e, .. } | NamedArg { value: e, .. } => {
+            collect_assigned_in_expr(&e.inner, names)
+        }
+        BinaryOp(lhs, _, rhs)
+        | Range(lhs, rhs)
+        | ArrayElem {
+            array: lhs,
+            index: rhs,
+            ..
+        } => {
+            collect_assigned_in_expr(&lhs.inner, names);
+            collect_assigned_in_expr(&rhs.inner, names);
+        }
+        NewArray { elem_cnt, .. } => collect_assigned_in_expr(&elem_cnt.inner, names),
+        FunCall { args, .. } => {
+            for a in args {
+                collect_assigned_in_expr(&a.inner, names);
+            }
         }
+        ObjMethodCall { obj, args, .. } | ClosureCall { closure: obj, args } => {
+            collect_assigned_in_expr(&obj.inner, names);
+            for a in args {
+                collect_assigned_in_expr(&a.inner, names);
+            }
+        }
+        // the body is another function
+        Lambda { captures, .. } => {
+            for (_, _, value) in captures {
+                collect_assigned_in_expr(&value.inner, names);
+            }
+        }
+    }
+}
+
+fn assigns_local_vars(expr: &ast::InnerExpr) -> bool {
+    let mut names = HashSet::new();
+    collect_assigned_in_expr(expr, &mut names);
+    !names.is_empty()
+}
+
+// the variable holding the assigned value (values of arrays and objects are pointers)
+fn get_assigned_local_var(expr: &ast::InnerExpr) -> Option<&str> {
+    match expr {
+        ast::InnerExpr::LitVar(name) => Some(name),
+        ast::InnerExpr::ObjField { obj, .. } if is_struct_field_of_local_var(expr) => {
+            get_assigned_local_var(&obj.inner)
+        }
+        _ => None,
+    }
+}
+
+fn is_struct_field_of_local_var(expr: &ast::InnerExpr) -> bool {
+    match expr {
+        ast::InnerExpr::ObjField {
+            obj,
+            is_obj_a_struct: Some(true),
+            ..
+        } => match &obj.inner {
+            ast::InnerExpr::LitVar(_) => true,
+            inner => is_struct_field_of_local_var(inner),
+        },
+        _ => false,
+    }
+}
+
+// value of a condition known without running the program, taking short-circuit
+// evaluation into account, e.g. `false && f()` is false, but `f() && false`
+// isn't known, because f still has to be called; known conditions have no side effects
+// the only statement of the block (possibly nested in blocks) assigns
+// a literal or a variable to a variable
+fn get_single_assignment(block: &ast::Block) -> Option<(&str, &ast::InnerExpr)> {
+    use model::ast::{InnerExpr::*, InnerStmt};
+    match block.stmts.as_slice() {
+        [stmt] => match &stmt.inner {
+            InnerStmt::Block(bl) => get_single_assignment(bl),
+            InnerStmt::Assign(lhs, rhs) => match (&lhs.inner, &rhs.inner) {
+                (LitVar(name), rhs) if is_trivial_value(rhs) => Some((name, rhs)),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn is_trivial_value(expr: &ast::InnerExpr) -> bool {
+    use model::ast::InnerExpr::*;
+    match expr {
+        LitVar(_) | LitInt(_) | LitBool(_) | LitStr(_) | LitNull => true,
+        CastType(e, _) => is_trivial_value(&e.inner),
+        _ => false,
+    }
+}
+
+fn get_const_cond_value(expr: &ast::InnerExpr) -> Option<bool> {
+    use model::ast::{BinaryOp::*, InnerExpr::*, InnerUnaryOp::*};
+    match expr {
+        LitBool(val) => Some(*val),
+        BinaryOp(lhs, op @ And, rhs) | BinaryOp(lhs, op @ Or, rhs) => {
+            // value deciding the result without evaluating the other operand
+            let decisive = matches!(op, Or);
+            match (
+                get_const_cond_value(&lhs.inner),
+                get_const_cond_value(&rhs.inner),
+            ) {
+                (Some(l), _) if l == decisive => Some(l),
+                (Some(_), r) => r,
+                (None, Some(r)) if r == decisive && is_side_effect_free(&lhs.inner) => Some(r),
+                _ => None,
+            }
+        }
+        UnaryOp(ast::ItemWithSpan { inner: BoolNeg, .. }, e) => {
+            get_const_cond_value(&e.inner).map(|val| !val)
+        }
+        _ => None,
+    }
+}
 
-        let reg = ir::GlobalStrNum(self.global_strings.len() as u32);
-        self.global_strings.insert(string.to_string(), reg);
-        ir::Value::GlobalRegister(ir::format_global_string(reg), str_type)
+// evaluation can't fail nor change anything (calls, accessing arrays
+// or fields and division, which may fail, are excluded)
+fn is_side_effect_free(expr: &ast::InnerExpr) -> bool {
+    use model::ast::{BinaryOp::*, InnerExpr::*};
+    match expr {
+        LitVar(_) | LitInt(_) | LitBool(_) | LitStr(_) | LitNull => true,
+        CastType(e, _) | UnaryOp(_, e) => is_side_effect_free(&e.inner),
+        BinaryOp(_, Div, _) | BinaryOp(_, Mod, _) => false,
+        BinaryOp(lhs, _, rhs) => is_side_effect_free(&lhs.inner) && is_side_effect_free(&rhs.inner),
+        _ => false,
     }
 }
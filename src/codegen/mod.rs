@@ -1,80 +1,61 @@
-use codegen::{class::ClassRegistry, function::FunctionCodeGen};
+use codegen::{cache::FunctionCache, class::ClassRegistry, function::FunctionCodeGen};
+use ice;
 use model::{ast, ir};
-use semantics::global_context::GlobalContext;
-use std::collections::{HashMap, VecDeque};
+use options::{CompilerOptions, Report};
+use semantics::global_context::{ClassDesc, GlobalContext};
 
-mod class;
+mod cache;
+pub mod class;
 mod function;
 
 pub struct CodeGen<'a> {
     ast: &'a ast::Program,
     gctx: &'a GlobalContext,
+    options: &'a CompilerOptions,
 }
 
 impl<'a> CodeGen<'a> {
-    pub fn new(ast: &'a ast::Program, gctx: &'a GlobalContext) -> CodeGen<'a> {
-        CodeGen { ast, gctx }
+    pub fn new(
+        ast: &'a ast::Program,
+        gctx: &'a GlobalContext,
+        options: &'a CompilerOptions,
+    ) -> CodeGen<'a> {
+        CodeGen { ast, gctx, options }
     }
 
     pub fn generate_ir(&self) -> ir::Program {
         let mut prog_ir = ir::Program {
+            size_type: ir::Type::size_type(self.options.pointer_width),
+            structs: vec![],
             classes: vec![],
             functions: vec![],
-            global_strings: HashMap::new(),
+            global_strings: ir::GlobalStrings::default(),
         };
-        let mut class_registry = ClassRegistry::new();
-
-        self.calculate_class_registry(&mut class_registry);
+        let class_registry = ClassRegistry::build(self.ast);
         self.generate_functions_ir(&mut prog_ir, &class_registry);
         class_registry.insert_classes_ir_into(&mut prog_ir);
 
         prog_ir
     }
 
-    fn calculate_class_registry(&self, class_registry: &mut ClassRegistry<'a>) {
-        let mut class_queue = VecDeque::new();
-        let mut class_hierarchy = HashMap::new();
-        for def in &self.ast.defs {
-            if let ast::TopDef::ClassDef(cl) = def {
-                match &cl.parent_type {
-                    Some(ast::ItemWithSpan {
-                        inner: ast::InnerType::Class(parent_name),
-                        ..
-                    }) => {
-                        class_hierarchy
-                            .entry(parent_name)
-                            .or_insert_with(Vec::new)
-                            .push(cl);
-                    }
-                    None => {
-                        class_registry.process_class_def(&cl);
-                        class_queue.push_back(&cl.name.inner);
-                    }
-                    _ => unreachable!(),
-                }
-            }
-        }
-        while let Some(cl_name) = class_queue.pop_front() {
-            if let Some(sons) = class_hierarchy.get(&cl_name) {
-                for cl in sons {
-                    class_registry.process_class_def(&cl);
-                    class_queue.push_back(&cl.name.inner);
-                }
-            }
-        }
-    }
-
     fn generate_functions_ir(&self, prog_ir: &mut ir::Program, class_registry: &ClassRegistry) {
+        // the trace shows the decisions of the codegen, so nothing is cached then;
+        // cached functions have no source spans (nor the positions of this build)
+        let cache = match &self.options.cache_dir {
+            Some(dir)
+                if !self.options.trace_codegen
+                    && self.options.report != Some(Report::SourceMap) =>
+            {
+                Some(FunctionCache::new(dir))
+            }
+            _ => None,
+        };
         for def in &self.ast.defs {
             match def {
                 ast::TopDef::FunDef(fun) => {
-                    let fun_cg = FunctionCodeGen::new(
-                        &self.gctx,
-                        None,
-                        &mut prog_ir.global_strings,
-                        &class_registry,
-                    );
-                    let fun_ir = fun_cg.generate_function_ir(&fun);
+                    ice::enter_function(&fun.name.inner, Some(fun.span));
+                    let fun_ir =
+                        self.generate_function_ir(fun, None, &cache, prog_ir, class_registry);
                     prog_ir.functions.push(fun_ir);
                 }
                 ast::TopDef::ClassDef(cl) => {
@@ -83,21 +64,58 @@ impl<'a> CodeGen<'a> {
                         match &it.inner {
                             ast::InnerClassItemDef::Field(_, _) => (),
                             ast::InnerClassItemDef::Method(fun) => {
-                                let fun_cg = FunctionCodeGen::new(
-                                    &self.gctx,
+                                let name = ir::format_method_name(&cl.name.inner, &fun.name.inner);
+                                ice::enter_function(&name, Some(fun.span));
+                                let fun_ir = self.generate_function_ir(
+                                    fun,
                                     Some(cl_desc),
-                                    &mut prog_ir.global_strings,
-                                    &class_registry,
+                                    &cache,
+                                    prog_ir,
+                                    class_registry,
                                 );
-                                let fun_ir = fun_cg.generate_function_ir(&fun);
                                 prog_ir.functions.push(fun_ir);
                             }
-                            ast::InnerClassItemDef::Error => unreachable!(),
+                            ast::InnerClassItemDef::Class(_) | ast::InnerClassItemDef::Error => {
+                                unreachable!()
+                            }
                         }
                     }
                 }
-                ast::TopDef::Error => unreachable!(),
+                ast::TopDef::StructDef(_) => (),
+                ast::TopDef::TypeDef(_) | ast::TopDef::Error => unreachable!(),
             }
         }
     }
+
+    // cl_desc is given for methods
+    fn generate_function_ir(
+        &self,
+        fun: &ast::FunDef,
+        cl_desc: Option<&ClassDesc>,
+        cache: &Option<FunctionCache>,
+        prog_ir: &mut ir::Program,
+        class_registry: &ClassRegistry,
+    ) -> ir::Function {
+        let key = cache.as_ref().map(|cache| {
+            let class_name = cl_desc.map(|cl| cl.get_name());
+            cache.get_key(fun, class_name, self.gctx, class_registry, self.options)
+        });
+        if let (Some(cache), Some(key)) = (cache, &key) {
+            if let Some(fun_ir) = cache.load(key, &mut prog_ir.global_strings) {
+                return fun_ir;
+            }
+        }
+        let fun_cg = FunctionCodeGen::new(
+            &self.gctx,
+            cl_desc,
+            &mut prog_ir.global_strings,
+            &class_registry,
+            self.options,
+        );
+        let fun_ir = fun_cg.generate_function_ir(&fun);
+        if let (Some(cache), Some(key)) = (cache, &key) {
+            cache.store(key, &fun_ir, &prog_ir.global_strings);
+        }
+        fun_ir
+    }
 }
@@ -22,14 +22,30 @@ impl<'a> CodeMap<'a> {
         }
     }
 
+    pub fn get_filename(&self) -> &str {
+        self.filename
+    }
+
     pub fn get_code(&self) -> &str {
         &self.code
     }
 
     pub fn format_message(&self, span: Span, msg: &str) -> String {
+        self.format_colored_message(span, msg, Color::Red)
+    }
+
+    pub fn format_warning_message(&self, span: Span, msg: &str) -> String {
+        self.format_colored_message(span, msg, Color::Yellow)
+    }
+
+    pub fn format_note_message(&self, span: Span, msg: &str) -> String {
+        self.format_colored_message(span, msg, Color::Cyan)
+    }
+
+    fn format_colored_message(&self, span: Span, msg: &str, color: Color) -> String {
         assert!(span.0 <= span.1);
         let mut result = String::new();
-        let err_fmt = |s: &str| s.red().bold();
+        let err_fmt = |s: &str| s.color(color).bold();
 
         // empty span means just a message, without localisation
         if span.0 != span.1 {
@@ -101,7 +117,8 @@ impl<'a> CodeMap<'a> {
         result
     }
 
-    fn find_row_col(&self, pos: usize) -> Option<(usize, usize)> {
+    // row and column (both counted from 0) of a byte position in the code
+    pub fn find_row_col(&self, pos: usize) -> Option<(usize, usize)> {
         let mut cur_pos = 0usize;
 
         for (row, line) in self.lines.iter().enumerate() {
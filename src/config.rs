@@ -0,0 +1,161 @@
+use options::{CompilerOptions, Dialect, IntDivision, LintLevel, Target};
+use semantics::LINT_NAMES;
+use std::path::{Path, PathBuf};
+
+// The project configuration, latte.toml, in the directory of the program or
+// one of its parents (the nearest one is used), e.g. the policy of a course:
+//   [build]
+//   opt-level = 2           # the defaults of -O, --target, --check-bounds,
+//   target = "x86_64"       # --check-stack and --strict
+//   check-bounds = true
+//   check-stack = true
+//   strict = true
+//   dialect = "core"        # or "extended", --dialect
+//   int-division = "trunc"  # or "euclid", --int-division
+//   [lints]
+//   unused_result = "deny"  # "allow", "warn" (the default) or "deny"
+// It gives only the defaults, the options of the command line override it.
+// Only this subset of TOML is supported: sections, comments and keys with
+// integers, booleans and strings (without escapes).
+
+pub const CONFIG_FILE: &str = "latte.toml";
+
+enum ConfigValue {
+    Int(i64),
+    Bool(bool),
+    Str(String),
+}
+
+// the configuration for the program in the directory
+pub fn find_config(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .map(|d| d.join(CONFIG_FILE))
+        .find(|path| path.is_file())
+}
+
+// errors are "line N: message"
+pub fn apply_config(text: &str, options: &mut CompilerOptions) -> Result<(), String> {
+    let mut section = String::new();
+    for (i, line) in text.lines().enumerate() {
+        let with_line = |msg: String| format!("line {}: {}", i + 1, msg);
+        let line = strip_comment(line).trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.starts_with('[') {
+            section = match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                Some(name @ "build") | Some(name @ "lints") => name.to_string(),
+                _ => return Err(with_line(format!("unknown section {}", line))),
+            };
+            continue;
+        }
+        let (key, value) = match line.find('=') {
+            Some(pos) => (line[..pos].trim(), line[pos + 1..].trim()),
+            None => return Err(with_line("expected key = value".to_string())),
+        };
+        let value = parse_value(value).ok_or_else(|| with_line("invalid value".to_string()))?;
+        match section.as_str() {
+            "build" => apply_build_option(key, value, options),
+            "lints" => apply_lint_level(key, value, options),
+            _ => Err(format!("{} outside of a section", key)),
+        }
+        .map_err(with_line)?;
+    }
+    Ok(())
+}
+
+// `#` inside of a string doesn't start a comment
+fn strip_comment(line: &str) -> &str {
+    let mut inside_string = false;
+    for (i, ch) in line.char_indices() {
+        match ch {
+            '"' => inside_string = !inside_string,
+            '#' if !inside_string => return &line[..i],
+            _ => (),
+        }
+    }
+    line
+}
+
+fn parse_value(value: &str) -> Option<ConfigValue> {
+    match value {
+        "true" => Some(ConfigValue::Bool(true)),
+        "false" => Some(ConfigValue::Bool(false)),
+        _ if value.len() >= 2 && value.starts_with('"') && value.ends_with('"') => {
+            let s = &value[1..value.len() - 1];
+            if s.contains('"') || s.contains('\\') {
+                return None;
+            }
+            Some(ConfigValue::Str(s.to_string()))
+        }
+        _ => value.parse().ok().map(ConfigValue::Int),
+    }
+}
+
+fn apply_build_option(
+    key: &str,
+    value: ConfigValue,
+    options: &mut CompilerOptions,
+) -> Result<(), String> {
+    match (key, value) {
+        ("opt-level", ConfigValue::Int(level)) if (0..=2).contains(&level) => {
+            options.opt_level = level as u32
+        }
+        ("target", ConfigValue::Str(target)) => {
+            options.target = match target.as_str() {
+                "x86_64" => Target::X86_64,
+                "aarch64" => Target::AArch64,
+                "riscv64" => Target::RiscV64,
+                _ => return Err(format!("unknown target {}", target)),
+            }
+        }
+        ("check-bounds", ConfigValue::Bool(b)) => options.check_bounds = b,
+        ("check-stack", ConfigValue::Bool(b)) => options.check_stack = b,
+        ("strict", ConfigValue::Bool(b)) => options.strict = b,
+        ("dialect", ConfigValue::Str(dialect)) => {
+            options.dialect = match dialect.as_str() {
+                "core" => Dialect::Core,
+                "extended" => Dialect::Extended,
+                _ => return Err(format!("unknown dialect {}", dialect)),
+            }
+        }
+        ("int-division", ConfigValue::Str(division)) => {
+            options.int_division = match division.as_str() {
+                "trunc" => IntDivision::Truncating,
+                "euclid" => IntDivision::Euclidean,
+                _ => return Err(format!("unknown int division {}", division)),
+            }
+        }
+        ("opt-level", _)
+        | ("target", _)
+        | ("check-bounds", _)
+        | ("check-stack", _)
+        | ("strict", _)
+        | ("dialect", _) => return Err(format!("invalid value of {}", key)),
+        _ => return Err(format!("unknown option {}", key)),
+    }
+    Ok(())
+}
+
+fn apply_lint_level(
+    key: &str,
+    value: ConfigValue,
+    options: &mut CompilerOptions,
+) -> Result<(), String> {
+    if !LINT_NAMES.contains(&key) {
+        return Err(format!("unknown lint {}", key));
+    }
+    let level = match value {
+        ConfigValue::Str(level) if level == "allow" => LintLevel::Allow,
+        ConfigValue::Str(level) if level == "warn" => LintLevel::Warn,
+        ConfigValue::Str(level) if level == "deny" => LintLevel::Deny,
+        _ => {
+            return Err(format!(
+                "invalid level of {}, expected \"allow\", \"warn\" or \"deny\"",
+                key
+            ))
+        }
+    };
+    options.lint_levels.insert(key.to_string(), level);
+    Ok(())
+}
@@ -0,0 +1,216 @@
+// Longer explanations of the diagnostics, for `explain`. A diagnostic is
+// found by its message: the parts of the message between the `{}`
+// placeholders have to appear in the query, so a whole reported message
+// (with the names and types filled in) can be pasted, or the query can be
+// just a part of the message.
+
+pub struct Explanation {
+    pub message: &'static str,
+    pub text: &'static str,
+}
+
+const EXPLANATIONS: &[Explanation] = &[
+    Explanation {
+        message: "Error: expected type {}, got type {}",
+        text: "The expression has a different type than required by the context: \
+               the declared type of a variable, the type of a parameter, the type \
+               of an array element or a condition (boolean). There are no implicit \
+               conversions, except that a reference to a subclass can be used where \
+               its superclass is expected and null can be used as any class or array. \
+               In particular an int isn't a condition (compare it with 0, e.g. \
+               `n != 0`) and a boolean isn't an int.",
+    },
+    Explanation {
+        message: "Error: variable not defined",
+        text: "The variable is used outside of the scope of its declaration or it isn't \
+               declared at all. A variable is visible from its declaration to the end of \
+               the enclosing block; fields of the class are visible in its methods \
+               (also as self.field).",
+    },
+    Explanation {
+        message: "Error: variable already defined in current scope",
+        text: "Two variables with the same name are declared in one block. A variable \
+               of an inner block can shadow a variable of an outer block, but in one \
+               block every name can be declared only once. The note points to the first \
+               declaration.",
+    },
+    Explanation {
+        message: "Error: variable shadows a parameter",
+        text: "The outermost block of the body of a function is the scope of its \
+               parameters, so a variable declared there can't have the name of \
+               a parameter. Rename the variable or declare it in a nested block.",
+    },
+    Explanation {
+        message: "Error: duplicate parameter name",
+        text: "Two parameters of the function, method or lambda have the same name.",
+    },
+    Explanation {
+        message: "Error: detected potential execution path without return",
+        text: "A function returning a value must end with a return statement on every \
+               execution path. The analysis only knows constant conditions, e.g. \
+               while (true) without break doesn't end, but it doesn't know that \
+               a condition like x > 0 || x <= 0 is always true. Add a return (or a call \
+               of error()) at the end of the function.",
+    },
+    Explanation {
+        message: "Error: unreachable statement after return statement",
+        text: "Statements following a return in the same block are never executed. \
+               Remove them or move the return.",
+    },
+    Explanation {
+        message: "Error: variable '{}' might be used before being assigned",
+        text: "Reported with --strict: on some execution path the variable is read \
+               before any value is assigned to it. Without --strict variables are \
+               initialized with default values (0, false, \"\" or null).",
+    },
+    Explanation {
+        message: "Error: type of returned expression mismatch declared return type",
+        text: "The returned expression has a different type than the declared return \
+               type of the function; a void function can only use return without \
+               an expression.",
+    },
+    Explanation {
+        message: "Error: main function not found",
+        text: "Every program needs the function int main(), where its execution starts.",
+    },
+    Explanation {
+        message: "Error: main function has invalid signature, it must return int and take no arguments",
+        text: "The main function must be declared as int main(); its result is the exit \
+               code of the program.",
+    },
+    Explanation {
+        message: "Error: expected {} argument(s), got {}.",
+        text: "The function or method is called with a wrong number of arguments. \
+               Every parameter needs exactly one argument; arguments can also be given \
+               by name (name: value) after the positional ones.",
+    },
+    Explanation {
+        message: "Error: method signature does not match method defined in superclass",
+        text: "A method overriding a method of the superclass must have the same types \
+               of parameters and the same return type, so it can be called virtually \
+               through a reference to the superclass.",
+    },
+    Explanation {
+        message: "Error: detected cycle in inheritance chain",
+        text: "A class extends itself, directly or through other classes. Inheritance \
+               must form a tree with Object at the root.",
+    },
+    Explanation {
+        message: "Error: required an l-value",
+        text: "Only a variable, an element of an array (arr.[i]) or a field of an object \
+               can be assigned, incremented or decremented.",
+    },
+    Explanation {
+        message: "Error: strings are immutable, their characters can't be modified",
+        text: "Characters of a string can be read by indexing, but not assigned. \
+               Use a StringBuilder to build a new string.",
+    },
+    Explanation {
+        message: "Error: variables captured by a lambda can't be modified in its body",
+        text: "A lambda captures the values of the variables it uses when it is created, \
+               so assigning them in its body wouldn't change the variables of the \
+               enclosing function. Use a local variable or a field of an object \
+               instead.",
+    },
+    Explanation {
+        message: "Error: break outside of a loop",
+        text: "break (and continue) can be used only inside a while or a for loop; \
+               a labeled break has to name one of the enclosing loops.",
+    },
+    Explanation {
+        message: "Error: structs are values, declare a variable instead of using new",
+        text: "Structs aren't allocated on the heap: a variable of a struct type holds \
+               the struct itself, with its fields initialized with default values, \
+               so new can't be used with them.",
+    },
+    Explanation {
+        message: "Error: too many instantiations of generic definition {}, its type arguments probably grow infinitely",
+        text: "Generic definitions are instantiated for every list of type arguments \
+               they are used with. A generic function calling itself with a bigger type \
+               (e.g. f<T> calling f<Box<T>>) would need infinitely many instances.",
+    },
+    Explanation {
+        message: "Warning: result of the expression is unused and it has no side effects",
+        text: "The statement computes a value and ignores it, so it has no effect; \
+               probably an assignment or a call is missing. \
+               The name of the lint (for allow annotations) is unused_result.",
+    },
+    Explanation {
+        message: "Warning: both branches of the if statement are the same",
+        text: "The if statement executes the same code regardless of the condition; \
+               probably one of the branches was supposed to be different. \
+               The name of the lint (for allow annotations) is same_branches.",
+    },
+    Explanation {
+        message: "Warning: the same condition is already checked earlier in this if-else chain",
+        text: "The condition was checked by an earlier if of the chain, so this branch \
+               is never executed. \
+               The name of the lint (for allow annotations) is repeated_condition.",
+    },
+    Explanation {
+        message: "Warning: invalid annotation, expected `latte: allow(<lint>, ...)`",
+        text: "A single line comment starting with `latte:` is an annotation. The only \
+               one is `// latte: allow(lint, ...)`, which silences the warnings of the \
+               named lints in the definition, class item or statement following it.",
+    },
+    Explanation {
+        message: "Warning: unknown lint {} in the annotation",
+        text: "The lints are unused_result, same_branches and repeated_condition; \
+               `explain` of a warning shows the name of its lint.",
+    },
+    Explanation {
+        message: "Warning: the annotation isn't followed by a definition or a statement",
+        text: "An allow annotation applies to the first definition, class item or \
+               statement starting after it, so it can't be at the end of the file.",
+    },
+    Explanation {
+        message: "Error: {} is an extension of Latte, not allowed with --dialect=core",
+        text: "With --dialect=core (or dialect = \"core\" in latte.toml) only the language \
+               of the original Latte specification is accepted, with arrays, classes, \
+               inheritance and virtual methods. Break, continue, labeled loops, ===, \
+               indexing of strings, foreach loops over objects, nested functions and \
+               classes, lambdas, ranges, generics, structs, type aliases, named arguments, \
+               inline hints and the builtin functions and classes other than printInt, \
+               printString, error, readInt and readString are extensions, allowed by \
+               the default --dialect=extended.",
+    },
+    Explanation {
+        message: "Multiline comment must be closed before EOF",
+        text: "A comment started with /* doesn't end with */ before the end of the file.",
+    },
+];
+
+pub fn get_explanations() -> &'static [Explanation] {
+    EXPLANATIONS
+}
+
+pub fn find_explanations(query: &str) -> Vec<&'static Explanation> {
+    let query = strip_severity(query.trim()).to_lowercase();
+    if query.is_empty() {
+        return vec![];
+    }
+    EXPLANATIONS
+        .iter()
+        .filter(|e| {
+            let message = strip_severity(e.message).to_lowercase();
+            message.replace("{}", "").contains(&query) || matches_message(&message, &query)
+        })
+        .collect()
+}
+
+fn strip_severity(msg: &str) -> &str {
+    msg.trim_start_matches("Error: ")
+        .trim_start_matches("Warning: ")
+}
+
+// the parts of the message between the placeholders appear in order
+fn matches_message(message: &str, query: &str) -> bool {
+    let mut pos = 0;
+    for part in message.split("{}").filter(|p| !p.is_empty()) {
+        match query[pos..].find(part) {
+            Some(i) => pos += i + part.len(),
+            None => return false,
+        }
+    }
+    true
+}
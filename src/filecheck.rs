@@ -0,0 +1,237 @@
+// A small FileCheck: the output of the compiler is checked against
+// directives written in comments of a test program, e.g.
+//     // RUN: build --stdout -O1 %s
+//     // CHECK: define i32 @main()
+//     // CHECK-NOT: phi
+//     // CHECK-NEXT: ret i32 0
+// RUN lines are arguments of the compiler (%s is the test file; RUN-FAIL
+// expects it to fail), --check-prefix=P makes a RUN line use the directives
+// P:, P-NEXT: and P-NOT: instead of CHECK. With ` | ` the output of the
+// compiler is the input of another run of it, e.g.
+//     // RUN: build --emit=ir-json %s | ir-dis -
+// The checked output is the standard output of the last run followed by
+// the standard error of all of them.
+//
+// The directives match whole lines of the output, in order: CHECK finds
+// the first matching line after the previous match, CHECK-NEXT has to
+// match the line right after it and CHECK-NOT can't match any line between
+// the matches around it. A pattern is a substring of the line, where
+// {{...}} is a regular expression and runs of spaces match any spaces.
+
+use regex::{self, Regex};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DirectiveKind {
+    Check,
+    Next,
+    Not,
+}
+
+pub struct Directive {
+    pub kind: DirectiveKind,
+    pub text: String,
+    pub line: usize, // in the test file, from 1
+    pattern: Regex,
+}
+
+pub struct RunLine {
+    pub args: Vec<String>, // with %s replaced
+    // of the runs reading the output of the previous one
+    pub piped_args: Vec<Vec<String>>,
+    pub expect_failure: bool,
+    pub check_prefix: String,
+    pub line: usize,
+}
+
+#[derive(Debug)]
+pub struct CheckError {
+    pub line: usize, // of the directive in the test file
+    pub message: String,
+}
+
+const DEFAULT_PREFIX: &str = "CHECK";
+
+pub fn get_run_lines(code: &str, file_name: &str) -> Vec<RunLine> {
+    let mut res = vec![];
+    for (i, comment) in get_comments(code) {
+        let (rest, expect_failure) = if let Some(rest) = comment.strip_prefix("RUN:") {
+            (rest, false)
+        } else if let Some(rest) = comment.strip_prefix("RUN-FAIL:") {
+            (rest, true)
+        } else {
+            continue;
+        };
+        let mut check_prefix = DEFAULT_PREFIX.to_string();
+        let mut commands = vec![vec![]];
+        for arg in rest.split_whitespace() {
+            if let Some(prefix) = arg.strip_prefix("--check-prefix=") {
+                check_prefix = prefix.to_string();
+            } else if arg == "|" {
+                commands.push(vec![]);
+            } else {
+                let args = commands.last_mut().unwrap();
+                args.push(arg.replace("%s", file_name));
+            }
+        }
+        let args = commands.remove(0);
+        res.push(RunLine {
+            args,
+            piped_args: commands,
+            expect_failure,
+            check_prefix,
+            line: i + 1,
+        });
+    }
+    res
+}
+
+pub fn get_directives(code: &str, prefix: &str) -> Result<Vec<Directive>, CheckError> {
+    let kinds = [
+        ("-NEXT:", DirectiveKind::Next),
+        ("-NOT:", DirectiveKind::Not),
+        (":", DirectiveKind::Check),
+    ];
+    let mut res: Vec<Directive> = vec![];
+    for (i, comment) in get_comments(code) {
+        let rest = match comment.strip_prefix(prefix) {
+            Some(rest) => rest,
+            None => continue,
+        };
+        let (suffix, kind, text) = match kinds
+            .iter()
+            .find_map(|&(suffix, kind)| rest.strip_prefix(suffix).map(|text| (suffix, kind, text)))
+        {
+            Some(found) => found,
+            None => continue,
+        };
+        let text = text.trim();
+        let error = |message: String| CheckError {
+            line: i + 1,
+            message,
+        };
+        if text.is_empty() {
+            return Err(error(format!("{}{} without a pattern", prefix, suffix)));
+        }
+        let has_match_before = res.iter().any(|d| d.kind != DirectiveKind::Not);
+        if kind == DirectiveKind::Next && !has_match_before {
+            return Err(error(format!(
+                "{}-NEXT: has to follow a matching directive",
+                prefix
+            )));
+        }
+        let pattern = compile_pattern(text).map_err(error)?;
+        res.push(Directive {
+            kind,
+            text: text.to_string(),
+            line: i + 1,
+            pattern,
+        });
+    }
+    Ok(res)
+}
+
+pub fn check_output(output: &str, directives: &[Directive]) -> Result<(), CheckError> {
+    let lines: Vec<_> = output.lines().collect();
+    let mut pos = 0; // the first line not checked yet
+    let mut nots: Vec<&Directive> = vec![];
+    for d in directives {
+        let found = match d.kind {
+            DirectiveKind::Not => {
+                nots.push(d);
+                continue;
+            }
+            DirectiveKind::Check => (pos..lines.len()).find(|&i| d.pattern.is_match(lines[i])),
+            DirectiveKind::Next => {
+                Some(pos).filter(|&i| i < lines.len() && d.pattern.is_match(lines[i]))
+            }
+        };
+        let i = match found {
+            Some(i) => i,
+            None => {
+                let place = match d.kind {
+                    DirectiveKind::Next => "on the next line",
+                    _ => "in the output",
+                };
+                return Err(CheckError {
+                    line: d.line,
+                    message: format!(
+                        "expected string not found {}: {}\n{}",
+                        place,
+                        d.text,
+                        format_position(&lines, pos, "scanning from")
+                    ),
+                });
+            }
+        };
+        check_excluded(&lines, pos, i, &nots)?;
+        nots.clear();
+        pos = i + 1;
+    }
+    check_excluded(&lines, pos, lines.len(), &nots)
+}
+
+// none of the CHECK-NOT patterns matches the lines from..to
+fn check_excluded(
+    lines: &[&str],
+    from: usize,
+    to: usize,
+    nots: &[&Directive],
+) -> Result<(), CheckError> {
+    for d in nots {
+        if let Some(i) = (from..to).find(|&i| d.pattern.is_match(lines[i])) {
+            return Err(CheckError {
+                line: d.line,
+                message: format!(
+                    "excluded string found in the output: {}\n{}",
+                    d.text,
+                    format_position(lines, i, "found at")
+                ),
+            });
+        }
+    }
+    Ok(())
+}
+
+fn format_position(lines: &[&str], i: usize, what: &str) -> String {
+    match lines.get(i) {
+        Some(line) => format!("{} output line {}: {}", what, i + 1, line),
+        None => format!("{} the end of the output", what),
+    }
+}
+
+// the text of line comments starting with //, with the numbers of their lines
+fn get_comments(code: &str) -> Vec<(usize, &str)> {
+    code.lines()
+        .enumerate()
+        .filter_map(|(i, line)| line.find("//").map(|pos| (i, line[pos + 2..].trim())))
+        .collect()
+}
+
+fn compile_pattern(text: &str) -> Result<Regex, String> {
+    let mut res = String::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let (literal, regex_part) = match rest.find("{{") {
+            Some(start) => match rest[start..].find("}}") {
+                Some(len) => (&rest[..start], Some(&rest[start + 2..start + len])),
+                None => return Err(format!("unterminated {{{{ in pattern: {}", text)),
+            },
+            None => (rest, None),
+        };
+        for (i, word) in literal.split(' ').enumerate() {
+            if i > 0 && !res.ends_with(" +") {
+                res += " +";
+            }
+            res += &regex::escape(word);
+        }
+        match regex_part {
+            Some(part) => {
+                res += &format!("(?:{})", part);
+                rest = &rest[literal.len() + part.len() + 4..];
+            }
+            None => rest = "",
+        }
+    }
+    Regex::new(&res)
+        .map_err(|err| format!("invalid regular expression in pattern {}: {}", text, err))
+}
@@ -0,0 +1,146 @@
+// Formatting of programs (`fmt`): only the whitespace at the ends of lines
+// is changed, so the formatted program has the same tokens. Lines are
+// indented by 4 spaces per level of unclosed braces and brackets (and
+// bodies of ifs and loops without braces), trailing whitespace is removed
+// and runs of blank lines are collapsed into one.
+
+const INDENT: &str = "    ";
+
+// state of the scanner between lines
+#[derive(Default)]
+struct Scanner {
+    depth: usize,
+    // quote of the string or character literal we are inside
+    literal: Option<char>,
+    escaped: bool,
+    block_comment: bool,
+}
+
+// what the scanner found in a line
+struct ScannedLine {
+    // closing braces and brackets at the beginning of the line
+    leading_closers: usize,
+    // the line without comments
+    code: String,
+}
+
+pub fn format_code(code: &str) -> String {
+    let mut scanner = Scanner::default();
+    let mut lines: Vec<String> = vec![];
+    // extra levels of the bodies of ifs and loops without braces
+    let mut body_levels = 0;
+    for line in code.lines() {
+        // a line beginning inside a literal or a comment is kept as it is
+        let verbatim = scanner.literal.is_some() || scanner.block_comment;
+        let depth = scanner.depth;
+        let scanned = scanner.scan(line);
+        let text = line.trim();
+        if verbatim {
+            lines.push(line.trim_end().to_string());
+            continue;
+        }
+        if text.is_empty() {
+            if matches!(lines.last(), Some(l) if !l.is_empty()) {
+                lines.push(String::new());
+            }
+            continue;
+        }
+        let code = scanned.code.trim();
+        // a brace opening the body is at the level of its header
+        let body_level = if code.starts_with('{') {
+            0
+        } else {
+            body_levels
+        };
+        let level = depth.saturating_sub(scanned.leading_closers) + body_level;
+        lines.push(format!("{}{}", INDENT.repeat(level), text));
+        if !code.is_empty() {
+            body_levels = if is_header_without_braces(code) {
+                body_levels + 1
+            } else {
+                0
+            };
+        }
+    }
+    while lines.last().map(String::as_str) == Some("") {
+        lines.pop();
+    }
+    lines.iter().map(|l| format!("{}\n", l)).collect()
+}
+
+impl Scanner {
+    // comments are recognized the same way as in parser::replace_comments
+    fn scan(&mut self, line: &str) -> ScannedLine {
+        let mut leading_closers = 0;
+        let mut at_beginning = true;
+        let mut code = String::new();
+        let mut chars = line.chars().peekable();
+        while let Some(ch) = chars.next() {
+            if self.block_comment {
+                if ch == '*' && chars.peek() == Some(&'/') {
+                    chars.next();
+                    self.block_comment = false;
+                }
+                continue;
+            }
+            if let Some(quote) = self.literal {
+                if self.escaped {
+                    self.escaped = false;
+                } else if ch == '\\' {
+                    self.escaped = true;
+                } else if ch == quote {
+                    self.literal = None;
+                }
+                code.push(ch);
+                continue;
+            }
+            match ch {
+                '#' => break,
+                '/' if chars.peek() == Some(&'/') => break,
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    self.block_comment = true;
+                    continue;
+                }
+                '"' | '\'' => self.literal = Some(ch),
+                '{' | '(' | '[' => self.depth += 1,
+                '}' | ')' | ']' => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if at_beginning {
+                        leading_closers += 1;
+                    }
+                }
+                _ => (),
+            }
+            if !ch.is_whitespace() && !"})]".contains(ch) {
+                at_beginning = false;
+            }
+            code.push(ch);
+        }
+        ScannedLine {
+            leading_closers,
+            code,
+        }
+    }
+}
+
+// e.g. `if (x)`, `} else` or `label: while (x)`, followed by a body
+// in the next line
+fn is_header_without_braces(code: &str) -> bool {
+    if code.ends_with('{') || code.ends_with(';') || code.ends_with('}') {
+        return false;
+    }
+    let code = code.trim_start_matches(|c: char| c == '}' || c.is_whitespace());
+    let first_word = |s: &str| -> String {
+        s.chars()
+            .take_while(|c| c.is_ascii_alphanumeric() || *c == '_')
+            .collect()
+    };
+    let mut word = first_word(code);
+    let rest = code[word.len()..].trim_start();
+    if !word.is_empty() && rest.starts_with(':') {
+        word = first_word(rest[1..].trim_start());
+    }
+    let mut last_word = code.rsplit(|c: char| !(c.is_ascii_alphanumeric() || c == '_'));
+    ["if", "else", "while", "for"].contains(&word.as_str()) || last_word.next() == Some("else")
+}
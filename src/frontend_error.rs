@@ -7,15 +7,47 @@ pub type FrontendResult<T> = Result<T, Vec<FrontendError>>;
 pub struct FrontendError {
     pub err: String, // consider variants with &'static str and owning String
     pub span: Span,
+    pub kind: ErrorKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ErrorKind {
+    // (warnings too, they are reported by format_warnings)
+    Error,
+    // points to a place related to the preceding error (e.g. a previous
+    // declaration), it isn't counted as an error
+    Note,
+}
+
+pub fn error_with_note(err: &str, span: Span, note: &str, note_span: Span) -> Vec<FrontendError> {
+    vec![
+        FrontendError {
+            err: err.to_string(),
+            span,
+            kind: ErrorKind::Error,
+        },
+        FrontendError {
+            err: format!("Note: {}", note),
+            span: note_span,
+            kind: ErrorKind::Note,
+        },
+    ]
 }
 
 pub fn format_errors(codemap: &CodeMap, errors: &[FrontendError]) -> String {
     let mut result = String::new();
-    for FrontendError { err, span } in errors {
-        let msg = codemap.format_message(*span, &err);
+    let mut errors_cnt = 0;
+    for FrontendError { err, span, kind } in errors {
+        let msg = match kind {
+            ErrorKind::Note => codemap.format_note_message(*span, err),
+            ErrorKind::Error => {
+                errors_cnt += 1;
+                codemap.format_message(*span, &err)
+            }
+        };
         result.push_str(&msg);
     }
-    let summary = format!("\nFound {} error(s) in total.", errors.len())
+    let summary = format!("\nFound {} error(s) in total.", errors_cnt)
         .red()
         .bold();
     // needs to be added with write macro for colors to be effective
@@ -23,6 +55,20 @@ pub fn format_errors(codemap: &CodeMap, errors: &[FrontendError]) -> String {
     result
 }
 
+// warnings don't stop the compilation, they're reported along with its result
+pub fn format_warnings(codemap: &CodeMap, warnings: &[FrontendError]) -> String {
+    let mut result = String::new();
+    for FrontendError { err, span, .. } in warnings {
+        let msg = codemap.format_warning_message(*span, err);
+        result.push_str(&msg);
+    }
+    let summary = format!("Found {} warning(s) in total.", warnings.len())
+        .yellow()
+        .bold();
+    write!(&mut result, "{}", summary).unwrap();
+    result
+}
+
 pub fn ok_if_no_error(errors: Vec<FrontendError>) -> FrontendResult<()> {
     // make it a macro (probably in Rust 2018, because of use mod::macro)
     // then add second branch, for returning something else than unit
@@ -0,0 +1,124 @@
+// Internal compiler errors: panics of the compiler (broken invariants,
+// e.g. unreachable!() in the codegen) are caught by the driver and reported
+// with what the compiler was doing, instead of a bare Rust panic message.
+
+use codemap::CodeMap;
+use model::ast::Span;
+use std::cell::RefCell;
+use std::panic::{self, UnwindSafe};
+
+// what the compiler is doing, updated by the stages
+#[derive(Clone, Default)]
+struct Context {
+    stage: &'static str,
+    function: Option<String>,
+    span: Option<Span>,
+    statement: Option<Span>, // in the function, known by the codegen
+}
+
+pub struct InternalError {
+    context: Context,
+    message: String, // with the location in the compiler sources
+}
+
+thread_local! {
+    static CONTEXT: RefCell<Context> = RefCell::new(Context::default());
+    static PANIC_MESSAGE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+pub fn enter_stage(stage: &'static str) {
+    CONTEXT.with(|ctx| {
+        *ctx.borrow_mut() = Context {
+            stage,
+            function: None,
+            span: None,
+            statement: None,
+        }
+    });
+}
+
+// the span is None for functions of the IR
+pub fn enter_function(name: &str, span: Option<Span>) {
+    CONTEXT.with(|ctx| {
+        let mut ctx = ctx.borrow_mut();
+        ctx.function = Some(name.to_string());
+        ctx.span = span;
+        ctx.statement = None;
+    });
+}
+
+// the innermost statement of the function being processed
+pub fn enter_statement(span: Option<Span>) {
+    CONTEXT.with(|ctx| ctx.borrow_mut().statement = span);
+}
+
+// runs f, its panic is turned into an internal error (the default panic
+// message is suppressed)
+pub fn catch_internal_errors<T, F: FnOnce() -> T + UnwindSafe>(f: F) -> Result<T, InternalError> {
+    let prev_hook = panic::take_hook();
+    panic::set_hook(Box::new(|info| {
+        let payload = info.payload();
+        let msg = match (
+            payload.downcast_ref::<&str>(),
+            payload.downcast_ref::<String>(),
+        ) {
+            (Some(s), _) => s.to_string(),
+            (_, Some(s)) => s.to_string(),
+            _ => "unknown panic".to_string(),
+        };
+        let msg = match info.location() {
+            Some(loc) => format!("{} ({}:{})", msg, loc.file(), loc.line()),
+            None => msg,
+        };
+        PANIC_MESSAGE.with(|m| *m.borrow_mut() = Some(msg));
+    }));
+    enter_stage("start");
+    let res = panic::catch_unwind(f);
+    panic::set_hook(prev_hook);
+    res.map_err(|_| InternalError {
+        context: CONTEXT.with(|ctx| ctx.borrow().clone()),
+        message: PANIC_MESSAGE
+            .with(|m| m.borrow_mut().take())
+            .unwrap_or_default(),
+    })
+}
+
+impl InternalError {
+    pub fn format(&self, codemap: &CodeMap) -> String {
+        let mut res = format!(
+            "Internal compiler error during {}: {}\n",
+            self.context.stage, self.message
+        );
+        if let Some(fun) = &self.context.function {
+            let pos = self
+                .context
+                .span
+                .and_then(|span| codemap.find_row_col(span.0));
+            res += &match pos {
+                Some((row, col)) => format!(
+                    "while processing function {} ({}:{}:{})\n",
+                    fun,
+                    codemap.get_filename(),
+                    row,
+                    col
+                ),
+                None => format!("while processing function {}\n", fun),
+            };
+        }
+        let pos = self
+            .context
+            .statement
+            .and_then(|span| codemap.find_row_col(span.0));
+        if let Some((row, col)) = pos {
+            res += &format!(
+                "in the statement at {}:{}:{}\n",
+                codemap.get_filename(),
+                row,
+                col
+            );
+        }
+        res += "This is a bug of the compiler, please report it along with the program \
+                (`latte-reduce --predicate=ice <file>` reduces it to a minimal one).";
+        res
+    }
+}
@@ -0,0 +1,875 @@
+// Reference interpreter: walks the analyzed (and lowered) AST directly,
+// with the builtins and the runtime implemented in Rust. It's much simpler
+// than the codegen, so it's used as an oracle for testing the generated IR.
+// Where the compiled code has undefined behavior (null dereference, index
+// out of bounds, division by zero, too deep recursion), it stops with an error.
+
+mod value;
+
+use self::value::*;
+use model::ast::*;
+use options::{CompilerOptions, IntDivision};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io::{BufRead, Write};
+use std::rc::Rc;
+
+// interpret has to be run in a thread with this stack size; too deep recursion
+// of the program is reported when less than STACK_RESERVE bytes are left
+pub const STACK_SIZE: usize = 1 << 30;
+const STACK_RESERVE: usize = 1 << 20;
+
+pub enum RuntimeError {
+    // error() was called, also by the runtime (e.g. for `new int[0]`),
+    // the program prints "runtime error" and exits with 1
+    ErrorCall,
+    // the compiled program would crash here (or the behavior is undefined)
+    Crash(String),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RuntimeError::ErrorCall => write!(f, "runtime error"),
+            RuntimeError::Crash(msg) => write!(f, "Runtime error: {}", msg),
+        }
+    }
+}
+
+type RunResult<T> = Result<T, RuntimeError>;
+
+fn crash<T>(msg: &str) -> RunResult<T> {
+    Err(RuntimeError::Crash(msg.to_string()))
+}
+
+// runs main of the program and returns its result (the exit code)
+// requirement: the program is analyzed by SemanticAnalyzer
+pub fn interpret(
+    prog: &Program,
+    options: &CompilerOptions,
+    input: &mut dyn BufRead,
+    output: &mut dyn Write,
+) -> RunResult<i32> {
+    let mut interpreter = Interpreter::new(prog, options, input, output);
+    let main = interpreter.functions["main"];
+    let result = interpreter.call_function(main, None, vec![]);
+    let _ = interpreter.output.flush();
+    result.map(|value| value.get_int())
+}
+
+struct ClassInfo<'a> {
+    parent: Option<&'a str>,
+    fields: Vec<(&'a str, &'a InnerType)>,
+    methods: HashMap<&'a str, &'a FunDef>,
+}
+
+struct Interpreter<'a, 'b> {
+    functions: HashMap<&'a str, &'a FunDef>,
+    classes: HashMap<&'a str, ClassInfo<'a>>,
+    structs: HashMap<&'a str, &'a StructDef>,
+    input: &'b mut dyn BufRead,
+    output: &'b mut dyn Write,
+    next_object_id: u32,
+    stack_base: usize, // an address close to the bottom of the stack
+    int_division: IntDivision,
+}
+
+// local variables of a function call, a scope per block
+struct Frame<'a> {
+    scopes: Vec<HashMap<&'a str, Value>>,
+}
+
+// how a statement ends: normally or by a jump (with the optional loop label)
+enum Flow<'a> {
+    Normal,
+    Return(Value),
+    Break(Option<&'a str>),
+    Continue(Option<&'a str>),
+}
+
+// assignable location: a variable, an array element or an object field,
+// possibly a field of a struct stored there (given by the path)
+struct Place<'a> {
+    root: PlaceRoot<'a>,
+    path: Vec<&'a str>,
+}
+
+enum PlaceRoot<'a> {
+    Var(&'a str),
+    ArrayElem(ArrayRef, usize),
+    Field(ObjectRef, &'a str),
+}
+
+impl<'a> Frame<'a> {
+    fn new() -> Self {
+        Frame {
+            scopes: vec![HashMap::new()],
+        }
+    }
+
+    fn declare(&mut self, name: &'a str, value: Value) {
+        self.scopes.last_mut().unwrap().insert(name, value);
+    }
+
+    fn get_mut(&mut self, name: &str) -> &mut Value {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .filter_map(|scope| scope.get_mut(name))
+            .next()
+            .expect("assumption: variables are declared before use")
+    }
+}
+
+fn matches_loop(jump_label: Option<&str>, loop_label: &Option<Ident>) -> bool {
+    match jump_label {
+        Some(name) => loop_label.as_ref().map(|l| l.inner.as_str()) == Some(name),
+        None => true,
+    }
+}
+
+impl<'a, 'b> Interpreter<'a, 'b> {
+    fn new(
+        prog: &'a Program,
+        options: &CompilerOptions,
+        input: &'b mut dyn BufRead,
+        output: &'b mut dyn Write,
+    ) -> Self {
+        let mut functions = HashMap::new();
+        let mut classes = HashMap::new();
+        let mut structs = HashMap::new();
+        for def in &prog.defs {
+            match def {
+                TopDef::FunDef(fun) => {
+                    functions.insert(fun.name.inner.as_str(), fun);
+                }
+                TopDef::ClassDef(cl) => {
+                    let mut info = ClassInfo {
+                        parent: cl.parent_type.as_ref().map(|t| match &t.inner {
+                            InnerType::Class(name) => name.as_str(),
+                            _ => unreachable!(),
+                        }),
+                        fields: vec![],
+                        methods: HashMap::new(),
+                    };
+                    for it in &cl.items {
+                        match &it.inner {
+                            InnerClassItemDef::Field(t, id) => {
+                                info.fields.push((id.inner.as_str(), &t.inner))
+                            }
+                            InnerClassItemDef::Method(fun) => {
+                                info.methods.insert(fun.name.inner.as_str(), fun);
+                            }
+                            InnerClassItemDef::Class(_) | InnerClassItemDef::Error => {
+                                unreachable!()
+                            }
+                        }
+                    }
+                    classes.insert(cl.name.inner.as_str(), info);
+                }
+                TopDef::StructDef(st) => {
+                    structs.insert(st.name.inner.as_str(), st);
+                }
+                TopDef::TypeDef(_) | TopDef::Error => unreachable!(),
+            }
+        }
+        Interpreter {
+            functions,
+            classes,
+            structs,
+            input,
+            output,
+            next_object_id: 1,
+            stack_base: get_stack_address(),
+            int_division: options.int_division,
+        }
+    }
+
+    fn call_function(
+        &mut self,
+        fun: &'a FunDef,
+        this: Option<Value>,
+        args: Vec<Value>,
+    ) -> RunResult<Value> {
+        if self.stack_base.saturating_sub(get_stack_address()) > STACK_SIZE - STACK_RESERVE {
+            return crash("stack overflow, recursion is too deep");
+        }
+        let mut frame = Frame::new();
+        if let Some(this) = this {
+            frame.declare(THIS_VAR, this);
+        }
+        for ((_, name), value) in fun.args.iter().zip(args) {
+            frame.declare(&name.inner, value);
+        }
+        match self.exec_block(&fun.body, &mut frame)? {
+            Flow::Return(value) => Ok(value),
+            Flow::Normal => Ok(Value::Void),
+            Flow::Break(_) | Flow::Continue(_) => unreachable!(),
+        }
+    }
+
+    fn exec_block(&mut self, block: &'a Block, frame: &mut Frame<'a>) -> RunResult<Flow<'a>> {
+        frame.scopes.push(HashMap::new());
+        let mut flow = Ok(Flow::Normal);
+        for stmt in &block.stmts {
+            flow = self.exec_stmt(stmt, frame);
+            match flow {
+                Ok(Flow::Normal) => (),
+                _ => break,
+            }
+        }
+        frame.scopes.pop();
+        flow
+    }
+
+    fn exec_stmt(&mut self, stmt: &'a Stmt, frame: &mut Frame<'a>) -> RunResult<Flow<'a>> {
+        use model::ast::InnerStmt::*;
+        match &stmt.inner {
+            Empty => (),
+            Block(bl) => return self.exec_block(bl, frame),
+            Decl {
+                var_type,
+                var_items,
+            } => {
+                for (name, init) in var_items {
+                    let value = match init {
+                        Some(expr) => self.eval(expr, frame)?,
+                        None => self.get_default_value(&var_type.inner),
+                    };
+                    frame.declare(&name.inner, value);
+                }
+            }
+            Assign(lhs, rhs) => {
+                // the same order of evaluation as in the codegen
+                let value = self.eval(rhs, frame)?;
+                let place = self.eval_place(lhs, frame)?;
+                self.with_place(&place, frame, |v| *v = value)?;
+            }
+            Incr(lhs) | Decr(lhs) => {
+                let delta = match &stmt.inner {
+                    Incr(_) => 1,
+                    _ => -1,
+                };
+                let place = self.eval_place(lhs, frame)?;
+                self.with_place(&place, frame, |v| {
+                    *v = Value::Int(v.get_int().wrapping_add(delta))
+                })?;
+            }
+            Ret(opt_expr) => {
+                let value = match opt_expr {
+                    Some(expr) => self.eval(expr, frame)?,
+                    None => Value::Void,
+                };
+                return Ok(Flow::Return(value));
+            }
+            Cond {
+                cond,
+                true_branch,
+                false_branch,
+            } => {
+                if self.eval(cond, frame)?.get_bool() {
+                    return self.exec_block(true_branch, frame);
+                } else if let Some(bl) = false_branch {
+                    return self.exec_block(bl, frame);
+                }
+            }
+            While { label, cond, body } => {
+                while self.eval(cond, frame)?.get_bool() {
+                    match self.exec_block(body, frame)? {
+                        Flow::Normal => (),
+                        Flow::Break(l) if matches_loop(l, label) => break,
+                        Flow::Continue(l) if matches_loop(l, label) => (),
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            ForEach {
+                label,
+                iter_name,
+                array,
+                body,
+                ..
+            } => {
+                // the length (or the end of a range) is read once, like in the codegen
+                let (array, start, end) = match &array.inner {
+                    InnerExpr::Range(start, end) => {
+                        let start = self.eval(start, frame)?.get_int();
+                        (None, start, self.eval(end, frame)?.get_int())
+                    }
+                    _ => match self.eval(array, frame)? {
+                        Value::Array(arr) => {
+                            let length = arr.borrow().len() as i32;
+                            (Some(arr), 0, length)
+                        }
+                        _ => return crash("null array in for each loop"),
+                    },
+                };
+                for i in start..end {
+                    let elem = match &array {
+                        Some(arr) => arr.borrow()[i as usize].clone(),
+                        None => Value::Int(i),
+                    };
+                    frame.scopes.push(HashMap::new());
+                    frame.declare(&iter_name.inner, elem);
+                    let flow = self.exec_block(body, frame);
+                    frame.scopes.pop();
+                    match flow? {
+                        Flow::Normal => (),
+                        Flow::Break(l) if matches_loop(l, label) => break,
+                        Flow::Continue(l) if matches_loop(l, label) => (),
+                        flow => return Ok(flow),
+                    }
+                }
+            }
+            Expr(expr) => {
+                self.eval(expr, frame)?;
+            }
+            Break(label) => return Ok(Flow::Break(label.as_ref().map(|l| l.inner.as_str()))),
+            Continue(label) => return Ok(Flow::Continue(label.as_ref().map(|l| l.inner.as_str()))),
+            NestedFun(_) | Error => unreachable!(), // nested functions are lifted by semantic analysis
+        }
+        Ok(Flow::Normal)
+    }
+
+    fn eval(&mut self, expr: &'a Expr, frame: &mut Frame<'a>) -> RunResult<Value> {
+        use model::ast::{BinaryOp::*, InnerExpr::*, InnerUnaryOp::*};
+        match &expr.inner {
+            LitVar(name) => Ok(frame.get_mut(name).clone()),
+            LitInt(n) => Ok(Value::Int(*n)),
+            LitBool(b) => Ok(Value::Bool(*b)),
+            LitStr(s) if s.is_empty() => Ok(Value::Str(None)),
+            LitStr(s) => Ok(Value::new_str(s)),
+            LitNull => Ok(Value::Null),
+            CastType(expr, _) => self.eval(expr, frame), // only upcasts
+            // only generic builtins keep type arguments after monomorphization
+            FunCall {
+                function_name,
+                args,
+                type_args,
+            } if !type_args.is_empty() => {
+                let array = self.eval(&args[0], frame)?;
+                let arg = self.eval(&args[1], frame)?;
+                self.call_array_builtin(&function_name.inner, &type_args[0], array, arg)
+            }
+            FunCall {
+                function_name,
+                args,
+                ..
+            } => {
+                let mut args_values = vec![];
+                for a in args {
+                    args_values.push(self.eval(a, frame)?);
+                }
+                match self.functions.get(function_name.inner.as_str()) {
+                    Some(fun) => self.call_function(fun, None, args_values),
+                    None => self.call_builtin(&function_name.inner, args_values),
+                }
+            }
+            BinaryOp(lhs, op @ And, rhs) | BinaryOp(lhs, op @ Or, rhs) => {
+                let lhs_value = self.eval(lhs, frame)?.get_bool();
+                if lhs_value == (*op == Or) {
+                    Ok(Value::Bool(lhs_value))
+                } else {
+                    self.eval(rhs, frame)
+                }
+            }
+            BinaryOp(lhs, op, rhs) => {
+                let lhs_value = self.eval(lhs, frame)?;
+                let rhs_value = self.eval(rhs, frame)?;
+                let int_op = |f: fn(i32, i32) -> i32| {
+                    Ok(Value::Int(f(lhs_value.get_int(), rhs_value.get_int())))
+                };
+                let cmp_op = |f: fn(&i32, &i32) -> bool| {
+                    Ok(Value::Bool(f(&lhs_value.get_int(), &rhs_value.get_int())))
+                };
+                match op {
+                    Add => match (&lhs_value, &rhs_value) {
+                        (Value::Str(l), Value::Str(r)) => Ok(Value::Str(match (l, r) {
+                            (Some(l), Some(r)) => Some(Rc::from(format!("{}{}", l, r).as_str())),
+                            (None, _) => r.clone(),
+                            (_, None) => l.clone(),
+                        })),
+                        _ => int_op(i32::wrapping_add),
+                    },
+                    Sub => int_op(i32::wrapping_sub),
+                    Mul => int_op(i32::wrapping_mul),
+                    // the compiled code gets SIGFPE in both cases
+                    Div | Mod if rhs_value.get_int() == 0 => crash("division by zero"),
+                    Div | Mod if lhs_value.get_int() == i32::MIN && rhs_value.get_int() == -1 => {
+                        crash("integer overflow in division")
+                    }
+                    Div if self.int_division == IntDivision::Euclidean => int_op(i32::div_euclid),
+                    Mod if self.int_division == IntDivision::Euclidean => int_op(i32::rem_euclid),
+                    Div => int_op(|l, r| l / r),
+                    Mod => int_op(|l, r| l % r),
+                    LT => cmp_op(i32::lt),
+                    LE => cmp_op(i32::le),
+                    GT => cmp_op(i32::gt),
+                    GE => cmp_op(i32::ge),
+                    EQ => Ok(Value::Bool(lhs_value.is_equal(&rhs_value))),
+                    NE => Ok(Value::Bool(!lhs_value.is_equal(&rhs_value))),
+                    And | Or => unreachable!(),
+                    RefEQ | RefNE => unreachable!(), // lowered by semantic analysis
+                }
+            }
+            UnaryOp(op, e) => {
+                let value = self.eval(e, frame)?;
+                match op.inner {
+                    IntNeg => Ok(Value::Int(value.get_int().wrapping_neg())),
+                    BoolNeg => Ok(Value::Bool(!value.get_bool())),
+                }
+            }
+            NewArray {
+                elem_type,
+                elem_cnt,
+            } => {
+                let cnt = self.eval(elem_cnt, frame)?.get_int();
+                self.new_array(&elem_type.inner, cnt, &[])
+            }
+            NewObject(t) => match &t.inner {
+                InnerType::Class(name) => Ok(self.new_object(name)),
+                _ => unreachable!(),
+            },
+            ArrayElem {
+                array,
+                index,
+                is_array_a_string: Some(true),
+            } => {
+                let s = self.eval(array, frame)?;
+                let idx = self.eval(index, frame)?.get_int();
+                match s.get_str() {
+                    // the terminating zero can be read, too
+                    Some(s) if 0 <= idx && idx as usize <= s.len() => Ok(Value::Int(i32::from(
+                        s.as_bytes().get(idx as usize).cloned().unwrap_or(0),
+                    ))),
+                    Some(_) => crash("string index out of bounds"),
+                    None => crash("null dereference (character of an empty string)"),
+                }
+            }
+            ObjField {
+                obj,
+                is_obj_an_array: Some(true),
+                ..
+            } => match self.eval(obj, frame)? {
+                Value::Array(arr) => Ok(Value::Int(arr.borrow().len() as i32)),
+                _ => crash("null dereference (length of an array)"),
+            },
+            ObjField {
+                obj,
+                is_obj_a_struct: Some(true),
+                field,
+                ..
+            } => match self.eval(obj, frame)? {
+                Value::Struct(fields) => Ok(fields[&field.inner].clone()),
+                _ => unreachable!(),
+            },
+            ArrayElem { .. } | ObjField { .. } => {
+                let place = self.eval_place(expr, frame)?;
+                self.with_place(&place, frame, |v| v.clone())
+            }
+            ObjMethodCall {
+                obj,
+                method_name,
+                args,
+            } => {
+                let this = self.eval(obj, frame)?;
+                let mut args_values = vec![];
+                for a in args {
+                    args_values.push(self.eval(a, frame)?);
+                }
+                self.call_method(this, &method_name.inner, args_values)
+            }
+            Assign(lhs, rhs) => {
+                let value = self.eval(rhs, frame)?;
+                let place = self.eval_place(lhs, frame)?;
+                self.with_place(&place, frame, |v| *v = value.clone())?;
+                Ok(value)
+            }
+            IncrDecr {
+                lvalue,
+                is_incr,
+                is_prefix,
+            } => {
+                let delta = if *is_incr { 1 } else { -1 };
+                let place = self.eval_place(lvalue, frame)?;
+                self.with_place(&place, frame, |v| {
+                    let old_value = v.get_int();
+                    *v = Value::Int(old_value.wrapping_add(delta));
+                    if *is_prefix {
+                        v.clone()
+                    } else {
+                        Value::Int(old_value)
+                    }
+                })
+            }
+            // lowered to classes by semantic analysis
+            Lambda { .. } | ClosureCall { .. } => unreachable!(),
+            NamedArg { .. } => unreachable!(), // reordered by semantic analysis
+            Range(..) => unreachable!(),       // only iterated by foreach loops
+        }
+    }
+
+    fn eval_place(&mut self, expr: &'a Expr, frame: &mut Frame<'a>) -> RunResult<Place<'a>> {
+        use model::ast::InnerExpr::*;
+        let root = match &expr.inner {
+            LitVar(name) => PlaceRoot::Var(name),
+            ArrayElem { array, index, .. } => {
+                let array = self.eval(array, frame)?;
+                let idx = self.eval(index, frame)?.get_int();
+                match array {
+                    Value::Array(arr) => {
+                        if idx < 0 || idx as usize >= arr.borrow().len() {
+                            return crash("array index out of bounds");
+                        }
+                        PlaceRoot::ArrayElem(arr, idx as usize)
+                    }
+                    _ => return crash("null dereference (element of an array)"),
+                }
+            }
+            ObjField {
+                obj,
+                is_obj_a_struct: Some(true),
+                field,
+                ..
+            } => {
+                let mut place = self.eval_place(obj, frame)?;
+                place.path.push(&field.inner);
+                return Ok(place);
+            }
+            ObjField { obj, field, .. } => match self.eval(obj, frame)? {
+                Value::Object(obj) => PlaceRoot::Field(obj, &field.inner),
+                _ => return crash("null dereference (field of an object)"),
+            },
+            _ => unreachable!(), // not an l-value
+        };
+        Ok(Place { root, path: vec![] })
+    }
+
+    fn with_place<T>(
+        &self,
+        place: &Place<'a>,
+        frame: &mut Frame<'a>,
+        f: impl FnOnce(&mut Value) -> T,
+    ) -> RunResult<T> {
+        let get_in_struct = |mut value: &mut Value| {
+            for field in &place.path {
+                value = match value {
+                    Value::Struct(fields) => fields.get_mut(*field).unwrap(),
+                    _ => unreachable!(),
+                };
+            }
+            f(value)
+        };
+        Ok(match &place.root {
+            PlaceRoot::Var(name) => get_in_struct(frame.get_mut(name)),
+            PlaceRoot::ArrayElem(arr, idx) => get_in_struct(&mut arr.borrow_mut()[*idx]),
+            PlaceRoot::Field(obj, name) => match &mut *obj.data.borrow_mut() {
+                ObjectData::Fields(fields) => get_in_struct(fields.get_mut(*name).unwrap()),
+                _ => unreachable!(),
+            },
+        })
+    }
+
+    fn get_default_value(&self, t: &InnerType) -> Value {
+        match t {
+            InnerType::Int => Value::Int(0),
+            InnerType::Bool => Value::Bool(false),
+            InnerType::String => Value::Str(None),
+            InnerType::Struct(name) => Value::Struct(
+                self.structs[name.as_str()]
+                    .fields
+                    .iter()
+                    .map(|(t, id)| (id.inner.to_string(), self.get_default_value(&t.inner)))
+                    .collect(),
+            ),
+            _ => Value::Null,
+        }
+    }
+
+    // `copied` elements go first, the others get default values
+    fn new_array(&self, elem_type: &InnerType, cnt: i32, copied: &[Value]) -> RunResult<Value> {
+        if cnt <= 0 {
+            return Err(RuntimeError::ErrorCall); // like the runtime
+        }
+        let mut elems: Vec<_> = copied.iter().take(cnt as usize).cloned().collect();
+        while elems.len() < cnt as usize {
+            elems.push(self.get_default_value(elem_type));
+        }
+        Ok(Value::Array(Rc::new(RefCell::new(elems))))
+    }
+
+    fn new_object(&mut self, class_name: &str) -> Value {
+        let data = match class_name {
+            STRING_BUILDER_CLASS => ObjectData::StringBuilder(String::new()),
+            INT_LIST_CLASS | STRING_LIST_CLASS => ObjectData::List(vec![]),
+            STRING_INT_MAP_CLASS => ObjectData::Map(vec![], HashMap::new()),
+            _ => {
+                let mut fields = HashMap::new();
+                let mut cur_class = self.classes.get(class_name);
+                while let Some(cl) = cur_class {
+                    for (name, t) in &cl.fields {
+                        fields.insert(name.to_string(), self.get_default_value(t));
+                    }
+                    cur_class = cl.parent.and_then(|p| self.classes.get(p));
+                }
+                ObjectData::Fields(fields)
+            }
+        };
+        let id = self.next_object_id;
+        self.next_object_id += 1;
+        Value::Object(Rc::new(Object {
+            class_name: class_name.to_string(),
+            id,
+            data: RefCell::new(data),
+        }))
+    }
+
+    fn find_method(&self, class_name: &str, method_name: &str) -> Option<&'a FunDef> {
+        let mut cur_class = self.classes.get(class_name);
+        while let Some(cl) = cur_class {
+            if let Some(fun) = cl.methods.get(method_name) {
+                return Some(fun);
+            }
+            cur_class = cl.parent.and_then(|p| self.classes.get(p));
+        }
+        None
+    }
+
+    fn call_method(
+        &mut self,
+        this: Value,
+        method_name: &str,
+        mut args: Vec<Value>,
+    ) -> RunResult<Value> {
+        let obj = match this {
+            Value::Object(obj) => obj,
+            Value::Array(arr) => return self.call_array_method(arr, method_name, args),
+            Value::Null => return crash("null dereference (method call)"),
+            _ => unreachable!(),
+        };
+        if let Some(fun) = self.find_method(&obj.class_name, method_name) {
+            return self.call_function(fun, Some(Value::Object(obj)), args);
+        }
+
+        // builtin classes, implemented like in the runtime
+        let mut data = obj.data.borrow_mut();
+        match (&mut *data, method_name) {
+            (ObjectData::StringBuilder(buf), "append") => {
+                if let Some(s) = args[0].get_str() {
+                    buf.push_str(s);
+                }
+                Ok(Value::Object(obj.clone()))
+            }
+            (ObjectData::StringBuilder(buf), TO_STRING_METHOD) => Ok(Value::new_str(buf)),
+            (ObjectData::List(elems), "push") => {
+                elems.push(args.remove(0));
+                Ok(Value::Void)
+            }
+            (ObjectData::List(elems), "get") | (ObjectData::List(elems), "set") => {
+                let idx = args[0].get_int();
+                if idx < 0 || idx as usize >= elems.len() {
+                    return Err(RuntimeError::ErrorCall);
+                }
+                if method_name == "get" {
+                    Ok(elems[idx as usize].clone())
+                } else {
+                    elems[idx as usize] = args.remove(1);
+                    Ok(Value::Void)
+                }
+            }
+            (ObjectData::List(elems), "size") => Ok(Value::Int(elems.len() as i32)),
+            (ObjectData::Map(entries, index), "put") => {
+                let key = args[0].get_str().unwrap_or("");
+                let value = args[1].get_int();
+                match index.get(key) {
+                    Some(e) => entries[*e].1 = value,
+                    None => {
+                        index.insert(key.to_string(), entries.len());
+                        entries.push((key.to_string(), value));
+                    }
+                }
+                Ok(Value::Void)
+            }
+            (ObjectData::Map(entries, index), "get") => {
+                match index.get(args[0].get_str().unwrap_or("")) {
+                    Some(e) => Ok(Value::Int(entries[*e].1)),
+                    None => Err(RuntimeError::ErrorCall),
+                }
+            }
+            (ObjectData::Map(_, index), "contains") => Ok(Value::Bool(
+                index.contains_key(args[0].get_str().unwrap_or("")),
+            )),
+            (ObjectData::Map(entries, _), "size") => Ok(Value::Int(entries.len() as i32)),
+            (ObjectData::Map(entries, _), "keys") => {
+                let keys = entries.iter().map(|(key, _)| Value::new_str(key)).collect();
+                Ok(Value::Array(Rc::new(RefCell::new(keys))))
+            }
+            // default methods of Object
+            (_, EQUALS_METHOD) => Ok(Value::Bool(Value::Object(obj.clone()).is_equal(&args[0]))),
+            (_, "hashCode") => Ok(Value::Int(obj.id as i32)),
+            (_, TO_STRING_METHOD) => Ok(Value::new_str(&format!("Object@{:x}", obj.id))),
+            _ => unreachable!(),
+        }
+    }
+
+    // copy and fill, the only methods of arrays
+    fn call_array_method(
+        &mut self,
+        arr: ArrayRef,
+        method_name: &str,
+        mut args: Vec<Value>,
+    ) -> RunResult<Value> {
+        match method_name {
+            "copy" => Ok(Value::Array(Rc::new(RefCell::new(arr.borrow().clone())))),
+            "fill" => {
+                let value = args.remove(0);
+                for elem in arr.borrow_mut().iter_mut() {
+                    *elem = value.clone();
+                }
+                Ok(Value::Void)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    // arrayResize and append, null is an empty array
+    fn call_array_builtin(
+        &mut self,
+        function_name: &str,
+        elem_type: &InnerType,
+        array: Value,
+        arg: Value,
+    ) -> RunResult<Value> {
+        let elems = match &array {
+            Value::Array(arr) => arr.borrow().clone(),
+            _ => vec![],
+        };
+        if function_name == ARRAY_RESIZE_FUN {
+            self.new_array(elem_type, arg.get_int(), &elems)
+        } else {
+            let cnt = elems.len() as i32 + 1;
+            let result = self.new_array(elem_type, cnt, &elems)?;
+            if let Value::Array(arr) = &result {
+                *arr.borrow_mut().last_mut().unwrap() = arg;
+            }
+            Ok(result)
+        }
+    }
+
+    fn call_builtin(&mut self, name: &str, args: Vec<Value>) -> RunResult<Value> {
+        // like printf, errors of the output are ignored
+        match name {
+            "printInt" => {
+                let _ = writeln!(self.output, "{}", args[0].get_int());
+            }
+            "printString" => {
+                let _ = writeln!(self.output, "{}", args[0].get_str().unwrap_or(""));
+            }
+            "printBoolean" => {
+                let _ = writeln!(self.output, "{}", args[0].get_bool());
+            }
+            "printErr" => {
+                eprintln!("{}", args[0].get_str().unwrap_or(""));
+            }
+            "error" => return Err(RuntimeError::ErrorCall),
+            "flushOutput" => {
+                let _ = self.output.flush();
+            }
+            "readInt" => {
+                return match self.read_line() {
+                    Some(line) => parse_int(&line).map(Value::Int),
+                    None => Err(RuntimeError::ErrorCall),
+                }
+            }
+            "readString" => {
+                return Ok(match self.read_line() {
+                    Some(line) => Value::new_str(line.strip_suffix('\n').unwrap_or(&line)),
+                    None => Value::Str(None),
+                })
+            }
+            "readFile" => {
+                return match fs::read(args[0].get_str().unwrap_or("")) {
+                    Ok(bytes) => Ok(Value::new_str(&String::from_utf8_lossy(&bytes))),
+                    Err(_) => Err(RuntimeError::ErrorCall),
+                }
+            }
+            "writeFile" => {
+                let contents = args[1].get_str().unwrap_or("");
+                if fs::write(args[0].get_str().unwrap_or(""), contents).is_err() {
+                    return Err(RuntimeError::ErrorCall);
+                }
+            }
+            "split" => {
+                let sep = match args[1].get_str() {
+                    Some(sep) if !sep.is_empty() => sep,
+                    _ => return Err(RuntimeError::ErrorCall),
+                };
+                let parts = args[0].get_str().unwrap_or("").split(sep);
+                let parts = parts.map(Value::new_str).collect();
+                return Ok(Value::Array(Rc::new(RefCell::new(parts))));
+            }
+            "join" => {
+                let parts: Vec<String> = match &args[0] {
+                    Value::Array(arr) => arr
+                        .borrow()
+                        .iter()
+                        .map(|part| part.get_str().unwrap_or("").to_string())
+                        .collect(),
+                    _ => vec![],
+                };
+                return Ok(Value::new_str(&parts.join(args[1].get_str().unwrap_or(""))));
+            }
+            _ => unreachable!(),
+        }
+        Ok(Value::Void)
+    }
+
+    fn read_line(&mut self) -> Option<String> {
+        // the output is flushed before waiting for the input
+        let _ = self.output.flush();
+        let mut line = String::new();
+        match self.input.read_line(&mut line) {
+            Ok(n) if n > 0 => Some(line),
+            _ => None,
+        }
+    }
+}
+
+// the stack grows down
+fn get_stack_address() -> usize {
+    let local = 0u8;
+    &local as *const u8 as usize
+}
+
+// like readInt of the runtime: a number with optional sign and whitespace
+// around, anything else is an error
+fn parse_int(line: &str) -> RunResult<i32> {
+    let is_space = |c: char| c.is_ascii_whitespace();
+    let rest = line.trim_start_matches(is_space);
+    let (negative, rest) = match rest.chars().next() {
+        Some('-') => (true, &rest[1..]),
+        Some('+') => (false, &rest[1..]),
+        _ => (false, rest),
+    };
+    let space_after_sign = rest.starts_with(is_space);
+    let rest = rest.trim_start_matches(is_space);
+    let digits_end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    if digits_end == 0 || !rest[digits_end..].chars().all(is_space) {
+        return Err(RuntimeError::ErrorCall);
+    }
+    if space_after_sign {
+        return Ok(0); // atoi stops at the space
+    }
+    let value = rest[..digits_end].bytes().fold(0i32, |acc, d| {
+        acc.wrapping_mul(10).wrapping_add(i32::from(d - b'0'))
+    });
+    Ok(if negative {
+        value.wrapping_neg()
+    } else {
+        value
+    })
+}
@@ -0,0 +1,80 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+pub type ArrayRef = Rc<RefCell<Vec<Value>>>;
+pub type ObjectRef = Rc<Object>;
+
+// strings, arrays and objects are shared like pointers of the compiled code,
+// structs are copied
+#[derive(Clone)]
+pub enum Value {
+    Int(i32),
+    Bool(bool),
+    // "" and uninitialized strings are null pointers in the compiled code,
+    // but e.g. an empty line read by readString isn't
+    Str(Option<Rc<str>>),
+    Array(ArrayRef),
+    Object(ObjectRef),
+    Struct(HashMap<String, Value>),
+    Null,
+    Void,
+}
+
+pub struct Object {
+    pub class_name: String,
+    // used instead of the address by the default hashCode and toString
+    pub id: u32,
+    pub data: RefCell<ObjectData>,
+}
+
+pub enum ObjectData {
+    Fields(HashMap<String, Value>),
+    // builtin classes
+    StringBuilder(String),
+    List(Vec<Value>),
+    // the entries in the order of insertion (like in the runtime), key ->
+    // the index of its entry
+    Map(Vec<(String, i32)>, HashMap<String, usize>),
+}
+
+// requirement (of the getters): the program is type checked
+impl Value {
+    pub fn get_int(&self) -> i32 {
+        match self {
+            Value::Int(n) => *n,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn get_bool(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn get_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => s.as_ref().map(|s| &**s),
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn new_str(s: &str) -> Value {
+        Value::Str(Some(Rc::from(s)))
+    }
+
+    // like the runtime: pointers are compared, except of contents of strings
+    pub fn is_equal(&self, rhs: &Value) -> bool {
+        match (self, rhs) {
+            (Value::Int(l), Value::Int(r)) => l == r,
+            (Value::Bool(l), Value::Bool(r)) => l == r,
+            (Value::Str(l), Value::Str(r)) => l == r,
+            (Value::Array(l), Value::Array(r)) => Rc::ptr_eq(l, r),
+            (Value::Object(l), Value::Object(r)) => Rc::ptr_eq(l, r),
+            (Value::Null, Value::Null) => true,
+            _ => false,
+        }
+    }
+}
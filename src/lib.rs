@@ -1,26 +1,217 @@
 #[macro_use]
 extern crate lalrpop_util;
 extern crate colored;
+extern crate regex;
 
+pub mod abi;
 pub mod codegen;
 pub mod codemap;
+pub mod config;
+pub mod explain;
+pub mod filecheck;
+pub mod formatter;
 pub mod frontend_error;
+pub mod ice;
+pub mod interpreter;
 pub mod model;
+pub mod optimizer;
+pub mod options;
 pub mod parser;
+pub mod regalloc;
 pub mod semantics;
 
-pub fn compile(filename: &str, code: &str) -> Result<model::ir::Program, String> {
+// returns the program and formatted warnings, if there are any
+pub fn compile(
+    filename: &str,
+    code: &str,
+    options: &options::CompilerOptions,
+) -> Result<(model::ir::Program, Option<String>), String> {
+    let (ast, global_ctx, warnings) = analyze_program(filename, code, options)?;
+    ice::enter_stage("code generation");
+    let cg = codegen::CodeGen::new(&ast, &global_ctx, options);
+    let mut ir = cg.generate_ir();
+    model::ir_verifier::verify_program(&ir);
+    ice::enter_stage("optimization");
+    let profile = match &options.use_profile {
+        Some(path) => Some(load_profile(&mut ir, path)?),
+        None => None,
+    };
+    if let Some(path) = &options.instrument {
+        optimizer::instrument_program(&mut ir, &path.to_string_lossy());
+    }
+    optimizer::optimize(&mut ir, options, profile.as_ref());
+    model::ir_verifier::verify_program(&ir);
+    Ok((ir, warnings))
+}
+
+// returns the analyzed (and lowered) AST and formatted warnings, e.g. for the interpreter
+pub fn analyze(
+    filename: &str,
+    code: &str,
+    options: &options::CompilerOptions,
+) -> Result<(model::ast::Program, Option<String>), String> {
+    analyze_program(filename, code, options).map(|(ast, _, warnings)| (ast, warnings))
+}
+
+// stops after the type checking (before lowering), returns the types
+// of the program as JSON and formatted warnings
+pub fn typecheck(
+    filename: &str,
+    code: &str,
+    options: &options::CompilerOptions,
+) -> Result<(String, Option<String>), String> {
+    let codemap = codemap::CodeMap::new(filename, code);
+    ice::enter_stage("parsing");
+    let res = parser::parse(&codemap, options.int_division);
+    let mut ast = res.map_err(|e| frontend_error::format_errors(&codemap, &e))?;
+    ice::enter_stage("semantic analysis");
+    let mut sem_anal = semantics::SemanticAnalyzer::new(&mut ast, options);
+    sem_anal.record_expr_types();
+    let res = sem_anal.perform_type_checking();
+    res.map_err(|e| frontend_error::format_errors(&codemap, &e))?;
+    let expr_types = sem_anal.take_expr_types();
+    let warnings = sem_anal.take_warnings();
+    let global_ctx = sem_anal.get_global_ctx().unwrap();
+    let types = semantics::format_type_dump(&global_ctx, &expr_types, &codemap);
+    let warnings = if warnings.is_empty() {
+        None
+    } else {
+        Some(frontend_error::format_warnings(&codemap, &warnings))
+    };
+    Ok((types, warnings))
+}
+
+// stops after the type checking (before lowering), returns the occurrences
+// of the identifiers (e.g. for editors) and formatted warnings
+pub fn index_symbols(
+    filename: &str,
+    code: &str,
+    options: &options::CompilerOptions,
+) -> Result<(semantics::SymbolIndex, Option<String>), String> {
+    let codemap = codemap::CodeMap::new(filename, code);
+    ice::enter_stage("parsing");
+    let res = parser::parse(&codemap, options.int_division);
+    let mut ast = res.map_err(|e| frontend_error::format_errors(&codemap, &e))?;
+    ice::enter_stage("semantic analysis");
+    let mut sem_anal = semantics::SemanticAnalyzer::new(&mut ast, options);
+    sem_anal.record_symbols();
+    let res = sem_anal.perform_type_checking();
+    res.map_err(|e| frontend_error::format_errors(&codemap, &e))?;
+    let index = sem_anal.take_symbol_index(codemap.get_code());
+    let warnings = sem_anal.take_warnings();
+    let warnings = if warnings.is_empty() {
+        None
+    } else {
+        Some(frontend_error::format_warnings(&codemap, &warnings))
+    };
+    Ok((index, warnings))
+}
+
+// stops after the type checking (before lowering), returns the call graph
+// of the program (DOT or JSON, by the report format) and formatted warnings
+pub fn call_graph(
+    filename: &str,
+    code: &str,
+    options: &options::CompilerOptions,
+) -> Result<(String, Option<String>), String> {
     let codemap = codemap::CodeMap::new(filename, code);
-    let res = parser::parse(&codemap);
+    ice::enter_stage("parsing");
+    let res = parser::parse(&codemap, options.int_division);
     let mut ast = res.map_err(|e| frontend_error::format_errors(&codemap, &e))?;
-    let global_ctx = {
+    ice::enter_stage("semantic analysis");
+    let mut sem_anal = semantics::SemanticAnalyzer::new(&mut ast, options);
+    sem_anal.record_expr_types();
+    let res = sem_anal.perform_type_checking();
+    res.map_err(|e| frontend_error::format_errors(&codemap, &e))?;
+    let expr_types = sem_anal.take_expr_types();
+    let warnings = sem_anal.take_warnings();
+    let global_ctx = sem_anal.get_global_ctx().unwrap();
+    let graph = semantics::build_call_graph(&mut ast, &global_ctx, &expr_types);
+    let warnings = if warnings.is_empty() {
+        None
+    } else {
+        Some(frontend_error::format_warnings(&codemap, &warnings))
+    };
+    Ok((graph.format(options.report_format), warnings))
+}
+
+// type checks the program again after it was edited (check --watch),
+// returns formatted warnings
+pub fn recheck(
+    filename: &str,
+    code: &str,
+    options: &options::CompilerOptions,
+    cache: &mut semantics::ContextCache,
+) -> Result<Option<String>, String> {
+    let codemap = codemap::CodeMap::new(filename, code);
+    ice::enter_stage("parsing");
+    let res = parser::parse(&codemap, options.int_division);
+    let mut ast = res.map_err(|e| frontend_error::format_errors(&codemap, &e))?;
+    ice::enter_stage("semantic analysis");
+    let mut sem_anal = semantics::SemanticAnalyzer::new(&mut ast, options);
+    let res = sem_anal.perform_type_checking_with_cache(cache);
+    let warnings = sem_anal.take_warnings();
+    cache.put(sem_anal.get_global_ctx());
+    res.map_err(|e| frontend_error::format_errors(&codemap, &e))?;
+    if warnings.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(frontend_error::format_warnings(&codemap, &warnings)))
+    }
+}
+
+// the program with normalized indentation and blank lines, if it parses
+pub fn format(filename: &str, code: &str) -> Result<String, String> {
+    let codemap = codemap::CodeMap::new(filename, code);
+    ice::enter_stage("parsing");
+    parser::parse(&codemap, options::IntDivision::Truncating)
+        .map_err(|e| frontend_error::format_errors(&codemap, &e))?;
+    ice::enter_stage("formatting");
+    Ok(formatter::format_code(code))
+}
+
+fn analyze_program(
+    filename: &str,
+    code: &str,
+    options: &options::CompilerOptions,
+) -> Result<
+    (
+        model::ast::Program,
+        semantics::global_context::GlobalContext,
+        Option<String>,
+    ),
+    String,
+> {
+    let codemap = codemap::CodeMap::new(filename, code);
+    ice::enter_stage("parsing");
+    let res = parser::parse(&codemap, options.int_division);
+    let mut ast = res.map_err(|e| frontend_error::format_errors(&codemap, &e))?;
+    ice::enter_stage("semantic analysis");
+    let (global_ctx, warnings) = {
         // new block to satisfy borrow checker
-        let mut sem_anal = semantics::SemanticAnalyzer::new(&mut ast);
+        let mut sem_anal = semantics::SemanticAnalyzer::new(&mut ast, options);
         let res = sem_anal.perform_full_analysis();
         res.map_err(|e| frontend_error::format_errors(&codemap, &e))?;
-        sem_anal.get_global_ctx().unwrap()
+        let warnings = sem_anal.take_warnings();
+        (sem_anal.get_global_ctx().unwrap(), warnings)
     };
-    let cg = codegen::CodeGen::new(&ast, &global_ctx);
-    let ir = cg.generate_ir();
-    Ok(ir)
+    let warnings = if warnings.is_empty() {
+        None
+    } else {
+        Some(frontend_error::format_warnings(&codemap, &warnings))
+    };
+    Ok((ast, global_ctx, warnings))
+}
+
+// loads the profile of the just generated program and adds its branch weights
+fn load_profile(
+    ir: &mut model::ir::Program,
+    path: &std::path::Path,
+) -> Result<optimizer::Profile, String> {
+    let text = std::fs::read_to_string(path)
+        .map_err(|_| format!("Cannot read the profile: {}", path.display()))?;
+    let profile = optimizer::Profile::load(ir, &text)
+        .map_err(|err| format!("Invalid profile {}: {}", path.display(), err))?;
+    profile.add_branch_weights(ir);
+    Ok(profile)
 }
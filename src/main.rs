@@ -1,127 +1,1065 @@
 extern crate latte_compiler;
 
-use latte_compiler::compile;
+use latte_compiler::abi::{format_calls, Abi};
+use latte_compiler::codemap::CodeMap;
+use latte_compiler::config::{apply_config, find_config};
+use latte_compiler::explain::{find_explanations, get_explanations};
+use latte_compiler::ice;
+use latte_compiler::interpreter::{self, RuntimeError};
+use latte_compiler::model::ir;
+use latte_compiler::model::ir_encoding::{
+    decode_program, encode_program_binary, encode_program_json,
+};
+use latte_compiler::model::ir_interpreter::{self, Stop};
+use latte_compiler::model::source_map::format_source_map;
+use latte_compiler::optimizer::format_ir_stats;
+use latte_compiler::options::{
+    CompilerOptions, Dialect, Emit, IntDivision, LintLevel, RegAlloc, RelocationModel, Report,
+    ReportFormat, Target,
+};
+use latte_compiler::regalloc::{format_allocations, format_stack_maps, format_stack_usage};
+use latte_compiler::semantics::{format_symbol_index, ContextCache, LINT_NAMES};
+use latte_compiler::{analyze, call_graph, compile, format, index_symbols, recheck, typecheck};
 use std::env;
 use std::fs;
-use std::path::Path;
+use std::io::{self, Read, Write};
+use std::panic::{AssertUnwindSafe, UnwindSafe};
+use std::path::{Path, PathBuf};
 use std::process;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime};
+
+const RUNTIME_LL: &str = include_str!("../lib/runtime.ll");
+const RUNTIME_BC: &[u8] = include_bytes!("../lib/runtime.bc");
+// operations run by the interpreter of the IR (--interp=ir)
+const IR_INTERPRETER_STEPS: u32 = 100_000_000;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Build,
+    Run,
+    Check,
+    Fmt,
+    EmitRuntime,
+    Explain,
+    IrDis,
+}
+
+const COMMANDS: &[(&str, Command)] = &[
+    ("build", Command::Build),
+    ("run", Command::Run),
+    ("check", Command::Check),
+    ("fmt", Command::Fmt),
+    ("emit-runtime", Command::EmitRuntime),
+    ("explain", Command::Explain),
+    ("ir-dis", Command::IrDis),
+];
+
+struct Args {
+    command: Command,
+    options: CompilerOptions,
+    // input files, "-" is the standard input; words of the query for explain
+    inputs: Vec<String>,
+    // instead of latte.toml found next to the input
+    config: Option<PathBuf>,
+    out_dir: Option<PathBuf>,
+    link: bool,
+    to_stdout: bool,
+    // the LLVM IR is checked by llvm-as and opt before it's written
+    validate_llvm: bool,
+    interpret: bool,
+    // the optimized IR is run instead of the executable
+    interpret_ir: bool,
+    dump_types: bool,
+    dump_symbols: bool,
+    watch: bool,
+    check_formatting: bool,
+}
 
-#[allow(clippy::nonminimal_bool)] // clippy is bugged and signals false positive
 fn main() {
     let args: Vec<_> = env::args().collect();
+    let args = parse_args(&args);
+    match args.command {
+        Command::Build | Command::Run => build(&args),
+        Command::Check => check(&args),
+        Command::Fmt => format_files(&args),
+        Command::EmitRuntime => emit_runtime(&args),
+        Command::Explain => explain(&args),
+        Command::IrDis => disassemble_ir(&args),
+    }
+}
 
-    if !(args.len() == 2 && args[1] != "--make-executable"
-        || args.len() == 3 && args[1] == "--make-executable")
-    {
-        eprintln!("Usage: {} [--make-executable] <filename.lat>", args[0]);
-        process::exit(1);
+fn parse_args(args: &[String]) -> Args {
+    let subcommand = args
+        .get(1)
+        .and_then(|arg| COMMANDS.iter().find(|(name, _)| name == arg));
+    // without a subcommand the original interface is used (e.g. by the latc
+    // script): build without linking, unless --make-executable is given;
+    // --interp and --typecheck-only select the other commands
+    let legacy = subcommand.is_none();
+    let mut res = Args {
+        command: subcommand.map_or(Command::Build, |(_, cmd)| *cmd),
+        options: CompilerOptions::default(),
+        inputs: vec![],
+        config: None,
+        out_dir: None,
+        link: !legacy,
+        to_stdout: false,
+        validate_llvm: false,
+        interpret: false,
+        interpret_ir: false,
+        dump_types: false,
+        dump_symbols: false,
+        watch: false,
+        check_formatting: false,
+    };
+    let rest = if legacy { &args[1..] } else { &args[2..] };
+    if res.command == Command::Explain {
+        res.inputs = rest.to_vec();
+        return res;
+    }
+
+    // applied after the configuration file, which is found by the input
+    let mut compiler_args = vec![];
+    for arg in rest {
+        let cmd = res.command;
+        let compiles = [Command::Build, Command::Run, Command::Check].contains(&cmd);
+        match arg.as_str() {
+            "--make-executable" if legacy => res.link = true,
+            "--interp" if legacy || cmd == Command::Run => {
+                res.command = Command::Run;
+                res.interpret = true;
+            }
+            "--interp=ir" if cmd == Command::Run && !legacy => res.interpret_ir = true,
+            "--typecheck-only" if legacy => res.command = Command::Check,
+            "--dump-types=json" if legacy || cmd == Command::Check => {
+                res.command = Command::Check;
+                res.dump_types = true;
+            }
+            "--dump-symbols=json" if cmd == Command::Check && !legacy => res.dump_symbols = true,
+            "--watch" if cmd == Command::Check && !legacy => res.watch = true,
+            "--no-link" if cmd == Command::Build => res.link = false,
+            "--stdout" if cmd == Command::Build => res.to_stdout = true,
+            "--validate-llvm" if [Command::Build, Command::Run].contains(&cmd) => {
+                res.validate_llvm = true
+            }
+            "--check" if cmd == Command::Fmt => res.check_formatting = true,
+            "-" => res.inputs.push(arg.clone()),
+            _ if arg.starts_with("--out-dir=")
+                && [Command::Build, Command::Run, Command::EmitRuntime].contains(&cmd) =>
+            {
+                res.out_dir = Some(PathBuf::from(&arg["--out-dir=".len()..]))
+            }
+            _ if arg.starts_with("--config=") && compiles => {
+                res.config = Some(PathBuf::from(&arg["--config=".len()..]))
+            }
+            _ if !arg.starts_with('-') => res.inputs.push(arg.clone()),
+            _ if compiles && parse_compiler_option(arg, &mut res.options) => {
+                compiler_args.push(arg)
+            }
+            _ => exit_with_usage(&args[0]),
+        }
+    }
+    let inputs_ok = match res.command {
+        Command::Fmt => !res.inputs.is_empty(),
+        Command::EmitRuntime => res.inputs.is_empty(),
+        Command::Check if res.watch => {
+            !res.inputs.is_empty() && !res.inputs.contains(&"-".to_string())
+        }
+        _ => res.inputs.len() == 1,
+    };
+    // run needs the executable
+    let emit_ok = !(res.command == Command::Run && res.options.emit == Emit::Obj);
+    if !inputs_ok || !emit_ok {
+        exit_with_usage(&args[0]);
+    }
+    if [Command::Build, Command::Run, Command::Check].contains(&res.command) {
+        res.options = CompilerOptions::default();
+        load_config(&res.config, &res.inputs[0], &mut res.options);
+        for arg in compiler_args {
+            parse_compiler_option(arg, &mut res.options);
+        }
     }
-    let make_executable = args.len() == 3;
+    res
+}
 
-    let input_file_str = &args[args.len() - 1];
-    let input_file = Path::new(&input_file_str);
-    let code = match fs::read_to_string(input_file) {
-        Ok(s) => s,
+// latte.toml (or --config=FILE) gives the defaults of the compiler options
+fn load_config(config: &Option<PathBuf>, input: &str, options: &mut CompilerOptions) {
+    let path = match config {
+        Some(path) => path.clone(),
+        None => {
+            let dir = if input == "-" {
+                env::current_dir().ok()
+            } else {
+                fs::canonicalize(input)
+                    .ok()
+                    .and_then(|path| path.parent().map(Path::to_path_buf))
+            };
+            match dir.and_then(|dir| find_config(&dir)) {
+                Some(path) => path,
+                None => return,
+            }
+        }
+    };
+    let text = match fs::read_to_string(&path) {
+        Ok(text) => text,
         Err(_) => {
-            eprintln!("Cannot read file: {}", input_file.display());
+            eprintln!("Cannot read file: {}", path.display());
             process::exit(1);
         }
     };
+    if let Err(msg) = apply_config(&text, options) {
+        eprintln!("Invalid configuration in {}: {}", path.display(), msg);
+        process::exit(1);
+    }
+}
+
+// false if the option is unknown
+fn parse_compiler_option(arg: &str, options: &mut CompilerOptions) -> bool {
+    match arg {
+        "--strict" => options.strict = true,
+        "--check-bounds" => options.check_bounds = true,
+        "--check-stack" => options.check_stack = true,
+        "--dialect=core" => options.dialect = Dialect::Core,
+        "--dialect=extended" => options.dialect = Dialect::Extended,
+        "--int-division=trunc" => options.int_division = IntDivision::Truncating,
+        "--int-division=euclid" => options.int_division = IntDivision::Euclidean,
+        "-O0" => options.opt_level = 0,
+        "-O1" => options.opt_level = 1,
+        "-O2" => options.opt_level = 2,
+        "--pointer-width=32" => options.pointer_width = 32,
+        "--pointer-width=64" => options.pointer_width = 64,
+        "--emit=llvm" => options.emit = Emit::Llvm,
+        "--emit=obj" => options.emit = Emit::Obj,
+        "--emit=class-hierarchy" => options.emit = Emit::ClassHierarchy,
+        "--emit=strings" => options.emit = Emit::Strings,
+        "--emit=vtables" => options.emit = Emit::Vtables,
+        "--emit=ir-json" => options.emit = Emit::IrJson,
+        "--emit=ir-binary" => options.emit = Emit::IrBinary,
+        "--emit=callgraph" => options.emit = Emit::CallGraph,
+        "--trace=codegen" => options.trace_codegen = true,
+        "--trace-eval-order" => options.trace_eval_order = true,
+        "--report=ir-stats" => options.report = Some(Report::IrStats),
+        "--report=regalloc" => options.report = Some(Report::RegAlloc),
+        "--regalloc=linear-scan" => options.regalloc = RegAlloc::LinearScan,
+        "--regalloc=graph-coloring" => options.regalloc = RegAlloc::GraphColoring,
+        "--report=abi" => options.report = Some(Report::Abi),
+        "--report=stack-maps" => options.report = Some(Report::StackMaps),
+        "--report=stack-usage" => options.report = Some(Report::StackUsage),
+        "--report=source-map" => options.report = Some(Report::SourceMap),
+        "--target=x86_64" => options.target = Target::X86_64,
+        "--target=aarch64" => options.target = Target::AArch64,
+        "--target=riscv64" => options.target = Target::RiscV64,
+        "--relocation-model=static" => options.relocation_model = RelocationModel::Static,
+        "--relocation-model=pic" => options.relocation_model = RelocationModel::Pic,
+        "--report-format=table" => options.report_format = ReportFormat::Table,
+        "--report-format=json" => options.report_format = ReportFormat::Json,
+        _ if arg.starts_with("--cache-dir=") => {
+            options.cache_dir = Some(PathBuf::from(&arg["--cache-dir=".len()..]))
+        }
+        _ if arg.starts_with("--instrument=") => {
+            options.instrument = Some(PathBuf::from(&arg["--instrument=".len()..]))
+        }
+        _ if arg.starts_with("--use-profile=") => {
+            options.use_profile = Some(PathBuf::from(&arg["--use-profile=".len()..]))
+        }
+        _ if arg.starts_with("--allow=")
+            || arg.starts_with("--warn=")
+            || arg.starts_with("--deny=") =>
+        {
+            let (flag, lint) = arg.split_at(arg.find('=').unwrap());
+            let lint = &lint[1..];
+            if !LINT_NAMES.contains(&lint) {
+                return false;
+            }
+            let level = match flag {
+                "--allow" => LintLevel::Allow,
+                "--warn" => LintLevel::Warn,
+                _ => LintLevel::Deny,
+            };
+            options.lint_levels.insert(lint.to_string(), level);
+        }
+        _ if arg.starts_with("--inline-threshold=") => {
+            match arg["--inline-threshold=".len()..].parse() {
+                Ok(threshold) => options.inline_threshold = threshold,
+                Err(_) => return false,
+            }
+        }
+        _ => return false,
+    }
+    true
+}
 
-    let res = compile(input_file_str, &code);
-    let ll_code = match res {
-        Ok(prog) => {
+fn exit_with_usage(program: &str) -> ! {
+    eprintln!("Usage: {} <command> [options] <file.lat | ->", program);
+    eprintln!("Commands:");
+    eprintln!("  build                    compile the program to an executable");
+    eprintln!("  run                      build the program and run it");
+    eprintln!("  check                    only parse and type check the program");
+    eprintln!("  fmt <files>              normalize indentation and blank lines of programs");
+    eprintln!("  emit-runtime             write the runtime (runtime.ll and runtime.bc)");
+    eprintln!("  explain [message]        explain an error or a warning, or list them");
+    eprintln!("  ir-dis <file | ->        print a serialized IR module as LLVM IR");
+    eprintln!("Without a command, the options --make-executable, --interp and --typecheck-only");
+    eprintln!("select what to do (build without linking by default).");
+    eprintln!("Options of build and run:");
+    eprintln!("  --out-dir=DIR            directory of the outputs (default: next to the input,");
+    eprintln!("                           a temporary one for run)");
+    eprintln!("  --no-link                stop after LLVM IR and bitcode (build)");
+    eprintln!("  --stdout                 write LLVM IR to stdout instead of files (build)");
+    eprintln!("  --interp                 run the program with the interpreter instead (run)");
+    eprintln!("  --interp=ir              run the optimized IR with its interpreter instead,");
+    eprintln!("                           only programs on ints and booleans (run)");
+    eprintln!("  --validate-llvm          check the LLVM IR with llvm-as and opt -passes=verify");
+    eprintln!("                           (skipped if they aren't installed)");
+    eprintln!("  --relocation-model=static|pic  absolute addresses (default) or position");
+    eprintln!("                           independent code, linked as PIE");
+    eprintln!("Options of check:");
+    eprintln!("  --dump-types=json        print types of the program as JSON");
+    eprintln!("  --dump-symbols=json      print identifiers of the program, their kinds and");
+    eprintln!("                           definitions as JSON (instead of the types)");
+    eprintln!("  --watch                  check the files again whenever they change");
+    eprintln!("Options of fmt:");
+    eprintln!("  --check                  only list the files which aren't formatted");
+    eprintln!("Options of the compiler (build, run and check):");
+    eprintln!("  --strict                 require variables to be assigned before use");
+    eprintln!("  --check-bounds           check indices of string characters at runtime");
+    eprintln!("  --check-stack            report too deep recursion instead of crashing");
+    eprintln!("  -O0, -O1, -O2            optimization level (default: -O1)");
+    eprintln!("  --dialect=core|extended  only the original Latte or also the extensions");
+    eprintln!("                           (default: extended)");
+    eprintln!("  --int-division=trunc|euclid  `/` and `%` of negative numbers rounded toward");
+    eprintln!("                           zero (default) or with a non-negative remainder");
+    eprintln!("  --allow=LINT, --warn=LINT, --deny=LINT  silence the warnings of the lint,");
+    eprintln!("                           report them (default) or make them errors");
+    eprintln!("  --config=FILE            defaults of the options (default: latte.toml in the");
+    eprintln!("                           directory of the input or one of its parents)");
+    eprintln!("  --cache-dir=DIR          reuse IR of unchanged functions from earlier builds");
+    eprintln!("  --inline-threshold=N     max size of functions inlined automatically");
+    eprintln!("  --instrument=FILE        count executed blocks, written to FILE at exit");
+    eprintln!("  --use-profile=FILE       optimize with the counts of an instrumented build");
+    eprintln!("  --pointer-width=32|64    pointer width of the target (default: 64)");
+    eprintln!("  --emit=llvm              output LLVM IR (default)");
+    eprintln!("  --emit=obj               stop after the object file written by llc (build)");
+    eprintln!("  --emit=class-hierarchy   print final classes and devirtualized methods");
+    eprintln!("  --emit=strings           print string literals and their global names");
+    eprintln!("  --emit=vtables           print object layouts and vtable slots of classes");
+    eprintln!("  --emit=ir-json           print the optimized IR serialized as JSON");
+    eprintln!("  --emit=ir-binary         print the optimized IR in the binary format");
+    eprintln!(
+        "  --emit=callgraph         print the call graph in DOT (JSON with --report-format=json)"
+    );
+    eprintln!("  --trace=codegen          print SSA construction steps of the codegen");
+    eprintln!("  --trace-eval-order       the program prints every evaluated call, array");
+    eprintln!("                           access etc. (\"eval f(x)\") in the order of evaluation");
+    eprintln!("  --report=ir-stats        print sizes, loop depth and stack usage of functions");
+    eprintln!("  --report=regalloc        print registers and stack slots assigned to values");
+    eprintln!("  --report=abi             print where functions get their arguments and results");
+    eprintln!("  --report=stack-maps      print where the heap pointers live across calls are");
+    eprintln!("  --report=stack-usage     print frame sizes, recursion and maximal stack usage");
+    eprintln!("  --report=source-map      print the statement of every line of the LLVM IR");
+    eprintln!("  --report-format=table|json  format of the report (default: table)");
+    eprintln!("  --regalloc=linear-scan|graph-coloring  register allocator (default: linear-scan)");
+    eprintln!("  --target=x86_64|aarch64|riscv64  target of object files (--emit=obj, only");
+    eprintln!("                           x86-64 is linked), registers and calling convention");
+    eprintln!("                           of the reports");
+    eprintln!("The file - is the standard input.");
+    process::exit(1);
+}
+
+// returns the name used in messages and the code
+fn read_input(input: &str) -> (String, String) {
+    if input == "-" {
+        let mut code = String::new();
+        if io::stdin().read_to_string(&mut code).is_err() {
+            eprintln!("Cannot read the standard input");
+            process::exit(1);
+        }
+        return ("<stdin>".to_string(), code);
+    }
+    match fs::read_to_string(input) {
+        Ok(code) => (input.to_string(), code),
+        Err(_) => {
+            eprintln!("Cannot read file: {}", input);
+            process::exit(1);
+        }
+    }
+}
+
+fn write_output<C: AsRef<[u8]>>(path: &Path, contents: C) {
+    if fs::write(path, contents).is_err() {
+        eprintln!("Cannot write file: {}", path.display());
+        process::exit(1);
+    }
+}
+
+// prints OK and the warnings, or ERROR and the errors and exits
+fn unwrap_result<T>(res: Result<(T, Option<String>), String>) -> T {
+    match res {
+        Ok((res, warnings)) => {
             eprintln!("OK");
-            format!("{}", prog)
+            if let Some(warnings) = warnings {
+                eprintln!("{}", warnings);
+            }
+            res
         }
         Err(msg) => {
             eprintln!("ERROR");
             eprintln!("{}", msg);
             process::exit(1);
         }
+    }
+}
+
+fn check(args: &Args) {
+    if args.watch {
+        watch(args);
+    }
+    let (filename, code) = read_input(&args.inputs[0]);
+    if args.dump_symbols {
+        let index = unwrap_result(catch_internal_errors(&filename, &code, || {
+            index_symbols(&filename, &code, &args.options)
+        }));
+        print!(
+            "{}",
+            format_symbol_index(&index, &CodeMap::new(&filename, &code))
+        );
+        return;
+    }
+    let types = unwrap_result(catch_internal_errors(&filename, &code, || {
+        typecheck(&filename, &code, &args.options)
+    }));
+    if args.dump_types {
+        print!("{}", types);
+    }
+}
+
+struct WatchedFile {
+    name: String,
+    modified: Option<SystemTime>,
+    // the last checked version
+    code: Option<String>,
+    cache: ContextCache,
+    reported_unreadable: bool,
+}
+
+// polls modification times of the files, and checks the ones which changed;
+// runs until interrupted
+fn watch(args: &Args) -> ! {
+    let mut files: Vec<_> = args
+        .inputs
+        .iter()
+        .map(|name| WatchedFile {
+            name: name.clone(),
+            modified: None,
+            code: None,
+            cache: ContextCache::default(),
+            reported_unreadable: false,
+        })
+        .collect();
+    loop {
+        for file in &mut files {
+            let modified = fs::metadata(&file.name).and_then(|m| m.modified()).ok();
+            if modified.is_some() && modified == file.modified {
+                continue;
+            }
+            let code = match fs::read_to_string(&file.name) {
+                Ok(code) if modified.is_some() => code,
+                _ => {
+                    if !file.reported_unreadable {
+                        eprintln!("Cannot read file: {}", file.name);
+                        file.reported_unreadable = true;
+                    }
+                    continue;
+                }
+            };
+            file.modified = modified;
+            file.reported_unreadable = false;
+            // e.g. only touched
+            if file.code.as_ref() == Some(&code) {
+                continue;
+            }
+            recheck_file(file, &code, &args.options);
+            file.code = Some(code);
+        }
+        thread::sleep(Duration::from_millis(200));
+    }
+}
+
+fn recheck_file(file: &mut WatchedFile, code: &str, options: &CompilerOptions) {
+    let start = Instant::now();
+    let (name, cache) = (&file.name, &mut file.cache);
+    let res = ice::catch_internal_errors(AssertUnwindSafe(|| recheck(name, code, options, cache)));
+    let reused = if cache.was_reused() {
+        ", global context reused"
+    } else {
+        ""
+    };
+    eprintln!(
+        "== {} (checked in {} ms{})",
+        name,
+        start.elapsed().as_millis(),
+        reused
+    );
+    match res {
+        Ok(Ok(warnings)) => {
+            eprintln!("OK");
+            if let Some(warnings) = warnings {
+                eprintln!("{}", warnings);
+            }
+        }
+        Ok(Err(msg)) => {
+            eprintln!("ERROR");
+            eprintln!("{}", msg);
+        }
+        Err(err) => {
+            eprintln!("ERROR");
+            eprintln!("{}", err.format(&CodeMap::new(name, code)));
+        }
+    }
+}
+
+fn build(args: &Args) {
+    let (filename, code) = read_input(&args.inputs[0]);
+    let options = &args.options;
+    if args.interpret {
+        run_interpreter(&filename, &code, options);
+    }
+    if options.emit == Emit::CallGraph {
+        let graph = unwrap_result(catch_internal_errors(&filename, &code, || {
+            call_graph(&filename, &code, options)
+        }));
+        print!("{}", graph);
+        return;
+    }
+    let prog = unwrap_result(catch_internal_errors(&filename, &code, || {
+        compile(&filename, &code, options)
+    }));
+    if args.interpret_ir {
+        run_ir_interpreter(&prog);
+    }
+    match options.report {
+        Some(Report::IrStats) => {
+            print!("{}", format_ir_stats(&prog, options.report_format));
+            return;
+        }
+        Some(Report::RegAlloc) => {
+            let abi = Abi::for_target(options.target);
+            let report = format_allocations(&prog, options.regalloc, abi, options.report_format);
+            print!("{}", report);
+            return;
+        }
+        Some(Report::StackMaps) => {
+            let abi = Abi::for_target(options.target);
+            let report = format_stack_maps(&prog, options.regalloc, abi, options.report_format);
+            print!("{}", report);
+            return;
+        }
+        Some(Report::StackUsage) => {
+            let abi = Abi::for_target(options.target);
+            let report = format_stack_usage(&prog, options.regalloc, abi, options.report_format);
+            print!("{}", report);
+            return;
+        }
+        Some(Report::Abi) => {
+            let abi = Abi::for_target(options.target);
+            print!("{}", format_calls(&prog, abi, options.report_format));
+            return;
+        }
+        Some(Report::SourceMap) => {
+            let codemap = CodeMap::new(&filename, &code);
+            print!(
+                "{}",
+                format_source_map(&prog, &codemap, options.report_format)
+            );
+            return;
+        }
+        None => (),
+    }
+    let ll_code = match options.emit {
+        Emit::Llvm | Emit::Obj => format!("{}", prog),
+        Emit::ClassHierarchy => {
+            print!("{}", prog.format_class_hierarchy());
+            return;
+        }
+        Emit::Strings => {
+            print!("{}", prog.format_global_strings());
+            return;
+        }
+        Emit::Vtables => {
+            print!("{}", prog.format_vtables());
+            return;
+        }
+        Emit::IrJson => {
+            print!("{}", encode_program_json(&prog));
+            return;
+        }
+        Emit::IrBinary => {
+            if io::stdout()
+                .write_all(&encode_program_binary(&prog))
+                .is_err()
+            {
+                process::exit(1);
+            }
+            return;
+        }
+        Emit::CallGraph => unreachable!(),
     };
+    if args.validate_llvm {
+        validate_llvm(&ll_code);
+    }
+    if args.to_stdout {
+        print!("{}", ll_code);
+        return;
+    }
 
-    let ll_output_file = input_file.with_extension("ll");
-    let bc_output_file = input_file.with_extension("bc");
-    match fs::write(&ll_output_file, ll_code) {
-        Ok(_) => {}
-        Err(_) => {
-            eprintln!("Cannot write file: {}", ll_output_file.display());
-            process::exit(1);
+    // the outputs are named after the input, the standard input is "a";
+    // run writes them to a temporary directory and only its program prints
+    let run = args.command == Command::Run;
+    let temp_dir = env::temp_dir().join(format!("latte-run-{}", process::id()));
+    let out_dir = match &args.out_dir {
+        Some(dir) => Some(dir.clone()),
+        None if run => Some(temp_dir.clone()),
+        None => None,
+    };
+    let input_file = Path::new(&args.inputs[0]);
+    let base = match out_dir {
+        Some(dir) => {
+            if fs::create_dir_all(&dir).is_err() {
+                eprintln!("Cannot create directory: {}", dir.display());
+                process::exit(1);
+            }
+            match input_file.file_stem() {
+                Some(stem) if args.inputs[0] != "-" => dir.join(stem),
+                _ => dir.join("a"),
+            }
+        }
+        None if args.inputs[0] == "-" => PathBuf::from("a"),
+        None => input_file.with_extension(""),
+    };
+
+    let bc_output_file = write_llvm(&ll_code, &filename, &base, !run);
+    let emit_object = options.emit == Emit::Obj;
+    if !args.link && !emit_object {
+        return;
+    }
+    if !emit_object && options.target != Target::X86_64 {
+        eprintln!(
+            "The runtime is built for x86-64 only, other targets need --emit=obj or --no-link."
+        );
+        process::exit(1);
+    }
+    let o_output_file = write_object(&bc_output_file, &base, options, emit_object);
+    if emit_object {
+        return;
+    }
+    let exec_output_file = make_executable(&o_output_file, &base, options.relocation_model, !run);
+    if run {
+        // a relative path without a directory would be searched in PATH
+        let exec_path = Path::new(".").join(&exec_output_file);
+        let status = process::Command::new(&exec_path).status();
+        if args.out_dir.is_none() {
+            let _ = fs::remove_dir_all(&temp_dir);
+        }
+        match status {
+            Ok(status) => process::exit(status.code().unwrap_or(1)),
+            Err(_) => {
+                eprintln!("Cannot run {}", exec_output_file.display());
+                process::exit(1);
+            }
+        }
+    }
+}
+
+// the printed IR is rejected only because of a bug of the compiler, so
+// it's reported with the function which has to be looked at; the checks
+// are skipped with a warning if LLVM isn't installed
+fn validate_llvm(ll_code: &str) {
+    let tools: &[&[&str]] = &[
+        &["llvm-as", "-o", "/dev/null", "-"],
+        &["opt", "-passes=verify", "-disable-output", "-"],
+    ];
+    for cmd in tools {
+        let child = process::Command::new(cmd[0])
+            .args(&cmd[1..])
+            .stdin(process::Stdio::piped())
+            .stdout(process::Stdio::null())
+            .stderr(process::Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(_) => {
+                eprintln!("Warning: {} not found, the LLVM IR isn't validated", cmd[0]);
+                continue;
+            }
+        };
+        // the tools read the whole input before reporting anything
+        let written = child
+            .stdin
+            .take()
+            .is_some_and(|mut stdin| stdin.write_all(ll_code.as_bytes()).is_ok());
+        let output = match child.wait_with_output() {
+            Ok(output) => output,
+            Err(_) => {
+                eprintln!(
+                    "Warning: cannot run {}, the LLVM IR isn't validated",
+                    cmd[0]
+                );
+                continue;
+            }
+        };
+        if written && output.status.success() {
+            continue;
         }
+        let msg = String::from_utf8_lossy(&output.stderr);
+        match find_failing_function(ll_code, &msg) {
+            Some(fun_name) => eprintln!("{} rejected the LLVM IR of {}:", cmd[0], fun_name),
+            None => eprintln!("{} rejected the LLVM IR:", cmd[0]),
+        }
+        eprint!("{}", msg);
+        eprintln!("This is a bug of the compiler.");
+        process::exit(1);
     }
+}
+
+// the parser reports the line (`<stdin>:LINE:COL: error: ...`), the verifier
+// prints the offending instructions, indented
+fn find_failing_function(ll_code: &str, msg: &str) -> Option<String> {
+    let lines: Vec<_> = ll_code.lines().collect();
+    let line_idx = match msg.find("<stdin>:") {
+        Some(pos) => msg[pos + "<stdin>:".len()..]
+            .split(':')
+            .next()
+            .and_then(|num| num.parse::<usize>().ok())
+            .map(|num| num.saturating_sub(1)),
+        None => msg
+            .lines()
+            .filter(|line| line.starts_with("  ") && !line.trim().is_empty())
+            .filter_map(|line| lines.iter().position(|l| l.trim() == line.trim()))
+            .next(),
+    }?;
+    lines
+        .get(..=line_idx)?
+        .iter()
+        .rev()
+        .take_while(|line| *line != &"}")
+        .find(|line| line.starts_with("define "))
+        .and_then(|line| line.split(" @").nth(1))
+        .and_then(|rest| rest.split('(').next())
+        .map(|name| format!("@{}", name))
+}
 
-    if run_command(&[
+// writes the .ll and .bc files, returns the latter
+fn write_llvm(ll_code: &str, filename: &str, base: &Path, verbose: bool) -> PathBuf {
+    let ll_output_file = base.with_extension("ll");
+    let bc_output_file = base.with_extension("bc");
+    write_output(&ll_output_file, ll_code);
+    if !run_command(&[
         "llvm-as",
         "-o",
         bc_output_file.to_str().unwrap(),
         ll_output_file.to_str().unwrap(),
     ]) {
+        eprintln!("Failed to run llvm-as");
+        process::exit(1);
+    }
+    if verbose {
         println!(
             "Compiled {} to {} and {}.",
-            input_file.display(),
+            filename,
             ll_output_file.display(),
             bc_output_file.display()
         );
-    } else {
-        eprintln!("Failed to run llvm-as");
+    }
+    bc_output_file
+}
+
+// compiles the bitcode to an object file of the target with llc, returns its path
+// todo (ext) object writer: write the object without llc (e.g. with the object
+// crate), needs a native backend generating the machine code first
+fn write_object(
+    bc_output_file: &Path,
+    base: &Path,
+    options: &CompilerOptions,
+    verbose: bool,
+) -> PathBuf {
+    let o_output_file = base.with_extension("o");
+    let mut cmd = vec!["llc", "-O0"];
+    cmd.extend(match options.target {
+        Target::X86_64 => &["-march=x86-64"][..],
+        Target::AArch64 => &["-mtriple=aarch64-linux-gnu"],
+        // riscv64gc, the hard-float ABI (the same as the soft-float one without floats)
+        Target::RiscV64 => &[
+            "-mtriple=riscv64-linux-gnu",
+            "-mattr=+m,+a,+f,+d,+c",
+            "-target-abi=lp64d",
+        ],
+    });
+    cmd.extend(&[
+        get_llc_relocation_model(options.relocation_model),
+        "-filetype=obj",
+        "-o",
+        o_output_file.to_str().unwrap(),
+        bc_output_file.to_str().unwrap(),
+    ]);
+    if !run_command(&cmd) {
+        eprintln!("Failed to compile generated llvm bitcode.");
         process::exit(1);
     }
+    if verbose {
+        println!("Created object file {}", o_output_file.display());
+    }
+    o_output_file
+}
 
-    if make_executable {
-        let o_output_file = input_file.with_extension("o");
-        let exec_output_file = input_file.with_extension("");
-        let bc_runtime = Path::new("lib/runtime.bc");
-        let o_runtime = bc_runtime.with_extension("o");
+fn get_llc_relocation_model(relocation_model: RelocationModel) -> &'static str {
+    match relocation_model {
+        RelocationModel::Static => "-relocation-model=static",
+        RelocationModel::Pic => "-relocation-model=pic",
+    }
+}
 
-        if !Path::exists(&o_runtime) {
+// links the program with the runtime, returns the executable; the runtime
+// is compiled once for each relocation model
+fn make_executable(
+    o_output_file: &Path,
+    base: &Path,
+    relocation_model: RelocationModel,
+    verbose: bool,
+) -> PathBuf {
+    let exec_output_file = base.to_path_buf();
+    let bc_runtime = Path::new("lib/runtime.bc");
+    let (o_runtime, gcc_pie) = match relocation_model {
+        RelocationModel::Static => (bc_runtime.with_extension("o"), "-no-pie"),
+        RelocationModel::Pic => (bc_runtime.with_extension("pic.o"), "-pie"),
+    };
+
+    if !Path::exists(&o_runtime) {
+        if verbose {
             println!("Compiling runtime.");
-            if !run_command(&[
-                "llc",
-                "-O0",
-                "-march=x86-64",
-                "-filetype=obj",
-                "-o",
-                o_runtime.to_str().unwrap(),
-                bc_runtime.to_str().unwrap(),
-            ]) {
-                eprintln!(
-                    "Failed to compile runtime!\nRuntime file: {}",
-                    bc_runtime.display()
-                );
-                process::exit(1);
-            }
         }
-
         if !run_command(&[
             "llc",
             "-O0",
             "-march=x86-64",
+            get_llc_relocation_model(relocation_model),
             "-filetype=obj",
             "-o",
-            o_output_file.to_str().unwrap(),
-            bc_output_file.to_str().unwrap(),
+            o_runtime.to_str().unwrap(),
+            bc_runtime.to_str().unwrap(),
         ]) {
-            eprintln!("Failed to compile generated llvm bitcode.");
+            eprintln!(
+                "Failed to compile runtime!\nRuntime file: {}",
+                bc_runtime.display()
+            );
             process::exit(1);
         }
+    }
 
-        if run_command(&[
-            "gcc",
-            "-no-pie",
-            "-O0",
-            "-o",
-            exec_output_file.to_str().unwrap(),
-            o_output_file.to_str().unwrap(),
-            o_runtime.to_str().unwrap(),
-        ]) {
-            println!("Created executable {}", exec_output_file.display());
-        } else {
+    if !run_command(&[
+        "gcc",
+        gcc_pie,
+        "-O0",
+        "-o",
+        exec_output_file.to_str().unwrap(),
+        o_output_file.to_str().unwrap(),
+        o_runtime.to_str().unwrap(),
+    ]) {
+        eprintln!(
+            "Failed to link {} and {} with gcc.",
+            o_output_file.display(),
+            o_runtime.display()
+        );
+        process::exit(1);
+    }
+    if verbose {
+        println!("Created executable {}", exec_output_file.display());
+    }
+    exec_output_file
+}
+
+// files are formatted in place, the standard input to stdout;
+// with --check exits with 1 if some file isn't formatted
+fn format_files(args: &Args) {
+    let mut all_formatted = true;
+    for input in &args.inputs {
+        let (filename, code) = read_input(input);
+        let formatted = match catch_internal_errors(&filename, &code, || format(&filename, &code)) {
+            Ok(formatted) => formatted,
+            Err(msg) => {
+                eprintln!("ERROR");
+                eprintln!("{}", msg);
+                process::exit(1);
+            }
+        };
+        if args.check_formatting {
+            if formatted != code {
+                println!("{} is not formatted", filename);
+                all_formatted = false;
+            }
+        } else if input == "-" {
+            print!("{}", formatted);
+        } else if formatted != code {
+            write_output(Path::new(input), formatted);
+        }
+    }
+    if !all_formatted {
+        process::exit(1);
+    }
+}
+
+fn emit_runtime(args: &Args) {
+    let dir = args.out_dir.clone().unwrap_or_else(|| PathBuf::from("."));
+    if fs::create_dir_all(&dir).is_err() {
+        eprintln!("Cannot create directory: {}", dir.display());
+        process::exit(1);
+    }
+    write_output(&dir.join("runtime.ll"), RUNTIME_LL);
+    write_output(&dir.join("runtime.bc"), RUNTIME_BC);
+    println!(
+        "Written {} and {}",
+        dir.join("runtime.ll").display(),
+        dir.join("runtime.bc").display()
+    );
+}
+
+fn explain(args: &Args) {
+    if args.inputs.is_empty() {
+        for e in get_explanations() {
+            println!("{}", e.message);
+        }
+        return;
+    }
+    let query = args.inputs.join(" ");
+    let explanations = find_explanations(&query);
+    if explanations.is_empty() {
+        eprintln!("No explanation for: {}", query);
+        process::exit(1);
+    }
+    for e in explanations {
+        println!("{}\n\n{}\n", e.message, wrap_words(e.text, 76));
+    }
+}
+
+// prints a program serialized with --emit=ir-json or --emit=ir-binary as LLVM IR
+fn disassemble_ir(args: &Args) {
+    let input = &args.inputs[0];
+    let mut bytes = vec![];
+    let read = if input == "-" {
+        io::stdin().read_to_end(&mut bytes).map(|_| ())
+    } else {
+        fs::read(input).map(|b| bytes = b)
+    };
+    if read.is_err() {
+        eprintln!("Cannot read file: {}", input);
+        process::exit(1);
+    }
+    match decode_program(&bytes) {
+        Ok(prog) => print!("{}", prog),
+        Err(msg) => {
+            eprintln!("Invalid IR in {}: {}", input, msg);
+            process::exit(1);
+        }
+    }
+}
+
+fn wrap_words(text: &str, width: usize) -> String {
+    let mut res = String::new();
+    let mut line_len = 0;
+    for word in text.split_whitespace() {
+        if line_len > 0 && line_len + 1 + word.len() > width {
+            res.push('\n');
+            line_len = 0;
+        } else if line_len > 0 {
+            res.push(' ');
+            line_len += 1;
+        }
+        res += word;
+        line_len += word.len();
+    }
+    res
+}
+
+// exits with the exit code of the interpreted program
+fn run_interpreter(filename: &str, code: &str, options: &CompilerOptions) -> ! {
+    let prog = unwrap_result(catch_internal_errors(filename, code, || {
+        analyze(filename, code, options)
+    }));
+
+    // for reporting internal errors of the interpreter
+    let (filename, code) = (filename.to_string(), code.to_string());
+    let options = options.clone();
+    let interpreter_thread = thread::Builder::new()
+        .stack_size(interpreter::STACK_SIZE)
+        .spawn(move || {
+            let stdin = io::stdin();
+            let stdout = io::stdout();
+            let mut output = io::BufWriter::new(stdout.lock());
+            let res = ice::catch_internal_errors(AssertUnwindSafe(|| {
+                ice::enter_stage("interpretation");
+                interpreter::interpret(&prog, &options, &mut stdin.lock(), &mut output)
+            }));
+            let res = match res {
+                Ok(res) => res,
+                Err(err) => {
+                    let _ = output.flush();
+                    eprintln!("{}", err.format(&CodeMap::new(&filename, &code)));
+                    return 1;
+                }
+            };
+            match res {
+                Ok(exit_code) => exit_code,
+                Err(RuntimeError::ErrorCall) => {
+                    let _ = writeln!(output, "runtime error");
+                    let _ = output.flush();
+                    1
+                }
+                Err(err) => {
+                    eprintln!("{}", err);
+                    1
+                }
+            }
+        })
+        .expect("Cannot start the interpreter thread");
+    process::exit(interpreter_thread.join().unwrap_or(1))
+}
+
+// exits with the exit code of the program, like run_interpreter
+fn run_ir_interpreter(prog: &ir::Program) -> ! {
+    let (output, res) = ir_interpreter::run_program(prog, IR_INTERPRETER_STEPS);
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    let _ = stdout.write_all(&output);
+    let exit_code = match res {
+        Ok(exit_code) => exit_code,
+        Err(Stop::ErrorCall) => {
+            let _ = writeln!(stdout, "runtime error");
+            1
+        }
+        Err(err) => {
+            let _ = stdout.flush();
             eprintln!(
-                "Failed to link {} and {} with gcc.",
-                o_output_file.display(),
-                o_runtime.display()
+                "{}",
+                match err {
+                    Stop::Unsupported => "The IR interpreter supports only ints and booleans",
+                    Stop::Undefined => "Runtime error: division by zero or unreachable code",
+                    _ => "Runtime error: too many steps or too deep recursion",
+                }
             );
+            1
+        }
+    };
+    let _ = stdout.flush();
+    process::exit(exit_code)
+}
+
+// panics of the compiler are reported as internal errors
+fn catch_internal_errors<T, F: FnOnce() -> T + UnwindSafe>(filename: &str, code: &str, f: F) -> T {
+    match ice::catch_internal_errors(f) {
+        Ok(res) => res,
+        Err(err) => {
+            eprintln!("ERROR");
+            eprintln!("{}", err.format(&CodeMap::new(filename, code)));
             process::exit(1);
         }
     }
@@ -3,46 +3,100 @@ use std::fmt;
 #[derive(Debug)]
 pub struct Program {
     pub defs: Vec<TopDef>,
+    pub annotations: Vec<Annotation>,
 }
 
-#[derive(Debug)]
+// `// latte: allow(...)` comment, the text after the colon
+// (checked by semantics::lints)
+pub type Annotation = ItemWithSpan<String>;
+
+#[derive(Debug, Clone)]
 pub enum TopDef {
     FunDef(FunDef),
     ClassDef(ClassDef),
+    StructDef(StructDef),
+    TypeDef(TypeDef),
     Error,
 }
 
 pub type Span = (usize, usize);
 pub const EMPTY_SPAN: Span = (0, 0);
 pub const THIS_VAR: &str = "self";
+// inside of lambda bodies: object holding captured variables
+pub const LAMBDA_ENV_VAR: &str = "lambda.env";
+pub const STRING_BUILDER_CLASS: &str = "StringBuilder";
+pub const INT_LIST_CLASS: &str = "IntList";
+pub const STRING_LIST_CLASS: &str = "StringList";
+pub const STRING_INT_MAP_CLASS: &str = "StringIntMap";
+// every class type can be used where Object is expected
+pub const OBJECT_CLASS: &str = "Object";
+// == and != of objects call it, if the class defines it
+pub const EQUALS_METHOD: &str = "equals";
+// print(obj) calls it
+pub const TO_STRING_METHOD: &str = "toString";
+// generic builtin, semantic analysis replaces its calls with
+// printInt, printBoolean, printString or printString(x.toString())
+pub const PRINT_FUN: &str = "print";
+// generic builtins, not monomorphized, the codegen gets the element type from type arguments
+pub const ARRAY_RESIZE_FUN: &str = "arrayResize";
+pub const ARRAY_APPEND_FUN: &str = "append";
 pub type Ident = ItemWithSpan<String>;
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ClassDef {
     pub name: Ident,
+    pub type_params: Vec<Ident>, // non-empty for generic classes
     pub parent_type: Option<Type>,
     pub items: Vec<ClassItemDef>,
     pub span: Span,
 }
 
+// `struct Point { int x; int y; }`, a value type: copied on assignment,
+// passed and returned by value, never null
+#[derive(Debug, Clone)]
+pub struct StructDef {
+    pub name: Ident,
+    pub fields: Vec<(Type, Ident)>,
+    pub span: Span,
+}
+
+// `typedef int[] Row;`, aliases are replaced by their types by semantic analysis
+#[derive(Debug, Clone)]
+pub struct TypeDef {
+    pub name: Ident,
+    pub aliased_type: Type,
+    pub span: Span,
+}
+
 pub type ClassItemDef = ItemWithSpan<InnerClassItemDef>;
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InnerClassItemDef {
     Field(Type, Ident),
     Method(FunDef),
+    Class(ClassDef), // nested, lifted to the top level by the semantic analysis
     Error,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct FunDef {
+    pub inline_hint: InlineHint,
     pub ret_type: Type,
     pub name: Ident,
+    pub type_params: Vec<Ident>, // non-empty for generic functions
     pub args: Vec<(Type, Ident)>,
     pub body: Block,
     pub span: Span,
 }
 
-#[derive(Debug)]
+// `inline` / `noinline` before function definition
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InlineHint {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone)]
 pub struct Block {
     pub stmts: Vec<Box<Stmt>>,
     pub span: Span,
@@ -67,7 +121,7 @@ pub fn new_spanned<T>(l: usize, inner: T, r: usize) -> ItemWithSpan<T> {
 }
 
 pub type Stmt = ItemWithSpan<InnerStmt>;
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum InnerStmt {
     Empty,
     Block(Block),
@@ -84,14 +138,24 @@ pub enum InnerStmt {
         true_branch: Block,
         false_branch: Option<Block>,
     },
-    While(Box<Expr>, Block),
+    While {
+        label: Option<Ident>,
+        cond: Box<Expr>,
+        body: Block,
+    },
     ForEach {
+        label: Option<Ident>,
         iter_type: Type,
         iter_name: Ident,
         array: Box<Expr>,
         body: Block,
     },
     Expr(Box<Expr>),
+    // with optional label of the loop
+    Break(Option<Ident>),
+    Continue(Option<Ident>),
+    // lifted to the top level by semantic analysis
+    NestedFun(FunDef),
     Error,
 }
 
@@ -103,6 +167,10 @@ pub enum InnerType {
     String,
     Array(Box<InnerType>),
     Class(String),
+    Struct(String),
+    GenericClass(String, Vec<InnerType>),     // e.g. Box<int>
+    TypeParam(String),                        // T inside of a generic definition
+    Function(Box<InnerType>, Vec<InnerType>), // return type, arguments types
     Null,
     Void,
 }
@@ -119,6 +187,7 @@ pub enum InnerExpr {
     FunCall {
         function_name: Ident,
         args: Vec<Box<Expr>>,
+        type_args: Vec<InnerType>, // inferred for generic functions
     },
     BinaryOp(Box<Expr>, BinaryOp, Box<Expr>),
     UnaryOp(UnaryOp, Box<Expr>),
@@ -129,11 +198,13 @@ pub enum InnerExpr {
     ArrayElem {
         array: Box<Expr>,
         index: Box<Expr>,
+        is_array_a_string: Option<bool>, // characters of strings are ints
     },
     NewObject(Type),
     ObjField {
         obj: Box<Expr>,
         is_obj_an_array: Option<bool>,
+        is_obj_a_struct: Option<bool>,
         field: Ident,
     },
     ObjMethodCall {
@@ -141,16 +212,43 @@ pub enum InnerExpr {
         method_name: Ident,
         args: Vec<Box<Expr>>,
     },
+    Lambda {
+        ret_type: Type,
+        args: Vec<(Type, Ident)>,
+        body: Block,
+        // filled by semantic analysis: (type, name, value in the enclosing function)
+        captures: Vec<(Type, Ident, Box<Expr>)>,
+    },
+    // call of a function value
+    ClosureCall {
+        closure: Box<Expr>,
+        args: Vec<Box<Expr>>,
+    },
+    // `name: value` argument of a call, semantic analysis puts it in place of the parameter
+    NamedArg {
+        name: Ident,
+        value: Box<Expr>,
+    },
+    // `start .. end` (without end), only iterated by foreach loops
+    Range(Box<Expr>, Box<Expr>),
+    // `(lhs = rhs)` or `b = 0` in `a = b = 0;`, its value is the assigned one
+    Assign(Box<Expr>, Box<Expr>),
+    // `++x`, `x--` etc., the value of a prefix one is the changed value
+    IncrDecr {
+        lvalue: Box<Expr>,
+        is_incr: bool,
+        is_prefix: bool,
+    },
 }
 
 pub type UnaryOp = ItemWithSpan<InnerUnaryOp>;
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum InnerUnaryOp {
     IntNeg,
     BoolNeg,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum BinaryOp {
     And,
     Or,
@@ -165,6 +263,9 @@ pub enum BinaryOp {
     GE,
     EQ,
     NE,
+    // === and !== compare references, lowered to EQ and NE by semantic analysis
+    RefEQ,
+    RefNE,
 }
 
 impl fmt::Display for InnerType {
@@ -178,9 +279,430 @@ impl fmt::Display for InnerType {
                 subtype.fmt(f)?;
                 write!(f, "[]")
             }
-            Class(name) => write!(f, "{}", name),
+            Class(name) | Struct(name) | TypeParam(name) => write!(f, "{}", name),
+            GenericClass(name, type_args) => {
+                write!(f, "{}<", name)?;
+                for (i, t) in type_args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    t.fmt(f)?;
+                }
+                write!(f, ">")
+            }
+            Function(ret_type, args_types) => {
+                write!(f, "fn {}(", ret_type)?;
+                for (i, t) in args_types.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    t.fmt(f)?;
+                }
+                write!(f, ")")
+            }
             Null => write!(f, "null"),
             Void => write!(f, "void"),
         }
     }
 }
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::BinaryOp::*;
+        let op = match self {
+            And => "&&",
+            Or => "||",
+            Add => "+",
+            Sub => "-",
+            Mul => "*",
+            Div => "/",
+            Mod => "%",
+            LT => "<",
+            LE => "<=",
+            GT => ">",
+            GE => ">=",
+            EQ => "==",
+            NE => "!=",
+            RefEQ => "===",
+            RefNE => "!==",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+// the expression in the source syntax (after the semantic analysis, so
+// with its rewrites), operands of operators are parenthesized if needed
+impl fmt::Display for InnerExpr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::InnerExpr::*;
+        let fmt_operand = |f: &mut fmt::Formatter, e: &Expr| match e.inner {
+            BinaryOp(..) => write!(f, "({})", e.inner),
+            _ => write!(f, "{}", e.inner),
+        };
+        let fmt_args = |f: &mut fmt::Formatter, args: &[Box<Expr>]| {
+            write!(f, "(")?;
+            for (i, arg) in args.iter().enumerate() {
+                if i > 0 {
+                    write!(f, ", ")?;
+                }
+                write!(f, "{}", arg.inner)?;
+            }
+            write!(f, ")")
+        };
+        match self {
+            LitVar(name) => write!(f, "{}", name),
+            LitInt(val) => write!(f, "{}", val),
+            LitBool(val) => write!(f, "{}", val),
+            LitStr(val) => write!(f, "{:?}", val),
+            LitNull => write!(f, "null"),
+            CastType(e, _) => write!(f, "{}", e.inner), // only implicit casts
+            FunCall {
+                function_name,
+                args,
+                ..
+            } => {
+                write!(f, "{}", function_name.inner)?;
+                fmt_args(f, args)
+            }
+            BinaryOp(lhs, op, rhs) => {
+                fmt_operand(f, lhs)?;
+                write!(f, " {} ", op)?;
+                fmt_operand(f, rhs)
+            }
+            UnaryOp(op, e) => {
+                let op = match op.inner {
+                    InnerUnaryOp::IntNeg => "-",
+                    InnerUnaryOp::BoolNeg => "!",
+                };
+                write!(f, "{}", op)?;
+                fmt_operand(f, e)
+            }
+            NewArray {
+                elem_type,
+                elem_cnt,
+            } => write!(f, "new {}[{}]", elem_type.inner, elem_cnt.inner),
+            ArrayElem { array, index, .. } => {
+                fmt_operand(f, array)?;
+                write!(f, ".[{}]", index.inner)
+            }
+            NewObject(t) => write!(f, "new {}", t.inner),
+            ObjField { obj, field, .. } => {
+                fmt_operand(f, obj)?;
+                write!(f, ".{}", field.inner)
+            }
+            ObjMethodCall {
+                obj,
+                method_name,
+                args,
+            } => {
+                fmt_operand(f, obj)?;
+                write!(f, ".{}", method_name.inner)?;
+                fmt_args(f, args)
+            }
+            Lambda { ret_type, args, .. } => {
+                write!(f, "fn {}(", ret_type.inner)?;
+                for (i, (t, name)) in args.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{} {}", t.inner, name.inner)?;
+                }
+                write!(f, ") {{ ... }}")
+            }
+            ClosureCall { closure, args } => {
+                fmt_operand(f, closure)?;
+                fmt_args(f, args)
+            }
+            NamedArg { name, value } => write!(f, "{}: {}", name.inner, value.inner),
+            Range(start, end) => {
+                fmt_operand(f, start)?;
+                write!(f, " .. ")?;
+                fmt_operand(f, end)
+            }
+            Assign(lhs, rhs) => write!(f, "({} = {})", lhs.inner, rhs.inner),
+            IncrDecr {
+                lvalue,
+                is_incr,
+                is_prefix,
+            } => {
+                let op = if *is_incr { "++" } else { "--" };
+                if *is_prefix {
+                    write!(f, "{}", op)?;
+                    fmt_operand(f, lvalue)
+                } else {
+                    fmt_operand(f, lvalue)?;
+                    write!(f, "{}", op)
+                }
+            }
+        }
+    }
+}
+
+// Structural equality of the code, ignoring spans (so it can't be derived),
+// e.g. both branches of `if (c) x++; else { x++; }` are the same.
+
+pub fn same_blocks(lhs: &Block, rhs: &Block) -> bool {
+    lhs.stmts.len() == rhs.stmts.len()
+        && lhs
+            .stmts
+            .iter()
+            .zip(&rhs.stmts)
+            .all(|(l, r)| same_stmts(l, r))
+}
+
+fn same_opt_blocks(lhs: &Option<Block>, rhs: &Option<Block>) -> bool {
+    match (lhs, rhs) {
+        (Some(l), Some(r)) => same_blocks(l, r),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+pub fn same_stmts(lhs: &Stmt, rhs: &Stmt) -> bool {
+    use self::InnerStmt::*;
+    match (&lhs.inner, &rhs.inner) {
+        (Empty, Empty) => true,
+        (Block(l), Block(r)) => same_blocks(l, r),
+        (
+            Decl {
+                var_type: l_type,
+                var_items: l_items,
+            },
+            Decl {
+                var_type: r_type,
+                var_items: r_items,
+            },
+        ) => {
+            l_type.inner == r_type.inner
+                && l_items.len() == r_items.len()
+                && l_items
+                    .iter()
+                    .zip(r_items)
+                    .all(|((l_id, l_e), (r_id, r_e))| {
+                        l_id.inner == r_id.inner && same_opt_exprs(l_e, r_e)
+                    })
+        }
+        (Assign(l_lhs, l_rhs), Assign(r_lhs, r_rhs)) => {
+            same_exprs(l_lhs, r_lhs) && same_exprs(l_rhs, r_rhs)
+        }
+        (Incr(l), Incr(r)) | (Decr(l), Decr(r)) | (Expr(l), Expr(r)) => same_exprs(l, r),
+        (Ret(l), Ret(r)) => same_opt_exprs(l, r),
+        (
+            Cond {
+                cond: l_cond,
+                true_branch: l_true,
+                false_branch: l_false,
+            },
+            Cond {
+                cond: r_cond,
+                true_branch: r_true,
+                false_branch: r_false,
+            },
+        ) => {
+            same_exprs(l_cond, r_cond)
+                && same_blocks(l_true, r_true)
+                && same_opt_blocks(l_false, r_false)
+        }
+        (
+            While {
+                label: l_label,
+                cond: l_cond,
+                body: l_body,
+            },
+            While {
+                label: r_label,
+                cond: r_cond,
+                body: r_body,
+            },
+        ) => {
+            same_opt_idents(l_label, r_label)
+                && same_exprs(l_cond, r_cond)
+                && same_blocks(l_body, r_body)
+        }
+        (
+            ForEach {
+                label: l_label,
+                iter_type: l_type,
+                iter_name: l_name,
+                array: l_array,
+                body: l_body,
+            },
+            ForEach {
+                label: r_label,
+                iter_type: r_type,
+                iter_name: r_name,
+                array: r_array,
+                body: r_body,
+            },
+        ) => {
+            same_opt_idents(l_label, r_label)
+                && l_type.inner == r_type.inner
+                && l_name.inner == r_name.inner
+                && same_exprs(l_array, r_array)
+                && same_blocks(l_body, r_body)
+        }
+        (Break(l), Break(r)) | (Continue(l), Continue(r)) => same_opt_idents(l, r),
+        _ => false, // nested functions are never compared, they're already lifted
+    }
+}
+
+fn same_opt_idents(lhs: &Option<Ident>, rhs: &Option<Ident>) -> bool {
+    lhs.as_ref().map(|id| &id.inner) == rhs.as_ref().map(|id| &id.inner)
+}
+
+fn same_opt_exprs(lhs: &Option<Box<Expr>>, rhs: &Option<Box<Expr>>) -> bool {
+    match (lhs, rhs) {
+        (Some(l), Some(r)) => same_exprs(l, r),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn same_expr_lists(lhs: &[Box<Expr>], rhs: &[Box<Expr>]) -> bool {
+    lhs.len() == rhs.len() && lhs.iter().zip(rhs).all(|(l, r)| same_exprs(l, r))
+}
+
+pub fn same_exprs(lhs: &Expr, rhs: &Expr) -> bool {
+    use self::InnerExpr::*;
+    match (&lhs.inner, &rhs.inner) {
+        (LitVar(l), LitVar(r)) | (LitStr(l), LitStr(r)) => l == r,
+        (LitInt(l), LitInt(r)) => l == r,
+        (LitBool(l), LitBool(r)) => l == r,
+        (LitNull, LitNull) => true,
+        (CastType(l, l_type), CastType(r, r_type)) => l_type == r_type && same_exprs(l, r),
+        (
+            FunCall {
+                function_name: l_name,
+                args: l_args,
+                type_args: l_type_args,
+            },
+            FunCall {
+                function_name: r_name,
+                args: r_args,
+                type_args: r_type_args,
+            },
+        ) => {
+            l_name.inner == r_name.inner
+                && l_type_args == r_type_args
+                && same_expr_lists(l_args, r_args)
+        }
+        (BinaryOp(l_lhs, l_op, l_rhs), BinaryOp(r_lhs, r_op, r_rhs)) => {
+            l_op == r_op && same_exprs(l_lhs, r_lhs) && same_exprs(l_rhs, r_rhs)
+        }
+        (UnaryOp(l_op, l), UnaryOp(r_op, r)) => l_op.inner == r_op.inner && same_exprs(l, r),
+        (
+            NewArray {
+                elem_type: l_type,
+                elem_cnt: l_cnt,
+            },
+            NewArray {
+                elem_type: r_type,
+                elem_cnt: r_cnt,
+            },
+        ) => l_type.inner == r_type.inner && same_exprs(l_cnt, r_cnt),
+        (
+            ArrayElem {
+                array: l_array,
+                index: l_index,
+                ..
+            },
+            ArrayElem {
+                array: r_array,
+                index: r_index,
+                ..
+            },
+        ) => same_exprs(l_array, r_array) && same_exprs(l_index, r_index),
+        (NewObject(l), NewObject(r)) => l.inner == r.inner,
+        (
+            ObjField {
+                obj: l_obj,
+                field: l_field,
+                ..
+            },
+            ObjField {
+                obj: r_obj,
+                field: r_field,
+                ..
+            },
+        ) => l_field.inner == r_field.inner && same_exprs(l_obj, r_obj),
+        (
+            ObjMethodCall {
+                obj: l_obj,
+                method_name: l_name,
+                args: l_args,
+            },
+            ObjMethodCall {
+                obj: r_obj,
+                method_name: r_name,
+                args: r_args,
+            },
+        ) => {
+            l_name.inner == r_name.inner
+                && same_exprs(l_obj, r_obj)
+                && same_expr_lists(l_args, r_args)
+        }
+        (
+            Lambda {
+                ret_type: l_ret_type,
+                args: l_args,
+                body: l_body,
+                ..
+            },
+            Lambda {
+                ret_type: r_ret_type,
+                args: r_args,
+                body: r_body,
+                ..
+            },
+        ) => {
+            l_ret_type.inner == r_ret_type.inner
+                && l_args.len() == r_args.len()
+                && l_args
+                    .iter()
+                    .zip(r_args)
+                    .all(|((l_type, l_id), (r_type, r_id))| {
+                        l_type.inner == r_type.inner && l_id.inner == r_id.inner
+                    })
+                && same_blocks(l_body, r_body)
+        }
+        (
+            ClosureCall {
+                closure: l_closure,
+                args: l_args,
+            },
+            ClosureCall {
+                closure: r_closure,
+                args: r_args,
+            },
+        ) => same_exprs(l_closure, r_closure) && same_expr_lists(l_args, r_args),
+        (
+            NamedArg {
+                name: l_name,
+                value: l,
+            },
+            NamedArg {
+                name: r_name,
+                value: r,
+            },
+        ) => l_name.inner == r_name.inner && same_exprs(l, r),
+        (Range(l_start, l_end), Range(r_start, r_end)) => {
+            same_exprs(l_start, r_start) && same_exprs(l_end, r_end)
+        }
+        (Assign(l_lhs, l_rhs), Assign(r_lhs, r_rhs)) => {
+            same_exprs(l_lhs, r_lhs) && same_exprs(l_rhs, r_rhs)
+        }
+        (
+            IncrDecr {
+                lvalue: l,
+                is_incr: l_incr,
+                is_prefix: l_prefix,
+            },
+            IncrDecr {
+                lvalue: r,
+                is_incr: r_incr,
+                is_prefix: r_prefix,
+            },
+        ) => l_incr == r_incr && l_prefix == r_prefix && same_exprs(l, r),
+        _ => false,
+    }
+}
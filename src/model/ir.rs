@@ -1,18 +1,37 @@
 use model::ast;
+pub use model::ir_types::{Type, TypeKind};
 use semantics::global_context::FunDesc;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fmt;
 
+// name of the first field of objects (pointer to the vtable)
+pub const VTABLE_FIELD_NAME: &str = "vtable";
+
 pub struct Program {
+    pub size_type: Type, // of allocation sizes, as wide as pointers
+    pub structs: Vec<Struct>,
     pub classes: Vec<Class>,
     pub functions: Vec<Function>,
-    pub global_strings: HashMap<String, GlobalStrNum>,
+    pub global_strings: GlobalStrings,
 }
 
 pub struct Class {
     pub name: String,
     pub fields: Vec<Type>,
+    pub field_names: Vec<String>, // for the --emit=vtables output
     pub vtable: Vec<(Type, String)>,
+    pub is_builtin: bool, // methods are implemented in the runtime
+    // closed world analysis results (the whole program is known)
+    pub parent: Option<String>,
+    pub is_final: bool,        // no subclasses
+    pub overridden: Vec<bool>, // per vtable entry: overridden in some subclass
+    pub needs_vtable: bool,    // some virtual call may use vtable of this class
+}
+
+// value type, kept in registers (LLVM first-class aggregate)
+pub struct Struct {
+    pub name: String,
+    pub fields: Vec<Type>,
 }
 
 pub struct Function {
@@ -20,6 +39,27 @@ pub struct Function {
     pub name: String,
     pub args: Vec<(RegNum, Type)>,
     pub blocks: Vec<Block>,
+    pub inline_hint: ast::InlineHint,
+    // inferred by the optimizer, codegen assumes the worst
+    pub purity: Purity,
+    pub always_returns: bool, // can't loop forever nor fail
+    pub never_recurses: bool, // isn't on the stack when called
+    pub attributes: BTreeSet<FunctionAttribute>,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum FunctionAttribute {
+    Internal, // not visible outside the module (all but main)
+    NoReturn, // ends the program or loops forever
+    Used,     // kept even if nothing in the module calls it (main)
+}
+
+// what a call of a function can do, from the worst
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Clone, Copy)]
+pub enum Purity {
+    Impure,
+    ReadOnly, // reads memory, but doesn't write it nor do IO
+    Pure,     // the result depends only on the arguments
 }
 
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
@@ -28,25 +68,42 @@ pub struct Label(pub u32);
 #[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub struct RegNum(pub u32);
 
-// consider replacing it with just a String
+// hash of the content, so the names don't depend on the order of functions
 #[derive(PartialEq, Eq, Hash, Clone, Copy)]
-pub struct GlobalStrNum(pub u32);
+pub struct GlobalStrId(pub u64);
 
+// string literals of the program, each one is defined once
+#[derive(Default)]
+pub struct GlobalStrings {
+    ids: HashMap<String, GlobalStrId>,
+    used_ids: HashSet<GlobalStrId>,
+}
+
+#[derive(Clone)]
 pub struct Block {
     pub label: Label,
-    pub phi_set: HashSet<PhiEntry>,
+    pub phis: Vec<Phi>, // in order of creation, one per destination register
     pub predecessors: Vec<Label>,
     pub body: Vec<Operation>,
+    pub terminator: Terminator, // exactly one, after the body
+}
+
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub struct Phi {
+    pub reg_num: RegNum,
+    pub reg_type: Type,
+    pub incoming: Vec<(Value, Label)>, // todo (optional) add string for var name
 }
-pub type PhiEntry = (RegNum, Type, Vec<(Value, Label)>); // todo (optional) add string for var name
 
 // almost-quadruple code
 // read left-to-right, like in LLVM
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum Operation {
-    Return(Option<Value>),
     FunctionCall(Option<RegNum>, Type, Value, Vec<Value>),
     Arithmetic(RegNum, ArithOp, Value, Value),
     Compare(RegNum, CmpOp, Value, Value),
+    Not(RegNum, Value),                  // negation of an i1, xor with true
+    Select(RegNum, Value, Value, Value), // (dst, i1 condition, if true, if false)
     GetElementPtr(RegNum, Type, Vec<Value>),
     CastGlobalString(RegNum, usize, Value), // usize is string length
     CastPtr {
@@ -55,23 +112,111 @@ pub enum Operation {
         src_value: Value,
     },
     CastPtrToInt {
+        dst: RegNum,
+        dst_type: Type,
+        src_value: Value,
+    },
+    CastCharToInt {
+        dst: RegNum,
+        src_value: Value,
+    },
+    // field of a struct value, nested fields are given by a path of indices
+    ExtractValue {
+        dst: RegNum,
+        src_value: Value,
+        indices: Vec<u32>,
+    },
+    // copy of a struct value with a (nested) field replaced
+    InsertValue {
         dst: RegNum,
         src_value: Value,
+        field_value: Value,
+        indices: Vec<u32>,
     },
     Load(RegNum, Value),
     Store(Value, Value),
+    Alloca(RegNum, Type, i32), // stack memory for given count of elements
+    MemZero(Value, Value),     // (i8* ptr, i32 or i64 size)
+    // the following operations of the block were generated for the statement
+    // at the span (the passes ignore it, printed only with {:#})
+    SourceSpan(ast::Span),
+}
+
+// the control flow out of a block
+#[derive(Clone, PartialEq, Eq, Hash)]
+pub enum Terminator {
+    Return(Option<Value>),
     Branch1(Label),
-    Branch2(Value, Label, Label),
+    Branch2(Value, Label, Label, BranchHint),
+    // of a new block until the codegen finishes it, or after a call of
+    // a noreturn function
+    Unreachable,
+}
+
+// expected value of the condition of Branch2 (the first target is taken
+// if it's true), emitted as branch weights
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum BranchHint {
+    None,
+    Likely,
+    Unlikely,
+    Weights(u32, u32), // measured (true, false), from a profile
+}
+
+// the weights of __builtin_expect in clang
+const LIKELY_WEIGHTS: (u32, u32) = (2000, 1);
+const LIKELY_METADATA: u32 = 0;
+const UNLIKELY_METADATA: u32 = 1;
+
+// type-based alias analysis: scalars of different types are never at the
+// same address, all pointers are one type; chars (string contents, also
+// accessed by the runtime) and structs aren't tagged
+const TBAA_ROOT_METADATA: u32 = 2;
+const TBAA_TYPES: &[(&str, u32)] = &[("int", 3), ("long", 5), ("bool", 7), ("pointer", 9)];
+
+fn get_tbaa_metadata(t: &Type) -> Option<u32> {
+    let name = match t.kind() {
+        TypeKind::Int => "int",
+        TypeKind::Long => "long",
+        TypeKind::Bool => "bool",
+        TypeKind::Ptr(_) => "pointer",
+        _ => return None,
+    };
+    let (_, type_metadata) = TBAA_TYPES.iter().find(|(n, _)| *n == name)?;
+    Some(type_metadata + 1) // the access tag follows the type
 }
 
+// runtime functions ending the program
+pub const NORETURN_FUNCTIONS: &[&str] = &[
+    "error",
+    "_bltn_stack_overflow_error",
+    "_bltn_null_dereference_error",
+];
+
+// runtime functions returning new memory, never null
+const ALLOCATING_FUNCTIONS: &[&str] = &[
+    "readFile",
+    "split",
+    "join",
+    "_bltn_malloc",
+    "_bltn_alloc_array",
+    "_bltn_array_copy",
+    "_bltn_array_resize",
+    "_bltn_array_append",
+];
+
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum ArithOp {
     Add,
     Sub,
     Mul,
     Div,
     Mod,
+    And,
+    Or,
 }
 
+#[derive(Clone, PartialEq, Eq, Hash)]
 pub enum CmpOp {
     LT,
     LE,
@@ -84,97 +229,546 @@ pub enum CmpOp {
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub enum Value {
     LitInt(i32),
+    LitLong(i64),
     LitBool(bool),
     LitNullPtr(Option<Type>),
+    LitZeroStruct(Type), // all fields zero (or null)
     Register(RegNum, Type),
     GlobalRegister(String, Type),
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone)]
-pub enum Type {
-    Void,
-    Int,
-    Bool,
-    Char,
-    Ptr(Box<Type>),
-    Class(String),
-    Func(Box<Type>, Vec<Type>),
-}
-
 impl Value {
     pub fn get_type(&self) -> Type {
         match self {
             Value::LitInt(_) => Type::Int,
+            Value::LitLong(_) => Type::Long,
             Value::LitBool(_) => Type::Bool,
-            Value::LitNullPtr(Some(t)) => t.clone(),
-            Value::LitNullPtr(None) => Type::Ptr(Box::new(Type::Char)), // void* is illegal in llvm
-            Value::Register(_, t) | Value::GlobalRegister(_, t) => t.clone(),
+            Value::LitNullPtr(Some(t)) => *t,
+            Value::LitNullPtr(None) => Type::ptr(Type::Char), // void* is illegal in llvm
+            Value::LitZeroStruct(t) => *t,
+            Value::Register(_, t) | Value::GlobalRegister(_, t) => *t,
+        }
+    }
+
+    // null takes the type of its context (the other operand of a comparison,
+    // the phi function, the variable), other values are unchanged
+    pub fn with_null_type(self, t: Type) -> Value {
+        match self {
+            Value::LitNullPtr(_) => Value::LitNullPtr(Some(t)),
+            _ => self,
+        }
+    }
+}
+
+impl Operation {
+    pub fn get_defined_reg_mut(&mut self) -> Option<&mut RegNum> {
+        use self::Operation::*;
+        match self {
+            FunctionCall(Some(reg_num), _, _, _)
+            | Arithmetic(reg_num, _, _, _)
+            | Compare(reg_num, _, _, _)
+            | Not(reg_num, _)
+            | Select(reg_num, _, _, _)
+            | GetElementPtr(reg_num, _, _)
+            | CastGlobalString(reg_num, _, _)
+            | CastPtr { dst: reg_num, .. }
+            | CastPtrToInt { dst: reg_num, .. }
+            | CastCharToInt { dst: reg_num, .. }
+            | ExtractValue { dst: reg_num, .. }
+            | InsertValue { dst: reg_num, .. }
+            | Load(reg_num, _)
+            | Alloca(reg_num, _, _) => Some(reg_num),
+            FunctionCall(None, _, _, _) | Store(_, _) | MemZero(_, _) | SourceSpan(_) => None,
+        }
+    }
+
+    pub fn get_defined_reg(&self) -> Option<RegNum> {
+        use self::Operation::*;
+        match self {
+            FunctionCall(Some(reg_num), _, _, _)
+            | Arithmetic(reg_num, _, _, _)
+            | Compare(reg_num, _, _, _)
+            | Not(reg_num, _)
+            | Select(reg_num, _, _, _)
+            | GetElementPtr(reg_num, _, _)
+            | CastGlobalString(reg_num, _, _)
+            | CastPtr { dst: reg_num, .. }
+            | CastPtrToInt { dst: reg_num, .. }
+            | CastCharToInt { dst: reg_num, .. }
+            | ExtractValue { dst: reg_num, .. }
+            | InsertValue { dst: reg_num, .. }
+            | Load(reg_num, _)
+            | Alloca(reg_num, _, _) => Some(*reg_num),
+            FunctionCall(None, _, _, _) | Store(_, _) | MemZero(_, _) | SourceSpan(_) => None,
+        }
+    }
+
+    // values read by the operation
+    pub fn get_used_values_mut(&mut self) -> Vec<&mut Value> {
+        use self::Operation::*;
+        match self {
+            FunctionCall(_, _, fun_val, args) => {
+                let mut vals = vec![fun_val];
+                vals.extend(args.iter_mut());
+                vals
+            }
+            Arithmetic(_, _, val1, val2)
+            | Compare(_, _, val1, val2)
+            | Store(val1, val2)
+            | MemZero(val1, val2)
+            | InsertValue {
+                src_value: val1,
+                field_value: val2,
+                ..
+            } => vec![val1, val2],
+            Select(_, cond, val1, val2) => vec![cond, val1, val2],
+            GetElementPtr(_, _, vals) => vals.iter_mut().collect(),
+            CastGlobalString(_, _, val)
+            | CastPtr { src_value: val, .. }
+            | CastPtrToInt { src_value: val, .. }
+            | CastCharToInt { src_value: val, .. }
+            | ExtractValue { src_value: val, .. }
+            | Not(_, val)
+            | Load(_, val) => vec![val],
+            Alloca(_, _, _) | SourceSpan(_) => vec![],
+        }
+    }
+
+    pub fn get_used_values(&self) -> Vec<&Value> {
+        use self::Operation::*;
+        match self {
+            FunctionCall(_, _, fun_val, args) => {
+                let mut vals = vec![fun_val];
+                vals.extend(args.iter());
+                vals
+            }
+            Arithmetic(_, _, val1, val2)
+            | Compare(_, _, val1, val2)
+            | Store(val1, val2)
+            | MemZero(val1, val2)
+            | InsertValue {
+                src_value: val1,
+                field_value: val2,
+                ..
+            } => vec![val1, val2],
+            Select(_, cond, val1, val2) => vec![cond, val1, val2],
+            GetElementPtr(_, _, vals) => vals.iter().collect(),
+            CastGlobalString(_, _, val)
+            | CastPtr { src_value: val, .. }
+            | CastPtrToInt { src_value: val, .. }
+            | CastCharToInt { src_value: val, .. }
+            | ExtractValue { src_value: val, .. }
+            | Not(_, val)
+            | Load(_, val) => vec![val],
+            Alloca(_, _, _) | SourceSpan(_) => vec![],
+        }
+    }
+}
+
+impl Terminator {
+    // like Operation::get_used_values
+    pub fn get_used_values_mut(&mut self) -> Vec<&mut Value> {
+        match self {
+            Terminator::Return(opt_val) => opt_val.iter_mut().collect(),
+            Terminator::Branch2(val, _, _, _) => vec![val],
+            Terminator::Branch1(_) | Terminator::Unreachable => vec![],
+        }
+    }
+
+    pub fn get_used_values(&self) -> Vec<&Value> {
+        match self {
+            Terminator::Return(opt_val) => opt_val.iter().collect(),
+            Terminator::Branch2(val, _, _, _) => vec![val],
+            Terminator::Branch1(_) | Terminator::Unreachable => vec![],
+        }
+    }
+
+    pub fn get_successors(&self) -> Vec<Label> {
+        match self {
+            Terminator::Branch1(label) => vec![*label],
+            Terminator::Branch2(_, label1, label2, _) => vec![*label1, *label2],
+            Terminator::Return(_) | Terminator::Unreachable => vec![],
         }
     }
 }
 
+impl Block {
+    pub fn new(label: Label) -> Block {
+        Block {
+            label,
+            phis: vec![],
+            predecessors: vec![],
+            body: vec![],
+            terminator: Terminator::Unreachable,
+        }
+    }
+
+    pub fn get_successors(&self) -> Vec<Label> {
+        self.terminator.get_successors()
+    }
+
+    // values read by the operations of the body and by the terminator
+    // (not by the phis, they are read on the edges)
+    pub fn get_used_values(&self) -> impl Iterator<Item = &Value> {
+        self.body
+            .iter()
+            .flat_map(|op| op.get_used_values())
+            .chain(self.terminator.get_used_values())
+    }
+
+    pub fn get_used_values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.body
+            .iter_mut()
+            .flat_map(|op| op.get_used_values_mut())
+            .chain(self.terminator.get_used_values_mut())
+    }
+
+    pub fn add_phi(&mut self, phi: Phi) {
+        debug_assert!(self.get_phi(phi.reg_num).is_none());
+        self.phis.push(phi);
+    }
+
+    pub fn get_phi(&self, reg_num: RegNum) -> Option<&Phi> {
+        self.phis.iter().find(|phi| phi.reg_num == reg_num)
+    }
+
+    pub fn get_phi_mut(&mut self, reg_num: RegNum) -> Option<&mut Phi> {
+        self.phis.iter_mut().find(|phi| phi.reg_num == reg_num)
+    }
+
+    // the edge from the predecessor is gone
+    pub fn remove_predecessor(&mut self, pred: Label) {
+        self.predecessors.retain(|p| *p != pred);
+        for phi in &mut self.phis {
+            phi.remove_incoming(pred);
+        }
+    }
+
+    // the edge from the predecessor now starts in another block
+    pub fn replace_predecessor(&mut self, old: Label, new: Label) {
+        for p in &mut self.predecessors {
+            if *p == old {
+                *p = new;
+            }
+        }
+        for phi in &mut self.phis {
+            for (_, label) in &mut phi.incoming {
+                if *label == old {
+                    *label = new;
+                }
+            }
+        }
+    }
+}
+
+impl Phi {
+    pub fn new(reg_num: RegNum, reg_type: Type, incoming: Vec<(Value, Label)>) -> Phi {
+        Phi {
+            reg_num,
+            reg_type,
+            incoming,
+        }
+    }
+
+    pub fn add_incoming(&mut self, value: Value, pred: Label) {
+        self.incoming.push((value, pred));
+    }
+
+    pub fn remove_incoming(&mut self, pred: Label) {
+        self.incoming.retain(|(_, label)| *label != pred);
+    }
+
+    pub fn get_incoming(&self, pred: Label) -> Option<&Value> {
+        self.incoming
+            .iter()
+            .find(|(_, label)| *label == pred)
+            .map(|(value, _)| value)
+    }
+
+    pub fn replace_value(&mut self, pred: Label, new_value: Value) {
+        for (value, label) in &mut self.incoming {
+            if *label == pred {
+                *value = new_value.clone();
+            }
+        }
+    }
+
+    // like Operation::get_used_values
+    pub fn get_used_values(&self) -> Vec<&Value> {
+        self.incoming.iter().map(|(value, _)| value).collect()
+    }
+
+    pub fn get_used_values_mut(&mut self) -> Vec<&mut Value> {
+        self.incoming.iter_mut().map(|(value, _)| value).collect()
+    }
+}
+
+impl Function {
+    pub fn get_block_index(&self, label: Label) -> usize {
+        // blocks are sorted by labels until they are laid out,
+        // so usually the binary search finds it without going through all of them
+        match self.blocks.binary_search_by_key(&label.0, |bl| bl.label.0) {
+            Ok(index) => index,
+            _ => self
+                .blocks
+                .iter()
+                .position(|bl| bl.label == label)
+                .expect("block with given label exists"),
+        }
+    }
+
+    pub fn get_block(&self, label: Label) -> &Block {
+        let index = self.get_block_index(label);
+        &self.blocks[index]
+    }
+
+    pub fn get_block_mut(&mut self, label: Label) -> &mut Block {
+        let index = self.get_block_index(label);
+        &mut self.blocks[index]
+    }
+
+    // edits of the CFG, they keep the predecessors lists and the labels in
+    // the phis in sync with the terminators
+
+    // the terminator of `from` (set by the caller) jumps to `to` now,
+    // the phis of `to` need entries for it already
+    pub fn add_edge(&mut self, from: Label, to: Label) {
+        let bl = self.get_block_mut(to);
+        debug_assert!(bl.phis.iter().all(|phi| phi.get_incoming(from).is_some()));
+        if !bl.predecessors.contains(&from) {
+            bl.predecessors.push(from);
+        }
+    }
+
+    // the terminator of `from` (set by the caller, or the block removed)
+    // doesn't jump to `to` anymore, unless its other branch still does
+    pub fn remove_edge(&mut self, from: Label, to: Label) {
+        let still_jumps = self
+            .blocks
+            .iter()
+            .any(|bl| bl.label == from && bl.get_successors().contains(&to));
+        if !still_jumps {
+            self.get_block_mut(to).remove_predecessor(from);
+        }
+    }
+
+    // the terminator of `from` was moved to block `to` (e.g. the tail
+    // of a split block), its successors get the edges from `to`
+    pub fn move_edges(&mut self, terminator: &Terminator, from: Label, to: Label) {
+        for succ in terminator.get_successors() {
+            self.get_block_mut(succ).replace_predecessor(from, to);
+        }
+    }
+
+    pub fn get_next_free_label(&self) -> Label {
+        let max_label = self.blocks.iter().map(|bl| bl.label.0).max();
+        Label(max_label.map_or(0, |max| max + 1))
+    }
+
+    pub fn get_next_free_reg_num(&self) -> RegNum {
+        let arg_regs = self.args.iter().map(|(reg_num, _)| reg_num.0);
+        let phi_regs = self
+            .blocks
+            .iter()
+            .flat_map(|bl| bl.phis.iter().map(|phi| phi.reg_num.0));
+        let op_regs = self
+            .blocks
+            .iter()
+            .flat_map(|bl| bl.body.iter().filter_map(|op| op.get_defined_reg()))
+            .map(|reg_num| reg_num.0);
+        let max_reg = arg_regs.chain(phi_regs).chain(op_regs).max();
+        RegNum(max_reg.map_or(0, |max| max + 1))
+    }
+}
+
 impl Type {
+    // size_t of the target, the runtime takes allocation sizes of this type
+    pub fn size_type(pointer_width: u32) -> Type {
+        match pointer_width {
+            32 => Type::Int,
+            _ => Type::Long,
+        }
+    }
+
     pub fn from_ast(ast_type: &ast::InnerType) -> Type {
         match ast_type {
             ast::InnerType::Int => Type::Int,
             ast::InnerType::Bool => Type::Bool,
-            ast::InnerType::String => Type::Ptr(Box::new(Type::Char)),
-            ast::InnerType::Array(subtype) => Type::Ptr(Box::new(Type::from_ast(&subtype))),
+            ast::InnerType::String => Type::ptr(Type::Char),
+            ast::InnerType::Array(subtype) => Type::ptr(Type::from_ast(subtype)),
             ast::InnerType::Class(name) => Type::from_class_name(&name),
-            ast::InnerType::Null => Type::Ptr(Box::new(Type::Char)),
+            ast::InnerType::Struct(name) => Type::intern(TypeKind::Struct(name.to_string())),
+            ast::InnerType::Null => Type::ptr(Type::Char),
             ast::InnerType::Void => Type::Void,
+            // instantiated or lowered by semantic analysis
+            ast::InnerType::GenericClass(..)
+            | ast::InnerType::TypeParam(_)
+            | ast::InnerType::Function(..) => unreachable!(),
         }
     }
 
     pub fn from_method_def(class_name: &str, fun_def: &ast::FunDef) -> Type {
-        Type::Ptr(Box::new(Type::Func(
-            Box::new(Type::from_ast(&fun_def.ret_type.inner)),
+        Type::ptr(Type::func(
+            Type::from_ast(&fun_def.ret_type.inner),
             vec![Type::from_class_name(class_name)]
                 .into_iter()
                 .chain(fun_def.args.iter().map(|(t, _)| Type::from_ast(&t.inner)))
                 .collect(),
-        )))
+        ))
     }
 
     pub fn from_function_desc(fun_desc: &FunDesc) -> Type {
-        Type::Ptr(Box::new(Type::Func(
-            Box::new(Type::from_ast(&fun_desc.ret_type.inner)),
+        Type::ptr(Type::func(
+            Type::from_ast(&fun_desc.ret_type.inner),
             fun_desc
                 .args_types
                 .iter()
                 .map(|t| Type::from_ast(&t.inner))
                 .collect(),
-        )))
+        ))
     }
 
     pub fn from_class_name(class_name: &str) -> Type {
-        Type::Ptr(Box::new(Type::Class(class_name.to_string())))
+        Type::ptr(Type::intern(TypeKind::Class(class_name.to_string())))
     }
 }
 
+// functions of the C runtime (without the methods of the builtin classes):
+// (name, return type, argument types)
+pub fn get_runtime_functions(size_type: &Type) -> Vec<(&'static str, Type, Vec<Type>)> {
+    let string = || Type::ptr(Type::Char);
+    let size = || *size_type;
+    vec![
+        ("printInt", Type::Void, vec![Type::Int]),
+        ("printString", Type::Void, vec![string()]),
+        ("printBoolean", Type::Void, vec![Type::Bool]),
+        ("printErr", Type::Void, vec![string()]),
+        ("error", Type::Void, vec![]),
+        ("flushOutput", Type::Void, vec![]),
+        ("readInt", Type::Int, vec![]),
+        ("readString", string(), vec![]),
+        ("readFile", string(), vec![string()]),
+        ("writeFile", Type::Void, vec![string(), string()]),
+        ("split", Type::ptr(string()), vec![string(), string()]),
+        ("join", string(), vec![Type::ptr(string()), string()]),
+        ("_bltn_string_concat", string(), vec![string(), string()]),
+        (
+            "_bltn_string_concat_n",
+            string(),
+            vec![Type::Int, Type::ptr(string())],
+        ),
+        ("_bltn_string_eq", Type::Bool, vec![string(), string()]),
+        ("_bltn_string_ne", Type::Bool, vec![string(), string()]),
+        ("_bltn_malloc", string(), vec![size()]),
+        ("_bltn_alloc_array", string(), vec![Type::Int, size()]),
+        ("_bltn_array_copy", string(), vec![string(), size()]),
+        ("_bltn_array_fill", Type::Void, vec![string(), size()]),
+        (
+            "_bltn_array_resize",
+            string(),
+            vec![string(), Type::Int, size()],
+        ),
+        ("_bltn_array_append", string(), vec![string(), size()]),
+        (
+            "_bltn_string_check_index",
+            Type::Void,
+            vec![string(), Type::Int],
+        ),
+        ("_bltn_stack_overflow_error", Type::Void, vec![]),
+        ("_bltn_null_dereference_error", Type::Void, vec![]),
+        ("_bltn_runtime_init", Type::Void, vec![]),
+        ("_bltn_runtime_shutdown", Type::Void, vec![]),
+        (
+            "_bltn_profile_init",
+            Type::Void,
+            vec![Type::Int, string(), string()],
+        ),
+        ("_bltn_profile_block", Type::Void, vec![Type::Int]),
+        (
+            "_bltn_profile_branch",
+            Type::Void,
+            vec![Type::Int, Type::Bool],
+        ),
+    ]
+}
+
 impl fmt::Display for Program {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (name, ret_type, arg_types) in get_runtime_functions(&self.size_type) {
+            let args: Vec<_> = arg_types.iter().map(|t| t.to_string()).collect();
+            let ret_type = ret_type.to_string();
+            let attrs = if ALLOCATING_FUNCTIONS.contains(&name) {
+                "noalias nonnull "
+            } else {
+                ""
+            };
+            let fun_attrs = if NORETURN_FUNCTIONS.contains(&name) {
+                " noreturn"
+            } else {
+                ""
+            };
+            writeln!(
+                f,
+                "declare {}{:<4} @{}({}){}",
+                attrs,
+                ret_type,
+                name,
+                args.join(", "),
+                fun_attrs
+            )?;
+        }
         write!(
             f,
-            r#"declare void @printInt(i32)
-declare void @printString(i8*)
-declare void @error()
-declare i32  @readInt()
-declare i8*  @readString()
-declare i8*  @_bltn_string_concat(i8*, i8*)
-declare i1   @_bltn_string_eq(i8*, i8*)
-declare i1   @_bltn_string_ne(i8*, i8*)
-declare i8*  @_bltn_malloc(i32)
-declare i8*  @_bltn_alloc_array(i32, i32)
+            r#"declare i8*  @llvm.frameaddress.p0i8(i32)
+@_bltn_stack_limit = external global i8*
+declare void @llvm.memset.p0i8.i32(i8*, i8, i32, i1)
+declare void @llvm.memset.p0i8.i64(i8*, i8, i64, i1)
 
 "#
         )?;
+        // the runtime is initialized before main and shut down at exit
+        for (list, fun_name) in &[
+            ("ctors", "_bltn_runtime_init"),
+            ("dtors", "_bltn_runtime_shutdown"),
+        ] {
+            writeln!(
+                f,
+                "@llvm.global_{} = appending global [1 x {{ i32, void ()*, i8* }}] \
+                 [{{ i32, void ()*, i8* }} {{ i32 65535, void ()* @{}, i8* null }}]",
+                list, fun_name
+            )?;
+        }
+        let used: Vec<_> = self
+            .functions
+            .iter()
+            .filter(|fun| fun.attributes.contains(&FunctionAttribute::Used))
+            .map(|fun| {
+                let fun_type = Type::ptr(Type::func(
+                    fun.ret_type,
+                    fun.args.iter().map(|(_, t)| *t).collect(),
+                ));
+                format!(
+                    "i8* bitcast ({} @{} to i8*)",
+                    fun_type,
+                    quote_name(&fun.name)
+                )
+            })
+            .collect();
+        if !used.is_empty() {
+            writeln!(
+                f,
+                "@llvm.used = appending global [{} x i8*] [{}], section \"llvm.metadata\"",
+                used.len(),
+                used.join(", ")
+            )?;
+        }
+        writeln!(f)?;
 
-        for (k, v) in self.global_strings.iter() {
+        for (k, v) in self.global_strings.iter_sorted() {
             writeln!(
                 f,
                 r#"@{} = private constant [{} x i8] c"{}\00""#,
-                format_global_string(*v),
+                format_global_string(v),
                 k.len() + 1,
                 k.replace("\\", "\\5C")
                     .replace("\"", "\\22")
@@ -184,6 +778,10 @@ declare i8*  @_bltn_alloc_array(i32, i32)
         }
         write!(f, "\n\n")?;
 
+        for st in &self.structs {
+            st.fmt(f)?;
+        }
+
         for cl in &self.classes {
             cl.fmt(f)?;
         }
@@ -192,13 +790,69 @@ declare i8*  @_bltn_alloc_array(i32, i32)
             fun.fmt(f)?;
         }
 
+        let has_hints =
+            self.functions
+                .iter()
+                .flat_map(|fun| &fun.blocks)
+                .any(|bl| match bl.terminator {
+                    Terminator::Branch2(_, _, _, hint) => hint != BranchHint::None,
+                    _ => false,
+                });
+        if has_hints {
+            let (hot, cold) = LIKELY_WEIGHTS;
+            writeln!(
+                f,
+                "!{} = !{{!\"branch_weights\", i32 {}, i32 {}}}",
+                LIKELY_METADATA, hot, cold
+            )?;
+            writeln!(
+                f,
+                "!{} = !{{!\"branch_weights\", i32 {}, i32 {}}}",
+                UNLIKELY_METADATA, cold, hot
+            )?;
+        }
+        writeln!(f, "!{} = !{{!\"Latte TBAA\"}}", TBAA_ROOT_METADATA)?;
+        for (name, type_metadata) in TBAA_TYPES {
+            writeln!(
+                f,
+                "!{} = !{{!\"{}\", !{}, i64 0}}",
+                type_metadata, name, TBAA_ROOT_METADATA
+            )?;
+            writeln!(
+                f,
+                "!{0} = !{{!{1}, !{1}, i64 0}}",
+                type_metadata + 1,
+                type_metadata
+            )?;
+        }
         Ok(())
     }
 }
 
+impl fmt::Display for Struct {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "%{} = type {{",
+            quote_name(&format_struct_name(&self.name))
+        )?;
+        for (i, f_type) in self.fields.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{}", f_type)?;
+        }
+        writeln!(f, "}}\n")
+    }
+}
+
 impl fmt::Display for Class {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "%{} = type {{", format_class_name(&self.name))?;
+        write!(
+            f,
+            "%{} = type {{",
+            quote_name(&format_class_name(&self.name))
+        )?;
         for (i, f_type) in self.fields.iter().enumerate() {
             if i > 0 {
                 write!(f, ", ")?;
@@ -207,7 +861,11 @@ impl fmt::Display for Class {
         }
         writeln!(f, "}}")?;
 
-        write!(f, "%{} = type {{", format_class_vtable_type(&self.name))?;
+        write!(
+            f,
+            "%{} = type {{",
+            quote_name(&format_class_vtable_type(&self.name))
+        )?;
         for (i, (f_type, _)) in self.vtable.iter().enumerate() {
             if i > 0 {
                 write!(f, ", ")?;
@@ -216,33 +874,281 @@ impl fmt::Display for Class {
         }
         writeln!(f, "}}")?;
 
+        if self.is_builtin {
+            for (f_type, f_name) in &self.vtable {
+                match f_type.kind() {
+                    TypeKind::Ptr(subtype) => match subtype.kind() {
+                        TypeKind::Func(ret_type, args_types) => {
+                            write!(f, "declare {} @{}(", ret_type, quote_name(f_name))?;
+                            for (i, arg_type) in args_types.iter().enumerate() {
+                                if i > 0 {
+                                    write!(f, ", ")?;
+                                }
+                                write!(f, "{}", arg_type)?;
+                            }
+                            writeln!(f, ")")?;
+                        }
+                        _ => unreachable!(),
+                    },
+                    _ => unreachable!(),
+                }
+            }
+        }
+        if !self.needs_vtable {
+            return writeln!(f);
+        }
         write!(
             f,
             "@{} = private global %{} {{\n    ",
-            format_class_vtable_data(&self.name),
-            format_class_vtable_type(&self.name)
+            quote_name(&format_class_vtable_data(&self.name)),
+            quote_name(&format_class_vtable_type(&self.name))
         )?;
         for (i, (f_type, f_name)) in self.vtable.iter().enumerate() {
             if i > 0 {
                 write!(f, ",\n    ")?;
             }
-            write!(f, "{} @{}", f_type, f_name)?;
+            write!(f, "{} @{}", f_type, quote_name(f_name))?;
         }
         writeln!(f, "\n}}\n")
     }
 }
 
+impl BranchHint {
+    // of the negated condition, i.e. with the targets swapped
+    pub fn negate(self) -> Self {
+        match self {
+            BranchHint::None => BranchHint::None,
+            BranchHint::Likely => BranchHint::Unlikely,
+            BranchHint::Unlikely => BranchHint::Likely,
+            BranchHint::Weights(t, f) => BranchHint::Weights(f, t),
+        }
+    }
+}
+
+impl GlobalStrings {
+    pub fn intern(&mut self, string: &str) -> GlobalStrId {
+        if let Some(id) = self.ids.get(string) {
+            return *id;
+        }
+        let id = self.get_new_id(string);
+        self.used_ids.insert(id);
+        self.ids.insert(string.to_string(), id);
+        id
+    }
+
+    // the id the string has or would get from intern
+    pub fn peek(&self, string: &str) -> GlobalStrId {
+        match self.ids.get(string) {
+            Some(id) => *id,
+            None => self.get_new_id(string),
+        }
+    }
+
+    fn get_new_id(&self, string: &str) -> GlobalStrId {
+        // FNV-1a, it's stable between compiler builds (unlike the std hasher)
+        let mut id = GlobalStrId(fnv1a_hash(string.as_bytes()));
+        while self.used_ids.contains(&id) {
+            id.0 = id.0.wrapping_add(1); // hash collision
+        }
+        id
+    }
+
+    // false if the string or the id is already used
+    pub fn insert_with_id(&mut self, string: &str, id: GlobalStrId) -> bool {
+        if self.ids.contains_key(string) || !self.used_ids.insert(id) {
+            return false;
+        }
+        self.ids.insert(string.to_string(), id);
+        true
+    }
+
+    pub fn get(&self, string: &str) -> Option<GlobalStrId> {
+        self.ids.get(string).cloned()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    // sorted by content
+    pub fn iter_sorted(&self) -> Vec<(&str, GlobalStrId)> {
+        let mut res: Vec<_> = self.ids.iter().map(|(s, id)| (s.as_str(), *id)).collect();
+        res.sort_by(|a, b| a.0.cmp(b.0));
+        res
+    }
+}
+
+impl Program {
+    // names of the runtime functions and of the functions with the attribute
+    pub fn get_noreturn_functions(&self) -> HashSet<String> {
+        let mut res: HashSet<_> = NORETURN_FUNCTIONS
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        res.extend(
+            self.functions
+                .iter()
+                .filter(|fun| fun.attributes.contains(&FunctionAttribute::NoReturn))
+                .map(|fun| fun.name.clone()),
+        );
+        res
+    }
+
+    // string literals with their names, one per line
+    pub fn format_global_strings(&self) -> String {
+        let mut res = String::new();
+        for (string, id) in self.global_strings.iter_sorted() {
+            res += &format!("{} {:?}\n", format_global_string(id), string);
+        }
+        res
+    }
+
+    // report of the closed world class hierarchy analysis
+    pub fn format_class_hierarchy(&self) -> String {
+        let mut classes: Vec<_> = self.classes.iter().filter(|cl| !cl.is_builtin).collect();
+        classes.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut res = String::new();
+        for cl in classes {
+            res += &format!("class {}", cl.name);
+            if let Some(parent) = &cl.parent {
+                res += &format!(" extends {}", parent);
+            }
+            let mut attrs = vec![];
+            if cl.is_final {
+                attrs.push("final");
+            }
+            if !cl.needs_vtable {
+                attrs.push("no vtable");
+            }
+            if !attrs.is_empty() {
+                res += &format!(" ({})", attrs.join(", "));
+            }
+            res += "\n";
+            for ((_, fun_name), overridden) in cl.vtable.iter().zip(&cl.overridden) {
+                let kind = if *overridden { "virtual" } else { "final" };
+                res += &format!("    method {} ({})\n", fun_name, kind);
+            }
+        }
+        res
+    }
+
+    // layout of objects and vtables: fields with their offsets, and vtable slots
+    // with the class defining the method and the class whose method it overrides
+    pub fn format_vtables(&self) -> String {
+        let classes: HashMap<_, _> = self.classes.iter().map(|cl| (&cl.name, cl)).collect();
+        let mut sorted: Vec<_> = self.classes.iter().filter(|cl| !cl.is_builtin).collect();
+        sorted.sort_by(|a, b| a.name.cmp(&b.name));
+        let mut res = String::new();
+        for cl in sorted {
+            let (offsets, size, _) = self.get_fields_layout(&cl.fields);
+            res += &format!("class {}", cl.name);
+            if let Some(parent) = &cl.parent {
+                res += &format!(" extends {}", parent);
+            }
+            res += &format!(" ({} bytes)\n", size);
+            for ((f_type, f_name), offset) in cl.fields.iter().zip(&cl.field_names).zip(offsets) {
+                res += &format!("    field {} at {}: {}\n", f_name, offset, f_type);
+            }
+            if !cl.needs_vtable {
+                res += "    vtable not emitted (no virtual calls)\n";
+            }
+            for (i, (_, fun_name)) in cl.vtable.iter().enumerate() {
+                let owner = get_slot_owner(&classes, cl, i);
+                res += &format!("    slot {}: {} from {}", i, fun_name, owner.name);
+                let overridden = owner.parent.as_ref().map(|p| classes[p]);
+                if let Some(parent) = overridden.filter(|p| p.vtable.len() > i) {
+                    res += &format!(", overrides {}", get_slot_owner(&classes, parent, i).name);
+                }
+                res += "\n";
+            }
+        }
+        res
+    }
+
+    // like the default data layout of LLVM: fields aligned to their size
+    pub fn get_size_and_align(&self, t: &Type) -> (i64, i64) {
+        match t.kind() {
+            TypeKind::Int => (4, 4),
+            TypeKind::Long => (8, 8),
+            TypeKind::Bool | TypeKind::Char => (1, 1),
+            TypeKind::Ptr(_) => match self.size_type {
+                Type::Long => (8, 8),
+                _ => (4, 4),
+            },
+            TypeKind::Class(name) => {
+                let cl = self.classes.iter().find(|cl| cl.name == *name).unwrap();
+                let (_, size, align) = self.get_fields_layout(&cl.fields);
+                (size, align)
+            }
+            TypeKind::Struct(name) => {
+                let st = self.structs.iter().find(|st| st.name == *name).unwrap();
+                let (_, size, align) = self.get_fields_layout(&st.fields);
+                (size, align)
+            }
+            TypeKind::Void | TypeKind::Func(..) => unreachable!(),
+        }
+    }
+
+    // offsets of the fields, size and alignment of the whole object; fields
+    // of class types are pointers, so classes referring to each other (also
+    // in cycles) don't need each other's layouts, only structs are nested
+    // (and the semantic analysis rejects structs containing themselves)
+    pub fn get_fields_layout(&self, fields: &[Type]) -> (Vec<i64>, i64, i64) {
+        let (mut offsets, mut size, mut align) = (vec![], 0, 1);
+        for f in fields {
+            let (f_size, f_align) = self.get_size_and_align(f);
+            let offset = (size + f_align - 1) / f_align * f_align;
+            offsets.push(offset);
+            size = offset + f_size;
+            align = align.max(f_align);
+        }
+        (offsets, (size + align - 1) / align * align, align)
+    }
+}
+
 impl fmt::Display for Function {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let priv_str = if self.name == "main" { "" } else { "private " };
-        write!(f, "define {}{} @{}(", priv_str, self.ret_type, self.name)?;
+        let linkage = if self.attributes.contains(&FunctionAttribute::Internal) {
+            "internal "
+        } else {
+            ""
+        };
+        write!(
+            f,
+            "define {}{} @{}(",
+            linkage,
+            self.ret_type,
+            quote_name(&self.name)
+        )?;
         for (i, (reg_num, arg_type)) in self.args.iter().enumerate() {
             if i > 0 {
                 write!(f, ", ")?;
             }
             write!(f, "{} %.r{}", arg_type, reg_num.0)?;
         }
-        writeln!(f, ") {{")?;
+        write!(f, ")")?;
+        match self.inline_hint {
+            ast::InlineHint::Always => write!(f, " alwaysinline")?,
+            ast::InlineHint::Never => write!(f, " noinline")?,
+            ast::InlineHint::Auto => (),
+        }
+        if self.never_recurses {
+            write!(f, " norecurse")?;
+        }
+        if self.attributes.contains(&FunctionAttribute::NoReturn) {
+            write!(f, " noreturn")?;
+        }
+        write!(f, " {{")?;
+        match self.purity {
+            Purity::Impure => (),
+            Purity::ReadOnly => write!(f, "  ; readonly")?,
+            Purity::Pure => write!(f, "  ; pure")?,
+        }
+        writeln!(f)?;
 
         for bl in &self.blocks {
             bl.fmt(f)?;
@@ -265,9 +1171,9 @@ impl fmt::Display for Block {
         }
         writeln!(f)?;
 
-        for (reg_num, reg_type, vals) in &self.phi_set {
-            write!(f, "    %.r{} = phi {} ", reg_num.0, reg_type)?;
-            for (i, (value, label)) in vals.iter().enumerate() {
+        for phi in &self.phis {
+            write!(f, "    %.r{} = phi {} ", phi.reg_num.0, phi.reg_type)?;
+            for (i, (value, label)) in phi.incoming.iter().enumerate() {
                 if i > 0 {
                     write!(f, ", ")?;
                 }
@@ -277,10 +1183,11 @@ impl fmt::Display for Block {
         }
 
         for op in &self.body {
-            writeln!(f, "    {}", op)?;
+            if f.alternate() || !matches!(op, Operation::SourceSpan(_)) {
+                writeln!(f, "    {}", op)?;
+            }
         }
-
-        Ok(())
+        writeln!(f, "    {}", self.terminator)
     }
 }
 
@@ -288,10 +1195,6 @@ impl fmt::Display for Operation {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use self::Operation::*;
         match self {
-            Return(opt_val) => match opt_val {
-                Some(val) => write!(f, "ret {} {}", val.get_type(), val)?,
-                None => write!(f, "ret void")?,
-            },
             FunctionCall(opt_reg_num, ret_type, fun_name, args) => {
                 match opt_reg_num {
                     Some(reg_num) => write!(f, "%.r{} = ", reg_num.0)?,
@@ -315,6 +1218,8 @@ impl fmt::Display for Operation {
                     Mul => "mul",
                     Div => "sdiv",
                     Mod => "srem",
+                    And => "and",
+                    Or => "or",
                 };
                 write!(
                     f,
@@ -346,6 +1251,18 @@ impl fmt::Display for Operation {
                     reg_num.0, op_str, val_type, val1, val2
                 )?;
             }
+            Not(reg_num, val) => write!(f, "%.r{} = xor i1 {}, true", reg_num.0, val)?,
+            Select(reg_num, cond, val1, val2) => {
+                let val_type = match val1 {
+                    Value::LitNullPtr(_) => val2.get_type(),
+                    _ => val1.get_type(),
+                };
+                write!(
+                    f,
+                    "%.r{} = select i1 {}, {2} {3}, {2} {4}",
+                    reg_num.0, cond, val_type, val1, val2
+                )?;
+            }
             GetElementPtr(reg_num, elem_type, vals) => {
                 write!(f, "%.r{} = getelementptr {}", reg_num.0, elem_type)?;
                 for val in vals {
@@ -364,36 +1281,84 @@ impl fmt::Display for Operation {
                 dst_type,
                 src_value,
             } => {
-                let (val_reg, val_type) = match src_value {
-                    Value::Register(val_reg, val_type) => (val_reg, val_type),
-                    _ => unreachable!(),
-                };
                 write!(
                     f,
-                    "%.r{} = bitcast {} %.r{} to {}",
-                    dst.0, val_type, val_reg.0, dst_type
+                    "%.r{} = bitcast {} {} to {}",
+                    dst.0,
+                    src_value.get_type(),
+                    src_value,
+                    dst_type
                 )?;
             }
-            CastPtrToInt { dst, src_value } => {
+            CastPtrToInt {
+                dst,
+                dst_type,
+                src_value,
+            } => {
                 write!(
                     f,
                     "%.r{} = ptrtoint {} {} to {}",
                     dst.0,
                     src_value.get_type(),
                     src_value,
+                    dst_type,
+                )?;
+            }
+            CastCharToInt { dst, src_value } => {
+                write!(
+                    f,
+                    "%.r{} = zext {} {} to {}",
+                    dst.0,
+                    src_value.get_type(),
+                    src_value,
                     Type::Int,
                 )?;
             }
+            ExtractValue {
+                dst,
+                src_value,
+                indices,
+            } => {
+                write!(
+                    f,
+                    "%.r{} = extractvalue {} {}",
+                    dst.0,
+                    src_value.get_type(),
+                    src_value
+                )?;
+                for idx in indices {
+                    write!(f, ", {}", idx)?;
+                }
+            }
+            InsertValue {
+                dst,
+                src_value,
+                field_value,
+                indices,
+            } => {
+                write!(
+                    f,
+                    "%.r{} = insertvalue {} {}, {} {}",
+                    dst.0,
+                    src_value.get_type(),
+                    src_value,
+                    field_value.get_type(),
+                    field_value
+                )?;
+                for idx in indices {
+                    write!(f, ", {}", idx)?;
+                }
+            }
             Load(reg_num, value) => {
-                let (val_reg, elem_type) = match value {
-                    Value::Register(val_reg, Type::Ptr(subtype)) => (val_reg, subtype),
-                    _ => unreachable!(),
-                };
+                let elem_type = value.get_type().pointee().unwrap();
                 write!(
                     f,
-                    "%.r{0} = load {1}, {1}* %.r{2}",
-                    reg_num.0, elem_type, val_reg.0
+                    "%.r{0} = load {1}, {1}* {2}",
+                    reg_num.0, elem_type, value
                 )?;
+                if let Some(tbaa) = get_tbaa_metadata(&elem_type) {
+                    write!(f, ", !tbaa !{}", tbaa)?;
+                }
             }
             Store(target_val, ref_val) => {
                 write!(
@@ -404,17 +1369,62 @@ impl fmt::Display for Operation {
                     ref_val.get_type(),
                     ref_val
                 )?;
+                if let Some(tbaa) = get_tbaa_metadata(&target_val.get_type()) {
+                    write!(f, ", !tbaa !{}", tbaa)?;
+                }
+            }
+            Alloca(reg_num, elem_type, cnt) => {
+                write!(
+                    f,
+                    "%.r{} = alloca {}, i32 {}, align 8",
+                    reg_num.0, elem_type, cnt
+                )?;
             }
+            MemZero(ptr_val, size_val) => {
+                write!(
+                    f,
+                    "call void @llvm.memset.p0i8.{0}(i8* {1}, i8 0, {0} {2}, i1 false)",
+                    size_val.get_type(),
+                    ptr_val,
+                    size_val
+                )?;
+            }
+            SourceSpan((begin, end)) => {
+                write!(f, "; source {}-{}", begin, end)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Terminator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Terminator::*;
+        match self {
+            Return(opt_val) => match opt_val {
+                Some(val) => write!(f, "ret {} {}", val.get_type(), val)?,
+                None => write!(f, "ret void")?,
+            },
             Branch1(label) => {
                 write!(f, "br label %.L{}", label.0)?;
             }
-            Branch2(value, label1, label2) => {
+            Branch2(value, label1, label2, hint) => {
                 write!(
                     f,
                     "br i1 {}, label %.L{}, label %.L{}",
                     value, label1.0, label2.0
                 )?;
+                match hint {
+                    BranchHint::None => (),
+                    BranchHint::Likely => write!(f, ", !prof !{}", LIKELY_METADATA)?,
+                    BranchHint::Unlikely => write!(f, ", !prof !{}", UNLIKELY_METADATA)?,
+                    BranchHint::Weights(t, w) => {
+                        write!(f, ", !prof !{{!\"branch_weights\", i32 {}, i32 {}}}", t, w)?
+                    }
+                }
             }
+            Unreachable => write!(f, "unreachable")?,
         }
 
         Ok(())
@@ -426,24 +1436,28 @@ impl fmt::Display for Value {
         use self::Value::*;
         match self {
             LitInt(val) => val.fmt(f),
+            LitLong(val) => val.fmt(f),
             LitBool(val) => (*val as i32).fmt(f),
             LitNullPtr(_) => "null".fmt(f),
+            LitZeroStruct(_) => "zeroinitializer".fmt(f),
             Register(reg_num, _) => write!(f, "%.r{}", reg_num.0),
-            GlobalRegister(reg_name, _) => write!(f, "@{}", reg_name),
+            GlobalRegister(reg_name, _) => write!(f, "@{}", quote_name(reg_name)),
         }
     }
 }
 
 impl fmt::Display for Type {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        use self::Type::*;
-        match self {
+        use self::TypeKind::*;
+        match self.kind() {
             Void => write!(f, "void"),
             Int => write!(f, "i32"),
+            Long => write!(f, "i64"),
             Bool => write!(f, "i1"),
             Char => write!(f, "i8"),
             Ptr(subtype) => write!(f, "{}*", subtype),
-            Class(name) => write!(f, "%{}", format_class_name(name)),
+            Class(name) => write!(f, "%{}", quote_name(&format_class_name(name))),
+            Struct(name) => write!(f, "%{}", quote_name(&format_struct_name(name))),
             Func(ret_t, args_ts) => {
                 write!(f, "{}(", ret_t)?;
                 for (i, t) in args_ts.iter().enumerate() {
@@ -458,21 +1472,72 @@ impl fmt::Display for Type {
     }
 }
 
-pub fn format_global_string(no: GlobalStrNum) -> String {
-    format!(".str.{}", no.0)
+// names of instances of generic classes and functions, like "Box<int>",
+// contain characters which are not allowed in LLVM identifiers
+fn quote_name(name: &str) -> String {
+    let is_plain = |c: char| c.is_ascii_alphanumeric() || "-$._".contains(c);
+    if name.chars().all(is_plain) {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name)
+    }
+}
+
+pub fn fnv1a_hash(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for b in bytes {
+        hash ^= u64::from(*b);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+pub fn format_global_string(id: GlobalStrId) -> String {
+    format!(".str.{:016x}", id.0)
+}
+
+// the highest ancestor (or the class itself) with the same function in the vtable slot
+fn get_slot_owner<'a>(
+    classes: &HashMap<&String, &'a Class>,
+    cl: &'a Class,
+    slot: usize,
+) -> &'a Class {
+    let mut owner = cl;
+    while let Some(parent) = owner.parent.as_ref().map(|p| classes[p]) {
+        if parent.vtable.len() <= slot || parent.vtable[slot].1 != cl.vtable[slot].1 {
+            break;
+        }
+        owner = parent;
+    }
+    owner
+}
+
+// nested classes are named `Outer.Inner` in the source, in the IR the nesting
+// is marked with `$`, so the names can't clash with the names derived from
+// the enclosing class, e.g. `Outer.Inner.f` can be a nested function of
+// its method `Inner`
+pub fn encode_nested_class_name(name: &str) -> String {
+    name.replace('.', "$")
 }
 
 pub fn format_class_name(name: &str) -> String {
     format!("cls.{}", name)
 }
 
+pub fn format_struct_name(name: &str) -> String {
+    format!("struct.{}", name)
+}
+
 pub fn format_class_vtable_type(name: &str) -> String {
     format!("cls.{}.vtable.type", name)
 }
 
 pub fn get_class_vtable_type(name: &str) -> Type {
     // note it'll get cls. prefix when using format_class_name
-    Type::Ptr(Box::new(Type::Class(format!("{}.vtable.type", name))))
+    Type::ptr(Type::intern(TypeKind::Class(format!(
+        "{}.vtable.type",
+        name
+    ))))
 }
 
 pub fn format_class_vtable_data(name: &str) -> String {
@@ -0,0 +1,1174 @@
+// Encoding of the IR as a tree of integers, strings and lists, written as
+// an s-expression, e.g. `(load 3 (reg 2 (ptr int)))`, as JSON (lists are
+// arrays) or in a compact binary form; used by the cache of generated
+// functions (codegen::cache) and for whole programs (--emit=ir-json,
+// --emit=ir-binary, ir-dis). Variants without fields are plain strings
+// (`int`), the other ones are lists starting with their tag.
+
+use model::ast::InlineHint;
+use model::ir::*;
+use std::char;
+use std::collections::BTreeSet;
+use std::convert::TryFrom;
+use std::iter::Peekable;
+use std::str::Chars;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Node {
+    Int(i64),
+    Str(String),
+    List(Vec<Node>),
+}
+
+pub type DecodeResult<T> = Result<T, String>;
+
+pub trait Encode: Sized {
+    fn encode(&self) -> Node;
+    fn decode(node: &Node) -> DecodeResult<Self>;
+}
+
+impl Node {
+    pub fn to_text(&self) -> String {
+        let mut res = String::new();
+        self.write_text(&mut res);
+        res
+    }
+
+    fn write_text(&self, out: &mut String) {
+        match self {
+            Node::Int(n) => *out += &n.to_string(),
+            // tags and most names are written without quotes
+            Node::Str(s) if is_word(s) => *out += s,
+            Node::Str(s) => {
+                out.push('"');
+                for c in s.chars() {
+                    if c == '"' || c == '\\' {
+                        out.push('\\');
+                    }
+                    out.push(c);
+                }
+                out.push('"');
+            }
+            Node::List(items) => {
+                out.push('(');
+                for (i, it) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(' ');
+                    }
+                    it.write_text(out);
+                }
+                out.push(')');
+            }
+        }
+    }
+
+    pub fn from_text(text: &str) -> DecodeResult<Node> {
+        let mut chars = text.chars().peekable();
+        let node = parse_node(&mut chars)?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(node),
+            Some(c) => Err(format!("unexpected '{}' after the end", c)),
+        }
+    }
+}
+
+fn is_word(s: &str) -> bool {
+    let mut chars = s.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic())
+        && chars.all(|c| c.is_ascii_alphanumeric())
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while matches!(chars.peek(), Some(c) if c.is_whitespace()) {
+        chars.next();
+    }
+}
+
+fn parse_node(chars: &mut Peekable<Chars>) -> DecodeResult<Node> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some('(') => {
+            let mut items = vec![];
+            loop {
+                skip_whitespace(chars);
+                if chars.peek() == Some(&')') {
+                    chars.next();
+                    return Ok(Node::List(items));
+                }
+                items.push(parse_node(chars)?);
+            }
+        }
+        Some('"') => {
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(Node::Str(s)),
+                    Some('\\') => s.extend(chars.next()),
+                    Some(c) => s.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+        }
+        Some(c) if c == '-' || c.is_ascii_digit() => {
+            let mut digits = c.to_string();
+            while let Some(c) = chars.peek().cloned().filter(char::is_ascii_digit) {
+                digits.push(c);
+                chars.next();
+            }
+            digits
+                .parse()
+                .map(Node::Int)
+                .map_err(|_| format!("invalid number {}", digits))
+        }
+        Some(c) if c.is_ascii_alphabetic() => {
+            let mut tag = c.to_string();
+            while let Some(c) = chars.peek().cloned().filter(char::is_ascii_alphanumeric) {
+                tag.push(c);
+                chars.next();
+            }
+            Ok(Node::Str(tag))
+        }
+        Some(c) => Err(format!("unexpected '{}'", c)),
+        None => Err("unexpected end".to_string()),
+    }
+}
+
+// --------------------------------------------------------
+// ----------------- JSON ---------------------------------
+// --------------------------------------------------------
+
+impl Node {
+    pub fn to_json(&self) -> String {
+        let mut res = String::new();
+        self.write_json(&mut res);
+        res
+    }
+
+    fn write_json(&self, out: &mut String) {
+        match self {
+            Node::Int(n) => *out += &n.to_string(),
+            Node::Str(s) => {
+                out.push('"');
+                for c in s.chars() {
+                    match c {
+                        '"' => *out += "\\\"",
+                        '\\' => *out += "\\\\",
+                        '\n' => *out += "\\n",
+                        '\t' => *out += "\\t",
+                        c if (c as u32) < 0x20 => *out += &format!("\\u{:04x}", c as u32),
+                        c => out.push(c),
+                    }
+                }
+                out.push('"');
+            }
+            Node::List(items) => {
+                out.push('[');
+                for (i, it) in items.iter().enumerate() {
+                    if i > 0 {
+                        out.push(',');
+                    }
+                    it.write_json(out);
+                }
+                out.push(']');
+            }
+        }
+    }
+
+    // only the subset written by to_json: integers, strings and arrays
+    pub fn from_json(text: &str) -> DecodeResult<Node> {
+        let mut chars = text.chars().peekable();
+        let node = parse_json(&mut chars)?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(node),
+            Some(c) => Err(format!("unexpected '{}' after the end", c)),
+        }
+    }
+}
+
+fn parse_json(chars: &mut Peekable<Chars>) -> DecodeResult<Node> {
+    skip_whitespace(chars);
+    match chars.next() {
+        Some('[') => {
+            let mut items = vec![];
+            skip_whitespace(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Node::List(items));
+            }
+            loop {
+                items.push(parse_json(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => (),
+                    Some(']') => return Ok(Node::List(items)),
+                    _ => return Err("expected ',' or ']'".to_string()),
+                }
+            }
+        }
+        Some('"') => {
+            let mut s = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => return Ok(Node::Str(s)),
+                    Some('\\') => s.push(parse_json_escape(chars)?),
+                    Some(c) => s.push(c),
+                    None => return Err("unterminated string".to_string()),
+                }
+            }
+        }
+        Some(c) if c == '-' || c.is_ascii_digit() => {
+            let mut digits = c.to_string();
+            while let Some(c) = chars.peek().cloned().filter(char::is_ascii_digit) {
+                digits.push(c);
+                chars.next();
+            }
+            digits
+                .parse()
+                .map(Node::Int)
+                .map_err(|_| format!("invalid integer {}", digits))
+        }
+        Some(c) => Err(format!("unexpected '{}'", c)),
+        None => Err("unexpected end".to_string()),
+    }
+}
+
+fn parse_json_escape(chars: &mut Peekable<Chars>) -> DecodeResult<char> {
+    Ok(match chars.next() {
+        Some('"') => '"',
+        Some('\\') => '\\',
+        Some('/') => '/',
+        Some('b') => '\u{8}',
+        Some('f') => '\u{c}',
+        Some('n') => '\n',
+        Some('r') => '\r',
+        Some('t') => '\t',
+        Some('u') => {
+            let high = parse_json_hex(chars)?;
+            let code = if (0xd800..0xdc00).contains(&high) {
+                // a surrogate pair
+                let low = match (chars.next(), chars.next()) {
+                    (Some('\\'), Some('u')) => parse_json_hex(chars)?,
+                    _ => return Err("unpaired surrogate".to_string()),
+                };
+                if !(0xdc00..0xe000).contains(&low) {
+                    return Err("unpaired surrogate".to_string());
+                }
+                0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00)
+            } else {
+                high
+            };
+            return char::from_u32(code).ok_or_else(|| format!("invalid character {:x}", code));
+        }
+        _ => return Err("invalid escape sequence".to_string()),
+    })
+}
+
+fn parse_json_hex(chars: &mut Peekable<Chars>) -> DecodeResult<u32> {
+    let digits: String = chars.take(4).collect();
+    match u32::from_str_radix(&digits, 16) {
+        Ok(n) if digits.len() == 4 => Ok(n),
+        _ => Err(format!("invalid escape \\u{}", digits)),
+    }
+}
+
+// --------------------------------------------------------
+// ----------------- binary -------------------------------
+// --------------------------------------------------------
+
+// a node is its kind (0 - integer, 1 - string, 2 - list) followed by
+// the integer (zigzag LEB128), the length and UTF-8 bytes of the string
+// or the length and items of the list
+const BINARY_INT: u8 = 0;
+const BINARY_STR: u8 = 1;
+const BINARY_LIST: u8 = 2;
+
+impl Node {
+    pub fn to_binary(&self) -> Vec<u8> {
+        let mut res = vec![];
+        self.write_binary(&mut res);
+        res
+    }
+
+    fn write_binary(&self, out: &mut Vec<u8>) {
+        match self {
+            Node::Int(n) => {
+                out.push(BINARY_INT);
+                write_varint(out, ((n << 1) ^ (n >> 63)) as u64);
+            }
+            Node::Str(s) => {
+                out.push(BINARY_STR);
+                write_varint(out, s.len() as u64);
+                out.extend_from_slice(s.as_bytes());
+            }
+            Node::List(items) => {
+                out.push(BINARY_LIST);
+                write_varint(out, items.len() as u64);
+                for it in items {
+                    it.write_binary(out);
+                }
+            }
+        }
+    }
+
+    pub fn from_binary(bytes: &[u8]) -> DecodeResult<Node> {
+        let mut rest = bytes;
+        let node = read_binary(&mut rest)?;
+        if rest.is_empty() {
+            Ok(node)
+        } else {
+            Err(format!("{} bytes after the end", rest.len()))
+        }
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> DecodeResult<u64> {
+    let mut res = 0;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first().ok_or("unexpected end")?;
+        *bytes = rest;
+        res |= u64::from(byte & 0x7f) << shift;
+        if byte < 0x80 {
+            return Ok(res);
+        }
+    }
+    Err("too long integer".to_string())
+}
+
+fn read_binary(bytes: &mut &[u8]) -> DecodeResult<Node> {
+    let (&kind, rest) = bytes.split_first().ok_or("unexpected end")?;
+    *bytes = rest;
+    match kind {
+        BINARY_INT => {
+            let n = read_varint(bytes)?;
+            Ok(Node::Int((n >> 1) as i64 ^ -((n & 1) as i64)))
+        }
+        BINARY_STR => {
+            let len = read_varint(bytes)? as usize;
+            if len > bytes.len() {
+                return Err("unexpected end".to_string());
+            }
+            let (s, rest) = bytes.split_at(len);
+            *bytes = rest;
+            String::from_utf8(s.to_vec())
+                .map(Node::Str)
+                .map_err(|_| "invalid UTF-8 string".to_string())
+        }
+        BINARY_LIST => {
+            let len = read_varint(bytes)?;
+            // every item takes at least two bytes
+            if len > bytes.len() as u64 {
+                return Err("unexpected end".to_string());
+            }
+            (0..len)
+                .map(|_| read_binary(bytes))
+                .collect::<DecodeResult<_>>()
+                .map(Node::List)
+        }
+        _ => Err(format!("unknown node kind {}", kind)),
+    }
+}
+
+// --------------------------------------------------------
+// ----------------- helpers ------------------------------
+// --------------------------------------------------------
+
+fn tagged(tag: &str, fields: Vec<Node>) -> Node {
+    if fields.is_empty() {
+        Node::Str(tag.to_string())
+    } else {
+        let mut items = vec![Node::Str(tag.to_string())];
+        items.extend(fields);
+        Node::List(items)
+    }
+}
+
+fn get_tagged(node: &Node) -> DecodeResult<(&str, &[Node])> {
+    match node {
+        Node::Str(tag) => Ok((tag, &[])),
+        Node::List(items) => match items.split_first() {
+            Some((Node::Str(tag), fields)) => Ok((tag, fields)),
+            _ => Err("expected a tagged list".to_string()),
+        },
+        Node::Int(_) => Err("expected a tag".to_string()),
+    }
+}
+
+fn get<T: Encode>(fields: &[Node], i: usize) -> DecodeResult<T> {
+    match fields.get(i) {
+        Some(node) => T::decode(node),
+        None => Err(format!("missing field {}", i)),
+    }
+}
+
+fn unknown_tag<T>(what: &str, tag: &str) -> DecodeResult<T> {
+    Err(format!("unknown {} {}", what, tag))
+}
+
+macro_rules! encode_int {
+    ($($t:ty),*) => {$(
+        impl Encode for $t {
+            fn encode(&self) -> Node {
+                Node::Int(*self as i64)
+            }
+
+            fn decode(node: &Node) -> DecodeResult<Self> {
+                match node {
+                    Node::Int(n) => <$t>::try_from(*n).map_err(|_| format!("{} out of range", n)),
+                    _ => Err("expected an integer".to_string()),
+                }
+            }
+        }
+    )*};
+}
+
+encode_int!(i32, u32, usize);
+
+impl Encode for i64 {
+    fn encode(&self) -> Node {
+        Node::Int(*self)
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        match node {
+            Node::Int(n) => Ok(*n),
+            _ => Err("expected an integer".to_string()),
+        }
+    }
+}
+
+// e.g. hashes, stored as their bits
+impl Encode for u64 {
+    fn encode(&self) -> Node {
+        Node::Int(*self as i64)
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        i64::decode(node).map(|n| n as u64)
+    }
+}
+
+impl Encode for bool {
+    fn encode(&self) -> Node {
+        Node::Int(i64::from(*self))
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        match node {
+            Node::Int(0) => Ok(false),
+            Node::Int(1) => Ok(true),
+            _ => Err("expected 0 or 1".to_string()),
+        }
+    }
+}
+
+impl Encode for String {
+    fn encode(&self) -> Node {
+        Node::Str(self.clone())
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        match node {
+            Node::Str(s) => Ok(s.clone()),
+            _ => Err("expected a string".to_string()),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self) -> Node {
+        Node::List(self.iter().map(Encode::encode).collect())
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        match node {
+            Node::List(items) => items.iter().map(T::decode).collect(),
+            _ => Err("expected a list".to_string()),
+        }
+    }
+}
+
+impl<T: Encode + Ord> Encode for BTreeSet<T> {
+    fn encode(&self) -> Node {
+        Node::List(self.iter().map(Encode::encode).collect())
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        match node {
+            Node::List(items) => items.iter().map(T::decode).collect(),
+            _ => Err("expected a list".to_string()),
+        }
+    }
+}
+
+// a list with at most one element
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self) -> Node {
+        Node::List(self.iter().map(Encode::encode).collect())
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let mut items = Vec::<T>::decode(node)?;
+        match items.len() {
+            0 | 1 => Ok(items.pop()),
+            _ => Err("expected at most one element".to_string()),
+        }
+    }
+}
+
+impl<T: Encode> Encode for Box<T> {
+    fn encode(&self) -> Node {
+        self.as_ref().encode()
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        T::decode(node).map(Box::new)
+    }
+}
+
+impl<A: Encode, B: Encode> Encode for (A, B) {
+    fn encode(&self) -> Node {
+        Node::List(vec![self.0.encode(), self.1.encode()])
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let items = Vec::<Node>::decode(node)?;
+        Ok((get(&items, 0)?, get(&items, 1)?))
+    }
+}
+
+impl<A: Encode, B: Encode, C: Encode> Encode for (A, B, C) {
+    fn encode(&self) -> Node {
+        Node::List(vec![self.0.encode(), self.1.encode(), self.2.encode()])
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let items = Vec::<Node>::decode(node)?;
+        Ok((get(&items, 0)?, get(&items, 1)?, get(&items, 2)?))
+    }
+}
+
+impl Encode for Node {
+    fn encode(&self) -> Node {
+        self.clone()
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        Ok(node.clone())
+    }
+}
+
+// --------------------------------------------------------
+// ----------------- IR -----------------------------------
+// --------------------------------------------------------
+
+impl Encode for Label {
+    fn encode(&self) -> Node {
+        self.0.encode()
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        u32::decode(node).map(Label)
+    }
+}
+
+impl Encode for RegNum {
+    fn encode(&self) -> Node {
+        self.0.encode()
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        u32::decode(node).map(RegNum)
+    }
+}
+
+// by structure, the ids of the type table differ between the runs
+impl Encode for Type {
+    fn encode(&self) -> Node {
+        match self.kind() {
+            TypeKind::Void => tagged("void", vec![]),
+            TypeKind::Int => tagged("int", vec![]),
+            TypeKind::Long => tagged("long", vec![]),
+            TypeKind::Bool => tagged("bool", vec![]),
+            TypeKind::Char => tagged("char", vec![]),
+            TypeKind::Ptr(t) => tagged("ptr", vec![t.encode()]),
+            TypeKind::Class(name) => tagged("class", vec![name.encode()]),
+            TypeKind::Struct(name) => tagged("struct", vec![name.encode()]),
+            TypeKind::Func(ret, args) => tagged("func", vec![ret.encode(), args.encode()]),
+        }
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let (tag, fields) = get_tagged(node)?;
+        Ok(match tag {
+            "void" => Type::Void,
+            "int" => Type::Int,
+            "long" => Type::Long,
+            "bool" => Type::Bool,
+            "char" => Type::Char,
+            "ptr" => Type::ptr(get(fields, 0)?),
+            "class" => Type::intern(TypeKind::Class(get(fields, 0)?)),
+            "struct" => Type::intern(TypeKind::Struct(get(fields, 0)?)),
+            "func" => Type::func(get(fields, 0)?, get(fields, 1)?),
+            _ => return unknown_tag("type", tag),
+        })
+    }
+}
+
+impl Encode for Value {
+    fn encode(&self) -> Node {
+        match self {
+            Value::LitInt(n) => tagged("int", vec![n.encode()]),
+            Value::LitLong(n) => tagged("long", vec![n.encode()]),
+            Value::LitBool(b) => tagged("bool", vec![b.encode()]),
+            Value::LitNullPtr(t) => tagged("null", vec![t.encode()]),
+            Value::LitZeroStruct(t) => tagged("zero", vec![t.encode()]),
+            Value::Register(reg, t) => tagged("reg", vec![reg.encode(), t.encode()]),
+            Value::GlobalRegister(name, t) => tagged("global", vec![name.encode(), t.encode()]),
+        }
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let (tag, fields) = get_tagged(node)?;
+        Ok(match tag {
+            "int" => Value::LitInt(get(fields, 0)?),
+            "long" => Value::LitLong(get(fields, 0)?),
+            "bool" => Value::LitBool(get(fields, 0)?),
+            "null" => Value::LitNullPtr(get(fields, 0)?),
+            "zero" => Value::LitZeroStruct(get(fields, 0)?),
+            "reg" => Value::Register(get(fields, 0)?, get(fields, 1)?),
+            "global" => Value::GlobalRegister(get(fields, 0)?, get(fields, 1)?),
+            _ => return unknown_tag("value", tag),
+        })
+    }
+}
+
+impl Encode for ArithOp {
+    fn encode(&self) -> Node {
+        let tag = match self {
+            ArithOp::Add => "add",
+            ArithOp::Sub => "sub",
+            ArithOp::Mul => "mul",
+            ArithOp::Div => "div",
+            ArithOp::Mod => "mod",
+            ArithOp::And => "and",
+            ArithOp::Or => "or",
+        };
+        tagged(tag, vec![])
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let (tag, _) = get_tagged(node)?;
+        Ok(match tag {
+            "add" => ArithOp::Add,
+            "sub" => ArithOp::Sub,
+            "mul" => ArithOp::Mul,
+            "div" => ArithOp::Div,
+            "mod" => ArithOp::Mod,
+            "and" => ArithOp::And,
+            "or" => ArithOp::Or,
+            _ => return unknown_tag("arithmetic operator", tag),
+        })
+    }
+}
+
+impl Encode for CmpOp {
+    fn encode(&self) -> Node {
+        let tag = match self {
+            CmpOp::LT => "lt",
+            CmpOp::LE => "le",
+            CmpOp::GT => "gt",
+            CmpOp::GE => "ge",
+            CmpOp::EQ => "eq",
+            CmpOp::NE => "ne",
+        };
+        tagged(tag, vec![])
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let (tag, _) = get_tagged(node)?;
+        Ok(match tag {
+            "lt" => CmpOp::LT,
+            "le" => CmpOp::LE,
+            "gt" => CmpOp::GT,
+            "ge" => CmpOp::GE,
+            "eq" => CmpOp::EQ,
+            "ne" => CmpOp::NE,
+            _ => return unknown_tag("comparison operator", tag),
+        })
+    }
+}
+
+impl Encode for Operation {
+    fn encode(&self) -> Node {
+        use self::Operation::*;
+        match self {
+            FunctionCall(dst, t, fun, args) => tagged(
+                "call",
+                vec![dst.encode(), t.encode(), fun.encode(), args.encode()],
+            ),
+            Arithmetic(dst, op, lhs, rhs) => tagged(
+                "arith",
+                vec![dst.encode(), op.encode(), lhs.encode(), rhs.encode()],
+            ),
+            Compare(dst, op, lhs, rhs) => tagged(
+                "cmp",
+                vec![dst.encode(), op.encode(), lhs.encode(), rhs.encode()],
+            ),
+            Not(dst, val) => tagged("not", vec![dst.encode(), val.encode()]),
+            Select(dst, cond, val1, val2) => tagged(
+                "select",
+                vec![dst.encode(), cond.encode(), val1.encode(), val2.encode()],
+            ),
+            GetElementPtr(dst, t, vals) => {
+                tagged("gep", vec![dst.encode(), t.encode(), vals.encode()])
+            }
+            CastGlobalString(dst, len, val) => {
+                tagged("caststr", vec![dst.encode(), len.encode(), val.encode()])
+            }
+            CastPtr {
+                dst,
+                dst_type,
+                src_value,
+            } => tagged(
+                "castptr",
+                vec![dst.encode(), dst_type.encode(), src_value.encode()],
+            ),
+            CastPtrToInt {
+                dst,
+                dst_type,
+                src_value,
+            } => tagged(
+                "ptrtoint",
+                vec![dst.encode(), dst_type.encode(), src_value.encode()],
+            ),
+            CastCharToInt { dst, src_value } => {
+                tagged("chartoint", vec![dst.encode(), src_value.encode()])
+            }
+            ExtractValue {
+                dst,
+                src_value,
+                indices,
+            } => tagged(
+                "extract",
+                vec![dst.encode(), src_value.encode(), indices.encode()],
+            ),
+            InsertValue {
+                dst,
+                src_value,
+                field_value,
+                indices,
+            } => tagged(
+                "insert",
+                vec![
+                    dst.encode(),
+                    src_value.encode(),
+                    field_value.encode(),
+                    indices.encode(),
+                ],
+            ),
+            Load(dst, ptr) => tagged("load", vec![dst.encode(), ptr.encode()]),
+            Store(val, ptr) => tagged("store", vec![val.encode(), ptr.encode()]),
+            Alloca(dst, t, cnt) => tagged("alloca", vec![dst.encode(), t.encode(), cnt.encode()]),
+            MemZero(ptr, size) => tagged("memzero", vec![ptr.encode(), size.encode()]),
+            SourceSpan(span) => tagged("source", vec![span.encode()]),
+        }
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        use self::Operation::*;
+        let (tag, f) = get_tagged(node)?;
+        Ok(match tag {
+            "call" => FunctionCall(get(f, 0)?, get(f, 1)?, get(f, 2)?, get(f, 3)?),
+            "arith" => Arithmetic(get(f, 0)?, get(f, 1)?, get(f, 2)?, get(f, 3)?),
+            "cmp" => Compare(get(f, 0)?, get(f, 1)?, get(f, 2)?, get(f, 3)?),
+            "not" => Not(get(f, 0)?, get(f, 1)?),
+            "select" => Select(get(f, 0)?, get(f, 1)?, get(f, 2)?, get(f, 3)?),
+            "gep" => GetElementPtr(get(f, 0)?, get(f, 1)?, get(f, 2)?),
+            "caststr" => CastGlobalString(get(f, 0)?, get(f, 1)?, get(f, 2)?),
+            "castptr" => CastPtr {
+                dst: get(f, 0)?,
+                dst_type: get(f, 1)?,
+                src_value: get(f, 2)?,
+            },
+            "ptrtoint" => CastPtrToInt {
+                dst: get(f, 0)?,
+                dst_type: get(f, 1)?,
+                src_value: get(f, 2)?,
+            },
+            "chartoint" => CastCharToInt {
+                dst: get(f, 0)?,
+                src_value: get(f, 1)?,
+            },
+            "extract" => ExtractValue {
+                dst: get(f, 0)?,
+                src_value: get(f, 1)?,
+                indices: get(f, 2)?,
+            },
+            "insert" => InsertValue {
+                dst: get(f, 0)?,
+                src_value: get(f, 1)?,
+                field_value: get(f, 2)?,
+                indices: get(f, 3)?,
+            },
+            "load" => Load(get(f, 0)?, get(f, 1)?),
+            "store" => Store(get(f, 0)?, get(f, 1)?),
+            "alloca" => Alloca(get(f, 0)?, get(f, 1)?, get(f, 2)?),
+            "memzero" => MemZero(get(f, 0)?, get(f, 1)?),
+            "source" => SourceSpan(get(f, 0)?),
+            _ => return unknown_tag("operation", tag),
+        })
+    }
+}
+
+impl Encode for Terminator {
+    fn encode(&self) -> Node {
+        use self::Terminator::*;
+        match self {
+            Return(val) => tagged("ret", vec![val.encode()]),
+            Branch1(label) => tagged("br", vec![label.encode()]),
+            Branch2(cond, l1, l2, hint) => tagged(
+                "brcond",
+                vec![cond.encode(), l1.encode(), l2.encode(), hint.encode()],
+            ),
+            Unreachable => tagged("unreachable", vec![]),
+        }
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        use self::Terminator::*;
+        let (tag, f) = get_tagged(node)?;
+        Ok(match tag {
+            "ret" => Return(get(f, 0)?),
+            "br" => Branch1(get(f, 0)?),
+            "brcond" => Branch2(get(f, 0)?, get(f, 1)?, get(f, 2)?, get(f, 3)?),
+            "unreachable" => Unreachable,
+            _ => return unknown_tag("terminator", tag),
+        })
+    }
+}
+
+impl Encode for BranchHint {
+    fn encode(&self) -> Node {
+        match self {
+            BranchHint::None => tagged("none", vec![]),
+            BranchHint::Likely => tagged("likely", vec![]),
+            BranchHint::Unlikely => tagged("unlikely", vec![]),
+            BranchHint::Weights(t, f) => tagged("weights", vec![t.encode(), f.encode()]),
+        }
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let (tag, fields) = get_tagged(node)?;
+        Ok(match tag {
+            "none" => BranchHint::None,
+            "likely" => BranchHint::Likely,
+            "unlikely" => BranchHint::Unlikely,
+            "weights" => BranchHint::Weights(get(fields, 0)?, get(fields, 1)?),
+            _ => return unknown_tag("branch hint", tag),
+        })
+    }
+}
+
+impl Encode for Phi {
+    fn encode(&self) -> Node {
+        tagged(
+            "phi",
+            vec![
+                self.reg_num.encode(),
+                self.reg_type.encode(),
+                self.incoming.encode(),
+            ],
+        )
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        match get_tagged(node)? {
+            ("phi", f) => Ok(Phi {
+                reg_num: get(f, 0)?,
+                reg_type: get(f, 1)?,
+                incoming: get(f, 2)?,
+            }),
+            (tag, _) => unknown_tag("phi", tag),
+        }
+    }
+}
+
+impl Encode for Block {
+    fn encode(&self) -> Node {
+        tagged(
+            "block",
+            vec![
+                self.label.encode(),
+                self.phis.encode(),
+                self.predecessors.encode(),
+                self.body.encode(),
+                self.terminator.encode(),
+            ],
+        )
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        match get_tagged(node)? {
+            ("block", f) => Ok(Block {
+                label: get(f, 0)?,
+                phis: get(f, 1)?,
+                predecessors: get(f, 2)?,
+                body: get(f, 3)?,
+                terminator: get(f, 4)?,
+            }),
+            (tag, _) => unknown_tag("block", tag),
+        }
+    }
+}
+
+impl Encode for InlineHint {
+    fn encode(&self) -> Node {
+        let tag = match self {
+            InlineHint::Auto => "auto",
+            InlineHint::Always => "always",
+            InlineHint::Never => "never",
+        };
+        tagged(tag, vec![])
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let (tag, _) = get_tagged(node)?;
+        Ok(match tag {
+            "auto" => InlineHint::Auto,
+            "always" => InlineHint::Always,
+            "never" => InlineHint::Never,
+            _ => return unknown_tag("inline hint", tag),
+        })
+    }
+}
+
+impl Encode for Purity {
+    fn encode(&self) -> Node {
+        let tag = match self {
+            Purity::Impure => "impure",
+            Purity::ReadOnly => "readonly",
+            Purity::Pure => "pure",
+        };
+        tagged(tag, vec![])
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let (tag, _) = get_tagged(node)?;
+        Ok(match tag {
+            "impure" => Purity::Impure,
+            "readonly" => Purity::ReadOnly,
+            "pure" => Purity::Pure,
+            _ => return unknown_tag("purity", tag),
+        })
+    }
+}
+
+impl Encode for FunctionAttribute {
+    fn encode(&self) -> Node {
+        let tag = match self {
+            FunctionAttribute::Internal => "internal",
+            FunctionAttribute::NoReturn => "noreturn",
+            FunctionAttribute::Used => "used",
+        };
+        tagged(tag, vec![])
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let (tag, _) = get_tagged(node)?;
+        Ok(match tag {
+            "internal" => FunctionAttribute::Internal,
+            "noreturn" => FunctionAttribute::NoReturn,
+            "used" => FunctionAttribute::Used,
+            _ => return unknown_tag("function attribute", tag),
+        })
+    }
+}
+
+impl Encode for Function {
+    fn encode(&self) -> Node {
+        tagged(
+            "function",
+            vec![
+                self.name.encode(),
+                self.ret_type.encode(),
+                self.args.encode(),
+                self.inline_hint.encode(),
+                self.blocks.encode(),
+                self.purity.encode(),
+                self.always_returns.encode(),
+                self.never_recurses.encode(),
+                self.attributes.encode(),
+            ],
+        )
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        match get_tagged(node)? {
+            ("function", f) => Ok(Function {
+                name: get(f, 0)?,
+                ret_type: get(f, 1)?,
+                args: get(f, 2)?,
+                inline_hint: get(f, 3)?,
+                blocks: get(f, 4)?,
+                purity: get(f, 5)?,
+                always_returns: get(f, 6)?,
+                never_recurses: get(f, 7)?,
+                attributes: get(f, 8)?,
+            }),
+            (tag, _) => unknown_tag("function", tag),
+        }
+    }
+}
+
+impl Encode for Struct {
+    fn encode(&self) -> Node {
+        tagged("struct", vec![self.name.encode(), self.fields.encode()])
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        match get_tagged(node)? {
+            ("struct", f) => Ok(Struct {
+                name: get(f, 0)?,
+                fields: get(f, 1)?,
+            }),
+            (tag, _) => unknown_tag("struct", tag),
+        }
+    }
+}
+
+impl Encode for Class {
+    fn encode(&self) -> Node {
+        tagged(
+            "class",
+            vec![
+                self.name.encode(),
+                self.fields.encode(),
+                self.field_names.encode(),
+                self.vtable.encode(),
+                self.is_builtin.encode(),
+                self.parent.encode(),
+                self.is_final.encode(),
+                self.overridden.encode(),
+                self.needs_vtable.encode(),
+            ],
+        )
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        match get_tagged(node)? {
+            ("class", f) => Ok(Class {
+                name: get(f, 0)?,
+                fields: get(f, 1)?,
+                field_names: get(f, 2)?,
+                vtable: get(f, 3)?,
+                is_builtin: get(f, 4)?,
+                parent: get(f, 5)?,
+                is_final: get(f, 6)?,
+                overridden: get(f, 7)?,
+                needs_vtable: get(f, 8)?,
+            }),
+            (tag, _) => unknown_tag("class", tag),
+        }
+    }
+}
+
+// the strings with their ids, sorted by content
+impl Encode for GlobalStrings {
+    fn encode(&self) -> Node {
+        let strings: Vec<_> = self
+            .iter_sorted()
+            .into_iter()
+            .map(|(s, id)| (s.to_string(), id.0))
+            .collect();
+        strings.encode()
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        let strings: Vec<(String, u64)> = Vec::decode(node)?;
+        let mut res = GlobalStrings::default();
+        for (s, id) in strings {
+            if !res.insert_with_id(&s, GlobalStrId(id)) {
+                return Err(format!("duplicate string {:?} or id {}", s, id));
+            }
+        }
+        Ok(res)
+    }
+}
+
+// changed with the encoding of the IR
+const PROGRAM_FORMAT_VERSION: u32 = 10;
+// starts the binary form of a program
+const BINARY_MAGIC: &[u8] = b"LATTEIR\0";
+
+impl Encode for Program {
+    fn encode(&self) -> Node {
+        tagged(
+            "program",
+            vec![
+                PROGRAM_FORMAT_VERSION.encode(),
+                self.size_type.encode(),
+                self.structs.encode(),
+                self.classes.encode(),
+                self.functions.encode(),
+                self.global_strings.encode(),
+            ],
+        )
+    }
+
+    fn decode(node: &Node) -> DecodeResult<Self> {
+        match get_tagged(node)? {
+            ("program", f) => {
+                let version: u32 = get(f, 0)?;
+                if version != PROGRAM_FORMAT_VERSION {
+                    return Err(format!(
+                        "unsupported format version {} (expected {})",
+                        version, PROGRAM_FORMAT_VERSION
+                    ));
+                }
+                Ok(Program {
+                    size_type: get(f, 1)?,
+                    structs: get(f, 2)?,
+                    classes: get(f, 3)?,
+                    functions: get(f, 4)?,
+                    global_strings: get(f, 5)?,
+                })
+            }
+            (tag, _) => unknown_tag("program", tag),
+        }
+    }
+}
+
+pub fn encode_program_json(prog: &Program) -> String {
+    prog.encode().to_json() + "\n"
+}
+
+pub fn encode_program_binary(prog: &Program) -> Vec<u8> {
+    let mut res = BINARY_MAGIC.to_vec();
+    res.extend(prog.encode().to_binary());
+    res
+}
+
+// the format (binary, JSON or s-expression) is recognized by the first bytes
+pub fn decode_program(bytes: &[u8]) -> DecodeResult<Program> {
+    let node = if bytes.starts_with(BINARY_MAGIC) {
+        Node::from_binary(&bytes[BINARY_MAGIC.len()..])?
+    } else {
+        let text = std::str::from_utf8(bytes).map_err(|_| "neither binary nor text")?;
+        if text.trim_start().starts_with('[') {
+            Node::from_json(text)?
+        } else {
+            Node::from_text(text)?
+        }
+    };
+    Program::decode(&node)
+}
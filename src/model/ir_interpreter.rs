@@ -0,0 +1,271 @@
+// Interpreter of the IR: runs functions of a program on ints, longs and
+// bools, without memory (so without strings, arrays and objects). The
+// optimizer evaluates calls of pure functions with constant arguments with
+// it (see const_eval) and `run --interp=ir` runs the optimized program, e.g.
+// to compare it with the interpreter of the AST in latte-fuzz. Anything else
+// stops the run as unsupported.
+
+use model::ir::{
+    ArithOp, CmpOp, Function, Label, Operation, Program, Terminator, Value, NORETURN_FUNCTIONS,
+};
+use std::collections::HashMap;
+
+// of the interpreter, it recurses like the interpreted functions
+const MAX_DEPTH: u32 = 200;
+
+// why a call wasn't finished
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stop {
+    Unsupported, // e.g. memory or a function of the runtime
+    Undefined,   // division by zero or unreachable
+    ErrorCall,   // error() or another function of the runtime ending the program
+    Limit,       // out of the steps or too deep
+}
+
+// (function, arguments) -> result of the call, None for void functions
+pub type CallResults = HashMap<(String, Vec<Value>), Result<Option<Value>, Stop>>;
+
+pub struct Interpreter<'a> {
+    functions: HashMap<&'a str, &'a Function>,
+    // only for pure functions, their results are reused
+    memo: Option<CallResults>,
+    steps_left: u32, // operations which can be executed yet
+    output: Vec<u8>,
+}
+
+// the output of main and its exit code
+pub fn run_program(prog: &Program, steps: u32) -> (Vec<u8>, Result<i32, Stop>) {
+    let mut interpreter = Interpreter::new(&prog.functions);
+    interpreter.set_steps(steps);
+    let res = match interpreter.call("main", vec![]) {
+        Ok(Some(Value::LitInt(exit_code))) => Ok(exit_code),
+        Ok(_) => Err(Stop::Unsupported),
+        Err(stop) => Err(stop),
+    };
+    (interpreter.output, res)
+}
+
+impl<'a> Interpreter<'a> {
+    pub fn new<I: IntoIterator<Item = &'a Function>>(functions: I) -> Self {
+        Interpreter {
+            functions: functions
+                .into_iter()
+                .map(|fun| (fun.name.as_str(), fun))
+                .collect(),
+            memo: None,
+            steps_left: 0,
+            output: vec![],
+        }
+    }
+
+    // the functions are pure, results of the calls are kept (also the
+    // earlier ones) and the output isn't allowed
+    pub fn with_memo(mut self, memo: CallResults) -> Self {
+        self.memo = Some(memo);
+        self
+    }
+
+    pub fn take_memo(&mut self) -> CallResults {
+        self.memo.take().unwrap_or_default()
+    }
+
+    pub fn set_steps(&mut self, steps: u32) {
+        self.steps_left = steps;
+    }
+
+    pub fn get_steps_left(&self) -> u32 {
+        self.steps_left
+    }
+
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Option<Value>, Stop> {
+        self.call_at_depth(name, args, 0)
+    }
+
+    fn call_at_depth(
+        &mut self,
+        name: &str,
+        args: Vec<Value>,
+        depth: u32,
+    ) -> Result<Option<Value>, Stop> {
+        let key = (name.to_string(), args);
+        if let Some(res) = self.memo.as_ref().and_then(|memo| memo.get(&key)) {
+            return res.clone();
+        }
+        let fun = match self.functions.get(name) {
+            Some(fun) => *fun,
+            None => return self.call_runtime(name, &key.1),
+        };
+        if depth >= MAX_DEPTH {
+            return Err(Stop::Limit);
+        }
+        let res = self.run(fun, &key.1, depth);
+        // hitting the limits doesn't mean the call can't be finished
+        if res != Err(Stop::Limit) {
+            if let Some(memo) = &mut self.memo {
+                memo.insert(key, res.clone());
+            }
+        }
+        res
+    }
+
+    fn call_runtime(&mut self, name: &str, args: &[Value]) -> Result<Option<Value>, Stop> {
+        if NORETURN_FUNCTIONS.contains(&name) {
+            return Err(Stop::ErrorCall);
+        }
+        if self.memo.is_some() {
+            return Err(Stop::Unsupported);
+        }
+        let line = match (name, args) {
+            ("printInt", [Value::LitInt(x)]) => x.to_string(),
+            ("printBoolean", [Value::LitBool(b)]) => b.to_string(),
+            _ => return Err(Stop::Unsupported),
+        };
+        self.output.extend(line.as_bytes());
+        self.output.push(b'\n');
+        Ok(None)
+    }
+
+    fn run(&mut self, fun: &Function, args: &[Value], depth: u32) -> Result<Option<Value>, Stop> {
+        let mut regs: HashMap<_, _> = fun
+            .args
+            .iter()
+            .map(|(reg_num, _)| *reg_num)
+            .zip(args.iter().cloned())
+            .collect();
+        let get = |regs: &HashMap<_, Value>, value: &Value| match value {
+            Value::Register(reg_num, _) => regs.get(reg_num).cloned().ok_or(Stop::Unsupported),
+            _ if is_constant(value) => Ok(value.clone()),
+            _ => Err(Stop::Unsupported),
+        };
+        let mut label = fun.blocks[0].label;
+        let mut prev_label: Option<Label> = None;
+        loop {
+            let bl = fun.get_block(label);
+            if let Some(prev) = prev_label {
+                // the phis are assigned together
+                let mut phi_values = vec![];
+                for phi in &bl.phis {
+                    let value = phi.get_incoming(prev).ok_or(Stop::Undefined)?;
+                    phi_values.push((phi.reg_num, get(&regs, value)?));
+                }
+                regs.extend(phi_values);
+            }
+            for op in &bl.body {
+                if self.steps_left == 0 {
+                    return Err(Stop::Limit);
+                }
+                self.steps_left -= 1;
+                match op {
+                    Operation::Arithmetic(dst, op, lhs, rhs) => {
+                        let value = evaluate_arithmetic(op, get(&regs, lhs)?, get(&regs, rhs)?)?;
+                        regs.insert(*dst, value);
+                    }
+                    Operation::Compare(dst, op, lhs, rhs) => {
+                        let (lhs, rhs) = (
+                            get_integer(&get(&regs, lhs)?)?,
+                            get_integer(&get(&regs, rhs)?)?,
+                        );
+                        regs.insert(*dst, Value::LitBool(evaluate_comparison(op, lhs, rhs)));
+                    }
+                    Operation::Not(dst, value) => match get(&regs, value)? {
+                        Value::LitBool(b) => {
+                            regs.insert(*dst, Value::LitBool(!b));
+                        }
+                        _ => return Err(Stop::Unsupported),
+                    },
+                    Operation::Select(dst, cond, if_true, if_false) => {
+                        let value = match get(&regs, cond)? {
+                            Value::LitBool(true) => get(&regs, if_true)?,
+                            Value::LitBool(false) => get(&regs, if_false)?,
+                            _ => return Err(Stop::Unsupported),
+                        };
+                        regs.insert(*dst, value);
+                    }
+                    Operation::FunctionCall(dst, _, Value::GlobalRegister(name, _), args) => {
+                        let args = args
+                            .iter()
+                            .map(|arg| get(&regs, arg))
+                            .collect::<Result<_, _>>()?;
+                        let res = self.call_at_depth(name, args, depth + 1)?;
+                        if let (Some(dst), Some(res)) = (dst, res) {
+                            regs.insert(*dst, res);
+                        }
+                    }
+                    Operation::SourceSpan(_) => (),
+                    _ => return Err(Stop::Unsupported),
+                }
+            }
+            let next_label = match &bl.terminator {
+                Terminator::Return(None) => return Ok(None),
+                Terminator::Return(Some(value)) => return get(&regs, value).map(Some),
+                Terminator::Branch1(target) => *target,
+                Terminator::Branch2(cond, if_true, if_false, _) => match get(&regs, cond)? {
+                    Value::LitBool(true) => *if_true,
+                    Value::LitBool(false) => *if_false,
+                    _ => return Err(Stop::Unsupported),
+                },
+                Terminator::Unreachable => return Err(Stop::Undefined),
+            };
+            prev_label = Some(label);
+            label = next_label;
+        }
+    }
+}
+
+pub fn is_constant(value: &Value) -> bool {
+    matches!(
+        value,
+        Value::LitInt(_) | Value::LitLong(_) | Value::LitBool(_)
+    )
+}
+
+// as compared by icmp (signed, true of i1 is -1)
+fn get_integer(value: &Value) -> Result<i64, Stop> {
+    match value {
+        Value::LitInt(x) => Ok(i64::from(*x)),
+        Value::LitLong(x) => Ok(*x),
+        Value::LitBool(b) => Ok(-i64::from(*b)),
+        _ => Err(Stop::Unsupported),
+    }
+}
+
+// wrapping like the LLVM operations, undefined where they are
+fn evaluate_arithmetic(op: &ArithOp, lhs: Value, rhs: Value) -> Result<Value, Stop> {
+    match (lhs, rhs) {
+        (Value::LitInt(a), Value::LitInt(b)) => Ok(Value::LitInt(match op {
+            ArithOp::Add => a.wrapping_add(b),
+            ArithOp::Sub => a.wrapping_sub(b),
+            ArithOp::Mul => a.wrapping_mul(b),
+            ArithOp::Div => a.checked_div(b).ok_or(Stop::Undefined)?,
+            ArithOp::Mod => a.checked_rem(b).ok_or(Stop::Undefined)?,
+            ArithOp::And => a & b,
+            ArithOp::Or => a | b,
+        })),
+        (Value::LitLong(a), Value::LitLong(b)) => Ok(Value::LitLong(match op {
+            ArithOp::Add => a.wrapping_add(b),
+            ArithOp::Sub => a.wrapping_sub(b),
+            ArithOp::Mul => a.wrapping_mul(b),
+            ArithOp::Div => a.checked_div(b).ok_or(Stop::Undefined)?,
+            ArithOp::Mod => a.checked_rem(b).ok_or(Stop::Undefined)?,
+            ArithOp::And => a & b,
+            ArithOp::Or => a | b,
+        })),
+        (Value::LitBool(a), Value::LitBool(b)) => Ok(Value::LitBool(match op {
+            ArithOp::And => a && b,
+            ArithOp::Or => a || b,
+            _ => return Err(Stop::Unsupported),
+        })),
+        _ => Err(Stop::Unsupported),
+    }
+}
+
+fn evaluate_comparison(op: &CmpOp, lhs: i64, rhs: i64) -> bool {
+    match op {
+        CmpOp::LT => lhs < rhs,
+        CmpOp::LE => lhs <= rhs,
+        CmpOp::GT => lhs > rhs,
+        CmpOp::GE => lhs >= rhs,
+        CmpOp::EQ => lhs == rhs,
+        CmpOp::NE => lhs != rhs,
+    }
+}
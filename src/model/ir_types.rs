@@ -0,0 +1,129 @@
+// Interned types of the IR: every distinct type is stored once in the global
+// type table and ir::Type is just its id, so types are copied instead of
+// cloned and compared in O(1).
+//
+// The table only grows (the number of distinct types of a program is small)
+// and is shared by all the threads, e.g. the codegen and the interpreter.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{LazyLock, Mutex};
+
+// index in the type table
+pub type TypeId = u32;
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Type(TypeId);
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+pub enum TypeKind {
+    Void,
+    Int,
+    Long,
+    Bool,
+    Char,
+    Ptr(Type),
+    Class(String),
+    Struct(String),
+    Func(Type, Vec<Type>),
+}
+
+// the primitive types have fixed ids, no lookup is needed for them
+static PRIMITIVES: [TypeKind; 5] = [
+    TypeKind::Void,
+    TypeKind::Int,
+    TypeKind::Long,
+    TypeKind::Bool,
+    TypeKind::Char,
+];
+
+struct TypeTable {
+    kinds: Vec<&'static TypeKind>, // leaked, never freed
+    ids: HashMap<&'static TypeKind, Type>,
+}
+
+static TYPE_TABLE: LazyLock<Mutex<TypeTable>> = LazyLock::new(|| {
+    let mut table = TypeTable {
+        kinds: vec![],
+        ids: HashMap::new(),
+    };
+    for kind in PRIMITIVES.iter() {
+        table.insert(kind);
+    }
+    Mutex::new(table)
+});
+
+impl TypeTable {
+    fn insert(&mut self, kind: &'static TypeKind) -> Type {
+        let t = Type(self.kinds.len() as TypeId);
+        self.kinds.push(kind);
+        self.ids.insert(kind, t);
+        t
+    }
+}
+
+// a panic elsewhere can't leave the table inconsistent
+fn lock_table() -> std::sync::MutexGuard<'static, TypeTable> {
+    TYPE_TABLE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+#[allow(non_upper_case_globals)]
+impl Type {
+    pub const Void: Type = Type(0);
+    pub const Int: Type = Type(1);
+    pub const Long: Type = Type(2);
+    pub const Bool: Type = Type(3);
+    pub const Char: Type = Type(4);
+
+    pub fn intern(kind: TypeKind) -> Type {
+        if let Some(id) = PRIMITIVES.iter().position(|p| *p == kind) {
+            return Type(id as TypeId);
+        }
+        let mut table = lock_table();
+        match table.ids.get(&kind) {
+            Some(t) => *t,
+            None => table.insert(Box::leak(Box::new(kind))),
+        }
+    }
+
+    pub fn ptr(pointee: Type) -> Type {
+        Type::intern(TypeKind::Ptr(pointee))
+    }
+
+    pub fn func(ret_type: Type, args_types: Vec<Type>) -> Type {
+        Type::intern(TypeKind::Func(ret_type, args_types))
+    }
+
+    pub fn kind(self) -> &'static TypeKind {
+        match PRIMITIVES.get(self.0 as usize) {
+            Some(kind) => kind,
+            None => lock_table().kinds[self.0 as usize],
+        }
+    }
+
+    pub fn id(self) -> TypeId {
+        self.0
+    }
+
+    // the pointed type, None for non-pointers
+    pub fn pointee(self) -> Option<Type> {
+        match self.kind() {
+            TypeKind::Ptr(t) => Some(*t),
+            _ => None,
+        }
+    }
+
+    pub fn is_ptr(self) -> bool {
+        self.pointee().is_some()
+    }
+
+    pub fn is_struct(self) -> bool {
+        matches!(self.kind(), TypeKind::Struct(_))
+    }
+}
+
+impl fmt::Debug for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.kind())
+    }
+}
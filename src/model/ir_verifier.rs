@@ -0,0 +1,136 @@
+// Checks of the invariants of the IR, run after the code generation and after
+// the optimizations; a broken one is an internal error of the compiler.
+
+use model::ir::{
+    ArithOp, Function, FunctionAttribute, Label, Operation, Program, Terminator, Type, Value,
+};
+use std::collections::{HashMap, HashSet};
+
+pub fn verify_program(prog: &Program) {
+    let no_return = prog.get_noreturn_functions();
+    for fun in &prog.functions {
+        verify_function(fun, &no_return);
+    }
+}
+
+fn verify_function(fun: &Function, no_return: &HashSet<String>) {
+    verify_cfg(fun);
+    for bl in &fun.blocks {
+        for phi in &bl.phis {
+            for value in phi.get_used_values() {
+                check_typed_null(fun, value);
+                check_same_type(fun, "phi", value.get_type(), phi.reg_type);
+            }
+        }
+        for value in bl.get_used_values() {
+            check_typed_null(fun, value);
+        }
+        for op in &bl.body {
+            match op {
+                Operation::Arithmetic(_, op, val1, val2) => {
+                    check_same_type(fun, "arithmetic", val1.get_type(), val2.get_type());
+                    check_arithmetic_type(fun, op, val1.get_type());
+                }
+                Operation::Not(_, value) => {
+                    check_same_type(fun, "not", value.get_type(), Type::Bool)
+                }
+                // null has the type of the other operand
+                Operation::Compare(_, _, val1, val2) => {
+                    check_same_type(fun, "compare", val1.get_type(), val2.get_type())
+                }
+                Operation::Select(_, _, val1, val2) => {
+                    check_same_type(fun, "select", val1.get_type(), val2.get_type())
+                }
+                _ => (),
+            }
+        }
+        match &bl.terminator {
+            Terminator::Return(value) => {
+                assert!(
+                    !fun.attributes.contains(&FunctionAttribute::NoReturn),
+                    "{}: noreturn function returns in block .L{}",
+                    fun.name,
+                    bl.label.0
+                );
+                if let Some(value) = value {
+                    check_same_type(fun, "return", value.get_type(), fun.ret_type)
+                }
+            }
+            // only after a call which doesn't return
+            Terminator::Unreachable => match bl.body.last() {
+                Some(Operation::FunctionCall(_, _, Value::GlobalRegister(name, _), _))
+                    if no_return.contains(name) => {}
+                _ => panic!("{}: block .L{} isn't finished", fun.name, bl.label.0),
+            },
+            _ => (),
+        }
+    }
+}
+
+// the predecessors lists and the labels in the phis agree with the terminators
+fn verify_cfg(fun: &Function) {
+    let mut preds: HashMap<Label, HashSet<Label>> = fun
+        .blocks
+        .iter()
+        .map(|bl| (bl.label, HashSet::new()))
+        .collect();
+    for bl in &fun.blocks {
+        for succ in bl.get_successors() {
+            match preds.get_mut(&succ) {
+                Some(succ_preds) => succ_preds.insert(bl.label),
+                None => panic!(
+                    "{}: block .L{} jumps to a missing block .L{}",
+                    fun.name, bl.label.0, succ.0
+                ),
+            };
+        }
+    }
+    for bl in &fun.blocks {
+        let listed: HashSet<_> = bl.predecessors.iter().cloned().collect();
+        assert!(
+            listed == preds[&bl.label],
+            "{}: stale predecessors of block .L{}",
+            fun.name,
+            bl.label.0
+        );
+        for phi in &bl.phis {
+            let incoming: HashSet<_> = phi.incoming.iter().map(|(_, label)| *label).collect();
+            assert!(
+                incoming == listed && phi.incoming.len() == listed.len(),
+                "{}: phi %.r{} doesn't match the predecessors of block .L{}",
+                fun.name,
+                phi.reg_num.0,
+                bl.label.0
+            );
+        }
+    }
+}
+
+// a null literal without a type would be emitted as i8*
+fn check_typed_null(fun: &Function, value: &Value) {
+    assert!(
+        *value != Value::LitNullPtr(None),
+        "{}: null without a type",
+        fun.name
+    );
+}
+
+// booleans are combined only by and/or (`!x` is Not)
+fn check_arithmetic_type(fun: &Function, op: &ArithOp, t: Type) {
+    let allowed = match op {
+        ArithOp::And | ArithOp::Or => t == Type::Bool || t == Type::Int || t == Type::Long,
+        _ => t == Type::Int || t == Type::Long,
+    };
+    assert!(allowed, "{}: arithmetic of values of type {}", fun.name, t);
+}
+
+fn check_same_type(fun: &Function, what: &str, t1: Type, t2: Type) {
+    assert!(
+        t1 == t2,
+        "{}: {} of values of different types {} and {}",
+        fun.name,
+        what,
+        t1,
+        t2
+    );
+}
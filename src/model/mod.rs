@@ -1,2 +1,7 @@
 pub mod ast;
 pub mod ir;
+pub mod ir_encoding;
+pub mod ir_interpreter;
+pub mod ir_types;
+pub mod ir_verifier;
+pub mod source_map;
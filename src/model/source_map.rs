@@ -0,0 +1,87 @@
+use codemap::CodeMap;
+use model::ast::Span;
+use model::ir::Program;
+use options::ReportFormat;
+
+// The source map of the printed LLVM IR (--report=source-map): for every
+// line, the span of the statement it was generated for. The codegen marks
+// the statements with Operation::SourceSpan, printed (with {:#}) as comment
+// lines and removed here, so the lines are numbered like in the IR printed
+// normally (the passes ignore the markers). A marker holds until the next
+// one in the block, the operations before the first one (e.g. of blocks
+// made by the optimizer), phis and lines outside of blocks have no source.
+
+const MARKER_PREFIX: &str = "    ; source ";
+
+// the printed lines with their statements
+fn build_source_map(prog: &Program) -> Vec<(String, Option<Span>)> {
+    let mut res = vec![];
+    let mut cur_span = None;
+    for line in format!("{:#}", prog).lines() {
+        if let Some(span) = line.strip_prefix(MARKER_PREFIX) {
+            let mut bounds = span.split('-').map(|n| n.parse().unwrap());
+            cur_span = Some((bounds.next().unwrap(), bounds.next().unwrap()));
+            continue;
+        }
+        // labels of blocks and lines outside of functions aren't indented
+        if !line.starts_with("    ") {
+            cur_span = None;
+        }
+        let is_operation = line.starts_with("    ") && !line.contains(" = phi ");
+        res.push((line.to_string(), if is_operation { cur_span } else { None }));
+    }
+    res
+}
+
+pub fn format_source_map(prog: &Program, codemap: &CodeMap, format: ReportFormat) -> String {
+    let lines: Vec<_> = build_source_map(prog)
+        .into_iter()
+        .map(|(text, span)| {
+            let pos = span.and_then(|span| {
+                Some((codemap.find_row_col(span.0)?, codemap.find_row_col(span.1)?))
+            });
+            (text, pos)
+        })
+        .collect();
+    match format {
+        ReportFormat::Table => format_table(&lines),
+        ReportFormat::Json => format_json(&lines),
+    }
+}
+
+type Position = ((usize, usize), (usize, usize));
+
+fn format_table(lines: &[(String, Option<Position>)]) -> String {
+    let mut res = String::new();
+    for (i, (text, pos)) in lines.iter().enumerate() {
+        let source = match pos {
+            Some(((row0, col0), (row1, col1))) => format!("{}:{}-{}:{}", row0, col0, row1, col1),
+            None => "-".to_string(),
+        };
+        res += &format!("{:>5} {:<15} {}\n", i + 1, source, text);
+    }
+    res
+}
+
+// only the lines with a source
+fn format_json(lines: &[(String, Option<Position>)]) -> String {
+    let entries: Vec<_> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, (_, pos))| {
+            let ((row0, col0), (row1, col1)) = (*pos)?;
+            Some(format!(
+                "  {{\"ir_line\": {}, \"line\": {}, \"column\": {}, \"end_line\": {}, \"end_column\": {}}}",
+                i + 1,
+                row0,
+                col0,
+                row1,
+                col1
+            ))
+        })
+        .collect();
+    if entries.is_empty() {
+        return "[]\n".to_string();
+    }
+    format!("[\n{}\n]\n", entries.join(",\n"))
+}
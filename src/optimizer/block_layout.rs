@@ -0,0 +1,125 @@
+use super::get_predecessors;
+use model::ir::{
+    BranchHint, CmpOp, Function, Label, Operation, RegNum, Terminator, Value, NORETURN_FUNCTIONS,
+};
+use std::collections::{HashMap, HashSet};
+
+// Branch hints and the order of blocks. Blocks which end the program with
+// an error (error() or the stack overflow check) are cold, and so are the
+// blocks which lead only to them; branches to cold blocks are unlikely,
+// like comparisons of pointers with null. The hints are emitted as LLVM
+// branch weights, the layout puts the cold blocks at the end of the
+// function and the likely target right after its branch, so the hot path
+// falls through (llc -O0 keeps the order of the blocks). Weights measured
+// by a profile (see profile.rs) replace the guesses.
+
+pub fn add_branch_hints(fun: &mut Function) {
+    let cold = get_cold_blocks(fun);
+    let null_checks = get_null_checks(fun);
+    for bl in &mut fun.blocks {
+        if let Terminator::Branch2(cond, t, f, hint @ BranchHint::None) = &mut bl.terminator {
+            *hint = match (cold.contains(t), cold.contains(f)) {
+                (false, true) => BranchHint::Likely,
+                (true, false) => BranchHint::Unlikely,
+                _ => match cond {
+                    Value::Register(reg_num, _) => null_checks
+                        .get(reg_num)
+                        .cloned()
+                        .unwrap_or(BranchHint::None),
+                    _ => BranchHint::None,
+                },
+            };
+        }
+    }
+}
+
+pub fn lay_out_blocks(fun: &mut Function) {
+    let cold = get_cold_blocks(fun);
+    let preds = get_predecessors(fun);
+    let entry = fun.blocks[0].label;
+    let mut order = vec![];
+    let mut placed = HashSet::new();
+    for bl in &fun.blocks {
+        if placed.contains(&bl.label) || (cold.contains(&bl.label) && bl.label != entry) {
+            continue;
+        }
+        // the chain of likely targets entered only from their branches
+        let mut label = bl.label;
+        loop {
+            order.push(label);
+            placed.insert(label);
+            let next = match fun.get_block(label).terminator {
+                Terminator::Branch2(_, t, _, BranchHint::Likely) => t,
+                Terminator::Branch2(_, _, f, BranchHint::Unlikely) => f,
+                Terminator::Branch2(_, t, _, BranchHint::Weights(wt, wf)) if wt > wf => t,
+                Terminator::Branch2(_, _, f, BranchHint::Weights(wt, wf)) if wf > wt => f,
+                _ => break,
+            };
+            if placed.contains(&next) || cold.contains(&next) || preds[&next] != [label] {
+                break;
+            }
+            label = next;
+        }
+    }
+    order.extend(
+        fun.blocks
+            .iter()
+            .map(|bl| bl.label)
+            .filter(|label| !placed.contains(label)),
+    );
+
+    let mut blocks: HashMap<_, _> = fun.blocks.drain(..).map(|bl| (bl.label, bl)).collect();
+    fun.blocks = order
+        .into_iter()
+        .map(|label| blocks.remove(&label).unwrap())
+        .collect();
+}
+
+// blocks calling an error function and blocks whose all successors are cold
+fn get_cold_blocks(fun: &Function) -> HashSet<Label> {
+    let calls_error = |op: &Operation| match op {
+        Operation::FunctionCall(_, _, Value::GlobalRegister(name, _), _) => {
+            NORETURN_FUNCTIONS.contains(&name.as_str())
+        }
+        _ => false,
+    };
+    let mut cold: HashSet<_> = fun
+        .blocks
+        .iter()
+        .filter(|bl| bl.body.iter().any(calls_error))
+        .map(|bl| bl.label)
+        .collect();
+    loop {
+        let new_cold: Vec<_> = fun
+            .blocks
+            .iter()
+            .filter(|bl| !cold.contains(&bl.label))
+            .filter(|bl| {
+                let succs = bl.get_successors();
+                !succs.is_empty() && succs.iter().all(|succ| cold.contains(succ))
+            })
+            .map(|bl| bl.label)
+            .collect();
+        if new_cold.is_empty() {
+            return cold;
+        }
+        cold.extend(new_cold);
+    }
+}
+
+// hints of the conditions comparing a pointer with null
+fn get_null_checks(fun: &Function) -> HashMap<RegNum, BranchHint> {
+    let mut res = HashMap::new();
+    for op in fun.blocks.iter().flat_map(|bl| &bl.body) {
+        if let Operation::Compare(dst, op, lhs, rhs) = op {
+            let with_null =
+                matches!(lhs, Value::LitNullPtr(_)) || matches!(rhs, Value::LitNullPtr(_));
+            match op {
+                CmpOp::EQ if with_null => res.insert(*dst, BranchHint::Unlikely),
+                CmpOp::NE if with_null => res.insert(*dst, BranchHint::Likely),
+                _ => None,
+            };
+        }
+    }
+    res
+}
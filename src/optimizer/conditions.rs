@@ -0,0 +1,162 @@
+use super::substitute_value;
+use model::ir::{CmpOp, Function, Operation, RegNum, Terminator, Type, Value};
+use std::collections::{HashMap, HashSet};
+
+// Normalizes the conditions of branches, after inlining made some of them
+// constant and the lowering of `!` left negations in them:
+//
+//     %n = xor i1 %c, true; br %n, a, b  ->  br %c, b, a
+//     %e = icmp eq i1 %c, 1              ->  %c (also ne 0; eq 0 and ne 1
+//                                            negate %c)
+//     %m = xor i1 %n, true (of %n above) ->  %c
+//     %k = icmp slt i32 1, 2             ->  1
+//     br 1, a, b                         ->  br a
+//
+// Blocks which are no longer reachable are removed, together with their
+// entries in phis.
+
+pub fn canonicalize_conditions(fun: &mut Function) {
+    fold_bool_values(fun);
+    simplify_branches(fun);
+    remove_unreachable_blocks(fun);
+}
+
+// a boolean value, possibly negated
+type BoolValue = (Value, bool);
+
+// the value of a register defined as a negation or a comparison with
+// a boolean constant, as a possibly negated other value
+fn get_bool_definitions(fun: &Function) -> HashMap<RegNum, BoolValue> {
+    let mut res = HashMap::new();
+    for op in fun.blocks.iter().flat_map(|bl| &bl.body) {
+        match op {
+            Operation::Not(dst, value) => {
+                res.insert(*dst, (value.clone(), true));
+            }
+            Operation::Compare(dst, op, Value::LitInt(lhs), Value::LitInt(rhs)) => {
+                let res_value = match op {
+                    CmpOp::LT => lhs < rhs,
+                    CmpOp::LE => lhs <= rhs,
+                    CmpOp::GT => lhs > rhs,
+                    CmpOp::GE => lhs >= rhs,
+                    CmpOp::EQ => lhs == rhs,
+                    CmpOp::NE => lhs != rhs,
+                };
+                res.insert(*dst, (Value::LitBool(res_value), false));
+            }
+            Operation::Compare(dst, op @ CmpOp::EQ, value, Value::LitBool(b))
+            | Operation::Compare(dst, op @ CmpOp::NE, value, Value::LitBool(b))
+            | Operation::Compare(dst, op @ CmpOp::EQ, Value::LitBool(b), value)
+            | Operation::Compare(dst, op @ CmpOp::NE, Value::LitBool(b), value) => {
+                // x == true and x != false are x
+                let negated = matches!(op, CmpOp::EQ) != *b;
+                res.insert(*dst, (value.clone(), negated));
+            }
+            _ => (),
+        }
+    }
+    res
+}
+
+// follows the definitions, e.g. through double negations
+fn resolve(defs: &HashMap<RegNum, BoolValue>, value: &Value) -> BoolValue {
+    let mut res = (value.clone(), false);
+    // bounded, like the chains in remove_trivial_phis
+    for _ in 0..=defs.len() {
+        let (inner, negated) = match &res.0 {
+            Value::Register(reg_num, _) => match defs.get(reg_num) {
+                Some(def) => def.clone(),
+                None => break,
+            },
+            Value::LitBool(b) => return (Value::LitBool(*b != res.1), false),
+            _ => break,
+        };
+        res = (inner, res.1 != negated);
+    }
+    res
+}
+
+// replaces the registers which are equal to other values (not negated),
+// their definitions are removed
+fn fold_bool_values(fun: &mut Function) {
+    let defs = get_bool_definitions(fun);
+    let mut subst = HashMap::new();
+    for reg_num in defs.keys() {
+        let value = Value::Register(*reg_num, Type::Bool);
+        match resolve(&defs, &value) {
+            (inner, false) if inner != value => {
+                subst.insert(*reg_num, inner);
+            }
+            _ => (),
+        }
+    }
+    if subst.is_empty() {
+        return;
+    }
+    for bl in &mut fun.blocks {
+        bl.body.retain(
+            |op| !matches!(op.get_defined_reg(), Some(reg_num) if subst.contains_key(&reg_num)),
+        );
+        for value in bl.get_used_values_mut() {
+            substitute_value(value, &subst);
+        }
+        for phi in &mut bl.phis {
+            for value in phi.get_used_values_mut() {
+                substitute_value(value, &subst);
+            }
+        }
+    }
+}
+
+// negated conditions swap the targets, constant ones become jumps
+fn simplify_branches(fun: &mut Function) {
+    let defs = get_bool_definitions(fun);
+    let mut removed_edges = vec![];
+    for bl in &mut fun.blocks {
+        bl.terminator = match &bl.terminator {
+            Terminator::Branch2(cond, t, f, hint) => match resolve(&defs, cond) {
+                (_, _) if t == f => Terminator::Branch1(*t),
+                (Value::LitBool(b), _) => {
+                    let (taken, skipped) = if b { (*t, *f) } else { (*f, *t) };
+                    removed_edges.push((bl.label, skipped));
+                    Terminator::Branch1(taken)
+                }
+                (cond, false) => Terminator::Branch2(cond, *t, *f, *hint),
+                (cond, true) => Terminator::Branch2(cond, *f, *t, hint.negate()),
+            },
+            _ => continue,
+        };
+    }
+    for (from, to) in removed_edges {
+        fun.remove_edge(from, to);
+    }
+}
+
+fn remove_unreachable_blocks(fun: &mut Function) {
+    let mut reachable = HashSet::new();
+    let mut stack = vec![fun.blocks[0].label];
+    while let Some(label) = stack.pop() {
+        if reachable.insert(label) {
+            stack.extend(fun.get_block(label).get_successors());
+        }
+    }
+    if reachable.len() == fun.blocks.len() {
+        return;
+    }
+    let mut removed_edges = vec![];
+    for bl in fun
+        .blocks
+        .iter()
+        .filter(|bl| !reachable.contains(&bl.label))
+    {
+        for succ in bl.get_successors() {
+            removed_edges.push((bl.label, succ));
+        }
+    }
+    fun.blocks.retain(|bl| reachable.contains(&bl.label));
+    for (from, to) in removed_edges {
+        if reachable.contains(&to) {
+            fun.remove_edge(from, to);
+        }
+    }
+}
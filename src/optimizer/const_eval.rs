@@ -0,0 +1,75 @@
+use super::substitute_value;
+use model::ir::{Function, Operation, Program, Purity, RegNum, Value};
+use model::ir_interpreter::{is_constant, Interpreter};
+use std::collections::HashMap;
+
+// Compile-time evaluation of calls of pure functions (after inlining, see
+// purity) with constant arguments: the callee is run by the interpreter of
+// the IR (model::ir_interpreter) and the call is replaced with the result.
+// Only ints, longs and bools are known; a function doing anything else (like
+// using strings or calling error), dividing by zero or running out of the
+// budget isn't evaluated and the call stays. New constants can make other calls constant,
+// so it's repeated until nothing changes.
+
+// operations executed per call and for the whole program
+const CALL_BUDGET: u32 = 100_000;
+const PROGRAM_BUDGET: u32 = 2_000_000;
+
+pub fn evaluate_constant_calls(prog: &mut Program) {
+    let mut results = HashMap::new();
+    let mut budget_left = PROGRAM_BUDGET;
+    loop {
+        let pure_functions = prog
+            .functions
+            .iter()
+            .filter(|fun| fun.purity == Purity::Pure);
+        let mut interpreter = Interpreter::new(pure_functions).with_memo(results);
+        // call results of the functions, by their positions
+        let mut evaluated = vec![];
+        for (fun_idx, fun) in prog.functions.iter().enumerate() {
+            for bl in &fun.blocks {
+                for (op_idx, op) in bl.body.iter().enumerate() {
+                    let (reg_num, name, args) = match op {
+                        Operation::FunctionCall(
+                            Some(reg_num),
+                            _,
+                            Value::GlobalRegister(name, _),
+                            args,
+                        ) if args.iter().all(is_constant) => (*reg_num, name, args),
+                        _ => continue,
+                    };
+                    let steps = CALL_BUDGET.min(budget_left);
+                    interpreter.set_steps(steps);
+                    let res = interpreter.call(name, args.clone());
+                    budget_left -= steps - interpreter.get_steps_left();
+                    if let Ok(Some(value)) = res {
+                        evaluated.push((fun_idx, bl.label, op_idx, reg_num, value));
+                    }
+                }
+            }
+        }
+        results = interpreter.take_memo();
+        if evaluated.is_empty() {
+            break;
+        }
+        for (fun_idx, label, op_idx, reg_num, value) in evaluated.into_iter().rev() {
+            let fun = &mut prog.functions[fun_idx];
+            fun.get_block_mut(label).body.remove(op_idx);
+            let subst = [(reg_num, value)].iter().cloned().collect();
+            substitute_in_function(fun, &subst);
+        }
+    }
+}
+
+fn substitute_in_function(fun: &mut Function, subst: &HashMap<RegNum, Value>) {
+    for bl in &mut fun.blocks {
+        for phi in &mut bl.phis {
+            for value in phi.get_used_values_mut() {
+                substitute_value(value, subst);
+            }
+        }
+        for value in bl.get_used_values_mut() {
+            substitute_value(value, subst);
+        }
+    }
+}
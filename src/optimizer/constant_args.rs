@@ -0,0 +1,144 @@
+use model::ir::{Function, Operation, Program, RegNum, Value};
+use std::collections::{HashMap, HashSet};
+
+// Interprocedural constant propagation: the whole program is known, so if
+// every call of a function passes the same constant as an argument, the
+// constant replaces the parameter in the body of the function. Functions
+// which may be called indirectly (methods in the used vtables) or are
+// used as values are skipped, like main. Substituted constants can make
+// the arguments of calls in the callee constant, so it's repeated until
+// nothing changes.
+
+pub fn propagate_constant_args(prog: &mut Program) {
+    let skipped = get_indirectly_called_functions(prog);
+    loop {
+        let constant_args = get_constant_args(prog, &skipped);
+        let mut changed = false;
+        for fun in &mut prog.functions {
+            if let Some(args) = constant_args.get(&fun.name) {
+                changed |= substitute_args(fun, args);
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+}
+
+pub fn get_indirectly_called_functions(prog: &Program) -> HashSet<String> {
+    let mut res: HashSet<_> = prog
+        .classes
+        .iter()
+        .filter(|cl| cl.needs_vtable)
+        .flat_map(|cl| cl.vtable.iter().map(|(_, name)| name.clone()))
+        .collect();
+    res.insert("main".to_string());
+    for op in prog
+        .functions
+        .iter()
+        .flat_map(|fun| fun.blocks.iter().flat_map(|bl| &bl.body))
+    {
+        let mut values = op.get_used_values();
+        if let Operation::FunctionCall(..) = op {
+            values.remove(0); // the callee
+        }
+        for value in values {
+            if let Value::GlobalRegister(name, _) = value {
+                res.insert(name.clone());
+            }
+        }
+    }
+    res
+}
+
+// function -> its parameters with the constant passed by all the calls
+fn get_constant_args(
+    prog: &Program,
+    skipped: &HashSet<String>,
+) -> HashMap<String, Vec<(RegNum, Value)>> {
+    let params: HashMap<_, _> = prog
+        .functions
+        .iter()
+        .filter(|fun| !skipped.contains(&fun.name))
+        .map(|fun| (fun.name.as_str(), fun))
+        .collect();
+    // the constant of each parameter, None if it's not constant
+    let mut args: HashMap<&str, Vec<Option<Value>>> = HashMap::new();
+    for op in prog
+        .functions
+        .iter()
+        .flat_map(|fun| fun.blocks.iter().flat_map(|bl| &bl.body))
+    {
+        let (name, call_args) = match op {
+            Operation::FunctionCall(_, _, Value::GlobalRegister(name, _), call_args) => {
+                (name.as_str(), call_args)
+            }
+            _ => continue,
+        };
+        let fun = match params.get(name) {
+            Some(fun) => fun,
+            None => continue,
+        };
+        let typed_args: Vec<_> = call_args
+            .iter()
+            .zip(&fun.args)
+            .map(|(arg, (_, arg_type))| match arg {
+                Value::LitNullPtr(_) => Some(Value::LitNullPtr(Some(*arg_type))),
+                Value::LitInt(_)
+                | Value::LitLong(_)
+                | Value::LitBool(_)
+                | Value::LitZeroStruct(_) => Some(arg.clone()),
+                _ => None,
+            })
+            .collect();
+        match args.get_mut(name) {
+            Some(consts) => {
+                for (c, arg) in consts.iter_mut().zip(typed_args) {
+                    if *c != arg {
+                        *c = None;
+                    }
+                }
+            }
+            None => {
+                args.insert(name, typed_args);
+            }
+        }
+    }
+    args.into_iter()
+        .map(|(name, consts)| {
+            let fun = params[name];
+            let constant_params = fun
+                .args
+                .iter()
+                .zip(consts)
+                .filter_map(|((reg_num, _), c)| c.map(|c| (*reg_num, c)))
+                .collect();
+            (name.to_string(), constant_params)
+        })
+        .collect()
+}
+
+// returns if anything was substituted
+fn substitute_args(fun: &mut Function, args: &[(RegNum, Value)]) -> bool {
+    let subst: HashMap<_, _> = args.iter().cloned().collect();
+    let mut changed = false;
+    let mut substitute = |value: &mut Value| {
+        if let Value::Register(reg_num, _) = value {
+            if let Some(c) = subst.get(reg_num) {
+                *value = c.clone();
+                changed = true;
+            }
+        }
+    };
+    for bl in &mut fun.blocks {
+        for value in bl.get_used_values_mut() {
+            substitute(value);
+        }
+        for phi in &mut bl.phis {
+            for value in phi.get_used_values_mut() {
+                substitute(value);
+            }
+        }
+    }
+    changed
+}
@@ -0,0 +1,120 @@
+use super::purity::CallInfo;
+use super::{get_dominators, rename_reg};
+use model::ir::{Function, Label, Operation, Purity, RegNum};
+use std::collections::{HashMap, HashSet};
+
+// Common subexpression elimination: an operation computing the same as
+// an operation in a block dominating it (or before it in the same block)
+// is removed and its register is replaced with the earlier one. Calls of
+// pure functions are expressions like arithmetic, calls of read-only ones
+// only until something in the block may write memory.
+
+pub fn eliminate_common_subexpressions(fun: &mut Function, calls: &CallInfo) {
+    let dominators = get_dominators(fun);
+    // (the operation with its register zeroed) -> where it's computed
+    let mut computed: HashMap<Operation, Vec<(Label, RegNum)>> = HashMap::new();
+    let mut renamed = HashMap::new();
+    for label in get_dominance_order(fun, &dominators) {
+        let bl = fun.get_block_mut(label);
+        let mut read_only_calls: HashMap<Operation, RegNum> = HashMap::new();
+        let mut new_body = vec![];
+        for mut op in bl.body.drain(..) {
+            for value in op.get_used_values_mut() {
+                rename_reg(value, &renamed);
+            }
+            let kind = get_kind(&op, calls);
+            if kind == ExprKind::None {
+                if may_write_memory(&op, calls) {
+                    read_only_calls.clear();
+                }
+                new_body.push(op);
+                continue;
+            }
+            let reg_num = op.get_defined_reg().unwrap();
+            let mut key = op.clone();
+            *key.get_defined_reg_mut().unwrap() = RegNum(0);
+            let earlier = match kind {
+                ExprKind::Pure => computed.get(&key).and_then(|defs| {
+                    defs.iter()
+                        .find(|(def_label, _)| dominators[&label].contains(def_label))
+                        .map(|(_, def_reg)| *def_reg)
+                }),
+                _ => read_only_calls.get(&key).cloned(),
+            };
+            match earlier {
+                Some(def_reg) => {
+                    renamed.insert(reg_num, def_reg);
+                }
+                None => {
+                    match kind {
+                        ExprKind::Pure => computed.entry(key).or_default().push((label, reg_num)),
+                        _ => {
+                            read_only_calls.insert(key, reg_num);
+                        }
+                    }
+                    new_body.push(op);
+                }
+            }
+        }
+        bl.body = new_body;
+        for value in bl.terminator.get_used_values_mut() {
+            rename_reg(value, &renamed);
+        }
+    }
+    if renamed.is_empty() {
+        return;
+    }
+    // phis may use the registers before the blocks defining them
+    for bl in &mut fun.blocks {
+        for phi in &mut bl.phis {
+            for value in phi.get_used_values_mut() {
+                rename_reg(value, &renamed);
+            }
+        }
+    }
+}
+
+#[derive(PartialEq)]
+enum ExprKind {
+    None,
+    Pure,
+    ReadOnly,
+}
+
+fn get_kind(op: &Operation, calls: &CallInfo) -> ExprKind {
+    use self::Operation::*;
+    match op {
+        Arithmetic(..)
+        | Compare(..)
+        | Not(..)
+        | Select(..)
+        | GetElementPtr(..)
+        | CastGlobalString(..)
+        | CastPtr { .. }
+        | CastPtrToInt { .. }
+        | CastCharToInt { .. }
+        | ExtractValue { .. }
+        | InsertValue { .. } => ExprKind::Pure,
+        FunctionCall(Some(_), ..) => match calls.get_purity(op) {
+            Purity::Pure => ExprKind::Pure,
+            Purity::ReadOnly => ExprKind::ReadOnly,
+            Purity::Impure => ExprKind::None,
+        },
+        _ => ExprKind::None,
+    }
+}
+
+fn may_write_memory(op: &Operation, calls: &CallInfo) -> bool {
+    match op {
+        Operation::Store(..) | Operation::MemZero(..) => true,
+        Operation::FunctionCall(..) => !calls.is_non_clobbering(op),
+        _ => false,
+    }
+}
+
+// dominators come before the blocks they dominate
+fn get_dominance_order(fun: &Function, dominators: &HashMap<Label, HashSet<Label>>) -> Vec<Label> {
+    let mut order: Vec<_> = fun.blocks.iter().map(|bl| bl.label).collect();
+    order.sort_by_key(|label| dominators[label].len());
+    order
+}
@@ -0,0 +1,202 @@
+use super::get_dominators;
+use model::ir::{Function, Label, Operation, RegNum, Terminator, Type, Value};
+use std::collections::{HashMap, HashSet};
+
+// Objects and arrays (of constant size) which never escape the function
+// are allocated on the stack instead of the heap. An allocation escapes
+// if a pointer to it (or into it) is stored somewhere, returned, passed
+// to a function or compared... anything except loading and storing
+// through it and comparing it.
+//
+// The stack memory is allocated once, in the entry block, and zeroed at
+// the original allocation point, so allocations in loops don't grow
+// the stack. It's correct only if the old object is dead when the
+// allocation is executed again. In SSA it can survive only in a phi
+// (like `prev` in a loop creating new objects), so pointers can go only
+// through phis in blocks strictly dominated by the allocation.
+
+const MAX_STACK_ARRAY_SIZE: i32 = 1024;
+
+pub fn allocate_on_stack(fun: &mut Function) {
+    let mut next_reg = fun.get_next_free_reg_num().0;
+    let candidates = find_non_escaping_allocations(fun);
+    let mut allocas = vec![];
+
+    for bl in &mut fun.blocks {
+        let mut new_body = vec![];
+        for op in bl.body.drain(..) {
+            let (reg_num, callee, args) = match &op {
+                Operation::FunctionCall(Some(reg_num), _, Value::GlobalRegister(name, _), args)
+                    if candidates.contains_key(reg_num) =>
+                {
+                    (*reg_num, name.as_str(), args)
+                }
+                _ => {
+                    new_body.push(op);
+                    continue;
+                }
+            };
+            let void_ptr_type = Type::ptr(Type::Char);
+            match (callee, args.as_slice(), &candidates[&reg_num]) {
+                ("_bltn_malloc", [size_val], Some(class_type)) => {
+                    let slot_reg = RegNum(next_reg);
+                    next_reg += 1;
+                    allocas.push(Operation::Alloca(slot_reg, *class_type, 1));
+                    new_body.push(Operation::CastPtr {
+                        dst: reg_num,
+                        dst_type: void_ptr_type,
+                        src_value: Value::Register(slot_reg, Type::ptr(*class_type)),
+                    });
+                    new_body.push(Operation::MemZero(
+                        Value::Register(reg_num, void_ptr_type),
+                        size_val.clone(),
+                    ));
+                }
+                ("_bltn_alloc_array", [Value::LitInt(cnt), elem_size], _)
+                    if get_stack_array_size(*cnt, elem_size).is_some() =>
+                {
+                    // the same layout as in runtime: length, then elements
+                    let size = get_stack_array_size(*cnt, elem_size).unwrap() + 4;
+                    let slot_reg = RegNum(next_reg);
+                    let length_ptr_reg = RegNum(next_reg + 1);
+                    next_reg += 2;
+                    let slot_val = Value::Register(slot_reg, void_ptr_type);
+                    let int_ptr_type = Type::ptr(Type::Int);
+                    allocas.push(Operation::Alloca(slot_reg, Type::Char, size));
+                    new_body.push(Operation::MemZero(slot_val.clone(), Value::LitInt(size)));
+                    new_body.push(Operation::CastPtr {
+                        dst: length_ptr_reg,
+                        dst_type: int_ptr_type,
+                        src_value: slot_val.clone(),
+                    });
+                    new_body.push(Operation::Store(
+                        Value::LitInt(*cnt),
+                        Value::Register(length_ptr_reg, int_ptr_type),
+                    ));
+                    new_body.push(Operation::GetElementPtr(
+                        reg_num,
+                        Type::Char,
+                        vec![slot_val, Value::LitInt(4)],
+                    ));
+                }
+                _ => new_body.push(op),
+            }
+        }
+        bl.body = new_body;
+    }
+
+    let entry = &mut fun.blocks[0];
+    entry.body.splice(0..0, allocas);
+}
+
+// size of elements of a constant size array small enough for the stack;
+// the element size has the size type of the target, i32 or i64
+fn get_stack_array_size(cnt: i32, elem_size: &Value) -> Option<i32> {
+    let elem_size = match elem_size {
+        Value::LitInt(size) => i64::from(*size),
+        Value::LitLong(size) => *size,
+        _ => return None,
+    };
+    let size = i64::from(cnt) * elem_size;
+    if cnt > 0 && size <= i64::from(MAX_STACK_ARRAY_SIZE) {
+        Some(size as i32)
+    } else {
+        None
+    }
+}
+
+// returns allocation calls results which don't escape,
+// with the class type for objects
+fn find_non_escaping_allocations(fun: &Function) -> HashMap<RegNum, Option<Type>> {
+    let dominators = get_dominators(fun);
+    let mut uses: HashMap<_, Vec<_>> = HashMap::new();
+    let mut phi_uses: HashMap<_, Vec<_>> = HashMap::new();
+    let mut returned = HashSet::new();
+    for bl in &fun.blocks {
+        for phi in &bl.phis {
+            for value in phi.get_used_values() {
+                if let Value::Register(reg_num, _) = value {
+                    phi_uses
+                        .entry(*reg_num)
+                        .or_default()
+                        .push((phi.reg_num, bl.label));
+                }
+            }
+        }
+        for op in &bl.body {
+            for value in op.get_used_values() {
+                if let Value::Register(reg_num, _) = value {
+                    uses.entry(*reg_num).or_default().push(op);
+                }
+            }
+        }
+        if let Terminator::Return(Some(Value::Register(reg_num, _))) = bl.terminator {
+            returned.insert(reg_num);
+        }
+    }
+    let is_reg = |value: &Value, reg_num: RegNum| match value {
+        Value::Register(r, _) => *r == reg_num,
+        _ => false,
+    };
+
+    let mut result = HashMap::new();
+    let all_ops = fun
+        .blocks
+        .iter()
+        .flat_map(|bl| bl.body.iter().map(move |op| (bl.label, op)));
+    for (alloc_label, op) in all_ops {
+        let alloc_reg = match op {
+            Operation::FunctionCall(Some(reg_num), _, Value::GlobalRegister(name, _), _)
+                if name == "_bltn_malloc" || name == "_bltn_alloc_array" =>
+            {
+                *reg_num
+            }
+            _ => continue,
+        };
+
+        let mut class_type = None;
+        let mut escapes = false;
+        let mut derived = vec![alloc_reg];
+        let mut visited = HashSet::new();
+        let is_strictly_dominated =
+            |label: Label| label != alloc_label && dominators[&label].contains(&alloc_label);
+        while let Some(reg_num) = derived.pop() {
+            if !visited.insert(reg_num) {
+                continue;
+            }
+            if returned.contains(&reg_num) {
+                escapes = true;
+            }
+            for (phi_reg_num, phi_label) in phi_uses.get(&reg_num).map_or(&[][..], |v| &v[..]) {
+                if is_strictly_dominated(*phi_label) {
+                    derived.push(*phi_reg_num);
+                } else {
+                    escapes = true;
+                }
+            }
+            for use_op in uses.get(&reg_num).map_or(&[][..], |v| &v[..]) {
+                match use_op {
+                    Operation::CastPtr { dst, dst_type, .. } => {
+                        if reg_num == alloc_reg {
+                            class_type = dst_type.pointee();
+                        }
+                        derived.push(*dst);
+                    }
+                    Operation::GetElementPtr(dst, _, vals) if is_reg(&vals[0], reg_num) => {
+                        derived.push(*dst)
+                    }
+                    Operation::Load(_, _) | Operation::Compare(_, _, _, _) => (),
+                    Operation::Store(value, _) if !is_reg(value, reg_num) => (),
+                    _ => escapes = true,
+                }
+            }
+            if escapes {
+                break;
+            }
+        }
+        if !escapes {
+            result.insert(alloc_reg, class_type);
+        }
+    }
+    result
+}
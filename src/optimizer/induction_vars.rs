@@ -0,0 +1,317 @@
+use super::{get_predecessors, substitute_value};
+use model::ir::{ArithOp, CmpOp, Function, Label, Operation, Phi, RegNum, Type, Value};
+use std::collections::{HashMap, HashSet};
+
+// Strength reduction of array indexing in loops. For a basic induction
+// variable i (header phi incremented by a constant once per iteration)
+// and a loop invariant array `arr`:
+//
+//     i = phi [init, pre], [i', latch]         p = phi [arr+init, pre], [p', latch]
+//     ptr = gep arr, i                   =>    (uses of ptr replaced with p)
+//     i' = add i, step                         i' = add i, step
+//                                              p' = gep p, step
+//
+// Then compares of i (or i') with a loop invariant bound are rewritten
+// to compares of p (or p') with arr+bound, so usually i becomes dead
+// and is removed together with its increment. This is exactly how foreach
+// used to be lowered by hand, but it works for `while (i < n) a.[i]` too.
+
+pub fn simplify_induction_vars(fun: &mut Function) {
+    let mut next_reg = fun.get_next_free_reg_num().0;
+    for lp in find_loops(fun) {
+        reduce_loop(fun, &lp, &mut next_reg);
+    }
+}
+
+struct Loop {
+    header: Label,
+    pre: Label,
+    latch: Label,
+    blocks: HashSet<Label>,
+}
+
+struct InductionVar {
+    reg_num: RegNum,
+    next_reg_num: RegNum,
+    init: Value,
+    step: i32,
+    next_block: Label,
+}
+
+struct PointerVar {
+    reg_num: RegNum,
+    next_reg_num: RegNum,
+    elem_type: Type,
+    array: Value,
+}
+
+// loops with a single back edge and a single entry edge (codegen always
+// creates such loops, also after rotation)
+fn find_loops(fun: &Function) -> Vec<Loop> {
+    let preds = get_predecessors(fun);
+    let entry = fun.blocks[0].label;
+    let mut loops = vec![];
+    for bl in &fun.blocks {
+        let header = bl.label;
+        if preds[&header].len() != 2 {
+            continue;
+        }
+        for &(latch, pre) in &[
+            (preds[&header][0], preds[&header][1]),
+            (preds[&header][1], preds[&header][0]),
+        ] {
+            // natural loop of the back edge: blocks reaching the latch
+            let mut blocks = HashSet::new();
+            blocks.insert(header);
+            let mut stack = vec![latch];
+            while let Some(label) = stack.pop() {
+                if blocks.insert(label) {
+                    stack.extend(preds[&label].iter().cloned());
+                }
+            }
+            // reaching the entry means the header doesn't dominate the latch
+            if !blocks.contains(&pre) && !blocks.contains(&entry) {
+                loops.push(Loop {
+                    header,
+                    pre,
+                    latch,
+                    blocks,
+                });
+                break;
+            }
+        }
+    }
+    loops
+}
+
+fn reduce_loop(fun: &mut Function, lp: &Loop, next_reg: &mut u32) {
+    let mut defined_in_loop = HashSet::new();
+    let mut definitions = HashMap::new();
+    for bl in fun.blocks.iter().filter(|bl| lp.blocks.contains(&bl.label)) {
+        for phi in &bl.phis {
+            defined_in_loop.insert(phi.reg_num);
+        }
+        for op in &bl.body {
+            if let Some(reg_num) = op.get_defined_reg() {
+                defined_in_loop.insert(reg_num);
+                definitions.insert(reg_num, (op, bl.label));
+            }
+        }
+    }
+    let is_invariant = |value: &Value| match value {
+        Value::Register(reg_num, _) => !defined_in_loop.contains(reg_num),
+        _ => true,
+    };
+
+    let mut ivs = HashMap::new();
+    for phi in &fun.get_block(lp.header).phis {
+        if phi.reg_type != Type::Int || phi.incoming.len() != 2 {
+            continue;
+        }
+        let (init, next_reg_num) = match (phi.get_incoming(lp.pre), phi.get_incoming(lp.latch)) {
+            (Some(init), Some(Value::Register(next_reg_num, _))) => (init, *next_reg_num),
+            _ => continue,
+        };
+        let is_iv = |value: &Value| match value {
+            Value::Register(r, _) => *r == phi.reg_num,
+            _ => false,
+        };
+        let (step, next_block) = match definitions.get(&next_reg_num) {
+            Some((Operation::Arithmetic(_, ArithOp::Add, v1, Value::LitInt(step)), label))
+            | Some((Operation::Arithmetic(_, ArithOp::Add, Value::LitInt(step), v1), label))
+                if is_iv(v1) =>
+            {
+                (*step, *label)
+            }
+            Some((Operation::Arithmetic(_, ArithOp::Sub, v1, Value::LitInt(step)), label))
+                if is_iv(v1) =>
+            {
+                // the step of i - MIN doesn't fit
+                match step.checked_neg() {
+                    Some(step) => (step, *label),
+                    None => continue,
+                }
+            }
+            _ => continue,
+        };
+        ivs.insert(
+            phi.reg_num,
+            InductionVar {
+                reg_num: phi.reg_num,
+                next_reg_num,
+                init: init.clone(),
+                step,
+                next_block,
+            },
+        );
+    }
+
+    // find indexing of invariant arrays with induction variables
+    let mut ptr_vars: HashMap<(RegNum, Value), PointerVar> = HashMap::new();
+    let mut subst = HashMap::new();
+    for bl in fun.blocks.iter().filter(|bl| lp.blocks.contains(&bl.label)) {
+        for op in &bl.body {
+            let (reg_num, elem_type, array, iv) = match op {
+                Operation::GetElementPtr(reg_num, elem_type, vals) if vals.len() == 2 => {
+                    match (&vals[0], &vals[1]) {
+                        (array, Value::Register(idx, _)) if ivs.contains_key(idx) => {
+                            (reg_num, elem_type, array, &ivs[idx])
+                        }
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
+            if !is_invariant(array) {
+                continue;
+            }
+            let ptr_var = ptr_vars
+                .entry((iv.reg_num, array.clone()))
+                .or_insert_with(|| {
+                    *next_reg += 2;
+                    PointerVar {
+                        reg_num: RegNum(*next_reg - 2),
+                        next_reg_num: RegNum(*next_reg - 1),
+                        elem_type: *elem_type,
+                        array: array.clone(),
+                    }
+                });
+            let ptr_type = Type::ptr(ptr_var.elem_type);
+            subst.insert(*reg_num, Value::Register(ptr_var.reg_num, ptr_type));
+        }
+    }
+    if ptr_vars.is_empty() {
+        return;
+    }
+
+    // linear function test replacement, only for simple counting loops
+    // (i < n with i++ or i > n with i--), so it's safe from overflows
+    let mut bounds = vec![];
+    let mut new_compares = HashMap::new();
+    for bl in fun.blocks.iter().filter(|bl| lp.blocks.contains(&bl.label)) {
+        for op in &bl.body {
+            let (reg_num, cmp_op, val1, val2) = match op {
+                Operation::Compare(reg_num, CmpOp::LT, val1, val2) => {
+                    (reg_num, CmpOp::LT, val1, val2)
+                }
+                Operation::Compare(reg_num, CmpOp::GT, val1, val2) => {
+                    (reg_num, CmpOp::GT, val1, val2)
+                }
+                _ => continue,
+            };
+            let as_iv = |value: &Value| -> Option<(&InductionVar, bool)> {
+                match value {
+                    Value::Register(r, _) => ivs
+                        .values()
+                        .find(|iv| iv.reg_num == *r || iv.next_reg_num == *r)
+                        .map(|iv| (iv, iv.next_reg_num == *r)),
+                    _ => None,
+                }
+            };
+            let (iv, is_next, bound, iv_on_left) = match (as_iv(val1), as_iv(val2)) {
+                (Some((iv, is_next)), None) if is_invariant(val2) => (iv, is_next, val2, true),
+                (None, Some((iv, is_next))) if is_invariant(val1) => (iv, is_next, val1, false),
+                _ => continue,
+            };
+            let counts_up = matches!(
+                (&cmp_op, iv_on_left),
+                (CmpOp::LT, true) | (CmpOp::GT, false)
+            );
+            if !(counts_up && iv.step == 1 || !counts_up && iv.step == -1) {
+                continue;
+            }
+            // if the variable indexes many arrays, any of them is fine
+            let ptr_var = ptr_vars
+                .iter()
+                .filter(|((iv_reg_num, _), _)| *iv_reg_num == iv.reg_num)
+                .map(|(_, ptr_var)| ptr_var)
+                .min_by_key(|ptr_var| ptr_var.reg_num.0)
+                .unwrap();
+            let ptr_type = Type::ptr(ptr_var.elem_type);
+            let bound_reg = RegNum(*next_reg);
+            *next_reg += 1;
+            bounds.push(Operation::GetElementPtr(
+                bound_reg,
+                ptr_var.elem_type,
+                vec![ptr_var.array.clone(), bound.clone()],
+            ));
+            let ptr_reg = if is_next {
+                ptr_var.next_reg_num
+            } else {
+                ptr_var.reg_num
+            };
+            let ptr_val = Value::Register(ptr_reg, ptr_type);
+            let bound_val = Value::Register(bound_reg, ptr_type);
+            let (val1, val2) = if iv_on_left {
+                (ptr_val, bound_val)
+            } else {
+                (bound_val, ptr_val)
+            };
+            new_compares.insert(*reg_num, Operation::Compare(*reg_num, cmp_op, val1, val2));
+        }
+    }
+
+    // apply the changes
+    let mut pre_ops = vec![];
+    let mut ptr_vars: Vec<_> = ptr_vars.into_iter().collect();
+    ptr_vars.sort_by_key(|(_, ptr_var)| ptr_var.reg_num.0);
+    for ((iv_reg_num, _), ptr_var) in &ptr_vars {
+        let iv = &ivs[iv_reg_num];
+        let ptr_type = Type::ptr(ptr_var.elem_type);
+        let init_reg = RegNum(*next_reg);
+        *next_reg += 1;
+        pre_ops.push(Operation::GetElementPtr(
+            init_reg,
+            ptr_var.elem_type,
+            vec![ptr_var.array.clone(), iv.init.clone()],
+        ));
+        fun.get_block_mut(lp.header).add_phi(Phi::new(
+            ptr_var.reg_num,
+            ptr_type,
+            vec![
+                (Value::Register(init_reg, ptr_type), lp.pre),
+                (Value::Register(ptr_var.next_reg_num, ptr_type), lp.latch),
+            ],
+        ));
+        let next_bl = fun.get_block_mut(iv.next_block);
+        let pos = next_bl
+            .body
+            .iter()
+            .position(|op| op.get_defined_reg() == Some(iv.next_reg_num))
+            .unwrap();
+        next_bl.body.insert(
+            pos + 1,
+            Operation::GetElementPtr(
+                ptr_var.next_reg_num,
+                ptr_var.elem_type,
+                vec![
+                    Value::Register(ptr_var.reg_num, ptr_type),
+                    Value::LitInt(iv.step),
+                ],
+            ),
+        );
+    }
+    pre_ops.extend(bounds);
+    fun.get_block_mut(lp.pre).body.extend(pre_ops);
+
+    for bl in &mut fun.blocks {
+        for phi in &mut bl.phis {
+            for value in phi.get_used_values_mut() {
+                substitute_value(value, &subst);
+            }
+        }
+        for op in &mut bl.body {
+            if let Operation::Compare(reg_num, _, _, _) = op {
+                if let Some(new_op) = new_compares.remove(reg_num) {
+                    *op = new_op;
+                }
+            }
+            for value in op.get_used_values_mut() {
+                substitute_value(value, &subst);
+            }
+        }
+        for value in bl.terminator.get_used_values_mut() {
+            substitute_value(value, &subst);
+        }
+    }
+}
@@ -0,0 +1,267 @@
+use super::get_source_span;
+use super::profile::Profile;
+use model::ir::{
+    format_class_vtable_data, get_class_vtable_type, Block, BranchHint, Class, CmpOp, Function,
+    Label, Operation, Phi, Program, RegNum, Terminator, Type, TypeKind, Value,
+};
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+// Inline caches of virtual calls (-O2): a call through the vtable of a class
+// whose objects are mostly of one class of its subtree compares the loaded
+// vtable with the vtable of that class and, if it's the same, calls its
+// method directly (so it can be inlined), else through the vtable:
+//   %vt = load ...; %m = load (gep %vt, 0, slot); %r = call %m(%obj, ...)
+// becomes
+//   %vt = load ...; %m = load ...; br (%vt == @cls.C.vtable.data), direct, indirect
+//   direct: %r1 = call @C.m(%obj, ...)    indirect: %r2 = call %m(%obj, ...)
+//   tail: %r = phi [%r1, direct], [%r2, indirect]
+// A class dominates when it has more than half of the allocation sites
+// (stores of vtables) of the subtree in the program. With a profile the
+// sites are counted as many times as they were executed and calls which
+// were never executed aren't changed.
+// Only methods defined in the program are called directly (not the ones
+// of builtin classes, implemented in the runtime). Runs before inlining.
+
+struct Site {
+    label: Label,
+    op_idx: usize, // of the call
+    vtable: Value,
+    class_name: String,
+    method: Value, // the implementation in the class
+}
+
+pub fn add_inline_caches(prog: &mut Program, profile: Option<&Profile>) {
+    let allocations = count_allocations(prog, profile);
+    let fun_names: HashSet<_> = prog.functions.iter().map(|f| f.name.clone()).collect();
+    for fun in &mut prog.functions {
+        let mut sites = find_sites(fun, &prog.classes, &allocations, &fun_names);
+        if let Some(profile) = profile {
+            sites.retain(|site| profile.get_count(fun, site.label) != Some(0));
+        }
+        // later calls of a block first, splitting it doesn't move the earlier ones
+        sites.sort_by_key(|site| (site.label.0, usize::MAX - site.op_idx));
+        for site in sites {
+            add_inline_cache(fun, site);
+        }
+    }
+}
+
+// class -> number of places where its objects are created
+// (or of their executions, with a profile)
+fn count_allocations(prog: &Program, profile: Option<&Profile>) -> HashMap<String, u64> {
+    let vtables: HashMap<_, _> = prog
+        .classes
+        .iter()
+        .map(|cl| (format_class_vtable_data(&cl.name), cl.name.as_str()))
+        .collect();
+    let mut res = HashMap::new();
+    for fun in &prog.functions {
+        for bl in &fun.blocks {
+            let weight = match profile {
+                Some(profile) => profile.get_count(fun, bl.label).unwrap_or(0),
+                None => 1,
+            };
+            for op in &bl.body {
+                if let Operation::Store(Value::GlobalRegister(name, _), _) = op {
+                    if let Some(cl_name) = vtables.get(name) {
+                        *res.entry(cl_name.to_string()).or_insert(0) += weight;
+                    }
+                }
+            }
+        }
+    }
+    res
+}
+
+fn is_in_subtree(classes: &[Class], cl: &Class, root: &str) -> bool {
+    let mut cur = Some(cl);
+    while let Some(cl) = cur {
+        if cl.name == root {
+            return true;
+        }
+        cur = cl
+            .parent
+            .as_ref()
+            .map(|parent| classes.iter().find(|c| c.name == *parent).unwrap());
+    }
+    false
+}
+
+fn find_sites(
+    fun: &Function,
+    classes: &[Class],
+    allocations: &HashMap<String, u64>,
+    fun_names: &HashSet<String>,
+) -> Vec<Site> {
+    let defs: HashMap<_, _> = fun
+        .blocks
+        .iter()
+        .flat_map(|bl| &bl.body)
+        .filter_map(|op| op.get_defined_reg().map(|reg_num| (reg_num, op)))
+        .collect();
+    let mut res = vec![];
+    for bl in &fun.blocks {
+        for (op_idx, op) in bl.body.iter().enumerate() {
+            let method_reg = match op {
+                Operation::FunctionCall(_, _, Value::Register(reg_num, _), _) => reg_num,
+                _ => continue,
+            };
+            // the method loaded from a slot of a vtable
+            let method_ptr_reg = match defs.get(method_reg) {
+                Some(Operation::Load(_, Value::Register(reg_num, _))) => reg_num,
+                _ => continue,
+            };
+            let (vtable_type, gep_args) = match defs.get(method_ptr_reg) {
+                Some(Operation::GetElementPtr(_, t, args)) => match t.kind() {
+                    TypeKind::Class(name) => (name, args),
+                    _ => continue,
+                },
+                _ => continue,
+            };
+            let (static_class, vtable, slot) = match (vtable_type, gep_args.as_slice()) {
+                (name, [vtable, Value::LitInt(0), Value::LitInt(slot)])
+                    if name.ends_with(".vtable.type") =>
+                {
+                    let static_class = &name[..name.len() - ".vtable.type".len()];
+                    (static_class, vtable, *slot as usize)
+                }
+                _ => continue,
+            };
+
+            let candidates: Vec<_> = classes
+                .iter()
+                .filter(|cl| is_in_subtree(classes, cl, static_class))
+                .map(|cl| (cl, allocations.get(&cl.name).cloned().unwrap_or(0)))
+                .collect();
+            let total: u64 = candidates.iter().map(|(_, count)| count).sum();
+            let (cl, count) = match candidates.iter().max_by_key(|(_, count)| *count) {
+                Some(best) => best,
+                None => continue,
+            };
+            let (method_type, method_name) = &cl.vtable[slot];
+            if 2 * count <= total || !fun_names.contains(method_name) {
+                continue;
+            }
+            res.push(Site {
+                label: bl.label,
+                op_idx,
+                vtable: vtable.clone(),
+                class_name: cl.name.clone(),
+                method: Value::GlobalRegister(method_name.clone(), *method_type),
+            });
+        }
+    }
+    res
+}
+
+fn add_inline_cache(fun: &mut Function, site: Site) {
+    let tail_label = fun.get_next_free_label();
+    let direct_label = Label(tail_label.0 + 1);
+    let indirect_label = Label(tail_label.0 + 2);
+    let mut next_reg = fun.get_next_free_reg_num().0;
+    let mut new_reg = || {
+        next_reg += 1;
+        RegNum(next_reg - 1)
+    };
+
+    // split the block: ops after the call and the terminator are moved
+    // to the tail block
+    let bl = fun.get_block_mut(site.label);
+    let mut tail_body = bl.body.split_off(site.op_idx + 1);
+    let span = get_source_span(&bl.body);
+    tail_body.splice(0..0, span.clone());
+    let tail_terminator = mem::replace(&mut bl.terminator, Terminator::Unreachable);
+    let (call_dst, ret_type, method_val, args) = match bl.body.pop() {
+        Some(Operation::FunctionCall(dst, ret_type, method_val, args)) => {
+            (dst, ret_type, method_val, args)
+        }
+        _ => unreachable!(),
+    };
+
+    let vtable_type = site.vtable.get_type();
+    let mut expected = Value::GlobalRegister(
+        format_class_vtable_data(&site.class_name),
+        get_class_vtable_type(&site.class_name),
+    );
+    if expected.get_type() != vtable_type {
+        let casted_reg = new_reg();
+        bl.body.push(Operation::CastPtr {
+            dst: casted_reg,
+            dst_type: vtable_type,
+            src_value: expected,
+        });
+        expected = Value::Register(casted_reg, vtable_type);
+    }
+    let cond_reg = new_reg();
+    bl.body.push(Operation::Compare(
+        cond_reg,
+        CmpOp::EQ,
+        site.vtable,
+        expected,
+    ));
+    bl.terminator = Terminator::Branch2(
+        Value::Register(cond_reg, Type::Bool),
+        direct_label,
+        indirect_label,
+        BranchHint::Likely,
+    );
+
+    // the direct call, with this of the class of the implementation
+    let mut direct_body: Vec<_> = span.clone().into_iter().collect();
+    let mut direct_args = args.clone();
+    if let TypeKind::Ptr(t) = site.method.get_type().kind() {
+        if let TypeKind::Func(_, args_types) = t.kind() {
+            if direct_args[0].get_type() != args_types[0] {
+                let casted_reg = new_reg();
+                direct_body.push(Operation::CastPtr {
+                    dst: casted_reg,
+                    dst_type: args_types[0],
+                    src_value: direct_args[0].clone(),
+                });
+                direct_args[0] = Value::Register(casted_reg, args_types[0]);
+            }
+        }
+    }
+    let direct_dst = call_dst.map(|_| new_reg());
+    direct_body.push(Operation::FunctionCall(
+        direct_dst,
+        ret_type,
+        site.method,
+        direct_args,
+    ));
+
+    let indirect_dst = call_dst.map(|_| new_reg());
+    let mut indirect_body: Vec<_> = span.into_iter().collect();
+    indirect_body.push(Operation::FunctionCall(
+        indirect_dst,
+        ret_type,
+        method_val,
+        args,
+    ));
+
+    fun.move_edges(&tail_terminator, site.label, tail_label);
+
+    let mut tail_bl = Block::new(tail_label);
+    tail_bl.body = tail_body;
+    tail_bl.terminator = tail_terminator;
+    if let (Some(dst), Some(direct_dst), Some(indirect_dst)) = (call_dst, direct_dst, indirect_dst)
+    {
+        let entries = vec![
+            (Value::Register(direct_dst, ret_type), direct_label),
+            (Value::Register(indirect_dst, ret_type), indirect_label),
+        ];
+        tail_bl.add_phi(Phi::new(dst, ret_type, entries));
+    }
+    for (label, body) in [(direct_label, direct_body), (indirect_label, indirect_body)] {
+        let mut bl = Block::new(label);
+        bl.body = body;
+        bl.terminator = Terminator::Branch1(tail_label);
+        fun.blocks.push(bl);
+    }
+    fun.blocks.push(tail_bl);
+    for label in [direct_label, indirect_label] {
+        fun.add_edge(site.label, label);
+        fun.add_edge(label, tail_label);
+    }
+}
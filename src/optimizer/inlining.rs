@@ -0,0 +1,331 @@
+use super::profile::Profile;
+use super::{get_source_span, rename_reg, substitute_value};
+use model::ast::InlineHint;
+use model::ir::{Block, Function, Label, Operation, Phi, Program, RegNum, Terminator, Type, Value};
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+// Inlines direct calls to small functions (at most `threshold` operations)
+// and to functions marked `inline`; functions marked `noinline` are never
+// inlined. Functions are processed in the call graph post-order, so callees
+// are already simplified. Recursive functions are never inlined.
+// With a profile, calls which were never executed are inlined only if the
+// callee is marked `inline` and hot calls get HOT_THRESHOLD_FACTOR times
+// bigger threshold.
+
+// stop inlining into a function when it gets this big
+const MAX_CALLER_SIZE: usize = 2000;
+const HOT_THRESHOLD_FACTOR: usize = 4;
+
+pub fn inline_functions(prog: &mut Program, threshold: usize, profile: Option<&Profile>) {
+    let call_graph = get_call_graph(prog);
+    for fun_name in get_post_order(&call_graph) {
+        let fun_idx = prog
+            .functions
+            .iter()
+            .position(|f| f.name == fun_name)
+            .unwrap();
+        let mut fun = prog.functions.swap_remove(fun_idx);
+        // of the blocks known to the profile, the tails of split blocks inherit them
+        let mut counts: HashMap<_, _> = match profile {
+            Some(profile) => fun
+                .blocks
+                .iter()
+                .filter_map(|bl| Some((bl.label, profile.get_count(&fun, bl.label)?)))
+                .collect(),
+            None => HashMap::new(),
+        };
+        while get_size(&fun) < MAX_CALLER_SIZE {
+            let site = find_call_site(&fun, prog, &call_graph, |label| {
+                match (profile, counts.get(&label)) {
+                    (Some(_), Some(0)) => None,
+                    (Some(profile), Some(count)) if profile.is_hot(*count) => {
+                        Some(threshold * HOT_THRESHOLD_FACTOR)
+                    }
+                    _ => Some(threshold),
+                }
+            });
+            match site {
+                Some((label, op_idx, callee_idx)) => {
+                    if let Some(count) = counts.get(&label).cloned() {
+                        counts.insert(fun.get_next_free_label(), count);
+                    }
+                    inline_call(&mut fun, label, op_idx, &prog.functions[callee_idx])
+                }
+                None => break,
+            }
+        }
+        prog.functions.push(fun);
+        let last = prog.functions.len() - 1;
+        prog.functions.swap(fun_idx, last);
+    }
+}
+
+// with the terminators, without source span markers (so they don't change
+// the decisions)
+fn get_size(fun: &Function) -> usize {
+    let ops = fun
+        .blocks
+        .iter()
+        .flat_map(|bl| &bl.body)
+        .filter(|op| !matches!(op, Operation::SourceSpan(_)))
+        .count();
+    ops + fun.blocks.len()
+}
+
+fn get_direct_callee(op: &Operation) -> Option<&str> {
+    match op {
+        Operation::FunctionCall(_, _, Value::GlobalRegister(name, _), _) => Some(name),
+        _ => None,
+    }
+}
+
+// function -> functions called directly (builtins are skipped)
+fn get_call_graph(prog: &Program) -> HashMap<String, HashSet<String>> {
+    let names: HashSet<_> = prog.functions.iter().map(|f| f.name.as_str()).collect();
+    let mut graph = HashMap::new();
+    for fun in &prog.functions {
+        let callees = fun
+            .blocks
+            .iter()
+            .flat_map(|bl| bl.body.iter())
+            .filter_map(get_direct_callee)
+            .filter(|name| names.contains(name))
+            .map(|name| name.to_string())
+            .collect();
+        graph.insert(fun.name.clone(), callees);
+    }
+    graph
+}
+
+fn get_post_order(call_graph: &HashMap<String, HashSet<String>>) -> Vec<String> {
+    fn visit<'a>(
+        name: &'a str,
+        call_graph: &'a HashMap<String, HashSet<String>>,
+        visited: &mut HashSet<&'a str>,
+        order: &mut Vec<String>,
+    ) {
+        if !visited.insert(name) {
+            return;
+        }
+        let mut callees: Vec<_> = call_graph[name].iter().collect();
+        callees.sort();
+        for callee in callees {
+            visit(callee, call_graph, visited, order);
+        }
+        order.push(name.to_string());
+    }
+
+    let mut names: Vec<_> = call_graph.keys().collect();
+    names.sort();
+    let mut visited = HashSet::new();
+    let mut order = vec![];
+    for name in names {
+        visit(name, call_graph, &mut visited, &mut order);
+    }
+    order
+}
+
+// also calls inside cycles of mutually recursive functions
+fn is_recursive(call_graph: &HashMap<String, HashSet<String>>, name: &str) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack: Vec<_> = call_graph[name].iter().map(|s| s.as_str()).collect();
+    while let Some(callee) = stack.pop() {
+        if callee == name {
+            return true;
+        }
+        if visited.insert(callee) {
+            stack.extend(call_graph[callee].iter().map(|s| s.as_str()));
+        }
+    }
+    false
+}
+
+// get_threshold gives the threshold of calls in the block,
+// None if only functions marked `inline` are inlined there
+fn find_call_site<F>(
+    fun: &Function,
+    prog: &Program,
+    call_graph: &HashMap<String, HashSet<String>>,
+    get_threshold: F,
+) -> Option<(Label, usize, usize)>
+where
+    F: Fn(Label) -> Option<usize>,
+{
+    for bl in &fun.blocks {
+        let threshold = get_threshold(bl.label);
+        for (op_idx, op) in bl.body.iter().enumerate() {
+            let callee_name = match get_direct_callee(op) {
+                Some(name) => name,
+                None => continue,
+            };
+            let callee_idx = match prog.functions.iter().position(|f| f.name == callee_name) {
+                Some(idx) => idx,
+                None => continue, // builtin or the function itself
+            };
+            let callee = &prog.functions[callee_idx];
+            let wanted = match callee.inline_hint {
+                InlineHint::Always => true,
+                InlineHint::Never => false,
+                InlineHint::Auto => threshold.is_some_and(|t| get_size(callee) <= t),
+            };
+            if wanted && can_be_inlined(callee) && !is_recursive(call_graph, callee_name) {
+                return Some((bl.label, op_idx, callee_idx));
+            }
+        }
+    }
+    None
+}
+
+// the call result must be defined after inlining
+fn can_be_inlined(callee: &Function) -> bool {
+    let mut has_return = false;
+    for bl in &callee.blocks {
+        match bl.terminator {
+            Terminator::Return(None) if callee.ret_type != Type::Void => return false,
+            Terminator::Return(_) => has_return = true,
+            _ => (),
+        }
+    }
+    has_return
+}
+
+fn inline_call(fun: &mut Function, label: Label, op_idx: usize, callee: &Function) {
+    let mut next_label = fun.get_next_free_label().0;
+    let mut next_reg = fun.get_next_free_reg_num().0;
+
+    // split the block: ops after the call and the terminator are moved
+    // to the new block
+    let tail_label = Label(next_label);
+    next_label += 1;
+    let bl = fun.get_block_mut(label);
+    let mut tail_body = bl.body.split_off(op_idx + 1);
+    tail_body.splice(0..0, get_source_span(&bl.body));
+    let tail_terminator = mem::replace(&mut bl.terminator, Terminator::Unreachable);
+    let (call_dst, args) = match bl.body.pop() {
+        Some(Operation::FunctionCall(dst, _, _, args)) => (dst, args),
+        _ => unreachable!(),
+    };
+    fun.move_edges(&tail_terminator, label, tail_label);
+
+    // copy the callee with fresh labels and registers
+    let mut label_map = HashMap::new();
+    for callee_bl in &callee.blocks {
+        label_map.insert(callee_bl.label, Label(next_label));
+        next_label += 1;
+    }
+    let args_subst: HashMap<_, _> = callee
+        .args
+        .iter()
+        .map(|(reg_num, _)| *reg_num)
+        .zip(args)
+        .collect();
+    let mut renamed = HashMap::new();
+    for callee_bl in &callee.blocks {
+        let defined_regs = callee_bl
+            .phis
+            .iter()
+            .map(|phi| phi.reg_num)
+            .chain(callee_bl.body.iter().filter_map(|op| op.get_defined_reg()));
+        for reg_num in defined_regs {
+            renamed.insert(reg_num, RegNum(next_reg));
+            next_reg += 1;
+        }
+    }
+    // exactly one of the maps applies: chaining them would mix caller's and
+    // callee's registers (renamed registers may collide with the callee's args)
+    let map_value = |value: &mut Value| {
+        let is_arg = match value {
+            Value::Register(reg_num, _) => args_subst.contains_key(reg_num),
+            _ => false,
+        };
+        if is_arg {
+            substitute_value(value, &args_subst);
+        } else {
+            rename_reg(value, &renamed);
+        }
+    };
+
+    let mut returns = vec![];
+    let mut new_blocks = vec![];
+    for callee_bl in &callee.blocks {
+        let new_label = label_map[&callee_bl.label];
+        let phis = callee_bl
+            .phis
+            .iter()
+            .map(|phi| {
+                let incoming = phi
+                    .incoming
+                    .iter()
+                    .map(|(value, l)| {
+                        let mut value = value.clone();
+                        map_value(&mut value);
+                        (value, label_map[l])
+                    })
+                    .collect();
+                Phi::new(renamed[&phi.reg_num], phi.reg_type, incoming)
+            })
+            .collect();
+        let mut body = vec![];
+        for op in &callee_bl.body {
+            let mut op = op.clone();
+            for value in op.get_used_values_mut() {
+                map_value(value);
+            }
+            if let Some(reg_num) = op.get_defined_reg_mut() {
+                *reg_num = renamed[reg_num];
+            }
+            body.push(op);
+        }
+        let mut terminator = match &callee_bl.terminator {
+            Terminator::Return(opt_value) => {
+                let mut opt_value = opt_value.clone();
+                if let Some(value) = &mut opt_value {
+                    map_value(value);
+                }
+                returns.push((opt_value, new_label));
+                Terminator::Branch1(tail_label)
+            }
+            Terminator::Branch1(l) => Terminator::Branch1(label_map[l]),
+            Terminator::Branch2(value, l1, l2, hint) => {
+                Terminator::Branch2(value.clone(), label_map[l1], label_map[l2], *hint)
+            }
+            Terminator::Unreachable => Terminator::Unreachable,
+        };
+        for value in terminator.get_used_values_mut() {
+            map_value(value);
+        }
+        let predecessors = callee_bl
+            .predecessors
+            .iter()
+            .map(|l| label_map[l])
+            .collect();
+        new_blocks.push(Block {
+            label: new_label,
+            phis,
+            predecessors,
+            body,
+            terminator,
+        });
+    }
+
+    let callee_entry = new_blocks[0].label;
+    let mut tail_bl = Block::new(tail_label);
+    tail_bl.body = tail_body;
+    tail_bl.terminator = tail_terminator;
+    if let Some(dst) = call_dst {
+        let entries = returns
+            .iter()
+            .map(|(opt_value, l)| (opt_value.clone().unwrap(), *l))
+            .collect();
+        tail_bl.add_phi(Phi::new(dst, callee.ret_type, entries));
+    }
+    fun.blocks.extend(new_blocks);
+    fun.blocks.push(tail_bl);
+
+    fun.get_block_mut(label).terminator = Terminator::Branch1(callee_entry);
+    fun.add_edge(label, callee_entry);
+    for (_, l) in returns {
+        fun.add_edge(l, tail_label);
+    }
+}
@@ -0,0 +1,96 @@
+use super::get_loop_depths;
+use model::ir::{Function, Operation, Program};
+use options::ReportFormat;
+
+// complexity of the generated code, per function (e.g. for grading assignments
+// with complexity constraints or checking what the optimizer did)
+struct FunctionStats<'a> {
+    name: &'a str,
+    blocks: usize,
+    instructions: usize,
+    phis: usize,
+    max_loop_depth: usize,
+    stack_bytes: i64, // only explicit stack allocations, without spills
+}
+
+pub fn format_ir_stats(prog: &Program, format: ReportFormat) -> String {
+    let stats: Vec<_> = prog
+        .functions
+        .iter()
+        .map(|fun| FunctionStats {
+            name: &fun.name,
+            blocks: fun.blocks.len(),
+            instructions: fun
+                .blocks
+                .iter()
+                .flat_map(|bl| &bl.body)
+                .filter(|op| !matches!(op, Operation::SourceSpan(_)))
+                .count()
+                + fun.blocks.len(), // the terminators
+            phis: fun.blocks.iter().map(|bl| bl.phis.len()).sum(),
+            max_loop_depth: get_loop_depths(fun).values().cloned().max().unwrap_or(0),
+            stack_bytes: get_stack_usage(prog, fun),
+        })
+        .collect();
+    match format {
+        ReportFormat::Table => format_table(&stats),
+        ReportFormat::Json => format_json(&stats),
+    }
+}
+
+fn format_table(stats: &[FunctionStats]) -> String {
+    let name_width = stats
+        .iter()
+        .map(|st| st.name.len())
+        .chain(Some("function".len()))
+        .max()
+        .unwrap();
+    let mut res = format!(
+        "{:<w$}  {:>6}  {:>6}  {:>6}  {:>10}  {:>11}\n",
+        "function",
+        "blocks",
+        "instrs",
+        "phis",
+        "loop depth",
+        "stack bytes",
+        w = name_width
+    );
+    for st in stats {
+        res += &format!(
+            "{:<w$}  {:>6}  {:>6}  {:>6}  {:>10}  {:>11}\n",
+            st.name,
+            st.blocks,
+            st.instructions,
+            st.phis,
+            st.max_loop_depth,
+            st.stack_bytes,
+            w = name_width
+        );
+    }
+    res
+}
+
+fn format_json(stats: &[FunctionStats]) -> String {
+    let mut res = "[\n".to_string();
+    for (i, st) in stats.iter().enumerate() {
+        let name = st.name.replace("\\", "\\\\").replace("\"", "\\\"");
+        res += &format!(
+            "  {{\"function\": \"{}\", \"blocks\": {}, \"instructions\": {}, \"phis\": {}, \"max_loop_depth\": {}, \"stack_bytes\": {}}}",
+            name, st.blocks, st.instructions, st.phis, st.max_loop_depth, st.stack_bytes
+        );
+        res += if i + 1 < stats.len() { ",\n" } else { "\n" };
+    }
+    res + "]\n"
+}
+
+pub fn get_stack_usage(prog: &Program, fun: &Function) -> i64 {
+    let mut res = 0;
+    for bl in &fun.blocks {
+        for op in &bl.body {
+            if let Operation::Alloca(_, elem_type, cnt) = op {
+                res += prog.get_size_and_align(elem_type).0 * i64::from(*cnt);
+            }
+        }
+    }
+    res
+}
@@ -0,0 +1,264 @@
+use super::{get_predecessors, get_reachable_blocks, rename_reg, substitute_value};
+use model::ir::{Function, Label, Phi, RegNum, Terminator, Value};
+use std::collections::{HashMap, HashSet};
+
+// Codegen emits loops with the condition checked at the top:
+//
+//     pre:   ...; br cond
+//     cond:  phis; <test>; br %c, body, cont
+//     body:  ...; br cond            (latch, may be another block)
+//     cont:  ...
+//
+// so every iteration jumps back to cond and then to body. The pass moves
+// the test to the end of both predecessors (guard in pre, bottom test
+// in the latch) and removes the cond block:
+//
+//     pre:   ...; <test>; br %c, body, cont
+//     body:  phis; ...; <test'>; br %c', body, cont
+//     cont:  phis
+//
+// Only single block tests are rotated (like `while (i < n)`), since the
+// test is duplicated. Lazy conditions span several blocks and are left as is.
+
+pub fn rotate_loops(fun: &mut Function) {
+    // rotating a loop can make an enclosing one a candidate, so blocks
+    // are checked again until nothing changes; predecessors are updated
+    // by rotations instead of being calculated for each of them
+    let mut preds = get_predecessors(fun);
+    let mut next_reg = fun.get_next_free_reg_num().0;
+    let mut changed = true;
+    while changed {
+        changed = false;
+        // rotations only add uses of registers which are already used
+        // outside of their blocks, so it's valid for the whole round
+        let using_blocks = get_using_blocks(fun);
+        let labels: Vec<_> = fun.blocks.iter().map(|bl| bl.label).collect();
+        for label in labels {
+            if !preds.contains_key(&label) {
+                continue; // condition of a loop rotated in this round
+            }
+            if let Some(candidate) = check_candidate(fun, &preds, &using_blocks, label) {
+                rotate(fun, &mut preds, &mut next_reg, &candidate);
+                changed = true;
+            }
+        }
+    }
+}
+
+struct Candidate {
+    cond: Label,
+    pre: Label,
+    latch: Label,
+    body: Label,
+    cont: Label,
+    loop_blocks: HashSet<Label>,
+}
+
+fn check_candidate(
+    fun: &Function,
+    preds: &HashMap<Label, Vec<Label>>,
+    using_blocks: &HashMap<RegNum, HashSet<Label>>,
+    cond: Label,
+) -> Option<Candidate> {
+    let cond_bl = fun.get_block(cond);
+    let (body, cont) = match cond_bl.terminator {
+        Terminator::Branch2(_, body, cont, _) if body != cont && body != cond && cont != cond => {
+            (body, cont)
+        }
+        _ => return None,
+    };
+    if preds[&cond].len() != 2 || preds[&body] != [cond] {
+        return None;
+    }
+    if !fun.get_block(body).phis.is_empty() || !fun.get_block(cont).phis.is_empty() {
+        return None;
+    }
+
+    // checked before finding the blocks of the loop, which visits all of them
+    let jumps_to_cond = |label| fun.get_block(label).terminator == Terminator::Branch1(cond);
+    if !preds[&cond].iter().all(|p| jumps_to_cond(*p)) {
+        return None;
+    }
+
+    let loop_blocks = get_reachable_blocks(fun, body, cond);
+    let (latch, pre) = match (preds[&cond][0], preds[&cond][1]) {
+        (l, p) if loop_blocks.contains(&l) && !loop_blocks.contains(&p) => (l, p),
+        (p, l) if loop_blocks.contains(&l) && !loop_blocks.contains(&p) => (l, p),
+        _ => return None,
+    };
+    // cont must be entered only from the loop (e.g. by break-like jumps of lazy
+    // conditions), otherwise the phis created there would be incomplete
+    if preds[&cont]
+        .iter()
+        .any(|p| *p != cond && !loop_blocks.contains(p))
+    {
+        return None;
+    }
+    // the loop is entered only through cond, so cont can't reach its blocks
+    // (checked without visiting the blocks after the loop)
+    if loop_blocks.contains(&cont)
+        || loop_blocks.iter().any(|l| {
+            preds[l]
+                .iter()
+                .any(|p| *p != cond && !loop_blocks.contains(p))
+        })
+    {
+        return None;
+    }
+    if cond_bl.phis.iter().any(|phi| phi.incoming.len() != 2) {
+        return None;
+    }
+
+    // values computed by the test are going to be duplicated,
+    // so they can't be used anywhere else
+    let used_outside = cond_bl
+        .body
+        .iter()
+        .filter_map(|op| op.get_defined_reg())
+        .any(|reg_num| match using_blocks.get(&reg_num) {
+            Some(labels) => labels.iter().any(|l| *l != cond),
+            None => false,
+        });
+    if used_outside {
+        return None;
+    }
+
+    Some(Candidate {
+        cond,
+        pre,
+        latch,
+        body,
+        cont,
+        loop_blocks,
+    })
+}
+
+// register -> blocks using it (in phis, operations or the terminator)
+fn get_using_blocks(fun: &Function) -> HashMap<RegNum, HashSet<Label>> {
+    let mut using_blocks: HashMap<_, HashSet<_>> = HashMap::new();
+    for bl in &fun.blocks {
+        let phi_values = bl.phis.iter().flat_map(|phi| phi.get_used_values());
+        for value in phi_values.chain(bl.get_used_values()) {
+            if let Value::Register(reg_num, _) = value {
+                using_blocks.entry(*reg_num).or_default().insert(bl.label);
+            }
+        }
+    }
+    using_blocks
+}
+
+fn rotate(
+    fun: &mut Function,
+    preds: &mut HashMap<Label, Vec<Label>>,
+    next_reg: &mut u32,
+    c: &Candidate,
+) {
+    let cont_preds: Vec<_> = preds[&c.cont]
+        .iter()
+        .flat_map(|p| {
+            if *p == c.cond {
+                vec![c.pre, c.latch]
+            } else {
+                vec![*p]
+            }
+        })
+        .collect();
+    let cond_bl = fun.blocks.remove(fun.get_block_index(c.cond));
+
+    // inside the loop, the values of cond phis are now defined by body phis
+    let mut in_loop_subst = HashMap::new();
+    for phi in &cond_bl.phis {
+        let new_value = Value::Register(RegNum(*next_reg), phi.reg_type);
+        *next_reg += 1;
+        in_loop_subst.insert(phi.reg_num, new_value);
+    }
+    for label in &c.loop_blocks {
+        let bl = fun.get_block_mut(*label);
+        for phi in &mut bl.phis {
+            for value in phi.get_used_values_mut() {
+                substitute_value(value, &in_loop_subst);
+            }
+        }
+        for value in bl.get_used_values_mut() {
+            substitute_value(value, &in_loop_subst);
+        }
+    }
+
+    // values of cond phis when entering from pre and from the latch
+    let mut pre_subst = HashMap::new();
+    let mut latch_subst = HashMap::new();
+    for phi in &cond_bl.phis {
+        let pre_value = phi.get_incoming(c.pre).unwrap().clone();
+        let mut latch_value = phi.get_incoming(c.latch).unwrap().clone();
+        substitute_value(&mut latch_value, &in_loop_subst);
+        let loop_value = in_loop_subst[&phi.reg_num].clone();
+
+        let body_bl = fun.get_block_mut(c.body);
+        let body_reg = match &loop_value {
+            Value::Register(body_reg, _) => *body_reg,
+            _ => unreachable!(),
+        };
+        body_bl.add_phi(Phi::new(
+            body_reg,
+            phi.reg_type,
+            vec![(pre_value.clone(), c.pre), (latch_value.clone(), c.latch)],
+        ));
+
+        // the phi stays alive after the loop, now in cont
+        let mut cont_entries = vec![];
+        for p in &cont_preds {
+            if *p == c.pre {
+                cont_entries.push((pre_value.clone(), c.pre));
+            } else if *p == c.latch {
+                cont_entries.push((latch_value.clone(), c.latch));
+            } else {
+                cont_entries.push((loop_value.clone(), *p));
+            }
+        }
+        let cont_bl = fun.get_block_mut(c.cont);
+        cont_bl.add_phi(Phi::new(phi.reg_num, phi.reg_type, cont_entries));
+
+        pre_subst.insert(phi.reg_num, pre_value);
+        latch_subst.insert(phi.reg_num, latch_value);
+    }
+
+    // copy the test to the end of pre and latch
+    for (target, subst) in &[(c.pre, pre_subst), (c.latch, latch_subst)] {
+        let mut renamed = HashMap::new();
+        let mut ops = vec![];
+        for op in &cond_bl.body {
+            let mut op = op.clone();
+            for value in op.get_used_values_mut() {
+                substitute_value(value, subst);
+                rename_reg(value, &renamed);
+            }
+            if let Some(reg_num) = op.get_defined_reg_mut() {
+                renamed.insert(*reg_num, RegNum(*next_reg));
+                *reg_num = RegNum(*next_reg);
+                *next_reg += 1;
+            }
+            ops.push(op);
+        }
+        let (mut cond_value, hint) = match &cond_bl.terminator {
+            Terminator::Branch2(value, _, _, hint) => (value.clone(), *hint),
+            _ => unreachable!(),
+        };
+        substitute_value(&mut cond_value, subst);
+        rename_reg(&mut cond_value, &renamed);
+
+        let target_bl = fun.get_block_mut(*target);
+        target_bl.body.extend(ops);
+        target_bl.terminator = Terminator::Branch2(cond_value, c.body, c.cont, hint);
+    }
+
+    // the phis of body and cont have the entries of the new edges already
+    for succ in [c.body, c.cont] {
+        fun.remove_edge(c.cond, succ);
+        fun.add_edge(c.pre, succ);
+        fun.add_edge(c.latch, succ);
+    }
+
+    preds.remove(&c.cond);
+    preds.insert(c.body, vec![c.pre, c.latch]);
+    preds.insert(c.cont, cont_preds);
+}
@@ -0,0 +1,355 @@
+use super::purity::CallInfo;
+use super::{get_predecessors, substitute_value};
+use model::ir::{Function, Label, Operation, RegNum, Type, TypeKind, Value};
+use std::collections::{HashMap, HashSet};
+
+// Loads and stores of fields and array elements. Codegen computes the
+// address and loads the value again on every access, so the pass keeps
+// the values known to be in memory and replaces the loads of them:
+//
+//     store i32 1, %p.x;  ...; %a = load %p.x   ->  1
+//     %a = load %p.x;     ...; %b = load %p.x   ->  %a
+//
+// and removes the stores overwritten in the same block before anything
+// could read them. The known values are intersected on joins of blocks
+// visited in reverse postorder, loop headers start with nothing.
+//
+// Two accesses may touch the same memory unless the alias analysis below
+// proves otherwise:
+// - different fields (prefixes of each other for fields of structs
+//   nested in classes) or different constant indices of arrays,
+// - a field of an object and an element or the length of an array,
+// - values of different primitive types (an int and a pointer),
+// - different new objects, or a new object and an argument of the function.
+// Calls clobber everything, except read-only and pure functions and the
+// builtins which don't write memory visible to the program.
+
+// their results are new memory, not aliased by anything existing
+const ALLOCATING_FUNCTIONS: &[&str] = &["_bltn_malloc", "_bltn_alloc_array", "_bltn_array_copy"];
+
+pub fn optimize_memory_accesses(fun: &mut Function, calls: &CallInfo) {
+    let mut info = PointerInfo::new(fun);
+    remove_redundant_loads(fun, &mut info, calls);
+    let info = PointerInfo::new(fun);
+    remove_dead_stores(fun, &info);
+}
+
+// the same key means the same address
+#[derive(PartialEq, Eq, Hash, Clone)]
+enum AddressKey {
+    Gep(Type, Vec<Value>),
+    Ptr(Value),
+}
+
+#[derive(Clone)]
+enum Location {
+    Field(Vec<i32>), // the path of indices in the class
+    ArrayElem(Value),
+    ArrayLength,
+    Unknown,
+}
+
+#[derive(Clone)]
+struct MemoryAccess {
+    key: AddressKey,
+    root: Value, // the pointer the address is computed from
+    location: Location,
+    value_type: Type,
+}
+
+enum PointerDef {
+    Gep(Type, Vec<Value>),
+    Cast(Value),
+    New,
+}
+
+struct PointerInfo {
+    defs: HashMap<RegNum, PointerDef>,
+    args: HashSet<RegNum>,
+}
+
+impl PointerInfo {
+    fn new(fun: &Function) -> Self {
+        let mut info = PointerInfo {
+            defs: HashMap::new(),
+            args: fun.args.iter().map(|(reg_num, _)| *reg_num).collect(),
+        };
+        for op in fun.blocks.iter().flat_map(|bl| &bl.body) {
+            info.update(op);
+        }
+        info
+    }
+
+    // records the definition of a pointer (again, after its operands
+    // were substituted)
+    fn update(&mut self, op: &Operation) {
+        let (reg_num, def) = match op {
+            Operation::GetElementPtr(dst, t, vals) => (*dst, PointerDef::Gep(*t, vals.clone())),
+            Operation::CastPtr { dst, src_value, .. } => {
+                (*dst, PointerDef::Cast(src_value.clone()))
+            }
+            Operation::Alloca(dst, _, _) => (*dst, PointerDef::New),
+            Operation::FunctionCall(Some(dst), _, Value::GlobalRegister(name, _), _)
+                if ALLOCATING_FUNCTIONS.contains(&name.as_str()) =>
+            {
+                (*dst, PointerDef::New)
+            }
+            _ => return,
+        };
+        self.defs.insert(reg_num, def);
+    }
+
+    fn get_access(&self, ptr: &Value) -> MemoryAccess {
+        let value_type = ptr.get_type().pointee().unwrap();
+        let (key, location) = match self.get_def(ptr) {
+            Some(PointerDef::Gep(t, vals)) => {
+                let key = AddressKey::Gep(*t, vals.clone());
+                (key, get_location(t, vals))
+            }
+            _ => (AddressKey::Ptr(ptr.clone()), Location::Unknown),
+        };
+        MemoryAccess {
+            key,
+            root: self.get_root(ptr),
+            location,
+            value_type,
+        }
+    }
+
+    fn get_def(&self, value: &Value) -> Option<&PointerDef> {
+        match value {
+            Value::Register(reg_num, _) => self.defs.get(reg_num),
+            _ => None,
+        }
+    }
+
+    fn get_root(&self, ptr: &Value) -> Value {
+        let mut root = ptr;
+        loop {
+            root = match self.get_def(root) {
+                Some(PointerDef::Gep(_, vals)) => &vals[0],
+                Some(PointerDef::Cast(value)) => value,
+                _ => return root.clone(),
+            };
+        }
+    }
+
+    fn is_new(&self, value: &Value) -> bool {
+        matches!(self.get_def(value), Some(PointerDef::New))
+    }
+
+    fn is_arg(&self, value: &Value) -> bool {
+        matches!(value, Value::Register(reg_num, _) if self.args.contains(reg_num))
+    }
+
+    fn may_alias(&self, a: &MemoryAccess, b: &MemoryAccess) -> bool {
+        if a.key == b.key {
+            return true;
+        }
+        let (ra, rb) = (&a.root, &b.root);
+        let different_objects = ra != rb
+            && ((self.is_new(ra) && (self.is_new(rb) || self.is_arg(rb)))
+                || (self.is_new(rb) && self.is_arg(ra)));
+        if different_objects || !may_have_same_type(&a.value_type, &b.value_type) {
+            return false;
+        }
+        use self::Location::*;
+        match (&a.location, &b.location) {
+            (Field(pa), Field(pb)) => pa.starts_with(pb) || pb.starts_with(pa),
+            (Field(_), ArrayElem(_))
+            | (Field(_), ArrayLength)
+            | (ArrayElem(_), Field(_))
+            | (ArrayLength, Field(_))
+            | (ArrayElem(_), ArrayLength)
+            | (ArrayLength, ArrayElem(_)) => false,
+            (ArrayElem(Value::LitInt(ia)), ArrayElem(Value::LitInt(ib))) => ia == ib,
+            _ => true,
+        }
+    }
+}
+
+// fields are `gep %cls, obj, 0, idx...`, elements are `gep T, arr, idx`,
+// the length is before the elements: `gep i32, (bitcast arr), -1`
+fn get_location(t: &Type, vals: &[Value]) -> Location {
+    let get_path = |vals: &[Value]| -> Option<Vec<i32>> {
+        vals.iter()
+            .map(|val| match val {
+                Value::LitInt(idx) => Some(*idx),
+                _ => None,
+            })
+            .collect()
+    };
+    match (t.kind(), vals) {
+        (TypeKind::Class(_), [_, Value::LitInt(0), path @ ..]) if !path.is_empty() => {
+            get_path(path).map_or(Location::Unknown, Location::Field)
+        }
+        (TypeKind::Class(_), _) => Location::Unknown,
+        (TypeKind::Int, [_, Value::LitInt(-1)]) => Location::ArrayLength,
+        (_, [_, idx]) => Location::ArrayElem(idx.clone()),
+        _ => Location::Unknown,
+    }
+}
+
+// memory keeps values of one type, but pointers to classes may be cast
+// and structs contain other values
+fn may_have_same_type(a: &Type, b: &Type) -> bool {
+    match (a.kind(), b.kind()) {
+        (TypeKind::Ptr(_), TypeKind::Ptr(_))
+        | (TypeKind::Struct(_), _)
+        | (_, TypeKind::Struct(_)) => true,
+        _ => a == b,
+    }
+}
+
+type KnownValues = Vec<(MemoryAccess, Value)>;
+
+fn remove_redundant_loads(fun: &mut Function, info: &mut PointerInfo, calls: &CallInfo) {
+    let preds = get_predecessors(fun);
+    let mut exit_values: HashMap<Label, KnownValues> = HashMap::new();
+    let mut subst = HashMap::new();
+    for label in get_reverse_postorder(fun) {
+        let mut known = get_entry_values(&preds[&label], &exit_values);
+        let bl = fun.get_block_mut(label);
+        let mut new_body = vec![];
+        for mut op in bl.body.drain(..) {
+            for value in op.get_used_values_mut() {
+                substitute_value(value, &subst);
+            }
+            info.update(&op);
+            match &op {
+                Operation::Load(dst, ptr) => {
+                    let access = info.get_access(ptr);
+                    let found = known.iter().find(|(a, _)| a.key == access.key);
+                    if let Some((_, value)) = found {
+                        subst.insert(*dst, with_type(value, &access.value_type));
+                        continue;
+                    }
+                    let value = Value::Register(*dst, access.value_type);
+                    known.push((access, value));
+                }
+                Operation::Store(value, ptr) => {
+                    let access = info.get_access(ptr);
+                    // stores the value which is already there
+                    if known.iter().any(|(a, v)| a.key == access.key && v == value) {
+                        continue;
+                    }
+                    known.retain(|(a, _)| !info.may_alias(a, &access));
+                    known.push((access, value.clone()));
+                }
+                Operation::FunctionCall(..) if calls.is_non_clobbering(&op) => (),
+                Operation::FunctionCall(..) | Operation::MemZero(..) => known.clear(),
+                _ => (),
+            }
+            new_body.push(op);
+        }
+        bl.body = new_body;
+        for value in bl.terminator.get_used_values_mut() {
+            substitute_value(value, &subst);
+        }
+        exit_values.insert(label, known);
+    }
+    if subst.is_empty() {
+        return;
+    }
+    // a value may be substituted already in the uses which come before
+    // the load in the reverse postorder, that is in phis
+    for bl in &mut fun.blocks {
+        for phi in &mut bl.phis {
+            for value in phi.get_used_values_mut() {
+                substitute_value(value, &subst);
+            }
+        }
+    }
+}
+
+// values known at the end of all predecessors, nothing if some of them
+// wasn't visited yet (loops)
+fn get_entry_values(preds: &[Label], exit_values: &HashMap<Label, KnownValues>) -> KnownValues {
+    let mut pred_values = preds.iter().map(|pred| exit_values.get(pred));
+    let mut res = match pred_values.next() {
+        Some(Some(values)) => values.clone(),
+        _ => return vec![],
+    };
+    for values in pred_values {
+        let values = match values {
+            Some(values) => values,
+            None => return vec![],
+        };
+        res.retain(|(a, v)| values.iter().any(|(b, w)| a.key == b.key && v == w));
+    }
+    res
+}
+
+// null literals are typed by the place they are used in
+fn with_type(value: &Value, value_type: &Type) -> Value {
+    match value {
+        Value::LitNullPtr(_) => Value::LitNullPtr(Some(*value_type)),
+        _ => value.clone(),
+    }
+}
+
+// unreachable blocks are at the end
+fn get_reverse_postorder(fun: &Function) -> Vec<Label> {
+    let mut postorder = vec![];
+    let mut visited = HashSet::new();
+    // (block, its successors are pushed already)
+    let mut stack = vec![(fun.blocks[0].label, false)];
+    while let Some((label, expanded)) = stack.pop() {
+        if expanded {
+            postorder.push(label);
+            continue;
+        }
+        if !visited.insert(label) {
+            continue;
+        }
+        stack.push((label, true));
+        for succ in fun.get_block(label).get_successors().into_iter().rev() {
+            if !visited.contains(&succ) {
+                stack.push((succ, false));
+            }
+        }
+    }
+    postorder.reverse();
+    postorder.extend(
+        fun.blocks
+            .iter()
+            .map(|bl| bl.label)
+            .filter(|label| !visited.contains(label)),
+    );
+    postorder
+}
+
+// a store is dead if the same address is written later in the block and
+// nothing could read it in between
+fn remove_dead_stores(fun: &mut Function, info: &PointerInfo) {
+    for bl in &mut fun.blocks {
+        let mut overwritten: Vec<MemoryAccess> = vec![];
+        let mut dead = HashSet::new();
+        for (i, op) in bl.body.iter().enumerate().rev() {
+            match op {
+                Operation::Store(_, ptr) => {
+                    let access = info.get_access(ptr);
+                    if overwritten.iter().any(|a| a.key == access.key) {
+                        dead.insert(i);
+                    } else {
+                        overwritten.push(access);
+                    }
+                }
+                Operation::Load(_, ptr) => {
+                    let access = info.get_access(ptr);
+                    overwritten.retain(|a| !info.may_alias(a, &access));
+                }
+                Operation::FunctionCall(..) | Operation::MemZero(..) => overwritten.clear(),
+                _ => (),
+            }
+        }
+        if dead.is_empty() {
+            continue;
+        }
+        let mut i = 0;
+        bl.body.retain(|_| {
+            i += 1;
+            !dead.contains(&(i - 1))
+        });
+    }
+}
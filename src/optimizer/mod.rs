@@ -0,0 +1,296 @@
+use self::purity::CallInfo;
+use ice;
+use model::ir::{ArithOp, Function, Label, Operation, Program, RegNum, Value};
+use options::CompilerOptions;
+use std::collections::{HashMap, HashSet};
+
+mod block_layout;
+mod conditions;
+mod const_eval;
+mod constant_args;
+mod cse;
+mod escape_analysis;
+mod induction_vars;
+mod inline_caches;
+mod inlining;
+mod ir_stats;
+mod loop_rotation;
+mod memory;
+mod no_return;
+mod profile;
+mod purity;
+mod recursion;
+mod string_concat;
+
+pub use self::constant_args::get_indirectly_called_functions;
+pub use self::ir_stats::{format_ir_stats, get_stack_usage};
+pub use self::profile::{instrument_program, Profile};
+
+// the profile (--use-profile) guides inlining and inline caches
+pub fn optimize(prog: &mut Program, options: &CompilerOptions, profile: Option<&Profile>) {
+    if options.opt_level == 0 {
+        return;
+    }
+    if options.opt_level >= 2 {
+        inline_caches::add_inline_caches(prog, profile);
+    }
+    inlining::inline_functions(prog, options.inline_threshold, profile);
+    constant_args::propagate_constant_args(prog);
+    no_return::remove_code_after_noreturn_calls(prog);
+    purity::infer_purity(prog);
+    const_eval::evaluate_constant_calls(prog);
+    let calls = CallInfo::new(prog);
+    for fun in &mut prog.functions {
+        ice::enter_function(&fun.name, None);
+        conditions::canonicalize_conditions(fun);
+        loop_rotation::rotate_loops(fun);
+        remove_trivial_phis(fun);
+        induction_vars::simplify_induction_vars(fun);
+        memory::optimize_memory_accesses(fun, &calls);
+        cse::eliminate_common_subexpressions(fun, &calls);
+        remove_dead_values(fun, &calls);
+        string_concat::merge_concat_chains(fun);
+        escape_analysis::allocate_on_stack(fun);
+        block_layout::add_branch_hints(fun);
+        block_layout::lay_out_blocks(fun);
+    }
+    recursion::mark_non_recursive_functions(prog);
+}
+
+// helpers shared by the passes
+
+// computed from the terminators, so it's valid even if a pass
+// hasn't updated the predecessors lists yet
+fn get_predecessors(fun: &Function) -> HashMap<Label, Vec<Label>> {
+    let mut preds: HashMap<_, Vec<_>> = fun.blocks.iter().map(|bl| (bl.label, vec![])).collect();
+    for bl in &fun.blocks {
+        for succ in bl.get_successors() {
+            let succ_preds = preds.get_mut(&succ).unwrap();
+            if !succ_preds.contains(&bl.label) {
+                succ_preds.push(bl.label);
+            }
+        }
+    }
+    preds
+}
+
+// blocks reachable from start without going through avoided block
+fn get_reachable_blocks(fun: &Function, start: Label, avoid: Label) -> HashSet<Label> {
+    let mut visited = HashSet::new();
+    let mut stack = vec![start];
+    while let Some(label) = stack.pop() {
+        if label == avoid || !visited.insert(label) {
+            continue;
+        }
+        let bl = fun.get_block(label);
+        stack.extend(bl.get_successors());
+    }
+    visited
+}
+
+// block -> blocks dominating it (including itself)
+fn get_dominators(fun: &Function) -> HashMap<Label, HashSet<Label>> {
+    let preds = get_predecessors(fun);
+    let entry = fun.blocks[0].label;
+    let all: HashSet<_> = fun.blocks.iter().map(|bl| bl.label).collect();
+    let mut doms: HashMap<_, _> = fun
+        .blocks
+        .iter()
+        .map(|bl| (bl.label, all.clone()))
+        .collect();
+    doms.insert(entry, [entry].iter().cloned().collect());
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for bl in fun.blocks.iter().filter(|bl| bl.label != entry) {
+            let mut new_doms = preds[&bl.label]
+                .iter()
+                .map(|p| doms[p].clone())
+                .fold(None, |acc: Option<HashSet<_>>, d| match acc {
+                    None => Some(d),
+                    Some(acc) => Some(acc.intersection(&d).cloned().collect()),
+                })
+                .unwrap_or_default();
+            new_doms.insert(bl.label);
+            if new_doms != doms[&bl.label] {
+                doms.insert(bl.label, new_doms);
+                changed = true;
+            }
+        }
+    }
+    doms
+}
+
+// block -> number of loops containing it; natural loops with the same
+// header are counted as one loop (also used by the register allocators)
+pub fn get_loop_depths(fun: &Function) -> HashMap<Label, usize> {
+    let preds = get_predecessors(fun);
+    let dominators = get_dominators(fun);
+    let mut loops: HashMap<Label, HashSet<Label>> = HashMap::new();
+    for bl in &fun.blocks {
+        for header in bl.get_successors() {
+            if !dominators[&bl.label].contains(&header) {
+                continue;
+            }
+            let blocks = loops.entry(header).or_default();
+            blocks.insert(header);
+            let mut stack = vec![bl.label];
+            while let Some(label) = stack.pop() {
+                if blocks.insert(label) {
+                    stack.extend(preds[&label].iter().cloned());
+                }
+            }
+        }
+    }
+    fun.blocks
+        .iter()
+        .map(|bl| {
+            let depth = loops
+                .values()
+                .filter(|blocks| blocks.contains(&bl.label))
+                .count();
+            (bl.label, depth)
+        })
+        .collect()
+}
+
+fn substitute_value(value: &mut Value, subst: &HashMap<RegNum, Value>) {
+    let new_value = match value {
+        Value::Register(reg_num, _) => subst.get(reg_num).cloned(),
+        _ => None,
+    };
+    if let Some(new_value) = new_value {
+        *value = new_value;
+    }
+}
+
+// the source span marker in effect after ops (see ir::Operation::SourceSpan),
+// to be copied to the start of a block made of operations moved out of them
+fn get_source_span(ops: &[Operation]) -> Option<Operation> {
+    ops.iter()
+        .rev()
+        .find(|op| matches!(op, Operation::SourceSpan(_)))
+        .cloned()
+}
+
+fn rename_reg(value: &mut Value, renamed: &HashMap<RegNum, RegNum>) {
+    if let Value::Register(reg_num, _) = value {
+        if let Some(new_reg_num) = renamed.get(reg_num) {
+            *reg_num = *new_reg_num;
+        }
+    }
+}
+
+// codegen creates phis in loops for all local variables, most of them
+// just pass the same value around: x = phi [v, pre], [x, latch]
+fn remove_trivial_phis(fun: &mut Function) {
+    loop {
+        let mut subst = HashMap::new();
+        for bl in &fun.blocks {
+            for phi in &bl.phis {
+                let mut values = phi.get_used_values().into_iter().filter(|v| match v {
+                    Value::Register(r, _) => *r != phi.reg_num,
+                    _ => true,
+                });
+                if let Some(first) = values.next() {
+                    if values.all(|v| v == first) {
+                        subst.insert(phi.reg_num, first.clone());
+                    }
+                }
+            }
+        }
+        if subst.is_empty() {
+            break;
+        }
+        // substituted values may be trivial phis too
+        let keys: Vec<_> = subst.keys().cloned().collect();
+        for reg_num in keys {
+            let mut value = subst[&reg_num].clone();
+            for _ in 0..subst.len() {
+                match &value {
+                    Value::Register(r, _) if subst.contains_key(r) => value = subst[r].clone(),
+                    _ => break,
+                }
+            }
+            subst.insert(reg_num, value);
+        }
+
+        for bl in &mut fun.blocks {
+            bl.phis.retain(|phi| !subst.contains_key(&phi.reg_num));
+            for phi in &mut bl.phis {
+                for value in phi.get_used_values_mut() {
+                    substitute_value(value, &subst);
+                }
+            }
+            for value in bl.get_used_values_mut() {
+                substitute_value(value, &subst);
+            }
+        }
+    }
+}
+
+// removes phis and operations without side effects whose results are never
+// used (also the ones used only by each other, like an unused loop counter)
+fn remove_dead_values(fun: &mut Function, calls: &CallInfo) {
+    let get_used_regs = |values: Vec<&Value>| -> Vec<RegNum> {
+        values
+            .into_iter()
+            .filter_map(|value| match value {
+                Value::Register(reg_num, _) => Some(*reg_num),
+                _ => None,
+            })
+            .collect()
+    };
+
+    let mut dependencies = HashMap::new();
+    let mut worklist = vec![];
+    for bl in &fun.blocks {
+        for phi in &bl.phis {
+            let used = get_used_regs(phi.get_used_values());
+            dependencies.insert(phi.reg_num, used);
+        }
+        for op in &bl.body {
+            let used = get_used_regs(op.get_used_values());
+            match op.get_defined_reg() {
+                Some(reg_num) if is_removable(op, calls) => {
+                    dependencies.insert(reg_num, used);
+                }
+                _ => worklist.extend(used),
+            }
+        }
+        worklist.extend(get_used_regs(bl.terminator.get_used_values()));
+    }
+
+    let mut live = HashSet::new();
+    while let Some(reg_num) = worklist.pop() {
+        if live.insert(reg_num) {
+            if let Some(used) = dependencies.get(&reg_num) {
+                worklist.extend(used.iter().cloned());
+            }
+        }
+    }
+
+    for bl in &mut fun.blocks {
+        bl.phis.retain(|phi| live.contains(&phi.reg_num));
+        bl.body.retain(|op| match op.get_defined_reg() {
+            Some(reg_num) if is_removable(op, calls) => live.contains(&reg_num),
+            _ => true,
+        });
+    }
+}
+
+// loads and divisions can crash the program, so they stay, like calls
+// of functions which may not return
+fn is_removable(op: &Operation, calls: &CallInfo) -> bool {
+    use self::Operation::*;
+    match op {
+        FunctionCall(..) => calls.is_removable(op),
+        Arithmetic(_, ArithOp::Div, _, _) | Arithmetic(_, ArithOp::Mod, _, _) => false,
+        Arithmetic(..) | Compare(..) | Not(..) | Select(..) | GetElementPtr(..) | Alloca(..) => {
+            true
+        }
+        CastGlobalString(..) | CastPtr { .. } | CastPtrToInt { .. } | CastCharToInt { .. } => true,
+        _ => false,
+    }
+}
@@ -0,0 +1,86 @@
+use model::ir::{Function, FunctionAttribute, Operation, Program, Terminator, Value};
+use std::collections::HashSet;
+use std::mem;
+
+// Functions which never return: a function is noreturn if no return is
+// reachable from its entry without passing a call of a noreturn function
+// (starting with error() and the stack overflow handler), so the ones
+// ending the program on every path and the ones looping forever. They get
+// the noreturn attribute, and the code after their calls is removed: the
+// block ends with unreachable, so the blocks only after it are dead (and
+// removed with the other unreachable blocks) and LLVM knows it too.
+// Mutually recursive functions without a return aren't found, only the
+// calls known to end are used.
+
+pub fn remove_code_after_noreturn_calls(prog: &mut Program) {
+    let no_return = get_noreturn_functions(prog);
+    for fun in &mut prog.functions {
+        if no_return.contains(&fun.name) {
+            fun.attributes.insert(FunctionAttribute::NoReturn);
+        }
+        let labels: Vec<_> = fun.blocks.iter().map(|bl| bl.label).collect();
+        for label in labels {
+            let bl = fun.get_block_mut(label);
+            let call_idx = match bl
+                .body
+                .iter()
+                .position(|op| is_noreturn_call(op, &no_return))
+            {
+                Some(idx) => idx,
+                None => continue,
+            };
+            if call_idx + 1 == bl.body.len() && bl.terminator == Terminator::Unreachable {
+                continue;
+            }
+            bl.body.truncate(call_idx + 1);
+            let old_terminator = mem::replace(&mut bl.terminator, Terminator::Unreachable);
+            for succ in old_terminator.get_successors() {
+                fun.remove_edge(label, succ);
+            }
+        }
+    }
+}
+
+fn get_noreturn_functions(prog: &Program) -> HashSet<String> {
+    let mut res = prog.get_noreturn_functions();
+    loop {
+        let new_no_return: Vec<_> = prog
+            .functions
+            .iter()
+            .filter(|fun| !res.contains(&fun.name) && !can_return(fun, &res))
+            .map(|fun| fun.name.clone())
+            .collect();
+        if new_no_return.is_empty() {
+            return res;
+        }
+        res.extend(new_no_return);
+    }
+}
+
+fn is_noreturn_call(op: &Operation, no_return: &HashSet<String>) -> bool {
+    match op {
+        Operation::FunctionCall(_, _, Value::GlobalRegister(name, _), _) => {
+            no_return.contains(name)
+        }
+        _ => false,
+    }
+}
+
+fn can_return(fun: &Function, no_return: &HashSet<String>) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = vec![fun.blocks[0].label];
+    while let Some(label) = stack.pop() {
+        if !visited.insert(label) {
+            continue;
+        }
+        let bl = fun.get_block(label);
+        if bl.body.iter().any(|op| is_noreturn_call(op, no_return)) {
+            continue;
+        }
+        if let Terminator::Return(_) = bl.terminator {
+            return true;
+        }
+        stack.extend(bl.get_successors());
+    }
+    false
+}
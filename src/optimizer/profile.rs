@@ -0,0 +1,199 @@
+use model::ir::{
+    fnv1a_hash, format_global_string, BranchHint, Function, Label, Operation, Program, RegNum,
+    Terminator, Type, Value,
+};
+use model::ir_encoding::Node;
+use std::collections::HashMap;
+
+// Profile-guided optimization. A program compiled with --instrument=FILE
+// counts the executions of every block of the IR (before the optimizer) and
+// the branches taken by its conditional jump (the condition was true), and
+// the runtime writes the counters to FILE at exit (not after a crash):
+//   ["latte-profile", "<checksum>", [executions...], [taken...]]
+// with one entry per block, numbered in the order of functions and blocks.
+// The checksum is of the labels and sizes of the blocks, so --use-profile=FILE
+// rejects a profile of another program (or of other codegen options). The
+// profile gives:
+//  - the branch weights of the conditional jumps (instead of the guesses
+//    of block_layout),
+//  - the inlining decisions: calls which were never executed aren't inlined,
+//    hot ones (at least 1% of the executions of the hottest block) get
+//    a bigger threshold,
+//  - the classes of the inline caches (-O2): allocation sites are counted
+//    by their executions and only executed calls are speculated.
+// The optimizer changes the blocks, so the counts are only known for the
+// blocks of the codegen (blocks created by the passes have none).
+
+const PROFILE_TAG: &str = "latte-profile";
+const INIT_FUNCTION: &str = "_bltn_profile_init";
+const BLOCK_FUNCTION: &str = "_bltn_profile_block";
+const BRANCH_FUNCTION: &str = "_bltn_profile_branch";
+const NOT_A_PROFILE: &str = "not a profile of a Latte program";
+
+pub struct Profile {
+    counts: HashMap<(String, Label), (u64, u64)>, // (executions, taken)
+    max_count: u64,
+}
+
+// requirement: the program is just generated (not optimized)
+pub fn instrument_program(prog: &mut Program, path: &str) {
+    let checksum = get_checksum(prog);
+    let void_fun = |args| Type::ptr(Type::func(Type::Void, args));
+    let string_type = Type::ptr(Type::Char);
+    let mut id = 0;
+    for fun in &mut prog.functions {
+        for bl in &mut fun.blocks {
+            let count = Operation::FunctionCall(
+                None,
+                Type::Void,
+                Value::GlobalRegister(BLOCK_FUNCTION.to_string(), void_fun(vec![Type::Int])),
+                vec![Value::LitInt(id)],
+            );
+            let idx = get_start(&bl.body);
+            bl.body.insert(idx, count);
+            if let Terminator::Branch2(cond, ..) = &bl.terminator {
+                let count = Operation::FunctionCall(
+                    None,
+                    Type::Void,
+                    Value::GlobalRegister(
+                        BRANCH_FUNCTION.to_string(),
+                        void_fun(vec![Type::Int, Type::Bool]),
+                    ),
+                    vec![Value::LitInt(id), cond.clone()],
+                );
+                bl.body.push(count);
+            }
+            id += 1;
+        }
+    }
+
+    // the counters are allocated at the start of main
+    let main = prog
+        .functions
+        .iter_mut()
+        .find(|f| f.name == "main")
+        .unwrap();
+    let first_reg = main.get_next_free_reg_num().0;
+    let mut init_args = vec![Value::LitInt(id)];
+    let mut init_ops = vec![];
+    for (i, string) in [checksum.as_str(), path].iter().enumerate() {
+        let str_id = prog.global_strings.intern(string);
+        let dst = RegNum(first_reg + i as u32);
+        init_ops.push(Operation::CastGlobalString(
+            dst,
+            string.len() + 1,
+            Value::GlobalRegister(format_global_string(str_id), string_type),
+        ));
+        init_args.push(Value::Register(dst, string_type));
+    }
+    init_ops.push(Operation::FunctionCall(
+        None,
+        Type::Void,
+        Value::GlobalRegister(
+            INIT_FUNCTION.to_string(),
+            void_fun(vec![Type::Int, string_type, string_type]),
+        ),
+        init_args,
+    ));
+    let entry = &mut main.blocks[0].body;
+    let idx = get_start(entry);
+    entry.splice(idx..idx, init_ops);
+}
+
+// where new operations of the block start: after its source span marker
+fn get_start(body: &[Operation]) -> usize {
+    match body.first() {
+        Some(Operation::SourceSpan(_)) => 1,
+        _ => 0,
+    }
+}
+
+// of the blocks of the program, as they are numbered
+fn get_checksum(prog: &Program) -> String {
+    let mut blocks = String::new();
+    for fun in &prog.functions {
+        for bl in &fun.blocks {
+            let len = bl
+                .body
+                .iter()
+                .filter(|op| !matches!(op, Operation::SourceSpan(_)))
+                .count()
+                + 1; // the terminator
+            blocks += &format!("{} {} {}\n", fun.name, bl.label.0, len);
+        }
+    }
+    format!("{:016x}", fnv1a_hash(blocks.as_bytes()))
+}
+
+impl Profile {
+    // requirement: the program is just generated (not optimized)
+    pub fn load(prog: &Program, text: &str) -> Result<Profile, String> {
+        let items = match Node::from_json(text)? {
+            Node::List(items) => items,
+            _ => return Err(NOT_A_PROFILE.to_string()),
+        };
+        let (executions, taken) = match items.as_slice() {
+            [Node::Str(tag), Node::Str(checksum), Node::List(executions), Node::List(taken)]
+                if tag == PROFILE_TAG =>
+            {
+                if *checksum != get_checksum(prog) {
+                    return Err("it was recorded for another program".to_string());
+                }
+                (executions, taken)
+            }
+            _ => return Err(NOT_A_PROFILE.to_string()),
+        };
+        let get_count = |node: &Node| match node {
+            Node::Int(n) if *n >= 0 => Ok(*n as u64),
+            _ => Err("invalid counter".to_string()),
+        };
+        let mut counts = HashMap::new();
+        let mut max_count = 0;
+        let blocks = prog
+            .functions
+            .iter()
+            .flat_map(|fun| fun.blocks.iter().map(move |bl| (&fun.name, bl.label)));
+        let mut entries = executions.iter().zip(taken);
+        for (fun_name, label) in blocks {
+            let (executions, taken) = match entries.next() {
+                Some((executions, taken)) => (get_count(executions)?, get_count(taken)?),
+                None => return Err("too few counters".to_string()),
+            };
+            max_count = max_count.max(executions);
+            counts.insert((fun_name.clone(), label), (executions, taken));
+        }
+        Ok(Profile { counts, max_count })
+    }
+
+    // how many times the block was executed, None for blocks which weren't
+    // in the instrumented program
+    pub fn get_count(&self, fun: &Function, label: Label) -> Option<u64> {
+        self.counts
+            .get(&(fun.name.clone(), label))
+            .map(|(executions, _)| *executions)
+    }
+
+    pub fn is_hot(&self, count: u64) -> bool {
+        count > 0 && count * 100 >= self.max_count
+    }
+
+    // requirement: the program is just generated (not optimized)
+    pub fn add_branch_weights(&self, prog: &mut Program) {
+        for fun in &mut prog.functions {
+            for bl in &mut fun.blocks {
+                let (executions, taken) = self.counts[&(fun.name.clone(), bl.label)];
+                if let Terminator::Branch2(_, _, _, hint) = &mut bl.terminator {
+                    if executions > 0 {
+                        *hint = get_weights(taken, executions - taken);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// the weights are 32-bit in LLVM
+fn get_weights(taken: u64, not_taken: u64) -> BranchHint {
+    let scale = (taken.max(not_taken) / u64::from(u32::MAX)) + 1;
+    BranchHint::Weights((taken / scale) as u32, (not_taken / scale) as u32)
+}
@@ -0,0 +1,226 @@
+use super::get_predecessors;
+use model::ir::{ArithOp, Function, Operation, Program, Purity, Value};
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+
+// Purity of the functions, inferred from their IR (after inlining): a
+// function storing to memory, doing IO or calling something unknown (like
+// a method through the vtable) is impure, one which only loads is
+// read-only, otherwise it's pure. Calls are as pure as their callees,
+// recursion is resolved by lowering the purity until nothing changes.
+//
+// A function always returns if it has no loops, doesn't call functions
+// which may not return (including recursion) and has no operations which
+// may fail (loads of possibly null pointers, divisions). Only calls of
+// such functions can be removed when their results are unused, others
+// can be replaced by the result of the same call before them.
+
+// ending the program doesn't prevent replacing a call with the result of
+// an earlier one, so error() doesn't make a function impure
+const PURE_BUILTINS: &[&str] = &[
+    "error",
+    "_bltn_stack_overflow_error",
+    "_bltn_null_dereference_error",
+    "_bltn_string_eq",
+    "_bltn_string_ne",
+    "llvm.frameaddress.p0i8",
+];
+
+const READ_ONLY_BUILTINS: &[&str] = &[
+    "_bltn_string_check_index",
+    "_bltn_int_list_get",
+    "_bltn_int_list_size",
+    "_bltn_string_list_get",
+    "_bltn_string_list_size",
+    "_bltn_string_int_map_get",
+    "_bltn_string_int_map_contains",
+    "_bltn_string_int_map_size",
+];
+
+// builtins which don't write memory visible to the program, but do IO
+// or return new memory
+const NON_CLOBBERING_BUILTINS: &[&str] = &[
+    "printInt",
+    "printString",
+    "printBoolean",
+    "printErr",
+    "flushOutput",
+    "readInt",
+    "readString",
+    "readFile",
+    "writeFile",
+    "split",
+    "join",
+    "_bltn_string_concat",
+    "_bltn_string_concat_n",
+    "_bltn_malloc",
+    "_bltn_alloc_array",
+    "_bltn_array_copy",
+    "_bltn_string_int_map_keys",
+];
+
+const RETURNING_BUILTINS: &[&str] = &["_bltn_string_eq", "_bltn_string_ne"];
+
+pub fn infer_purity(prog: &mut Program) {
+    let mut purity: HashMap<_, _> = prog
+        .functions
+        .iter()
+        .map(|fun| (fun.name.clone(), Purity::Pure))
+        .collect();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for fun in &prog.functions {
+            let new_purity = get_function_purity(fun, &purity);
+            if new_purity != purity[&fun.name] {
+                purity.insert(fun.name.clone(), new_purity);
+                changed = true;
+            }
+        }
+    }
+
+    // only functions proven to return, so recursion never does
+    let mut returning = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for fun in &prog.functions {
+            if !returning.contains(&fun.name) && always_returns(fun, &returning) {
+                returning.insert(fun.name.clone());
+                changed = true;
+            }
+        }
+    }
+
+    for fun in &mut prog.functions {
+        fun.purity = purity[&fun.name];
+        fun.always_returns = returning.contains(&fun.name);
+    }
+}
+
+// purity of the calls, for the passes over single functions
+pub struct CallInfo {
+    purity: HashMap<String, Purity>,
+    returning: HashSet<String>,
+}
+
+impl CallInfo {
+    pub fn new(prog: &Program) -> Self {
+        CallInfo {
+            purity: prog
+                .functions
+                .iter()
+                .map(|fun| (fun.name.clone(), fun.purity))
+                .collect(),
+            returning: prog
+                .functions
+                .iter()
+                .filter(|fun| fun.always_returns)
+                .map(|fun| fun.name.clone())
+                .collect(),
+        }
+    }
+
+    pub fn get_purity(&self, op: &Operation) -> Purity {
+        match get_callee(op) {
+            Some(name) => get_callee_purity(name, &self.purity),
+            None => Purity::Impure,
+        }
+    }
+
+    // the call can't change memory visible to the program
+    pub fn is_non_clobbering(&self, op: &Operation) -> bool {
+        match get_callee(op) {
+            Some(name) => {
+                NON_CLOBBERING_BUILTINS.contains(&name) || self.get_purity(op) != Purity::Impure
+            }
+            None => false,
+        }
+    }
+
+    // the call can be removed if its result is unused
+    pub fn is_removable(&self, op: &Operation) -> bool {
+        match get_callee(op) {
+            Some(name) => {
+                self.get_purity(op) != Purity::Impure
+                    && (self.returning.contains(name) || RETURNING_BUILTINS.contains(&name))
+            }
+            None => false,
+        }
+    }
+}
+
+fn get_callee(op: &Operation) -> Option<&str> {
+    match op {
+        Operation::FunctionCall(_, _, Value::GlobalRegister(name, _), _) => Some(name),
+        _ => None,
+    }
+}
+
+fn get_callee_purity(name: &str, purity: &HashMap<String, Purity>) -> Purity {
+    match purity.get(name) {
+        Some(purity) => *purity,
+        None if PURE_BUILTINS.contains(&name) => Purity::Pure,
+        None if READ_ONLY_BUILTINS.contains(&name) => Purity::ReadOnly,
+        None => Purity::Impure,
+    }
+}
+
+fn get_function_purity(fun: &Function, purity: &HashMap<String, Purity>) -> Purity {
+    let mut res = Purity::Pure;
+    for op in fun.blocks.iter().flat_map(|bl| &bl.body) {
+        let op_purity = match op {
+            Operation::Store(..) | Operation::MemZero(..) => Purity::Impure,
+            Operation::Load(..) => Purity::ReadOnly,
+            Operation::FunctionCall(..) => match get_callee(op) {
+                Some(name) => get_callee_purity(name, purity),
+                None => Purity::Impure,
+            },
+            _ => Purity::Pure,
+        };
+        res = cmp::min(res, op_purity);
+    }
+    res
+}
+
+fn always_returns(fun: &Function, returning: &HashSet<String>) -> bool {
+    use self::Operation::*;
+    let may_fail = |op: &Operation| match op {
+        Arithmetic(_, ArithOp::Div, _, divisor) | Arithmetic(_, ArithOp::Mod, _, divisor) => {
+            // INT_MIN / -1 overflows
+            !matches!(divisor, Value::LitInt(d) if *d != 0 && *d != -1)
+        }
+        Load(..) => true,
+        FunctionCall(..) => match get_callee(op) {
+            Some(name) => !returning.contains(name) && !RETURNING_BUILTINS.contains(&name),
+            None => true,
+        },
+        _ => false,
+    };
+    !has_loops(fun) && !fun.blocks.iter().flat_map(|bl| &bl.body).any(may_fail)
+}
+
+// the blocks can't be sorted topologically
+fn has_loops(fun: &Function) -> bool {
+    let preds = get_predecessors(fun);
+    let mut in_degree: HashMap<_, _> = preds.iter().map(|(l, p)| (*l, p.len())).collect();
+    let mut ready: Vec<_> = in_degree
+        .iter()
+        .filter(|(_, d)| **d == 0)
+        .map(|(l, _)| *l)
+        .collect();
+    let mut sorted = 0;
+    while let Some(label) = ready.pop() {
+        sorted += 1;
+        let mut succs = fun.get_block(label).get_successors();
+        succs.dedup();
+        for succ in succs {
+            let d = in_degree.get_mut(&succ).unwrap();
+            *d -= 1;
+            if *d == 0 {
+                ready.push(succ);
+            }
+        }
+    }
+    sorted != fun.blocks.len()
+}
@@ -0,0 +1,54 @@
+use super::constant_args::get_indirectly_called_functions;
+use model::ir::{Operation, Program, Value};
+use std::collections::{HashMap, HashSet};
+
+// Functions which can't be called (directly, through other functions or
+// through pointers) while they're running are marked as never recursing,
+// which is emitted as norecurse. Runs after the other passes, which only
+// remove calls. The runtime doesn't call back into the program.
+
+pub fn mark_non_recursive_functions(prog: &mut Program) {
+    let indirect = get_indirectly_called_functions(prog);
+    let callees: HashMap<_, _> = prog
+        .functions
+        .iter()
+        .map(|fun| {
+            let mut res = HashSet::new();
+            for op in fun.blocks.iter().flat_map(|bl| &bl.body) {
+                match op {
+                    Operation::FunctionCall(_, _, Value::GlobalRegister(name, _), _) => {
+                        res.insert(name.as_str());
+                    }
+                    Operation::FunctionCall(..) => {
+                        res.extend(indirect.iter().map(|name| name.as_str()));
+                    }
+                    _ => (),
+                }
+            }
+            (fun.name.clone(), res)
+        })
+        .collect();
+    let no_callees = HashSet::new();
+    let is_recursive = |name: &str| {
+        let mut visited = HashSet::new();
+        let mut stack: Vec<_> = callees[name].iter().cloned().collect();
+        while let Some(callee) = stack.pop() {
+            if callee == name {
+                return true;
+            }
+            if visited.insert(callee) {
+                stack.extend(callees.get(callee).unwrap_or(&no_callees));
+            }
+        }
+        false
+    };
+    let non_recursive: HashSet<_> = prog
+        .functions
+        .iter()
+        .filter(|fun| !is_recursive(&fun.name))
+        .map(|fun| fun.name.clone())
+        .collect();
+    for fun in &mut prog.functions {
+        fun.never_recurses = non_recursive.contains(&fun.name);
+    }
+}
@@ -0,0 +1,133 @@
+use model::ir::{Function, Operation, RegNum, Type, Value};
+use std::collections::{HashMap, HashSet};
+
+// `a + b + c + d` is generated as a chain of _bltn_string_concat calls,
+// each allocating and copying the whole prefix. Calls whose result is
+// used only by another concatenation are merged into one
+// _bltn_string_concat_n call, which gets the operands in an array.
+// The array is allocated on the stack once, in the entry block,
+// with the size of the longest chain.
+
+const CONCAT_FUN: &str = "_bltn_string_concat";
+const CONCAT_N_FUN: &str = "_bltn_string_concat_n";
+
+pub fn merge_concat_chains(fun: &mut Function) {
+    let concat_args = get_concat_calls(fun);
+    let mut use_cnt = HashMap::new();
+    for bl in &fun.blocks {
+        let phi_values = bl.phis.iter().flat_map(|phi| phi.get_used_values());
+        for value in phi_values.chain(bl.get_used_values()) {
+            if let Value::Register(reg_num, _) = value {
+                *use_cnt.entry(*reg_num).or_insert(0) += 1;
+            }
+        }
+    }
+
+    // results used only as an operand of another concatenation
+    let mut merged = HashSet::new();
+    for args in concat_args.values() {
+        for arg in args {
+            if let Value::Register(reg_num, _) = arg {
+                if concat_args.contains_key(reg_num) && use_cnt[reg_num] == 1 {
+                    merged.insert(*reg_num);
+                }
+            }
+        }
+    }
+    if merged.is_empty() {
+        return;
+    }
+
+    let str_type = Type::ptr(Type::Char);
+    let array_type = Type::ptr(str_type);
+    let fun_type = Type::ptr(Type::func(str_type, vec![Type::Int, array_type]));
+    let mut next_reg = fun.get_next_free_reg_num().0;
+    let array_reg = RegNum(next_reg);
+    next_reg += 1;
+    let array_val = Value::Register(array_reg, array_type);
+    let mut max_cnt = 0;
+
+    for bl in &mut fun.blocks {
+        let mut new_body = vec![];
+        for op in bl.body.drain(..) {
+            let reg_num = match &op {
+                Operation::FunctionCall(Some(reg_num), _, _, _)
+                    if concat_args.contains_key(reg_num) =>
+                {
+                    *reg_num
+                }
+                _ => {
+                    new_body.push(op);
+                    continue;
+                }
+            };
+            if merged.contains(&reg_num) {
+                // computed by the call using its result
+                continue;
+            }
+
+            let mut operands = vec![];
+            collect_operands(reg_num, &concat_args, &merged, &mut operands);
+            if operands.len() == 2 {
+                new_body.push(op);
+                continue;
+            }
+            for (i, value) in operands.iter().enumerate() {
+                let elem_reg = RegNum(next_reg);
+                next_reg += 1;
+                new_body.push(Operation::GetElementPtr(
+                    elem_reg,
+                    str_type,
+                    vec![array_val.clone(), Value::LitInt(i as i32)],
+                ));
+                new_body.push(Operation::Store(
+                    value.clone(),
+                    Value::Register(elem_reg, Type::ptr(str_type)),
+                ));
+            }
+            new_body.push(Operation::FunctionCall(
+                Some(reg_num),
+                str_type,
+                Value::GlobalRegister(CONCAT_N_FUN.to_string(), fun_type),
+                vec![Value::LitInt(operands.len() as i32), array_val.clone()],
+            ));
+            max_cnt = max_cnt.max(operands.len() as i32);
+        }
+        bl.body = new_body;
+    }
+
+    let entry = &mut fun.blocks[0];
+    entry
+        .body
+        .insert(0, Operation::Alloca(array_reg, str_type, max_cnt));
+}
+
+fn get_concat_calls(fun: &Function) -> HashMap<RegNum, Vec<Value>> {
+    let mut result = HashMap::new();
+    for op in fun.blocks.iter().flat_map(|bl| bl.body.iter()) {
+        if let Operation::FunctionCall(Some(reg_num), _, Value::GlobalRegister(name, _), args) = op
+        {
+            if name == CONCAT_FUN {
+                result.insert(*reg_num, args.clone());
+            }
+        }
+    }
+    result
+}
+
+// operands of the whole chain, left to right
+fn collect_operands(
+    reg_num: RegNum,
+    concat_args: &HashMap<RegNum, Vec<Value>>,
+    merged: &HashSet<RegNum>,
+    operands: &mut Vec<Value>,
+) {
+    for arg in &concat_args[&reg_num] {
+        match arg {
+            Value::Register(arg_reg, _) if merged.contains(arg_reg) => {
+                collect_operands(*arg_reg, concat_args, merged, operands)
+            }
+            _ => operands.push(arg.clone()),
+        }
+    }
+}
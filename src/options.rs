@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+// options set from the command line, passed down to the compiler stages
+#[derive(Clone)]
+pub struct CompilerOptions {
+    // require local variables to be definitely assigned before they are read
+    // (by default declarations without initializer get 0, false or null)
+    pub strict: bool,
+    // check indices of string characters at runtime
+    // (strings don't store their length, so it costs strlen per access)
+    pub check_bounds: bool,
+    // check the stack pointer in the prologue of every function, so too deep
+    // recursion ends with an error message instead of a segfault
+    pub check_stack: bool,
+    // 0 disables the optimizer, it's run on the IR just before printing;
+    // 2 adds speculative optimizations (inline caches of virtual calls)
+    pub opt_level: u32,
+    // max number of IR operations of a function inlined without `inline`
+    pub inline_threshold: usize,
+    // 32 or 64, the width of pointers and allocation sizes (size_t) of the target;
+    // the runtime has to be compiled for the same target
+    pub pointer_width: u32,
+    pub emit: Emit,
+    // print decisions of the SSA construction (blocks, proxy frames, phis) to stderr
+    pub trace_codegen: bool,
+    // the program prints a line for every evaluated expression with side
+    // effects, in the order of evaluation (codegen::function)
+    pub trace_eval_order: bool,
+    // printed to stdout instead of compiling the program to a file
+    pub report: Option<Report>,
+    pub report_format: ReportFormat,
+    // directory of the persistent cache of generated functions (codegen::cache)
+    pub cache_dir: Option<PathBuf>,
+    // the program counts executions of blocks and branches and writes them
+    // to this file at exit (see optimizer::profile)
+    pub instrument: Option<PathBuf>,
+    // counts written by an instrumented build, used by the optimizer
+    pub use_profile: Option<PathBuf>,
+    // register allocator of the native backend (see regalloc)
+    pub regalloc: RegAlloc,
+    // architecture of the object files (--emit=obj) and of the native backend:
+    // registers and calling convention (see abi)
+    pub target: Target,
+    // how the object code addresses globals and functions: absolute addresses
+    // (only position-dependent executables) or through the GOT and PLT
+    pub relocation_model: RelocationModel,
+    // lint name -> level (semantics::lints), the other lints are warnings
+    pub lint_levels: HashMap<String, LintLevel>,
+    // the accepted language (semantics::dialect)
+    pub dialect: Dialect,
+    // the result of `/` and `%` with negative operands
+    pub int_division: IntDivision,
+}
+
+// what the compiler outputs
+#[derive(Clone, Copy, PartialEq)]
+pub enum Emit {
+    Llvm,
+    // an object file next to the .ll and .bc files, not linked
+    Obj,
+    // results of the class hierarchy analysis, printed to stdout
+    ClassHierarchy,
+    // string literals of the program and their names in the IR, printed to stdout
+    Strings,
+    // layout of objects and vtables of the classes, printed to stdout
+    Vtables,
+    // the IR serialized (model::ir_encoding), printed to stdout; ir-dis prints it as LLVM IR
+    IrJson,
+    IrBinary,
+    // calls between the functions of the type checked program (semantics::call_graph),
+    // printed to stdout as DOT or JSON (--report-format)
+    CallGraph,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Report {
+    // per function: blocks, instructions, phis, loop depth and stack usage
+    IrStats,
+    // per function: registers assigned to the values by the register allocator
+    RegAlloc,
+    // per function of the C runtime: where the arguments and the result are passed
+    Abi,
+    // per call: where the live heap pointers are (regalloc::stack_maps)
+    StackMaps,
+    // per function: frame size, recursion and the maximal stack of its calls
+    StackUsage,
+    // per line of the printed LLVM IR: the statement it was generated for
+    SourceMap,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RegAlloc {
+    LinearScan,
+    GraphColoring,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Target {
+    X86_64,
+    AArch64,
+    RiscV64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum RelocationModel {
+    Static,
+    Pic,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny, // reported as an error
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Dialect {
+    // the original Latte with the extensions of its specification
+    Core,
+    // with all the extensions of this compiler
+    Extended,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum IntDivision {
+    // rounded toward zero, the remainder has the sign of the dividend (like in C
+    // and Java, sdiv and srem of LLVM)
+    Truncating,
+    // the remainder is never negative, e.g. -7 / 2 == -4 and -7 % 2 == 1
+    Euclidean,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ReportFormat {
+    Table,
+    Json,
+}
+
+impl Default for CompilerOptions {
+    fn default() -> CompilerOptions {
+        CompilerOptions {
+            strict: false,
+            check_bounds: false,
+            check_stack: false,
+            opt_level: 1,
+            inline_threshold: 30,
+            pointer_width: 64,
+            emit: Emit::Llvm,
+            trace_codegen: false,
+            trace_eval_order: false,
+            report: None,
+            report_format: ReportFormat::Table,
+            cache_dir: None,
+            instrument: None,
+            use_profile: None,
+            regalloc: RegAlloc::LinearScan,
+            target: Target::X86_64,
+            relocation_model: RelocationModel::Static,
+            lint_levels: HashMap::new(),
+            dialect: Dialect::Extended,
+            int_division: IntDivision::Truncating,
+        }
+    }
+}
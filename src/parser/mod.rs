@@ -1,25 +1,33 @@
 lalrpop_mod!(#[allow(clippy::all)] pub latte, "/parser/latte.rs");
 use self::latte::ProgramParser;
 use codemap::CodeMap;
-use frontend_error::{FrontendError, FrontendResult};
+use frontend_error::{ErrorKind, FrontendError, FrontendResult};
 use model::ast::{
-    new_spanned_boxed, BinaryOp, Block, Expr, InnerExpr, InnerStmt, InnerUnaryOp, Program, Stmt,
+    new_spanned_boxed, Annotation, BinaryOp, Block, Expr, Ident, InnerExpr, InnerStmt,
+    InnerUnaryOp, Program, Stmt,
 };
+use options::IntDivision;
 
 const KEYWORDS: &[&str] = &[
     "if", "else", "return", "while", "for", "new", "class", "extends", "true", "false", "null",
-    "int", "string", "boolean", "void",
+    "int", "string", "boolean", "void", "inline", "noinline", "fn", "break", "continue", "typedef",
+    "struct",
 ];
 
-pub fn parse(codemap: &CodeMap) -> FrontendResult<Program> {
-    let code = replace_comments(codemap.get_code())?;
+// single line comments starting with it are kept as annotations
+const ANNOTATION_PREFIX: &str = "latte:";
+
+// int_division is needed by the folding of constant expressions
+pub fn parse(codemap: &CodeMap, int_division: IntDivision) -> FrontendResult<Program> {
+    let (code, annotations) = replace_comments(codemap.get_code())?;
 
     let mut errors = Vec::new();
-    let result = ProgramParser::new().parse(&mut errors, &code);
+    let result = ProgramParser::new().parse(&mut errors, int_division, &code);
     match result {
-        Ok(program) => {
+        Ok(mut program) => {
             if errors.is_empty() {
                 // probably must be empty
+                program.annotations = annotations;
                 Ok(program)
             } else {
                 Err(errors)
@@ -31,6 +39,7 @@ pub fn parse(codemap: &CodeMap) -> FrontendResult<Program> {
                 errors.push(FrontendError {
                     err: "Fatal syntax error: can not recognize anything".to_string(),
                     span: (0, code.len() - 1),
+                    kind: ErrorKind::Error,
                 });
             }
             Err(errors)
@@ -38,31 +47,41 @@ pub fn parse(codemap: &CodeMap) -> FrontendResult<Program> {
     }
 }
 
-fn replace_comments(code: &str) -> FrontendResult<String> {
+// also returns the annotations
+fn replace_comments(code: &str) -> FrontendResult<(String, Vec<Annotation>)> {
     let mut result = String::new();
+    let mut annotations = vec![];
 
     let mut last_ch = '\0';
     let mut erasing = false;
     let mut multiline = false;
-    let mut inside_string = false;
-    for ch in code.chars() {
+    // of the current comment, the start and where its text starts
+    let mut comment_start = (0, 0);
+    // quote of the string or character literal we are inside
+    let mut inside_literal = None;
+    let mut escaped = false;
+    for (i, ch) in code.char_indices() {
         if !erasing {
             // check if comment begins
-            match (inside_string, last_ch, ch) {
-                (false, _, '"') => {
-                    inside_string = true;
+            match (inside_literal.is_some(), last_ch, ch) {
+                (true, _, _) => {
+                    if escaped {
+                        escaped = false;
+                    } else if ch == '\\' {
+                        escaped = true;
+                    } else if inside_literal == Some(ch) {
+                        inside_literal = None;
+                    }
                     result.push(ch);
                 }
-                (true, _, '"') if last_ch != '\\' => {
-                    inside_string = false;
-                    result.push(ch);
-                }
-                (true, _, _) => {
+                (false, _, '"') | (false, _, '\'') => {
+                    inside_literal = Some(ch);
                     result.push(ch);
                 }
                 (false, _, '#') | (false, '/', '/') => {
                     erasing = true;
                     multiline = false;
+                    comment_start = (if ch == '#' { i } else { i - 1 }, i + 1);
 
                     if last_ch == '/' {
                         result.pop();
@@ -85,6 +104,7 @@ fn replace_comments(code: &str) -> FrontendResult<String> {
             match (multiline, last_ch, ch) {
                 (false, _, '\n') => {
                     erasing = false;
+                    add_annotation(code, comment_start, i, &mut annotations);
                     result.push(ch);
                 }
                 (true, '*', '/') => {
@@ -104,16 +124,37 @@ fn replace_comments(code: &str) -> FrontendResult<String> {
         Err(vec![FrontendError {
             err: "Multiline comment must be closed before EOF".to_string(),
             span: (code.len() - 1, code.len()),
+            kind: ErrorKind::Error,
         }])
     } else {
-        Ok(result)
+        if erasing {
+            add_annotation(code, comment_start, code.len(), &mut annotations);
+        }
+        Ok((result, annotations))
+    }
+}
+
+fn add_annotation(
+    code: &str,
+    (start, text_start): (usize, usize),
+    end: usize,
+    res: &mut Vec<Annotation>,
+) {
+    if let Some(text) = code[text_start..end].trim().strip_prefix(ANNOTATION_PREFIX) {
+        res.push(Annotation {
+            inner: text.trim().to_string(),
+            span: (start, end),
+        });
     }
 }
 
 // ---------------------------- ----------------------
 // --------------- parser utils ----------------------
 // ---------------------------------------------------
-fn optimize_const_expr_shallow(expr: InnerExpr) -> Result<InnerExpr, &'static str> {
+fn optimize_const_expr_shallow(
+    expr: InnerExpr,
+    int_division: IntDivision,
+) -> Result<InnerExpr, &'static str> {
     use self::BinaryOp::*;
     use self::InnerExpr::*;
     use self::InnerUnaryOp::*;
@@ -125,18 +166,17 @@ fn optimize_const_expr_shallow(expr: InnerExpr) -> Result<InnerExpr, &'static st
             (LitInt(l), Add, LitInt(r)) => LitInt(l + r),
             (LitInt(l), Sub, LitInt(r)) => LitInt(l - r),
             (LitInt(l), Mul, LitInt(r)) => LitInt(l * r),
-            (LitInt(l), Div, LitInt(r)) => {
-                if *r == 0 {
-                    return Err("Assertion Error: Division by zero in constant expression");
-                }
-                LitInt(l / r)
+            (LitInt(_), Div, LitInt(0)) | (LitInt(_), Mod, LitInt(0)) => {
+                return Err("Assertion Error: Division by zero in constant expression");
             }
-            (LitInt(l), Mod, LitInt(r)) => {
-                if *r == 0 {
-                    return Err("Assertion Error: Division by zero in constant expression");
-                }
-                LitInt(l % r)
+            // euclidean division of negative dividends is folded by the optimizer
+            (LitInt(l), Div, LitInt(_)) | (LitInt(l), Mod, LitInt(_))
+                if *l < 0 && int_division == IntDivision::Euclidean =>
+            {
+                LitNull
             }
+            (LitInt(l), Div, LitInt(r)) => LitInt(l / r),
+            (LitInt(l), Mod, LitInt(r)) => LitInt(l % r),
             (LitInt(l), LT, LitInt(r)) => LitBool(l < r),
             (LitInt(l), LE, LitInt(r)) => LitBool(l <= r),
             (LitInt(l), GT, LitInt(r)) => LitBool(l > r),
@@ -171,6 +211,7 @@ fn return_or_fail(
             errors.push(FrontendError {
                 err: err.to_string(),
                 span: (l, r),
+                kind: ErrorKind::Error,
             });
             new_spanned_boxed(l, InnerExpr::LitNull, r)
         }
@@ -188,3 +229,32 @@ fn stmt_to_block(stmt: Box<Stmt>) -> Block {
         }
     }
 }
+
+// `a.b.c` in an expression: the variable with a path of fields
+fn path_to_expr(path: Vec<Ident>) -> Box<Expr> {
+    let mut path = path.into_iter();
+    let var = path.next().unwrap();
+    let (l, r) = var.span;
+    let mut e = new_spanned_boxed(l, InnerExpr::LitVar(var.inner), r);
+    for field in path {
+        let r = field.span.1;
+        let obj_field = InnerExpr::ObjField {
+            obj: e,
+            is_obj_an_array: None,
+            is_obj_a_struct: None,
+            field,
+        };
+        e = new_spanned_boxed(l, obj_field, r);
+    }
+    e
+}
+
+fn array_elem(array: Box<Expr>, index: Box<Expr>, r: usize) -> Box<Expr> {
+    let l = array.span.0;
+    let e = InnerExpr::ArrayElem {
+        array,
+        index,
+        is_array_a_string: None,
+    };
+    new_spanned_boxed(l, e, r)
+}
@@ -0,0 +1,122 @@
+use super::liveness::{get_terminator_regs, get_used_regs};
+use super::{get_allowed_registers, Allocation, Interference, Location};
+use abi::Abi;
+use model::ir::{Function, RegNum, Value};
+use std::collections::{HashMap, HashSet};
+
+// Graph coloring (Chaitin-Briggs): nodes with fewer neighbours than the
+// registers they can use are removed from the interference graph, when
+// there are none, the node with the lowest spill cost (uses weighted by
+// the loop depth, divided by the degree) is removed optimistically. Then
+// the nodes get the registers in the reverse order and the ones left
+// without a register are spilled.
+
+pub fn allocate(fun: &Function, interference: &Interference, abi: &Abi) -> Allocation {
+    let spill_costs = get_spill_costs(fun);
+    let nodes: Vec<_> = interference
+        .regs
+        .iter()
+        .filter(|reg_num| !interference.in_memory.contains(reg_num))
+        .cloned()
+        .collect();
+    let no_edges = HashSet::new();
+    let get_neighbours = |reg_num: &RegNum| {
+        interference
+            .edges
+            .get(reg_num)
+            .unwrap_or(&no_edges)
+            .iter()
+            .filter(|other| !interference.in_memory.contains(other))
+    };
+    let get_allowed =
+        |reg_num: &RegNum| get_allowed_registers(abi, interference.across_calls.contains(reg_num));
+
+    // simplify
+    let mut degrees: HashMap<_, _> = nodes
+        .iter()
+        .map(|reg_num| (*reg_num, get_neighbours(reg_num).count()))
+        .collect();
+    let mut removed = HashSet::new();
+    let mut stack = vec![];
+    while stack.len() < nodes.len() {
+        let remaining = nodes.iter().filter(|reg_num| !removed.contains(*reg_num));
+        let trivial = remaining
+            .clone()
+            .find(|reg_num| degrees[reg_num] < get_allowed(reg_num).len());
+        let node = match trivial {
+            Some(reg_num) => *reg_num,
+            None => *remaining
+                .min_by(|a, b| {
+                    let cost = |reg_num: &RegNum| {
+                        spill_costs.get(reg_num).cloned().unwrap_or(0.0)
+                            / degrees[reg_num].max(1) as f64
+                    };
+                    cost(a).partial_cmp(&cost(b)).unwrap()
+                })
+                .unwrap(),
+        };
+        removed.insert(node);
+        for other in get_neighbours(&node) {
+            if !removed.contains(other) {
+                *degrees.get_mut(other).unwrap() -= 1;
+            }
+        }
+        stack.push(node);
+    }
+
+    // select
+    let mut locations = HashMap::new();
+    let mut next_slot = 0;
+    for reg_num in &interference.regs {
+        if interference.in_memory.contains(reg_num) {
+            locations.insert(*reg_num, Location::Stack(next_slot));
+            next_slot += 1;
+        }
+    }
+    while let Some(reg_num) = stack.pop() {
+        let used: HashSet<_> = get_neighbours(&reg_num)
+            .filter_map(|other| match locations.get(other) {
+                Some(Location::Reg(reg)) => Some(*reg),
+                _ => None,
+            })
+            .collect();
+        let location = match get_allowed(&reg_num)
+            .into_iter()
+            .find(|reg| !used.contains(reg))
+        {
+            Some(reg) => Location::Reg(reg),
+            None => {
+                next_slot += 1;
+                Location::Stack(next_slot - 1)
+            }
+        };
+        locations.insert(reg_num, location);
+    }
+    Allocation::new(locations, abi)
+}
+
+// the number of uses and definitions, weighted by the loop depth
+fn get_spill_costs(fun: &Function) -> HashMap<RegNum, f64> {
+    let loop_depths = ::optimizer::get_loop_depths(fun);
+    let mut res: HashMap<_, f64> = HashMap::new();
+    for bl in &fun.blocks {
+        let weight = 10f64.powi(loop_depths[&bl.label].min(8) as i32);
+        for phi in &bl.phis {
+            *res.entry(phi.reg_num).or_default() += weight;
+            for value in phi.get_used_values() {
+                if let Value::Register(used, _) = value {
+                    *res.entry(*used).or_default() += weight;
+                }
+            }
+        }
+        for op in &bl.body {
+            for reg_num in op.get_defined_reg().into_iter().chain(get_used_regs(op)) {
+                *res.entry(reg_num).or_default() += weight;
+            }
+        }
+        for reg_num in get_terminator_regs(bl) {
+            *res.entry(reg_num).or_default() += weight;
+        }
+    }
+    res
+}
@@ -0,0 +1,108 @@
+use super::liveness::{get_terminator_regs, get_used_regs, Liveness};
+use super::{get_allowed_registers, Allocation, Interference, Location};
+use abi::Abi;
+use model::ir::{Function, RegNum};
+use std::collections::HashMap;
+
+// Linear scan (Poletto & Sarkar): the blocks are numbered in their order,
+// every value gets the interval from its first to its last live position
+// (holes aren't tracked) and the intervals are assigned registers in the
+// order of their starts. When there's no free register, the interval
+// ending last is spilled.
+
+pub fn allocate(
+    fun: &Function,
+    liveness: &Liveness,
+    interference: &Interference,
+    abi: &Abi,
+) -> Allocation {
+    let mut intervals: Vec<_> = get_intervals(fun, liveness)
+        .into_iter()
+        .filter(|(reg_num, _)| !interference.in_memory.contains(reg_num))
+        .collect();
+    intervals.sort_by_key(|(reg_num, (start, _))| (*start, reg_num.0));
+
+    let mut locations = HashMap::new();
+    let mut next_slot = 0;
+    let mut spill = |locations: &mut HashMap<_, _>, reg_num| {
+        locations.insert(reg_num, Location::Stack(next_slot));
+        next_slot += 1;
+    };
+    for reg_num in &interference.regs {
+        if interference.in_memory.contains(reg_num) {
+            spill(&mut locations, *reg_num);
+        }
+    }
+
+    // (end, value, register), ordered by the end
+    let mut active: Vec<(usize, RegNum, &'static str)> = vec![];
+    for (reg_num, (start, end)) in intervals {
+        active.retain(|(active_end, _, _)| *active_end >= start);
+        let allowed = get_allowed_registers(abi, interference.across_calls.contains(&reg_num));
+        let free = allowed
+            .iter()
+            .find(|reg| active.iter().all(|(_, _, used)| used != *reg));
+        if let Some(reg) = free {
+            locations.insert(reg_num, Location::Reg(reg));
+            active.push((end, reg_num, reg));
+            active.sort_by_key(|(end, _, _)| *end);
+            continue;
+        }
+        let victim = active
+            .iter()
+            .rposition(|(_, _, used)| allowed.contains(used))
+            .filter(|i| active[*i].0 > end);
+        match victim {
+            Some(i) => {
+                let (_, spilled, reg) = active.remove(i);
+                spill(&mut locations, spilled);
+                locations.insert(reg_num, Location::Reg(reg));
+                active.push((end, reg_num, reg));
+                active.sort_by_key(|(end, _, _)| *end);
+            }
+            None => spill(&mut locations, reg_num),
+        }
+    }
+    Allocation::new(locations, abi)
+}
+
+// value -> (first position, last position), with a position at the start
+// of every block (the phis and the values live in), one for every
+// operation and the terminator and one at the end of every block (the
+// values live out)
+fn get_intervals(fun: &Function, liveness: &Liveness) -> HashMap<RegNum, (usize, usize)> {
+    let mut res: HashMap<RegNum, (usize, usize)> = HashMap::new();
+    let mut extend = |reg_num, pos| {
+        let interval = res.entry(reg_num).or_insert((pos, pos));
+        interval.0 = interval.0.min(pos);
+        interval.1 = interval.1.max(pos);
+    };
+    for (reg_num, _) in &fun.args {
+        extend(*reg_num, 0);
+    }
+    let mut pos = 0;
+    for bl in &fun.blocks {
+        for reg_num in &liveness.live_in[&bl.label] {
+            extend(*reg_num, pos);
+        }
+        for phi in &bl.phis {
+            extend(phi.reg_num, pos);
+        }
+        for op in &bl.body {
+            pos += 1;
+            for reg_num in op.get_defined_reg().into_iter().chain(get_used_regs(op)) {
+                extend(reg_num, pos);
+            }
+        }
+        pos += 1;
+        for reg_num in get_terminator_regs(bl) {
+            extend(reg_num, pos);
+        }
+        pos += 1;
+        for reg_num in &liveness.live_out[&bl.label] {
+            extend(*reg_num, pos);
+        }
+        pos += 1;
+    }
+    res
+}
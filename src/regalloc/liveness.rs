@@ -0,0 +1,107 @@
+use model::ir::{Block, Function, Label, Operation, RegNum, Value};
+use std::collections::{HashMap, HashSet};
+
+// Liveness of the registers, shared by the allocators. Phis are seen as
+// parallel copies on the edges from the predecessors (critical edges are
+// split by the backend), so a phi defines its register at the start of
+// its block and its values are used at the end of the predecessors.
+
+pub struct Liveness {
+    pub live_in: HashMap<Label, HashSet<RegNum>>, // without the phis of the block
+    pub live_out: HashMap<Label, HashSet<RegNum>>,
+}
+
+pub fn analyze_liveness(fun: &Function) -> Liveness {
+    let mut live_in: HashMap<_, HashSet<_>> = fun
+        .blocks
+        .iter()
+        .map(|bl| (bl.label, HashSet::new()))
+        .collect();
+    let mut live_out: HashMap<_, HashSet<_>> = live_in.clone();
+    let phi_uses = get_phi_uses(fun);
+    let mut changed = true;
+    while changed {
+        changed = false;
+        // backwards, so it usually converges in a few passes
+        for bl in fun.blocks.iter().rev() {
+            let mut out = phi_uses[&bl.label].clone();
+            for succ in bl.get_successors() {
+                out.extend(live_in[&succ].iter().cloned());
+            }
+            let mut live = out.clone();
+            live.extend(get_terminator_regs(bl));
+            for op in bl.body.iter().rev() {
+                update_backwards(&mut live, op);
+            }
+            for phi in &bl.phis {
+                live.remove(&phi.reg_num);
+            }
+            if live != live_in[&bl.label] || out != live_out[&bl.label] {
+                live_in.insert(bl.label, live);
+                live_out.insert(bl.label, out);
+                changed = true;
+            }
+        }
+    }
+    Liveness { live_in, live_out }
+}
+
+impl Liveness {
+    // the registers live after each operation of the block (the terminator
+    // comes after the last one)
+    pub fn get_live_after(&self, bl: &Block) -> Vec<HashSet<RegNum>> {
+        let mut res = vec![HashSet::new(); bl.body.len()];
+        let mut live = self.live_out[&bl.label].clone();
+        live.extend(get_terminator_regs(bl));
+        for (i, op) in bl.body.iter().enumerate().rev() {
+            res[i] = live.clone();
+            update_backwards(&mut live, op);
+        }
+        res
+    }
+}
+
+// from the registers live after the operation to the ones live before it
+fn update_backwards(live: &mut HashSet<RegNum>, op: &Operation) {
+    if let Some(reg_num) = op.get_defined_reg() {
+        live.remove(&reg_num);
+    }
+    live.extend(get_used_regs(op));
+}
+
+pub fn get_used_regs(op: &Operation) -> Vec<RegNum> {
+    get_regs(op.get_used_values())
+}
+
+pub fn get_terminator_regs(bl: &Block) -> Vec<RegNum> {
+    get_regs(bl.terminator.get_used_values())
+}
+
+fn get_regs(values: Vec<&Value>) -> Vec<RegNum> {
+    values
+        .into_iter()
+        .filter_map(|value| match value {
+            Value::Register(reg_num, _) => Some(*reg_num),
+            _ => None,
+        })
+        .collect()
+}
+
+// block -> registers used by the phis of its successors on the edges from it
+fn get_phi_uses(fun: &Function) -> HashMap<Label, HashSet<RegNum>> {
+    let mut res: HashMap<_, HashSet<_>> = fun
+        .blocks
+        .iter()
+        .map(|bl| (bl.label, HashSet::new()))
+        .collect();
+    for bl in &fun.blocks {
+        for phi in &bl.phis {
+            for (value, pred) in &phi.incoming {
+                if let (Value::Register(reg_num, _), Some(uses)) = (value, res.get_mut(pred)) {
+                    uses.insert(*reg_num);
+                }
+            }
+        }
+    }
+    res
+}
@@ -0,0 +1,275 @@
+use abi::Abi;
+use model::ir::{Function, Program, RegNum, Value};
+use options::{RegAlloc, ReportFormat};
+use std::collections::{HashMap, HashSet};
+
+// Register allocation for a native backend: every value (register of the
+// IR) gets a register of the target (see abi) or a stack slot. There are
+// two allocators over the optimized IR, selected with --regalloc: linear
+// scan (fast, over the live intervals in the order of blocks) and graph
+// coloring (Chaitin-Briggs, over the interference graph). Both use the
+// liveness computed in liveness.rs.
+//
+// Values live across a call are kept in callee-saved registers (which the
+// function saves in its prologue, if it uses them) or spilled. Spilled
+// values and struct values are in stack slots, the scratch registers of
+// the target are left for loading them.
+//
+// The LLVM backend doesn't need the allocation, it's printed with
+// --report=regalloc, and the stack maps for a garbage collector built from
+// it (stack_maps.rs) with --report=stack-maps, the frames of the functions
+// (stack_usage.rs) with --report=stack-usage.
+
+mod graph_coloring;
+mod linear_scan;
+mod liveness;
+mod stack_maps;
+mod stack_usage;
+
+pub use self::stack_maps::format_stack_maps;
+pub use self::stack_usage::format_stack_usage;
+
+use self::liveness::{analyze_liveness, Liveness};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Location {
+    Reg(&'static str),
+    Stack(u32), // slot number
+}
+
+pub struct Allocation {
+    pub locations: HashMap<RegNum, Location>,
+    pub stack_slots: u32,
+    pub used_callee_saved: Vec<&'static str>,
+}
+
+impl Allocation {
+    fn new(locations: HashMap<RegNum, Location>, abi: &Abi) -> Self {
+        let stack_slots = locations
+            .values()
+            .filter_map(|loc| match loc {
+                Location::Stack(slot) => Some(slot + 1),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(0);
+        let used_callee_saved = abi
+            .callee_saved
+            .iter()
+            .cloned()
+            .filter(|reg| locations.values().any(|loc| *loc == Location::Reg(reg)))
+            .collect();
+        Allocation {
+            locations,
+            stack_slots,
+            used_callee_saved,
+        }
+    }
+}
+
+// registers which can't be in the same place
+pub struct Interference {
+    pub regs: Vec<RegNum>, // all the registers of the function
+    pub edges: HashMap<RegNum, HashSet<RegNum>>,
+    pub across_calls: HashSet<RegNum>,
+    pub in_memory: HashSet<RegNum>, // struct values
+}
+
+pub fn allocate_registers(fun: &Function, kind: RegAlloc, abi: &Abi) -> Allocation {
+    allocate(fun, kind, abi).1
+}
+
+fn allocate(fun: &Function, kind: RegAlloc, abi: &Abi) -> (Liveness, Allocation) {
+    let liveness = analyze_liveness(fun);
+    let interference = build_interference(fun, &liveness);
+    let alloc = match kind {
+        RegAlloc::LinearScan => linear_scan::allocate(fun, &liveness, &interference, abi),
+        RegAlloc::GraphColoring => graph_coloring::allocate(fun, &interference, abi),
+    };
+    verify(&interference, &alloc, abi);
+    (liveness, alloc)
+}
+
+fn build_interference(fun: &Function, liveness: &Liveness) -> Interference {
+    let mut res = Interference {
+        regs: fun.args.iter().map(|(reg_num, _)| *reg_num).collect(),
+        edges: HashMap::new(),
+        across_calls: HashSet::new(),
+        in_memory: HashSet::new(),
+    };
+    let add_edges =
+        |edges: &mut HashMap<RegNum, HashSet<RegNum>>, reg_num, live: &HashSet<RegNum>| {
+            for other in live.iter().filter(|other| **other != reg_num) {
+                edges.entry(reg_num).or_default().insert(*other);
+                edges.entry(*other).or_default().insert(reg_num);
+            }
+        };
+    // the arguments are defined together at the entry
+    let entry = &fun.blocks[0];
+    let mut live: HashSet<_> = liveness.live_in[&entry.label].clone();
+    live.extend(res.regs.iter().cloned());
+    for (reg_num, _) in &fun.args {
+        add_edges(&mut res.edges, *reg_num, &live);
+    }
+    for bl in &fun.blocks {
+        // phis are defined together at the start of the block
+        let mut live = liveness.live_in[&bl.label].clone();
+        live.extend(bl.phis.iter().map(|phi| phi.reg_num));
+        for phi in &bl.phis {
+            res.regs.push(phi.reg_num);
+            add_edges(&mut res.edges, phi.reg_num, &live);
+        }
+        let live_after = liveness.get_live_after(bl);
+        for (op, live) in bl.body.iter().zip(&live_after) {
+            let def = op.get_defined_reg();
+            if let Some(reg_num) = def {
+                res.regs.push(reg_num);
+                add_edges(&mut res.edges, reg_num, live);
+            }
+            if let ::model::ir::Operation::FunctionCall(..) = op {
+                res.across_calls
+                    .extend(live.iter().filter(|reg_num| Some(**reg_num) != def));
+            }
+        }
+    }
+
+    let mut note_type = |value: &Value| {
+        if let Value::Register(reg_num, t) = value {
+            if t.is_struct() {
+                res.in_memory.insert(*reg_num);
+            }
+        }
+    };
+    for bl in &fun.blocks {
+        for phi in &bl.phis {
+            note_type(&Value::Register(phi.reg_num, phi.reg_type));
+            phi.get_used_values().into_iter().for_each(&mut note_type);
+        }
+        bl.get_used_values().for_each(&mut note_type);
+    }
+    for (reg_num, arg_type) in &fun.args {
+        note_type(&Value::Register(*reg_num, *arg_type));
+    }
+    // the allocators shouldn't depend on the order of the blocks
+    res.regs.sort_by_key(|reg_num| reg_num.0);
+    res
+}
+
+// interfering values in the same register, or a value live across a call
+// in a caller-saved register, would be a bug of the allocator
+fn verify(interference: &Interference, alloc: &Allocation, abi: &Abi) {
+    for (reg_num, others) in &interference.edges {
+        let loc = alloc.locations[reg_num];
+        for other in others {
+            if alloc.locations[other] == loc {
+                panic!(
+                    "%.r{} and %.r{} are both in {:?}, but interfere",
+                    reg_num.0, other.0, loc
+                );
+            }
+        }
+    }
+    for reg_num in &interference.across_calls {
+        if let Location::Reg(reg) = alloc.locations[reg_num] {
+            if abi.caller_saved.contains(&reg) {
+                panic!("%.r{} is live across a call in {}", reg_num.0, reg);
+            }
+        }
+    }
+}
+
+pub fn format_allocations(
+    prog: &Program,
+    kind: RegAlloc,
+    abi: &Abi,
+    format: ReportFormat,
+) -> String {
+    let allocs: Vec<_> = prog
+        .functions
+        .iter()
+        .map(|fun| (fun.name.as_str(), allocate_registers(fun, kind, abi)))
+        .collect();
+    match format {
+        ReportFormat::Table => format_table(&allocs),
+        ReportFormat::Json => format_json(&allocs),
+    }
+}
+
+fn get_sorted_locations(alloc: &Allocation) -> Vec<(RegNum, Location)> {
+    let mut locations: Vec<_> = alloc.locations.iter().map(|(r, l)| (*r, *l)).collect();
+    locations.sort_by_key(|(reg_num, _)| reg_num.0);
+    locations
+}
+
+pub fn format_location(loc: Location) -> String {
+    match loc {
+        Location::Reg(reg) => reg.to_string(),
+        Location::Stack(slot) => format!("slot {}", slot),
+    }
+}
+
+fn format_table(allocs: &[(&str, Allocation)]) -> String {
+    let mut res = String::new();
+    for (name, alloc) in allocs {
+        let locations = get_sorted_locations(alloc);
+        let in_regs = locations
+            .iter()
+            .filter(|(_, loc)| matches!(loc, Location::Reg(_)))
+            .count();
+        res += &format!(
+            "{}: {} values, {} in registers, {} stack slots, callee-saved: {}\n",
+            name,
+            locations.len(),
+            in_regs,
+            alloc.stack_slots,
+            if alloc.used_callee_saved.is_empty() {
+                "none".to_string()
+            } else {
+                alloc.used_callee_saved.join(", ")
+            }
+        );
+        for (reg_num, loc) in locations {
+            res += &format!("    %.r{:<6} {}\n", reg_num.0, format_location(loc));
+        }
+    }
+    res
+}
+
+fn format_json(allocs: &[(&str, Allocation)]) -> String {
+    let mut res = "[\n".to_string();
+    for (i, (name, alloc)) in allocs.iter().enumerate() {
+        let name = name.replace("\\", "\\\\").replace("\"", "\\\"");
+        let locations: Vec<_> = get_sorted_locations(alloc)
+            .into_iter()
+            .map(|(reg_num, loc)| format!("\"%.r{}\": \"{}\"", reg_num.0, format_location(loc)))
+            .collect();
+        let callee_saved: Vec<_> = alloc
+            .used_callee_saved
+            .iter()
+            .map(|reg| format!("\"{}\"", reg))
+            .collect();
+        res += &format!(
+            "  {{\"function\": \"{}\", \"locations\": {{{}}}, \"stack_slots\": {}, \"callee_saved\": [{}]}}",
+            name,
+            locations.join(", "),
+            alloc.stack_slots,
+            callee_saved.join(", ")
+        );
+        res += if i + 1 < allocs.len() { ",\n" } else { "\n" };
+    }
+    res + "]\n"
+}
+
+// values live across calls have to be in callee-saved registers, others
+// prefer the caller-saved ones (which don't have to be saved in the prologue)
+fn get_allowed_registers(abi: &Abi, across_calls: bool) -> Vec<&'static str> {
+    if across_calls {
+        abi.callee_saved.to_vec()
+    } else {
+        abi.caller_saved
+            .iter()
+            .chain(abi.callee_saved)
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,275 @@
+use super::liveness::Liveness;
+use super::{allocate, format_location, Allocation, Location};
+use abi::Abi;
+use model::ir::{Function, Label, Operation, Program, RegNum, Type, TypeKind, Value};
+use options::{RegAlloc, ReportFormat};
+use std::collections::{HashMap, HashSet};
+
+// Stack maps for a garbage collector: at every call (where the collector
+// may run), the registers and stack slots holding pointers to the heap
+// which are live across the call, so the roots can be found without
+// a shadow stack. Pointers to the stack (allocas), to string literals, to
+// vtables and to functions aren't roots. A pointer computed by a GEP points
+// inside an object, it's reported with the pointer it's derived from. For
+// struct values (always on the stack) the offsets of their pointer fields
+// are given.
+
+pub struct StackMap {
+    pub block: Label,
+    pub index: usize,           // of the call in the block (as printed)
+    pub callee: Option<String>, // None for calls through pointers
+    pub roots: Vec<Root>,
+}
+
+pub struct Root {
+    pub value: RegNum,
+    pub location: Location,
+    pub offsets: Vec<i64>, // of the pointers in the location
+    pub base: Option<RegNum>,
+}
+
+fn build_stack_maps(
+    prog: &Program,
+    fun: &Function,
+    liveness: &Liveness,
+    alloc: &Allocation,
+) -> Vec<StackMap> {
+    let types = get_register_types(fun);
+    let (non_heap, bases) = analyze_pointers(fun);
+    let mut res = vec![];
+    for bl in &fun.blocks {
+        let live_after = liveness.get_live_after(bl);
+        let ops = bl
+            .body
+            .iter()
+            .zip(&live_after)
+            .filter(|(op, _)| !matches!(op, Operation::SourceSpan(_)));
+        for (index, (op, live)) in ops.enumerate() {
+            let (def, callee) = match op {
+                Operation::FunctionCall(def, _, Value::GlobalRegister(name, _), _) => {
+                    (*def, Some(name.clone()))
+                }
+                Operation::FunctionCall(def, ..) => (*def, None),
+                _ => continue,
+            };
+            let mut live: Vec<_> = live
+                .iter()
+                .filter(|reg_num| Some(**reg_num) != def && !non_heap.contains(reg_num))
+                .collect();
+            live.sort_by_key(|reg_num| reg_num.0);
+            let roots = live
+                .into_iter()
+                .filter_map(|reg_num| {
+                    let offsets = get_pointer_offsets(prog, types.get(reg_num)?);
+                    if offsets.is_empty() {
+                        return None;
+                    }
+                    Some(Root {
+                        value: *reg_num,
+                        location: alloc.locations[reg_num],
+                        offsets,
+                        base: bases.get(reg_num).cloned(),
+                    })
+                })
+                .collect();
+            res.push(StackMap {
+                block: bl.label,
+                index,
+                callee,
+                roots,
+            });
+        }
+    }
+    res
+}
+
+pub fn get_register_types(fun: &Function) -> HashMap<RegNum, Type> {
+    let mut res: HashMap<_, _> = fun.args.iter().cloned().collect();
+    let mut note = |value: &Value| {
+        if let Value::Register(reg_num, reg_type) = value {
+            res.entry(*reg_num).or_insert_with(|| *reg_type);
+        }
+    };
+    for bl in &fun.blocks {
+        for phi in &bl.phis {
+            note(&Value::Register(phi.reg_num, phi.reg_type));
+            phi.get_used_values().into_iter().for_each(&mut note);
+        }
+        bl.get_used_values().for_each(&mut note);
+    }
+    res
+}
+
+// (registers pointing to the stack or to constants, derived pointer -> its base)
+fn analyze_pointers(fun: &Function) -> (HashSet<RegNum>, HashMap<RegNum, RegNum>) {
+    let mut non_heap = HashSet::new();
+    let mut sources = HashMap::new(); // GEPs and casts -> their pointers
+    for op in fun.blocks.iter().flat_map(|bl| &bl.body) {
+        match op {
+            Operation::Alloca(dst, ..) | Operation::CastGlobalString(dst, ..) => {
+                non_heap.insert(*dst);
+            }
+            Operation::GetElementPtr(dst, _, args) => {
+                if let Some(Value::Register(src, _)) = args.first() {
+                    sources.insert(*dst, (*src, true));
+                }
+            }
+            Operation::CastPtr {
+                dst,
+                src_value: Value::Register(src, _),
+                ..
+            } => {
+                sources.insert(*dst, (*src, false));
+            }
+            _ => (),
+        }
+    }
+    let mut bases = HashMap::new();
+    for (dst, (src, is_gep)) in &sources {
+        let (mut base, mut derived) = (*src, *is_gep);
+        while let Some((src, is_gep)) = sources.get(&base) {
+            base = *src;
+            derived |= is_gep;
+        }
+        if non_heap.contains(&base) {
+            non_heap.insert(*dst);
+        } else if derived {
+            bases.insert(*dst, base);
+        }
+    }
+    (non_heap, bases)
+}
+
+// offsets of the heap pointers in a value of the type
+fn get_pointer_offsets(prog: &Program, t: &Type) -> Vec<i64> {
+    match t.kind() {
+        TypeKind::Ptr(pointee) => match pointee.kind() {
+            TypeKind::Func(..) => vec![],
+            TypeKind::Class(name) if name.ends_with(".vtable.type") => vec![],
+            _ => vec![0],
+        },
+        TypeKind::Struct(name) => {
+            let st = prog.structs.iter().find(|st| st.name == *name).unwrap();
+            let (field_offsets, _, _) = prog.get_fields_layout(&st.fields);
+            st.fields
+                .iter()
+                .zip(field_offsets)
+                .flat_map(|(field_type, field_offset)| {
+                    get_pointer_offsets(prog, field_type)
+                        .into_iter()
+                        .map(move |offset| field_offset + offset)
+                })
+                .collect()
+        }
+        _ => vec![],
+    }
+}
+
+pub fn format_stack_maps(
+    prog: &Program,
+    kind: RegAlloc,
+    abi: &Abi,
+    format: ReportFormat,
+) -> String {
+    let maps: Vec<_> = prog
+        .functions
+        .iter()
+        .map(|fun| {
+            let (liveness, alloc) = allocate(fun, kind, abi);
+            (
+                fun.name.as_str(),
+                build_stack_maps(prog, fun, &liveness, &alloc),
+            )
+        })
+        .collect();
+    match format {
+        ReportFormat::Table => format_table(&maps),
+        ReportFormat::Json => format_json(&maps),
+    }
+}
+
+fn format_root(root: &Root) -> String {
+    let mut res = format_location(root.location);
+    if root.offsets != [0] {
+        let offsets: Vec<_> = root.offsets.iter().map(|o| o.to_string()).collect();
+        res += &format!("+{{{}}}", offsets.join(", "));
+    }
+    res += &format!(" (%.r{}", root.value.0);
+    if let Some(base) = root.base {
+        res += &format!(" derived from %.r{}", base.0);
+    }
+    res + ")"
+}
+
+fn format_callee(map: &StackMap) -> String {
+    match &map.callee {
+        Some(name) => format!("@{}", name),
+        None => "indirect".to_string(),
+    }
+}
+
+fn format_table(maps: &[(&str, Vec<StackMap>)]) -> String {
+    let mut res = String::new();
+    for (name, fun_maps) in maps {
+        res += &format!("{}: {} calls\n", name, fun_maps.len());
+        for map in fun_maps {
+            let roots: Vec<_> = map.roots.iter().map(format_root).collect();
+            res += &format!(
+                "    .L{} #{} call {}: {}\n",
+                map.block.0,
+                map.index,
+                format_callee(map),
+                if roots.is_empty() {
+                    "no roots".to_string()
+                } else {
+                    roots.join(", ")
+                }
+            );
+        }
+    }
+    res
+}
+
+fn format_json(maps: &[(&str, Vec<StackMap>)]) -> String {
+    let mut res = "[\n".to_string();
+    for (i, (name, fun_maps)) in maps.iter().enumerate() {
+        let name = name.replace("\\", "\\\\").replace("\"", "\\\"");
+        let calls: Vec<_> = fun_maps
+            .iter()
+            .map(|map| {
+                let roots: Vec<_> = map
+                    .roots
+                    .iter()
+                    .map(|root| {
+                        let offsets: Vec<_> = root.offsets.iter().map(|o| o.to_string()).collect();
+                        let base = match root.base {
+                            Some(base) => format!("\"%.r{}\"", base.0),
+                            None => "null".to_string(),
+                        };
+                        format!(
+                            "{{\"value\": \"%.r{}\", \"location\": \"{}\", \"offsets\": [{}], \"base\": {}}}",
+                            root.value.0,
+                            format_location(root.location),
+                            offsets.join(", "),
+                            base
+                        )
+                    })
+                    .collect();
+                format!(
+                    "{{\"block\": \".L{}\", \"index\": {}, \"callee\": \"{}\", \"roots\": [{}]}}",
+                    map.block.0,
+                    map.index,
+                    format_callee(map),
+                    roots.join(", ")
+                )
+            })
+            .collect();
+        res += &format!(
+            "  {{\"function\": \"{}\", \"calls\": [{}]}}",
+            name,
+            calls.join(", ")
+        );
+        res += if i + 1 < maps.len() { ",\n" } else { "\n" };
+    }
+    res + "]\n"
+}
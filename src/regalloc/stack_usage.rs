@@ -0,0 +1,256 @@
+use super::stack_maps::get_register_types;
+use super::{allocate_registers, Allocation, Location};
+use abi::Abi;
+use model::ir::{Function, Operation, Program, Value};
+use optimizer::{get_indirectly_called_functions, get_stack_usage};
+use options::{RegAlloc, ReportFormat};
+use std::collections::{HashMap, HashSet};
+
+// Stack usage of the functions for a native backend, to diagnose stack
+// overflows: the estimated frame of every function (return address, saved
+// callee-saved registers, stack slots of the allocation, allocas and
+// outgoing arguments, aligned) and, over the call graph of the IR, which
+// functions are recursive (with the other functions of their cycles) and
+// the maximal stack used by a call of a function which can't recurse.
+// Calls through pointers can call any function whose address is taken,
+// the frames of the runtime aren't counted.
+
+struct FunctionUsage<'a> {
+    name: &'a str,
+    frame_bytes: i64,
+    recursion: Option<Vec<&'a str>>, // the functions of the cycle, with this one
+    max_stack_bytes: Option<i64>,    // None if it can recurse
+}
+
+pub fn format_stack_usage(
+    prog: &Program,
+    kind: RegAlloc,
+    abi: &Abi,
+    format: ReportFormat,
+) -> String {
+    let frames: HashMap<_, _> = prog
+        .functions
+        .iter()
+        .map(|fun| {
+            let alloc = allocate_registers(fun, kind, abi);
+            (fun.name.as_str(), get_frame_bytes(prog, fun, &alloc, abi))
+        })
+        .collect();
+    let callees = get_callees(prog);
+    let reachable: HashMap<_, _> = prog
+        .functions
+        .iter()
+        .map(|fun| (fun.name.as_str(), get_reachable(&callees, &fun.name)))
+        .collect();
+    let recursion: HashMap<_, _> = prog
+        .functions
+        .iter()
+        .map(|fun| {
+            let name = fun.name.as_str();
+            let cycle: Vec<_> = prog
+                .functions
+                .iter()
+                .map(|other| other.name.as_str())
+                .filter(|other| reachable[name].contains(other) && reachable[other].contains(name))
+                .collect();
+            (name, if cycle.is_empty() { None } else { Some(cycle) })
+        })
+        .collect();
+    let mut max_stack = HashMap::new();
+    let usages: Vec<_> = prog
+        .functions
+        .iter()
+        .map(|fun| FunctionUsage {
+            name: &fun.name,
+            frame_bytes: frames[fun.name.as_str()],
+            recursion: recursion[fun.name.as_str()].clone(),
+            max_stack_bytes: get_max_stack(
+                &fun.name,
+                &callees,
+                &frames,
+                &recursion,
+                &mut max_stack,
+            ),
+        })
+        .collect();
+    match format {
+        ReportFormat::Table => format_table(&usages),
+        ReportFormat::Json => format_json(&usages),
+    }
+}
+
+fn get_frame_bytes(prog: &Program, fun: &Function, alloc: &Allocation, abi: &Abi) -> i64 {
+    // a slot holds a word or a struct value
+    let types = get_register_types(fun);
+    let mut slots = vec![8; alloc.stack_slots as usize];
+    for (reg_num, loc) in &alloc.locations {
+        if let (Location::Stack(slot), Some(reg_type)) = (loc, types.get(reg_num)) {
+            let size = (prog.get_size_and_align(reg_type).0 + 7) / 8 * 8;
+            slots[*slot as usize] = slots[*slot as usize].max(size);
+        }
+    }
+    let outgoing_args = fun
+        .blocks
+        .iter()
+        .flat_map(|bl| &bl.body)
+        .filter_map(|op| match op {
+            Operation::FunctionCall(_, ret_type, _, args) => {
+                let arg_types: Vec<_> = args.iter().map(Value::get_type).collect();
+                Some(abi.lower_call(prog, ret_type, &arg_types).stack_bytes)
+            }
+            _ => None,
+        })
+        .max()
+        .unwrap_or(0);
+    let bytes = 8 // the return address (or the saved link register)
+        + 8 * alloc.used_callee_saved.len() as i64
+        + slots.iter().sum::<i64>()
+        + get_stack_usage(prog, fun)
+        + outgoing_args;
+    let align = abi.stack_alignment;
+    (bytes + align - 1) / align * align
+}
+
+// the functions of the program each function can call directly
+fn get_callees(prog: &Program) -> HashMap<&str, HashSet<&str>> {
+    let indirect = get_indirectly_called_functions(prog);
+    let defined: HashSet<_> = prog.functions.iter().map(|fun| fun.name.as_str()).collect();
+    prog.functions
+        .iter()
+        .map(|fun| {
+            let mut res = HashSet::new();
+            for op in fun.blocks.iter().flat_map(|bl| &bl.body) {
+                match op {
+                    Operation::FunctionCall(_, _, Value::GlobalRegister(name, _), _) => {
+                        res.insert(name.as_str());
+                    }
+                    Operation::FunctionCall(..) => {
+                        res.extend(
+                            indirect
+                                .iter()
+                                .filter_map(|name| defined.get(name.as_str())),
+                        );
+                    }
+                    _ => (),
+                }
+            }
+            res.retain(|name| defined.contains(name));
+            (fun.name.as_str(), res)
+        })
+        .collect()
+}
+
+// the functions which can be called during a call of the function
+fn get_reachable<'a>(callees: &HashMap<&str, HashSet<&'a str>>, name: &str) -> HashSet<&'a str> {
+    let mut res = HashSet::new();
+    let mut stack: Vec<_> = callees[name].iter().cloned().collect();
+    while let Some(callee) = stack.pop() {
+        if res.insert(callee) {
+            stack.extend(&callees[callee]);
+        }
+    }
+    res
+}
+
+// requirement: the calls of a function which can't recurse form no cycles
+fn get_max_stack<'a>(
+    name: &'a str,
+    callees: &HashMap<&'a str, HashSet<&'a str>>,
+    frames: &HashMap<&str, i64>,
+    recursion: &HashMap<&str, Option<Vec<&str>>>,
+    memo: &mut HashMap<&'a str, Option<i64>>,
+) -> Option<i64> {
+    if let Some(res) = memo.get(name) {
+        return *res;
+    }
+    let res = match recursion[name] {
+        Some(_) => None,
+        None => {
+            let mut deepest = Some(0);
+            for callee in &callees[name] {
+                let callee_max = get_max_stack(callee, callees, frames, recursion, memo);
+                deepest = match (deepest, callee_max) {
+                    (Some(a), Some(b)) => Some(a.max(b)),
+                    _ => None,
+                };
+            }
+            deepest.map(|bytes| frames[name] + bytes)
+        }
+    };
+    memo.insert(name, res);
+    res
+}
+
+fn format_recursion(usage: &FunctionUsage) -> String {
+    match &usage.recursion {
+        None => "-".to_string(),
+        Some(cycle) if cycle.len() == 1 => "self".to_string(),
+        Some(cycle) => {
+            let others: Vec<_> = cycle
+                .iter()
+                .filter(|name| **name != usage.name)
+                .cloned()
+                .collect();
+            format!("mutual with {}", others.join(", "))
+        }
+    }
+}
+
+fn format_table(usages: &[FunctionUsage]) -> String {
+    let name_width = usages
+        .iter()
+        .map(|u| u.name.len())
+        .chain(Some("function".len()))
+        .max()
+        .unwrap();
+    let mut res = format!(
+        "{:<w$}  {:>11}  {:>15}  {}\n",
+        "function",
+        "frame bytes",
+        "max stack bytes",
+        "recursion",
+        w = name_width
+    );
+    for u in usages {
+        let max_stack = match u.max_stack_bytes {
+            Some(bytes) => bytes.to_string(),
+            None => "unbounded".to_string(),
+        };
+        res += &format!(
+            "{:<w$}  {:>11}  {:>15}  {}\n",
+            u.name,
+            u.frame_bytes,
+            max_stack,
+            format_recursion(u),
+            w = name_width
+        );
+    }
+    res
+}
+
+fn format_json(usages: &[FunctionUsage]) -> String {
+    let quote = |name: &str| format!("\"{}\"", name.replace("\\", "\\\\").replace("\"", "\\\""));
+    let mut res = "[\n".to_string();
+    for (i, u) in usages.iter().enumerate() {
+        let cycle: Vec<_> = u
+            .recursion
+            .iter()
+            .flatten()
+            .map(|name| quote(name))
+            .collect();
+        let max_stack = match u.max_stack_bytes {
+            Some(bytes) => bytes.to_string(),
+            None => "null".to_string(),
+        };
+        res += &format!(
+            "  {{\"function\": {}, \"frame_bytes\": {}, \"max_stack_bytes\": {}, \"recursive\": {}, \"recursion_cycle\": [{}]}}",
+            quote(u.name),
+            u.frame_bytes,
+            max_stack,
+            u.recursion.is_some(),
+            cycle.join(", ")
+        );
+        res += if i + 1 < usages.len() { ",\n" } else { "\n" };
+    }
+    res + "]\n"
+}
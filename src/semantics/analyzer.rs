@@ -1,31 +1,124 @@
-use super::function::FunctionContext;
+use super::context_cache::ContextCache;
+use super::definite_assignment::DefiniteAssignment;
+use super::dialect;
+use super::function::{ExprTypes, FunctionContext};
+use super::generics;
 use super::global_context::GlobalContext;
-use frontend_error::{ok_if_no_error, ErrorAccumulation, FrontendError, FrontendResult};
+use super::lambdas;
+use super::lints;
+use super::nested_classes;
+use super::nested_functions;
+use super::structs;
+use super::symbols::{SymbolIndex, SymbolRecorder};
+use super::tail_recursion;
+use super::type_aliases;
+use frontend_error::{ok_if_no_error, ErrorAccumulation, ErrorKind, FrontendError, FrontendResult};
 use model::ast::*;
+use options::{CompilerOptions, Dialect};
+use std::collections::BTreeMap;
+use std::mem;
 
 pub struct SemanticAnalyzer<'a> {
     ast: &'a mut Program,
     ctx: Option<GlobalContext>,
+    options: &'a CompilerOptions,
+    warnings: Vec<FrontendError>,
+    // collected only on demand
+    expr_types: Option<ExprTypes>,
+    symbols: Option<SymbolRecorder>,
 }
 
 impl<'a> SemanticAnalyzer<'a> {
-    pub fn new(prog: &'a mut Program) -> Self {
+    pub fn new(prog: &'a mut Program, options: &'a CompilerOptions) -> Self {
         SemanticAnalyzer {
             ast: prog,
             ctx: None,
+            options,
+            warnings: vec![],
+            expr_types: None,
+            symbols: None,
         }
     }
 
     pub fn perform_full_analysis(&mut self) -> FrontendResult<()> {
+        self.perform_type_checking()?;
+        self.lower_program()
+    }
+
+    // the analysis without lowering, so the global context and the AST
+    // still have generic definitions and lambdas
+    pub fn perform_type_checking(&mut self) -> FrontendResult<()> {
+        self.check_types(None)
+    }
+
+    // for check --watch, the global context of the previous check is reused
+    // if possible; the caller puts the new one into the cache afterwards
+    pub fn perform_type_checking_with_cache(
+        &mut self,
+        cache: &mut ContextCache,
+    ) -> FrontendResult<()> {
+        self.check_types(Some(cache))
+    }
+
+    fn check_types(&mut self, cache: Option<&mut ContextCache>) -> FrontendResult<()> {
+        nested_classes::lift_nested_classes(self.ast)?;
+        nested_functions::lift_nested_functions(self.ast)?;
+        if self.options.dialect == Dialect::Core {
+            dialect::check_core_syntax(self.ast)?;
+        }
+        nested_classes::resolve_nested_class_names(self.ast);
+        type_aliases::resolve_type_aliases(self.ast)?;
+        generics::resolve_type_params(self.ast)?;
+        structs::resolve_struct_types(self.ast)?;
+        if let Some(cache) = cache {
+            self.ctx = cache.take(self.ast);
+        }
         self.calculate_global_context()?;
         self.analyze_functions()?;
-        self.check_main_signature()
+        if self.options.dialect == Dialect::Core {
+            dialect::check_core_semantics(self.ast)?;
+        }
+        if self.options.strict {
+            self.check_definite_assignment()?;
+        }
+        self.check_main_signature()?;
+        self.warnings = lints::check_lints(self.ast, &self.options.lint_levels)?;
+        Ok(())
+    }
+
+    // has to be called before the analysis
+    pub fn record_expr_types(&mut self) {
+        self.expr_types = Some(ExprTypes::default());
+    }
+
+    pub fn take_expr_types(&mut self) -> BTreeMap<Span, InnerType> {
+        self.expr_types
+            .take()
+            .map(|t| t.into_inner())
+            .unwrap_or_default()
+    }
+
+    // has to be called before the analysis
+    pub fn record_symbols(&mut self) {
+        self.symbols = Some(SymbolRecorder::default());
+    }
+
+    // requirement: the type checking succeeded, the code is the one the
+    // program was parsed from
+    pub fn take_symbol_index(&mut self, code: &str) -> SymbolIndex {
+        let recorded = self.symbols.take().unwrap_or_default();
+        let ctx = self.ctx.as_ref().expect("the type checking succeeded");
+        SymbolIndex::build(self.ast, ctx, recorded, code)
     }
 
     pub fn get_global_ctx(self) -> Option<GlobalContext> {
         self.ctx
     }
 
+    pub fn take_warnings(&mut self) -> Vec<FrontendError> {
+        mem::take(&mut self.warnings)
+    }
+
     fn calculate_global_context(&mut self) -> FrontendResult<()> {
         if self.ctx.is_some() {
             return Ok(());
@@ -44,7 +137,11 @@ impl<'a> SemanticAnalyzer<'a> {
         let mut errors = vec![];
         let err_msg = "Global analysis succeeded before function body analysis";
         let gctx = self.ctx.as_ref().expect(err_msg);
-        let gfun_ctx = FunctionContext::new(None, &gctx);
+        let expr_types = self.expr_types.as_ref();
+        let symbols = self.symbols.as_ref();
+        let gfun_ctx = FunctionContext::new(None, &gctx)
+            .with_expr_types(expr_types)
+            .with_symbols(symbols);
         for def in &mut self.ast.defs {
             match def {
                 TopDef::FunDef(ref mut fun) => {
@@ -54,7 +151,9 @@ impl<'a> SemanticAnalyzer<'a> {
                 }
                 TopDef::ClassDef(cl) => {
                     let cl_desc = gctx.get_class_description(&cl.name.inner).expect(err_msg);
-                    let cl_ctx = FunctionContext::new(Some(cl_desc), &gctx);
+                    let cl_ctx = FunctionContext::new(Some(cl_desc), &gctx)
+                        .with_expr_types(expr_types)
+                        .with_symbols(symbols);
                     for it in &mut cl.items {
                         match &mut it.inner {
                             InnerClassItemDef::Field(_, _) => (),
@@ -63,34 +162,77 @@ impl<'a> SemanticAnalyzer<'a> {
                                     .analyze_function(fun)
                                     .accumulate_errors_in(&mut errors);
                             }
-                            InnerClassItemDef::Error => unreachable!(),
+                            InnerClassItemDef::Class(_) | InnerClassItemDef::Error => {
+                                unreachable!()
+                            }
+                        }
+                    }
+                }
+                TopDef::StructDef(_) => (),
+                TopDef::TypeDef(_) | TopDef::Error => unreachable!(),
+            }
+        }
+
+        ok_if_no_error(errors)
+    }
+
+    // requirement: function bodies are already analyzed
+    fn check_definite_assignment(&self) -> FrontendResult<()> {
+        let mut errors = vec![];
+        for def in &self.ast.defs {
+            match def {
+                TopDef::FunDef(fun) => {
+                    DefiniteAssignment::check_function(fun).accumulate_errors_in(&mut errors);
+                }
+                TopDef::ClassDef(cl) => {
+                    for it in &cl.items {
+                        if let InnerClassItemDef::Method(fun) = &it.inner {
+                            DefiniteAssignment::check_function(fun)
+                                .accumulate_errors_in(&mut errors);
                         }
                     }
                 }
-                TopDef::Error => unreachable!(),
+                TopDef::StructDef(_) => (),
+                TopDef::TypeDef(_) | TopDef::Error => unreachable!(),
             }
         }
 
         ok_if_no_error(errors)
     }
 
+    // replaces generic definitions with their instances and lambdas with classes
+    // (and tail recursion with loops), so the global context has to be calculated again (requirement: whole analysis succeeded)
+    fn lower_program(&mut self) -> FrontendResult<()> {
+        nested_classes::encode_nested_class_names(self.ast);
+        generics::monomorphize(self.ast)?;
+        lambdas::lower_lambdas(self.ast);
+        tail_recursion::eliminate_tail_recursion(self.ast);
+        self.ctx = None;
+        self.calculate_global_context()
+    }
+
     fn check_main_signature(&mut self) -> FrontendResult<()> {
         let err_msg = "Global analysis succeeded before function body analysis";
         let gctx = self.ctx.as_ref().expect(err_msg);
         match gctx.get_function_description("main") {
             Some(f) => {
-                if f.ret_type.inner == InnerType::Int && f.args_types.is_empty() {
+                if f.ret_type.inner == InnerType::Int
+                    && f.args_types.is_empty()
+                    && f.type_params.is_empty()
+                {
                     Ok(())
                 } else {
                     Err(vec![FrontendError {
                     err: "Error: main function has invalid signature, it must return int and take no arguments".to_string(),
                     span: EMPTY_SPAN, // we could have correct span here, though
+                    kind: ErrorKind::Error,
                 }])
                 }
             }
             None => Err(vec![FrontendError {
                 err: "Error: main function not found".to_string(),
                 span: EMPTY_SPAN,
+                kind: ErrorKind::Error,
             }]),
         }
     }
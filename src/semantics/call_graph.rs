@@ -0,0 +1,242 @@
+use super::global_context::{ClassDesc, GlobalContext, TypeWrapper};
+use super::visitor::*;
+use model::ast::*;
+use options::ReportFormat;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+// The call graph of the type checked program (--emit=callgraph): functions
+// and methods (`Class.method`) of the program and the calls between them.
+// A method call can execute the implementation of the static class of the
+// object (maybe inherited) or one overriding it in any of its subclasses,
+// so it has an edge to each of them (virtual if there are more than one).
+// Calls in lambdas belong to the enclosing function, a function used as
+// a value counts as called there, calls of function values are unknown,
+// builtin functions and methods aren't included. The functions which aren't
+// reachable from main are marked, e.g. for dead code reports.
+
+pub struct CallGraph {
+    nodes: BTreeMap<String, bool>,           // name -> is it a method
+    edges: BTreeMap<(String, String), bool>, // (caller, callee) -> is it virtual
+}
+
+// requirement: the program is type checked (not lowered), expr_types are
+// recorded by the type checking
+pub fn build_call_graph(
+    prog: &mut Program,
+    ctx: &GlobalContext,
+    expr_types: &BTreeMap<Span, InnerType>,
+) -> CallGraph {
+    let mut subclasses: HashMap<&str, Vec<&ClassDesc>> = HashMap::new();
+    for cl in ctx.get_classes() {
+        if let Some(InnerType::Class(parent)) = cl.get_parent_type().map(|t| &t.inner) {
+            subclasses.entry(parent).or_default().push(cl);
+        }
+    }
+    let mut collector = CallCollector {
+        ctx,
+        expr_types,
+        subclasses,
+        cur_class: None,
+        caller: String::new(),
+        graph: CallGraph {
+            nodes: BTreeMap::new(),
+            edges: BTreeMap::new(),
+        },
+    };
+    for def in &mut prog.defs {
+        match def {
+            TopDef::FunDef(fun) => {
+                collector.caller = fun.name.inner.to_string();
+                collector
+                    .graph
+                    .nodes
+                    .insert(fun.name.inner.to_string(), false);
+                walk_fun(fun, &mut collector);
+            }
+            TopDef::ClassDef(cl) => {
+                collector.cur_class = Some(cl.name.inner.to_string());
+                for it in &mut cl.items {
+                    if let InnerClassItemDef::Method(fun) = &mut it.inner {
+                        let name = format!("{}.{}", cl.name.inner, fun.name.inner);
+                        collector.caller = name.to_string();
+                        collector.graph.nodes.insert(name, true);
+                        walk_fun(fun, &mut collector);
+                    }
+                }
+                collector.cur_class = None;
+            }
+            TopDef::StructDef(_) => (),
+            TopDef::TypeDef(_) | TopDef::Error => unreachable!(),
+        }
+    }
+    collector.graph
+}
+
+struct CallCollector<'a> {
+    ctx: &'a GlobalContext,
+    expr_types: &'a BTreeMap<Span, InnerType>,
+    subclasses: HashMap<&'a str, Vec<&'a ClassDesc>>,
+    cur_class: Option<String>,
+    caller: String,
+    graph: CallGraph,
+}
+
+impl<'a> CallCollector<'a> {
+    // the implementation of the method inherited by the class and the ones
+    // overriding it in its subclasses
+    fn get_implementations(&self, cl_name: &str, method_name: &str) -> Vec<String> {
+        let mut res = vec![];
+        let mut cur = self.ctx.get_class_description(cl_name);
+        while let Some(cl) = cur {
+            if let Some(TypeWrapper::Fun(_)) = cl.get_items().get(method_name) {
+                if !cl.is_builtin() {
+                    res.push(format!("{}.{}", cl.get_name(), method_name));
+                }
+                break;
+            }
+            cur = match cl.get_parent_type().map(|t| &t.inner) {
+                Some(InnerType::Class(parent)) => self.ctx.get_class_description(parent),
+                _ => None,
+            };
+        }
+        let mut stack = vec![cl_name];
+        while let Some(name) = stack.pop() {
+            for sub in self.subclasses.get(name).into_iter().flatten() {
+                if let Some(TypeWrapper::Fun(_)) = sub.get_items().get(method_name) {
+                    res.push(format!("{}.{}", sub.get_name(), method_name));
+                }
+                stack.push(sub.get_name());
+            }
+        }
+        res
+    }
+
+    fn add_edge(&mut self, callee: String, is_virtual: bool) {
+        let edge = self
+            .graph
+            .edges
+            .entry((self.caller.to_string(), callee))
+            .or_insert(false);
+        *edge |= is_virtual;
+    }
+}
+
+impl<'a> TypeVisitor for CallCollector<'a> {
+    fn visit_type(&mut self, _t: &mut InnerType) {}
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        match &expr.inner {
+            // calls of methods and function values are already rewritten
+            InnerExpr::FunCall { function_name, .. } => {
+                let name = &function_name.inner;
+                if self.ctx.get_function_description(name).is_some()
+                    && !self.ctx.is_builtin_function(name)
+                {
+                    self.add_edge(name.to_string(), false);
+                }
+            }
+            InnerExpr::ObjMethodCall {
+                obj, method_name, ..
+            } => {
+                let cl_name = match self.expr_types.get(&obj.span) {
+                    Some(InnerType::Class(name)) | Some(InnerType::GenericClass(name, _)) => {
+                        name.to_string()
+                    }
+                    Some(_) => return, // methods of arrays
+                    // `this` added by the type checking to calls without it isn't checked
+                    None => match &self.cur_class {
+                        Some(name) => name.to_string(),
+                        None => return,
+                    },
+                };
+                let implementations = self.get_implementations(&cl_name, &method_name.inner);
+                let is_virtual = implementations.len() > 1;
+                for callee in implementations {
+                    self.add_edge(callee, is_virtual);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl CallGraph {
+    // the functions reachable from main
+    fn get_reachable(&self) -> BTreeSet<&str> {
+        let mut res = BTreeSet::new();
+        let mut stack = vec!["main"];
+        while let Some(name) = stack.pop() {
+            if !res.insert(name) {
+                continue;
+            }
+            for (caller, callee) in self.edges.keys() {
+                if caller == name {
+                    stack.push(callee);
+                }
+            }
+        }
+        res
+    }
+
+    pub fn format(&self, format: ReportFormat) -> String {
+        match format {
+            ReportFormat::Table => self.format_dot(),
+            ReportFormat::Json => self.format_json(),
+        }
+    }
+
+    // unreachable functions are gray, virtual calls dashed, methods in boxes
+    fn format_dot(&self) -> String {
+        let reachable = self.get_reachable();
+        let mut res = "digraph callgraph {\n".to_string();
+        for (name, is_method) in &self.nodes {
+            let mut attrs = vec![];
+            if *is_method {
+                attrs.push("shape=box");
+            }
+            if !reachable.contains(name.as_str()) {
+                attrs.push("color=gray, fontcolor=gray");
+            }
+            match attrs.is_empty() {
+                true => res += &format!("    \"{}\";\n", name),
+                false => res += &format!("    \"{}\" [{}];\n", name, attrs.join(", ")),
+            }
+        }
+        for ((caller, callee), is_virtual) in &self.edges {
+            let style = if *is_virtual { " [style=dashed]" } else { "" };
+            res += &format!("    \"{}\" -> \"{}\"{};\n", caller, callee, style);
+        }
+        res + "}\n"
+    }
+
+    fn format_json(&self) -> String {
+        let reachable = self.get_reachable();
+        let nodes: Vec<_> = self
+            .nodes
+            .iter()
+            .map(|(name, is_method)| {
+                format!(
+                    "    {{\"name\": \"{}\", \"kind\": \"{}\", \"reachable\": {}}}",
+                    name,
+                    if *is_method { "method" } else { "function" },
+                    reachable.contains(name.as_str())
+                )
+            })
+            .collect();
+        let edges: Vec<_> = self
+            .edges
+            .iter()
+            .map(|((caller, callee), is_virtual)| {
+                format!(
+                    "    {{\"caller\": \"{}\", \"callee\": \"{}\", \"virtual\": {}}}",
+                    caller, callee, is_virtual
+                )
+            })
+            .collect();
+        format!(
+            "{{\n  \"nodes\": [\n{}\n  ],\n  \"edges\": [\n{}\n  ]\n}}\n",
+            nodes.join(",\n"),
+            edges.join(",\n")
+        )
+    }
+}
@@ -0,0 +1,78 @@
+use super::global_context::GlobalContext;
+use model::ast::*;
+
+// The global context of the previous check of a program (check --watch).
+// It depends only on the declarations (signatures of functions and methods,
+// fields, classes and structs), so it's reused if they didn't change,
+// e.g. when only function bodies were edited. The types in the context
+// have spans, so the declarations have to be at the same positions too.
+#[derive(Default)]
+pub struct ContextCache {
+    declarations: String,
+    ctx: Option<GlobalContext>,
+    reused: bool,
+}
+
+impl ContextCache {
+    // the cached context, if it was calculated for the same declarations;
+    // called after the passes rewriting declarations (e.g. lifting of nested functions)
+    pub(super) fn take(&mut self, prog: &Program) -> Option<GlobalContext> {
+        let declarations = format_declarations(prog);
+        let ctx = if declarations == self.declarations {
+            self.ctx.take()
+        } else {
+            None
+        };
+        self.declarations = declarations;
+        self.ctx = None;
+        self.reused = ctx.is_some();
+        ctx
+    }
+
+    // the context after the analysis, None if it failed before calculating it
+    pub fn put(&mut self, ctx: Option<GlobalContext>) {
+        self.ctx = ctx;
+    }
+
+    // if the last check reused the context
+    pub fn was_reused(&self) -> bool {
+        self.reused
+    }
+}
+
+// everything GlobalContext::from looks at, i.e. the program without
+// function bodies (and spans of whole definitions, which include them)
+fn format_declarations(prog: &Program) -> String {
+    let mut res = String::new();
+    for def in &prog.defs {
+        match def {
+            TopDef::FunDef(fun) => res += &format_signature(fun),
+            TopDef::ClassDef(cl) => {
+                res += &format!(
+                    "class {:?} {:?} {:?}\n",
+                    cl.name, cl.type_params, cl.parent_type
+                );
+                for it in &cl.items {
+                    match &it.inner {
+                        InnerClassItemDef::Field(t, name) => {
+                            res += &format!("field {:?} {:?}\n", t, name)
+                        }
+                        InnerClassItemDef::Method(fun) => res += &format_signature(fun),
+                        InnerClassItemDef::Class(_) | InnerClassItemDef::Error => (),
+                    }
+                }
+            }
+            TopDef::StructDef(st) => res += &format!("struct {:?} {:?}\n", st.name, st.fields),
+            TopDef::TypeDef(td) => res += &format!("typedef {:?}\n", td),
+            TopDef::Error => (),
+        }
+    }
+    res
+}
+
+fn format_signature(fun: &FunDef) -> String {
+    format!(
+        "fun {:?} {:?} {:?} {:?} {:?}\n",
+        fun.inline_hint, fun.ret_type, fun.name, fun.type_params, fun.args
+    )
+}
@@ -0,0 +1,311 @@
+use frontend_error::{ok_if_no_error, ErrorKind, FrontendError, FrontendResult};
+use model::ast::*;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+// Checks (in strict mode) that every local variable declared without
+// an initializer is assigned on every execution path before it is read.
+// It walks the typed AST, so implicit "self." is already resolved
+// and every remaining LitVar refers to a local variable or a parameter.
+
+type VarId = usize;
+
+#[derive(Clone)]
+enum State {
+    // set of definitely assigned variables
+    Reachable(HashSet<VarId>),
+    // there is no execution path to this point, so everything is assigned
+    Unreachable,
+}
+
+impl State {
+    fn merge(self, rhs: State) -> State {
+        match (self, rhs) {
+            (State::Unreachable, s) | (s, State::Unreachable) => s,
+            (State::Reachable(lhs), State::Reachable(rhs)) => {
+                State::Reachable(lhs.intersection(&rhs).cloned().collect())
+            }
+        }
+    }
+
+    fn mark_assigned(&mut self, id: VarId) {
+        if let State::Reachable(assigned) = self {
+            assigned.insert(id);
+        }
+    }
+
+    fn is_assigned(&self, id: VarId) -> bool {
+        match self {
+            State::Reachable(assigned) => assigned.contains(&id),
+            State::Unreachable => true,
+        }
+    }
+}
+
+pub struct DefiniteAssignment {
+    scopes: Vec<HashMap<String, VarId>>,
+    // enclosing loops: label and merged states at breaks out of the loop
+    loops: Vec<(Option<String>, State)>,
+    next_id: VarId,
+    reported: HashSet<VarId>,
+    errors: Vec<FrontendError>,
+}
+
+impl DefiniteAssignment {
+    pub fn check_function(fun: &FunDef) -> FrontendResult<()> {
+        let mut da = DefiniteAssignment {
+            scopes: vec![],
+            loops: vec![],
+            next_id: 0,
+            reported: HashSet::new(),
+            errors: vec![],
+        };
+        da.check_body(&fun.args, &fun.body);
+        ok_if_no_error(da.errors)
+    }
+
+    // body of a function or a lambda
+    fn check_body(&mut self, args: &[(Type, Ident)], body: &Block) {
+        let mut state = State::Reachable(HashSet::new());
+        self.scopes.push(HashMap::new());
+        for (_, id) in args {
+            let var_id = self.declare(&id.inner);
+            state.mark_assigned(var_id);
+        }
+        self.check_block(body, state);
+        self.scopes.pop();
+    }
+
+    fn declare(&mut self, name: &str) -> VarId {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.scopes.last_mut().unwrap().insert(name.to_string(), id);
+        id
+    }
+
+    // None means it's not a local variable (e.g. self)
+    fn lookup(&self, name: &str) -> Option<VarId> {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name))
+            .cloned()
+    }
+
+    fn check_block(&mut self, block: &Block, state: State) -> State {
+        self.scopes.push(HashMap::new());
+        let mut state = state;
+        for st in &block.stmts {
+            state = self.check_stmt(st, state);
+        }
+        self.scopes.pop();
+        state
+    }
+
+    fn check_stmt(&mut self, stmt: &Stmt, state: State) -> State {
+        let mut state = state;
+        use self::InnerStmt::*;
+        match &stmt.inner {
+            Empty => state,
+            Block(bl) => self.check_block(bl, state),
+            Decl {
+                var_type,
+                var_items,
+            } => {
+                for (id, init_expr) in var_items {
+                    // semantic analysis puts the variable in scope before its initializer
+                    let var_id = self.declare(&id.inner);
+                    if let Some(init_expr) = init_expr {
+                        self.check_expr(init_expr, &mut state);
+                        state.mark_assigned(var_id);
+                    } else if let InnerType::Struct(_) = var_type.inner {
+                        // there's no struct literal, fields are set one by one
+                        state.mark_assigned(var_id);
+                    }
+                }
+                state
+            }
+            Assign(lhs, rhs) => {
+                self.check_assignment(lhs, rhs, &mut state);
+                state
+            }
+            Incr(e) | Decr(e) => {
+                self.check_expr(e, &mut state);
+                state
+            }
+            Ret(opt_expr) => {
+                if let Some(e) = opt_expr {
+                    self.check_expr(e, &mut state);
+                }
+                State::Unreachable
+            }
+            Cond {
+                cond,
+                true_branch,
+                false_branch,
+            } => {
+                self.check_expr(cond, &mut state);
+                let (true_state, false_state) = match &cond.inner {
+                    InnerExpr::LitBool(true) => (state, State::Unreachable),
+                    InnerExpr::LitBool(false) => (State::Unreachable, state),
+                    _ => (state.clone(), state),
+                };
+                let true_state = self.check_block(true_branch, true_state);
+                let false_state = match false_branch {
+                    Some(bl) => self.check_block(bl, false_state),
+                    None => false_state,
+                };
+                true_state.merge(false_state)
+            }
+            While { label, cond, body } => {
+                self.check_expr(cond, &mut state);
+                match &cond.inner {
+                    InnerExpr::LitBool(true) => {
+                        // the loop is left only by break
+                        self.check_loop_body(label, body, state)
+                    }
+                    InnerExpr::LitBool(false) => {
+                        self.check_loop_body(label, body, State::Unreachable);
+                        state
+                    }
+                    _ => {
+                        // assignments in the body don't count after the loop,
+                        // because the body may not be executed at all
+                        self.check_loop_body(label, body, state.clone());
+                        state
+                    }
+                }
+            }
+            ForEach {
+                label,
+                iter_name,
+                array,
+                body,
+                ..
+            } => {
+                self.check_expr(array, &mut state);
+                self.scopes.push(HashMap::new());
+                let var_id = self.declare(&iter_name.inner);
+                let mut body_state = state.clone();
+                body_state.mark_assigned(var_id);
+                self.check_loop_body(label, body, body_state);
+                self.scopes.pop();
+                state
+            }
+            Break(label) => {
+                let pos = self.find_loop(label);
+                let break_state = mem::replace(&mut self.loops[pos].1, State::Unreachable);
+                self.loops[pos].1 = break_state.merge(state);
+                State::Unreachable
+            }
+            Continue(_) => State::Unreachable,
+            Expr(e) => {
+                self.check_expr(e, &mut state);
+                state
+            }
+            NestedFun(_) | Error => unreachable!(),
+        }
+    }
+
+    fn check_assignment(&mut self, lhs: &Expr, rhs: &Expr, state: &mut State) {
+        self.check_expr(rhs, state);
+        match &lhs.inner {
+            InnerExpr::LitVar(name) => {
+                if let Some(var_id) = self.lookup(name) {
+                    state.mark_assigned(var_id);
+                }
+            }
+            _ => self.check_expr(lhs, state),
+        }
+    }
+
+    // returns merged states at breaks out of the loop
+    fn check_loop_body(&mut self, label: &Option<Ident>, body: &Block, state: State) -> State {
+        let label = label.as_ref().map(|l| l.inner.to_string());
+        self.loops.push((label, State::Unreachable));
+        self.check_block(body, state);
+        self.loops.pop().unwrap().1
+    }
+
+    // semantic analysis guarantees the loop exists
+    fn find_loop(&self, label: &Option<Ident>) -> usize {
+        match label {
+            Some(l) => self
+                .loops
+                .iter()
+                .rposition(|(name, _)| name.as_ref() == Some(&l.inner))
+                .unwrap(),
+            None => self.loops.len() - 1,
+        }
+    }
+
+    fn check_expr(&mut self, expr: &Expr, state: &mut State) {
+        use self::BinaryOp::{And, Or};
+        use self::InnerExpr::*;
+        match &expr.inner {
+            LitVar(name) => {
+                if let Some(var_id) = self.lookup(name) {
+                    // report only the first suspicious use of a variable
+                    if !state.is_assigned(var_id) && self.reported.insert(var_id) {
+                        self.errors.push(FrontendError {
+                            err: format!(
+                                "Error: variable '{}' might be used before being assigned",
+                                name
+                            ),
+                            span: expr.span,
+                            kind: ErrorKind::Error,
+                        });
+                    }
+                }
+            }
+            LitInt(_) | LitBool(_) | LitStr(_) | LitNull | NewObject(_) => (),
+            CastType(e, _) | UnaryOp(_, e) | IncrDecr { lvalue: e, .. } => {
+                self.check_expr(e, state)
+            }
+            FunCall { args, .. } => {
+                for a in args {
+                    self.check_expr(a, state);
+                }
+            }
+            // assignments in the right operand may not be executed
+            BinaryOp(lhs, And, rhs) | BinaryOp(lhs, Or, rhs) => {
+                self.check_expr(lhs, state);
+                self.check_expr(rhs, &mut state.clone());
+            }
+            BinaryOp(lhs, _, rhs) | Range(lhs, rhs) => {
+                self.check_expr(lhs, state);
+                self.check_expr(rhs, state);
+            }
+            Assign(lhs, rhs) => self.check_assignment(lhs, rhs, state),
+            NewArray { elem_cnt, .. } => self.check_expr(elem_cnt, state),
+            ArrayElem { array, index, .. } => {
+                self.check_expr(array, state);
+                self.check_expr(index, state);
+            }
+            ObjField { obj, .. } => self.check_expr(obj, state),
+            ObjMethodCall { obj, args, .. } | ClosureCall { closure: obj, args } => {
+                self.check_expr(obj, state);
+                for a in args {
+                    self.check_expr(a, state);
+                }
+            }
+            Lambda {
+                args,
+                body,
+                captures,
+                ..
+            } => {
+                for (_, _, value) in captures {
+                    self.check_expr(value, state);
+                }
+                // the body can access only its arguments and the captured values
+                let outer_scopes = mem::take(&mut self.scopes);
+                let outer_loops = mem::take(&mut self.loops);
+                self.check_body(args, body);
+                self.scopes = outer_scopes;
+                self.loops = outer_loops;
+            }
+            NamedArg { .. } => unreachable!(), // reordered by semantic analysis
+        }
+    }
+}
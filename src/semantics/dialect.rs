@@ -0,0 +1,193 @@
+use super::global_context::get_builtin_functions;
+use super::iterators::is_iterator_loop;
+use super::visitor::*;
+use frontend_error::{ok_if_no_error, ErrorKind, FrontendError, FrontendResult};
+use model::ast::*;
+use std::collections::HashSet;
+
+// --dialect=core: only the language of the original Latte specification, with
+// its extensions (arrays, classes with inheritance and virtual methods), e.g.
+// for graders; every other extension of this compiler is an error. The syntax
+// is checked when the nested definitions are lifted (their names have dots),
+// the extensions which depend on the types (indexing of strings, foreach
+// loops over objects) after the type checking.
+
+const CORE_FUNCTIONS: &[&str] = &["printInt", "printString", "error", "readInt", "readString"];
+const EXTENSION_CLASSES: &[&str] = &[
+    OBJECT_CLASS,
+    STRING_BUILDER_CLASS,
+    INT_LIST_CLASS,
+    STRING_LIST_CLASS,
+    STRING_INT_MAP_CLASS,
+];
+
+// requirement: nested classes and functions are lifted, nothing else was done
+pub fn check_core_syntax(prog: &mut Program) -> FrontendResult<()> {
+    let mut checker = CoreChecker {
+        errors: vec![],
+        builtin_functions: get_builtin_functions().into_keys().collect(),
+    };
+    for def in &mut prog.defs {
+        match def {
+            TopDef::FunDef(fun) => checker.check_fun(fun),
+            TopDef::ClassDef(cl) => {
+                if cl.name.inner.contains('.') {
+                    checker.report("a nested class", cl.name.span);
+                }
+                if !cl.type_params.is_empty() {
+                    checker.report("a generic class", cl.name.span);
+                }
+                if let Some(t) = &cl.parent_type {
+                    checker.check_type(&t.inner, t.span);
+                }
+                for it in &mut cl.items {
+                    match &mut it.inner {
+                        InnerClassItemDef::Field(t, _) => checker.check_type(&t.inner, t.span),
+                        InnerClassItemDef::Method(fun) => checker.check_fun(fun),
+                        InnerClassItemDef::Class(_) | InnerClassItemDef::Error => unreachable!(),
+                    }
+                }
+            }
+            TopDef::StructDef(st) => checker.report("a struct", st.name.span),
+            TopDef::TypeDef(td) => checker.report("a type alias", td.name.span),
+            TopDef::Error => unreachable!(),
+        }
+    }
+    checker.into_result()
+}
+
+// requirement: the program is type checked, not lowered
+pub fn check_core_semantics(prog: &mut Program) -> FrontendResult<()> {
+    let mut checker = CoreChecker {
+        errors: vec![],
+        builtin_functions: HashSet::new(),
+    };
+    for def in &mut prog.defs {
+        if let TopDef::FunDef(_) | TopDef::ClassDef(_) = def {
+            walk_top_def(def, &mut SemanticsChecker(&mut checker));
+        }
+    }
+    checker.into_result()
+}
+
+struct CoreChecker {
+    errors: Vec<FrontendError>,
+    builtin_functions: HashSet<String>,
+}
+
+impl CoreChecker {
+    fn report(&mut self, what: &str, span: Span) {
+        self.errors.push(FrontendError {
+            err: format!(
+                "Error: {} is an extension of Latte, not allowed with --dialect=core",
+                what
+            ),
+            span,
+            kind: ErrorKind::Error,
+        });
+    }
+
+    fn into_result(mut self) -> FrontendResult<()> {
+        self.errors.sort_by_key(|e| e.span);
+        ok_if_no_error(self.errors)
+    }
+
+    fn check_fun(&mut self, fun: &mut FunDef) {
+        if fun.name.inner.contains('.') {
+            self.report("a nested function", fun.name.span);
+        }
+        if !fun.type_params.is_empty() {
+            self.report("a generic function", fun.name.span);
+        }
+        if fun.inline_hint != InlineHint::Auto {
+            self.report("an inline hint", fun.span);
+        }
+        self.check_type(&fun.ret_type.inner, fun.ret_type.span);
+        for (t, _) in &fun.args {
+            self.check_type(&t.inner, t.span);
+        }
+        walk_block(&mut fun.body, self);
+    }
+
+    fn check_type(&mut self, t: &InnerType, span: Span) {
+        match t {
+            InnerType::Array(subtype) => self.check_type(subtype, span),
+            InnerType::Class(name) if EXTENSION_CLASSES.contains(&name.as_str()) => {
+                self.report(&format!("the builtin class {}", name), span)
+            }
+            InnerType::GenericClass(..) => self.report("a generic type", span),
+            InnerType::Function(..) => self.report("a function type", span),
+            _ => (),
+        }
+    }
+}
+
+impl TypeVisitor for CoreChecker {
+    // checked with the spans of the statements and expressions
+    fn visit_type(&mut self, _t: &mut InnerType) {}
+
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        match &stmt.inner {
+            InnerStmt::Decl { var_type, .. } => self.check_type(&var_type.inner, var_type.span),
+            InnerStmt::While { label, .. } | InnerStmt::ForEach { label, .. }
+                if label.is_some() =>
+            {
+                self.report("a labeled loop", stmt.span)
+            }
+            InnerStmt::Break(_) => self.report("break", stmt.span),
+            InnerStmt::Continue(_) => self.report("continue", stmt.span),
+            _ => (),
+        }
+        if let InnerStmt::ForEach { iter_type, .. } = &stmt.inner {
+            self.check_type(&iter_type.inner, iter_type.span);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        match &expr.inner {
+            InnerExpr::FunCall { function_name, .. }
+                if self.builtin_functions.contains(&function_name.inner)
+                    && !CORE_FUNCTIONS.contains(&function_name.inner.as_str()) =>
+            {
+                let what = format!("the builtin function {}", function_name.inner);
+                self.report(&what, function_name.span)
+            }
+            InnerExpr::BinaryOp(_, BinaryOp::RefEQ, _) => self.report("===", expr.span),
+            InnerExpr::BinaryOp(_, BinaryOp::RefNE, _) => self.report("!==", expr.span),
+            InnerExpr::NewArray { elem_type: t, .. } | InnerExpr::NewObject(t) => {
+                self.check_type(&t.inner, t.span)
+            }
+            InnerExpr::Lambda { .. } => self.report("a lambda", expr.span),
+            InnerExpr::ClosureCall { .. } => self.report("a call of a function value", expr.span),
+            InnerExpr::NamedArg { name, .. } => self.report("a named argument", name.span),
+            InnerExpr::Range(..) => self.report("a range", expr.span),
+            InnerExpr::Assign(..) => self.report("an assignment expression", expr.span),
+            InnerExpr::IncrDecr { .. } => {
+                self.report("an increment or decrement expression", expr.span)
+            }
+            _ => (),
+        }
+    }
+}
+
+struct SemanticsChecker<'a>(&'a mut CoreChecker);
+
+impl<'a> TypeVisitor for SemanticsChecker<'a> {
+    fn visit_type(&mut self, _t: &mut InnerType) {}
+
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        if is_iterator_loop(stmt) {
+            self.0.report("a foreach loop over an object", stmt.span);
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        if let InnerExpr::ArrayElem {
+            is_array_a_string: Some(true),
+            ..
+        } = expr.inner
+        {
+            self.0.report("indexing of strings", expr.span);
+        }
+    }
+}
@@ -1,21 +1,56 @@
+use super::generics::infer_type_args;
 use super::global_context::{ClassDesc, FunDesc, GlobalContext, TypeWrapper};
-use frontend_error::{ok_if_no_error, ErrorAccumulation, FrontendError, FrontendResult};
+use super::iterators::{self, HAS_NEXT_METHOD, NEXT_METHOD};
+use super::symbols::{Occurrence, SymbolKind, SymbolRecorder, SymbolTarget};
+use frontend_error::{
+    error_with_note, ok_if_no_error, ErrorAccumulation, ErrorKind, FrontendError, FrontendResult,
+};
 use model::ast::*;
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeMap, HashMap};
+use std::mem;
+
+// types of the checked expressions by their spans, e.g. for the --dump-types output
+pub type ExprTypes = RefCell<BTreeMap<Span, InnerType>>;
 
 pub struct FunctionContext<'a> {
     class_ctx: Option<&'a ClassDesc>,
     global_ctx: &'a GlobalContext,
+    expr_types: Option<&'a ExprTypes>,
+    symbols: Option<&'a SymbolRecorder>,
 }
 
 enum Env<'a> {
     Root(&'a FunctionContext<'a>),
     Nested {
         parent: &'a Env<'a>,
-        locals: HashMap<String, Type>,
+        // with spans of the names, for the diagnostics
+        locals: HashMap<String, (Type, Span)>,
+        is_params: bool, // parameters of a function or a lambda
+    },
+    // boundary of a lambda body, collects variables captured from the enclosing function
+    Lambda {
+        parent: &'a Env<'a>,
+        captures: RefCell<Vec<(String, InnerType)>>,
+    },
+    // body of a loop, records if it's left by break or continue
+    Loop {
+        parent: &'a Env<'a>,
+        label: Option<String>,
+        has_break: Cell<bool>,
+        has_continue: Cell<bool>,
     },
 }
 
+// how a variable (or a function) is accessed from the current function
+#[derive(Clone, Copy)]
+enum VarAccess {
+    Local, // local variable, parameter or global function
+    Member,
+    Captured,
+    CapturedMember,
+}
+
 impl<'a> Env<'a> {
     pub fn new_root(fctx: &'a FunctionContext<'a>) -> Env<'a> {
         Env::Root(fctx)
@@ -25,6 +60,31 @@ impl<'a> Env<'a> {
         Env::Nested {
             parent,
             locals: HashMap::new(),
+            is_params: false,
+        }
+    }
+
+    pub fn new_params(parent: &'a Env<'a>) -> Env<'a> {
+        Env::Nested {
+            parent,
+            locals: HashMap::new(),
+            is_params: true,
+        }
+    }
+
+    pub fn new_lambda(parent: &'a Env<'a>) -> Env<'a> {
+        Env::Lambda {
+            parent,
+            captures: RefCell::new(vec![]),
+        }
+    }
+
+    pub fn new_loop(parent: &'a Env<'a>, label: &Option<Ident>) -> Env<'a> {
+        Env::Loop {
+            parent,
+            label: label.as_ref().map(|l| l.inner.to_string()),
+            has_break: Cell::new(false),
+            has_continue: Cell::new(false),
         }
     }
 
@@ -34,34 +94,65 @@ impl<'a> Env<'a> {
                 err: "Error: \"this\" variable is reserved for class methods and can't be defined"
                     .to_string(),
                 span: name.span,
+                kind: ErrorKind::Error,
             }]);
         }
         match self {
-            Env::Root(_) => unreachable!(),
-            Env::Nested { ref mut locals, .. } => {
-                if locals.insert(name.inner, var_type).is_some() {
-                    Err(vec![FrontendError {
-                        err: "Error: variable already defined in current scope".to_string(),
-                        span: name.span,
-                    }])
-                } else {
-                    Ok(())
+            Env::Root(_) | Env::Lambda { .. } | Env::Loop { .. } => unreachable!(),
+            // all the checks of redeclared names are here, the codegen relies on them
+            Env::Nested {
+                locals,
+                parent,
+                is_params,
+            } => {
+                if let Some((_, prev_span)) = locals.get(&name.inner) {
+                    let err = if *is_params {
+                        "Error: duplicate parameter name"
+                    } else {
+                        "Error: variable already defined in current scope"
+                    };
+                    return Err(error_with_note(
+                        err,
+                        name.span,
+                        "previously declared here",
+                        *prev_span,
+                    ));
+                }
+                // the outermost block of a body is a separate scope,
+                // but redeclaring a parameter there is most likely a mistake
+                if let Env::Nested {
+                    locals: params,
+                    is_params: true,
+                    ..
+                } = parent
+                {
+                    if let Some((_, param_span)) = params.get(&name.inner) {
+                        return Err(error_with_note(
+                            "Error: variable shadows a parameter",
+                            name.span,
+                            "parameter declared here",
+                            *param_span,
+                        ));
+                    }
                 }
+                locals.insert(name.inner, (var_type, name.span));
+                Ok(())
             }
         }
     }
 
-    // returns type & is member of a class
-    pub fn get_variable(&self, name: &str, span: Span) -> FrontendResult<(InnerType, bool)> {
+    pub fn get_variable(&self, name: &str, span: Span) -> FrontendResult<(InnerType, VarAccess)> {
         match self {
             Env::Root(ctx) => {
                 let mut err_msg = None;
                 if let Some(cctx) = ctx.class_ctx {
                     if name == THIS_VAR {
-                        return Ok((InnerType::Class(cctx.get_name().to_string()), false));
+                        return Ok((cctx.get_self_type(), VarAccess::Local));
                     }
                     match cctx.get_item(ctx.global_ctx, name) {
-                        Some(TypeWrapper::Var(t)) => return Ok((t.inner.clone(), true)),
+                        Some(TypeWrapper::Var(t)) => {
+                            return Ok((t.inner.clone(), VarAccess::Member))
+                        }
                         Some(TypeWrapper::Fun(_)) => {
                             err_msg = Some("Error: expected variable, found a class method")
                         }
@@ -78,23 +169,38 @@ impl<'a> Env<'a> {
                 Err(vec![FrontendError {
                     err: err_msg.to_string(),
                     span,
+                    kind: ErrorKind::Error,
                 }])
             }
-            Env::Nested { locals, parent } => match locals.get(name) {
-                Some(t) => Ok((t.inner.clone(), false)),
+            Env::Nested { locals, parent, .. } => match locals.get(name) {
+                Some((t, _)) => Ok((t.inner.clone(), VarAccess::Local)),
                 None => parent.get_variable(name, span),
             },
+            Env::Loop { parent, .. } => parent.get_variable(name, span),
+            Env::Lambda { parent, .. } => {
+                let (t, access) = parent.get_variable(name, span)?;
+                let access = match access {
+                    VarAccess::Local | VarAccess::Captured => {
+                        self.add_capture(name, &t);
+                        VarAccess::Captured
+                    }
+                    VarAccess::Member | VarAccess::CapturedMember => {
+                        self.capture_self(span)?;
+                        VarAccess::CapturedMember
+                    }
+                };
+                Ok((t, access))
+            }
         }
     }
 
-    // returns fun desc & is a class method
-    pub fn get_function(&self, name: &str, span: Span) -> FrontendResult<(&'a FunDesc, bool)> {
+    pub fn get_function(&self, name: &str, span: Span) -> FrontendResult<(&'a FunDesc, VarAccess)> {
         match self {
             Env::Root(ctx) => {
                 let mut err_msg = None;
                 if let Some(cctx) = ctx.class_ctx {
                     match cctx.get_item(ctx.global_ctx, name) {
-                        Some(TypeWrapper::Fun(f)) => return Ok((f, true)),
+                        Some(TypeWrapper::Fun(f)) => return Ok((f, VarAccess::Member)),
                         Some(TypeWrapper::Var(_)) => {
                             err_msg = Some("Error: expected function, found a class field")
                         }
@@ -104,22 +210,262 @@ impl<'a> Env<'a> {
                 let err_msg = match err_msg {
                     Some(e) => e,
                     None => match ctx.global_ctx.get_function_description(name) {
-                        Some(f) => return Ok((f, false)),
+                        Some(f) => return Ok((f, VarAccess::Local)),
                         None => "Error: function not defined",
                     },
                 };
                 Err(vec![FrontendError {
                     err: err_msg.to_string(),
                     span,
+                    kind: ErrorKind::Error,
                 }])
             }
-            Env::Nested { locals, parent } => match locals.get(name) {
+            Env::Nested { locals, parent, .. } => match locals.get(name) {
                 Some(_) => Err(vec![FrontendError {
                     err: "Error: expected function, got a variable".to_string(),
                     span,
+                    kind: ErrorKind::Error,
                 }]),
                 None => parent.get_function(name, span),
             },
+            Env::Loop { parent, .. } => parent.get_function(name, span),
+            Env::Lambda { parent, .. } => match parent.get_function(name, span)? {
+                (f, VarAccess::Local) => Ok((f, VarAccess::Local)),
+                (f, _) => {
+                    self.capture_self(span)?;
+                    Ok((f, VarAccess::CapturedMember))
+                }
+            },
+        }
+    }
+
+    // the span of the declaration of the local variable (or parameter)
+    // and whether it's a parameter
+    fn find_local(&self, name: &str) -> Option<(Span, bool)> {
+        match self {
+            Env::Root(_) => None,
+            Env::Nested {
+                locals,
+                parent,
+                is_params,
+            } => match locals.get(name) {
+                Some((_, span)) => Some((*span, *is_params)),
+                None => parent.find_local(name),
+            },
+            Env::Loop { parent, .. } | Env::Lambda { parent, .. } => parent.find_local(name),
+        }
+    }
+
+    fn add_capture(&self, name: &str, t: &InnerType) {
+        if let Env::Lambda { captures, .. } = self {
+            let mut captures = captures.borrow_mut();
+            if captures.iter().all(|(n, _)| n != name) {
+                captures.push((name.to_string(), t.clone()));
+            }
+        }
+    }
+
+    // members are accessed through captured self
+    fn capture_self(&self, span: Span) -> FrontendResult<()> {
+        if let Env::Lambda { parent, .. } = self {
+            let (self_type, _) = parent.get_variable(THIS_VAR, span)?;
+            self.add_capture(THIS_VAR, &self_type);
+        }
+        Ok(())
+    }
+
+    // innermost enclosing loop with the label (any loop if there's no label),
+    // loops outside of the current function or lambda aren't visible
+    fn find_loop(&self, label: Option<&str>) -> Option<&Env<'a>> {
+        match self {
+            Env::Root(_) | Env::Lambda { .. } => None,
+            Env::Nested { parent, .. } => parent.find_loop(label),
+            Env::Loop {
+                parent,
+                label: loop_label,
+                ..
+            } => match label {
+                Some(l) if loop_label.as_ref().map(|s| s.as_str()) != Some(l) => {
+                    parent.find_loop(label)
+                }
+                _ => Some(self),
+            },
+        }
+    }
+
+    fn mark_jump(&self, label: &Option<Ident>, is_break: bool, span: Span) -> FrontendResult<()> {
+        match self.find_loop(label.as_ref().map(|l| l.inner.as_str())) {
+            Some(Env::Loop {
+                has_break,
+                has_continue,
+                ..
+            }) => {
+                if is_break {
+                    has_break.set(true);
+                } else {
+                    has_continue.set(true);
+                }
+                Ok(())
+            }
+            Some(_) => unreachable!(),
+            None => Err(vec![FrontendError {
+                err: match (label, is_break) {
+                    (Some(l), _) => format!("Error: undefined loop label {}", l.inner),
+                    (None, true) => "Error: break outside of a loop".to_string(),
+                    (None, false) => "Error: continue outside of a loop".to_string(),
+                },
+                span,
+                kind: ErrorKind::Error,
+            }]),
+        }
+    }
+
+    // (has_break, has_continue)
+    fn loop_jumps(&self) -> (bool, bool) {
+        match self {
+            Env::Loop {
+                has_break,
+                has_continue,
+                ..
+            } => (has_break.get(), has_continue.get()),
+            _ => unreachable!(),
+        }
+    }
+
+    fn take_captures(self) -> Vec<(String, InnerType)> {
+        match self {
+            Env::Lambda { captures, .. } => captures.into_inner(),
+            _ => unreachable!(),
+        }
+    }
+}
+
+// replaces `name: value` arguments with values at positions of the parameters
+// (so the arguments are evaluated in order of the parameters)
+fn reorder_named_args(
+    fun_desc: &FunDesc,
+    args: &mut [Box<Expr>],
+    span: Span,
+) -> FrontendResult<()> {
+    if !args
+        .iter()
+        .any(|a| matches!(a.inner, InnerExpr::NamedArg { .. }))
+    {
+        return Ok(());
+    }
+
+    let mut errors = vec![];
+    // index of the parameter for each argument
+    let mut positions = vec![];
+    let mut after_named = false;
+    for (i, a) in args.iter().enumerate() {
+        match &a.inner {
+            InnerExpr::NamedArg { name, .. } => {
+                after_named = true;
+                match fun_desc.args_names.iter().position(|n| *n == name.inner) {
+                    Some(pos) if !positions.contains(&pos) => positions.push(pos),
+                    Some(_) => errors.push(FrontendError {
+                        err: format!("Error: argument {} specified more than once", name.inner),
+                        span: name.span,
+                        kind: ErrorKind::Error,
+                    }),
+                    None => errors.push(FrontendError {
+                        err: format!(
+                            "Error: function {} has no parameter named {}",
+                            fun_desc.name, name.inner
+                        ),
+                        span: name.span,
+                        kind: ErrorKind::Error,
+                    }),
+                }
+            }
+            _ if after_named => errors.push(FrontendError {
+                err: "Error: positional argument after named arguments".to_string(),
+                span: a.span,
+                kind: ErrorKind::Error,
+            }),
+            _ => positions.push(i),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+    if args.len() > fun_desc.args_names.len() {
+        return Err(vec![FrontendError {
+            err: format!(
+                "Error: expected {} argument(s), got {}.",
+                fun_desc.args_names.len(),
+                args.len()
+            ),
+            span,
+            kind: ErrorKind::Error,
+        }]);
+    }
+    for (i, name) in fun_desc.args_names.iter().enumerate() {
+        if !positions.contains(&i) {
+            errors.push(FrontendError {
+                err: format!("Error: missing argument {}", name),
+                span,
+                kind: ErrorKind::Error,
+            });
+        }
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let mut new_args: Vec<_> = args.iter().map(|_| None).collect();
+    for (a, pos) in args.iter_mut().zip(positions) {
+        let placeholder = Box::new(ItemWithSpan {
+            inner: InnerExpr::LitNull,
+            span,
+        });
+        let a = mem::replace(a, placeholder);
+        new_args[pos] = Some(match a.inner {
+            InnerExpr::NamedArg { value, .. } => value,
+            _ => a,
+        });
+    }
+    for (a, new_a) in args.iter_mut().zip(new_args) {
+        *a = new_a.expect("every parameter has an argument");
+    }
+    Ok(())
+}
+
+fn is_lambda_env(expr: &Expr) -> bool {
+    match &expr.inner {
+        InnerExpr::LitVar(name) => name == LAMBDA_ENV_VAR,
+        _ => false,
+    }
+}
+
+// expression accessing the variable, e.g. self.x for a class field
+fn access_expr(name: &str, access: VarAccess, span: Span) -> InnerExpr {
+    let lit_var = |name: &str| {
+        Box::new(ItemWithSpan {
+            inner: InnerExpr::LitVar(name.to_string()),
+            span,
+        })
+    };
+    let obj_field = |obj, name: &str| InnerExpr::ObjField {
+        obj,
+        is_obj_an_array: Some(false),
+        is_obj_a_struct: Some(false),
+        field: ItemWithSpan {
+            inner: name.to_string(),
+            span,
+        },
+    };
+    match access {
+        VarAccess::Local => InnerExpr::LitVar(name.to_string()),
+        VarAccess::Member => obj_field(lit_var(THIS_VAR), name),
+        VarAccess::Captured => obj_field(lit_var(LAMBDA_ENV_VAR), name),
+        VarAccess::CapturedMember => {
+            let captured_self = ItemWithSpan {
+                inner: obj_field(lit_var(LAMBDA_ENV_VAR), THIS_VAR),
+                span,
+            };
+            obj_field(Box::new(captured_self), name)
         }
     }
 }
@@ -129,30 +475,118 @@ impl<'a> FunctionContext<'a> {
         FunctionContext {
             class_ctx: cctx,
             global_ctx: gctx,
+            expr_types: None,
+            symbols: None,
+        }
+    }
+
+    pub fn with_expr_types(mut self, expr_types: Option<&'a ExprTypes>) -> Self {
+        self.expr_types = expr_types;
+        self
+    }
+
+    pub fn with_symbols(mut self, symbols: Option<&'a SymbolRecorder>) -> Self {
+        self.symbols = symbols;
+        self
+    }
+
+    fn record_symbol(&self, id: &Ident, kind: SymbolKind, target: SymbolTarget, is_def: bool) {
+        if let Some(symbols) = self.symbols {
+            let occurrence = Occurrence {
+                name: id.inner.to_string(),
+                kind,
+                target,
+                is_definition: is_def,
+            };
+            // the last one wins, like for the types of expressions checked again
+            symbols.borrow_mut().insert(id.span, occurrence);
+        }
+    }
+
+    // adds the variable to the scope and records its definition
+    fn declare_variable(&self, env: &mut Env<'a>, t: &Type, id: &Ident) -> FrontendResult<()> {
+        env.add_variable(t.clone(), id.clone())?;
+        let kind = match env {
+            Env::Nested {
+                is_params: true, ..
+            } => SymbolKind::Param,
+            _ => SymbolKind::Local,
+        };
+        self.record_symbol(id, kind, SymbolTarget::Variable(id.span), true);
+        Ok(())
+    }
+
+    fn record_variable_use(&self, id: &Ident, access: VarAccess, env: &Env<'a>) {
+        match access {
+            VarAccess::Local | VarAccess::Captured => {
+                // `this` isn't declared
+                if let Some((decl_span, is_param)) = env.find_local(&id.inner) {
+                    let kind = if is_param {
+                        SymbolKind::Param
+                    } else {
+                        SymbolKind::Local
+                    };
+                    self.record_symbol(id, kind, SymbolTarget::Variable(decl_span), false);
+                }
+            }
+            VarAccess::Member | VarAccess::CapturedMember => {
+                if let Some(cctx) = self.class_ctx {
+                    self.record_member_use(cctx, id, SymbolKind::Field);
+                }
+            }
+        }
+    }
+
+    fn record_function_use(&self, id: &Ident, access: VarAccess) {
+        match (access, self.class_ctx) {
+            (VarAccess::Local, _) | (_, None) => {
+                let target = SymbolTarget::Function(id.inner.to_string());
+                self.record_symbol(id, SymbolKind::Function, target, false);
+            }
+            (_, Some(cctx)) => self.record_member_use(cctx, id, SymbolKind::Method),
+        }
+    }
+
+    fn record_member_use(&self, cl_desc: &ClassDesc, id: &Ident, kind: SymbolKind) {
+        if let Some(origin) = cl_desc.get_item_origin(self.global_ctx, &id.inner) {
+            let target = SymbolTarget::Member(origin.get_name().to_string(), id.inner.to_string());
+            self.record_symbol(id, kind, target, false);
         }
     }
 
     pub fn analyze_function(&self, fun: &'a mut FunDef) -> FrontendResult<()> {
-        let mut errors = vec![];
         let root = Env::new_root(&self);
-        let mut params_env = Env::new_nested(&root);
-        for (t, id) in &fun.args {
+        self.analyze_body(&fun.ret_type, &fun.args, &mut fun.body, &root)
+    }
+
+    // body of a function or a lambda
+    fn analyze_body(
+        &self,
+        ret_type: &Type,
+        args: &[(Type, Ident)],
+        body: &mut Block,
+        parent_env: &Env<'a>,
+    ) -> FrontendResult<()> {
+        let mut errors = vec![];
+        let mut params_env = Env::new_params(parent_env);
+        for (t, id) in args {
             match self.global_ctx.check_local_var_type(&t) {
-                Ok(()) => params_env
-                    .add_variable(t.clone(), id.clone())
+                Ok(()) => self
+                    .declare_variable(&mut params_env, t, id)
                     .accumulate_errors_in(&mut errors),
                 Err(err) => errors.extend(err),
             }
         }
 
         match (
-            self.enter_block(&fun.ret_type, &mut fun.body, &params_env),
-            &fun.ret_type.inner,
+            self.enter_block(ret_type, body, &params_env),
+            &ret_type.inner,
         ) {
             (Ok(true), _) | (Ok(false), InnerType::Void) => (),
             (Ok(false), _) => errors.push(FrontendError {
                 err: "Error: detected potential execution path without return".to_string(),
-                span: fun.body.span,
+                span: body.span,
+                kind: ErrorKind::Error,
             }),
             (Err(err), _) => errors.extend(err),
         }
@@ -160,11 +594,55 @@ impl<'a> FunctionContext<'a> {
         ok_if_no_error(errors)
     }
 
+    // the type of the object iterated by a foreach loop (which is lowered to
+    // a while loop), None for other expressions (checked as arrays or ranges)
+    fn get_iterated_object_type(
+        &self,
+        obj: &Expr,
+        cur_env: &Env<'a>,
+    ) -> FrontendResult<Option<InnerType>> {
+        if let InnerExpr::Range(..) = obj.inner {
+            return Ok(None);
+        }
+        // the expression is checked again in the lowered loop
+        let obj_type = match self.check_expression_get_type(&mut obj.clone(), cur_env) {
+            Ok(t @ InnerType::Class(_)) | Ok(t @ InnerType::GenericClass(..)) => t,
+            _ => return Ok(None),
+        };
+        let name = match &obj_type {
+            InnerType::Class(name) | InnerType::GenericClass(name, _) => name.as_str(),
+            _ => unreachable!(),
+        };
+        if [INT_LIST_CLASS, STRING_LIST_CLASS, STRING_INT_MAP_CLASS].contains(&name) {
+            return Ok(Some(obj_type));
+        }
+        let has_method = |method_name| match self
+            .global_ctx
+            .get_class_description(name)
+            .and_then(|cl| cl.get_item(self.global_ctx, method_name))
+        {
+            Some(TypeWrapper::Fun(fun_desc)) => fun_desc.args_types.is_empty(),
+            _ => false,
+        };
+        if has_method(HAS_NEXT_METHOD) && has_method(NEXT_METHOD) {
+            Ok(Some(obj_type))
+        } else {
+            Err(vec![FrontendError {
+                err: format!(
+                    "Error: foreach can iterate only over arrays, builtin collections and objects with methods {}() and {}(), not over {}",
+                    HAS_NEXT_METHOD, NEXT_METHOD, name
+                ),
+                span: obj.span,
+                kind: ErrorKind::Error,
+            }])
+        }
+    }
+
     // return value: if block always returns
     fn enter_block(
         &self,
-        ret_type: &'a Type,
-        block: &'a mut Block,
+        ret_type: &Type,
+        block: &mut Block,
         parent_env: &Env<'a>,
     ) -> FrontendResult<bool> {
         let mut errors = vec![];
@@ -182,6 +660,16 @@ impl<'a> FunctionContext<'a> {
             //     })
             // }
             let st_span = st.span; // making borrow checker happy
+            if let ForEach { array, .. } = &st.inner {
+                match self.get_iterated_object_type(array, &cur_env) {
+                    Ok(Some(obj_type)) => iterators::lower_iterator_loop(st, &obj_type),
+                    Ok(None) => (),
+                    Err(err) => {
+                        errors.extend(err);
+                        continue;
+                    }
+                }
+            }
             match &mut st.inner {
                 Empty => (),
                 Block(ref mut bl) => match self.enter_block(ret_type, bl, &cur_env) {
@@ -201,8 +689,7 @@ impl<'a> FunctionContext<'a> {
                     };
                     for (id, init_expr) in var_items {
                         if corr_type {
-                            cur_env
-                                .add_variable(var_type.clone(), id.clone())
+                            self.declare_variable(&mut cur_env, var_type, id)
                                 .accumulate_errors_in(&mut errors);
                         }
                         if let Some(ref mut init_expr) = init_expr {
@@ -239,6 +726,7 @@ impl<'a> FunctionContext<'a> {
                                     err: "Error: type of returned expression mismatch declared return type"
                                         .to_string(),
                                     span: st_span,
+                                    kind: ErrorKind::Error,
                                 })
                             }
                         }
@@ -278,52 +766,104 @@ impl<'a> FunctionContext<'a> {
                         None => br1_ret && br2_ret,
                     };
                 }
-                While(ref mut cond_expr, ref mut body_bl) => {
+                While {
+                    label,
+                    cond: ref mut cond_expr,
+                    body: ref mut body_bl,
+                } => {
                     self.check_expression_check_type(cond_expr, &InnerType::Bool, &cur_env)
                         .accumulate_errors_in(&mut errors);
-                    match self.enter_block(ret_type, body_bl, &cur_env) {
-                        Ok(does_ret) => after_ret |= does_ret,
-                        Err(err) => errors.extend(err),
+                    self.check_loop_label(label, &cur_env)
+                        .accumulate_errors_in(&mut errors);
+                    let loop_env = Env::new_loop(&cur_env, label);
+                    let does_ret = match self.enter_block(ret_type, body_bl, &loop_env) {
+                        Ok(does_ret) => does_ret,
+                        Err(err) => {
+                            errors.extend(err);
+                            false
+                        }
                     };
+                    let (has_break, has_continue) = loop_env.loop_jumps();
+                    after_ret |= does_ret && !has_break && !has_continue;
                     if let InnerExpr::LitBool(ret) = &cond_expr.inner {
                         // while (true) just loops, so we don't have to check if we return after it
-                        // while (false) just need to be skipped,
-                        after_ret |= *ret;
+                        // (unless it's left by break), while (false) just need to be skipped,
+                        after_ret |= *ret && !has_break;
                     };
                 }
                 ForEach {
+                    label,
                     iter_type,
                     iter_name,
                     ref mut array,
                     body,
                 } => {
-                    let mut new_env = Env::new_nested(&cur_env);
+                    self.check_loop_label(label, &cur_env)
+                        .accumulate_errors_in(&mut errors);
+                    let loop_env = Env::new_loop(&cur_env, label);
+                    let mut new_env = Env::new_nested(&loop_env);
                     match self.global_ctx.check_local_var_type(&iter_type) {
                         Ok(()) => {
-                            new_env
-                                .add_variable(iter_type.clone(), iter_name.clone())
+                            self.declare_variable(&mut new_env, iter_type, iter_name)
                                 .accumulate_errors_in(&mut errors);
 
-                            self.check_expression_check_type(
-                                array,
-                                &InnerType::Array(Box::new(iter_type.inner.clone())),
-                                &cur_env,
-                            )
-                            .accumulate_errors_in(&mut errors)
+                            match &mut array.inner {
+                                InnerExpr::Range(start, end) => {
+                                    if iter_type.inner != InnerType::Int {
+                                        errors.push(FrontendError {
+                                            err: "Error: only an int variable can iterate over a range".to_string(),
+                                            span: iter_type.span,
+                                            kind: ErrorKind::Error,
+                                        });
+                                    }
+                                    for e in &mut [start, end] {
+                                        self.check_expression_check_type(
+                                            e,
+                                            &InnerType::Int,
+                                            &cur_env,
+                                        )
+                                        .accumulate_errors_in(&mut errors);
+                                    }
+                                }
+                                _ => self
+                                    .check_expression_check_type(
+                                        array,
+                                        &InnerType::Array(Box::new(iter_type.inner.clone())),
+                                        &cur_env,
+                                    )
+                                    .accumulate_errors_in(&mut errors),
+                            }
                         }
                         Err(err) => errors.extend(err),
                     }
 
-                    match self.enter_block(ret_type, body, &new_env) {
-                        Ok(does_ret) => after_ret |= does_ret,
-                        Err(err) => errors.extend(err),
-                    }
+                    let does_ret = match self.enter_block(ret_type, body, &new_env) {
+                        Ok(does_ret) => does_ret,
+                        Err(err) => {
+                            errors.extend(err);
+                            false
+                        }
+                    };
+                    let (has_break, has_continue) = loop_env.loop_jumps();
+                    after_ret |= does_ret && !has_break && !has_continue;
+                }
+                Break(label) => {
+                    after_ret = true;
+                    cur_env
+                        .mark_jump(label, true, st_span)
+                        .accumulate_errors_in(&mut errors);
+                }
+                Continue(label) => {
+                    after_ret = true;
+                    cur_env
+                        .mark_jump(label, false, st_span)
+                        .accumulate_errors_in(&mut errors);
                 }
                 Expr(ref mut subexpr) => match self.check_expression_get_type(subexpr, &cur_env) {
                     Ok(_) => (),
                     Err(err) => errors.extend(err),
                 },
-                Error => unreachable!(),
+                NestedFun(_) | Error => unreachable!(), // lifted before the analysis
             }
         }
 
@@ -334,15 +874,57 @@ impl<'a> FunctionContext<'a> {
         }
     }
 
+    fn check_loop_label(&self, label: &Option<Ident>, env: &Env<'a>) -> FrontendResult<()> {
+        match label {
+            Some(l) if env.find_loop(Some(&l.inner)).is_some() => Err(vec![FrontendError {
+                err: format!(
+                    "Error: label {} is already used by an enclosing loop",
+                    l.inner
+                ),
+                span: l.span,
+                kind: ErrorKind::Error,
+            }]),
+            _ => Ok(()),
+        }
+    }
+
     // requirement: check_expr called on expr beforehand
-    fn check_if_lvalue(&self, expr: &'a Expr) -> FrontendResult<()> {
+    fn check_if_lvalue(&self, expr: &Expr) -> FrontendResult<()> {
         use self::InnerExpr::*;
         match &expr.inner {
-            LitVar(_) | ArrayElem { .. } => Ok(()),
+            LitVar(_) => Ok(()),
+            ArrayElem { is_array_a_string, .. } => match is_array_a_string {
+                Some(true) => Err(vec![FrontendError {
+                    err: "Error: strings are immutable, their characters can't be modified".to_string(),
+                    span: expr.span,
+                    kind: ErrorKind::Error,
+                }]),
+                Some(false) => Ok(()),
+                None => unreachable!(), // this function requires analysis to be done beforehand
+            },
+            ObjField { obj, .. } if is_lambda_env(obj) => Err(vec![FrontendError {
+                err: "Error: variables captured by a lambda can't be modified in its body".to_string(),
+                span: expr.span,
+                kind: ErrorKind::Error,
+            }]),
+            ObjField {
+                obj,
+                is_obj_a_struct: Some(true),
+                ..
+            } => match &obj.inner {
+                // modifies the field in place
+                LitVar(_) | ArrayElem { .. } | ObjField { .. } => self.check_if_lvalue(obj),
+                _ => Err(vec![FrontendError {
+                    err: "Error: fields of a temporary struct value can't be modified".to_string(),
+                    span: expr.span,
+                    kind: ErrorKind::Error,
+                }]),
+            },
             ObjField { is_obj_an_array, .. } => match is_obj_an_array {
                 Some(true) => Err(vec![FrontendError {
                     err: "Error: only class objects have mutable fields".to_string(),
-                    span: expr.span
+                    span: expr.span,
+                    kind: ErrorKind::Error,
                 }]),
                 Some(false) => Ok(()), // it's a class
                 None => unreachable!(), // this function requires analysis to be done beforehand
@@ -350,20 +932,35 @@ impl<'a> FunctionContext<'a> {
             _ => Err(vec![FrontendError {
                 err: "Error: required an l-value (options: variable <var>, array elem <expr>.[index], or object field <obj>.<field>)".to_string(),
                 span: expr.span,
+                kind: ErrorKind::Error,
             }]),
         }
     }
 
     fn check_expression_check_type(
         &self,
-        expr: &'a mut Expr,
-        expected_expr_type: &'a InnerType,
+        expr: &mut Expr,
+        expected_expr_type: &InnerType,
         cur_env: &Env<'a>,
     ) -> FrontendResult<()> {
         let expr_type = self.check_expression_get_type(expr, cur_env)?;
+        self.cast_expression_to_type(expr, &expr_type, expected_expr_type)
+    }
+
+    // requirement: expr_type is the type returned by check_expression_get_type
+    fn cast_expression_to_type(
+        &self,
+        expr: &mut Expr,
+        expr_type: &InnerType,
+        expected_expr_type: &InnerType,
+    ) -> FrontendResult<()> {
         self.global_ctx
-            .check_types_compatibility(expected_expr_type, &expr_type, expr.span)?;
-        if *expected_expr_type != expr_type {
+            .check_types_compatibility(expected_expr_type, expr_type, expr.span)
+            .map_err(|mut errors| {
+                errors[0].err += &conversion_note(&expr.inner, expected_expr_type, expr_type);
+                errors
+            })?;
+        if expected_expr_type != expr_type {
             expr.inner = InnerExpr::CastType(
                 Box::new(ItemWithSpan {
                     inner: expr.inner.clone(), // clone to satisfy borrow checker, usually should be small expr, anyway
@@ -375,9 +972,272 @@ impl<'a> FunctionContext<'a> {
         Ok(())
     }
 
+    // type arguments of a generic function are inferred from types of the arguments
+    fn check_generic_fun_call(
+        &self,
+        fun_desc: &FunDesc,
+        args: &mut [Box<Expr>],
+        cur_env: &Env<'a>,
+        span: Span,
+    ) -> FrontendResult<(InnerType, Vec<InnerType>)> {
+        reorder_named_args(fun_desc, args, span)?;
+        if fun_desc.args_types.len() != args.len() {
+            return Err(vec![FrontendError {
+                err: format!(
+                    "Error: expected {} argument(s), got {}.",
+                    fun_desc.args_types.len(),
+                    args.len()
+                ),
+                span,
+                kind: ErrorKind::Error,
+            }]);
+        }
+
+        let mut errors = vec![];
+        let mut args_types = vec![];
+        for a in args.iter_mut() {
+            match self.check_expression_get_type(a, cur_env) {
+                Ok(t) => args_types.push(t),
+                Err(err) => errors.extend(err),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let mut bindings = HashMap::new();
+        for (t, arg_type) in fun_desc.args_types.iter().zip(&args_types) {
+            infer_type_args(&t.inner, arg_type, &mut bindings);
+        }
+        let mut type_args = vec![];
+        for p in &fun_desc.type_params {
+            match bindings.remove(p) {
+                Some(t) => type_args.push(t),
+                None => errors.push(FrontendError {
+                    err: format!(
+                        "Error: cannot infer type argument {} of generic function {}",
+                        p, fun_desc.name
+                    ),
+                    span,
+                    kind: ErrorKind::Error,
+                }),
+            }
+        }
+        if !errors.is_empty() {
+            return Err(errors);
+        }
+
+        let inst_desc = fun_desc.substitute_type_params(&fun_desc.type_params, &type_args);
+        for ((t, arg_type), a) in inst_desc.args_types.iter().zip(&args_types).zip(args) {
+            self.cast_expression_to_type(a, arg_type, &t.inner)
+                .accumulate_errors_in(&mut errors);
+        }
+        if errors.is_empty() {
+            Ok((inst_desc.ret_type.inner, type_args))
+        } else {
+            Err(errors)
+        }
+    }
+
+    // print is dispatched on the static type of its argument
+    fn lower_print_call(
+        &self,
+        arg: &Expr,
+        arg_type: &InnerType,
+        span: Span,
+    ) -> FrontendResult<InnerExpr> {
+        use self::InnerType::*;
+        let fun_call = |name: &str, arg: Expr| InnerExpr::FunCall {
+            function_name: ItemWithSpan {
+                inner: name.to_string(),
+                span,
+            },
+            args: vec![Box::new(arg)],
+            type_args: vec![],
+        };
+        match arg_type {
+            Int => Ok(fun_call("printInt", arg.clone())),
+            Bool => Ok(fun_call("printBoolean", arg.clone())),
+            String => Ok(fun_call("printString", arg.clone())),
+            Class(_) | GenericClass(..) => {
+                let (desc, _) = self
+                    .global_ctx
+                    .get_class_of_type(arg_type)
+                    .expect("check_expression_get_type returns correct types");
+                match desc.get_item(self.global_ctx, TO_STRING_METHOD) {
+                    Some(TypeWrapper::Fun(fun_desc))
+                        if fun_desc.ret_type.inner == String && fun_desc.args_types.is_empty() => {}
+                    _ => {
+                        return Err(vec![FrontendError {
+                            err: format!(
+                                "Error: print needs method string toString() of class {}",
+                                arg_type
+                            ),
+                            span,
+                            kind: ErrorKind::Error,
+                        }])
+                    }
+                }
+                let to_string_call = ItemWithSpan {
+                    inner: InnerExpr::ObjMethodCall {
+                        obj: Box::new(arg.clone()),
+                        method_name: ItemWithSpan {
+                            inner: TO_STRING_METHOD.to_string(),
+                            span,
+                        },
+                        args: vec![],
+                    },
+                    span: arg.span,
+                };
+                Ok(fun_call("printString", to_string_call))
+            }
+            _ => Err(vec![FrontendError {
+                err: format!(
+                    "Error: print can't print values of type {}, only int, boolean, string and objects",
+                    arg_type
+                ),
+                span,
+                kind: ErrorKind::Error,
+            }]),
+        }
+    }
+
+    // == and != of objects call equals if the class of the left operand defines it,
+    // otherwise (and always for === and !==) they compare references
+    fn check_references_comparison(
+        &self,
+        lhs: &mut Expr,
+        lhs_type: InnerType,
+        op: &mut BinaryOp,
+        rhs: &mut Expr,
+        rhs_type: InnerType,
+        span: Span,
+    ) -> FrontendResult<Option<InnerExpr>> {
+        use self::InnerType::*;
+        let is_negated = matches!(op, BinaryOp::NE | BinaryOp::RefNE);
+        if matches!(op, BinaryOp::EQ | BinaryOp::NE) {
+            let (desc, type_args) = self
+                .global_ctx
+                .get_class_of_type(&lhs_type)
+                .expect("check_expression_get_type returns correct types");
+            // the default equals of Object compares references anyway
+            let equals = match desc.get_item(self.global_ctx, EQUALS_METHOD) {
+                Some(TypeWrapper::Fun(fun_desc))
+                    if !self.global_ctx.is_default_object_method(fun_desc) =>
+                {
+                    Some(fun_desc)
+                }
+                _ => None,
+            };
+            if let Some(fun_desc) = equals {
+                let fun_desc = fun_desc.substitute_type_params(desc.get_type_params(), type_args);
+                if fun_desc.ret_type.inner == Bool && fun_desc.args_types.len() == 1 {
+                    let arg_type = &fun_desc.args_types[0].inner;
+                    if self
+                        .cast_expression_to_type(rhs, &rhs_type, arg_type)
+                        .is_err()
+                    {
+                        return Err(vec![FrontendError {
+                            err: format!(
+                                "Error: {}.equals doesn't accept {}, use === or !== to compare references",
+                                lhs_type, rhs_type
+                            ),
+                            span,
+                            kind: ErrorKind::Error,
+                        }]);
+                    }
+                    let call = ItemWithSpan {
+                        inner: InnerExpr::ObjMethodCall {
+                            obj: Box::new(lhs.clone()),
+                            method_name: ItemWithSpan {
+                                inner: EQUALS_METHOD.to_string(),
+                                span,
+                            },
+                            args: vec![Box::new(rhs.clone())],
+                        },
+                        span,
+                    };
+                    return Ok(Some(if is_negated {
+                        InnerExpr::UnaryOp(
+                            ItemWithSpan {
+                                inner: InnerUnaryOp::BoolNeg,
+                                span,
+                            },
+                            Box::new(call),
+                        )
+                    } else {
+                        call.inner
+                    }));
+                }
+            }
+        }
+
+        // operands of codegen comparison must have the same type
+        let is_reference = |t: &InnerType| {
+            matches!(
+                t,
+                Class(_) | GenericClass(..) | Array(_) | Function(..) | Null
+            )
+        };
+        let compatible = |l: &InnerType, r: &InnerType| {
+            self.global_ctx
+                .check_types_compatibility(l, r, span)
+                .is_ok()
+        };
+        if is_reference(&lhs_type) && is_reference(&rhs_type) {
+            let casted = match (&lhs_type, &rhs_type) {
+                (Null, _) | (_, Null) => Ok(()),
+                _ if compatible(&lhs_type, &rhs_type) => {
+                    self.cast_expression_to_type(rhs, &rhs_type, &lhs_type)
+                }
+                _ if compatible(&rhs_type, &lhs_type) => {
+                    self.cast_expression_to_type(lhs, &lhs_type, &rhs_type)
+                }
+                _ => Err(vec![]),
+            };
+            if casted.is_ok() {
+                *op = if is_negated {
+                    BinaryOp::NE
+                } else {
+                    BinaryOp::EQ
+                };
+                return Ok(None);
+            }
+        }
+        let op_str = match op {
+            BinaryOp::EQ => "==",
+            BinaryOp::NE => "!=",
+            BinaryOp::RefEQ => "===",
+            _ => "!==",
+        };
+        Err(vec![FrontendError {
+            err: format!(
+                "Error: binary operator '{}' can be applied only to references of related types, got {} and {}",
+                op_str, lhs_type, rhs_type
+            ),
+            span,
+            kind: ErrorKind::Error,
+        }])
+    }
+
     fn check_expression_get_type(
         &self,
-        expr: &'a mut Expr,
+        expr: &mut Expr,
+        cur_env: &Env<'a>,
+    ) -> FrontendResult<InnerType> {
+        let span = expr.span;
+        let t = self.infer_expression_type(expr, cur_env)?;
+        // an expression checked again (e.g. a rewritten one) keeps the last type,
+        // the outer expressions with the same span are finished later
+        if let Some(expr_types) = self.expr_types {
+            expr_types.borrow_mut().insert(span, t.clone());
+        }
+        Ok(t)
+    }
+
+    fn infer_expression_type(
+        &self,
+        expr: &mut Expr,
         cur_env: &Env<'a>,
     ) -> FrontendResult<InnerType> {
         let expr_span = expr.span; // making borrow checker happy
@@ -385,10 +1245,12 @@ impl<'a> FunctionContext<'a> {
             Err(vec![FrontendError {
                 err,
                 span: expr_span,
+                kind: ErrorKind::Error,
             }])
         };
 
         let validate_fun_call = |fun_desc: &FunDesc, args: &mut Vec<Box<Expr>>| {
+            reorder_named_args(fun_desc, args, expr_span)?;
             let mut errors = vec![];
             let expected_args_no = fun_desc.args_types.len();
             let got_args_no = args.len();
@@ -418,22 +1280,31 @@ impl<'a> FunctionContext<'a> {
         use self::InnerUnaryOp::*;
         let result = match &mut expr.inner {
             LitVar(var) => match cur_env.get_variable(&var, expr.span) {
-                Ok((var_type, true)) => {
-                    override_expr = Some(InnerExpr::ObjField {
-                        obj: Box::new(ItemWithSpan {
-                            span: expr.span,
-                            inner: InnerExpr::LitVar(THIS_VAR.to_string()),
-                        }),
-                        is_obj_an_array: Some(false),
-                        field: ItemWithSpan {
-                            span: expr.span,
-                            inner: var.to_string(),
-                        },
-                    });
+                Ok((var_type, access)) => {
+                    let id = ItemWithSpan {
+                        inner: var.to_string(),
+                        span: expr_span,
+                    };
+                    self.record_variable_use(&id, access, cur_env);
+                    match access {
+                        VarAccess::Local => (),
+                        _ => override_expr = Some(access_expr(var, access, expr_span)),
+                    }
                     Ok(var_type)
                 }
-                Ok((var_type, false)) => Ok(var_type),
-                Err(err) => Err(err),
+                Err(err) => match cur_env.get_function(var, expr.span) {
+                    Ok((fun_desc, _)) if fun_desc.type_params.is_empty() => {
+                        let mut lambda = function_value_lambda(fun_desc, expr_span);
+                        let result = self.check_expression_get_type(&mut lambda, cur_env);
+                        override_expr = Some(lambda.inner);
+                        result
+                    }
+                    Ok(_) => front_err(format!(
+                        "Error: generic function {} can't be used as a value",
+                        var
+                    )),
+                    Err(_) => Err(err),
+                },
             },
             LitInt(_) => Ok(Int),
             LitBool(_) => Ok(Bool),
@@ -443,61 +1314,124 @@ impl<'a> FunctionContext<'a> {
             FunCall {
                 function_name,
                 ref mut args,
-            } => match cur_env.get_function(function_name.inner.as_ref(), function_name.span) {
-                Ok((fun_desc, is_class_member)) => {
+                ref mut type_args,
+            } => match cur_env.get_variable(&function_name.inner, function_name.span) {
+                // call of a function value
+                Ok((Function(ret_type, args_types), access)) => {
+                    self.record_variable_use(function_name, access, cur_env);
+                    let fun_desc = FunDesc::of_function_type(&ret_type, &args_types, expr_span);
                     let result = validate_fun_call(&fun_desc, args);
-                    if is_class_member {
-                        override_expr = Some(InnerExpr::ObjMethodCall {
-                            obj: Box::new(ItemWithSpan {
-                                span: function_name.span,
-                                inner: InnerExpr::LitVar(THIS_VAR.to_string()),
-                            }),
-                            method_name: function_name.clone(),
-                            args: args.to_vec(), // copy to satisfy borrow checker, usually should be small objects
-                        });
-                    }
+                    override_expr = Some(InnerExpr::ClosureCall {
+                        closure: Box::new(ItemWithSpan {
+                            inner: access_expr(&function_name.inner, access, function_name.span),
+                            span: function_name.span,
+                        }),
+                        args: args.to_vec(), // copy to satisfy borrow checker, usually should be small objects
+                    });
                     result
                 }
-                Err(err) => Err(err),
+                Ok((_, VarAccess::Member)) | Ok((_, VarAccess::CapturedMember)) => {
+                    front_err("Error: expected function, found a class field".to_string())
+                }
+                Ok(_) => front_err("Error: expected function, got a variable".to_string()),
+                Err(_) => match cur_env.get_function(&function_name.inner, function_name.span) {
+                    Ok((fun_desc, access)) if !fun_desc.type_params.is_empty() => {
+                        let result =
+                            self.check_generic_fun_call(fun_desc, args, cur_env, expr_span);
+                        // after the arguments, which can have its span (lifted nested functions)
+                        self.record_function_use(function_name, access);
+                        match result {
+                            Ok((ret_type, inferred_type_args))
+                                if function_name.inner == PRINT_FUN =>
+                            {
+                                self.lower_print_call(&args[0], &inferred_type_args[0], expr_span)
+                                    .map(|new_expr| {
+                                        override_expr = Some(new_expr);
+                                        ret_type
+                                    })
+                            }
+                            Ok((ret_type, inferred_type_args)) => {
+                                *type_args = inferred_type_args;
+                                Ok(ret_type)
+                            }
+                            Err(err) => Err(err),
+                        }
+                    }
+                    Ok((fun_desc, access)) => {
+                        let result = validate_fun_call(&fun_desc, args);
+                        self.record_function_use(function_name, access);
+                        let self_access = match access {
+                            VarAccess::Local => None,
+                            VarAccess::Member => Some(VarAccess::Local),
+                            _ => Some(VarAccess::Captured),
+                        };
+                        if let Some(self_access) = self_access {
+                            override_expr = Some(InnerExpr::ObjMethodCall {
+                                obj: Box::new(ItemWithSpan {
+                                    span: function_name.span,
+                                    inner: access_expr(THIS_VAR, self_access, function_name.span),
+                                }),
+                                method_name: function_name.clone(),
+                                args: args.to_vec(), // copy to satisfy borrow checker, usually should be small objects
+                            });
+                        }
+                        result
+                    }
+                    Err(err) => Err(err),
+                },
             },
             BinaryOp(ref mut lhs, op, ref mut rhs) => {
-                let fail_with = |op_str: &str, args: &str| {
-                    front_err(format!(
-                        "Error: binary operator '{}' can be applied only to {}",
-                        op_str, args
-                    ))
-                };
                 let lhs_res = self.check_expression_get_type(lhs, &cur_env);
                 let rhs_res = self.check_expression_get_type(rhs, &cur_env);
                 match (lhs_res, rhs_res) {
-                    (Ok(lhs_t), Ok(rhs_t)) => match (lhs_t, op, rhs_t) {
-                        (Bool, And, Bool) | (Bool, Or, Bool) => Ok(Bool),
-                        (_, And, _) => fail_with("&&", "boolean expressions"),
-                        (_, Or, _) => fail_with("||", "boolean expressions"),
-                        (String, Add, String) => Ok(String),
-                        (Int, Add, Int) | (Int, Sub, Int)
-                        | (Int, Mul, Int) | (Int, Div, Int) | (Int, Mod, Int) => Ok(Int),
-                        (_, Add, _) => fail_with("+", "two integer expressions (sum) or two string expressions (concatenation)"),
-                        (_, Sub, _) => fail_with("-", "integer expressions"),
-                        (_, Mul, _) => fail_with("*", "integer expressions"),
-                        (_, Div, _) => fail_with("/", "integer expressions"),
-                        (_, Mod, _) => fail_with("%", "integer expressions"),
-                        (Int, LT, Int) | (Int, LE, Int)
-                        | (Int, GT, Int) | (Int, GE, Int)
-                        | (Int, EQ, Int) | (Int, NE, Int) => Ok(Bool),
-                        (_, LT, _) => fail_with("<", "integer expressions"),
-                        (_, LE, _) => fail_with("<=", "integer expressions"),
-                        (_, GT, _) => fail_with(">", "integer expressions"),
-                        (_, GE, _) => fail_with(">=", "integer expressions"),
-                        (Bool, EQ, Bool) | (String, EQ, String) => Ok(Bool),
-                        (Class(_), EQ, Null) | (Null, EQ, Class(_))
-                        | (Array(_), EQ, Null) | (Null, EQ, Array(_)) => Ok(Bool),
-                        (_, EQ, _) => fail_with("==", "two operands of same type: integer, boolean and string, or used to check if array or class reference is null"),
-                        (Bool, NE, Bool) | (String, NE, String) => Ok(Bool),
-                        (Class(_), NE, Null) | (Null, NE, Class(_))
-                        | (Array(_), NE, Null) | (Null, NE, Array(_)) => Ok(Bool),
-                        (_, NE, _) => fail_with("!=", "two operands of same type: integer, boolean and string, or used to check if array or class reference is null"),
-                    },
+                    (Ok(lhs_t), Ok(rhs_t)) if is_references_comparison(op, &lhs_t, &rhs_t) => self
+                        .check_references_comparison(lhs, lhs_t, op, rhs, rhs_t, expr_span)
+                        .map(|new_expr| {
+                            override_expr = new_expr;
+                            Bool
+                        }),
+                    (Ok(lhs_t), Ok(rhs_t)) => {
+                        let note = operands_conversion_note(op, (lhs, &lhs_t), (rhs, &rhs_t));
+                        let fail_with = |op_str: &str, args: &str| {
+                            front_err(format!(
+                                "Error: binary operator '{}' can be applied only to {}{}",
+                                op_str, args, note
+                            ))
+                        };
+                        match (lhs_t, op, rhs_t) {
+                            (Bool, And, Bool) | (Bool, Or, Bool) => Ok(Bool),
+                            (_, And, _) => fail_with("&&", "boolean expressions"),
+                            (_, Or, _) => fail_with("||", "boolean expressions"),
+                            (String, Add, String) => Ok(String),
+                            (Int, Add, Int) | (Int, Sub, Int)
+                            | (Int, Mul, Int) | (Int, Div, Int) | (Int, Mod, Int) => Ok(Int),
+                            (_, Add, _) => fail_with("+", "two integer expressions (sum) or two string expressions (concatenation)"),
+                            (_, Sub, _) => fail_with("-", "integer expressions"),
+                            (_, Mul, _) => fail_with("*", "integer expressions"),
+                            (_, Div, _) => fail_with("/", "integer expressions"),
+                            (_, Mod, _) => fail_with("%", "integer expressions"),
+                            (Int, LT, Int) | (Int, LE, Int)
+                            | (Int, GT, Int) | (Int, GE, Int)
+                            | (Int, EQ, Int) | (Int, NE, Int) => Ok(Bool),
+                            (_, LT, _) => fail_with("<", "integer expressions"),
+                            (_, LE, _) => fail_with("<=", "integer expressions"),
+                            (_, GT, _) => fail_with(">", "integer expressions"),
+                            (_, GE, _) => fail_with(">=", "integer expressions"),
+                            (Bool, EQ, Bool) | (String, EQ, String) => Ok(Bool),
+                            (Class(_), EQ, Null) | (Null, EQ, Class(_))
+                            | (GenericClass(..), EQ, Null) | (Null, EQ, GenericClass(..))
+                            | (Function(..), EQ, Null) | (Null, EQ, Function(..))
+                            | (Array(_), EQ, Null) | (Null, EQ, Array(_)) => Ok(Bool),
+                            (_, EQ, _) => fail_with("==", "two operands of same type: integer, boolean and string, or used to check if array or class reference is null"),
+                            (Bool, NE, Bool) | (String, NE, String) => Ok(Bool),
+                            (Class(_), NE, Null) | (Null, NE, Class(_))
+                            | (GenericClass(..), NE, Null) | (Null, NE, GenericClass(..))
+                            | (Function(..), NE, Null) | (Null, NE, Function(..))
+                            | (Array(_), NE, Null) | (Null, NE, Array(_)) => Ok(Bool),
+                            (_, NE, _) => fail_with("!=", "two operands of same type: integer, boolean and string, or used to check if array or class reference is null"),
+                            (_, RefEQ, _) | (_, RefNE, _) => unreachable!(),
+                        }
+                    }
                     (Ok(_), err @ Err(_)) => err,
                     (err @ Err(_), Ok(_)) => err,
                     (Err(mut err1), Err(err2)) => {
@@ -515,6 +1449,11 @@ impl<'a> FunctionContext<'a> {
                         "Error: unary operator '-' can be applied only to integer expressions"
                             .to_string(),
                     ),
+                    (BoolNeg, Int) => front_err(format!(
+                        "Error: unary operator '!' can be applied only to boolean expressions \
+                         (note: ints aren't converted to booleans, write `{} == 0`)",
+                        e.inner
+                    )),
                     (BoolNeg, _) => front_err(
                         "Error: unary operator '!' can be applied only to boolean expressions"
                             .to_string(),
@@ -540,16 +1479,25 @@ impl<'a> FunctionContext<'a> {
             ArrayElem {
                 ref mut array,
                 ref mut index,
+                ref mut is_array_a_string,
             } => {
                 let mut errors = vec![];
                 self.check_expression_check_type(index, &Int, &cur_env)
                     .accumulate_errors_in(&mut errors);
                 let res = match self.check_expression_get_type(array, &cur_env) {
-                    Ok(Array(t)) => Some(t),
+                    Ok(Array(t)) => {
+                        *is_array_a_string = Some(false);
+                        Some(t)
+                    }
+                    Ok(String) => {
+                        *is_array_a_string = Some(true);
+                        Some(Box::new(Int))
+                    }
                     Ok(_) => {
                         errors.push(FrontendError {
-                            err: "Error: only arrays can be indexed".to_string(),
+                            err: "Error: only arrays and strings can be indexed".to_string(),
                             span: expr.span,
+                            kind: ErrorKind::Error,
                         });
                         None
                     }
@@ -566,43 +1514,58 @@ impl<'a> FunctionContext<'a> {
             }
             NewObject(obj_type) => {
                 self.global_ctx.check_local_var_type(&obj_type)?;
-                if let Class(_) = obj_type.inner {
-                    Ok(obj_type.inner.clone())
-                } else {
-                    front_err("Error: you can use new only with class and array types".to_string())
+                match obj_type.inner {
+                    Class(_) | GenericClass(..) => Ok(obj_type.inner.clone()),
+                    Struct(_) => front_err(
+                        "Error: structs are values, declare a variable instead of using new"
+                            .to_string(),
+                    ),
+                    _ => front_err(
+                        "Error: you can use new only with class and array types".to_string(),
+                    ),
                 }
             }
             ObjField {
                 ref mut obj,
                 ref mut is_obj_an_array,
+                ref mut is_obj_a_struct,
                 field,
             } => match self.check_expression_get_type(obj, &cur_env) {
-                Ok(Class(cl_name)) => {
+                Ok(obj_type @ Class(_))
+                | Ok(obj_type @ Struct(_))
+                | Ok(obj_type @ GenericClass(..)) => {
                     *is_obj_an_array = Some(false);
-                    let desc = self
+                    *is_obj_a_struct = Some(matches!(obj_type, Struct(_)));
+                    let (desc, type_args) = self
                         .global_ctx
-                        .get_class_description(&cl_name)
+                        .get_class_of_type(&obj_type)
                         .expect("check_expression_get_type returns correct types");
                     match desc.get_item(self.global_ctx, &field.inner) {
-                        Some(TypeWrapper::Var(t)) => Ok(t.inner.clone()),
+                        Some(TypeWrapper::Var(t)) => {
+                            self.record_member_use(desc, field, SymbolKind::Field);
+                            Ok(desc.substitute_type_params(&t.inner, type_args))
+                        }
                         Some(TypeWrapper::Fun(_)) => {
                             front_err(format!("Error: {} is a method, not a field", field.inner))
                         }
                         None => front_err(format!(
                             "Error: {} is not defined for class {}",
-                            field.inner, cl_name
+                            field.inner, obj_type
                         )),
                     }
                 }
                 Ok(Array(_)) => {
                     *is_obj_an_array = Some(true);
+                    *is_obj_a_struct = Some(false);
                     if field.inner == "length" {
                         Ok(Int)
                     } else {
                         front_err("Error: array's only field is length".to_string())
                     }
                 }
-                Ok(_) => front_err("Error: only classes and arrays have fields".to_string()),
+                Ok(_) => {
+                    front_err("Error: only classes, structs and arrays have fields".to_string())
+                }
                 Err(err) => Err(err),
             },
             ObjMethodCall {
@@ -610,26 +1573,150 @@ impl<'a> FunctionContext<'a> {
                 method_name,
                 ref mut args,
             } => match self.check_expression_get_type(obj, &cur_env) {
-                Ok(Class(cl_name)) => {
-                    let desc = self
+                Ok(obj_type @ Class(_))
+                | Ok(obj_type @ Struct(_))
+                | Ok(obj_type @ GenericClass(..)) => {
+                    let (desc, type_args) = self
                         .global_ctx
-                        .get_class_description(&cl_name)
+                        .get_class_of_type(&obj_type)
                         .expect("check_expression_get_type returns correct types");
                     match desc.get_item(self.global_ctx, &method_name.inner) {
-                        Some(TypeWrapper::Fun(fun_desc)) => validate_fun_call(&fun_desc, args),
-                        Some(TypeWrapper::Var(_)) => front_err(format!(
-                            "Error: {} is a field, not a method",
-                            method_name.inner
-                        )),
+                        Some(TypeWrapper::Fun(fun_desc)) => {
+                            self.record_member_use(desc, method_name, SymbolKind::Method);
+                            let params = desc.get_type_params();
+                            validate_fun_call(
+                                &fun_desc.substitute_type_params(params, type_args),
+                                args,
+                            )
+                        }
+                        Some(TypeWrapper::Var(t)) => {
+                            match desc.substitute_type_params(&t.inner, type_args) {
+                                // call of a function stored in a field
+                                Function(ret_type, args_types) => {
+                                    self.record_member_use(desc, method_name, SymbolKind::Field);
+                                    let fun_desc = FunDesc::of_function_type(
+                                        &ret_type,
+                                        &args_types,
+                                        expr_span,
+                                    );
+                                    let result = validate_fun_call(&fun_desc, args);
+                                    override_expr = Some(InnerExpr::ClosureCall {
+                                        closure: Box::new(ItemWithSpan {
+                                            inner: InnerExpr::ObjField {
+                                                obj: obj.clone(),
+                                                is_obj_an_array: Some(false),
+                                                is_obj_a_struct: Some(matches!(
+                                                    obj_type,
+                                                    Struct(_)
+                                                )),
+                                                field: method_name.clone(),
+                                            },
+                                            span: expr_span,
+                                        }),
+                                        args: args.to_vec(),
+                                    });
+                                    result
+                                }
+                                _ => front_err(format!(
+                                    "Error: {} is a field, not a method",
+                                    method_name.inner
+                                )),
+                            }
+                        }
                         None => front_err(format!(
                             "Error: {} is not defined for class {}",
-                            method_name.inner, cl_name
+                            method_name.inner, obj_type
                         )),
                     }
                 }
-                Ok(_) => front_err("Error: only classes have methods".to_string()),
+                // builtin methods of arrays, lowered to runtime calls by the codegen
+                Ok(Array(elem_type)) => match method_name.inner.as_str() {
+                    "copy" => validate_fun_call(
+                        &FunDesc::of_function_type(&Array(elem_type), &[], expr_span),
+                        args,
+                    ),
+                    "fill" => validate_fun_call(
+                        &FunDesc::of_function_type(&Void, &[*elem_type], expr_span),
+                        args,
+                    ),
+                    _ => front_err("Error: array's only methods are copy and fill".to_string()),
+                },
+                Ok(_) => {
+                    front_err("Error: only classes, structs and arrays have methods".to_string())
+                }
+                Err(err) => Err(err),
+            },
+            Lambda {
+                ret_type,
+                args,
+                ref mut body,
+                captures,
+            } => {
+                let mut errors = vec![];
+                self.global_ctx
+                    .check_ret_type(ret_type)
+                    .accumulate_errors_in(&mut errors);
+                let lambda_env = Env::new_lambda(cur_env);
+                self.analyze_body(ret_type, args, body, &lambda_env)
+                    .accumulate_errors_in(&mut errors);
+                if !errors.is_empty() {
+                    return Err(errors);
+                }
+
+                // values are copied when the lambda is created
+                for (name, t) in lambda_env.take_captures() {
+                    let (_, access) = cur_env.get_variable(&name, expr_span)?;
+                    captures.push((
+                        ItemWithSpan {
+                            inner: t,
+                            span: expr_span,
+                        },
+                        ItemWithSpan {
+                            inner: name.to_string(),
+                            span: expr_span,
+                        },
+                        Box::new(ItemWithSpan {
+                            inner: access_expr(&name, access, expr_span),
+                            span: expr_span,
+                        }),
+                    ));
+                }
+                Ok(Function(
+                    Box::new(ret_type.inner.clone()),
+                    args.iter().map(|(t, _)| t.inner.clone()).collect(),
+                ))
+            }
+            ClosureCall {
+                ref mut closure,
+                ref mut args,
+            } => match self.check_expression_get_type(closure, cur_env) {
+                Ok(Function(ret_type, args_types)) => validate_fun_call(
+                    &FunDesc::of_function_type(&ret_type, &args_types, expr_span),
+                    args,
+                ),
+                Ok(_) => front_err("Error: only functions can be called".to_string()),
                 Err(err) => Err(err),
             },
+            NamedArg { .. } => unreachable!(), // reordered before checking the arguments
+            Range(..) => unreachable!(),       // only iterated by foreach loops, checked there
+            // the value is the assigned one, converted to the type of lhs
+            Assign(ref mut lhs, ref mut rhs) => {
+                let lhs_type = self.check_expression_get_type(lhs, cur_env)?;
+                let mut errors = vec![];
+                self.check_if_lvalue(lhs).accumulate_errors_in(&mut errors);
+                self.check_expression_check_type(rhs, &lhs_type, cur_env)
+                    .accumulate_errors_in(&mut errors);
+                if errors.is_empty() {
+                    Ok(lhs_type)
+                } else {
+                    Err(errors)
+                }
+            }
+            IncrDecr { ref mut lvalue, .. } => {
+                self.check_expression_check_type(lvalue, &InnerType::Int, cur_env)?;
+                self.check_if_lvalue(lvalue)?;
+                Ok(InnerType::Int)
+            }
         };
         if let Some(new_expr) = override_expr {
             expr.inner = new_expr;
@@ -637,3 +1724,97 @@ impl<'a> FunctionContext<'a> {
         result
     }
 }
+
+// there are no implicit conversions between ints and booleans, the note
+// suggests an explicit one
+fn conversion_note(expr: &InnerExpr, expected_type: &InnerType, expr_type: &InnerType) -> String {
+    match (expected_type, expr_type) {
+        (InnerType::Bool, InnerType::Int) => format!(
+            " (note: ints aren't converted to booleans, write `{} != 0`)",
+            expr
+        ),
+        (InnerType::Int, InnerType::Bool) => format!(
+            " (note: booleans aren't converted to ints, choose the value with `if ({}) ... else ...`)",
+            expr
+        ),
+        _ => String::new(),
+    }
+}
+
+// e.g. for `n && b` or `b + 1`, for the first of the converted operands
+fn operands_conversion_note(
+    op: &BinaryOp,
+    (lhs, lhs_type): (&Expr, &InnerType),
+    (rhs, rhs_type): (&Expr, &InnerType),
+) -> String {
+    let expected_type = match op {
+        BinaryOp::And | BinaryOp::Or => InnerType::Bool,
+        BinaryOp::EQ | BinaryOp::NE | BinaryOp::RefEQ | BinaryOp::RefNE => return String::new(),
+        _ => InnerType::Int,
+    };
+    match conversion_note(&lhs.inner, &expected_type, lhs_type) {
+        note if note.is_empty() => conversion_note(&rhs.inner, &expected_type, rhs_type),
+        note => note,
+    }
+}
+
+// `f` used as a value is `fn R (T1 arg.0, ...) { return f(arg.0, ...); }`
+// comparisons handled by check_references_comparison
+fn is_references_comparison(op: &BinaryOp, lhs_type: &InnerType, rhs_type: &InnerType) -> bool {
+    let is_object = |t: &InnerType| matches!(t, InnerType::Class(_) | InnerType::GenericClass(..));
+    match op {
+        BinaryOp::RefEQ | BinaryOp::RefNE => true,
+        BinaryOp::EQ | BinaryOp::NE => is_object(lhs_type) && is_object(rhs_type),
+        _ => false,
+    }
+}
+
+fn function_value_lambda(fun_desc: &FunDesc, span: Span) -> Expr {
+    let args: Vec<_> = fun_desc
+        .args_types
+        .iter()
+        .enumerate()
+        .map(|(i, t)| {
+            let name = ItemWithSpan {
+                inner: format!("arg.{}", i),
+                span,
+            };
+            (t.clone(), name)
+        })
+        .collect();
+    let call = Box::new(ItemWithSpan {
+        inner: InnerExpr::FunCall {
+            function_name: ItemWithSpan {
+                inner: fun_desc.name.to_string(),
+                span,
+            },
+            args: args
+                .iter()
+                .map(|(_, name)| {
+                    Box::new(ItemWithSpan {
+                        inner: InnerExpr::LitVar(name.inner.to_string()),
+                        span,
+                    })
+                })
+                .collect(),
+            type_args: vec![],
+        },
+        span,
+    });
+    let stmt = match fun_desc.ret_type.inner {
+        InnerType::Void => InnerStmt::Expr(call),
+        _ => InnerStmt::Ret(Some(call)),
+    };
+    ItemWithSpan {
+        inner: InnerExpr::Lambda {
+            ret_type: fun_desc.ret_type.clone(),
+            args,
+            body: Block {
+                stmts: vec![Box::new(ItemWithSpan { inner: stmt, span })],
+                span,
+            },
+            captures: vec![],
+        },
+        span,
+    }
+}
@@ -0,0 +1,320 @@
+use super::visitor::*;
+use frontend_error::{ok_if_no_error, ErrorKind, FrontendError, FrontendResult};
+use model::ast::*;
+use std::collections::{HashMap, HashSet};
+
+// Generic classes and functions are type checked once, with type parameters
+// treated as opaque types (InnerType::TypeParam). After the analysis every
+// instantiation used by non-generic code (transitively) gets its own copy
+// of the definition, with type parameters substituted and a name like
+// "Box<int>" or "id<string>", so codegen sees only ordinary classes and functions.
+
+// protects against definitions like `void f<T>(T x) { f(new Box<T>); }`
+const MAX_INSTANTIATIONS: usize = 1000;
+
+// Parser doesn't know type parameters, so they are parsed as class types.
+// Also checks restrictions of generic definitions.
+pub fn resolve_type_params(prog: &mut Program) -> FrontendResult<()> {
+    let mut errors = vec![];
+    for def in &mut prog.defs {
+        match def {
+            TopDef::FunDef(fun) => {
+                check_type_params(&fun.type_params, &mut errors);
+                let mut resolver = TypeParamResolver::new(&fun.type_params);
+                walk_fun(fun, &mut resolver);
+            }
+            TopDef::ClassDef(cl) => {
+                check_type_params(&cl.type_params, &mut errors);
+                if let (false, Some(t)) = (cl.type_params.is_empty(), &cl.parent_type) {
+                    errors.push(FrontendError {
+                        err: "Error: generic classes can't take part in inheritance".to_string(),
+                        span: t.span,
+                        kind: ErrorKind::Error,
+                    });
+                }
+                for it in &cl.items {
+                    if let InnerClassItemDef::Method(fun) = &it.inner {
+                        if !fun.type_params.is_empty() {
+                            errors.push(FrontendError {
+                                err: "Error: methods can't be generic".to_string(),
+                                span: fun.name.span,
+                                kind: ErrorKind::Error,
+                            });
+                        }
+                    }
+                }
+                let mut resolver = TypeParamResolver::new(&cl.type_params);
+                walk_class(cl, &mut resolver);
+            }
+            TopDef::StructDef(_) => (),
+            TopDef::TypeDef(_) | TopDef::Error => unreachable!(),
+        }
+    }
+
+    ok_if_no_error(errors)
+}
+
+fn check_type_params(type_params: &[Ident], errors: &mut Vec<FrontendError>) {
+    let mut names = HashSet::new();
+    for p in type_params {
+        if !names.insert(&p.inner) {
+            errors.push(FrontendError {
+                err: "Error: type parameter redefinition".to_string(),
+                span: p.span,
+                kind: ErrorKind::Error,
+            });
+        }
+    }
+}
+
+pub fn substitute_type_params(t: &InnerType, params: &[String], args: &[InnerType]) -> InnerType {
+    match t {
+        InnerType::TypeParam(name) => match params.iter().position(|p| p == name) {
+            Some(i) => args[i].clone(),
+            None => t.clone(),
+        },
+        InnerType::Array(subtype) => {
+            InnerType::Array(Box::new(substitute_type_params(subtype, params, args)))
+        }
+        InnerType::GenericClass(name, type_args) => InnerType::GenericClass(
+            name.to_string(),
+            type_args
+                .iter()
+                .map(|a| substitute_type_params(a, params, args))
+                .collect(),
+        ),
+        InnerType::Function(ret_type, args_types) => InnerType::Function(
+            Box::new(substitute_type_params(ret_type, params, args)),
+            args_types
+                .iter()
+                .map(|a| substitute_type_params(a, params, args))
+                .collect(),
+        ),
+        _ => t.clone(),
+    }
+}
+
+// binds type parameters occurring in the declared type of an argument
+// (the first binding wins, compatibility is checked afterwards)
+pub fn infer_type_args(
+    declared: &InnerType,
+    actual: &InnerType,
+    bindings: &mut HashMap<String, InnerType>,
+) {
+    use self::InnerType::*;
+    match (declared, actual) {
+        (TypeParam(_), Null) => (), // null doesn't tell anything
+        (TypeParam(name), _) => {
+            bindings
+                .entry(name.to_string())
+                .or_insert_with(|| actual.clone());
+        }
+        (Array(declared), Array(actual)) => infer_type_args(declared, actual, bindings),
+        (GenericClass(name1, args1), GenericClass(name2, args2)) if name1 == name2 => {
+            for (d, a) in args1.iter().zip(args2) {
+                infer_type_args(d, a, bindings);
+            }
+        }
+        (Function(ret1, args1), Function(ret2, args2)) if args1.len() == args2.len() => {
+            infer_type_args(ret1, ret2, bindings);
+            for (d, a) in args1.iter().zip(args2) {
+                infer_type_args(d, a, bindings);
+            }
+        }
+        _ => (),
+    }
+}
+
+// they keep their type arguments after monomorphization
+fn is_generic_builtin(name: &str) -> bool {
+    name == ARRAY_RESIZE_FUN || name == ARRAY_APPEND_FUN
+}
+
+fn mangle_name(name: &str, type_args: &[InnerType]) -> String {
+    InnerType::GenericClass(name.to_string(), type_args.to_vec()).to_string()
+}
+
+// requirement: program is analyzed (generic function calls have type_args inferred)
+pub fn monomorphize(prog: &mut Program) -> FrontendResult<()> {
+    let mut class_templates = HashMap::new();
+    let mut fun_templates = HashMap::new();
+    let mut defs = vec![];
+    for def in prog.defs.drain(..) {
+        match def {
+            TopDef::ClassDef(ref cl) if !cl.type_params.is_empty() => {
+                class_templates.insert(cl.name.inner.to_string(), cl.clone());
+            }
+            TopDef::FunDef(ref fun) if !fun.type_params.is_empty() => {
+                fun_templates.insert(fun.name.inner.to_string(), fun.clone());
+            }
+            _ => defs.push(def),
+        }
+    }
+
+    let mut collector = InstanceCollector { instances: vec![] };
+    for def in &mut defs {
+        walk_top_def(def, &mut collector);
+    }
+
+    let mut done = HashSet::new();
+    while let Some((name, type_args)) = collector.instances.pop() {
+        let new_name = mangle_name(&name, &type_args);
+        if !done.insert(new_name.to_string()) {
+            continue;
+        }
+
+        let mut def = match (class_templates.get(&name), fun_templates.get(&name)) {
+            (Some(cl), _) => TopDef::ClassDef(cl.clone()),
+            (None, Some(fun)) => TopDef::FunDef(fun.clone()),
+            (None, None) => continue, // generic builtin
+        };
+        let template_name = match &mut def {
+            TopDef::ClassDef(cl) => {
+                let mut subst = TypeSubstitution::new(&cl.type_params, type_args);
+                walk_class(cl, &mut subst);
+                cl.type_params.clear();
+                &mut cl.name
+            }
+            TopDef::FunDef(fun) => {
+                let mut subst = TypeSubstitution::new(&fun.type_params, type_args);
+                walk_fun(fun, &mut subst);
+                fun.type_params.clear();
+                &mut fun.name
+            }
+            TopDef::StructDef(_) | TopDef::TypeDef(_) | TopDef::Error => unreachable!(),
+        };
+        if done.len() > MAX_INSTANTIATIONS {
+            return Err(vec![FrontendError {
+                err: format!(
+                    "Error: too many instantiations of generic definition {}, its type arguments probably grow infinitely",
+                    template_name.inner
+                ),
+                span: template_name.span,
+                kind: ErrorKind::Error,
+            }]);
+        }
+        template_name.inner = new_name;
+
+        walk_top_def(&mut def, &mut collector);
+        defs.push(def);
+    }
+
+    for def in &mut defs {
+        walk_top_def(def, &mut InstanceNameRewriter);
+    }
+    prog.defs = defs;
+    Ok(())
+}
+
+struct TypeParamResolver {
+    type_params: Vec<String>,
+}
+
+impl TypeParamResolver {
+    fn new(type_params: &[Ident]) -> Self {
+        TypeParamResolver {
+            type_params: type_params.iter().map(|p| p.inner.to_string()).collect(),
+        }
+    }
+}
+
+impl TypeVisitor for TypeParamResolver {
+    fn visit_type(&mut self, t: &mut InnerType) {
+        match t {
+            InnerType::Class(name) if self.type_params.contains(name) => {
+                *t = InnerType::TypeParam(name.to_string());
+            }
+            InnerType::Array(subtype) => self.visit_type(subtype),
+            InnerType::GenericClass(_, type_args) => {
+                for a in type_args {
+                    self.visit_type(a);
+                }
+            }
+            InnerType::Function(ret_type, args_types) => {
+                self.visit_type(ret_type);
+                for a in args_types {
+                    self.visit_type(a);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+struct TypeSubstitution {
+    params: Vec<String>,
+    args: Vec<InnerType>,
+}
+
+impl TypeSubstitution {
+    fn new(type_params: &[Ident], type_args: Vec<InnerType>) -> Self {
+        TypeSubstitution {
+            params: type_params.iter().map(|p| p.inner.to_string()).collect(),
+            args: type_args,
+        }
+    }
+}
+
+impl TypeVisitor for TypeSubstitution {
+    fn visit_type(&mut self, t: &mut InnerType) {
+        *t = substitute_type_params(t, &self.params, &self.args);
+    }
+}
+
+// (name of generic class or function, type arguments)
+struct InstanceCollector {
+    instances: Vec<(String, Vec<InnerType>)>,
+}
+
+impl TypeVisitor for InstanceCollector {
+    fn visit_type(&mut self, t: &mut InnerType) {
+        match t {
+            InnerType::Array(subtype) => self.visit_type(subtype),
+            InnerType::GenericClass(name, type_args) => {
+                for a in type_args.iter_mut() {
+                    self.visit_type(a);
+                }
+                self.instances.push((name.to_string(), type_args.clone()));
+            }
+            InnerType::Function(ret_type, args_types) => {
+                self.visit_type(ret_type);
+                for a in args_types {
+                    self.visit_type(a);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn visit_fun_call(&mut self, name: &mut Ident, type_args: &mut Vec<InnerType>) {
+        if !type_args.is_empty() {
+            self.instances
+                .push((name.inner.to_string(), type_args.clone()));
+        }
+    }
+}
+
+struct InstanceNameRewriter;
+
+impl TypeVisitor for InstanceNameRewriter {
+    fn visit_type(&mut self, t: &mut InnerType) {
+        match t {
+            InnerType::Array(subtype) => self.visit_type(subtype),
+            InnerType::GenericClass(..) => *t = InnerType::Class(t.to_string()),
+            InnerType::Function(ret_type, args_types) => {
+                self.visit_type(ret_type);
+                for a in args_types {
+                    self.visit_type(a);
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn visit_fun_call(&mut self, name: &mut Ident, type_args: &mut Vec<InnerType>) {
+        if !type_args.is_empty() && !is_generic_builtin(&name.inner) {
+            name.inner = mangle_name(&name.inner, type_args);
+            type_args.clear();
+        }
+    }
+}
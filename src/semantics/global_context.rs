@@ -1,4 +1,5 @@
-use frontend_error::{ok_if_no_error, ErrorAccumulation, FrontendError, FrontendResult};
+use super::generics::substitute_type_params;
+use frontend_error::{ok_if_no_error, ErrorAccumulation, ErrorKind, FrontendError, FrontendResult};
 use model::ast::*;
 use std::collections::HashMap;
 
@@ -9,8 +10,11 @@ pub struct GlobalContext {
 
 pub struct ClassDesc {
     name: String,
+    type_params: Vec<String>,
     parent_type: Option<Type>,
     items: HashMap<String, TypeWrapper>,
+    // implemented in the runtime, can't be extended
+    is_builtin: bool,
 }
 
 pub enum TypeWrapper {
@@ -22,13 +26,17 @@ pub struct FunDesc {
     // todo (optional) use getters instead of pub fields?
     pub ret_type: Type,
     pub name: String,
+    pub type_params: Vec<String>,
     pub args_types: Vec<Type>,
+    pub args_names: Vec<String>, // used by named arguments
+    pub inline_hint: InlineHint,
+    pub no_return: bool, // of the builtins, inferred from the IR for the others
 }
 
 impl GlobalContext {
     fn new_with_builtins() -> Self {
         GlobalContext {
-            classes: HashMap::new(),
+            classes: get_builtin_classes(),
             functions: get_builtin_functions(),
         }
     }
@@ -58,6 +66,44 @@ impl GlobalContext {
         self.functions.get(fun_name)
     }
 
+    // classes and structs, in arbitrary order
+    pub fn get_classes(&self) -> impl Iterator<Item = &ClassDesc> {
+        self.classes.values()
+    }
+
+    // global functions, in arbitrary order
+    pub fn get_functions(&self) -> impl Iterator<Item = &FunDesc> {
+        self.functions.values()
+    }
+
+    pub fn is_builtin_function(&self, fun_name: &str) -> bool {
+        get_builtin_functions().contains_key(fun_name)
+    }
+
+    // default methods of Object can be hidden by methods with other signatures
+    pub fn is_default_object_method(&self, fun_desc: &FunDesc) -> bool {
+        match self.classes[OBJECT_CLASS].items.get(&fun_desc.name) {
+            Some(TypeWrapper::Fun(object_fun)) => std::ptr::eq(object_fun, fun_desc),
+            _ => false,
+        }
+    }
+
+    // class description and type arguments of a class (or generic class instance) type
+    pub fn get_class_of_type<'a>(
+        &'a self,
+        t: &'a InnerType,
+    ) -> Option<(&'a ClassDesc, &'a [InnerType])> {
+        match t {
+            InnerType::Class(name) | InnerType::Struct(name) => {
+                self.classes.get(name).map(|cl| (cl, &[][..]))
+            }
+            InnerType::GenericClass(name, type_args) => {
+                self.classes.get(name).map(|cl| (cl, &type_args[..]))
+            }
+            _ => None,
+        }
+    }
+
     fn scan_global_defenitions(&mut self, prog: &Program) -> FrontendResult<()> {
         let mut errors = vec![];
         for def in &prog.defs {
@@ -68,6 +114,7 @@ impl GlobalContext {
                         errors.push(FrontendError {
                             err: "Error: class with same name already defined".to_string(),
                             span: fun.name.span,
+                            kind: ErrorKind::Error,
                         });
                     } else if self
                         .functions
@@ -77,6 +124,7 @@ impl GlobalContext {
                         errors.push(FrontendError {
                             err: "Error: function redefinition".to_string(),
                             span: fun.name.span,
+                            kind: ErrorKind::Error,
                         });
                     }
                 }
@@ -89,24 +137,48 @@ impl GlobalContext {
                                     err: "Error: function with same name already defined"
                                         .to_string(),
                                     span: cl.name.span,
+                                    kind: ErrorKind::Error,
                                 });
                             } else if self.classes.insert(desc.name.to_string(), desc).is_some() {
                                 errors.push(FrontendError {
                                     err: "Error: class redefinition".to_string(),
                                     span: cl.name.span,
+                                    kind: ErrorKind::Error,
                                 });
                             }
                         }
                         Err(err) => errors.extend(err),
                     }
                 }
-                TopDef::Error => unreachable!(),
+                // structs share the namespace (and fields lookup) with classes
+                TopDef::StructDef(st) => match ClassDesc::from_struct(st) {
+                    Ok(desc) => {
+                        if self.functions.contains_key(&desc.name) {
+                            errors.push(FrontendError {
+                                err: "Error: function with same name already defined".to_string(),
+                                span: st.name.span,
+                                kind: ErrorKind::Error,
+                            });
+                        } else if self.classes.insert(desc.name.to_string(), desc).is_some() {
+                            errors.push(FrontendError {
+                                err: "Error: class or struct with same name already defined"
+                                    .to_string(),
+                                span: st.name.span,
+                                kind: ErrorKind::Error,
+                            });
+                        }
+                    }
+                    Err(err) => errors.extend(err),
+                },
+                TopDef::TypeDef(_) | TopDef::Error => unreachable!(),
             }
         }
 
         ok_if_no_error(errors)
     }
 
+    // the definitions can refer to each other in any order, they're checked
+    // when all of them are known
     fn check_types_in_context_defs(&mut self) -> FrontendResult<()> {
         let mut errors = vec![];
         for f in self.functions.values() {
@@ -115,6 +187,8 @@ impl GlobalContext {
         for c in self.classes.values() {
             c.check_types(&self).accumulate_errors_in(&mut errors);
         }
+        // in the order of the program, not of the hash maps
+        errors.sort_by_key(|e| e.span);
 
         ok_if_no_error(errors)
     }
@@ -129,21 +203,76 @@ impl GlobalContext {
                 };
                 self.check_local_var_type(&tt)
             }
-            Class(name) => {
-                if self.classes.contains_key(name.as_str()) {
-                    Ok(())
-                } else {
-                    Err(vec![FrontendError {
-                        err: "Error: invalid type - class not defined".to_string(),
+            Class(name) => match self.classes.get(name.as_str()) {
+                Some(cl) if !cl.type_params.is_empty() => Err(vec![FrontendError {
+                    err: format!(
+                        "Error: invalid type - generic class {} requires {} type argument(s)",
+                        name,
+                        cl.type_params.len()
+                    ),
+                    span: t.span,
+                    kind: ErrorKind::Error,
+                }]),
+                Some(_) => Ok(()),
+                None => Err(vec![FrontendError {
+                    err: "Error: invalid type - class not defined".to_string(),
+                    span: t.span,
+                    kind: ErrorKind::Error,
+                }]),
+            },
+            Struct(_) => Ok(()), // only defined structs are resolved
+            GenericClass(name, type_args) => match self.classes.get(name.as_str()) {
+                Some(cl) if cl.type_params.len() == type_args.len() => {
+                    let mut errors = vec![];
+                    for a in type_args {
+                        let tt = Type {
+                            inner: a.clone(),
+                            span: t.span,
+                        };
+                        self.check_local_var_type(&tt)
+                            .accumulate_errors_in(&mut errors);
+                    }
+                    ok_if_no_error(errors)
+                }
+                Some(cl) => Err(vec![FrontendError {
+                    err: format!(
+                        "Error: invalid type - class {} requires {} type argument(s), got {}",
+                        name,
+                        cl.type_params.len(),
+                        type_args.len()
+                    ),
+                    span: t.span,
+                    kind: ErrorKind::Error,
+                }]),
+                None => Err(vec![FrontendError {
+                    err: "Error: invalid type - class not defined".to_string(),
+                    span: t.span,
+                    kind: ErrorKind::Error,
+                }]),
+            },
+            Function(ret_type, args_types) => {
+                let mut errors = vec![];
+                let tt = Type {
+                    inner: *ret_type.clone(),
+                    span: t.span,
+                };
+                self.check_ret_type(&tt).accumulate_errors_in(&mut errors);
+                for a in args_types {
+                    let tt = Type {
+                        inner: a.clone(),
                         span: t.span,
-                    }])
+                    };
+                    self.check_local_var_type(&tt)
+                        .accumulate_errors_in(&mut errors);
                 }
+                ok_if_no_error(errors)
             }
             Void => Err(vec![FrontendError {
                 err: "Error: invalid type - cannot use void here".to_string(),
                 span: t.span,
+                kind: ErrorKind::Error,
             }]),
-            Int | Bool | String => Ok(()),
+            Int | Bool | String | TypeParam(_) => Ok(()),
             Null => unreachable!(),
         }
     }
@@ -158,43 +287,68 @@ impl GlobalContext {
 
     pub fn check_superclass_type(&self, t: &Type, my_name: &str) -> FrontendResult<()> {
         if let InnerType::Class(parent_name) = &t.inner {
-            self.check_for_inheritance_cycle(my_name, &parent_name, t.span)
+            match self.classes.get(parent_name.as_str()) {
+                Some(cl) if cl.is_builtin && parent_name != OBJECT_CLASS => {
+                    Err(vec![FrontendError {
+                        err: format!("Error: cannot extend builtin class {}", parent_name),
+                        span: t.span,
+                        kind: ErrorKind::Error,
+                    }])
+                }
+                Some(cl) if !cl.type_params.is_empty() => Err(vec![FrontendError {
+                    err: "Error: generic classes can't take part in inheritance".to_string(),
+                    span: t.span,
+                    kind: ErrorKind::Error,
+                }]),
+                _ => self.check_for_inheritance_cycle(my_name, &parent_name, t.span),
+            }
+        } else if let InnerType::GenericClass(..) = &t.inner {
+            Err(vec![FrontendError {
+                err: "Error: generic classes can't take part in inheritance".to_string(),
+                span: t.span,
+                kind: ErrorKind::Error,
+            }])
         } else {
             Err(vec![FrontendError {
                 err: "Error: super class must be a class".to_string(),
                 span: t.span,
+                kind: ErrorKind::Error,
             }])
         }
     }
 
+    // the chain of superclasses has to end with Object; a cycle doesn't have
+    // to go through the start class (it can extend a class from a cycle)
     fn check_for_inheritance_cycle(
         &self,
         start_name: &str,
-        cur_name: &str,
+        parent_name: &str,
         span: Span,
     ) -> FrontendResult<()> {
-        if let Some(cl) = self.classes.get(cur_name) {
-            if cl.name == start_name {
-                Err(vec![FrontendError {
+        let mut visited = vec![start_name];
+        let mut cur_name = parent_name;
+        while let Some(cl) = self.classes.get(cur_name) {
+            if visited.contains(&cl.name.as_str()) {
+                return Err(vec![FrontendError {
                     err: "Error: detected cycle in inheritance chain".to_string(),
-                    span: span,
-                }])
-            } else if let Some(t) = &cl.parent_type {
-                match &t.inner {
-                    InnerType::Class(parent_name) => {
-                        self.check_for_inheritance_cycle(start_name, &parent_name, span)
-                    }
-                    _ => unreachable!(), // assumption: tree made by our parser
-                }
-            } else {
-                Ok(())
+                    span,
+                    kind: ErrorKind::Error,
+                }]);
             }
-        } else {
-            Err(vec![FrontendError {
-                err: "Error: invalid type - class not defined".to_string(),
-                span: span,
-            }])
+            visited.push(&cl.name);
+            cur_name = match &cl.parent_type {
+                Some(t) => match &t.inner {
+                    InnerType::Class(parent_name) => parent_name,
+                    _ => unreachable!(), // assumption: tree made by our parser
+                },
+                None => return Ok(()),
+            };
         }
+        Err(vec![FrontendError {
+            err: "Error: invalid type - class not defined".to_string(),
+            span,
+            kind: ErrorKind::Error,
+        }])
     }
 
     pub fn check_types_compatibility(
@@ -203,19 +357,30 @@ impl GlobalContext {
         rhs: &InnerType,
         span: Span,
     ) -> FrontendResult<()> {
-        use self::InnerType::{Array, Class, Null};
+        use self::InnerType::{Array, Class, Function, GenericClass, Null};
         match (lhs, rhs) {
-            (Array(_), Null) | (Class(_), Null) => Ok(()),
+            (Array(_), Null)
+            | (Class(_), Null)
+            | (GenericClass(..), Null)
+            | (Function(..), Null) => Ok(()),
             _ => {
                 match self.check_arrays_types_compatibility(lhs, rhs) {
                     (true, _) => Ok(()),
                     (false, Some((superclass, subclass))) => {
                         let err = format!("Error: expected type {}, got type {} (note: {} is not a subclass of {})", lhs, rhs, subclass, superclass);
-                        Err(vec![FrontendError { err, span }])
+                        Err(vec![FrontendError {
+                            err,
+                            span,
+                            kind: ErrorKind::Error,
+                        }])
                     }
                     (false, None) => {
                         let err = format!("Error: expected type {}, got type {}", lhs, rhs);
-                        Err(vec![FrontendError { err, span }])
+                        Err(vec![FrontendError {
+                            err,
+                            span,
+                            kind: ErrorKind::Error,
+                        }])
                     }
                 }
             }
@@ -227,10 +392,13 @@ impl GlobalContext {
         lhs: &'a InnerType,
         rhs: &'a InnerType,
     ) -> (bool, Option<(&'a str, &'a str)>) {
-        use self::InnerType::{Array, Class};
+        use self::InnerType::{Array, Class, GenericClass};
         match (lhs, rhs) {
             (Array(lhs2), Array(rhs2)) => self.check_arrays_types_compatibility(lhs2, rhs2),
             (Array(_), _) | (_, Array(_)) => (false, None),
+            (Class(superclass), GenericClass(subclass, _)) => {
+                (self.check_if_subclass(superclass, subclass), None)
+            }
             (Class(superclass), Class(subclass)) => (
                 self.check_if_subclass(superclass, subclass),
                 Some((superclass, subclass)),
@@ -262,8 +430,21 @@ impl ClassDesc {
         let mut errors = vec![];
         let mut result = ClassDesc {
             name: cldef.name.inner.to_string(),
-            parent_type: cldef.parent_type.clone(),
+            type_params: cldef
+                .type_params
+                .iter()
+                .map(|p| p.inner.to_string())
+                .collect(),
+            // classes without a superclass extend Object (it's done here
+            // and in the codegen, so the classes made by lowering extend it, too)
+            parent_type: cldef.parent_type.clone().or_else(|| {
+                Some(Type {
+                    inner: InnerType::Class(OBJECT_CLASS.to_string()),
+                    span: cldef.name.span,
+                })
+            }),
             items: HashMap::new(),
+            is_builtin: false,
         };
 
         // scope for the closure which borrows errors
@@ -273,6 +454,7 @@ impl ClassDesc {
                     errors.push(FrontendError {
                         err: "Error: class item redefinition".to_string(),
                         span,
+                        kind: ErrorKind::Error,
                     });
                 }
             };
@@ -290,7 +472,7 @@ impl ClassDesc {
                             fun.name.span,
                         )
                     }
-                    InnerClassItemDef::Error => unreachable!(),
+                    InnerClassItemDef::Class(_) | InnerClassItemDef::Error => unreachable!(),
                 }
             }
         }
@@ -302,6 +484,37 @@ impl ClassDesc {
         }
     }
 
+    pub fn from_struct(stdef: &StructDef) -> FrontendResult<Self> {
+        let mut errors = vec![];
+        let mut result = ClassDesc {
+            name: stdef.name.inner.to_string(),
+            type_params: vec![],
+            parent_type: None,
+            items: HashMap::new(),
+            is_builtin: false,
+        };
+        for (t, id) in &stdef.fields {
+            let span = (t.span.0, id.span.1);
+            if result
+                .items
+                .insert(id.inner.to_string(), TypeWrapper::Var(t.clone()))
+                .is_some()
+            {
+                errors.push(FrontendError {
+                    err: "Error: struct field redefinition".to_string(),
+                    span,
+                    kind: ErrorKind::Error,
+                });
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(result)
+        } else {
+            Err(errors)
+        }
+    }
+
     pub fn check_types(&self, ctx: &GlobalContext) -> FrontendResult<()> {
         let mut errors = vec![];
         let parent_desc = match &self.parent_type {
@@ -332,6 +545,7 @@ impl ClassDesc {
                             ),
                             // todo (optional) remember span for the name
                             span: var_type.span,
+                            kind: ErrorKind::Error,
                         })
                     }
                 }
@@ -346,14 +560,18 @@ impl ClassDesc {
                                 ),
                                 // todo (optional) remember span for the name
                                 span: fun_desc.ret_type.span,
+                                kind: ErrorKind::Error,
                             })
                         }
                         Some(TypeWrapper::Fun(parent_fun)) => {
-                            if !fun_desc.does_signature_match(&parent_fun) {
+                            if !fun_desc.does_signature_match(&parent_fun)
+                                && !ctx.is_default_object_method(parent_fun)
+                            {
                                 errors.push(FrontendError {
                                     err: "Error: method signature does not match method defined in superclass".to_string(),
                                     // todo (optional) remember span for the name
                                     span: fun_desc.ret_type.span,
+                                    kind: ErrorKind::Error,
                                 })
                             }
                         }
@@ -389,9 +607,72 @@ impl ClassDesc {
         }
     }
 
+    // the first class of the inheritance chain (counting from Object) which
+    // has the item, e.g. the class of the method overridden by this one
+    pub fn get_item_origin<'a>(
+        &'a self,
+        global_ctx: &'a GlobalContext,
+        name: &str,
+    ) -> Option<&'a ClassDesc> {
+        let parent_origin = self.parent_type.as_ref().and_then(|parent_type| {
+            let parent_name = match &parent_type.inner {
+                InnerType::Class(n) => n,
+                _ => unreachable!(), // assumption: tree made by our parser
+            };
+            global_ctx
+                .get_class_description(parent_name)
+                .and_then(|cl_desc| cl_desc.get_item_origin(global_ctx, name))
+        });
+        match parent_origin {
+            Some(cl_desc) => Some(cl_desc),
+            None if self.items.contains_key(name) => Some(self),
+            None => None,
+        }
+    }
+
     pub fn get_name(&self) -> &str {
         &self.name
     }
+
+    pub fn get_type_params(&self) -> &[String] {
+        &self.type_params
+    }
+
+    pub fn get_parent_type(&self) -> Option<&Type> {
+        self.parent_type.as_ref()
+    }
+
+    pub fn get_items(&self) -> &HashMap<String, TypeWrapper> {
+        &self.items
+    }
+
+    pub fn is_builtin(&self) -> bool {
+        self.is_builtin
+    }
+
+    // classes extend at least Object, only Object and structs have no parent
+    pub fn is_struct(&self) -> bool {
+        !self.is_builtin && self.parent_type.is_none()
+    }
+
+    // type of "self" in methods
+    pub fn get_self_type(&self) -> InnerType {
+        if self.type_params.is_empty() {
+            InnerType::Class(self.name.to_string())
+        } else {
+            let type_args = self
+                .type_params
+                .iter()
+                .map(|p| InnerType::TypeParam(p.to_string()))
+                .collect();
+            InnerType::GenericClass(self.name.to_string(), type_args)
+        }
+    }
+
+    // type of an item accessed through an object of type Class<type_args>
+    pub fn substitute_type_params(&self, t: &InnerType, type_args: &[InnerType]) -> InnerType {
+        substitute_type_params(t, &self.type_params, type_args)
+    }
 }
 
 impl FunDesc {
@@ -399,7 +680,59 @@ impl FunDesc {
         FunDesc {
             ret_type: fundef.ret_type.clone(),
             name: fundef.name.inner.to_string(),
+            type_params: fundef
+                .type_params
+                .iter()
+                .map(|p| p.inner.to_string())
+                .collect(),
             args_types: fundef.args.iter().map(|(t, _)| t.clone()).collect(),
+            args_names: fundef
+                .args
+                .iter()
+                .map(|(_, id)| id.inner.to_string())
+                .collect(),
+            inline_hint: fundef.inline_hint,
+            no_return: false,
+        }
+    }
+
+    // signature of a call through a function value
+    pub fn of_function_type(ret_type: &InnerType, args_types: &[InnerType], span: Span) -> Self {
+        let to_type = |t: &InnerType| Type {
+            inner: t.clone(),
+            span,
+        };
+        FunDesc {
+            ret_type: to_type(ret_type),
+            name: InnerType::Function(Box::new(ret_type.clone()), args_types.to_vec()).to_string(),
+            type_params: vec![],
+            args_types: args_types.iter().map(to_type).collect(),
+            // parameters of function values are unnamed
+            args_names: args_types.iter().map(|_| String::new()).collect(),
+            inline_hint: InlineHint::Auto,
+            no_return: false,
+        }
+    }
+
+    // signature with given type parameters substituted, e.g. of a method of generic class
+    pub fn substitute_type_params(&self, params: &[String], args: &[InnerType]) -> FunDesc {
+        let subst = |t: &Type| Type {
+            inner: substitute_type_params(&t.inner, params, args),
+            span: t.span,
+        };
+        FunDesc {
+            ret_type: subst(&self.ret_type),
+            name: self.name.to_string(),
+            type_params: self
+                .type_params
+                .iter()
+                .filter(|p| !params.contains(p))
+                .cloned()
+                .collect(),
+            args_types: self.args_types.iter().map(subst).collect(),
+            args_names: self.args_names.clone(),
+            inline_hint: self.inline_hint,
+            no_return: self.no_return,
         }
     }
 
@@ -436,7 +769,7 @@ impl FunDesc {
 // --------------------------------------------------------
 // ----------------- builtins -----------------------------
 // --------------------------------------------------------
-fn get_builtin_functions() -> HashMap<String, FunDesc> {
+pub fn get_builtin_functions() -> HashMap<String, FunDesc> {
     let t_void = Type {
         inner: InnerType::Void,
         span: EMPTY_SPAN,
@@ -456,7 +789,11 @@ fn get_builtin_functions() -> HashMap<String, FunDesc> {
         FunDesc {
             ret_type: t_void.clone(),
             name: "printInt".to_string(),
+            type_params: vec![],
             args_types: vec![t_int.clone()],
+            args_names: vec!["n".to_string()],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
         },
     );
     m.insert(
@@ -464,32 +801,269 @@ fn get_builtin_functions() -> HashMap<String, FunDesc> {
         FunDesc {
             ret_type: t_void.clone(),
             name: "printString".to_string(),
+            type_params: vec![],
+            args_types: vec![t_string.clone()],
+            args_names: vec!["s".to_string()],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
+        },
+    );
+    m.insert(
+        "printBoolean".to_string(),
+        FunDesc {
+            ret_type: t_void.clone(),
+            name: "printBoolean".to_string(),
+            type_params: vec![],
+            args_types: vec![Type {
+                inner: InnerType::Bool,
+                span: EMPTY_SPAN,
+            }],
+            args_names: vec!["b".to_string()],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
+        },
+    );
+    m.insert(
+        "printErr".to_string(),
+        FunDesc {
+            ret_type: t_void.clone(),
+            name: "printErr".to_string(),
+            type_params: vec![],
             args_types: vec![t_string.clone()],
+            args_names: vec!["s".to_string()],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
         },
     );
     m.insert(
         "error".to_string(),
         FunDesc {
-            ret_type: t_void,
+            ret_type: t_void.clone(),
             name: "error".to_string(),
+            type_params: vec![],
             args_types: vec![],
+            args_names: vec![],
+            inline_hint: InlineHint::Auto,
+            no_return: true,
+        },
+    );
+    m.insert(
+        "flushOutput".to_string(),
+        FunDesc {
+            ret_type: t_void.clone(),
+            name: "flushOutput".to_string(),
+            type_params: vec![],
+            args_types: vec![],
+            args_names: vec![],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
         },
     );
     m.insert(
         "readInt".to_string(),
         FunDesc {
-            ret_type: t_int,
+            ret_type: t_int.clone(),
             name: "readInt".to_string(),
+            type_params: vec![],
             args_types: vec![],
+            args_names: vec![],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
         },
     );
     m.insert(
         "readString".to_string(),
         FunDesc {
-            ret_type: t_string,
+            ret_type: t_string.clone(),
             name: "readString".to_string(),
+            type_params: vec![],
             args_types: vec![],
+            args_names: vec![],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
+        },
+    );
+
+    m.insert(
+        "readFile".to_string(),
+        FunDesc {
+            ret_type: t_string.clone(),
+            name: "readFile".to_string(),
+            type_params: vec![],
+            args_types: vec![t_string.clone()],
+            args_names: vec!["path".to_string()],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
+        },
+    );
+    m.insert(
+        "writeFile".to_string(),
+        FunDesc {
+            ret_type: t_void.clone(),
+            name: "writeFile".to_string(),
+            type_params: vec![],
+            args_types: vec![t_string.clone(), t_string.clone()],
+            args_names: vec!["path".to_string(), "contents".to_string()],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
+        },
+    );
+
+    let t_string_array = Type {
+        inner: InnerType::Array(Box::new(InnerType::String)),
+        span: EMPTY_SPAN,
+    };
+    m.insert(
+        "split".to_string(),
+        FunDesc {
+            ret_type: t_string_array.clone(),
+            name: "split".to_string(),
+            type_params: vec![],
+            args_types: vec![t_string.clone(), t_string.clone()],
+            args_names: vec!["s".to_string(), "sep".to_string()],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
+        },
+    );
+    m.insert(
+        "join".to_string(),
+        FunDesc {
+            ret_type: t_string.clone(),
+            name: "join".to_string(),
+            type_params: vec![],
+            args_types: vec![t_string_array, t_string],
+            args_names: vec!["parts".to_string(), "sep".to_string()],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
+        },
+    );
+
+    // T[] arrayResize<T>(T[] a, int n), T[] append<T>(T[] a, T x) and void print<T>(T x)
+    let t_elem = || InnerType::TypeParam("T".to_string());
+    m.insert(
+        PRINT_FUN.to_string(),
+        FunDesc {
+            ret_type: t_void,
+            name: PRINT_FUN.to_string(),
+            type_params: vec!["T".to_string()],
+            args_types: vec![Type {
+                inner: t_elem(),
+                span: EMPTY_SPAN,
+            }],
+            args_names: vec!["x".to_string()],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
+        },
+    );
+    let t_array = Type {
+        inner: InnerType::Array(Box::new(t_elem())),
+        span: EMPTY_SPAN,
+    };
+    m.insert(
+        ARRAY_RESIZE_FUN.to_string(),
+        FunDesc {
+            ret_type: t_array.clone(),
+            name: ARRAY_RESIZE_FUN.to_string(),
+            type_params: vec!["T".to_string()],
+            args_types: vec![t_array.clone(), t_int],
+            args_names: vec!["a".to_string(), "n".to_string()],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
+        },
+    );
+    m.insert(
+        ARRAY_APPEND_FUN.to_string(),
+        FunDesc {
+            ret_type: t_array.clone(),
+            name: ARRAY_APPEND_FUN.to_string(),
+            type_params: vec!["T".to_string()],
+            args_types: vec![
+                t_array,
+                Type {
+                    inner: t_elem(),
+                    span: EMPTY_SPAN,
+                },
+            ],
+            args_names: vec!["a".to_string(), "x".to_string()],
+            inline_hint: InlineHint::Auto,
+            no_return: false,
         },
     );
     m
 }
+
+fn get_builtin_classes() -> HashMap<String, ClassDesc> {
+    let new_type = |inner| Type {
+        inner,
+        span: EMPTY_SPAN,
+    };
+    let new_method = |name: &str, ret_type: &InnerType, args: Vec<(&str, &InnerType)>| FunDesc {
+        ret_type: new_type(ret_type.clone()),
+        name: name.to_string(),
+        type_params: vec![],
+        args_types: args.iter().map(|(_, t)| new_type((*t).clone())).collect(),
+        args_names: args.iter().map(|(n, _)| n.to_string()).collect(),
+        inline_hint: InlineHint::Auto,
+        no_return: false,
+    };
+    let new_class = |name: &str, methods: Vec<FunDesc>| ClassDesc {
+        name: name.to_string(),
+        type_params: vec![],
+        parent_type: None,
+        items: methods
+            .into_iter()
+            .map(|m| (m.name.to_string(), TypeWrapper::Fun(m)))
+            .collect(),
+        is_builtin: true,
+    };
+
+    let t_void = InnerType::Void;
+    let t_int = InnerType::Int;
+    let t_string = InnerType::String;
+    let t_string_builder = InnerType::Class(STRING_BUILDER_CLASS.to_string());
+
+    let t_object = InnerType::Class(OBJECT_CLASS.to_string());
+
+    let mut classes = vec![new_class(
+        OBJECT_CLASS,
+        vec![
+            new_method(EQUALS_METHOD, &InnerType::Bool, vec![("other", &t_object)]),
+            new_method("toString", &t_string, vec![]),
+            new_method("hashCode", &t_int, vec![]),
+        ],
+    )];
+    classes.push(new_class(
+        STRING_BUILDER_CLASS,
+        vec![
+            new_method("append", &t_string_builder, vec![("s", &t_string)]),
+            new_method("toString", &t_string, vec![]),
+        ],
+    ));
+    for (name, t_elem) in &[(INT_LIST_CLASS, &t_int), (STRING_LIST_CLASS, &t_string)] {
+        classes.push(new_class(
+            name,
+            vec![
+                new_method("push", &t_void, vec![("elem", t_elem)]),
+                new_method("get", t_elem, vec![("index", &t_int)]),
+                new_method("set", &t_void, vec![("index", &t_int), ("elem", t_elem)]),
+                new_method("size", &t_int, vec![]),
+            ],
+        ));
+    }
+    let t_string_array = InnerType::Array(Box::new(t_string.clone()));
+    classes.push(new_class(
+        STRING_INT_MAP_CLASS,
+        vec![
+            new_method("put", &t_void, vec![("key", &t_string), ("value", &t_int)]),
+            new_method("get", &t_int, vec![("key", &t_string)]),
+            new_method("contains", &InnerType::Bool, vec![("key", &t_string)]),
+            new_method("size", &t_int, vec![]),
+            new_method("keys", &t_string_array, vec![]),
+        ],
+    ));
+
+    classes
+        .into_iter()
+        .map(|cl| (cl.name.to_string(), cl))
+        .collect()
+}
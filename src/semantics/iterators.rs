@@ -0,0 +1,169 @@
+use model::ast::*;
+use std::mem;
+
+// Foreach loops over objects are syntax sugar, lowered by the type checking
+// (which knows the type of the iterated expression), so the rest of the
+// compiler and the interpreter see only foreach loops over arrays:
+//   for (T x : obj) body
+// becomes, for a class with methods `boolean hasNext()` and `T next()`,
+//   { C foreach.iter = obj; while (foreach.iter.hasNext()) { T x = foreach.iter.next(); body } }
+// and for the builtin lists (which have only get and size)
+//   { IntList foreach.list = obj; int foreach.idx = 0; while (foreach.idx < foreach.list.size()) {
+//       T x = foreach.list.get(foreach.idx); foreach.idx++; body } }
+// The label of the loop is kept, so break and continue work like before.
+// Maps are iterated over their keys: `obj` is replaced by `obj.keys()`.
+
+pub const HAS_NEXT_METHOD: &str = "hasNext";
+pub const NEXT_METHOD: &str = "next";
+const KEYS_METHOD: &str = "keys";
+const ITER_VAR: &str = "foreach.iter";
+const LIST_VAR: &str = "foreach.list";
+const INDEX_VAR: &str = "foreach.idx";
+
+// requirement: stmt is a foreach loop over an object of type obj_type
+// (a class with the methods above or a builtin collection)
+pub fn lower_iterator_loop(stmt: &mut Stmt, obj_type: &InnerType) {
+    let span = stmt.span;
+    let (label, iter_type, iter_name, obj, body) = match &mut stmt.inner {
+        InnerStmt::ForEach {
+            label,
+            iter_type,
+            iter_name,
+            array,
+            body,
+        } => {
+            let class_name = match obj_type {
+                InnerType::Class(name) | InnerType::GenericClass(name, _) => name.as_str(),
+                _ => unreachable!(),
+            };
+            if class_name == STRING_INT_MAP_CLASS {
+                let obj = mem::replace(array, spanned(InnerExpr::LitNull, span));
+                *array = method_call(obj, KEYS_METHOD, None, span);
+                return;
+            }
+            let body = mem::replace(
+                body,
+                Block {
+                    stmts: vec![],
+                    span,
+                },
+            );
+            let obj = mem::replace(array, spanned(InnerExpr::LitNull, span));
+            (
+                label.take(),
+                iter_type.clone(),
+                iter_name.clone(),
+                obj,
+                body,
+            )
+        }
+        _ => unreachable!(),
+    };
+    let obj_type = ItemWithSpan {
+        inner: obj_type.clone(),
+        span: obj.span,
+    };
+    let is_list = obj_type.inner == InnerType::Class(INT_LIST_CLASS.to_string())
+        || obj_type.inner == InnerType::Class(STRING_LIST_CLASS.to_string());
+
+    let mut stmts = vec![decl(
+        obj_type,
+        if is_list { LIST_VAR } else { ITER_VAR },
+        obj,
+    )];
+    let (cond, elem) = if is_list {
+        let int_type = ItemWithSpan {
+            inner: InnerType::Int,
+            span,
+        };
+        stmts.push(decl(
+            int_type,
+            INDEX_VAR,
+            spanned(InnerExpr::LitInt(0), span),
+        ));
+        let size = method_call(var(LIST_VAR, span), "size", None, span);
+        let cond = InnerExpr::BinaryOp(var(INDEX_VAR, span), BinaryOp::LT, size);
+        let elem = method_call(var(LIST_VAR, span), "get", Some(var(INDEX_VAR, span)), span);
+        (spanned(cond, span), elem)
+    } else {
+        let cond = method_call(var(ITER_VAR, span), HAS_NEXT_METHOD, None, span);
+        let elem = method_call(var(ITER_VAR, span), NEXT_METHOD, None, span);
+        (cond, elem)
+    };
+
+    let mut loop_stmts = vec![spanned(
+        InnerStmt::Decl {
+            var_type: iter_type,
+            var_items: vec![(iter_name, Some(elem))],
+        },
+        span,
+    )];
+    if is_list {
+        loop_stmts.push(spanned(InnerStmt::Incr(var(INDEX_VAR, span)), span));
+    }
+    loop_stmts.push(spanned(InnerStmt::Block(body), span));
+    stmts.push(spanned(
+        InnerStmt::While {
+            label,
+            cond,
+            body: Block {
+                stmts: loop_stmts,
+                span,
+            },
+        },
+        span,
+    ));
+    stmt.inner = InnerStmt::Block(Block { stmts, span });
+}
+
+// whether the statement is a foreach loop over an object, as lowered above
+pub fn is_iterator_loop(stmt: &Stmt) -> bool {
+    match &stmt.inner {
+        InnerStmt::Block(Block { stmts, .. }) => match stmts.first().map(|st| &st.inner) {
+            Some(InnerStmt::Decl { var_items, .. }) => var_items
+                .iter()
+                .any(|(name, _)| name.inner == ITER_VAR || name.inner == LIST_VAR),
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+fn spanned<T>(inner: T, span: Span) -> Box<ItemWithSpan<T>> {
+    Box::new(ItemWithSpan { inner, span })
+}
+
+fn var(name: &str, span: Span) -> Box<Expr> {
+    spanned(InnerExpr::LitVar(name.to_string()), span)
+}
+
+// with at most one argument
+fn method_call(obj: Box<Expr>, name: &str, arg: Option<Box<Expr>>, span: Span) -> Box<Expr> {
+    let method_name = ItemWithSpan {
+        inner: name.to_string(),
+        span,
+    };
+    spanned(
+        InnerExpr::ObjMethodCall {
+            obj,
+            method_name,
+            args: arg.into_iter().collect(),
+        },
+        span,
+    )
+}
+
+fn decl(var_type: Type, name: &str, value: Box<Expr>) -> Box<Stmt> {
+    let span = value.span;
+    let name = ItemWithSpan {
+        inner: name.to_string(),
+        span,
+    };
+    spanned(
+        InnerStmt::Decl {
+            var_type,
+            var_items: vec![(name, Some(value))],
+        },
+        span,
+    )
+}
@@ -0,0 +1,282 @@
+use super::visitor::*;
+use model::ast::*;
+use std::collections::HashSet;
+use std::mem;
+
+// Lambdas are lowered to classes, so codegen doesn't know about them.
+// Every function type `fn R(A, B)` becomes an (abstract-like) base class
+// with a method `lambda.call`, and every lambda becomes its subclass
+// with captured values as fields and the body as the overriding method.
+// The lambda expression itself is replaced by a call of a generated
+// function creating the object and copying captured values to it.
+
+const CALL_METHOD: &str = "lambda.call";
+
+// requirement: program is analyzed and monomorphized
+pub fn lower_lambdas(prog: &mut Program) {
+    let mut lifter = LambdaLifter {
+        new_defs: vec![],
+        next_id: 0,
+    };
+    for def in &mut prog.defs {
+        walk_top_def(def, &mut lifter);
+    }
+    prog.defs.extend(lifter.new_defs);
+
+    let mut collector = FunctionTypeCollector {
+        names: HashSet::new(),
+        types: vec![],
+    };
+    for def in &mut prog.defs {
+        walk_top_def(def, &mut collector);
+    }
+    // base classes go first, before their subclasses
+    let mut defs: Vec<_> = collector
+        .types
+        .iter()
+        .map(|t| TopDef::ClassDef(new_base_class(t)))
+        .collect();
+    defs.append(&mut prog.defs);
+
+    for def in &mut defs {
+        walk_top_def(def, &mut FunctionTypeRewriter);
+    }
+    prog.defs = defs;
+}
+
+fn spanned<T>(inner: T) -> ItemWithSpan<T> {
+    ItemWithSpan {
+        inner,
+        span: EMPTY_SPAN,
+    }
+}
+
+fn lit_var(name: &str) -> Box<Expr> {
+    Box::new(spanned(InnerExpr::LitVar(name.to_string())))
+}
+
+fn new_method(ret_type: &Type, args: Vec<(Type, Ident)>, body: Block) -> ClassItemDef {
+    spanned(InnerClassItemDef::Method(FunDef {
+        inline_hint: InlineHint::Auto,
+        ret_type: ret_type.clone(),
+        name: spanned(CALL_METHOD.to_string()),
+        type_params: vec![],
+        args,
+        body,
+        span: EMPTY_SPAN,
+    }))
+}
+
+// `call` of the base class is never executed, as only lambdas are instantiated
+fn new_base_class(fun_type: &InnerType) -> ClassDef {
+    let (ret_type, args_types) = match fun_type {
+        InnerType::Function(ret_type, args_types) => (ret_type, args_types),
+        _ => unreachable!(),
+    };
+    let ret_type = spanned(*ret_type.clone());
+    let args = args_types
+        .iter()
+        .enumerate()
+        .map(|(i, t)| (spanned(t.clone()), spanned(format!("arg.{}", i))))
+        .collect();
+    let error_call = InnerExpr::FunCall {
+        function_name: spanned("error".to_string()),
+        args: vec![],
+        type_args: vec![],
+    };
+    let mut stmts = vec![Box::new(spanned(InnerStmt::Expr(Box::new(spanned(
+        error_call,
+    )))))];
+    if ret_type.inner != InnerType::Void {
+        // default value of the type
+        let result_var = "lambda.result";
+        stmts.push(Box::new(spanned(InnerStmt::Decl {
+            var_type: ret_type.clone(),
+            var_items: vec![(spanned(result_var.to_string()), None)],
+        })));
+        stmts.push(Box::new(spanned(InnerStmt::Ret(Some(lit_var(result_var))))));
+    }
+
+    ClassDef {
+        name: spanned(fun_type.to_string()),
+        type_params: vec![],
+        parent_type: None,
+        items: vec![new_method(
+            &ret_type,
+            args,
+            Block {
+                stmts,
+                span: EMPTY_SPAN,
+            },
+        )],
+        span: EMPTY_SPAN,
+    }
+}
+
+struct LambdaLifter {
+    new_defs: Vec<TopDef>,
+    next_id: usize,
+}
+
+impl LambdaLifter {
+    // class of the lambda and the function creating its objects
+    fn lift(
+        &mut self,
+        ret_type: &Type,
+        args: Vec<(Type, Ident)>,
+        body: Block,
+        captures: &[(Type, Ident, Box<Expr>)],
+    ) -> String {
+        let class_name = format!("lambda.{}", self.next_id);
+        self.next_id += 1;
+        let fun_type = InnerType::Function(
+            Box::new(ret_type.inner.clone()),
+            args.iter().map(|(t, _)| t.inner.clone()).collect(),
+        );
+        let class_type = spanned(InnerType::Class(class_name.to_string()));
+
+        let mut items: Vec<_> = captures
+            .iter()
+            .map(|(t, name, _)| spanned(InnerClassItemDef::Field(t.clone(), name.clone())))
+            .collect();
+        // the body accesses captured values through LAMBDA_ENV_VAR
+        let env_decl = InnerStmt::Decl {
+            var_type: class_type.clone(),
+            var_items: vec![(spanned(LAMBDA_ENV_VAR.to_string()), Some(lit_var(THIS_VAR)))],
+        };
+        let span = body.span;
+        let mut stmts = vec![Box::new(spanned(env_decl))];
+        stmts.extend(body.stmts);
+        items.push(new_method(ret_type, args, Block { stmts, span }));
+        self.new_defs.push(TopDef::ClassDef(ClassDef {
+            name: spanned(class_name.to_string()),
+            type_params: vec![],
+            parent_type: Some(spanned(fun_type)),
+            items,
+            span,
+        }));
+
+        let new_obj = InnerStmt::Decl {
+            var_type: class_type.clone(),
+            var_items: vec![(
+                spanned(LAMBDA_ENV_VAR.to_string()),
+                Some(Box::new(spanned(InnerExpr::NewObject(class_type.clone())))),
+            )],
+        };
+        let mut stmts = vec![Box::new(spanned(new_obj))];
+        let mut ctor_args = vec![];
+        for (i, (t, name, _)) in captures.iter().enumerate() {
+            let arg_name = format!("capture.{}", i);
+            let field = InnerExpr::ObjField {
+                obj: lit_var(LAMBDA_ENV_VAR),
+                is_obj_an_array: Some(false),
+                is_obj_a_struct: Some(false),
+                field: name.clone(),
+            };
+            let assign = InnerStmt::Assign(Box::new(spanned(field)), lit_var(&arg_name));
+            stmts.push(Box::new(spanned(assign)));
+            ctor_args.push((t.clone(), spanned(arg_name)));
+        }
+        stmts.push(Box::new(spanned(InnerStmt::Ret(Some(lit_var(
+            LAMBDA_ENV_VAR,
+        ))))));
+        let ctor_name = format!("{}.new", class_name);
+        self.new_defs.push(TopDef::FunDef(FunDef {
+            inline_hint: InlineHint::Auto,
+            ret_type: class_type,
+            name: spanned(ctor_name.to_string()),
+            type_params: vec![],
+            args: ctor_args,
+            body: Block {
+                stmts,
+                span: EMPTY_SPAN,
+            },
+            span: EMPTY_SPAN,
+        }));
+        ctor_name
+    }
+}
+
+impl TypeVisitor for LambdaLifter {
+    fn visit_type(&mut self, _t: &mut InnerType) {}
+
+    // inner lambdas are already lifted, when the outer one is visited
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        let (ret_type, args, body, captures) =
+            match mem::replace(&mut expr.inner, InnerExpr::LitNull) {
+                InnerExpr::Lambda {
+                    ret_type,
+                    args,
+                    body,
+                    captures,
+                } => (ret_type, args, body, captures),
+                other => {
+                    expr.inner = other;
+                    return;
+                }
+            };
+        let fun_type = InnerType::Function(
+            Box::new(ret_type.inner.clone()),
+            args.iter().map(|(t, _)| t.inner.clone()).collect(),
+        );
+        let ctor_name = self.lift(&ret_type, args, body, &captures);
+        let ctor_call = InnerExpr::FunCall {
+            function_name: spanned(ctor_name),
+            args: captures.into_iter().map(|(_, _, value)| value).collect(),
+            type_args: vec![],
+        };
+        expr.inner = InnerExpr::CastType(
+            Box::new(ItemWithSpan {
+                inner: ctor_call,
+                span: expr.span,
+            }),
+            fun_type,
+        );
+    }
+}
+
+struct FunctionTypeCollector {
+    names: HashSet<String>,
+    types: Vec<InnerType>,
+}
+
+impl TypeVisitor for FunctionTypeCollector {
+    fn visit_type(&mut self, t: &mut InnerType) {
+        match t {
+            InnerType::Array(subtype) => self.visit_type(subtype),
+            InnerType::Function(ret_type, args_types) => {
+                self.visit_type(ret_type);
+                for a in args_types.iter_mut() {
+                    self.visit_type(a);
+                }
+                if self.names.insert(t.to_string()) {
+                    self.types.push(t.clone());
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+struct FunctionTypeRewriter;
+
+impl TypeVisitor for FunctionTypeRewriter {
+    fn visit_type(&mut self, t: &mut InnerType) {
+        match t {
+            InnerType::Array(subtype) => self.visit_type(subtype),
+            InnerType::Function(..) => *t = InnerType::Class(t.to_string()),
+            _ => (),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        expr.inner = match mem::replace(&mut expr.inner, InnerExpr::LitNull) {
+            InnerExpr::ClosureCall { closure, args } => InnerExpr::ObjMethodCall {
+                obj: closure,
+                method_name: spanned(CALL_METHOD.to_string()),
+                args,
+            },
+            other => other,
+        };
+    }
+}
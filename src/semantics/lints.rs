@@ -0,0 +1,234 @@
+use super::visitor::*;
+use frontend_error::{ErrorKind, FrontendError, FrontendResult};
+use model::ast::*;
+use options::LintLevel;
+use std::collections::{HashMap, HashSet};
+
+// Checks of correct programs for code which is probably a mistake.
+// They're reported as warnings (requirement: whole analysis succeeded).
+// A comment `// latte: allow(lint, ...)` silences the named lints in the
+// definition, class item or statement following it (the first one which
+// starts after the comment, with everything inside of it). The levels of
+// the lints (--allow, --deny, latte.toml) can silence a lint everywhere or
+// make its warnings errors.
+
+const UNUSED_RESULT: &str = "unused_result";
+const SAME_BRANCHES: &str = "same_branches";
+const REPEATED_CONDITION: &str = "repeated_condition";
+pub const LINT_NAMES: &[&str] = &[UNUSED_RESULT, SAME_BRANCHES, REPEATED_CONDITION];
+
+// the warnings, or the errors of the denied lints
+pub fn check_lints(
+    prog: &mut Program,
+    levels: &HashMap<String, LintLevel>,
+) -> FrontendResult<Vec<FrontendError>> {
+    let mut lints = Lints {
+        warnings: vec![],
+        else_if_conds: HashSet::new(),
+        item_spans: vec![],
+    };
+    for def in &mut prog.defs {
+        match def {
+            TopDef::FunDef(fun) => lints.item_spans.push(fun.span),
+            TopDef::ClassDef(cl) => {
+                lints.item_spans.push(cl.span);
+                lints.item_spans.extend(cl.items.iter().map(|it| it.span));
+            }
+            TopDef::StructDef(st) => lints.item_spans.push(st.span),
+            TopDef::TypeDef(_) | TopDef::Error => unreachable!(),
+        }
+        if let TopDef::FunDef(_) | TopDef::ClassDef(_) = def {
+            walk_top_def(def, &mut lints);
+        }
+    }
+
+    let (allowed, mut warnings) = lints.get_allowed(&prog.annotations);
+    let mut errors = vec![];
+    for (lint, mut w) in lints.warnings {
+        let is_allowed = allowed
+            .iter()
+            .any(|(name, span)| name == lint && span.0 <= w.span.0 && w.span.1 <= span.1);
+        match levels.get(lint) {
+            _ if is_allowed => (),
+            Some(LintLevel::Allow) => (),
+            Some(LintLevel::Deny) => {
+                w.err = w.err.replacen("Warning: ", "Error: ", 1);
+                errors.push(w);
+            }
+            Some(LintLevel::Warn) | None => warnings.push(w),
+        }
+    }
+    if !errors.is_empty() {
+        errors.sort_by_key(|e| e.span.0);
+        return Err(errors);
+    }
+    warnings.sort_by_key(|w| w.span.0);
+    Ok(warnings)
+}
+
+struct Lints {
+    warnings: Vec<(&'static str, FrontendError)>,
+    // conditions of `else if` already checked with their whole if-else chain
+    else_if_conds: HashSet<Span>,
+    // of the definitions, class items and statements, for the annotations
+    item_spans: Vec<Span>,
+}
+
+impl TypeVisitor for Lints {
+    fn visit_type(&mut self, _t: &mut InnerType) {}
+
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        self.item_spans.push(stmt.span);
+        match &stmt.inner {
+            InnerStmt::Expr(e) if !has_side_effects(&e.inner) => {
+                self.warn(
+                    UNUSED_RESULT,
+                    "Warning: result of the expression is unused and it has no side effects",
+                    e.span,
+                );
+            }
+            InnerStmt::Cond {
+                cond,
+                true_branch,
+                false_branch,
+            } => {
+                if let Some(bl) = false_branch {
+                    if same_blocks(true_branch, bl) {
+                        self.warn(
+                            SAME_BRANCHES,
+                            "Warning: both branches of the if statement are the same",
+                            stmt.span,
+                        );
+                    }
+                }
+                if !self.else_if_conds.contains(&cond.span) {
+                    self.check_if_else_chain(cond, false_branch);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+impl Lints {
+    fn warn(&mut self, lint: &'static str, msg: &str, span: Span) {
+        let warning = FrontendError {
+            err: msg.to_string(),
+            span,
+            kind: ErrorKind::Error,
+        };
+        self.warnings.push((lint, warning));
+    }
+
+    // (lint, span where it's allowed) and the warnings about the annotations
+    fn get_allowed(&self, annotations: &[Annotation]) -> (Vec<(String, Span)>, Vec<FrontendError>) {
+        let mut allowed = vec![];
+        let mut warnings = vec![];
+        for ann in annotations {
+            let names = match parse_allow(&ann.inner) {
+                Some(names) => names,
+                None => {
+                    warnings.push(FrontendError {
+                        err: "Warning: invalid annotation, expected `latte: allow(<lint>, ...)`"
+                            .to_string(),
+                        span: ann.span,
+                        kind: ErrorKind::Error,
+                    });
+                    continue;
+                }
+            };
+            for name in &names {
+                if !LINT_NAMES.contains(name) {
+                    warnings.push(FrontendError {
+                        err: format!("Warning: unknown lint {} in the annotation", name),
+                        span: ann.span,
+                        kind: ErrorKind::Error,
+                    });
+                }
+            }
+            // the outermost of the items starting first
+            let scope = self
+                .item_spans
+                .iter()
+                .filter(|span| span.0 >= ann.span.1)
+                .min_by_key(|span| (span.0, usize::MAX - span.1));
+            match scope {
+                Some(span) => allowed.extend(names.iter().map(|name| (name.to_string(), *span))),
+                None => warnings.push(FrontendError {
+                    err: "Warning: the annotation isn't followed by a definition or a statement"
+                        .to_string(),
+                    span: ann.span,
+                    kind: ErrorKind::Error,
+                }),
+            }
+        }
+        (allowed, warnings)
+    }
+
+    // `if (c) ... else if (c) ...`, the second branch is never taken
+    // (unless the condition has side effects, e.g. reads the input)
+    fn check_if_else_chain(&mut self, cond: &Expr, false_branch: &Option<Block>) {
+        let mut conds = vec![cond];
+        let mut next_branch = false_branch;
+        while let Some(bl) = next_branch {
+            match bl.stmts.as_slice() {
+                [stmt] => match &stmt.inner {
+                    InnerStmt::Cond {
+                        cond, false_branch, ..
+                    } => {
+                        self.else_if_conds.insert(cond.span);
+                        if !has_side_effects(&cond.inner)
+                            && conds.iter().any(|prev| same_exprs(prev, cond))
+                        {
+                            self.warn(
+                                REPEATED_CONDITION,
+                                "Warning: the same condition is already checked earlier in this if-else chain",
+                                cond.span,
+                            );
+                        }
+                        conds.push(cond);
+                        next_branch = false_branch;
+                    }
+                    _ => break,
+                },
+                _ => break,
+            }
+        }
+    }
+}
+
+// `allow(a, b)` -> [a, b]
+fn parse_allow(text: &str) -> Option<Vec<&str>> {
+    let list = text.strip_prefix("allow")?.trim_start().strip_prefix('(')?;
+    let names: Vec<_> = list.strip_suffix(')')?.split(',').map(str::trim).collect();
+    if names.iter().any(|name| name.is_empty()) {
+        return None;
+    }
+    Some(names)
+}
+
+// calls, assignments, increments and decrements are the only expressions changing anything
+// (a failure, like division by zero, isn't an intended effect, and unused new objects are just
+// garbage)
+fn has_side_effects(expr: &InnerExpr) -> bool {
+    use self::InnerExpr::*;
+    match expr {
+        LitVar(_) | LitInt(_) | LitBool(_) | LitStr(_) | LitNull => false,
+        FunCall { .. }
+        | ObjMethodCall { .. }
+        | ClosureCall { .. }
+        | Assign(..)
+        | IncrDecr { .. } => true,
+        CastType(e, _) | UnaryOp(_, e) | ObjField { obj: e, .. } | NamedArg { value: e, .. } => {
+            has_side_effects(&e.inner)
+        }
+        BinaryOp(lhs, _, rhs) | Range(lhs, rhs) => {
+            has_side_effects(&lhs.inner) || has_side_effects(&rhs.inner)
+        }
+        NewArray { elem_cnt, .. } => has_side_effects(&elem_cnt.inner),
+        ArrayElem { array, index, .. } => {
+            has_side_effects(&array.inner) || has_side_effects(&index.inner)
+        }
+        NewObject(_) | Lambda { .. } => false,
+    }
+}
@@ -1,5 +1,26 @@
 mod analyzer;
+mod call_graph;
+mod context_cache;
+mod definite_assignment;
+mod dialect;
 mod function;
+mod generics;
 pub mod global_context;
+mod iterators;
+mod lambdas;
+mod lints;
+mod nested_classes;
+mod nested_functions;
+mod structs;
+mod symbols;
+mod tail_recursion;
+mod type_aliases;
+mod type_dump;
+mod visitor;
 
 pub use self::analyzer::SemanticAnalyzer;
+pub use self::call_graph::{build_call_graph, CallGraph};
+pub use self::context_cache::ContextCache;
+pub use self::lints::LINT_NAMES;
+pub use self::symbols::{format_symbol_index, Occurrence, SymbolIndex, SymbolKind, SymbolTarget};
+pub use self::type_dump::format_type_dump;
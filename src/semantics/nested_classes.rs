@@ -0,0 +1,190 @@
+use super::visitor::*;
+use frontend_error::{ok_if_no_error, ErrorKind, FrontendError, FrontendResult};
+use model::ast::*;
+use model::ir::encode_nested_class_name;
+use std::collections::HashSet;
+
+// Classes defined inside of classes are lifted to the top level before any
+// other analysis, named with the path of the enclosing classes (`Outer.Inner`).
+// They are like top level classes, their objects don't belong to objects of
+// the enclosing classes. Inside of a class, the classes nested in it and in
+// the enclosing classes can be named without the path: a class name used in
+// `A.B` refers to `A.B.Name`, `A.Name` or `Name`, the first which exists
+// (the same for qualified names, `Inner.Deep` used in `A` can be `A.Inner.Deep`).
+
+pub fn lift_nested_classes(prog: &mut Program) -> FrontendResult<()> {
+    let mut errors = vec![];
+    let mut defs = vec![];
+    for mut def in prog.defs.drain(..) {
+        let mut lifted = vec![];
+        if let TopDef::ClassDef(cl) = &mut def {
+            lift_from_class(cl, &mut lifted, &mut errors);
+        }
+        defs.push(def);
+        defs.extend(lifted.into_iter().map(TopDef::ClassDef));
+    }
+    prog.defs = defs;
+
+    ok_if_no_error(errors)
+}
+
+// adds the classes nested in cl (with the classes nested in them) to lifted
+fn lift_from_class(cl: &mut ClassDef, lifted: &mut Vec<ClassDef>, errors: &mut Vec<FrontendError>) {
+    let mut items = vec![];
+    for it in cl.items.drain(..) {
+        match it.inner {
+            InnerClassItemDef::Class(mut nested) => {
+                if !cl.type_params.is_empty() {
+                    errors.push(FrontendError {
+                        err: "Error: generic classes can't contain nested classes".to_string(),
+                        span: nested.name.span,
+                        kind: ErrorKind::Error,
+                    });
+                }
+                nested.name.inner = format!("{}.{}", cl.name.inner, nested.name.inner);
+                let mut nested_lifted = vec![];
+                lift_from_class(&mut nested, &mut nested_lifted, errors);
+                lifted.push(nested);
+                lifted.extend(nested_lifted);
+            }
+            _ => items.push(it),
+        }
+    }
+    cl.items = items;
+}
+
+// requirement: nested classes and functions are lifted
+pub fn resolve_nested_class_names(prog: &mut Program) {
+    let class_names: HashSet<_> = prog
+        .defs
+        .iter()
+        .filter_map(|def| match def {
+            TopDef::ClassDef(cl) => Some(cl.name.inner.to_string()),
+            _ => None,
+        })
+        .collect();
+    if class_names.iter().all(|name| !name.contains('.')) {
+        return;
+    }
+
+    for def in &mut prog.defs {
+        let cl = match def {
+            TopDef::ClassDef(cl) => cl,
+            _ => continue,
+        };
+        // the class itself and the enclosing classes, innermost first
+        let mut scopes = vec![cl.name.inner.to_string()];
+        while let Some(pos) = scopes.last().unwrap().rfind('.') {
+            let outer = scopes.last().unwrap()[..pos].to_string();
+            scopes.push(outer);
+        }
+        let class_params: Vec<_> = cl.type_params.iter().map(|p| p.inner.to_string()).collect();
+        let mut resolver = NameResolver {
+            class_names: &class_names,
+            scopes: &scopes,
+            type_params: class_params.clone(),
+        };
+        if let Some(t) = &mut cl.parent_type {
+            resolver.visit_type(&mut t.inner);
+        }
+        for it in &mut cl.items {
+            match &mut it.inner {
+                InnerClassItemDef::Field(t, _) => resolver.visit_type(&mut t.inner),
+                InnerClassItemDef::Method(fun) => {
+                    // type parameters shadow classes
+                    resolver.type_params = class_params.clone();
+                    resolver
+                        .type_params
+                        .extend(fun.type_params.iter().map(|p| p.inner.to_string()));
+                    walk_fun(fun, &mut resolver);
+                }
+                InnerClassItemDef::Class(_) | InnerClassItemDef::Error => unreachable!(),
+            }
+        }
+    }
+}
+
+struct NameResolver<'a> {
+    class_names: &'a HashSet<String>,
+    scopes: &'a [String],
+    type_params: Vec<String>,
+}
+
+impl<'a> NameResolver<'a> {
+    fn resolve(&self, name: &mut String) {
+        if self.type_params.contains(name) {
+            return;
+        }
+        for scope in self.scopes {
+            let qualified = format!("{}.{}", scope, name);
+            if self.class_names.contains(&qualified) {
+                *name = qualified;
+                return;
+            }
+        }
+    }
+}
+
+impl<'a> TypeVisitor for NameResolver<'a> {
+    fn visit_type(&mut self, t: &mut InnerType) {
+        match t {
+            InnerType::Class(name) => self.resolve(name),
+            InnerType::GenericClass(name, type_args) => {
+                self.resolve(name);
+                for a in type_args {
+                    self.visit_type(a);
+                }
+            }
+            InnerType::Array(subtype) => self.visit_type(subtype),
+            InnerType::Function(ret_type, args_types) => {
+                self.visit_type(ret_type);
+                for a in args_types {
+                    self.visit_type(a);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+// for the lowered program: the names of nested classes as in the IR
+// (requirement: lambdas aren't lowered yet, so only these names have dots)
+pub fn encode_nested_class_names(prog: &mut Program) {
+    let has_nested = prog.defs.iter().any(|def| match def {
+        TopDef::ClassDef(cl) => cl.name.inner.contains('.'),
+        _ => false,
+    });
+    if !has_nested {
+        return;
+    }
+    for def in &mut prog.defs {
+        if let TopDef::ClassDef(cl) = def {
+            cl.name.inner = encode_nested_class_name(&cl.name.inner);
+        }
+        walk_top_def(def, &mut NameEncoder);
+    }
+}
+
+struct NameEncoder;
+
+impl TypeVisitor for NameEncoder {
+    fn visit_type(&mut self, t: &mut InnerType) {
+        match t {
+            InnerType::Class(name) => *name = encode_nested_class_name(name),
+            InnerType::GenericClass(name, type_args) => {
+                *name = encode_nested_class_name(name);
+                for a in type_args {
+                    self.visit_type(a);
+                }
+            }
+            InnerType::Array(subtype) => self.visit_type(subtype),
+            InnerType::Function(ret_type, args_types) => {
+                self.visit_type(ret_type);
+                for a in args_types {
+                    self.visit_type(a);
+                }
+            }
+            _ => (),
+        }
+    }
+}
@@ -0,0 +1,522 @@
+use frontend_error::{ok_if_no_error, ErrorKind, FrontendError, FrontendResult};
+use model::ast::*;
+use std::collections::{HashMap, HashSet};
+use std::mem;
+
+// Functions defined inside of function bodies are lifted before any other
+// analysis: to the top level, or to the class for nested functions of methods
+// (so they can use its members). Variables of the enclosing functions used by
+// a nested function become its leading parameters, and every call passes their
+// current values, so the nested function can't modify them.
+// Every variable has its type declared, so the captured variables are found
+// by scopes alone, before type checking: a name refers to the innermost
+// declaration visible at the point of use (nested functions are visible from
+// their definition, also in their own bodies).
+
+pub fn lift_nested_functions(prog: &mut Program) -> FrontendResult<()> {
+    let struct_names: HashSet<_> = prog
+        .defs
+        .iter()
+        .filter_map(|def| match def {
+            TopDef::StructDef(st) => Some(st.name.inner.to_string()),
+            _ => None,
+        })
+        .collect();
+
+    let mut errors = vec![];
+    let mut new_defs = vec![];
+    for def in &mut prog.defs {
+        match def {
+            TopDef::FunDef(fun) => {
+                let name = fun.name.inner.to_string();
+                let lifted = lift_from_function(fun, name, &struct_names, &mut errors);
+                if let (false, Some(nested)) = (fun.type_params.is_empty(), lifted.first()) {
+                    errors.push(FrontendError {
+                        err: "Error: generic functions can't contain nested functions".to_string(),
+                        span: nested.name.span,
+                        kind: ErrorKind::Error,
+                    });
+                }
+                new_defs.extend(lifted.into_iter().map(TopDef::FunDef));
+            }
+            TopDef::ClassDef(cl) => {
+                let mut lifted = vec![];
+                for it in &mut cl.items {
+                    if let InnerClassItemDef::Method(fun) = &mut it.inner {
+                        let name = format!("{}.{}", cl.name.inner, fun.name.inner);
+                        lifted.extend(lift_from_function(fun, name, &struct_names, &mut errors));
+                    }
+                }
+                for fun in lifted {
+                    if !fun.type_params.is_empty() {
+                        errors.push(FrontendError {
+                            err: "Error: nested functions of methods can't be generic".to_string(),
+                            span: fun.name.span,
+                            kind: ErrorKind::Error,
+                        });
+                    }
+                    cl.items.push(ItemWithSpan {
+                        span: fun.span,
+                        inner: InnerClassItemDef::Method(fun),
+                    });
+                }
+            }
+            TopDef::StructDef(_) | TopDef::TypeDef(_) => (),
+            TopDef::Error => unreachable!(),
+        }
+    }
+    prog.defs.extend(new_defs);
+
+    ok_if_no_error(errors)
+}
+
+// returns the lifted functions
+fn lift_from_function(
+    fun: &mut FunDef,
+    name: String,
+    struct_names: &HashSet<String>,
+    errors: &mut Vec<FrontendError>,
+) -> Vec<FunDef> {
+    let mut lifter = Lifter {
+        struct_names,
+        scopes: vec![],
+        boundaries: vec![],
+        lifted_names: vec![name],
+        funs: vec![],
+        next_var_id: 0,
+        next_fun_id: 0,
+        rewrite: false,
+        lifted: vec![],
+        errors: vec![],
+    };
+
+    // a call of a nested function passes its captured variables, so they are
+    // also captured by nested functions calling it; it grows until a fixpoint
+    loop {
+        let captures_cnt: Vec<_> = lifter.funs.iter().map(|f| f.captures.len()).collect();
+        lifter.walk_fun_body(&fun.args, &mut fun.body);
+        if lifter.funs.is_empty() {
+            return vec![];
+        }
+        if lifter
+            .funs
+            .iter()
+            .map(|f| f.captures.len())
+            .eq(captures_cnt)
+        {
+            break;
+        }
+    }
+    lifter.rewrite = true;
+    lifter.walk_fun_body(&fun.args, &mut fun.body);
+
+    errors.append(&mut lifter.errors);
+    lifter.lifted
+}
+
+#[derive(Clone)]
+enum Binding {
+    Var(usize, Type), // unique id of the declaration
+    Fun(usize),       // index of the nested function
+}
+
+struct NestedFunInfo {
+    lifted_name: String,
+    type_params_cnt: usize,
+    ret_type: Type,
+    args_types: Vec<Type>,
+    captures: Vec<(String, usize, Type)>,
+}
+
+struct Lifter<'a> {
+    struct_names: &'a HashSet<String>,
+    scopes: Vec<HashMap<String, Binding>>,
+    // nested functions being walked: (index, count of scopes outside of it)
+    boundaries: Vec<(usize, usize)>,
+    // the outer function, then names of nested functions being walked
+    lifted_names: Vec<String>,
+    funs: Vec<NestedFunInfo>,
+    next_var_id: usize,
+    next_fun_id: usize,
+    // the last walk replaces definitions and uses of nested functions
+    rewrite: bool,
+    lifted: Vec<FunDef>,
+    errors: Vec<FrontendError>,
+}
+
+impl<'a> Lifter<'a> {
+    fn walk_fun_body(&mut self, args: &[(Type, Ident)], body: &mut Block) {
+        if self.boundaries.is_empty() {
+            self.next_var_id = 0;
+            self.next_fun_id = 0;
+        }
+        self.scopes.push(HashMap::new());
+        for (t, id) in args {
+            self.declare_var(id, t);
+        }
+        self.walk_block(body);
+        self.scopes.pop();
+    }
+
+    fn walk_block(&mut self, block: &mut Block) {
+        self.scopes.push(HashMap::new());
+        for st in &mut block.stmts {
+            self.walk_stmt(st);
+        }
+        self.scopes.pop();
+    }
+
+    fn walk_stmt(&mut self, stmt: &mut Stmt) {
+        use self::InnerStmt::*;
+        let mut nested_fun_id = None;
+        match &mut stmt.inner {
+            Empty | Break(_) | Continue(_) => (),
+            Block(bl) => self.walk_block(bl),
+            Decl {
+                var_type,
+                var_items,
+            } => {
+                for (id, init_expr) in var_items {
+                    // the variable is in scope in its initializer, like in the type checking
+                    self.declare_var(id, var_type);
+                    if let Some(e) = init_expr {
+                        self.walk_expr(e);
+                    }
+                }
+            }
+            Assign(lhs, rhs) => {
+                self.walk_expr(rhs);
+                self.check_modification(lhs);
+                self.walk_expr(lhs);
+            }
+            Incr(e) | Decr(e) => {
+                self.check_modification(e);
+                self.walk_expr(e);
+            }
+            Expr(e) => self.walk_expr(e),
+            Ret(opt_expr) => {
+                if let Some(e) = opt_expr {
+                    self.walk_expr(e);
+                }
+            }
+            Cond {
+                cond,
+                true_branch,
+                false_branch,
+            } => {
+                self.walk_expr(cond);
+                self.walk_block(true_branch);
+                if let Some(bl) = false_branch {
+                    self.walk_block(bl);
+                }
+            }
+            While { cond, body, .. } => {
+                self.walk_expr(cond);
+                self.walk_block(body);
+            }
+            ForEach {
+                iter_type,
+                iter_name,
+                array,
+                body,
+                ..
+            } => {
+                self.walk_expr(array);
+                self.scopes.push(HashMap::new());
+                self.declare_var(iter_name, iter_type);
+                self.walk_block(body);
+                self.scopes.pop();
+            }
+            NestedFun(fun) => {
+                let fun_id = self.declare_fun(fun);
+                self.boundaries.push((fun_id, self.scopes.len()));
+                self.lifted_names
+                    .push(self.funs[fun_id].lifted_name.to_string());
+                self.walk_fun_body(&fun.args, &mut fun.body);
+                self.lifted_names.pop();
+                self.boundaries.pop();
+                nested_fun_id = Some(fun_id);
+            }
+            Error => unreachable!(),
+        }
+
+        if let (true, Some(fun_id)) = (self.rewrite, nested_fun_id) {
+            if let NestedFun(mut fun) = mem::replace(&mut stmt.inner, Empty) {
+                let info = &self.funs[fun_id];
+                let mut args: Vec<_> = info
+                    .captures
+                    .iter()
+                    .map(|(name, _, t)| {
+                        let id = ItemWithSpan {
+                            inner: name.to_string(),
+                            span: fun.name.span,
+                        };
+                        (t.clone(), id)
+                    })
+                    .collect();
+                args.append(&mut fun.args);
+                fun.args = args;
+                fun.name.inner = info.lifted_name.to_string();
+                self.lifted.push(fun);
+            }
+        }
+    }
+
+    fn walk_expr(&mut self, expr: &mut Expr) {
+        use self::InnerExpr::*;
+        let span = expr.span;
+        let mut override_expr = None;
+        match &mut expr.inner {
+            LitVar(name) => {
+                if let Some(Binding::Fun(fun_id)) = self.use_name(name, span) {
+                    override_expr = self.function_value_lambda(fun_id, span);
+                }
+            }
+            LitInt(_) | LitBool(_) | LitStr(_) | LitNull | NewObject(_) => (),
+            FunCall {
+                function_name,
+                args,
+                ..
+            } => {
+                for a in args.iter_mut() {
+                    self.walk_expr(a);
+                }
+                if let (Some(Binding::Fun(fun_id)), true) = (
+                    self.use_name(&function_name.inner, function_name.span),
+                    self.rewrite,
+                ) {
+                    let info = &self.funs[fun_id];
+                    function_name.inner = info.lifted_name.to_string();
+                    let captured_args = info.captures.iter().map(|(name, _, _)| {
+                        Box::new(ItemWithSpan {
+                            inner: LitVar(name.to_string()),
+                            span: function_name.span,
+                        })
+                    });
+                    args.splice(0..0, captured_args);
+                }
+            }
+            BinaryOp(lhs, _, rhs) | Range(lhs, rhs) => {
+                self.walk_expr(lhs);
+                self.walk_expr(rhs);
+            }
+            Assign(lhs, rhs) => {
+                self.walk_expr(rhs);
+                self.check_modification(lhs);
+                self.walk_expr(lhs);
+            }
+            IncrDecr { lvalue, .. } => {
+                self.check_modification(lvalue);
+                self.walk_expr(lvalue);
+            }
+            UnaryOp(_, e) | ObjField { obj: e, .. } | NamedArg { value: e, .. } => {
+                self.walk_expr(e)
+            }
+            NewArray { elem_cnt, .. } => self.walk_expr(elem_cnt),
+            ArrayElem { array, index, .. } => {
+                self.walk_expr(array);
+                self.walk_expr(index);
+            }
+            ObjMethodCall { obj, args, .. } | ClosureCall { closure: obj, args } => {
+                self.walk_expr(obj);
+                for a in args {
+                    self.walk_expr(a);
+                }
+            }
+            // lambdas use variables of the enclosing function by themselves
+            Lambda { args, body, .. } => {
+                self.scopes.push(HashMap::new());
+                for (t, id) in args.iter() {
+                    self.declare_var(id, t);
+                }
+                self.walk_block(body);
+                self.scopes.pop();
+            }
+            CastType(..) => unreachable!(), // added by type checking
+        }
+        if let Some(e) = override_expr {
+            expr.inner = e;
+        }
+    }
+
+    fn declare_var(&mut self, id: &Ident, t: &Type) {
+        let var_id = self.next_var_id;
+        self.next_var_id += 1;
+        let scope = self.scopes.last_mut().unwrap();
+        // redefinitions of variables are reported by type checking
+        if let Some(Binding::Fun(_)) =
+            scope.insert(id.inner.to_string(), Binding::Var(var_id, t.clone()))
+        {
+            self.report_redefinition(id);
+        }
+    }
+
+    // returns index of the function
+    fn declare_fun(&mut self, fun: &FunDef) -> usize {
+        let fun_id = self.next_fun_id;
+        self.next_fun_id += 1;
+        if fun_id == self.funs.len() {
+            let mut lifted_name =
+                format!("{}.{}", self.lifted_names.last().unwrap(), fun.name.inner);
+            if self.funs.iter().any(|f| f.lifted_name == lifted_name) {
+                lifted_name = format!("{}.{}", lifted_name, fun_id);
+            }
+            self.funs.push(NestedFunInfo {
+                lifted_name,
+                type_params_cnt: fun.type_params.len(),
+                ret_type: fun.ret_type.clone(),
+                args_types: fun.args.iter().map(|(t, _)| t.clone()).collect(),
+                captures: vec![],
+            });
+        }
+        let scope = self.scopes.last_mut().unwrap();
+        if scope
+            .insert(fun.name.inner.to_string(), Binding::Fun(fun_id))
+            .is_some()
+        {
+            self.report_redefinition(&fun.name);
+        }
+        fun_id
+    }
+
+    fn report_redefinition(&mut self, id: &Ident) {
+        if self.rewrite {
+            self.errors.push(FrontendError {
+                err: "Error: variable or nested function with same name already defined in current scope".to_string(),
+                span: id.span,
+                kind: ErrorKind::Error,
+            });
+        }
+    }
+
+    // (index of the scope, binding)
+    fn lookup(&self, name: &str) -> Option<(usize, Binding)> {
+        self.scopes
+            .iter()
+            .enumerate()
+            .rev()
+            .find_map(|(i, scope)| scope.get(name).map(|b| (i, b.clone())))
+    }
+
+    // marks the variable as captured by nested functions between its
+    // declaration and the use, for a function - its captured variables
+    fn use_name(&mut self, name: &str, span: Span) -> Option<Binding> {
+        let (depth, binding) = self.lookup(name)?;
+        match &binding {
+            Binding::Var(var_id, t) => {
+                for (fun_id, outside_cnt) in &self.boundaries {
+                    let captures = &mut self.funs[*fun_id].captures;
+                    if depth < *outside_cnt && captures.iter().all(|(_, id, _)| id != var_id) {
+                        captures.push((name.to_string(), *var_id, t.clone()));
+                    }
+                }
+            }
+            Binding::Fun(fun_id) => {
+                let captures = self.funs[*fun_id].captures.clone();
+                for (captured_name, captured_id, _) in captures {
+                    match self.lookup(&captured_name) {
+                        Some((_, Binding::Var(id, _))) if id == captured_id => {
+                            self.use_name(&captured_name, span);
+                        }
+                        _ if self.rewrite => self.errors.push(FrontendError {
+                            err: format!(
+                                "Error: variable {} used by nested function {} is shadowed here",
+                                captured_name, name
+                            ),
+                            span,
+                            kind: ErrorKind::Error,
+                        }),
+                        _ => (),
+                    }
+                }
+            }
+        }
+        Some(binding)
+    }
+
+    // captured variables are copies, so they can't be assigned
+    // (also fields of captured structs, as they are values)
+    fn check_modification(&mut self, lhs: &Expr) {
+        let mut root = lhs;
+        let mut is_field = false;
+        while let InnerExpr::ObjField { obj, .. } = &root.inner {
+            root = obj;
+            is_field = true;
+        }
+        let name = match &root.inner {
+            InnerExpr::LitVar(name) => name,
+            _ => return,
+        };
+        if let (Some((depth, Binding::Var(_, t))), Some((_, outside_cnt))) =
+            (self.lookup(name), self.boundaries.last())
+        {
+            let is_copy = match &t.inner {
+                InnerType::Class(name) => self.struct_names.contains(name),
+                _ => !is_field,
+            };
+            if depth < *outside_cnt && is_copy && self.rewrite {
+                self.errors.push(FrontendError {
+                    err: "Error: variables of the enclosing function can't be modified in a nested function".to_string(),
+                    span: lhs.span,
+                    kind: ErrorKind::Error,
+                });
+            }
+        }
+    }
+
+    // the nested function used as a value, like in the type checking
+    // of global functions: `fn R(A a) { return f(captured..., a); }`
+    fn function_value_lambda(&mut self, fun_id: usize, span: Span) -> Option<InnerExpr> {
+        let info = &self.funs[fun_id];
+        if info.type_params_cnt > 0 {
+            if self.rewrite {
+                let name = info.lifted_name.rsplit('.').next().unwrap();
+                self.errors.push(FrontendError {
+                    err: format!("Error: generic function {} can't be used as a value", name),
+                    span,
+                    kind: ErrorKind::Error,
+                });
+            }
+            return None;
+        }
+        if !self.rewrite {
+            return None;
+        }
+
+        let (l, r) = span;
+        let args: Vec<_> = info
+            .args_types
+            .iter()
+            .enumerate()
+            .map(|(i, t)| (t.clone(), new_spanned(l, format!("arg.{}", i), r)))
+            .collect();
+        let call_args = info
+            .captures
+            .iter()
+            .map(|(name, _, _)| name)
+            .chain(args.iter().map(|(_, id)| &id.inner))
+            .map(|name| new_spanned_boxed(l, InnerExpr::LitVar(name.to_string()), r))
+            .collect();
+        let call = Box::new(ItemWithSpan {
+            inner: InnerExpr::FunCall {
+                function_name: new_spanned(l, info.lifted_name.to_string(), r),
+                args: call_args,
+                type_args: vec![],
+            },
+            span,
+        });
+        let stmt = match info.ret_type.inner {
+            InnerType::Void => InnerStmt::Expr(call),
+            _ => InnerStmt::Ret(Some(call)),
+        };
+        Some(InnerExpr::Lambda {
+            ret_type: info.ret_type.clone(),
+            args,
+            body: Block {
+                stmts: vec![Box::new(ItemWithSpan { inner: stmt, span })],
+                span,
+            },
+            captures: vec![],
+        })
+    }
+}
@@ -0,0 +1,95 @@
+use super::visitor::*;
+use frontend_error::{ok_if_no_error, ErrorKind, FrontendError, FrontendResult};
+use model::ast::*;
+use std::collections::{HashMap, HashSet};
+
+// Parser doesn't know which names are structs, so struct types are parsed
+// as class types. They are distinguished before building the global context,
+// because structs are values: they are never null, can't be created with new,
+// and the codegen keeps them in registers instead of on the heap.
+
+pub fn resolve_struct_types(prog: &mut Program) -> FrontendResult<()> {
+    let names: HashSet<_> = prog
+        .defs
+        .iter()
+        .filter_map(|def| match def {
+            TopDef::StructDef(st) => Some(st.name.inner.to_string()),
+            _ => None,
+        })
+        .collect();
+    if names.is_empty() {
+        return Ok(());
+    }
+
+    let mut resolver = StructResolver { names: &names };
+    for def in &mut prog.defs {
+        walk_top_def(def, &mut resolver);
+    }
+
+    // a struct containing itself (directly or through other structs)
+    // would have infinite size; arrays and classes are references, so they're fine
+    let mut fields = HashMap::new();
+    for def in &prog.defs {
+        if let TopDef::StructDef(st) = def {
+            fields.insert(st.name.inner.as_str(), &st.fields);
+        }
+    }
+    let mut errors = vec![];
+    for def in &prog.defs {
+        if let TopDef::StructDef(st) = def {
+            let mut visited = HashSet::new();
+            let mut stack = vec![st.name.inner.as_str()];
+            let mut is_recursive = false;
+            while let Some(name) = stack.pop() {
+                for (t, _) in fields[name].iter() {
+                    if let InnerType::Struct(field_struct) = &t.inner {
+                        if *field_struct == st.name.inner {
+                            is_recursive = true;
+                        } else if visited.insert(field_struct.as_str()) {
+                            stack.push(field_struct);
+                        }
+                    }
+                }
+            }
+            if is_recursive {
+                errors.push(FrontendError {
+                    err: format!(
+                        "Error: struct {} contains itself, use a class for recursive data",
+                        st.name.inner
+                    ),
+                    span: st.name.span,
+                    kind: ErrorKind::Error,
+                });
+            }
+        }
+    }
+
+    ok_if_no_error(errors)
+}
+
+struct StructResolver<'a> {
+    names: &'a HashSet<String>,
+}
+
+impl<'a> TypeVisitor for StructResolver<'a> {
+    fn visit_type(&mut self, t: &mut InnerType) {
+        match t {
+            InnerType::Class(name) if self.names.contains(name) => {
+                *t = InnerType::Struct(name.to_string());
+            }
+            InnerType::Array(subtype) => self.visit_type(subtype),
+            InnerType::GenericClass(_, type_args) => {
+                for a in type_args {
+                    self.visit_type(a);
+                }
+            }
+            InnerType::Function(ret_type, args_types) => {
+                self.visit_type(ret_type);
+                for a in args_types {
+                    self.visit_type(a);
+                }
+            }
+            _ => (),
+        }
+    }
+}
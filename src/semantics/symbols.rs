@@ -0,0 +1,289 @@
+use super::global_context::GlobalContext;
+use super::visitor::*;
+use codemap::CodeMap;
+use model::ast::*;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashSet};
+
+// Index of the identifiers of the type checked program, for editors (semantic
+// highlighting, go to definition, rename): every occurrence of a variable,
+// parameter, field, method, function or class name with its span, whether it's
+// the definition, and the symbol it refers to, the same for all occurrences
+// of the symbol. The uses are recorded by the type checking, which resolves
+// the names; the definitions and the class names in types are added from the
+// AST afterwards. Names made by the analysis (e.g. the calls of foreach loops
+// over objects) don't match the code at their spans, so they're dropped.
+// Not indexed: `this`, type parameters, type aliases, types inside of generic
+// and function types, and variables of the enclosing functions used in nested
+// functions (they become parameters of the lifted functions).
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SymbolKind {
+    Local,
+    Param,
+    Field,
+    Method,
+    Function,
+    Class, // also structs
+}
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum SymbolTarget {
+    // by the span of the name in its declaration
+    Variable(Span),
+    Function(String),
+    Class(String),
+    // a class item, by the class where it's defined first, so an overriding
+    // method is the same symbol as the overridden one
+    Member(String, String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Occurrence {
+    pub name: String, // as written in the code
+    pub kind: SymbolKind,
+    pub target: SymbolTarget,
+    pub is_definition: bool,
+}
+
+// occurrences by their spans, filled during the type checking
+pub type SymbolRecorder = RefCell<BTreeMap<Span, Occurrence>>;
+
+pub struct SymbolIndex {
+    occurrences: BTreeMap<Span, Occurrence>,
+}
+
+impl SymbolIndex {
+    // requirement: the program is type checked (not lowered), the code is
+    // the one it was parsed from (as in the code map)
+    pub fn build(
+        prog: &mut Program,
+        ctx: &GlobalContext,
+        recorded: SymbolRecorder,
+        code: &str,
+    ) -> SymbolIndex {
+        let mut builder = IndexBuilder {
+            occurrences: recorded.into_inner(),
+            ctx,
+            code,
+        };
+        for def in &mut prog.defs {
+            builder.add_definitions(def);
+        }
+        let mut occurrences = builder.occurrences;
+
+        let is_written = |span: &Span, occ: &Occurrence| match code.get(span.0..span.1) {
+            // lifted nested classes and functions are named with their paths
+            Some(text) => occ.name == text || occ.name.ends_with(&format!(".{}", text)),
+            None => false,
+        };
+        occurrences.retain(|span, occ| is_written(span, occ));
+        // uses of the parameters added by lifting nested functions
+        let variables: HashSet<_> = occurrences
+            .iter()
+            .filter(|(_, occ)| occ.is_definition)
+            .filter_map(|(span, occ)| match occ.target {
+                SymbolTarget::Variable(_) => Some(*span),
+                _ => None,
+            })
+            .collect();
+        occurrences.retain(|_, occ| match occ.target {
+            SymbolTarget::Variable(decl_span) => variables.contains(&decl_span),
+            _ => true,
+        });
+        for (span, occ) in &mut occurrences {
+            occ.name = code[span.0..span.1].to_string();
+        }
+        SymbolIndex { occurrences }
+    }
+
+    // sorted by the spans
+    pub fn get_occurrences(&self) -> impl Iterator<Item = (Span, &Occurrence)> {
+        self.occurrences.iter().map(|(span, occ)| (*span, occ))
+    }
+
+    // the occurrence at the byte position of the code, e.g. under the cursor
+    pub fn find_at(&self, pos: usize) -> Option<(Span, &Occurrence)> {
+        self.occurrences
+            .range(..=(pos, usize::MAX))
+            .next_back()
+            .filter(|(span, _)| pos <= span.1)
+            .map(|(span, occ)| (*span, occ))
+    }
+
+    // all occurrences of the symbol, e.g. to rename it
+    pub fn find_references(&self, target: &SymbolTarget) -> Vec<Span> {
+        self.occurrences
+            .iter()
+            .filter(|(_, occ)| occ.target == *target)
+            .map(|(span, _)| *span)
+            .collect()
+    }
+}
+
+struct IndexBuilder<'a> {
+    occurrences: BTreeMap<Span, Occurrence>,
+    ctx: &'a GlobalContext,
+    code: &'a str,
+}
+
+impl<'a> IndexBuilder<'a> {
+    fn add(&mut self, id: &Ident, kind: SymbolKind, target: SymbolTarget, is_definition: bool) {
+        let occurrence = Occurrence {
+            name: id.inner.to_string(),
+            kind,
+            target,
+            is_definition,
+        };
+        self.occurrences.insert(id.span, occurrence);
+    }
+
+    fn add_definitions(&mut self, def: &mut TopDef) {
+        match def {
+            TopDef::FunDef(fun) => {
+                let target = SymbolTarget::Function(fun.name.inner.to_string());
+                self.add(&fun.name, SymbolKind::Function, target, true);
+                self.add_fun_types(fun);
+            }
+            TopDef::ClassDef(cl) => {
+                let target = SymbolTarget::Class(cl.name.inner.to_string());
+                self.add(&cl.name, SymbolKind::Class, target, true);
+                if let Some(t) = &cl.parent_type {
+                    self.add_type(t);
+                }
+                for it in &mut cl.items {
+                    match &mut it.inner {
+                        InnerClassItemDef::Field(t, id) => {
+                            self.add_type(t);
+                            self.add_member(&cl.name.inner, id, SymbolKind::Field);
+                        }
+                        InnerClassItemDef::Method(fun) => {
+                            self.add_member(&cl.name.inner, &fun.name, SymbolKind::Method);
+                            self.add_fun_types(fun);
+                        }
+                        InnerClassItemDef::Class(_) | InnerClassItemDef::Error => unreachable!(),
+                    }
+                }
+            }
+            TopDef::StructDef(st) => {
+                let target = SymbolTarget::Class(st.name.inner.to_string());
+                self.add(&st.name, SymbolKind::Class, target, true);
+                for (t, id) in &st.fields {
+                    self.add_type(t);
+                    self.add_member(&st.name.inner, id, SymbolKind::Field);
+                }
+            }
+            TopDef::TypeDef(_) | TopDef::Error => unreachable!(),
+        }
+    }
+
+    fn add_member(&mut self, cl_name: &str, id: &Ident, kind: SymbolKind) {
+        let origin = self
+            .ctx
+            .get_class_description(cl_name)
+            .and_then(|cl_desc| cl_desc.get_item_origin(self.ctx, &id.inner));
+        if let Some(origin) = origin {
+            let target = SymbolTarget::Member(origin.get_name().to_string(), id.inner.to_string());
+            self.add(id, kind, target, true);
+        }
+    }
+
+    fn add_fun_types(&mut self, fun: &mut FunDef) {
+        self.add_type(&fun.ret_type);
+        for (t, _) in &fun.args {
+            self.add_type(t);
+        }
+        walk_block(&mut fun.body, self);
+    }
+
+    // the class name of the type, without `[]` and type arguments
+    fn add_type(&mut self, t: &Type) {
+        let mut inner = &t.inner;
+        while let InnerType::Array(subtype) = inner {
+            inner = subtype;
+        }
+        let name = match inner {
+            InnerType::Class(name) | InnerType::Struct(name) | InnerType::GenericClass(name, _) => {
+                name
+            }
+            _ => return,
+        };
+        let start = t.span.0;
+        let len = match self.code.get(start..t.span.1) {
+            Some(text) => text
+                .find(|c: char| !c.is_alphanumeric() && c != '_' && c != '.')
+                .unwrap_or(text.len()),
+            None => return,
+        };
+        let id = ItemWithSpan {
+            inner: name.to_string(),
+            span: (start, start + len),
+        };
+        let target = SymbolTarget::Class(name.to_string());
+        self.add(&id, SymbolKind::Class, target, false);
+    }
+}
+
+impl<'a> TypeVisitor for IndexBuilder<'a> {
+    // added with the spans of the statements and expressions
+    fn visit_type(&mut self, _t: &mut InnerType) {}
+
+    fn visit_stmt(&mut self, stmt: &mut Stmt) {
+        match &stmt.inner {
+            InnerStmt::Decl { var_type: t, .. } | InnerStmt::ForEach { iter_type: t, .. } => {
+                self.add_type(t)
+            }
+            _ => (),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &mut Expr) {
+        match &expr.inner {
+            InnerExpr::NewArray { elem_type: t, .. } | InnerExpr::NewObject(t) => self.add_type(t),
+            InnerExpr::Lambda { ret_type, args, .. } => {
+                self.add_type(ret_type);
+                for (t, _) in args {
+                    self.add_type(t);
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+// JSON list of the occurrences, for check --dump-symbols=json; the symbol
+// is the same string for all occurrences of a symbol
+pub fn format_symbol_index(index: &SymbolIndex, codemap: &CodeMap) -> String {
+    let position = |pos| codemap.find_row_col(pos).unwrap_or((0, 0));
+    let entries: Vec<_> = index
+        .get_occurrences()
+        .map(|(span, occ)| {
+            let (beg, end) = (position(span.0), position(span.1));
+            let kind = match occ.kind {
+                SymbolKind::Local => "local",
+                SymbolKind::Param => "param",
+                SymbolKind::Field => "field",
+                SymbolKind::Method => "method",
+                SymbolKind::Function => "function",
+                SymbolKind::Class => "class",
+            };
+            let symbol = match &occ.target {
+                SymbolTarget::Variable(decl_span) => {
+                    let (row, col) = position(decl_span.0);
+                    format!("variable {}:{}", row, col)
+                }
+                SymbolTarget::Function(name) => format!("function {}", name),
+                SymbolTarget::Class(name) => format!("class {}", name),
+                SymbolTarget::Member(cl_name, name) => format!("member {}.{}", cl_name, name),
+            };
+            format!(
+                "  {{\"line\": {}, \"column\": {}, \"end_line\": {}, \"end_column\": {}, \"name\": \"{}\", \"kind\": \"{}\", \"definition\": {}, \"symbol\": \"{}\"}}",
+                beg.0, beg.1, end.0, end.1, occ.name, kind, occ.is_definition, symbol
+            )
+        })
+        .collect();
+    if entries.is_empty() {
+        return "[]\n".to_string();
+    }
+    format!("[\n{}\n]\n", entries.join(",\n"))
+}
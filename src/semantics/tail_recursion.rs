@@ -0,0 +1,362 @@
+use model::ast::*;
+use std::collections::HashSet;
+use std::mem;
+
+// Directly tail recursive global functions (returning the result of the call,
+// or calling themselves at the end of a void function) are rewritten to loops,
+// so even without the optimizer they run in constant stack space:
+//   int f(int n, int acc) { if (n == 0) return acc; return f(n - 1, acc * n); }
+// becomes
+//   int f(int n, int acc) { tail.rec: while (true) {
+//       if (n == 0) return acc;
+//       { int tail.arg.0 = n - 1; int tail.arg.1 = acc * n; n = tail.arg.0; acc = tail.arg.1; continue tail.rec; }
+//   } }
+// Calls followed only by an addition or a multiplication (of ints, they are
+// associative and commutative, also with overflow), like `return n * f(n - 1);`,
+// are rewritten the same way with an accumulator of the pending operations,
+// which is applied to every other returned value.
+// (requirement: whole analysis succeeded, lambdas are lowered to classes)
+
+const LOOP_LABEL: &str = "tail.rec";
+const ACC_VAR: &str = "tail.acc";
+
+pub fn eliminate_tail_recursion(prog: &mut Program) {
+    for def in &mut prog.defs {
+        if let TopDef::FunDef(fun) = def {
+            rewrite_function(fun);
+        }
+    }
+}
+
+enum Return<'a> {
+    TailCall,
+    // the operation and the other operand
+    Accumulated(&'a BinaryOp, &'a Expr),
+    Other,
+}
+
+fn rewrite_function(fun: &mut FunDef) {
+    let mut scan = Scan {
+        fun_name: &fun.name.inner,
+        is_int: fun.ret_type.inner == InnerType::Int,
+        is_void: fun.ret_type.inner == InnerType::Void,
+        tail_calls_cnt: 0,
+        acc_ops: vec![],
+        declared_names: HashSet::new(),
+    };
+    scan.scan_block(&fun.body, true);
+    // arguments are assigned to parameters, so they can't be shadowed
+    if fun
+        .args
+        .iter()
+        .any(|(_, id)| scan.declared_names.contains(id.inner.as_str()))
+    {
+        return;
+    }
+    let acc_op = match scan.acc_ops.split_first() {
+        Some((op, rest)) if rest.iter().all(|other| other == op) => Some((*op).clone()),
+        _ => None,
+    };
+    if scan.tail_calls_cnt == 0 && acc_op.is_none() {
+        return;
+    }
+
+    let span = fun.body.span;
+    let mut rewriter = Rewriter {
+        fun_name: fun.name.inner.to_string(),
+        is_int: scan.is_int,
+        is_void: scan.is_void,
+        params: fun.args.clone(),
+        acc_op: acc_op.clone(),
+    };
+    let mut body = mem::replace(
+        &mut fun.body,
+        Block {
+            stmts: vec![],
+            span,
+        },
+    );
+    rewriter.rewrite_block(&mut body, true);
+    if fun.ret_type.inner == InnerType::Void {
+        // the loop is left by returning, also at the end of the body
+        body.stmts.push(spanned(InnerStmt::Ret(None), span));
+    }
+
+    if let Some(op) = acc_op {
+        // identity of the operation
+        let init_value = match op {
+            BinaryOp::Add => 0,
+            _ => 1,
+        };
+        fun.body.stmts.push(spanned(
+            InnerStmt::Decl {
+                var_type: ItemWithSpan {
+                    inner: InnerType::Int,
+                    span,
+                },
+                var_items: vec![(
+                    ItemWithSpan {
+                        inner: ACC_VAR.to_string(),
+                        span,
+                    },
+                    Some(spanned(InnerExpr::LitInt(init_value), span)),
+                )],
+            },
+            span,
+        ));
+    }
+    fun.body.stmts.push(spanned(
+        InnerStmt::While {
+            label: Some(ItemWithSpan {
+                inner: LOOP_LABEL.to_string(),
+                span,
+            }),
+            cond: spanned(InnerExpr::LitBool(true), span),
+            body,
+        },
+        span,
+    ));
+}
+
+fn spanned<T>(inner: T, span: Span) -> Box<ItemWithSpan<T>> {
+    Box::new(ItemWithSpan { inner, span })
+}
+
+fn classify_return<'a>(expr: &'a Expr, fun_name: &str, is_int: bool) -> Return<'a> {
+    use self::BinaryOp::*;
+    if is_self_call(expr, fun_name) {
+        return Return::TailCall;
+    }
+    match &expr.inner {
+        InnerExpr::BinaryOp(lhs, op @ Add, rhs) | InnerExpr::BinaryOp(lhs, op @ Mul, rhs)
+            if is_int =>
+        {
+            if is_self_call(rhs, fun_name) {
+                // lhs is evaluated before the call anyway
+                Return::Accumulated(op, lhs)
+            } else if is_self_call(lhs, fun_name) && is_simple(&rhs.inner) {
+                // rhs would be evaluated before the rest of the recursion
+                Return::Accumulated(op, rhs)
+            } else {
+                Return::Other
+            }
+        }
+        _ => Return::Other,
+    }
+}
+
+// the function returns right after the statement (in a void function
+// also after the last statement of the body)
+fn is_tail_stmt(stmts: &[Box<Stmt>], idx: usize, is_block_tail: bool) -> bool {
+    match stmts.get(idx + 1) {
+        Some(next) => matches!(next.inner, InnerStmt::Ret(None)),
+        None => is_block_tail,
+    }
+}
+
+// `f(...);` in a void function f
+fn is_self_call_stmt(stmt: &Stmt, fun_name: &str) -> bool {
+    match &stmt.inner {
+        InnerStmt::Expr(e) => is_self_call(e, fun_name),
+        _ => false,
+    }
+}
+
+fn is_self_call(expr: &Expr, fun_name: &str) -> bool {
+    match &expr.inner {
+        InnerExpr::FunCall { function_name, .. } => function_name.inner == fun_name,
+        _ => false,
+    }
+}
+
+// the value doesn't depend on the moment of evaluation: only local variables
+// (the recursion can't change them) and operations which can't fail
+fn is_simple(expr: &InnerExpr) -> bool {
+    use model::ast::{BinaryOp::*, InnerExpr::*};
+    match expr {
+        LitVar(_) | LitInt(_) => true,
+        UnaryOp(_, e) => is_simple(&e.inner),
+        BinaryOp(_, Div, _) | BinaryOp(_, Mod, _) => false,
+        BinaryOp(lhs, _, rhs) => is_simple(&lhs.inner) && is_simple(&rhs.inner),
+        _ => false,
+    }
+}
+
+struct Scan<'a> {
+    fun_name: &'a str,
+    is_int: bool,
+    is_void: bool,
+    tail_calls_cnt: usize,
+    acc_ops: Vec<&'a BinaryOp>,
+    declared_names: HashSet<&'a str>,
+}
+
+impl<'a> Scan<'a> {
+    // is_tail: the function returns right after the block
+    fn scan_block(&mut self, block: &'a Block, is_tail: bool) {
+        for (i, st) in block.stmts.iter().enumerate() {
+            let is_st_tail = is_tail_stmt(&block.stmts, i, is_tail);
+            if self.is_void && is_st_tail && is_self_call_stmt(st, self.fun_name) {
+                self.tail_calls_cnt += 1;
+            }
+            self.scan_stmt(st, is_st_tail);
+        }
+    }
+
+    fn scan_stmt(&mut self, stmt: &'a Stmt, is_tail: bool) {
+        use self::InnerStmt::*;
+        match &stmt.inner {
+            Block(bl) => self.scan_block(bl, is_tail),
+            Decl { var_items, .. } => {
+                for (id, _) in var_items {
+                    self.declared_names.insert(&id.inner);
+                }
+            }
+            Ret(Some(e)) => match classify_return(e, self.fun_name, self.is_int) {
+                Return::TailCall => self.tail_calls_cnt += 1,
+                Return::Accumulated(op, _) => self.acc_ops.push(op),
+                Return::Other => (),
+            },
+            Cond {
+                true_branch,
+                false_branch,
+                ..
+            } => {
+                self.scan_block(true_branch, is_tail);
+                if let Some(bl) = false_branch {
+                    self.scan_block(bl, is_tail);
+                }
+            }
+            While { body, .. } => self.scan_block(body, false),
+            ForEach {
+                iter_name, body, ..
+            } => {
+                self.declared_names.insert(&iter_name.inner);
+                self.scan_block(body, false);
+            }
+            Empty | Assign(..) | Incr(_) | Decr(_) | Expr(_) | Ret(None) | Break(_)
+            | Continue(_) => (),
+            NestedFun(_) | Error => unreachable!(),
+        }
+    }
+}
+
+struct Rewriter {
+    fun_name: String,
+    is_int: bool,
+    is_void: bool,
+    params: Vec<(Type, Ident)>,
+    // set if all accumulated returns have the same operation, otherwise
+    // they are left as they are (like other returns)
+    acc_op: Option<BinaryOp>,
+}
+
+impl Rewriter {
+    fn rewrite_block(&mut self, block: &mut Block, is_tail: bool) {
+        for i in 0..block.stmts.len() {
+            let is_st_tail = is_tail_stmt(&block.stmts, i, is_tail);
+            let st = &mut block.stmts[i];
+            if self.is_void && is_st_tail && is_self_call_stmt(st, &self.fun_name) {
+                if let InnerStmt::Expr(call) = &st.inner {
+                    st.inner = self.loop_again(None, call, st.span);
+                }
+            } else {
+                self.rewrite_stmt(st, is_st_tail);
+            }
+        }
+    }
+
+    fn rewrite_stmt(&mut self, stmt: &mut Stmt, is_tail: bool) {
+        use self::InnerStmt::*;
+        let span = stmt.span;
+        let new_stmt = match &mut stmt.inner {
+            Block(bl) => {
+                self.rewrite_block(bl, is_tail);
+                None
+            }
+            Cond {
+                true_branch,
+                false_branch,
+                ..
+            } => {
+                self.rewrite_block(true_branch, is_tail);
+                if let Some(bl) = false_branch {
+                    self.rewrite_block(bl, is_tail);
+                }
+                None
+            }
+            While { body, .. } | ForEach { body, .. } => {
+                self.rewrite_block(body, false);
+                None
+            }
+            Ret(Some(e)) => match classify_return(e, &self.fun_name, self.is_int) {
+                Return::TailCall => Some(self.loop_again(None, e, span)),
+                Return::Accumulated(op, operand) if Some(op) == self.acc_op.as_ref() => {
+                    let call = match &e.inner {
+                        InnerExpr::BinaryOp(_, _, rhs) if is_self_call(rhs, &self.fun_name) => rhs,
+                        InnerExpr::BinaryOp(lhs, _, _) => lhs,
+                        _ => unreachable!(),
+                    };
+                    let acc_update = self.apply_acc(Box::new(operand.clone()), span);
+                    let acc_update = InnerStmt::Assign(self.acc_var(span), acc_update);
+                    Some(self.loop_again(Some(spanned(acc_update, span)), call, span))
+                }
+                _ if self.acc_op.is_some() => {
+                    let value = mem::replace(e, spanned(InnerExpr::LitNull, span));
+                    Some(Ret(Some(self.apply_acc(value, span))))
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(st) = new_stmt {
+            stmt.inner = st;
+        }
+    }
+
+    fn acc_var(&self, span: Span) -> Box<Expr> {
+        spanned(InnerExpr::LitVar(ACC_VAR.to_string()), span)
+    }
+
+    // `tail.acc op value`
+    fn apply_acc(&self, value: Box<Expr>, span: Span) -> Box<Expr> {
+        let op = self.acc_op.clone().unwrap();
+        spanned(InnerExpr::BinaryOp(self.acc_var(span), op, value), span)
+    }
+
+    // arguments of the call are evaluated (after updating the accumulator),
+    // then assigned to the parameters, and the loop starts again
+    fn loop_again(&self, acc_update: Option<Box<Stmt>>, call: &Expr, span: Span) -> InnerStmt {
+        let mut stmts: Vec<_> = acc_update.into_iter().collect();
+        let args = match &call.inner {
+            InnerExpr::FunCall { args, .. } => args,
+            _ => unreachable!(),
+        };
+        let tmp_name = |i| ItemWithSpan {
+            inner: format!("tail.arg.{}", i),
+            span,
+        };
+        for (i, ((t, _), a)) in self.params.iter().zip(args).enumerate() {
+            stmts.push(spanned(
+                InnerStmt::Decl {
+                    var_type: t.clone(),
+                    var_items: vec![(tmp_name(i), Some(a.clone()))],
+                },
+                span,
+            ));
+        }
+        for (i, (_, id)) in self.params.iter().enumerate() {
+            let param = spanned(InnerExpr::LitVar(id.inner.to_string()), span);
+            let tmp = spanned(InnerExpr::LitVar(tmp_name(i).inner), span);
+            stmts.push(spanned(InnerStmt::Assign(param, tmp), span));
+        }
+        stmts.push(spanned(
+            InnerStmt::Continue(Some(ItemWithSpan {
+                inner: LOOP_LABEL.to_string(),
+                span,
+            })),
+            span,
+        ));
+        InnerStmt::Block(Block { stmts, span })
+    }
+}
@@ -0,0 +1,147 @@
+use super::visitor::*;
+use frontend_error::{ErrorKind, FrontendError, FrontendResult};
+use model::ast::*;
+use std::collections::{HashMap, HashSet};
+
+// Type aliases (`typedef int[] Row;`) are replaced by the aliased types
+// in the whole program before any other analysis, so the global context,
+// type checking and codegen see only the resolved types.
+
+pub fn resolve_type_aliases(prog: &mut Program) -> FrontendResult<()> {
+    let mut errors = vec![];
+    let mut type_defs = vec![];
+    let mut other_names = HashSet::new();
+    other_names.insert(STRING_BUILDER_CLASS.to_string());
+    other_names.insert(INT_LIST_CLASS.to_string());
+    other_names.insert(STRING_LIST_CLASS.to_string());
+    other_names.insert(STRING_INT_MAP_CLASS.to_string());
+    let mut defs = vec![];
+    for def in prog.defs.drain(..) {
+        match def {
+            TopDef::TypeDef(td) => type_defs.push(td),
+            TopDef::ClassDef(ref cl) => {
+                other_names.insert(cl.name.inner.to_string());
+                defs.push(def);
+            }
+            TopDef::FunDef(ref fun) => {
+                other_names.insert(fun.name.inner.to_string());
+                defs.push(def);
+            }
+            TopDef::StructDef(ref st) => {
+                other_names.insert(st.name.inner.to_string());
+                defs.push(def);
+            }
+            TopDef::Error => unreachable!(),
+        }
+    }
+    if type_defs.is_empty() {
+        prog.defs = defs;
+        return Ok(());
+    }
+
+    let mut aliases = HashMap::new();
+    for td in &type_defs {
+        if other_names.contains(&td.name.inner) {
+            errors.push(FrontendError {
+                err: "Error: class, struct or function with same name already defined".to_string(),
+                span: td.name.span,
+                kind: ErrorKind::Error,
+            });
+        } else if aliases
+            .insert(td.name.inner.to_string(), td.aliased_type.inner.clone())
+            .is_some()
+        {
+            errors.push(FrontendError {
+                err: "Error: type alias redefinition".to_string(),
+                span: td.name.span,
+                kind: ErrorKind::Error,
+            });
+        }
+    }
+
+    // aliases can refer to other aliases
+    let mut resolved = HashMap::new();
+    for td in &type_defs {
+        if resolved.contains_key(&td.name.inner) || !aliases.contains_key(&td.name.inner) {
+            continue;
+        }
+        let mut resolver = AliasResolver {
+            aliases: &aliases,
+            type_params: vec![],
+            expanding: vec![td.name.inner.to_string()],
+            is_cyclic: false,
+        };
+        let mut t = aliases[&td.name.inner].clone();
+        resolver.visit_type(&mut t);
+        if resolver.is_cyclic {
+            errors.push(FrontendError {
+                err: format!("Error: type alias {} refers to itself", td.name.inner),
+                span: td.span,
+                kind: ErrorKind::Error,
+            });
+        }
+        resolved.insert(td.name.inner.to_string(), t);
+    }
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    for def in &mut defs {
+        // type parameters shadow aliases
+        let type_params = match def {
+            TopDef::FunDef(fun) => &fun.type_params,
+            TopDef::ClassDef(cl) => &cl.type_params,
+            TopDef::StructDef(_) => &[][..],
+            _ => unreachable!(),
+        };
+        let mut resolver = AliasResolver {
+            aliases: &resolved,
+            type_params: type_params.iter().map(|p| p.inner.to_string()).collect(),
+            expanding: vec![],
+            is_cyclic: false,
+        };
+        walk_top_def(def, &mut resolver);
+    }
+    prog.defs = defs;
+    Ok(())
+}
+
+struct AliasResolver<'a> {
+    aliases: &'a HashMap<String, InnerType>,
+    type_params: Vec<String>,
+    // aliases being expanded, to detect cycles
+    expanding: Vec<String>,
+    is_cyclic: bool,
+}
+
+impl<'a> TypeVisitor for AliasResolver<'a> {
+    fn visit_type(&mut self, t: &mut InnerType) {
+        match t {
+            InnerType::Class(name) if self.type_params.contains(name) => (),
+            InnerType::Class(name) if self.aliases.contains_key(name) => {
+                if self.expanding.contains(name) {
+                    self.is_cyclic = true;
+                    return;
+                }
+                self.expanding.push(name.to_string());
+                let mut aliased_type = self.aliases[name].clone();
+                self.visit_type(&mut aliased_type);
+                self.expanding.pop();
+                *t = aliased_type;
+            }
+            InnerType::Array(subtype) => self.visit_type(subtype),
+            InnerType::GenericClass(_, type_args) => {
+                for a in type_args {
+                    self.visit_type(a);
+                }
+            }
+            InnerType::Function(ret_type, args_types) => {
+                self.visit_type(ret_type);
+                for a in args_types {
+                    self.visit_type(a);
+                }
+            }
+            _ => (),
+        }
+    }
+}
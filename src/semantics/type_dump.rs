@@ -0,0 +1,131 @@
+use super::global_context::{ClassDesc, FunDesc, GlobalContext, TypeWrapper};
+use codemap::CodeMap;
+use model::ast::*;
+use std::collections::BTreeMap;
+
+// JSON with classes (and structs), functions and types of expressions of
+// the type checked program, for --typecheck-only --dump-types=json;
+// the lists are sorted, so the output is deterministic
+pub fn format_type_dump(
+    ctx: &GlobalContext,
+    expr_types: &BTreeMap<Span, InnerType>,
+    codemap: &CodeMap,
+) -> String {
+    let mut classes: Vec<_> = ctx.get_classes().collect();
+    classes.sort_by(|a, b| a.get_name().cmp(b.get_name()));
+    let mut functions: Vec<_> = ctx.get_functions().collect();
+    functions.sort_by(|a, b| a.name.cmp(&b.name));
+
+    let mut res = "{\n  \"classes\": [".to_string();
+    for (i, cl) in classes.iter().enumerate() {
+        res += if i == 0 { "\n" } else { ",\n" };
+        res += &format_class(cl);
+    }
+    res += "\n  ],\n  \"functions\": [";
+    for (i, fun) in functions.iter().enumerate() {
+        res += if i == 0 { "\n    " } else { ",\n    " };
+        res += &format_function(fun, Some(ctx.is_builtin_function(&fun.name)));
+    }
+    res += "\n  ],\n  \"expressions\": [";
+    let mut first = true;
+    for (span, t) in expr_types {
+        // expressions made by the analysis (e.g. of default values) have no position
+        let (beg, end) = match (codemap.find_row_col(span.0), codemap.find_row_col(span.1)) {
+            (Some(beg), Some(end)) if span.0 != span.1 => (beg, end),
+            _ => continue,
+        };
+        res += if first { "\n    " } else { ",\n    " };
+        first = false;
+        res += &format!(
+            "{{\"line\": {}, \"column\": {}, \"end_line\": {}, \"end_column\": {}, \"type\": {}}}",
+            beg.0,
+            beg.1,
+            end.0,
+            end.1,
+            quote(&t.to_string())
+        );
+    }
+    res + "\n  ]\n}\n"
+}
+
+fn format_class(cl: &ClassDesc) -> String {
+    let mut items: Vec<_> = cl.get_items().iter().collect();
+    items.sort_by(|a, b| a.0.cmp(b.0));
+    let fields: Vec<_> = items
+        .iter()
+        .filter_map(|(name, it)| match it {
+            TypeWrapper::Var(t) => Some(format!(
+                "{{\"name\": {}, \"type\": {}}}",
+                quote(name),
+                quote(&t.inner.to_string())
+            )),
+            TypeWrapper::Fun(_) => None,
+        })
+        .collect();
+    let methods: Vec<_> = items
+        .iter()
+        .filter_map(|(_, it)| match it {
+            TypeWrapper::Fun(fun) => Some(format_function(fun, None)),
+            TypeWrapper::Var(_) => None,
+        })
+        .collect();
+    let parent = match cl.get_parent_type() {
+        Some(t) => quote(&t.inner.to_string()),
+        None => "null".to_string(),
+    };
+    let kind = if cl.is_struct() { "struct" } else { "class" };
+    format!(
+        "    {{\"name\": {}, \"kind\": \"{}\", \"builtin\": {}, \"type_params\": {}, \"parent\": {},\n      \"fields\": [{}],\n      \"methods\": [{}]}}",
+        quote(cl.get_name()),
+        kind,
+        cl.is_builtin(),
+        format_list(cl.get_type_params()),
+        parent,
+        join_items(&fields),
+        join_items(&methods)
+    )
+}
+
+fn format_function(fun: &FunDesc, builtin: Option<bool>) -> String {
+    let args: Vec<_> = fun
+        .args_names
+        .iter()
+        .zip(&fun.args_types)
+        .map(|(name, t)| {
+            format!(
+                "{{\"name\": {}, \"type\": {}}}",
+                quote(name),
+                quote(&t.inner.to_string())
+            )
+        })
+        .collect();
+    let builtin = match builtin {
+        Some(b) => format!(", \"builtin\": {}", b),
+        None => String::new(),
+    };
+    format!(
+        "{{\"name\": {}{}, \"type_params\": {}, \"args\": [{}], \"ret_type\": {}}}",
+        quote(&fun.name),
+        builtin,
+        format_list(&fun.type_params),
+        args.join(", "),
+        quote(&fun.ret_type.inner.to_string())
+    )
+}
+
+fn join_items(items: &[String]) -> String {
+    if items.is_empty() {
+        String::new()
+    } else {
+        format!("\n        {}\n      ", items.join(",\n        "))
+    }
+}
+
+fn format_list(names: &[String]) -> String {
+    let names: Vec<_> = names.iter().map(|n| quote(n)).collect();
+    format!("[{}]", names.join(", "))
+}
+
+fn quote(s: &str) -> String {
+    format!("\"{}\"", s.replace("\\", "\\\\").replace("\"", "\\\""))
+}
@@ -0,0 +1,194 @@
+use model::ast::*;
+
+// Traversal of the whole AST, used by the passes transforming the analyzed
+// program. Types are visited pre-order, expressions post-order.
+
+pub trait TypeVisitor {
+    fn visit_type(&mut self, t: &mut InnerType);
+
+    fn visit_fun_call(&mut self, _name: &mut Ident, _type_args: &mut Vec<InnerType>) {}
+
+    // called before visiting substatements
+    fn visit_stmt(&mut self, _stmt: &mut Stmt) {}
+
+    // called after visiting subexpressions, can replace the expression
+    fn visit_expr(&mut self, _expr: &mut Expr) {}
+}
+
+pub fn walk_top_def(def: &mut TopDef, v: &mut impl TypeVisitor) {
+    match def {
+        TopDef::FunDef(fun) => walk_fun(fun, v),
+        TopDef::ClassDef(cl) => walk_class(cl, v),
+        TopDef::StructDef(st) => {
+            for (t, _) in &mut st.fields {
+                v.visit_type(&mut t.inner);
+            }
+        }
+        TopDef::TypeDef(_) | TopDef::Error => unreachable!(),
+    }
+}
+
+pub fn walk_class(cl: &mut ClassDef, v: &mut impl TypeVisitor) {
+    if let Some(t) = &mut cl.parent_type {
+        v.visit_type(&mut t.inner);
+    }
+    for it in &mut cl.items {
+        match &mut it.inner {
+            InnerClassItemDef::Field(t, _) => v.visit_type(&mut t.inner),
+            InnerClassItemDef::Method(fun) => walk_fun(fun, v),
+            InnerClassItemDef::Class(_) | InnerClassItemDef::Error => unreachable!(),
+        }
+    }
+}
+
+pub fn walk_fun(fun: &mut FunDef, v: &mut impl TypeVisitor) {
+    v.visit_type(&mut fun.ret_type.inner);
+    for (t, _) in &mut fun.args {
+        v.visit_type(&mut t.inner);
+    }
+    walk_block(&mut fun.body, v);
+}
+
+pub fn walk_block(block: &mut Block, v: &mut impl TypeVisitor) {
+    for st in &mut block.stmts {
+        walk_stmt(st, v);
+    }
+}
+
+pub fn walk_stmt(stmt: &mut Stmt, v: &mut impl TypeVisitor) {
+    use self::InnerStmt::*;
+    v.visit_stmt(stmt);
+    match &mut stmt.inner {
+        Empty => (),
+        Block(bl) => walk_block(bl, v),
+        Decl {
+            var_type,
+            var_items,
+        } => {
+            v.visit_type(&mut var_type.inner);
+            for (_, init_expr) in var_items {
+                if let Some(e) = init_expr {
+                    walk_expr(e, v);
+                }
+            }
+        }
+        Assign(lhs, rhs) => {
+            walk_expr(lhs, v);
+            walk_expr(rhs, v);
+        }
+        Incr(e) | Decr(e) | Expr(e) => walk_expr(e, v),
+        Ret(opt_expr) => {
+            if let Some(e) = opt_expr {
+                walk_expr(e, v);
+            }
+        }
+        Cond {
+            cond,
+            true_branch,
+            false_branch,
+        } => {
+            walk_expr(cond, v);
+            walk_block(true_branch, v);
+            if let Some(bl) = false_branch {
+                walk_block(bl, v);
+            }
+        }
+        While { cond, body, .. } => {
+            walk_expr(cond, v);
+            walk_block(body, v);
+        }
+        ForEach {
+            iter_type,
+            array,
+            body,
+            ..
+        } => {
+            v.visit_type(&mut iter_type.inner);
+            walk_expr(array, v);
+            walk_block(body, v);
+        }
+        Break(_) | Continue(_) => (),
+        NestedFun(_) | Error => unreachable!(),
+    }
+}
+
+pub fn walk_expr(expr: &mut Expr, v: &mut impl TypeVisitor) {
+    use self::InnerExpr::*;
+    match &mut expr.inner {
+        LitVar(_) | LitInt(_) | LitBool(_) | LitStr(_) | LitNull => (),
+        CastType(e, t) => {
+            walk_expr(e, v);
+            v.visit_type(t);
+        }
+        FunCall {
+            function_name,
+            args,
+            type_args,
+        } => {
+            for t in type_args.iter_mut() {
+                v.visit_type(t);
+            }
+            v.visit_fun_call(function_name, type_args);
+            for a in args {
+                walk_expr(a, v);
+            }
+        }
+        BinaryOp(lhs, _, rhs) => {
+            walk_expr(lhs, v);
+            walk_expr(rhs, v);
+        }
+        UnaryOp(_, e) => walk_expr(e, v),
+        NewArray {
+            elem_type,
+            elem_cnt,
+        } => {
+            v.visit_type(&mut elem_type.inner);
+            walk_expr(elem_cnt, v);
+        }
+        ArrayElem { array, index, .. } => {
+            walk_expr(array, v);
+            walk_expr(index, v);
+        }
+        NewObject(t) => v.visit_type(&mut t.inner),
+        ObjField { obj, .. } => walk_expr(obj, v),
+        ObjMethodCall { obj, args, .. } => {
+            walk_expr(obj, v);
+            for a in args {
+                walk_expr(a, v);
+            }
+        }
+        Lambda {
+            ret_type,
+            args,
+            body,
+            captures,
+        } => {
+            v.visit_type(&mut ret_type.inner);
+            for (t, _) in args {
+                v.visit_type(&mut t.inner);
+            }
+            walk_block(body, v);
+            for (t, _, value) in captures {
+                v.visit_type(&mut t.inner);
+                walk_expr(value, v);
+            }
+        }
+        ClosureCall { closure, args } => {
+            walk_expr(closure, v);
+            for a in args {
+                walk_expr(a, v);
+            }
+        }
+        NamedArg { value, .. } => walk_expr(value, v),
+        Range(start, end) => {
+            walk_expr(start, v);
+            walk_expr(end, v);
+        }
+        Assign(lhs, rhs) => {
+            walk_expr(lhs, v);
+            walk_expr(rhs, v);
+        }
+        IncrDecr { lvalue, .. } => walk_expr(lvalue, v),
+    }
+    v.visit_expr(expr);
+}